@@ -0,0 +1,95 @@
+// animation.rs
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::temporal::TimeStep;
+
+// More frames than this are refused rather than rendered for minutes
+pub const MAX_FRAMES: usize = 1000;
+
+// Palette quantization effort from 1 (best, slowest) to 30
+const QUANTIZE_SPEED: i32 = 10;
+
+/// What changes from one frame of an animation to the next.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Animation {
+    Layers,         // The layers are added one at a time
+    Time(TimeStep), // One year, month or day of features per frame
+}
+
+impl Animation {
+    pub fn parse(text: &str) -> Option<Animation> {
+        match text.trim().to_lowercase().as_str() {
+            "layers" => Some(Animation::Layers),
+            step => TimeStep::parse(step).map(Animation::Time),
+        }
+    }
+}
+
+/// Whether frames of `output` go into one animated GIF rather than numbered PNGs.
+pub fn is_gif(output: &Path) -> bool {
+    output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"))
+}
+
+/// Path of the numbered PNG of frame `index` (from 1): `<name>_001.png` next to `output`.
+pub fn frame_path(output: &Path, index: usize) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("frame");
+    output.with_file_name(format!("{}_{:03}.png", stem, index))
+}
+
+/// Animated GIF written frame by frame, looping forever. The first frame sets
+/// the size of the animation.
+pub struct GifWriter {
+    path: PathBuf,
+    delay: u16, // Hundredths of a second each frame is shown
+    encoder: Option<gif::Encoder<BufWriter<File>>>,
+}
+
+impl GifWriter {
+    pub fn new(path: &Path, delay_ms: u32) -> GifWriter {
+        GifWriter {
+            path: path.to_path_buf(),
+            delay: (delay_ms / 10).clamp(1, u16::MAX as u32) as u16,
+            encoder: None,
+        }
+    }
+
+    /// Appends a frame given as the bytes of a PNG image.
+    pub fn add_png(&mut self, png: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut image = image::load_from_memory(png)?.to_rgba8();
+        let (width, height) = (
+            u16::try_from(image.width()).map_err(|_| "Image too wide for a GIF")?,
+            u16::try_from(image.height()).map_err(|_| "Image too high for a GIF")?,
+        );
+        let encoder = match &mut self.encoder {
+            Some(encoder) => encoder,
+            None => {
+                let file = BufWriter::new(File::create(&self.path)?);
+                let mut encoder = gif::Encoder::new(file, width, height, &[])?;
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+                self.encoder.insert(encoder)
+            }
+        };
+        let mut frame = gif::Frame::from_rgba_speed(width, height, &mut image, QUANTIZE_SPEED);
+        frame.delay = self.delay;
+        encoder.write_frame(&frame)?;
+        Ok(())
+    }
+
+    /// Ends the animation; an error if no frame was added.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        let encoder = self.encoder.ok_or("No frames to animate")?;
+        encoder.into_inner()?.flush()?;
+        Ok(())
+    }
+}
@@ -1,20 +1,372 @@
 // app.rs
 
+use geojson::Feature;
 use plotters::prelude::RGBColor;
-use std::collections::HashMap; // For plot colors
+use ratatui::layout::Rect;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet}; // For plot colors
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::basemap::Basemap;
+use crate::duplicates::DuplicateReport;
+use crate::filter::FeatureFilter;
+use crate::geocode::{PlaceSearch, ReverseGeocoder};
+use crate::gps::{GpsFix, GpsReader, GpsSource};
+use crate::history::History;
+use crate::info_cache::InfoCache;
+use crate::inset::Inset;
+use crate::json_preview::JsonPreview;
+use crate::keymap::KeyMap;
+use crate::loader::FollowState;
+use crate::lod::LevelsOfDetail;
+use crate::measure;
+use crate::plot::{
+    self, FigureBackground, GeometryTypes, LineStyle, MapMarker, PLOT_COLORS, PlotOptions,
+    PointShape, RenderEstimate, RenderProgress,
+};
+use crate::projection::Projection;
+use crate::raster::Raster;
+use crate::regions::Regions;
+use crate::remote::Download;
+use crate::search::{PropertySearch, SearchMode};
+use crate::serve::{self, PreviewServer};
+use crate::spatial::FeatureIndex;
+use crate::style::StyleRule;
+use crate::table::AttributeTable;
+use crate::temporal::{self, TimeRange};
+use crate::text_input::TextInput;
+use crate::topology::TopologyReport;
+use crate::validate::ValidationReport;
+use crate::watch::DirectoryWatcher;
+use crate::wfs::{Capabilities, CapabilitiesRequest};
+
+// Range the divider between the file list and the preview can be moved in,
+// as a percentage of the terminal width
+const LEFT_PANE_MIN_PERCENT: u16 = 10;
+const LEFT_PANE_MAX_PERCENT: u16 = 90;
+
+// Notifications kept for the Log tab, the oldest are dropped first
+const LOG_CAPACITY: usize = 500;
 
 #[derive(PartialEq)]
 pub enum CurrentScreen {
     Main,
     Help,
     GeoJsonMapper,
+    Styling,
+    Validation,
+    Duplicates,
+    Topology,
+    PlotSummary,
+    Logs,
+    AttributeTable,
+}
+
+/// Severity of a log entry.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum LogLevel {
+    Info, // Notifications
+    Warning,
+    Error,
+}
+
+impl LogLevel {
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Info => "info",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+        }
+    }
+
+    /// Lowest level shown after this one on the Logs screen: all, then warnings
+    /// and errors, then errors only.
+    pub fn next(self) -> LogLevel {
+        match self {
+            LogLevel::Info => LogLevel::Warning,
+            LogLevel::Warning => LogLevel::Error,
+            LogLevel::Error => LogLevel::Info,
+        }
+    }
+}
+
+/// A notification, warning or error logged during the session.
+pub struct LogEntry {
+    pub time: String, // Local time, HH:MM:SS
+    pub level: LogLevel,
+    pub text: String,
+}
+
+/// Tab shown in the right panel above the preview.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PanelTab {
+    Info,       // Information about the highlighted file
+    Properties, // Color, sizes, CRS and filter of the highlighted layer
+    Style,      // Options applying to the whole plot
+    Json,       // Start of the highlighted file as indented JSON
+    Log,        // Earlier notifications, newest first
+}
+
+impl PanelTab {
+    pub const ALL: [PanelTab; 5] = [
+        PanelTab::Info,
+        PanelTab::Properties,
+        PanelTab::Style,
+        PanelTab::Json,
+        PanelTab::Log,
+    ];
+
+    pub fn next(self) -> PanelTab {
+        match self {
+            PanelTab::Info => PanelTab::Properties,
+            PanelTab::Properties => PanelTab::Style,
+            PanelTab::Style => PanelTab::Json,
+            PanelTab::Json => PanelTab::Log,
+            PanelTab::Log => PanelTab::Info,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PanelTab::Info => "Info",
+            PanelTab::Properties => "Properties",
+            PanelTab::Style => "Style",
+            PanelTab::Json => "JSON",
+            PanelTab::Log => "Log",
+        }
+    }
+}
+
+/// Tab shown in the Info tab of the right panel.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InfoTab {
+    Details,    // Size, features, extent and CRS of the file
+    Attributes, // Statistics of every property
+}
+
+impl InfoTab {
+    pub fn next(self) -> InfoTab {
+        match self {
+            InfoTab::Details => InfoTab::Attributes,
+            InfoTab::Attributes => InfoTab::Details,
+        }
+    }
+}
+
+/// Text of the output image edited in `AppMode::EditingTitle`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TitleField {
+    Title,
+    Subtitle,
+    Attribution,
+}
+
+impl TitleField {
+    pub fn label(self) -> &'static str {
+        match self {
+            TitleField::Title => "Title:",
+            TitleField::Subtitle => "Subtitle:",
+            TitleField::Attribution => "Attribution:",
+        }
+    }
+
+    /// Field edited after pressing Tab.
+    pub fn next(self) -> TitleField {
+        match self {
+            TitleField::Title => TitleField::Subtitle,
+            TitleField::Subtitle => TitleField::Attribution,
+            TitleField::Attribution => TitleField::Title,
+        }
+    }
+}
+
+/// Layer setting adjusted in the layer properties popup.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayerProperty {
+    LineWidth,
+    LineStyle, // Index into `LineStyle::ALL`
+    PointSize,
+    PointShape, // Index into `PointShape::ALL`
+    Opacity,
+    Points, // Whether the layer draws its points, and the two below likewise
+    Lines,
+    Polygons,
+}
+
+impl LayerProperty {
+    pub const ALL: [LayerProperty; 8] = [
+        LayerProperty::LineWidth,
+        LayerProperty::LineStyle,
+        LayerProperty::PointSize,
+        LayerProperty::PointShape,
+        LayerProperty::Opacity,
+        LayerProperty::Points,
+        LayerProperty::Lines,
+        LayerProperty::Polygons,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LayerProperty::LineWidth => "Line width",
+            LayerProperty::LineStyle => "Line style",
+            LayerProperty::PointSize => "Point size",
+            LayerProperty::PointShape => "Point shape",
+            LayerProperty::Opacity => "Opacity",
+            LayerProperty::Points => "Points",
+            LayerProperty::Lines => "Lines",
+            LayerProperty::Polygons => "Polygons",
+        }
+    }
+
+    /// Whether the property is shown or hidden rather than a number.
+    pub fn is_toggle(self) -> bool {
+        matches!(
+            self,
+            LayerProperty::Points | LayerProperty::Lines | LayerProperty::Polygons
+        )
+    }
+
+    /// Whether the property picks one of a few named values.
+    pub fn is_choice(self) -> bool {
+        matches!(self, LayerProperty::LineStyle | LayerProperty::PointShape)
+    }
+
+    /// `value` with its unit, the name of a choice, or "shown"/"hidden" for
+    /// toggles.
+    pub fn format(self, value: u32) -> String {
+        match self {
+            LayerProperty::LineWidth | LayerProperty::PointSize => format!("{}px", value),
+            LayerProperty::LineStyle => LineStyle::ALL[value as usize].name().to_string(),
+            LayerProperty::PointShape => PointShape::ALL[value as usize].name().to_string(),
+            LayerProperty::Opacity => format!("{}%", value),
+            _ if value == 0 => String::from("hidden"),
+            _ => String::from("shown"),
+        }
+    }
+
+    /// Value of a layer that does not set the property; 1 for shown toggles.
+    pub fn default_value(self) -> u32 {
+        match self {
+            LayerProperty::LineWidth => plot::DEFAULT_LINE_WIDTH,
+            LayerProperty::LineStyle => 0, // Solid
+            LayerProperty::PointSize => plot::DEFAULT_POINT_SIZE,
+            LayerProperty::PointShape => 0, // Circle
+            LayerProperty::Opacity => 100,
+            LayerProperty::Points | LayerProperty::Lines | LayerProperty::Polygons => 1,
+        }
+    }
+
+    // Change per key press and the allowed values
+    fn step(self) -> u32 {
+        match self {
+            LayerProperty::Opacity => 10,
+            _ => 1,
+        }
+    }
+
+    fn range(self) -> (u32, u32) {
+        match self {
+            LayerProperty::LineWidth | LayerProperty::PointSize => (1, plot::MAX_SYMBOL_SIZE),
+            LayerProperty::LineStyle => (0, LineStyle::ALL.len() as u32 - 1),
+            LayerProperty::PointShape => (0, PointShape::ALL.len() as u32 - 1),
+            LayerProperty::Opacity => (10, 100),
+            LayerProperty::Points | LayerProperty::Lines | LayerProperty::Polygons => (0, 1),
+        }
+    }
+
+    /// Property selected after pressing Down.
+    pub fn next(self) -> LayerProperty {
+        let index = LayerProperty::ALL
+            .iter()
+            .position(|&p| p == self)
+            .unwrap_or(0);
+        LayerProperty::ALL[(index + 1) % LayerProperty::ALL.len()]
+    }
+
+    /// Property selected after pressing Up.
+    pub fn previous(self) -> LayerProperty {
+        let index = LayerProperty::ALL
+            .iter()
+            .position(|&p| p == self)
+            .unwrap_or(0);
+        LayerProperty::ALL[(index + LayerProperty::ALL.len() - 1) % LayerProperty::ALL.len()]
+    }
 }
 
 #[derive(PartialEq)]
 pub enum AppMode {
     Navigation,
     EditingFilename,
+    EditingTitle, // Typing the title, subtitle or attribution of the output image
     Searching,
+    EditingSelection,   // Typing an expression selecting files by their metadata
+    EditingOpenPath,    // Typing the path of a file outside the data directory
+    EditingExport,      // Typing the file to save the selected layers to
+    Sessions,           // Sessions popup is open
+    EditingSessionName, // Typing a name to save the current session under
+    EditingMarker,      // Typing the coordinates of a new marker
+    Styling,            // Browsing the rules on the Styling screen
+    EditingRule,        // Typing a new styling rule
+    EditingTolerance,   // Typing the simplification tolerance of the highlighted file
+    EditingCrs,         // Typing the CRS override of the highlighted file
+    EditingFilter,      // Typing the feature filter of the highlighted file
+    EditingBuffer,      // Typing the distance to buffer the highlighted file by
+    EditingDissolve,    // Typing the property to dissolve the highlighted file by
+    EditingJoin,        // Typing the CSV file and key to join to the highlighted file
+    ChoosingPoints,     // Asking which point to compute for each polygon
+    EditingMinify,      // Typing the decimals to round the highlighted file to
+    EditingSnap,        // Typing the grid to snap the highlighted file to
+    ConfirmPlot,        // Asking whether to start a large render
+    NavigatingPreview,  // Panning and zooming the preview with the keyboard
+    EditingExtent,      // Typing an explicit plot extent
+    EditingPlace,       // Typing the name of a place to go to
+    EditingJump,        // Typing the coordinates to center the preview on
+    EditingTime,        // Typing the time range of the features to plot
+    LayerProperties,    // Layer properties popup of the highlighted file is open
+    Validation,         // Scrolling the validation report on the Validation screen
+    EditingDuplicates,  // Typing the tolerance to find near-duplicate features with
+    Duplicates,         // Scrolling the duplicates found on the Duplicates screen
+    ChoosingTopology,   // Asking which layers to check for overlaps and gaps
+    Topology,           // Browsing the overlaps and gaps found on the Topology screen
+    PlotSummary,        // Looking at the results of the last plot
+    Logs,               // Scrolling the log on the Logs screen
+    Identify,           // Looking at the features under the preview crosshair
+    Measuring,          // Placing the vertices of a path measured in the preview
+    SelectingFeatures,  // Checking the features of the highlighted file to plot
+    AttributeTable,     // Browsing the attribute table of a file
+    EditingCell,        // Typing the value of the highlighted cell of the attribute table
+    AddingColumn,       // Typing the name of a property to add to the attribute table
+    SavingTable,        // Typing the file to save the edited attribute table to
+    Wfs,                // WFS popup listing the feature types of a service is open
+    EditingWfsUrl,      // Typing the URL of a WFS service
+}
+
+impl AppMode {
+    /// Modes that change something over several keys, recorded for undo as a
+    /// single change once left.
+    pub fn is_editing(&self) -> bool {
+        !matches!(
+            self,
+            AppMode::Navigation
+                | AppMode::Sessions
+                | AppMode::Wfs
+                | AppMode::Styling
+                | AppMode::ChoosingPoints
+                | AppMode::ConfirmPlot
+                | AppMode::Validation
+                | AppMode::Duplicates
+                | AppMode::ChoosingTopology
+                | AppMode::Topology
+                | AppMode::PlotSummary
+                | AppMode::Logs
+                | AppMode::Identify
+                | AppMode::AttributeTable
+        )
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -23,7 +375,7 @@ pub enum TerminalEvent {
 }
 
 // Struct to hold cached GeoJSON file information
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct GeoJsonInfo {
     pub file_size_kb: u64,
     pub modified_time: String,
@@ -31,6 +383,231 @@ pub struct GeoJsonInfo {
     pub geometry_counts: HashMap<String, usize>,
     pub bbox: Option<[f64; 4]>, // [min_lon, min_lat, max_lon, max_lat]
     pub parse_error: Option<String>,
+    pub skipped_message: Option<String>, // Invalid features left out of a file that parsed
+    pub crs: Option<String>,             // Legacy `crs` member, if the file declares one
+    pub crs_overridden: bool,            // `crs` is the user's override instead
+    pub crs_warning: Option<String>,     // Why the coordinates could not be reprojected
+    #[serde(skip)]
+    pub filtered_count: Option<usize>, // Features matching the layer filter, if one is set
+    pub line_length_km: f64,             // Great-circle length of all lines
+    pub polygon_area_km2: f64,           // Spherical area of all polygons, holes left out
+    #[serde(default)]
+    pub time_extent: Option<(i64, i64)>, // Earliest and latest feature time (see `temporal::feature_time`)
+    pub header_only: bool, // Counted from a large file's header and bytes; its features were not read
+    pub property_stats: Vec<PropertyStats>, // In the order the properties first appear
+    property_index: HashMap<String, usize>, // Position of each property in `property_stats`
+}
+
+impl GeoJsonInfo {
+    /// The metadata of the file `name` as the properties of a feature without
+    /// geometry, so a `FeatureFilter` can select files by it, e.g.
+    /// `polygons && features < 10000`: `name`, `features`, `points`, `lines`,
+    /// `polygons` (counts including the Multi types), `size_kb`, `modified`,
+    /// `crs` (null if none is declared) and `error` (whether it failed to parse).
+    pub fn metadata_feature(&self, name: &str) -> Feature {
+        let count = |types: &[&str]| -> usize {
+            types
+                .iter()
+                .filter_map(|t| self.geometry_counts.get(*t))
+                .sum()
+        };
+        let mut properties = geojson::JsonObject::new();
+        properties.insert(String::from("name"), name.into());
+        properties.insert(String::from("features"), self.feature_count.into());
+        properties.insert(
+            String::from("points"),
+            count(&["Point", "MultiPoint"]).into(),
+        );
+        properties.insert(
+            String::from("lines"),
+            count(&["LineString", "MultiLineString"]).into(),
+        );
+        properties.insert(
+            String::from("polygons"),
+            count(&["Polygon", "MultiPolygon"]).into(),
+        );
+        properties.insert(String::from("size_kb"), self.file_size_kb.into());
+        properties.insert(String::from("modified"), self.modified_time.clone().into());
+        properties.insert(String::from("crs"), self.crs.clone().into());
+        properties.insert(String::from("error"), self.parse_error.is_some().into());
+        Feature {
+            bbox: None,
+            geometry: None,
+            id: None,
+            properties: Some(properties),
+            foreign_members: None,
+        }
+    }
+
+    /// Adds the counts, extent, lengths and areas of `features` to this info.
+    pub fn record_features(&mut self, features: &[Feature]) {
+        self.feature_count += features.len();
+        let mut bbox = self.bbox.unwrap_or_else(plot::empty_bbox);
+        for geometry in features.iter().filter_map(|f| f.geometry.as_ref()) {
+            let geom_type = geometry.value.type_name().to_string();
+            *self.geometry_counts.entry(geom_type).or_insert(0) += 1;
+            plot::extend_bbox(&mut bbox, &geometry.value);
+            self.line_length_km += measure::geometry_length_km(&geometry.value);
+            self.polygon_area_km2 += measure::geometry_area_km2(&geometry.value);
+        }
+        if bbox[0] != f64::MAX {
+            self.bbox = Some(bbox);
+        }
+        for time in features.iter().filter_map(temporal::feature_time) {
+            self.time_extent = Some(match self.time_extent {
+                Some((first, last)) => (first.min(time), last.max(time)),
+                None => (time, time),
+            });
+        }
+        for properties in features.iter().filter_map(|f| f.properties.as_ref()) {
+            for (name, value) in properties {
+                let index = *self.property_index.entry(name.clone()).or_insert_with(|| {
+                    self.property_stats.push(PropertyStats {
+                        name: name.clone(),
+                        ..PropertyStats::default()
+                    });
+                    self.property_stats.len() - 1
+                });
+                self.property_stats[index].add(value);
+            }
+        }
+    }
+}
+
+// Distinct values counted per property; beyond this only "at least" is known
+const MAX_DISTINCT_VALUES: usize = 1000;
+
+// Distinct values kept per property to show as examples
+const SAMPLE_VALUES: usize = 5;
+
+/// Statistics of one property over the features of a file, shown on the
+/// Attributes view of the Info tab.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct PropertyStats {
+    pub name: String,
+    pub count: usize,   // Features with a value other than null
+    pub numbers: usize, // Values that are numbers
+    pub min: f64,
+    pub max: f64,
+    sum: f64,
+    distinct: HashSet<String>,
+    pub samples: Vec<String>, // The first distinct values
+}
+
+impl PropertyStats {
+    fn add(&mut self, value: &serde_json::Value) {
+        if value.is_null() {
+            return;
+        }
+        if let Some(number) = value.as_f64() {
+            if self.numbers == 0 {
+                (self.min, self.max) = (number, number);
+            }
+            self.min = self.min.min(number);
+            self.max = self.max.max(number);
+            self.sum += number;
+            self.numbers += 1;
+        }
+        self.count += 1;
+        if self.distinct.len() < MAX_DISTINCT_VALUES {
+            let text = match value {
+                serde_json::Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            if self.samples.len() < SAMPLE_VALUES && !self.distinct.contains(&text) {
+                self.samples.push(text.clone());
+            }
+            self.distinct.insert(text);
+        }
+    }
+
+    /// Number of distinct values, and whether counting stopped at the limit.
+    pub fn distinct(&self) -> (usize, bool) {
+        (
+            self.distinct.len(),
+            self.distinct.len() >= MAX_DISTINCT_VALUES,
+        )
+    }
+
+    /// Whether every value is a number.
+    pub fn is_numeric(&self) -> bool {
+        self.numbers > 0 && self.numbers == self.count
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.numbers > 0).then(|| self.sum / self.numbers as f64)
+    }
+}
+
+/// A plotted file on the plot summary screen.
+pub struct LayerSummary {
+    pub name: String,
+    pub drawn: usize,
+    pub skipped: usize, // Features left out by the layer filter, clip mask or style filters
+}
+
+/// What the last plot from the TUI produced, shown on the plot summary screen.
+#[derive(Default)]
+pub struct PlotSummary {
+    pub outputs: Vec<PathBuf>, // The image, then its thumbnail if one was written
+    pub layers: Vec<LayerSummary>,
+    pub read_time: Duration, // Reading files that were not loaded yet
+    pub render_time: Duration,
+    pub warnings: Vec<String>,
+    pub error: Option<String>, // Why no image was written, if none was
+    pub copied: bool,          // The image went to the clipboard instead of a file
+}
+
+/// A plot rendering on a worker thread, which reports its progress and result
+/// through the event channel.
+pub struct PlotJob {
+    pub progress: Arc<RenderProgress>, // Cancels the render
+    pub counts: (usize, usize),        // Features drawn and to draw, as last reported
+    pub to_clipboard: bool,
+    pub layers: Vec<(String, usize, usize)>, // Name, features given to the render and features left out by its filter
+    pub summary: PlotSummary,                // Read time and warnings known before rendering
+    pub started: Instant,
+}
+
+/// A listed file with its selection and styling, and what was read of it.
+#[derive(Default)]
+pub struct Layer {
+    pub file: String, // Name in the data directory, or the absolute path of a file opened from elsewhere
+    pub selected: bool,
+    pub color: Option<RGBColor>, // Assigned for plotting once selected
+    pub simplify_tolerance: Option<f64>, // Overrides the global tolerance
+    pub line_width: Option<u32>, // `None` is the default
+    pub line_style: Option<LineStyle>, // `None` is solid
+    pub point_size: Option<u32>, // `None` is the default
+    pub point_shape: Option<PointShape>, // `None` is a circle
+    pub opacity: Option<u32>,    // Percent; `None` is opaque
+    pub crs: Option<String>,     // Overrides the CRS the file declares
+    pub filter: Option<FeatureFilter>, // Expression selecting features
+    // Positions of the features checked for plotting; `None` plots all
+    pub feature_selection: Option<BTreeSet<usize>>,
+    // Geometry types drawn, within the P/L/O toggles; `None` draws all
+    pub geometry_types: Option<GeometryTypes>,
+    pub info: Option<GeoJsonInfo>,           // Metadata, once parsed
+    pub features: Option<Vec<Feature>>,      // Parsed features, shown in the preview
+    pub feature_index: Option<FeatureIndex>, // R-tree of the features, kept by the preview
+    pub levels: Option<Arc<LevelsOfDetail>>, // Simplified copies of the features, kept between plots
+}
+
+impl Layer {
+    pub fn new(file: String) -> Layer {
+        Layer {
+            file,
+            ..Layer::default()
+        }
+    }
+
+    /// Forgets everything read from the file, so it is read again.
+    pub fn forget_contents(&mut self) {
+        self.info = None;
+        self.features = None;
+        self.feature_index = None;
+        self.levels = None;
+    }
 }
 
 pub struct App {
@@ -38,36 +615,156 @@ pub struct App {
     pub current_mode: AppMode, // Current operational mode of the TUI
 
     // State related to GeoJSON files
-    pub geojson_files: Vec<String>,
+    pub layers: Vec<Layer>,         // Every listed file, in list order
     pub selected_file_index: usize, // Index in `filtered_geojson_indices`
     pub scroll_offset: usize,       // Scroll position for the file list
-    pub selected_files_status: Vec<bool>, // Selection status for all original files
-    pub assigned_plot_colors: Vec<Option<RGBColor>>, // Assigned colors for plotting
     pub current_color_index_for_assignment: usize, // Index for cycling colors
 
     // Plotting options
     pub plot_points: bool,
     pub plot_lines: bool,
     pub plot_polygons: bool,
+    pub scale_bar: bool,
+    pub north_arrow: bool,
+    pub basemap_enabled: bool,
+    pub basemap: Basemap, // Tile source from the [basemap] config section
+    pub underlay: Option<Arc<Raster>>, // Georeferenced image opened with Ctrl+X
+    pub thumbnails: bool, // Write a thumbnail next to the exported image
+    pub thumbnail_size: u32, // Longest side of the thumbnail, from the [plot] config section
+    pub warning_banner: bool, // Name unreadable layers on the image, from the [plot] config section
+    pub clip_mask_index: Option<usize>, // File whose polygons clip the other layers
+    pub clip_mask_shade: bool, // Shade outside the mask instead of hiding it
+    pub focus_index: Option<usize>, // File drawn in color while the others are muted
+    pub figure_mode: Option<FigureBackground>, // Geometry-only output when set
+    pub projection: Projection, // Layout of the plotted map
+    pub keep_aspect: bool, // Same ground scale along both axes of the plot
+    pub cluster_distance: Option<u32>, // Pixels within which points are drawn as one marker
+    pub inset: Option<Inset>, // World overview map in a corner of the plot
+    pub time_range: Option<TimeRange>, // Plot only features without a time or one inside it
+    pub simplify_tolerance: f64, // Global tolerance from the [plot] config section (0 = off)
+    pub auto_simplify: bool, // Global tolerance derived from the output pixel size instead
+    pub selected_feature: usize, // Row highlighted in the feature selection popup
+    pub attribute_table: Option<AttributeTable>, // Table shown on the Attribute Table screen
+    pub table_input: TextInput, // Cell value, property name or file typed on the Attribute Table screen
+    pub layer_property: LayerProperty, // Row selected in the layer properties popup
+    pub panel_tab: PanelTab,
+    pub info_tab: InfoTab,
+    pub info_scroll: u16, // First line shown in the right panel
+    pub tolerance_input: TextInput,
+    pub crs_input: TextInput,
+    pub filter_input: TextInput,
+    pub buffer_input: TextInput,
+    pub dissolve_input: TextInput,
+    pub join_input: TextInput,
+    pub minify_input: TextInput,
+    pub snap_input: TextInput,
+    pub selection_input: TextInput,
+    pub open_path_input: TextInput,
+    pub export_path_input: TextInput,
 
     // Output filename editing
-    pub output_filename_buffer: String,
-    pub output_filename_cursor: usize,
+    pub output_filename_input: TextInput,
     pub previous_output_filename_buffer: String,
 
+    // Title, subtitle and attribution of the output image
+    pub title_input: TextInput,
+    pub subtitle_input: TextInput,
+    pub attribution_input: TextInput,
+    pub title_field: TitleField,             // Field being edited
+    pub previous_title_buffers: [String; 3], // Restored when editing is cancelled
+
     // Fuzzy search
-    pub search_query_buffer: String,
-    pub search_query_cursor: usize,
-    pub filtered_geojson_indices: Vec<usize>, // Indices into `geojson_files`
+    pub search_query_input: TextInput,
+    pub filtered_geojson_indices: Vec<usize>, // Indices into `layers`
     pub previous_search_query_buffer: String,
+    pub search_mode: SearchMode,
+    pub property_search: Option<PropertySearch>, // Last property search; lists only its files
 
     // Cached GeoJSON metadata
-    pub cached_geojson_info: Vec<Option<GeoJsonInfo>>,
+    pub info_cache: InfoCache, // Metadata of files parsed by this and earlier runs
     pub previous_selected_file_index_in_filtered: usize,
 
+    // Growing GeoJSONL file being tailed, if any
+    pub follow: Option<FollowState>,
+    pub plot_job: Option<PlotJob>, // Plot rendering in the background, if any
+    pub history: History,          // Undo and redo of selection and option changes
+    pub stdin_file: Option<String>, // Temporary copy of GeoJSON piped in with `--stdin`
+    pub data_watcher: Option<DirectoryWatcher>, // Lists the data directory again as it changes
+
+    // Live GPS position, drawn on top of the preview
+    pub gps_source: GpsSource, // From the [gps] config section
+    pub gps: Option<GpsReader>,
+    pub gps_position: Option<GpsFix>,
+    pub gps_track: Vec<GpsFix>, // Fixes recorded since recording started
+    pub gps_recording: bool,
+
+    // Markers dropped at typed coordinates, included in the plot
+    pub markers: Vec<MapMarker>,
+    pub marker_input: TextInput,
+    pub jump_point: Option<(f64, f64)>, // Position jumped to, marked in the preview only
+    pub jump_input: TextInput,
+
+    // Attribute-based styling rules, edited on the Styling screen
+    pub style_rules: Vec<StyleRule>,
+    pub selected_rule_index: usize,
+    pub rule_input: TextInput,
+
+    // Report of the last validated file, shown on the Validation screen
+    pub validation_report: Option<ValidationReport>,
+    pub validation_scroll: usize, // First issue shown
+
+    // Duplicated geometries of the last checked file, shown on the Duplicates screen
+    pub duplicate_report: Option<DuplicateReport>,
+    pub duplicate_scroll: usize, // First duplicate shown
+    pub duplicate_input: TextInput,
+
+    // Overlaps and gaps of the last checked layers, shown on the Topology screen
+    pub topology_report: Option<TopologyReport>,
+    pub topology_selected: usize, // Highlighted issue
+    pub plot_summary: Option<PlotSummary>,
+
+    // Point identified by clicking the preview, and the optional place name lookup
+    pub preview_area: Rect, // Preview canvas including borders, as last rendered
+    pub preview_bounds: [f64; 4], // Extent shown in the preview, as last rendered
+    pub preview_view: Option<[f64; 4]>, // Viewport set by panning/zooming; `None` follows the data
+    pub extent_locked: bool, // Plot the previewed viewport instead of the data bbox
+    pub extent_input: TextInput,
+    pub place_input: TextInput,
+    pub time_input: TextInput,
+    pub identified_point: Option<(f64, f64)>,
+    pub preview_cursor: Option<(u16, u16)>, // Crosshair cell on the canvas while navigating the preview
+    pub identified_features: Vec<(String, Feature)>, // Features under the crosshair and their file
+    pub identify_scroll: usize,             // First line shown in the Identify popup
+    pub measure_points: Vec<(f64, f64)>,    // Vertices (lon, lat) of the measured path
+    pub measure_closed: bool,               // Whether the measured path is closed into a polygon
+    pub geocoder: Option<ReverseGeocoder>,
+    pub place_search: PlaceSearch, // Finds places to go to by name
+    pub downloads: Vec<Download>,  // Remote files being fetched into the cache
+    pub finished_downloads: Vec<String>, // Outcomes of downloads that ended while others still run
+    pub preview_server: Option<PreviewServer>, // Serving the selection to browsers while running
+    pub serve_address: String,     // From the [serve] config section
+
+    // Saved sessions
+    pub session_names: Vec<String>,
+    pub selected_session_index: usize,
+    pub session_name_input: TextInput,
+
+    // WFS client
+    pub wfs_url_input: TextInput,
+    pub wfs_request: Option<CapabilitiesRequest>, // Capabilities not yet returned
+    pub wfs_service: Option<(String, Capabilities)>, // Last service listed and what it offers
+    pub selected_wfs_type: usize,
+
     // UI related
     pub notification: String,
-    pub help_keybinds: Vec<String>,
+    // File shown in the JSON tab and its text, or why it could not be read
+    pub json_preview: Option<(String, Result<JsonPreview, String>)>,
+    pub log: Vec<LogEntry>, // Earlier notifications, warnings and errors, oldest first
+    logged_notification: String, // Last notification added to the log
+    pub log_scroll: usize,  // Entries skipped from the newest on the Logs screen
+    pub log_min_level: LogLevel, // Least severe entries shown on the Logs screen
+    pub help_keybinds: Vec<String>, // Rendered from `keymap`
+    pub keymap: KeyMap,
 
     // Plotting colors
     pub plot_colors: [RGBColor; 7],
@@ -75,77 +772,823 @@ pub struct App {
     // Resizing for main GeoJSON Mapper UI
     pub left_pane_width_percentage: u16, // Width of the left (file list) pane
     pub is_resizing: bool,               // True when actively dragging the divider
+
+    // Mouse selection in the file list
+    pub file_list_area: Rect, // Bordered file list, as last rendered
+    pub regions: Regions,     // Clickable parts of the last rendering
+    pub last_click: Option<(Instant, usize)>, // Time and file of the last click, for double-clicks
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl App {
     /// Constructs a new `App` with initial states.
     pub fn new() -> App {
-        App {
+        let mut app = App {
             current_screen: CurrentScreen::GeoJsonMapper, // Start directly in the GeoJSON Mapper UI
             current_mode: AppMode::Navigation,
 
-            geojson_files: Vec::new(),
+            layers: Vec::new(),
             selected_file_index: 0,
             scroll_offset: 0,
-            selected_files_status: Vec::new(),
-            assigned_plot_colors: Vec::new(),
             current_color_index_for_assignment: 0,
 
             plot_points: true,
             plot_lines: true,
             plot_polygons: true,
+            scale_bar: false,
+            north_arrow: false,
+            basemap_enabled: false,
+            basemap: Basemap::default(),
+            underlay: None,
+            thumbnails: false,
+            thumbnail_size: plot::DEFAULT_THUMBNAIL_SIZE,
+            warning_banner: false,
+            clip_mask_index: None,
+            clip_mask_shade: false,
+            focus_index: None,
+            figure_mode: None,
+            projection: Projection::LonLat,
+            keep_aspect: false,
+            cluster_distance: None,
+            inset: None,
+            time_range: None,
+            simplify_tolerance: 0.0,
+            auto_simplify: false,
+            selected_feature: 0,
+            attribute_table: None,
+            table_input: TextInput::default(),
+            layer_property: LayerProperty::LineWidth,
+            panel_tab: PanelTab::Info,
+            info_tab: InfoTab::Details,
+            info_scroll: 0,
+            tolerance_input: TextInput::default(),
+            crs_input: TextInput::default(),
+            filter_input: TextInput::default(),
+            buffer_input: TextInput::default(),
+            dissolve_input: TextInput::default(),
+            join_input: TextInput::default(),
+            minify_input: TextInput::default(),
+            snap_input: TextInput::default(),
+            selection_input: TextInput::default(),
+            open_path_input: TextInput::default(),
+            export_path_input: TextInput::new("layers.gpkg"),
 
-            output_filename_buffer: String::from("combined_plot.png"),
-            output_filename_cursor: 0,
+            output_filename_input: TextInput::new("combined_plot.png"),
             previous_output_filename_buffer: String::new(),
 
-            search_query_buffer: String::new(),
-            search_query_cursor: 0,
+            title_input: TextInput::new(plot::DEFAULT_TITLE),
+            subtitle_input: TextInput::default(),
+            attribution_input: TextInput::default(),
+            title_field: TitleField::Title,
+            previous_title_buffers: Default::default(),
+
+            search_query_input: TextInput::default(),
             filtered_geojson_indices: Vec::new(),
             previous_search_query_buffer: String::new(),
+            search_mode: SearchMode::FileNames,
+            property_search: None,
 
-            cached_geojson_info: Vec::new(),
+            info_cache: InfoCache::default(),
             previous_selected_file_index_in_filtered: 0,
 
+            follow: None,
+            plot_job: None,
+            history: History::default(),
+            stdin_file: None,
+            data_watcher: None,
+
+            gps_source: GpsSource::default(),
+            gps: None,
+            gps_position: None,
+            gps_track: Vec::new(),
+            gps_recording: false,
+
+            markers: Vec::new(),
+            marker_input: TextInput::default(),
+            jump_point: None,
+            jump_input: TextInput::default(),
+
+            style_rules: Vec::new(),
+            selected_rule_index: 0,
+            rule_input: TextInput::default(),
+
+            validation_report: None,
+            validation_scroll: 0,
+            duplicate_report: None,
+            duplicate_scroll: 0,
+            duplicate_input: TextInput::default(),
+            topology_report: None,
+            topology_selected: 0,
+            plot_summary: None,
+
+            preview_area: Rect::default(),
+            preview_bounds: [-180.0, -90.0, 180.0, 90.0],
+            preview_view: None,
+            extent_locked: false,
+            extent_input: TextInput::default(),
+            place_input: TextInput::default(),
+            time_input: TextInput::default(),
+            identified_point: None,
+            preview_cursor: None,
+            identified_features: Vec::new(),
+            identify_scroll: 0,
+            measure_points: Vec::new(),
+            measure_closed: false,
+            geocoder: None,
+            place_search: PlaceSearch::default(),
+            downloads: Vec::new(),
+            finished_downloads: Vec::new(),
+            preview_server: None,
+            serve_address: String::from(serve::DEFAULT_SERVE_ADDRESS),
+
+            session_names: Vec::new(),
+            selected_session_index: 0,
+            session_name_input: TextInput::default(),
+
+            wfs_url_input: TextInput::default(),
+            wfs_request: None,
+            wfs_service: None,
+            selected_wfs_type: 0,
+
             notification: String::from("Select GeoJSON files to plot:"),
-            help_keybinds: vec![
-                "J/K or Arrow Keys: Navigate file list".to_string(),
-                "Space: Toggle file selection".to_string(),
-                "Enter: Plot selected files".to_string(),
-                "C: Cycle next assignment color".to_string(),
-                "R: Rename output plot".to_string(),
-                "/: Start fuzzy search".to_string(),
-                "P: Toggle Points visibility".to_string(),
-                "L: Toggle Lines visibility".to_string(),
-                "O: Toggle Polygons visibility".to_string(),
-                "Q: Quit the application".to_string(),
-                "H: Show Help screen".to_string(),
-                "Click & Drag Divider: Resize panels".to_string(),
-            ],
-
-            plot_colors: [
-                RGBColor(0, 0, 0),     // Black
-                RGBColor(255, 0, 0),   // Red
-                RGBColor(0, 255, 0),   // Green
-                RGBColor(0, 0, 255),   // Blue
-                RGBColor(255, 255, 0), // Yellow
-                RGBColor(255, 0, 255), // Magenta
-                RGBColor(0, 255, 255), // Cyan
-            ],
+            json_preview: None,
+            log: Vec::new(),
+            logged_notification: String::new(),
+            log_scroll: 0,
+            log_min_level: LogLevel::Info,
+            help_keybinds: Vec::new(),
+            keymap: KeyMap::default(),
+
+            plot_colors: PLOT_COLORS,
 
             left_pane_width_percentage: 50, // Default 50% width for left pane
             is_resizing: false,
+
+            file_list_area: Rect::default(),
+            regions: Regions::default(),
+            last_click: None,
+        };
+        app.refresh_help_keybinds();
+        app
+    }
+
+    /// Replaces the active key bindings and regenerates the help text.
+    pub fn set_keymap(&mut self, keymap: KeyMap) {
+        self.keymap = keymap;
+        self.refresh_help_keybinds();
+    }
+
+    fn refresh_help_keybinds(&mut self) {
+        self.help_keybinds = self.keymap.help_lines();
+        self.help_keybinds
+            .push(String::from("Click & Drag Divider: Resize panels"));
+        self.help_keybinds.push(String::from(
+            "Click file: Highlight, Double-click/Checkbox: Toggle selection, Wheel: Scroll",
+        ));
+        self.help_keybinds.push(String::from(
+            "Click preview: Identify point (and its place name, if configured)",
+        ));
+    }
+
+    /// The title field being edited.
+    pub fn title_field_input(&mut self) -> &mut TextInput {
+        match self.title_field {
+            TitleField::Title => &mut self.title_input,
+            TitleField::Subtitle => &mut self.subtitle_input,
+            TitleField::Attribution => &mut self.attribution_input,
+        }
+    }
+
+    /// Plot options for the current settings.
+    pub fn plot_options(&self) -> PlotOptions {
+        PlotOptions {
+            caption: self.title_input.text.clone(),
+            subtitle: self.subtitle_input.text.clone(),
+            attribution: self.attribution_input.text.clone(),
+            plot_points: self.plot_points,
+            plot_lines: self.plot_lines,
+            plot_polygons: self.plot_polygons,
+            markers: self.markers.clone(),
+            style_rules: self.style_rules.clone(),
+            figure: self.figure_mode,
+            projection: self.projection,
+            keep_aspect: self.keep_aspect,
+            cluster_distance: self.cluster_distance,
+            inset: self.inset,
+            time_range: self.time_range.clone(),
+            simplify_tolerance: self.simplify_tolerance,
+            auto_simplify: self.auto_simplify,
+            scale_bar: self.scale_bar,
+            north_arrow: self.north_arrow,
+            basemap: self.basemap_enabled.then(|| self.basemap.clone()),
+            underlay: self.underlay.clone(),
+            extent: self.preview_view.filter(|_| self.extent_locked),
+            thumbnail: self.thumbnails.then_some(self.thumbnail_size),
+            warning_banner: self.warning_banner,
+            ..PlotOptions::default()
+        }
+    }
+
+    /// Estimates the cost of plotting the selected files that are loaded.
+    pub fn estimate_plot(&self) -> RenderEstimate {
+        let layers: Vec<(&[Feature], Option<f64>)> = self
+            .layers
+            .iter()
+            .filter(|layer| layer.selected)
+            .filter_map(|layer| Some((layer.features.as_deref()?, layer.simplify_tolerance)))
+            .collect();
+        plot::estimate_render(&layers, &self.plot_options())
+    }
+
+    /// Describes the global simplification tolerance, e.g. "auto" or "0.05".
+    pub fn simplify_label(&self) -> String {
+        if self.auto_simplify {
+            String::from("auto")
+        } else if self.simplify_tolerance > 0.0 {
+            self.simplify_tolerance.to_string()
+        } else {
+            String::from("off")
         }
     }
 
     /// Sets up initial GeoJSON data
     pub fn setup_geojson_data(&mut self, geojson_files_input: Vec<String>) {
-        self.geojson_files = geojson_files_input;
-        let num_files = self.geojson_files.len();
-        self.selected_files_status = vec![false; num_files];
-        self.assigned_plot_colors = vec![None; num_files];
-        self.cached_geojson_info = vec![None; num_files];
-        self.filtered_geojson_indices = (0..num_files).collect(); // Initially all files are filtered
+        self.layers = geojson_files_input.into_iter().map(Layer::new).collect();
+        self.filtered_geojson_indices = (0..self.layers.len()).collect(); // Initially all files are filtered
         self.selected_file_index = 0; // Reset selected index
     }
+
+    /// Adds a file written to the data directory to the end of the list, or
+    /// forgets what was parsed of it if it is listed already. Returns its index.
+    pub fn add_geojson_file(&mut self, name: String) -> usize {
+        if let Some(index) = self.file_index(&name) {
+            self.layers[index].forget_contents();
+            return index;
+        }
+        self.layers.push(Layer::new(name));
+        let index = self.layers.len() - 1;
+        self.filtered_geojson_indices.push(index);
+        index
+    }
+
+    /// Position of the file listed as `name`.
+    pub fn file_index(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.file == name)
+    }
+
+    pub fn selected_count(&self) -> usize {
+        self.layers.iter().filter(|layer| layer.selected).count()
+    }
+
+    /// Whether the file was opened from outside the data directory; such
+    /// files are listed by their absolute path.
+    pub fn is_external(&self, original_index: usize) -> bool {
+        Path::new(&self.layers[original_index].file).is_absolute()
+    }
+
+    /// Forgets a file that disappeared from the data directory, shifting the
+    /// indices of the files after it. The property search is dropped since
+    /// it refers to files by index.
+    pub fn remove_geojson_file(&mut self, index: usize) {
+        self.layers.remove(index);
+
+        // `None` if it was the removed file, shifted down if it came after it
+        let shift = |i: usize| match i.cmp(&index) {
+            std::cmp::Ordering::Less => Some(i),
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => Some(i - 1),
+        };
+        self.filtered_geojson_indices = self
+            .filtered_geojson_indices
+            .iter()
+            .filter_map(|&i| shift(i))
+            .collect();
+        self.selected_file_index = self
+            .selected_file_index
+            .min(self.filtered_geojson_indices.len().saturating_sub(1));
+        self.clip_mask_index = self.clip_mask_index.and_then(shift);
+        self.focus_index = self.focus_index.and_then(shift);
+        if let Some(follow) = &mut self.follow {
+            match shift(follow.file_index) {
+                Some(i) => follow.file_index = i,
+                None => self.follow = None,
+            }
+        }
+        if let Some(table) = &mut self.attribute_table {
+            match shift(table.file_index) {
+                Some(i) => table.file_index = i,
+                None => self.attribute_table = None,
+            }
+        }
+        self.property_search = None;
+    }
+
+    /// Counts the loaded features of `original_index` matching its filter
+    /// into its cached info.
+    pub fn refresh_filter_count(&mut self, original_index: usize) {
+        let count = match (
+            &self.layers[original_index].filter,
+            &self.layers[original_index].features,
+        ) {
+            (Some(filter), Some(features)) => Some(filter.count(features)),
+            _ => None,
+        };
+        if let Some(info) = self.layers[original_index].info.as_mut() {
+            info.filtered_count = count;
+        }
+    }
+
+    /// Whether `feature`, at `position` in `original_index`, is checked for
+    /// plotting and passes the file's filter and the time range, if any.
+    pub fn layer_accepts(&self, original_index: usize, position: usize, feature: &Feature) -> bool {
+        self.layers[original_index]
+            .feature_selection
+            .as_ref()
+            .is_none_or(|checked| checked.contains(&position))
+            && self.layers[original_index]
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.accepts(feature))
+            && self
+                .time_range
+                .as_ref()
+                .is_none_or(|range| range.accepts(feature))
+    }
+
+    /// Earliest and latest feature time of the selected files read so far.
+    pub fn time_extent(&self) -> Option<(i64, i64)> {
+        self.layers
+            .iter()
+            .filter(|layer| layer.selected)
+            .filter_map(|layer| layer.info.as_ref()?.time_extent)
+            .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+    }
+
+    /// Checks or unchecks the features at `positions` of `original_index`,
+    /// which has `count` features; checking all of them plots the whole file again.
+    pub fn set_features_checked(
+        &mut self,
+        original_index: usize,
+        count: usize,
+        positions: impl IntoIterator<Item = usize>,
+        checked: bool,
+    ) {
+        let selection = self.layers[original_index]
+            .feature_selection
+            .get_or_insert_with(|| (0..count).collect());
+        for position in positions {
+            if checked {
+                selection.insert(position);
+            } else {
+                selection.remove(&position);
+            }
+        }
+        if selection.range(..count).count() == count {
+            self.layers[original_index].feature_selection = None;
+        }
+    }
+
+    /// Records the notification in the log if it changed since it was last
+    /// recorded. Called once per frame, so every message that was shown ends
+    /// up in the Log tab without the places setting it knowing about it.
+    pub fn log_notification(&mut self) {
+        if self.notification.is_empty() || self.notification == self.logged_notification {
+            return;
+        }
+        self.logged_notification.clone_from(&self.notification);
+        self.log(LogLevel::Info, self.notification.clone());
+    }
+
+    /// Adds `text` to the log unless it repeats the last entry.
+    pub fn log(&mut self, level: LogLevel, text: String) {
+        if self.log.last().is_some_and(|entry| entry.text == text) {
+            return;
+        }
+        if self.log.len() == LOG_CAPACITY {
+            self.log.remove(0);
+        }
+        let time = chrono::Local::now().format("%H:%M:%S").to_string();
+        self.log.push(LogEntry { time, level, text });
+    }
+
+    /// Original index of the highlighted file, if the filtered list is not empty.
+    pub fn highlighted_file_index(&self) -> Option<usize> {
+        self.filtered_geojson_indices
+            .get(self.selected_file_index)
+            .copied()
+    }
+
+    /// Selects a file with the next assignment color, or deselects it.
+    /// Does nothing if the file already has the requested state.
+    pub fn set_selected(&mut self, original_index: usize, selected: bool) {
+        if self.layers[original_index].selected == selected {
+            return;
+        }
+        self.layers[original_index].selected = selected;
+        if selected {
+            self.layers[original_index].color =
+                Some(self.plot_colors[self.current_color_index_for_assignment]);
+            self.current_color_index_for_assignment =
+                (self.current_color_index_for_assignment + 1) % self.plot_colors.len();
+        } else {
+            self.layers[original_index].color = None;
+        }
+    }
+
+    /// Sets the selection of every file in the filtered list using `f(currently_selected)`.
+    /// Returns the number of selected files in the filtered list afterwards.
+    pub fn update_filtered_selection(&mut self, f: impl Fn(bool) -> bool) -> usize {
+        for i in self.filtered_geojson_indices.clone() {
+            let selected = f(self.layers[i].selected);
+            self.set_selected(i, selected);
+        }
+        self.filtered_geojson_indices
+            .iter()
+            .filter(|&&i| self.layers[i].selected)
+            .count()
+    }
+
+    /// Selects every file between the filtered list positions `from` and `to`, inclusive.
+    pub fn select_range(&mut self, from: usize, to: usize) {
+        let (start, end) = (from.min(to), from.max(to));
+        for list_index in start..=end {
+            if let Some(&i) = self.filtered_geojson_indices.get(list_index) {
+                self.set_selected(i, true);
+            }
+        }
+    }
+
+    /// Converts a terminal cell inside the preview to lon/lat, if it is on the canvas.
+    pub fn preview_position(&self, column: u16, row: u16) -> Option<(f64, f64)> {
+        let area = self.preview_area;
+        // The canvas fills the area inside the block borders
+        if column <= area.x
+            || row <= area.y
+            || column + 1 >= area.x + area.width
+            || row + 1 >= area.y + area.height
+        {
+            return None;
+        }
+        let [min_lon, min_lat, max_lon, max_lat] = self.preview_bounds;
+        let fx = (column - area.x - 1) as f64 + 0.5;
+        let fy = (row - area.y - 1) as f64 + 0.5;
+        let lon = min_lon + fx / (area.width - 2) as f64 * (max_lon - min_lon);
+        let lat = max_lat - fy / (area.height - 2) as f64 * (max_lat - min_lat);
+        Some((lon, lat))
+    }
+
+    /// Lon/lat under the crosshair of the preview, if it is shown.
+    pub fn preview_cursor_position(&self) -> Option<(f64, f64)> {
+        let (column, row) = self.preview_cursor?;
+        let area = self.preview_area;
+        self.preview_position(area.x + 1 + column, area.y + 1 + row)
+    }
+
+    /// Moves the crosshair by whole cells, keeping it on the canvas; it starts
+    /// in the middle.
+    pub fn move_preview_cursor(&mut self, dx: i32, dy: i32) {
+        let width = self.preview_area.width.saturating_sub(2).max(1);
+        let height = self.preview_area.height.saturating_sub(2).max(1);
+        let (column, row) = self.preview_cursor.unwrap_or((width / 2, height / 2));
+        let clamp = |value: u16, delta: i32, size: u16| {
+            (value as i32 + delta).clamp(0, size as i32 - 1) as u16
+        };
+        self.preview_cursor = Some((clamp(column, dx, width), clamp(row, dy, height)));
+    }
+
+    /// Moves the preview by the given fractions of its width and height.
+    pub fn pan_preview(&mut self, dx: f64, dy: f64) {
+        let [min_lon, min_lat, max_lon, max_lat] = self.preview_bounds;
+        let (x, y) = (dx * (max_lon - min_lon), dy * (max_lat - min_lat));
+        self.preview_view = Some([min_lon + x, min_lat + y, max_lon + x, max_lat + y]);
+    }
+
+    /// Centers the preview on `lon`/`lat`, zooming in until it is at most
+    /// `max_span` degrees across (keeping its proportions).
+    pub fn center_preview(&mut self, lon: f64, lat: f64, max_span: f64) {
+        let [min_lon, min_lat, max_lon, max_lat] = self.preview_bounds;
+        let (width, height) = (max_lon - min_lon, max_lat - min_lat);
+        let scale = (max_span / width.max(height)).min(1.0);
+        let (half_width, half_height) = (width * scale / 2.0, height * scale / 2.0);
+        self.preview_view = Some([
+            lon - half_width,
+            lat - half_height,
+            lon + half_width,
+            lat + half_height,
+        ]);
+    }
+
+    /// Scales the preview around its center; factors below 1 zoom in.
+    pub fn zoom_preview(&mut self, factor: f64) {
+        let [min_lon, min_lat, max_lon, max_lat] = self.preview_bounds;
+        let (center_lon, center_lat) = ((min_lon + max_lon) / 2.0, (min_lat + max_lat) / 2.0);
+        // Viewports stay between a few metres and the whole world across
+        let half_width = ((max_lon - min_lon) * factor / 2.0).clamp(1e-5, 180.0);
+        let half_height = ((max_lat - min_lat) * factor / 2.0).clamp(1e-5, 90.0);
+        self.preview_view = Some([
+            center_lon - half_width,
+            center_lat - half_height,
+            center_lon + half_width,
+            center_lat + half_height,
+        ]);
+    }
+
+    // The value of `property` of `original_index`
+    fn layer_value(&mut self, original_index: usize, property: LayerProperty) -> &mut Option<u32> {
+        let layer = &mut self.layers[original_index];
+        match property {
+            LayerProperty::LineWidth => &mut layer.line_width,
+            LayerProperty::PointSize => &mut layer.point_size,
+            LayerProperty::Opacity => &mut layer.opacity,
+            _ => unreachable!("choices and geometry types are not numbers"),
+        }
+    }
+
+    // The drawn types of `original_index` with the one of a toggle property set
+    fn set_layer_geometry_type(
+        &mut self,
+        original_index: usize,
+        property: LayerProperty,
+        shown: bool,
+    ) {
+        let mut types = self.layers[original_index]
+            .geometry_types
+            .unwrap_or(GeometryTypes::ALL);
+        match property {
+            LayerProperty::Points => types.points = shown,
+            LayerProperty::Lines => types.lines = shown,
+            LayerProperty::Polygons => types.polygons = shown,
+            _ => return,
+        }
+        self.layers[original_index].geometry_types = (types != GeometryTypes::ALL).then_some(types);
+    }
+
+    // Sets a choice property of `original_index` to its `index`th value; the
+    // first is the default
+    fn set_layer_choice(&mut self, original_index: usize, property: LayerProperty, index: usize) {
+        match property {
+            LayerProperty::LineStyle => {
+                self.layers[original_index].line_style = (index > 0).then(|| LineStyle::ALL[index]);
+            }
+            LayerProperty::PointShape => {
+                self.layers[original_index].point_shape =
+                    (index > 0).then(|| PointShape::ALL[index]);
+            }
+            _ => {}
+        }
+    }
+
+    /// Geometry types drawn from `original_index`: those of the P/L/O toggles
+    /// that the layer does not hide.
+    pub fn layer_drawn_types(&self, original_index: usize) -> GeometryTypes {
+        let global = GeometryTypes {
+            points: self.plot_points,
+            lines: self.plot_lines,
+            polygons: self.plot_polygons,
+        };
+        global.and(
+            self.layers[original_index]
+                .geometry_types
+                .unwrap_or(GeometryTypes::ALL),
+        )
+    }
+
+    /// Value of `property` for `original_index`, or `None` if it uses the
+    /// default. Hidden geometry types are `Some(0)`.
+    pub fn layer_property_value(
+        &self,
+        original_index: usize,
+        property: LayerProperty,
+    ) -> Option<u32> {
+        let types = self.layers[original_index]
+            .geometry_types
+            .unwrap_or(GeometryTypes::ALL);
+        let hidden = |shown: bool| (!shown).then_some(0);
+        match property {
+            LayerProperty::LineWidth => self.layers[original_index].line_width,
+            LayerProperty::LineStyle => self.layers[original_index]
+                .line_style
+                .and_then(|style| LineStyle::ALL.iter().position(|&s| s == style))
+                .map(|index| index as u32),
+            LayerProperty::PointSize => self.layers[original_index].point_size,
+            LayerProperty::PointShape => self.layers[original_index]
+                .point_shape
+                .and_then(|shape| PointShape::ALL.iter().position(|&s| s == shape))
+                .map(|index| index as u32),
+            LayerProperty::Opacity => self.layers[original_index].opacity,
+            LayerProperty::Points => hidden(types.points),
+            LayerProperty::Lines => hidden(types.lines),
+            LayerProperty::Polygons => hidden(types.polygons),
+        }
+    }
+
+    /// Changes the selected property of `original_index` by `steps` steps;
+    /// values equal to the default are stored as `None`. Toggles flip
+    /// whichever way they are stepped and choices go round.
+    pub fn adjust_layer_property(&mut self, original_index: usize, steps: i32) {
+        let property = self.layer_property;
+        if property.is_toggle() {
+            let shown = self
+                .layer_property_value(original_index, property)
+                .is_some();
+            self.set_layer_geometry_type(original_index, property, shown);
+            return;
+        }
+        if property.is_choice() {
+            let (_, last) = property.range();
+            let current = self
+                .layer_property_value(original_index, property)
+                .unwrap_or(0) as i32;
+            let index = (current + steps).rem_euclid(last as i32 + 1) as usize;
+            self.set_layer_choice(original_index, property, index);
+            return;
+        }
+        let default = property.default_value();
+        let (min, max) = property.range();
+        let value = self.layer_value(original_index, property);
+        let stepped = value
+            .unwrap_or(default)
+            .saturating_add_signed(steps * property.step() as i32)
+            .clamp(min, max);
+        *value = (stepped != default).then_some(stepped);
+    }
+
+    /// Restores the default of the selected property of `original_index`.
+    pub fn reset_layer_property(&mut self, original_index: usize) {
+        let property = self.layer_property;
+        if property.is_toggle() {
+            self.set_layer_geometry_type(original_index, property, true);
+        } else if property.is_choice() {
+            self.set_layer_choice(original_index, property, 0);
+        } else {
+            *self.layer_value(original_index, property) = None;
+        }
+    }
+
+    /// Cycles `original_index` through clip mask, shaded clip mask and no mask.
+    pub fn cycle_clip_mask(&mut self, original_index: usize) {
+        match (
+            self.clip_mask_index == Some(original_index),
+            self.clip_mask_shade,
+        ) {
+            (false, _) => {
+                self.clip_mask_index = Some(original_index);
+                self.clip_mask_shade = false;
+            }
+            (true, false) => self.clip_mask_shade = true,
+            (true, true) => {
+                self.clip_mask_index = None;
+                self.clip_mask_shade = false;
+            }
+        }
+    }
+
+    /// Locks the plot to the current preview viewport, or unlocks it.
+    pub fn toggle_extent_lock(&mut self) {
+        self.extent_locked = !self.extent_locked;
+        if self.extent_locked {
+            // Freezes the viewport so newly loaded layers no longer move it
+            self.preview_view = Some(self.preview_bounds);
+        }
+    }
+
+    /// Moves the divider between the file list and the preview to `percent`
+    /// of the terminal width, within a range that keeps both panes usable.
+    pub fn set_left_pane_width(&mut self, percent: i32) {
+        self.left_pane_width_percentage =
+            percent.clamp(LEFT_PANE_MIN_PERCENT as i32, LEFT_PANE_MAX_PERCENT as i32) as u16;
+    }
+
+    /// Number of file rows that fit in the file list.
+    pub fn visible_file_rows(&self) -> usize {
+        self.file_list_area.height.saturating_sub(2) as usize // Minus the borders
+    }
+
+    /// Scrolls the file list by `delta` rows, keeping the highlight on screen.
+    pub fn scroll_file_list(&mut self, delta: isize) {
+        let visible = self.visible_file_rows();
+        let max_offset = self.filtered_geojson_indices.len().saturating_sub(visible);
+        self.scroll_offset = self
+            .scroll_offset
+            .saturating_add_signed(delta)
+            .min(max_offset);
+        if visible > 0 {
+            self.selected_file_index = self
+                .selected_file_index
+                .clamp(self.scroll_offset, self.scroll_offset + visible - 1)
+                .min(self.filtered_geojson_indices.len().saturating_sub(1));
+        }
+    }
+
+    /// Simplified copies of the loaded features of `index`, shared by its
+    /// plots; started afresh when the features changed since they were built.
+    pub fn levels_of_detail(&mut self, index: usize) -> Option<Arc<LevelsOfDetail>> {
+        let layer = &mut self.layers[index];
+        let features = layer.features.as_ref()?;
+        let levels = layer.levels.get_or_insert_with(Arc::default);
+        if !levels.fits(features) {
+            *levels = Arc::default();
+        }
+        Some(Arc::clone(levels))
+    }
+
+    /// Brings the spatial index of every loaded layer up to date: built when a
+    /// layer is loaded, extended by appended features and dropped with them.
+    pub fn update_feature_indexes(&mut self) {
+        for layer in &mut self.layers {
+            match (&layer.features, layer.feature_index.as_mut()) {
+                (None, _) => layer.feature_index = None,
+                (Some(features), Some(existing)) if existing.feature_count() <= features.len() => {
+                    existing.extend(features)
+                }
+                (Some(features), _) => layer.feature_index = Some(FeatureIndex::new(features)),
+            }
+        }
+    }
+
+    /// Drops parsed features that are neither selected nor highlighted.
+    pub fn release_unused_features(&mut self) {
+        let highlighted = self.highlighted_file_index();
+        let followed = self.follow.as_ref().map(|f| f.file_index);
+        let tabulated = self.attribute_table.as_ref().map(|t| t.file_index);
+        for (i, layer) in self.layers.iter_mut().enumerate() {
+            if !layer.selected
+                && Some(i) != highlighted
+                && Some(i) != followed
+                && Some(i) != tabulated
+                && Some(i) != self.clip_mask_index
+            {
+                layer.features = None;
+                layer.levels = None;
+            }
+        }
+    }
+
+    /// Extent covering every loaded layer, the markers and the GPS track, used for the preview.
+    pub fn preview_extent(&self) -> Option<[f64; 4]> {
+        let mut bbox = plot::empty_bbox();
+        for layer in &self.layers {
+            if layer.features.is_none() {
+                continue;
+            }
+            if let Some(info_bbox) = layer.info.as_ref().and_then(|info| info.bbox) {
+                bbox[0] = bbox[0].min(info_bbox[0]);
+                bbox[1] = bbox[1].min(info_bbox[1]);
+                bbox[2] = bbox[2].max(info_bbox[2]);
+                bbox[3] = bbox[3].max(info_bbox[3]);
+            }
+        }
+        let points = self.markers.iter().map(|m| (m.lon, m.lat)).chain(
+            self.gps_track
+                .iter()
+                .chain(self.gps_position.as_ref())
+                .map(|fix| (fix.lon, fix.lat)),
+        );
+        for (lon, lat) in points {
+            bbox[0] = bbox[0].min(lon);
+            bbox[1] = bbox[1].min(lat);
+            bbox[2] = bbox[2].max(lon);
+            bbox[3] = bbox[3].max(lat);
+        }
+        (bbox[0] != f64::MAX).then_some(bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listing_a_file_again_forgets_what_was_read() {
+        let mut app = App::new();
+        app.setup_geojson_data(vec![String::from("a.geojson"), String::from("b.geojson")]);
+        app.layers[1].selected = true;
+        app.layers[1].features = Some(Vec::new());
+        app.layers[1].feature_index = Some(FeatureIndex::new(&[]));
+        app.layers[1].info = Some(GeoJsonInfo::default());
+
+        assert_eq!(app.add_geojson_file(String::from("b.geojson")), 1);
+        let layer = &app.layers[1];
+        assert!(layer.features.is_none() && layer.feature_index.is_none() && layer.info.is_none());
+        assert!(layer.selected, "the selection is kept");
+    }
+
+    #[test]
+    fn removing_a_file_shifts_the_ones_after_it() {
+        let mut app = App::new();
+        app.setup_geojson_data(vec![String::from("a.geojson"), String::from("b.geojson")]);
+        let c = app.add_geojson_file(String::from("c.geojson"));
+        app.layers[c].selected = true;
+        app.focus_index = Some(c);
+        app.remove_geojson_file(0);
+        assert_eq!(app.file_index("c.geojson"), Some(1));
+        assert_eq!(app.focus_index, Some(1));
+        assert_eq!(app.selected_count(), 1);
+        assert_eq!(app.filtered_geojson_indices, vec![0, 1]);
+    }
 }
@@ -0,0 +1,202 @@
+// basemap.rs
+
+use plotters::prelude::RGBColor;
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use crate::config::Config;
+use crate::remote;
+
+pub const DEFAULT_TILE_URL: &str = "https://tile.openstreetmap.org/{z}/{x}/{y}.png";
+pub const DEFAULT_TILE_CACHE: &str = "tiles/";
+const OSM_ATTRIBUTION: &str = "© OpenStreetMap contributors";
+const TILE_SIZE: u32 = 256;
+const TILE_TIMEOUT: u32 = 20; // Seconds
+const MAX_ZOOM: u32 = 19;
+const MAX_TILES: usize = 64; // Zoom is lowered until the extent needs at most this many tiles
+const MAX_LATITUDE: f64 = 85.051_128_78; // Web Mercator cut-off
+
+/// XYZ raster tile source drawn underneath the vector layers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Basemap {
+    pub url: String,         // Template with `{z}`, `{x}` and `{y}` placeholders
+    pub cache_dir: PathBuf,  // Fetched tiles are kept here and reused
+    pub attribution: String, // Shown when no attribution is set for the plot
+}
+
+impl Default for Basemap {
+    fn default() -> Self {
+        Basemap {
+            url: String::from(DEFAULT_TILE_URL),
+            cache_dir: PathBuf::from(DEFAULT_TILE_CACHE),
+            attribution: String::from(OSM_ATTRIBUTION),
+        }
+    }
+}
+
+/// Basemap pixels for a plot, with the tiles that could not be loaded.
+pub struct BasemapImage {
+    pub pixels: Vec<Option<RGBColor>>, // Row-major; `None` outside the tile coverage
+    pub missing_tiles: usize,
+    pub first_error: Option<String>,
+}
+
+impl Basemap {
+    /// Reads the `[basemap]` section: `url`, `cache_dir` and `attribution`.
+    /// A custom `url` has no attribution unless one is given.
+    pub fn from_config(config: &Config) -> Result<Basemap, String> {
+        let setting = |key: &str| config.get("basemap", key).and_then(|v| v.as_str());
+        let mut basemap = Basemap::default();
+        if let Some(url) = setting("url") {
+            if !(url.contains("{z}") && url.contains("{x}") && url.contains("{y}")) {
+                return Err(format!(
+                    "[basemap] url '{}' needs {{z}}, {{x}} and {{y}} placeholders",
+                    url
+                ));
+            }
+            basemap.url = url.to_string();
+            basemap.attribution = String::new();
+        }
+        if let Some(dir) = setting("cache_dir") {
+            basemap.cache_dir = PathBuf::from(dir);
+        }
+        if let Some(attribution) = setting("attribution") {
+            basemap.attribution = attribution.to_string();
+        }
+        Ok(basemap)
+    }
+
+    /// Renders the tiles covering `extent` (in lon/lat) into `width` x `height`
+    /// pixels, reprojecting them from Web Mercator.
+    pub fn render(&self, extent: [f64; 4], width: u32, height: u32) -> BasemapImage {
+        let zoom = tile_zoom(extent, width);
+        let tiles_across = 1u32 << zoom;
+        let scale = (tiles_across * TILE_SIZE) as f64;
+
+        // Position in zoom-level pixels of every output column and row
+        let columns: Vec<f64> = (0..width)
+            .map(|px| {
+                let lon = extent[0] + (extent[2] - extent[0]) * (px as f64 + 0.5) / width as f64;
+                (lon + 180.0) / 360.0 * scale
+            })
+            .collect();
+        let rows: Vec<Option<f64>> = (0..height)
+            .map(|py| {
+                let lat = extent[3] - (extent[3] - extent[1]) * (py as f64 + 0.5) / height as f64;
+                (lat.abs() <= MAX_LATITUDE).then(|| mercator_y(lat) * scale)
+            })
+            .collect();
+
+        let mut tiles: HashMap<(u32, u32), Option<image::RgbImage>> = HashMap::new();
+        let mut image = BasemapImage {
+            pixels: Vec::with_capacity(width as usize * height as usize),
+            missing_tiles: 0,
+            first_error: None,
+        };
+        for row in &rows {
+            for &column in &columns {
+                let Some(row) = *row else {
+                    image.pixels.push(None);
+                    continue;
+                };
+                let (x, y) = (column.floor() as i64, row.floor() as i64);
+                // Longitudes beyond ±180 wrap around
+                let tile_x =
+                    (x.div_euclid(TILE_SIZE as i64)).rem_euclid(tiles_across as i64) as u32;
+                let tile_y = ((y / TILE_SIZE as i64) as u32).min(tiles_across - 1);
+                let tile = tiles.entry((tile_x, tile_y)).or_insert_with(|| {
+                    match self.load_tile(zoom, tile_x, tile_y) {
+                        Ok(tile) => Some(tile),
+                        Err(e) => {
+                            image.missing_tiles += 1;
+                            image.first_error.get_or_insert(e);
+                            None
+                        }
+                    }
+                });
+                image.pixels.push(tile.as_ref().map(|tile| {
+                    let pixel = tile.get_pixel(
+                        (x.rem_euclid(TILE_SIZE as i64) as u32).min(tile.width() - 1),
+                        (y.rem_euclid(TILE_SIZE as i64) as u32).min(tile.height() - 1),
+                    );
+                    RGBColor(pixel[0], pixel[1], pixel[2])
+                }));
+            }
+        }
+        image
+    }
+
+    // Reads a tile from the cache, fetching it first if needed
+    fn load_tile(&self, zoom: u32, x: u32, y: u32) -> Result<image::RgbImage, String> {
+        let path = self
+            .cache_dir
+            .join(cache_name(&self.url))
+            .join(zoom.to_string())
+            .join(x.to_string())
+            .join(format!("{}.tile", y));
+        if !path.exists() {
+            let url = self
+                .url
+                .replace("{z}", &zoom.to_string())
+                .replace("{x}", &x.to_string())
+                .replace("{y}", &y.to_string());
+            remote::download(&url, &path, TILE_TIMEOUT)?;
+        }
+        let bytes = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let tile = image::load_from_memory(&bytes).map_err(|e| {
+            // A corrupt download would otherwise be reused forever
+            let _ = fs::remove_file(&path);
+            format!("tile {}/{}/{}: {}", zoom, x, y, e)
+        })?;
+        Ok(tile.to_rgb8())
+    }
+}
+
+// Highest zoom at which a tile pixel is no larger than an output pixel,
+// lowered until the extent fits into `MAX_TILES` tiles
+fn tile_zoom(extent: [f64; 4], width: u32) -> u32 {
+    let lon_span = (extent[2] - extent[0]).clamp(1e-9, 360.0);
+    let wanted = (width as f64 * 360.0 / (TILE_SIZE as f64 * lon_span))
+        .log2()
+        .ceil();
+    let mut zoom = wanted.clamp(0.0, MAX_ZOOM as f64) as u32;
+    while zoom > 0 && tile_count(extent, zoom) > MAX_TILES {
+        zoom -= 1;
+    }
+    zoom
+}
+
+fn tile_count(extent: [f64; 4], zoom: u32) -> usize {
+    let tiles_across = (1u64 << zoom) as f64;
+    let columns = ((extent[2] + 180.0) / 360.0 * tiles_across).floor()
+        - ((extent[0] + 180.0) / 360.0 * tiles_across).floor()
+        + 1.0;
+    let lat = |lat: f64| mercator_y(lat.clamp(-MAX_LATITUDE, MAX_LATITUDE)) * tiles_across;
+    let rows = lat(extent[1]).min(tiles_across - 1.0).floor() - lat(extent[3]).floor() + 1.0;
+    (columns.min(tiles_across) * rows) as usize
+}
+
+// Web Mercator y in [0, 1], from the top
+fn mercator_y(lat: f64) -> f64 {
+    let lat = lat.to_radians();
+    (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / std::f64::consts::PI) / 2.0
+}
+
+// Cache directory for a tile source, so different servers never share tiles
+fn cache_name(url: &str) -> String {
+    let host = url
+        .split("://")
+        .nth(1)
+        .unwrap_or(url)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    host.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
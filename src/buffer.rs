@@ -0,0 +1,493 @@
+// buffer.rs
+
+use geojson::{Feature, Geometry, Value};
+use std::collections::HashMap;
+
+use crate::clip::{self, Point};
+use crate::simplify::simplify_line;
+
+// Length of a degree of latitude, and of longitude at the equator, in metres
+const METRES_PER_DEGREE: f64 = 111_320.0;
+
+// Grid nodes along the longest side of a feature; bounds time and memory of a
+// single feature regardless of how small the distance is
+const MAX_CELLS: usize = 1024;
+
+// Cells per buffer distance when the feature is small enough
+const CELLS_PER_DISTANCE: f64 = 8.0;
+
+/// Buffer distance typed by the user.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BufferDistance {
+    Degrees(f64),
+    Metres(f64),
+}
+
+impl BufferDistance {
+    /// Parses a plain number as degrees, or a number followed by `m` or `km` as
+    /// metres, e.g. `0.5`, `250m`, `10 km`. Negative distances shrink polygons.
+    pub fn parse(text: &str) -> Result<BufferDistance, String> {
+        let compact = text.trim().to_lowercase().replace(' ', "");
+        let (number, scale, metres) = if let Some(number) = compact.strip_suffix("km") {
+            (number, 1000.0, true)
+        } else if let Some(number) = compact.strip_suffix('m') {
+            (number, 1.0, true)
+        } else {
+            let number = compact
+                .strip_suffix("deg")
+                .or(compact.strip_suffix('°'))
+                .unwrap_or(&compact);
+            (number, 1.0, false)
+        };
+        match number.parse::<f64>() {
+            Ok(value) if value.is_finite() && value != 0.0 => Ok(if metres {
+                BufferDistance::Metres(value * scale)
+            } else {
+                BufferDistance::Degrees(value)
+            }),
+            _ => Err(format!(
+                "'{}' is not a distance like 0.5 (degrees), 250m or 10km",
+                text.trim()
+            )),
+        }
+    }
+
+    /// Short form used in file names, e.g. `250m` or `0.5deg`.
+    pub fn label(&self) -> String {
+        match self {
+            BufferDistance::Degrees(degrees) => format!("{}deg", degrees),
+            BufferDistance::Metres(metres) => format!("{}m", metres),
+        }
+    }
+}
+
+/// Buffers the geometry of every feature by `distance`: points and lines grow
+/// into polygons, polygons grow or, with a negative distance, shrink.
+/// Overlapping parts of one feature are merged. Metres are converted with a
+/// local equirectangular approximation around the middle latitude of each
+/// feature, which stays within a few percent for features smaller than a
+/// country. Features left without area are removed; properties are kept.
+pub fn buffer_features(features: &[Feature], distance: BufferDistance) -> Vec<Feature> {
+    features
+        .iter()
+        .filter_map(|feature| {
+            let geometry = feature.geometry.as_ref()?;
+            let value = buffer_value(&geometry.value, distance)?;
+            Some(Feature {
+                bbox: None,
+                geometry: Some(Geometry::new(value)),
+                ..feature.clone()
+            })
+        })
+        .collect()
+}
+
+// Parts of a geometry the distance is measured to
+#[derive(Default)]
+struct Parts {
+    segments: Vec<(Point, Point)>, // Points are segments of length zero
+    rings: Vec<Vec<Point>>,        // Polygon rings whose inside is filled
+}
+
+impl Parts {
+    fn add(&mut self, value: &Value) {
+        let position = |p: &Vec<f64>| [p[0], p[1]];
+        let line = |parts: &mut Parts, line: &[Vec<f64>]| {
+            let line: Vec<Point> = line.iter().filter(|p| p.len() >= 2).map(position).collect();
+            parts
+                .segments
+                .extend(line.windows(2).map(|pair| (pair[0], pair[1])));
+            if line.len() == 1 {
+                parts.segments.push((line[0], line[0]));
+            }
+            line
+        };
+        match value {
+            Value::Point(p) if p.len() >= 2 => self.segments.push((position(p), position(p))),
+            Value::Point(_) => {}
+            Value::MultiPoint(points) => {
+                for p in points.iter().filter(|p| p.len() >= 2) {
+                    self.segments.push((position(p), position(p)));
+                }
+            }
+            Value::LineString(points) => {
+                line(self, points);
+            }
+            Value::MultiLineString(lines) => {
+                for points in lines {
+                    line(self, points);
+                }
+            }
+            Value::Polygon(rings) => {
+                for ring in rings {
+                    let ring = line(self, ring);
+                    self.rings.push(ring);
+                }
+            }
+            Value::MultiPolygon(polygons) => {
+                for ring in polygons.iter().flatten() {
+                    let ring = line(self, ring);
+                    self.rings.push(ring);
+                }
+            }
+            Value::GeometryCollection(geometries) => {
+                for geometry in geometries {
+                    self.add(&geometry.value);
+                }
+            }
+        }
+    }
+
+    fn bbox(&self) -> Option<[f64; 4]> {
+        let mut points = self.segments.iter().flat_map(|(a, b)| [a, b]);
+        let first = points.next()?;
+        Some(
+            points.fold([first[0], first[1], first[0], first[1]], |b, p| {
+                [
+                    b[0].min(p[0]),
+                    b[1].min(p[1]),
+                    b[2].max(p[0]),
+                    b[3].max(p[1]),
+                ]
+            }),
+        )
+    }
+
+    // Scales every x by `k`
+    fn stretch(&mut self, k: f64) {
+        for (a, b) in &mut self.segments {
+            a[0] *= k;
+            b[0] *= k;
+        }
+        for p in self.rings.iter_mut().flatten() {
+            p[0] *= k;
+        }
+    }
+}
+
+// Buffers one geometry: builds a distance field around it on a grid (negative
+// inside polygons) and traces the contour at the buffer distance
+fn buffer_value(value: &Value, distance: BufferDistance) -> Option<Value> {
+    let mut parts = Parts::default();
+    parts.add(value);
+    let bbox = parts.bbox()?;
+    // Metres are measured in a frame where a degree of longitude is as long as
+    // one of latitude at the middle of the feature
+    let (d, k) = match distance {
+        BufferDistance::Degrees(degrees) => (degrees, 1.0),
+        BufferDistance::Metres(metres) => (
+            metres / METRES_PER_DEGREE,
+            ((bbox[1] + bbox[3]) / 2.0).to_radians().cos().max(0.01),
+        ),
+    };
+    if d < 0.0 && parts.rings.is_empty() {
+        return None;
+    }
+    parts.stretch(k);
+    let [min_x, min_y, max_x, max_y] = [bbox[0] * k, bbox[1], bbox[2] * k, bbox[3]];
+
+    let grow = d.max(0.0);
+    let longest = (max_x - min_x).max(max_y - min_y) + 2.0 * grow;
+    let cell = (d.abs() / CELLS_PER_DISTANCE).max(longest / (MAX_CELLS - 4) as f64);
+    // Two cells of margin keep the border nodes outside, so every contour closes
+    let origin = [min_x - grow - 2.0 * cell, min_y - grow - 2.0 * cell];
+    let nx = ((max_x - min_x + 2.0 * grow) / cell).ceil() as usize + 5;
+    let ny = ((max_y - min_y + 2.0 * grow) / cell).ceil() as usize + 5;
+    let node = |i: usize, j: usize| [origin[0] + i as f64 * cell, origin[1] + j as f64 * cell];
+
+    // Distances beyond `reach` do not matter for the contour
+    let reach = d.abs() + 2.0 * cell;
+    let mut field = vec![reach; nx * ny];
+    fill_rings(&parts.rings, &mut field, nx, ny, &node, -reach);
+    for &(a, b) in &parts.segments {
+        let span = |lo: f64, hi: f64, origin: f64, n: usize| {
+            let first = ((lo - reach - origin) / cell).floor().max(0.0) as usize;
+            let last = (((hi + reach - origin) / cell).ceil().max(0.0) as usize).min(n - 1);
+            first..=last
+        };
+        for j in span(a[1].min(b[1]), a[1].max(b[1]), origin[1], ny) {
+            for i in span(a[0].min(b[0]), a[0].max(b[0]), origin[0], nx) {
+                let value = &mut field[j * nx + i];
+                let distance = segment_distance(node(i, j), a, b);
+                if distance < value.abs() {
+                    *value = distance.copysign(*value);
+                }
+            }
+        }
+    }
+
+    let rings: Vec<Vec<Point>> = contours(&field, nx, ny, d, &node)
+        .into_iter()
+        .map(|ring| {
+            let ring: Vec<Vec<f64>> = ring.iter().map(|p| vec![p[0] / k, p[1]]).collect();
+            // Drops the points of straight runs the grid put along the contour
+            simplify_line(&ring, cell * 0.05)
+                .into_iter()
+                .map(|p| [p[0], p[1]])
+                .collect::<Vec<Point>>()
+        })
+        .filter(|ring| ring.len() >= 3)
+        .collect();
+    let mut polygons = clip::polygons_from_rings(rings);
+    match polygons.len() {
+        0 => None,
+        1 => polygons.pop().map(Value::Polygon),
+        _ => Some(Value::MultiPolygon(polygons)),
+    }
+}
+
+// Sets the nodes inside `rings` (even-odd) to `inside`, row by row
+fn fill_rings(
+    rings: &[Vec<Point>],
+    field: &mut [f64],
+    nx: usize,
+    ny: usize,
+    node: &dyn Fn(usize, usize) -> Point,
+    inside: f64,
+) {
+    if rings.is_empty() {
+        return;
+    }
+    let mut crossings = Vec::new();
+    for j in 0..ny {
+        let [x0, y] = node(0, j);
+        let cell = node(1, j)[0] - x0;
+        crossings.clear();
+        for ring in rings {
+            for (i, a) in ring.iter().enumerate() {
+                let b = ring[(i + 1) % ring.len()];
+                if (a[1] > y) != (b[1] > y) {
+                    crossings.push(a[0] + (y - a[1]) / (b[1] - a[1]) * (b[0] - a[0]));
+                }
+            }
+        }
+        crossings.sort_by(|a, b| a.total_cmp(b));
+        for pair in crossings.chunks_exact(2) {
+            let first = ((pair[0] - x0) / cell).ceil().max(0.0) as usize;
+            let last = ((pair[1] - x0) / cell).floor().min((nx - 1) as f64);
+            if last < 0.0 {
+                continue;
+            }
+            for value in &mut field[j * nx + first.min(nx)..=j * nx + last as usize] {
+                *value = inside;
+            }
+        }
+    }
+}
+
+// Crossing of the contour with a grid edge: the edge from node (i, j) to the
+// right (horizontal) or upwards
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct EdgeKey(bool, usize, usize);
+
+// Marching squares: traces the rings where `field` equals `level`, with nodes
+// below `level` inside
+fn contours(
+    field: &[f64],
+    nx: usize,
+    ny: usize,
+    level: f64,
+    node: &dyn Fn(usize, usize) -> Point,
+) -> Vec<Vec<Point>> {
+    let value = |i: usize, j: usize| field[j * nx + i];
+    let point = |EdgeKey(horizontal, i, j): EdgeKey| {
+        let (a, b) = if horizontal {
+            ((i, j), (i + 1, j))
+        } else {
+            ((i, j), (i, j + 1))
+        };
+        let (va, vb) = (value(a.0, a.1), value(b.0, b.1));
+        let t = ((level - va) / (vb - va)).clamp(0.0, 1.0);
+        let (pa, pb) = (node(a.0, a.1), node(b.0, b.1));
+        [pa[0] + (pb[0] - pa[0]) * t, pa[1] + (pb[1] - pa[1]) * t]
+    };
+
+    let mut segments: Vec<[EdgeKey; 2]> = Vec::new();
+    for j in 0..ny - 1 {
+        for i in 0..nx - 1 {
+            let corners = [
+                value(i, j),
+                value(i + 1, j),
+                value(i + 1, j + 1),
+                value(i, j + 1),
+            ];
+            let case = corners
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| **v < level)
+                .fold(0, |case, (bit, _)| case | 1 << bit);
+            let bottom = EdgeKey(true, i, j);
+            let right = EdgeKey(false, i + 1, j);
+            let top = EdgeKey(true, i, j + 1);
+            let left = EdgeKey(false, i, j);
+            // Saddles are decided by the value at the centre of the cell
+            let centre_inside = corners.iter().sum::<f64>() / 4.0 < level;
+            let cell_segments: &[[EdgeKey; 2]] = match case {
+                1 | 14 => &[[left, bottom]],
+                2 | 13 => &[[bottom, right]],
+                3 | 12 => &[[left, right]],
+                4 | 11 => &[[right, top]],
+                6 | 9 => &[[bottom, top]],
+                7 | 8 => &[[left, top]],
+                5 if centre_inside => &[[bottom, right], [top, left]],
+                5 => &[[left, bottom], [right, top]],
+                10 if centre_inside => &[[left, bottom], [right, top]],
+                10 => &[[bottom, right], [top, left]],
+                _ => &[],
+            };
+            segments.extend_from_slice(cell_segments);
+        }
+    }
+
+    // Every crossing is shared by exactly two segments, so following them
+    // from crossing to crossing closes each ring
+    let mut at: HashMap<EdgeKey, Vec<usize>> = HashMap::new();
+    for (index, segment) in segments.iter().enumerate() {
+        for key in segment {
+            at.entry(*key).or_default().push(index);
+        }
+    }
+    let mut used = vec![false; segments.len()];
+    let mut rings = Vec::new();
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let mut ring = vec![point(segments[start][0])];
+        let (mut segment, mut key) = (start, segments[start][1]);
+        while let Some(&next) = at[&key].iter().find(|&&other| other != segment) {
+            ring.push(point(key));
+            if used[next] {
+                break;
+            }
+            used[next] = true;
+            key = if segments[next][0] == key {
+                segments[next][1]
+            } else {
+                segments[next][0]
+            };
+            segment = next;
+        }
+        if ring.len() > 1 && ring.first() == ring.last() {
+            ring.pop();
+        }
+        rings.push(ring);
+    }
+    rings
+}
+
+fn segment_distance(p: Point, a: Point, b: Point) -> f64 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    (p[0] - a[0] - t * dx).hypot(p[1] - a[1] - t * dy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot;
+
+    fn buffered(value: Value, distance: &str) -> Option<Value> {
+        let feature = Feature::from(Geometry::new(value));
+        let distance = BufferDistance::parse(distance).unwrap();
+        let buffered = buffer_features(&[feature], distance);
+        buffered.into_iter().next()?.geometry.map(|g| g.value)
+    }
+
+    fn bbox(value: &Value) -> [f64; 4] {
+        let mut bbox = plot::empty_bbox();
+        plot::extend_bbox(&mut bbox, value);
+        bbox
+    }
+
+    // Area inside the outer rings
+    fn outer_area(value: &Value) -> f64 {
+        let ring_area = |ring: &Vec<Vec<f64>>| {
+            let ring: Vec<Point> = ring.iter().map(|p| [p[0], p[1]]).collect();
+            clip::area(&ring).abs()
+        };
+        match value {
+            Value::Polygon(rings) => ring_area(&rings[0]),
+            Value::MultiPolygon(polygons) => {
+                polygons.iter().map(|rings| ring_area(&rings[0])).sum()
+            }
+            _ => 0.0,
+        }
+    }
+
+    #[test]
+    fn distances_are_read_in_degrees_or_metres() {
+        assert_eq!(
+            BufferDistance::parse("0.5"),
+            Ok(BufferDistance::Degrees(0.5))
+        );
+        assert_eq!(
+            BufferDistance::parse("-2°"),
+            Ok(BufferDistance::Degrees(-2.0))
+        );
+        assert_eq!(
+            BufferDistance::parse(" 10 KM"),
+            Ok(BufferDistance::Metres(10_000.0))
+        );
+        assert_eq!(BufferDistance::parse("250m").unwrap().label(), "250m");
+        assert!(BufferDistance::parse("0").is_err());
+        assert!(BufferDistance::parse("far").is_err());
+    }
+
+    #[test]
+    fn points_grow_into_discs() {
+        let disc = buffered(Value::Point(vec![10.0, 0.0]), "1").unwrap();
+        let [min_x, min_y, max_x, max_y] = bbox(&disc);
+        for (found, expected) in [(min_x, 9.0), (min_y, -1.0), (max_x, 11.0), (max_y, 1.0)] {
+            assert!(
+                (found - expected).abs() < 0.05,
+                "{} is not near {}",
+                found,
+                expected
+            );
+        }
+        let area = outer_area(&disc);
+        assert!(
+            (area - std::f64::consts::PI).abs() < 0.05 * std::f64::consts::PI,
+            "area {}",
+            area
+        );
+    }
+
+    #[test]
+    fn metres_are_measured_along_the_latitude() {
+        // At 60° a degree of longitude is half as long as one of latitude
+        let disc = buffered(Value::Point(vec![0.0, 60.0]), "111.32km").unwrap();
+        let [min_x, min_y, max_x, max_y] = bbox(&disc);
+        assert!(((max_x - min_x) / (max_y - min_y) - 2.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn polygons_shrink_until_they_vanish() {
+        let square = Value::Polygon(vec![vec![
+            vec![0.0, 0.0],
+            vec![4.0, 0.0],
+            vec![4.0, 4.0],
+            vec![0.0, 4.0],
+            vec![0.0, 0.0],
+        ]]);
+        let shrunk = buffered(square.clone(), "-1").unwrap();
+        let [min_x, min_y, max_x, max_y] = bbox(&shrunk);
+        assert!((min_x - 1.0).abs() < 0.1 && (max_x - 3.0).abs() < 0.1);
+        assert!((min_y - 1.0).abs() < 0.1 && (max_y - 3.0).abs() < 0.1);
+        assert!(buffered(square, "-3").is_none());
+        assert!(
+            buffered(
+                Value::LineString(vec![vec![0.0, 0.0], vec![1.0, 0.0]]),
+                "-1"
+            )
+            .is_none()
+        );
+    }
+}
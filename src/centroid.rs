@@ -0,0 +1,253 @@
+// centroid.rs
+
+use geojson::{Feature, Geometry, Value};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::clip::{self, Point};
+
+// The pole is searched down to this fraction of the longer side of the feature
+const POLE_PRECISION: f64 = 1e-3;
+
+// Cells probed per feature before the best point found so far is taken
+const MAX_POLE_CELLS: usize = 10_000;
+
+/// Point computed for every polygon feature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointKind {
+    /// Area-weighted center; may fall outside concave or ring-shaped polygons.
+    Centroid,
+    /// Pole of inaccessibility: the inner point farthest from the outline,
+    /// which always lies inside and suits labels.
+    Pole,
+}
+
+impl PointKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            PointKind::Centroid => "centroid",
+            PointKind::Pole => "pole",
+        }
+    }
+}
+
+/// One point feature per polygon feature of `features`, keeping its id and
+/// properties so the points can be labeled and styled like the polygons.
+/// Points and lines are ignored. Positions are computed in the plane of the
+/// coordinates.
+pub fn representative_points(features: &[Feature], kind: PointKind) -> Vec<Feature> {
+    features
+        .iter()
+        .filter_map(|feature| {
+            let mut rings = Vec::new();
+            collect_rings(&feature.geometry.as_ref()?.value, &mut rings);
+            let point = match kind {
+                PointKind::Centroid => centroid(&rings),
+                PointKind::Pole => pole_of_inaccessibility(&rings),
+            }?;
+            Some(Feature {
+                bbox: None,
+                geometry: Some(Geometry::new(Value::Point(point.to_vec()))),
+                id: feature.id.clone(),
+                properties: feature.properties.clone(),
+                foreign_members: None,
+            })
+        })
+        .collect()
+}
+
+// Open rings of every polygon in `value`, exteriors and holes alike
+fn collect_rings(value: &Value, rings: &mut Vec<Vec<Point>>) {
+    let mut push = |polygon: &[Vec<Vec<f64>>]| {
+        for ring in polygon {
+            let mut points: Vec<Point> = ring
+                .iter()
+                .filter(|p| p.len() >= 2)
+                .map(|p| [p[0], p[1]])
+                .collect();
+            if points.len() > 1 && points.first() == points.last() {
+                points.pop();
+            }
+            if points.len() >= 3 {
+                rings.push(points);
+            }
+        }
+    };
+    match value {
+        Value::Polygon(polygon) => push(polygon),
+        Value::MultiPolygon(parts) => parts.iter().for_each(|polygon| push(polygon)),
+        Value::GeometryCollection(geometries) => {
+            for geometry in geometries {
+                collect_rings(&geometry.value, rings);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Area-weighted centroid, holes subtracted whichever way their rings run.
+// Degenerate polygons without area fall back to the mean of their vertices.
+fn centroid(rings: &[Vec<Point>]) -> Option<Point> {
+    let (mut sum_x, mut sum_y, mut total) = (0.0, 0.0, 0.0);
+    for ring in rings {
+        let area = clip::area(ring);
+        // A hole is a ring inside another one of the polygons
+        let sign = if is_hole(rings, ring) { -1.0 } else { 1.0 };
+        let (mut cx, mut cy) = (0.0, 0.0);
+        for (i, a) in ring.iter().enumerate() {
+            let b = ring[(i + 1) % ring.len()];
+            let cross = a[0] * b[1] - b[0] * a[1];
+            cx += (a[0] + b[0]) * cross;
+            cy += (a[1] + b[1]) * cross;
+        }
+        if area != 0.0 {
+            // The ring's centroid is (cx, cy) / (6 * area); weighted by |area|
+            let weight = sign * area.abs() / (6.0 * area);
+            sum_x += cx * weight;
+            sum_y += cy * weight;
+            total += sign * area.abs();
+        }
+    }
+    if total != 0.0 {
+        return Some([sum_x / total, sum_y / total]);
+    }
+    let count = rings.iter().map(Vec::len).sum::<usize>();
+    (count > 0).then(|| {
+        let (x, y) = rings
+            .iter()
+            .flatten()
+            .fold((0.0, 0.0), |(x, y), p| (x + p[0], y + p[1]));
+        [x / count as f64, y / count as f64]
+    })
+}
+
+fn is_hole(rings: &[Vec<Point>], ring: &[Point]) -> bool {
+    let around = rings
+        .iter()
+        .filter(|other| other.as_slice() != ring)
+        .filter(|other| clip::contains(std::slice::from_ref(other), ring[0]))
+        .count();
+    around % 2 == 1
+}
+
+// A square cell of the pole search, ordered by how far inside any point of it
+// could possibly be
+struct Cell {
+    center: Point,
+    half: f64,
+    distance: f64, // Signed distance of the center to the outline, positive inside
+    potential: f64,
+}
+
+impl Cell {
+    fn new(center: Point, half: f64, rings: &[Vec<Point>]) -> Cell {
+        let distance = signed_distance(center, rings);
+        Cell {
+            center,
+            half,
+            distance,
+            potential: distance + half * std::f64::consts::SQRT_2,
+        }
+    }
+}
+
+impl PartialEq for Cell {
+    fn eq(&self, other: &Self) -> bool {
+        self.potential == other.potential
+    }
+}
+
+impl Eq for Cell {}
+
+impl PartialOrd for Cell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Cell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.potential.total_cmp(&other.potential)
+    }
+}
+
+// Quadtree search for the point farthest inside the polygons (polylabel):
+// cells are split best first and dropped once they cannot beat the best point
+fn pole_of_inaccessibility(rings: &[Vec<Point>]) -> Option<Point> {
+    let mut bbox = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+    for p in rings.iter().flatten() {
+        bbox = [
+            bbox[0].min(p[0]),
+            bbox[1].min(p[1]),
+            bbox[2].max(p[0]),
+            bbox[3].max(p[1]),
+        ];
+    }
+    if bbox[0] > bbox[2] {
+        return None;
+    }
+    let (width, height) = (bbox[2] - bbox[0], bbox[3] - bbox[1]);
+    let size = width.min(height);
+    if size <= 0.0 {
+        return centroid(rings);
+    }
+    let precision = width.max(height) * POLE_PRECISION;
+
+    let mut cells = BinaryHeap::new();
+    let half = size / 2.0;
+    let mut y = bbox[1];
+    while y < bbox[3] {
+        let mut x = bbox[0];
+        while x < bbox[2] {
+            cells.push(Cell::new([x + half, y + half], half, rings));
+            x += size;
+        }
+        y += size;
+    }
+    // The centroid is a good first guess for compact shapes
+    let mut best = centroid(rings).unwrap_or([bbox[0] + width / 2.0, bbox[1] + height / 2.0]);
+    let mut best_distance = signed_distance(best, rings);
+
+    let mut probed = 0;
+    while let Some(cell) = cells.pop() {
+        if cell.distance > best_distance {
+            (best, best_distance) = (cell.center, cell.distance);
+        }
+        probed += 1;
+        if cell.potential - best_distance <= precision || probed >= MAX_POLE_CELLS {
+            continue;
+        }
+        let half = cell.half / 2.0;
+        for (dx, dy) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+            let center = [cell.center[0] + dx * half, cell.center[1] + dy * half];
+            cells.push(Cell::new(center, half, rings));
+        }
+    }
+    Some(best)
+}
+
+fn signed_distance(p: Point, rings: &[Vec<Point>]) -> f64 {
+    let mut nearest = f64::MAX;
+    for ring in rings {
+        for (i, a) in ring.iter().enumerate() {
+            let b = ring[(i + 1) % ring.len()];
+            nearest = nearest.min(segment_distance(p, *a, b));
+        }
+    }
+    if clip::contains(rings, p) {
+        nearest
+    } else {
+        -nearest
+    }
+}
+
+fn segment_distance(p: Point, a: Point, b: Point) -> f64 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (p[0] - a[0] - t * dx).hypot(p[1] - a[1] - t * dy)
+}
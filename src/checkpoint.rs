@@ -0,0 +1,94 @@
+// checkpoint.rs
+
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+/// Progress of a long batch job (the tiles of a pyramid, the images of a split),
+/// appended to a file after every finished unit so an interrupted run can skip
+/// what it already wrote. The first line identifies the job; a checkpoint of a
+/// different job is never resumed.
+pub struct Checkpoint {
+    path: PathBuf,
+    done: HashSet<String>,
+    file: File,
+}
+
+impl Checkpoint {
+    /// Starts recording progress of the job `fingerprint` in `path`. With
+    /// `resume`, units recorded by an earlier run of the same job count as done.
+    pub fn open(path: &Path, fingerprint: &str, resume: bool) -> io::Result<Checkpoint> {
+        let fingerprint = fingerprint.replace('\n', " ");
+        let mut done = HashSet::new();
+        if resume && let Ok(file) = File::open(path) {
+            let mut lines = BufReader::new(file).lines();
+            if lines.next().transpose()?.as_deref() == Some(fingerprint.as_str()) {
+                done = lines.collect::<Result<_, _>>()?;
+            }
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Rewritten rather than appended to, so a stale checkpoint never grows
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", fingerprint)?;
+        for key in &done {
+            writeln!(file, "{}", key)?;
+        }
+        file.sync_data()?;
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(Checkpoint {
+            path: path.to_path_buf(),
+            done,
+            file,
+        })
+    }
+
+    /// Number of units finished by earlier runs.
+    pub fn resumed(&self) -> usize {
+        self.done.len()
+    }
+
+    pub fn is_done(&self, key: &str) -> bool {
+        self.done.contains(key)
+    }
+
+    /// Records `key` as finished; call only once its output is complete.
+    pub fn mark_done(&mut self, key: &str) -> io::Result<()> {
+        writeln!(self.file, "{}", key)?;
+        self.file.flush()?;
+        self.done.insert(key.to_string());
+        Ok(())
+    }
+
+    /// Removes the checkpoint once the whole job has finished.
+    pub fn finish(self) -> io::Result<()> {
+        drop(self.file);
+        fs::remove_file(&self.path)
+    }
+}
+
+/// Identifies a batch job by its arguments and the size and modification time
+/// of its input files, so a checkpoint is not resumed after the data changed.
+pub fn fingerprint(arguments: &[String], inputs: &[PathBuf]) -> String {
+    let mut parts: Vec<String> = arguments.to_vec();
+    for input in inputs {
+        let stamp = fs::metadata(input).ok().map(|metadata| {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_secs());
+            format!("{}:{}", metadata.len(), modified)
+        });
+        parts.push(format!(
+            "{}@{}",
+            input.display(),
+            stamp.unwrap_or_else(|| String::from("missing"))
+        ));
+    }
+    parts.join(" ")
+}
@@ -0,0 +1,1628 @@
+// cli.rs
+
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs,
+    io::{self, IsTerminal, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::OUTPUT_DIR;
+use crate::animation::{self, Animation, GifWriter};
+use crate::basemap::Basemap;
+use crate::checkpoint::{self, Checkpoint};
+use crate::config;
+use crate::crs;
+use crate::export::{self, ExportLayer};
+use crate::filter::FeatureFilter;
+use crate::inset::{Inset, InsetCorner};
+use crate::join::{self, JoinSpec};
+use crate::loader;
+use crate::osm::{self, TagFilter};
+use crate::plot::{
+    self, ClipMask, FigureBackground, GeometryTypes, LineStyle, MapMarker, PLOT_COLORS, PlotLayer,
+    PlotOptions, PointShape, StreamedLayer,
+};
+use crate::projection::Projection;
+use crate::raster::Raster;
+use crate::session::Session;
+use crate::style::StyleRule;
+use crate::temporal::{self, TimeRange};
+use plotters::style::RGBColor;
+
+const USAGE: &str = "Usage:
+  plots                        Start the interactive TUI
+  plots --stdin                Start the TUI with GeoJSON or GeoJSONL piped on
+                               standard input as a temporary layer
+  plots --batch [OPTIONS] <FILE>...
+  plots --preset <NAME> [OPTIONS] [<FILE>...]
+                               Plot a session saved in the TUI, e.g. --preset monthly
+
+Batch options:
+      --preset <NAME>          Take the layers, colors and plot options of the
+                               session sessions/NAME.json; options after it
+                               override the session's
+  -o, --output <TEMPLATE>      Output filename (default: combined_plot.png)
+      --split-by <PROPERTY>    Render one image per unique value of PROPERTY;
+                               `{value}` in the output name is replaced by it
+      --animate <STEP>         Render a sequence of frames into an animated GIF (for
+                               an output name ending in .gif) or numbered PNGs
+                               (<name>_001.png...): `layers` adds the input files
+                               one at a time, `year`, `month` or `day` shows the
+                               features of one period per frame (see --time); the
+                               extent stays fixed, and `{value}` in --title is
+                               replaced by the period or the added file
+      --frame-delay <MS>       Time each GIF frame is shown (default: 500)
+      --no-points              Do not draw Point/MultiPoint geometries
+      --no-lines               Do not draw LineString/MultiLineString geometries
+      --no-polygons            Do not draw Polygon/MultiPolygon geometries
+      --title <TEXT>           Title above the map (default: \"GeoJSON Plot\"; with
+                               --split-by, `{value}` is replaced by the value)
+      --subtitle <TEXT>        Smaller line below the title
+      --attribution <TEXT>     Source/credits line in the lower right
+      --scale-bar              Draw a scale bar
+      --north-arrow            Draw a north arrow
+      --basemap                Draw raster tiles underneath the layers (default:
+                               OpenStreetMap, or the [basemap] url in config.toml)
+      --tile-url <TEMPLATE>    Tile URL with {z}, {x} and {y}; implies --basemap
+      --tile-cache <DIR>       Directory for downloaded tiles (default: tiles/)
+      --underlay <IMAGE>       Draw a georeferenced PNG or JPEG underneath the layers;
+                               its world file (.pgw, .jgw or .wld) must be next to
+                               it and in lon/lat degrees (not with --tiles)
+      --extent <BBOX>          Plot exactly \"min_lon,min_lat,max_lon,max_lat\"
+                               instead of the padded bbox of the features (also
+                               for every image of --split-by)
+      --focus <FILE>           Draw this input file in full color on top and all
+                               other layers in grey
+      --clip-mask <FILE>       Intersect every layer with the polygons of FILE
+                               before drawing; the extent defaults to the mask
+      --shade-outside          Shade the area outside the clip mask grey instead
+                               of leaving it empty
+      --style <RULE>           Add a styling rule, e.g. \"where type == river\",
+                               \"color pop_est #ffffcc #800026\" or
+                               \"choropleth pop_est blues quantile 5\" (repeatable)
+      --figure <BACKGROUND>    Draw only the geometry, without axes, margins or
+                               caption, sized to the extent; BACKGROUND is
+                               `white` or `transparent`
+      --projection <NAME>      Lay out the map as `lonlat` (default), or polar
+                               stereographic around a pole as `north-polar` or
+                               `south-polar`, cutting off the other hemisphere;
+                               polar maps are never stretched, leaving margins
+                               empty (not with --tiles, which are Web Mercator)
+      --keep-aspect            Widen or heighten the extent to the proportions of
+                               the image, so shapes are not stretched (a degree of
+                               longitude counts as its cosine-latitude length)
+      --inset <CORNER>         Draw a world map with the extent outlined in a corner:
+                               `upper-left`, `upper-right`, `lower-left` or `lower-right`
+      --inset-size <PERCENT>   Width of the inset in percent of the map (default: 20)
+      --cluster <PX>           Draw point features of a file closer than PX pixels
+                               (e.g. 40) as one circle showing their number
+      --simplify <TOLERANCE>   Simplify lines and polygons with this tolerance
+                               in coordinate units (default: 0, full detail), or
+                               `auto` to drop detail smaller than half a pixel
+      --thumbnail <PX>         Also write a thumbnail of at most PX x PX pixels
+                               (e.g. 256) as <name>_thumb.png next to every image
+      --stream                 Read the input files feature by feature while
+                               drawing instead of loading them, to plot files
+                               larger than memory (slower; single images only)
+      --tiles <ZOOMS>          Export a {z}/{x}/{y}.png Web Mercator tile pyramid for
+                               a zoom level or range, e.g. \"0-6\", into the output
+                               directory (the output name without .png)
+      --layer-simplify <FILE>=<TOLERANCE>
+                               Override the tolerance for one input file, e.g.
+                               \"coast.geojson=0.5\" (repeatable)
+      --layer-width <FILE>=<PX>
+                               Line width of one input file (default: 1; repeatable)
+      --layer-line-style <FILE>=<STYLE>
+                               Lines and outlines of one input file `solid`,
+                               `dashed`, `dotted` or `dash-dot` (repeatable)
+      --layer-point-size <FILE>=<PX>
+                               Point radius of one input file (default: 5; repeatable)
+      --layer-point-shape <FILE>=<SHAPE>
+                               Symbol drawn for the points of one input file:
+                               `circle`, `square`, `triangle` or `cross` (repeatable)
+      --layer-opacity <FILE>=<OPACITY>
+                               Opacity of one input file from 0 to 1, e.g.
+                               \"lakes.geojson=0.5\"; translucent polygons are
+                               filled so overlapping layers blend (repeatable)
+      --layer-types <FILE>=<TYPES>
+                               Geometry types drawn from one input file, e.g.
+                               \"counties.geojson=lines,polygons\" (points, lines,
+                               polygons, all or none; repeatable)
+      --crs <FILE>=<CRS>       Coordinate system of one input file or the clip mask,
+                               overriding what it declares, e.g. \"roads.geojson=EPSG:32633\"
+                               (EPSG code, UTM33N or, with proj, any PROJ definition;
+                               repeatable)
+      --filter <FILE>=<EXPR>   Only use the features of one input file matching an
+                               expression, e.g. 'roads.geojson=properties.lanes >= 4
+                               && properties.country == \"IN\"' (repeatable)
+      --join <FILE>=<CSV>:<PROPERTY>[=<COLUMN>]
+                               Add the columns of a CSV file to the features of one
+                               input file whose PROPERTY matches its COLUMN (named
+                               like the property unless given), e.g. for a
+                               choropleth: \"countries.geojson=gdp.csv:ISO_A3=iso\";
+                               filters and styles see the joined columns; not with
+                               --stream (repeatable)
+      --osm-tags <TAGS>        Only read the elements of .osm.pbf extracts with one of
+                               these tags, e.g. \"highway,waterway,building\" or
+                               \"amenity=cafe\" (default: every tagged element)
+      --time <RANGE>           Only use features whose time (a `time`, `timestamp`,
+                               `date`... property) is in RANGE, e.g. \"2020-01..2020-06\",
+                               \"2021\" or \"2020-06-30..\"; features without one are kept
+      --warning-banner         List input files that could not be read in a red
+                               strip across the top of the image (they are left
+                               out of the plot either way)
+      --export <FILE>          Write the layers as they would be plotted (filtered,
+                               clipped and simplified) to FILE in the output
+                               directory instead of drawing them: .geojson, .shp
+                               (split by geometry kind if mixed), .gpkg (needs
+                               the sqlite3 command) or .html (an interactive
+                               Leaflet map with the layer colors and --title);
+                               not with --stream or --tiles
+      --resume                 Continue an interrupted --tiles or --split-by run,
+                               skipping what it already wrote; progress is saved
+                               as it goes unless the inputs or options changed
+  -h, --help                   Print this help
+
+Files are looked up in data/geojson/ unless they exist as given; `-` reads
+standard input, e.g. `jq ... | plots --batch -`.";
+
+// Tile exports above this size ask for confirmation when run in a terminal
+const LARGE_TILE_COUNT: usize = 5000;
+
+const DEFAULT_FRAME_DELAY: u32 = 500;
+
+/// Options for a non-interactive run, parsed from the command line.
+pub struct BatchArgs {
+    pub files: Vec<String>,
+    pub output_template: String,
+    pub split_by: Option<String>,
+    pub animate: Option<Animation>, // Render frames instead of one image
+    pub frame_delay: u32,           // Milliseconds per frame of an animated GIF
+    pub plot_points: bool,
+    pub plot_lines: bool,
+    pub plot_polygons: bool,
+    pub title: Option<String>,
+    pub subtitle: String,
+    pub attribution: String,
+    pub scale_bar: bool,
+    pub north_arrow: bool,
+    pub basemap: bool,
+    pub tile_url: Option<String>,
+    pub tile_cache: Option<String>,
+    pub underlay: Option<String>, // Path of the raster image
+    pub extent: Option<[f64; 4]>,
+    pub focus: Option<String>,
+    pub clip_mask: Option<String>,
+    pub shade_outside: bool,
+    pub style_rules: Vec<StyleRule>,
+    pub figure: Option<FigureBackground>,
+    pub projection: Projection,
+    pub keep_aspect: bool,
+    pub cluster_distance: Option<u32>, // Pixels within which points are drawn as one
+    pub inset: Option<Inset>,
+    pub time_range: Option<TimeRange>,
+    pub osm_tags: Option<TagFilter>, // Elements read from OSM extracts
+    pub simplify_tolerance: f64,
+    pub auto_simplify: bool,
+    pub layer_simplify_tolerances: Vec<(String, f64)>, // Input file and its tolerance
+    pub layer_line_widths: Vec<(String, u32)>,         // Input file and its line width
+    pub layer_line_styles: Vec<(String, LineStyle)>,   // Input file and its dash pattern
+    pub layer_point_sizes: Vec<(String, u32)>,         // Input file and its point radius
+    pub layer_point_shapes: Vec<(String, PointShape)>, // Input file and its point symbol
+    pub layer_opacities: Vec<(String, f64)>,           // Input file and its opacity
+    pub layer_geometry_types: Vec<(String, GeometryTypes)>, // Input file and its drawn types
+    pub layer_crs: Vec<(String, String)>,              // Input file and its CRS override
+    pub layer_filters: Vec<(String, FeatureFilter)>,   // Input file and its feature filter
+    pub layer_joins: Vec<(String, JoinSpec)>,          // Input file and a CSV joined to it
+    pub layer_colors: Vec<(String, RGBColor)>,         // Input file and its color, from a preset
+    pub markers: Vec<MapMarker>,                       // From a preset
+    pub thumbnail: Option<u32>,
+    pub tile_zooms: Option<RangeInclusive<u32>>, // Export tiles instead of an image
+    pub export: Option<String>, // Write the prepared layers to this file instead of an image
+    pub stream: bool,           // Read features from disk while drawing instead of loading them
+    pub resume: bool,           // Skip tiles or images an interrupted run already wrote
+    pub warning_banner: bool,   // Name the files that could not be read on the image
+    pub arguments: Vec<String>, // Command line without --resume, identifying the job
+    pub stdin_file: Option<PathBuf>, // Temporary copy of the `-` input, removed after the run
+}
+
+/// Parses the program arguments (without the executable name).
+/// Returns `Ok(None)` when the interactive TUI should be started.
+pub fn parse_args(args: &[String]) -> Result<Option<BatchArgs>, String> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        std::process::exit(0);
+    }
+    // `--preset` alone starts a batch run too
+    let options = match args[0].as_str() {
+        "--batch" => &args[1..],
+        "--preset" => args,
+        _ => return Err(format!("Unknown argument '{}'.\n\n{}", args[0], USAGE)),
+    };
+
+    let mut batch = BatchArgs {
+        files: Vec::new(),
+        output_template: String::from("combined_plot.png"),
+        split_by: None,
+        animate: None,
+        frame_delay: DEFAULT_FRAME_DELAY,
+        plot_points: true,
+        plot_lines: true,
+        plot_polygons: true,
+        title: None,
+        subtitle: String::new(),
+        attribution: String::new(),
+        scale_bar: false,
+        north_arrow: false,
+        basemap: false,
+        tile_url: None,
+        tile_cache: None,
+        underlay: None,
+        extent: None,
+        focus: None,
+        clip_mask: None,
+        shade_outside: false,
+        style_rules: Vec::new(),
+        figure: None,
+        projection: Projection::LonLat,
+        keep_aspect: false,
+        cluster_distance: None,
+        inset: None,
+        time_range: None,
+        osm_tags: None,
+        simplify_tolerance: 0.0,
+        auto_simplify: false,
+        layer_simplify_tolerances: Vec::new(),
+        layer_line_widths: Vec::new(),
+        layer_line_styles: Vec::new(),
+        layer_crs: Vec::new(),
+        layer_filters: Vec::new(),
+        layer_joins: Vec::new(),
+        layer_point_sizes: Vec::new(),
+        layer_point_shapes: Vec::new(),
+        layer_opacities: Vec::new(),
+        layer_geometry_types: Vec::new(),
+        layer_colors: Vec::new(),
+        markers: Vec::new(),
+        thumbnail: None,
+        tile_zooms: None,
+        export: None,
+        stream: false,
+        resume: false,
+        warning_banner: false,
+        arguments: args.iter().filter(|a| *a != "--resume").cloned().collect(),
+        stdin_file: None,
+    };
+
+    let mut inset_size = None; // Applied once all options are read, so it may come first
+    let mut iter = options.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                batch.output_template = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?
+                    .clone();
+            }
+            "--preset" => {
+                let name = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                let preset = Session::load(name)
+                    .map_err(|e| format!("Cannot load preset '{}': {}.", name, e))?;
+                apply_preset(&mut batch, &preset)
+                    .map_err(|e| format!("Invalid preset '{}': {}", name, e))?;
+            }
+            "--resume" => batch.resume = true,
+            "--stream" => batch.stream = true,
+            "--warning-banner" => batch.warning_banner = true,
+            "--keep-aspect" => batch.keep_aspect = true,
+            "--split-by" => {
+                batch.split_by = Some(
+                    iter.next()
+                        .ok_or_else(|| format!("Missing value for '{}'.", arg))?
+                        .clone(),
+                );
+            }
+            "--animate" => {
+                let step = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                batch.animate = Some(Animation::parse(step).ok_or_else(|| {
+                    format!(
+                        "Unknown animation '{}' (use layers, year, month or day).",
+                        step
+                    )
+                })?);
+            }
+            "--frame-delay" => {
+                let delay = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                batch.frame_delay = delay
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|&delay| delay > 0)
+                    .ok_or_else(|| {
+                        format!(
+                            "Frame delay '{}' must be a whole number of milliseconds above 0.",
+                            delay
+                        )
+                    })?;
+            }
+            "--style" => {
+                let rule = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                batch.style_rules.push(StyleRule::parse(rule)?);
+            }
+            "--figure" => {
+                let background = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                batch.figure = Some(FigureBackground::parse(background).ok_or_else(|| {
+                    format!(
+                        "Unknown figure background '{}' (use white or transparent).",
+                        background
+                    )
+                })?);
+            }
+            "--projection" => {
+                let name = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                batch.projection = Projection::parse(name).ok_or_else(|| {
+                    format!(
+                        "Unknown projection '{}' (use lonlat, north-polar or south-polar).",
+                        name
+                    )
+                })?;
+            }
+            "--time" => {
+                let range = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                batch.time_range =
+                    Some(TimeRange::parse(range).map_err(|e| format!("Invalid --time: {}.", e))?);
+            }
+            "--simplify" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                if value == "auto" {
+                    batch.auto_simplify = true;
+                } else {
+                    batch.auto_simplify = false;
+                    batch.simplify_tolerance = parse_tolerance(value)?;
+                }
+            }
+            "--layer-simplify" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                let (file, tolerance) = value
+                    .rsplit_once('=')
+                    .ok_or_else(|| format!("Expected <FILE>=<TOLERANCE>, got '{}'.", value))?;
+                batch
+                    .layer_simplify_tolerances
+                    .push((file.to_string(), parse_tolerance(tolerance)?));
+            }
+            "--layer-width" | "--layer-point-size" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                let (file, size) = value
+                    .rsplit_once('=')
+                    .ok_or_else(|| format!("Expected <FILE>=<PX>, got '{}'.", value))?;
+                let size = size
+                    .trim()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|size| (1..=plot::MAX_SYMBOL_SIZE).contains(size))
+                    .ok_or_else(|| {
+                        format!(
+                            "Size '{}' must be a whole number from 1 to {}.",
+                            size,
+                            plot::MAX_SYMBOL_SIZE
+                        )
+                    })?;
+                let sizes = if arg == "--layer-width" {
+                    &mut batch.layer_line_widths
+                } else {
+                    &mut batch.layer_point_sizes
+                };
+                sizes.push((file.to_string(), size));
+            }
+            "--layer-line-style" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                let (file, style) = value
+                    .rsplit_once('=')
+                    .ok_or_else(|| format!("Expected <FILE>=<STYLE>, got '{}'.", value))?;
+                let style = LineStyle::parse(style).ok_or_else(|| {
+                    format!(
+                        "Unknown line style '{}' (use solid, dashed, dotted or dash-dot).",
+                        style
+                    )
+                })?;
+                batch.layer_line_styles.push((file.to_string(), style));
+            }
+            "--layer-point-shape" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                let (file, shape) = value
+                    .rsplit_once('=')
+                    .ok_or_else(|| format!("Expected <FILE>=<SHAPE>, got '{}'.", value))?;
+                let shape = PointShape::parse(shape).ok_or_else(|| {
+                    format!(
+                        "Unknown point shape '{}' (use circle, square, triangle or cross).",
+                        shape
+                    )
+                })?;
+                batch.layer_point_shapes.push((file.to_string(), shape));
+            }
+            "--layer-opacity" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                let (file, opacity) = value
+                    .rsplit_once('=')
+                    .ok_or_else(|| format!("Expected <FILE>=<OPACITY>, got '{}'.", value))?;
+                let opacity = opacity
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|opacity| (0.0..=1.0).contains(opacity))
+                    .ok_or_else(|| format!("Opacity '{}' must be from 0 to 1.", opacity))?;
+                batch.layer_opacities.push((file.to_string(), opacity));
+            }
+            "--layer-types" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                let (file, types) = value
+                    .rsplit_once('=')
+                    .ok_or_else(|| format!("Expected <FILE>=<TYPES>, got '{}'.", value))?;
+                let types = GeometryTypes::parse(types).map_err(|e| format!("{}.", e))?;
+                batch.layer_geometry_types.push((file.to_string(), types));
+            }
+            "--osm-tags" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                batch.osm_tags = Some(TagFilter::parse(value).map_err(|e| format!("{}.", e))?);
+            }
+            "--crs" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                let (file, name) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("Expected <FILE>=<CRS>, got '{}'.", value))?;
+                batch
+                    .layer_crs
+                    .push((file.to_string(), crs::parse_crs(name)?));
+            }
+            "--filter" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                let (file, expression) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("Expected <FILE>=<EXPR>, got '{}'.", value))?;
+                let filter = FeatureFilter::parse(expression)
+                    .map_err(|e| format!("Invalid filter for {}: {}.", file, e))?;
+                batch.layer_filters.push((file.to_string(), filter));
+            }
+            "--join" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                let (file, csv, key) = value
+                    .split_once('=')
+                    .and_then(|(file, join)| {
+                        let (csv, key) = join.rsplit_once(':')?;
+                        Some((file, csv, key))
+                    })
+                    .ok_or_else(|| {
+                        format!(
+                            "Expected <FILE>=<CSV>:<PROPERTY>[=<COLUMN>], got '{}'.",
+                            value
+                        )
+                    })?;
+                let spec = JoinSpec::from_parts(csv, key)
+                    .map_err(|e| format!("Invalid join for {}: {}.", file, e))?;
+                batch.layer_joins.push((file.to_string(), spec));
+            }
+            "--no-points" => batch.plot_points = false,
+            "--no-lines" => batch.plot_lines = false,
+            "--no-polygons" => batch.plot_polygons = false,
+            "--title" | "--subtitle" | "--attribution" => {
+                let text = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?
+                    .clone();
+                match arg.as_str() {
+                    "--title" => batch.title = Some(text),
+                    "--subtitle" => batch.subtitle = text,
+                    _ => batch.attribution = text,
+                }
+            }
+            "--scale-bar" => batch.scale_bar = true,
+            "--north-arrow" => batch.north_arrow = true,
+            "--basemap" => batch.basemap = true,
+            "--inset" => {
+                let corner = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                let corner = InsetCorner::parse(corner).ok_or_else(|| {
+                    format!(
+                        "Unknown inset corner '{}' (use upper-left, upper-right, lower-left or lower-right).",
+                        corner
+                    )
+                })?;
+                batch.inset = Some(Inset {
+                    corner,
+                    ..batch.inset.unwrap_or_default()
+                });
+            }
+            "--inset-size" => {
+                let size = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                let size = size
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|size| (5..=50).contains(size))
+                    .ok_or_else(|| format!("Inset size '{}' must be 5 to 50 percent.", size))?;
+                inset_size = Some(size);
+            }
+            "--cluster" => {
+                let distance = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                batch.cluster_distance = Some(
+                    distance
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|&distance| distance > 0)
+                        .ok_or_else(|| {
+                            format!(
+                                "Cluster distance '{}' must be a whole number of pixels above 0.",
+                                distance
+                            )
+                        })?,
+                );
+            }
+            "--thumbnail" => {
+                let size = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                batch.thumbnail = Some(
+                    size.parse::<u32>()
+                        .ok()
+                        .filter(|&size| size > 0)
+                        .ok_or_else(|| {
+                            format!("Thumbnail size '{}' must be a whole number above 0.", size)
+                        })?,
+                );
+            }
+            "--export" => {
+                let file = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                if !export::is_export_file(Path::new(file)) {
+                    return Err(format!(
+                        "Cannot export to '{}': use .geojson, .shp, .gpkg or .html.",
+                        file
+                    ));
+                }
+                batch.export = Some(file.clone());
+            }
+            "--tiles" => {
+                let zooms = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                batch.tile_zooms = Some(parse_zooms(zooms)?);
+            }
+            "--tile-url" => {
+                let url = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                if !(url.contains("{z}") && url.contains("{x}") && url.contains("{y}")) {
+                    return Err(format!(
+                        "Tile URL '{}' needs {{z}}, {{x}} and {{y}} placeholders.",
+                        url
+                    ));
+                }
+                batch.tile_url = Some(url.clone());
+                batch.basemap = true;
+            }
+            "--tile-cache" => {
+                batch.tile_cache = Some(
+                    iter.next()
+                        .ok_or_else(|| format!("Missing value for '{}'.", arg))?
+                        .clone(),
+                );
+            }
+            "--underlay" => {
+                batch.underlay = Some(
+                    iter.next()
+                        .ok_or_else(|| format!("Missing value for '{}'.", arg))?
+                        .clone(),
+                );
+            }
+            "--extent" => {
+                let extent = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{}'.", arg))?;
+                batch.extent = Some(
+                    plot::parse_extent(extent)
+                        .map_err(|e| format!("Invalid extent '{}': {}.", extent, e))?,
+                );
+            }
+            "--focus" => {
+                batch.focus = Some(
+                    iter.next()
+                        .ok_or_else(|| format!("Missing value for '{}'.", arg))?
+                        .clone(),
+                );
+            }
+            "--clip-mask" => {
+                batch.clip_mask = Some(
+                    iter.next()
+                        .ok_or_else(|| format!("Missing value for '{}'.", arg))?
+                        .clone(),
+                );
+            }
+            "--shade-outside" => batch.shade_outside = true,
+            "-" => {
+                if batch.stdin_file.is_none() {
+                    let path = loader::read_stdin_to_temp()
+                        .map_err(|e| format!("Cannot read standard input: {}", e))?;
+                    batch.stdin_file = Some(path);
+                }
+                if let Some(path) = batch.stdin_file.as_ref().and_then(|p| p.to_str()) {
+                    batch.files.push(path.to_string());
+                }
+            }
+            flag if flag.starts_with('-') => {
+                return Err(format!("Unknown option '{}'.\n\n{}", flag, USAGE));
+            }
+            file => batch.files.push(file.to_string()),
+        }
+    }
+
+    if batch.files.is_empty() {
+        return Err(format!("No input files given.\n\n{}", USAGE));
+    }
+    if let Some(size) = inset_size {
+        let inset = batch.inset.as_mut().ok_or("--inset-size needs --inset.")?;
+        inset.size = size;
+    }
+    if batch.shade_outside && batch.clip_mask.is_none() {
+        return Err(String::from("--shade-outside needs --clip-mask."));
+    }
+    if batch.tile_zooms.is_some() && batch.split_by.is_some() {
+        return Err(String::from("--tiles cannot be combined with --split-by."));
+    }
+    if batch.tile_zooms.is_some() && batch.projection.is_polar() {
+        return Err(String::from(
+            "--tiles are always Web Mercator and cannot be combined with a polar --projection.",
+        ));
+    }
+    if batch.stream && (batch.tile_zooms.is_some() || batch.split_by.is_some()) {
+        return Err(String::from(
+            "--stream cannot be combined with --tiles or --split-by.",
+        ));
+    }
+    if batch.stream && !batch.layer_joins.is_empty() {
+        return Err(String::from("--join cannot be combined with --stream."));
+    }
+    if batch.export.is_some()
+        && (batch.stream || batch.tile_zooms.is_some() || batch.split_by.is_some())
+    {
+        return Err(String::from(
+            "--export cannot be combined with --stream, --tiles or --split-by.",
+        ));
+    }
+    if batch.animate.is_some()
+        && (batch.stream
+            || batch.tile_zooms.is_some()
+            || batch.split_by.is_some()
+            || batch.export.is_some())
+    {
+        return Err(String::from(
+            "--animate cannot be combined with --stream, --tiles, --split-by or --export.",
+        ));
+    }
+    if let Some(focus) = batch.focus.as_ref().filter(|f| !batch.files.contains(f)) {
+        return Err(format!("'{}' in --focus is not an input file.", focus));
+    }
+    if let Some((file, _)) = batch
+        .layer_simplify_tolerances
+        .iter()
+        .find(|(file, _)| !batch.files.contains(file))
+    {
+        return Err(format!(
+            "'{}' in --layer-simplify is not an input file.",
+            file
+        ));
+    }
+    for (option, sizes) in [
+        ("--layer-width", &batch.layer_line_widths),
+        ("--layer-point-size", &batch.layer_point_sizes),
+    ] {
+        if let Some((file, _)) = sizes.iter().find(|(file, _)| !batch.files.contains(file)) {
+            return Err(format!("'{}' in {} is not an input file.", file, option));
+        }
+    }
+    if let Some((file, _)) = batch
+        .layer_opacities
+        .iter()
+        .find(|(file, _)| !batch.files.contains(file))
+    {
+        return Err(format!(
+            "'{}' in --layer-opacity is not an input file.",
+            file
+        ));
+    }
+    if let Some((file, _)) = batch
+        .layer_line_styles
+        .iter()
+        .find(|(file, _)| !batch.files.contains(file))
+    {
+        return Err(format!(
+            "'{}' in --layer-line-style is not an input file.",
+            file
+        ));
+    }
+    if let Some((file, _)) = batch
+        .layer_point_shapes
+        .iter()
+        .find(|(file, _)| !batch.files.contains(file))
+    {
+        return Err(format!(
+            "'{}' in --layer-point-shape is not an input file.",
+            file
+        ));
+    }
+    if let Some((file, _)) = batch
+        .layer_geometry_types
+        .iter()
+        .find(|(file, _)| !batch.files.contains(file))
+    {
+        return Err(format!("'{}' in --layer-types is not an input file.", file));
+    }
+    if let Some((file, _)) = batch
+        .layer_crs
+        .iter()
+        .find(|(file, _)| !batch.files.contains(file) && batch.clip_mask.as_ref() != Some(file))
+    {
+        return Err(format!("'{}' in --crs is not an input file.", file));
+    }
+    if let Some((file, _)) = batch
+        .layer_filters
+        .iter()
+        .find(|(file, _)| !batch.files.contains(file))
+    {
+        return Err(format!("'{}' in --filter is not an input file.", file));
+    }
+    if let Some((file, _)) = batch
+        .layer_joins
+        .iter()
+        .find(|(file, _)| !batch.files.contains(file))
+    {
+        return Err(format!("'{}' in --join is not an input file.", file));
+    }
+    Ok(Some(batch))
+}
+
+// Adds the layers of a session saved in the TUI to `batch` and takes over its
+// plot options
+fn apply_preset(batch: &mut BatchArgs, preset: &Session) -> Result<(), String> {
+    for layer in &preset.layers {
+        let file = &layer.file;
+        batch.files.push(file.clone());
+        batch.layer_colors.push((
+            file.clone(),
+            RGBColor(layer.color[0], layer.color[1], layer.color[2]),
+        ));
+        if let Some(tolerance) = layer.simplify_tolerance {
+            batch
+                .layer_simplify_tolerances
+                .push((file.clone(), tolerance));
+        }
+        if let Some(width) = layer.line_width {
+            batch.layer_line_widths.push((file.clone(), width));
+        }
+        if let Some(style) = layer.line_style {
+            batch.layer_line_styles.push((file.clone(), style));
+        }
+        if let Some(size) = layer.point_size {
+            batch.layer_point_sizes.push((file.clone(), size));
+        }
+        if let Some(shape) = layer.point_shape {
+            batch.layer_point_shapes.push((file.clone(), shape));
+        }
+        if let Some(opacity) = layer.opacity {
+            batch
+                .layer_opacities
+                .push((file.clone(), opacity as f64 / 100.0));
+        }
+        if let Some(types) = &layer.geometry_types {
+            let types = GeometryTypes::parse(types)
+                .map_err(|e| format!("invalid geometry types for {}: {}.", file, e))?;
+            batch.layer_geometry_types.push((file.clone(), types));
+        }
+        if let Some(crs) = &layer.crs {
+            batch.layer_crs.push((file.clone(), crs.clone()));
+        }
+        if let Some(filter) = &layer.filter {
+            let filter = FeatureFilter::parse(filter)
+                .map_err(|e| format!("invalid filter for {}: {}.", file, e))?;
+            batch.layer_filters.push((file.clone(), filter));
+        }
+    }
+    batch.output_template.clone_from(&preset.output_filename);
+    batch.plot_points = preset.plot_points;
+    batch.plot_lines = preset.plot_lines;
+    batch.plot_polygons = preset.plot_polygons;
+    batch.title = Some(preset.title.clone());
+    batch.subtitle.clone_from(&preset.subtitle);
+    batch.attribution.clone_from(&preset.attribution);
+    batch.scale_bar = preset.scale_bar;
+    batch.north_arrow = preset.north_arrow;
+    batch.basemap = preset.basemap;
+    batch.underlay.clone_from(&preset.underlay);
+    batch.extent = preset.extent;
+    batch.focus.clone_from(&preset.focus);
+    batch.clip_mask.clone_from(&preset.clip_mask);
+    batch.shade_outside = preset.clip_mask_shade;
+    batch.style_rules = preset
+        .style_rules
+        .iter()
+        .map(|rule| StyleRule::parse(rule))
+        .collect::<Result<_, _>>()?;
+    batch.figure = preset.figure_mode;
+    batch.projection = preset.projection;
+    batch.keep_aspect = preset.keep_aspect;
+    batch.cluster_distance = preset.cluster_distance;
+    batch.inset = preset.inset;
+    batch.time_range = match &preset.time_range {
+        Some(range) => Some(TimeRange::parse(range)?),
+        None => None,
+    };
+    batch.simplify_tolerance = preset.simplify_tolerance;
+    batch.auto_simplify = preset.auto_simplify;
+    batch.thumbnail = preset.thumbnails.then_some(plot::DEFAULT_THUMBNAIL_SIZE);
+    batch.markers.clone_from(&preset.markers);
+    Ok(())
+}
+
+fn parse_tolerance(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(tolerance) if tolerance >= 0.0 => Ok(tolerance),
+        _ => Err(format!("'{}' is not a tolerance >= 0.", value)),
+    }
+}
+
+// Parses a zoom level ("5") or an inclusive range ("0-6")
+fn parse_zooms(value: &str) -> Result<RangeInclusive<u32>, String> {
+    let zoom = |z: &str| {
+        z.trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|z| *z <= plot::MAX_TILE_ZOOM)
+            .ok_or_else(|| {
+                format!(
+                    "'{}' is not a zoom level from 0 to {}.",
+                    z,
+                    plot::MAX_TILE_ZOOM
+                )
+            })
+    };
+    let (min, max) = match value.split_once('-') {
+        Some((min, max)) => (zoom(min)?, zoom(max)?),
+        None => (zoom(value)?, zoom(value)?),
+    };
+    if min > max {
+        return Err(format!("Zoom range '{}' is reversed.", value));
+    }
+    Ok(min..=max)
+}
+
+/// Replaces characters that are unsafe in file names.
+fn sanitize_filename_part(value: &str) -> String {
+    let sanitized: String = value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized.is_empty() {
+        String::from("_")
+    } else {
+        sanitized
+    }
+}
+
+/// Substitutes `{value}` in the output template. Templates without the
+/// placeholder get the value appended before the extension.
+fn expand_template(template: &str, value: &str) -> String {
+    let value = sanitize_filename_part(value);
+    if template.contains("{value}") {
+        return template.replace("{value}", &value);
+    }
+    let path = Path::new(template);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("plot");
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}_{}.{}", stem, value, ext),
+        None => format!("{}_{}", stem, value),
+    }
+}
+
+/// The output file name for each of `values`, in order. Values that come out
+/// the same once sanitized (`a b` and `a_b`, or `A` and `a` on a file system
+/// that ignores case) get `_2`, `_3`, ... after the first, so no plot
+/// overwrites another.
+fn output_names(template: &str, values: &[String]) -> Vec<String> {
+    let mut taken = HashSet::new();
+    values
+        .iter()
+        .map(|value| {
+            let base = sanitize_filename_part(value);
+            let mut part = base.clone();
+            let mut count = 1;
+            while !taken.insert(expand_template(template, &part).to_lowercase()) {
+                count += 1;
+                part = format!("{}_{}", base, count);
+            }
+            expand_template(template, &part)
+        })
+        .collect()
+}
+
+fn batch_clip_mask(args: &BatchArgs, file: &str) -> Result<ClipMask, Box<dyn Error>> {
+    let path = loader::resolve_input(file);
+    let features = loader::read_features_with_crs(&path, crs_override(args, file))
+        .map_err(|e| format!("Error reading clip mask from {}: {}", path.display(), e))?
+        .features;
+    Ok(ClipMask::from_features(&features, args.shade_outside)
+        .ok_or_else(|| format!("Clip mask {} has no polygons.", path.display()))?)
+}
+
+// Starts recording progress in `path`, resuming it if asked to
+fn open_checkpoint(args: &BatchArgs, path: &Path) -> Result<Checkpoint, Box<dyn Error>> {
+    let mut inputs: Vec<PathBuf> = args
+        .files
+        .iter()
+        .map(|f| loader::resolve_input(f))
+        .collect();
+    inputs.extend(args.clip_mask.iter().map(|f| loader::resolve_input(f)));
+    let fingerprint = checkpoint::fingerprint(&args.arguments, &inputs);
+    let checkpoint = Checkpoint::open(path, &fingerprint, args.resume)
+        .map_err(|e| format!("Cannot write checkpoint {}: {}", path.display(), e))?;
+    if checkpoint.resumed() > 0 {
+        println!(
+            "Resuming: {} already written by an earlier run",
+            checkpoint.resumed()
+        );
+    } else if args.resume {
+        println!("Nothing to resume; starting from the beginning");
+    }
+    Ok(checkpoint)
+}
+
+// The last --crs given for a file wins
+fn crs_override<'a>(args: &'a BatchArgs, file: &str) -> Option<&'a str> {
+    args.layer_crs
+        .iter()
+        .rev()
+        .find(|(name, _)| name == file)
+        .map(|(_, crs)| crs.as_str())
+}
+
+// The last --filter given for a file wins
+fn filter_for<'a>(args: &'a BatchArgs, file: &str) -> Option<&'a FeatureFilter> {
+    args.layer_filters
+        .iter()
+        .rev()
+        .find(|(name, _)| name == file)
+        .map(|(_, filter)| filter)
+}
+
+// Prints the warnings of reading `path` and whether it was reprojected
+fn report_read(path: &Path, parsed: &loader::ParsedFeatures) {
+    if let Some(message) = parsed.skipped_message() {
+        eprintln!("Warning: {}: {}", path.display(), message);
+    }
+    if let Some(warning) = &parsed.crs_warning {
+        eprintln!("Warning: {}: {}", path.display(), warning);
+    } else if let Some(crs) = parsed.crs.as_deref().filter(|crs| !crs::is_wgs84(crs)) {
+        println!("Reprojected {} from {}", path.display(), crs);
+    }
+}
+
+// Prints what a render reported as falling back or left out
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+// Tile source from config.toml, overridden by the command line
+fn batch_basemap(args: &BatchArgs) -> Result<Basemap, String> {
+    let mut basemap = Basemap::from_config(&config::load_config()?)?;
+    if let Some(url) = &args.tile_url {
+        basemap.url = url.clone();
+        basemap.attribution = String::new();
+    }
+    if let Some(dir) = &args.tile_cache {
+        basemap.cache_dir = PathBuf::from(dir);
+    }
+    Ok(basemap)
+}
+
+// Asks a yes/no question on the terminal, defaulting to no
+fn confirm(question: &str) -> io::Result<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Runs a batch render and prints the generated files. Input files that cannot
+/// be read are left out and listed at the end; the run fails only if none can.
+pub fn run_batch(args: &BatchArgs) -> Result<(), Box<dyn Error>> {
+    if let Some(filter) = &args.osm_tags {
+        osm::set_tag_filter(filter.clone());
+    }
+    let mut failed = Vec::new();
+    let result = render_batch(args, &mut failed);
+    if let Some(path) = &args.stdin_file {
+        let _ = fs::remove_file(path);
+    }
+    if let Some(summary) = plot::failed_layers_summary(&failed) {
+        println!("{}", summary);
+    }
+    result
+}
+
+// Writes the layers to `export` clipped and simplified like the plot would be
+fn export_batch(
+    args: &BatchArgs,
+    export: &str,
+    files: &[&String],
+    layers: Vec<PlotLayer>,
+) -> Result<(), Box<dyn Error>> {
+    let mask = match &args.clip_mask {
+        Some(file) => Some(batch_clip_mask(args, file)?),
+        None => None,
+    };
+    let layers: Vec<ExportLayer> = files
+        .iter()
+        .zip(layers)
+        .map(|(file, layer)| ExportLayer {
+            name: loader::resolve_input(file)
+                .file_stem()
+                .map_or_else(|| file.to_string(), |s| s.to_string_lossy().into_owned()),
+            features: export::prepare_features(
+                layer.features,
+                mask.as_ref(),
+                layer.simplify_tolerance.unwrap_or(args.simplify_tolerance),
+            ),
+            color: layer.color,
+        })
+        .collect();
+    let title = args.title.as_deref().unwrap_or(plot::DEFAULT_TITLE);
+    let written = export::write_layers(&PathBuf::from(OUTPUT_DIR).join(export), &layers, title)?;
+    for path in written {
+        println!("Layers exported to {}", path.display());
+    }
+    Ok(())
+}
+
+// Renders what `args` asks for, adding the input files that could not be read
+// to `failed`
+fn render_batch(args: &BatchArgs, failed: &mut Vec<String>) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(OUTPUT_DIR)?;
+
+    let mut layers = Vec::new();
+    let mut files = Vec::new(); // Input files of `layers`
+    for (i, file) in args.files.iter().enumerate() {
+        // Streamed files are only read while drawing
+        let features = if args.stream {
+            Vec::new()
+        } else {
+            let path = loader::resolve_input(file);
+            match loader::read_features_with_crs(&path, crs_override(args, file)) {
+                Ok(parsed) => {
+                    report_read(&path, &parsed);
+                    let mut features = parsed.features;
+                    for (_, spec) in args.layer_joins.iter().filter(|(name, _)| name == file) {
+                        let csv = loader::resolve_input(&spec.csv);
+                        let report = join::read_csv(&csv).and_then(|table| {
+                            join::join_features(&mut features, &table, &spec.property, &spec.column)
+                        })?;
+                        println!(
+                            "{}: joined {} of {} features to {} ({} columns)",
+                            path.display(),
+                            report.matched,
+                            report.total,
+                            csv.display(),
+                            report.columns
+                        );
+                    }
+                    if let Some(filter) = filter_for(args, file) {
+                        let total = features.len();
+                        features.retain(|feature| filter.accepts(feature));
+                        println!(
+                            "{}: {} of {} features match {}",
+                            path.display(),
+                            features.len(),
+                            total,
+                            filter
+                        );
+                    }
+                    if let Some(range) = &args.time_range {
+                        let total = features.len();
+                        features.retain(|feature| range.accepts(feature));
+                        println!(
+                            "{}: {} of {} features in time range {}",
+                            path.display(),
+                            features.len(),
+                            total,
+                            range
+                        );
+                    }
+                    features
+                }
+                Err(e) => {
+                    failed.push(format!("{}: {}", path.display(), e));
+                    continue;
+                }
+            }
+        };
+        let mut layer = PlotLayer {
+            features,
+            color: args
+                .layer_colors
+                .iter()
+                .rev()
+                .find(|(name, _)| name == file)
+                .map_or(PLOT_COLORS[i % PLOT_COLORS.len()], |(_, color)| *color),
+            simplify_tolerance: None,
+            line_width: None,
+            line_style: None,
+            point_size: None,
+            point_shape: None,
+            opacity: None,
+            geometry_types: None,
+            // Shared by the images of a --split-by run
+            levels: args.split_by.is_some().then(Arc::default),
+        };
+        // The last override given for a file wins
+        layer.simplify_tolerance = args
+            .layer_simplify_tolerances
+            .iter()
+            .rev()
+            .find(|(name, _)| name == file)
+            .map(|(_, tolerance)| *tolerance);
+        let size_for = |sizes: &[(String, u32)]| {
+            sizes
+                .iter()
+                .rev()
+                .find(|(name, _)| name == file)
+                .map(|(_, size)| *size)
+        };
+        layer.line_width = size_for(&args.layer_line_widths);
+        layer.point_size = size_for(&args.layer_point_sizes);
+        layer.point_shape = args
+            .layer_point_shapes
+            .iter()
+            .rev()
+            .find(|(name, _)| name == file)
+            .map(|(_, shape)| *shape);
+        layer.line_style = args
+            .layer_line_styles
+            .iter()
+            .rev()
+            .find(|(name, _)| name == file)
+            .map(|(_, style)| *style);
+        layer.opacity = args
+            .layer_opacities
+            .iter()
+            .rev()
+            .find(|(name, _)| name == file)
+            .map(|(_, opacity)| *opacity);
+        layer.geometry_types = args
+            .layer_geometry_types
+            .iter()
+            .rev()
+            .find(|(name, _)| name == file)
+            .map(|(_, types)| *types);
+        layers.push(layer);
+        files.push(file);
+    }
+    if layers.is_empty() {
+        return Err("None of the input files could be read".into());
+    }
+    if let Some(export) = &args.export {
+        return export_batch(args, export, &files, layers);
+    }
+
+    let mut options = PlotOptions {
+        plot_points: args.plot_points,
+        plot_lines: args.plot_lines,
+        plot_polygons: args.plot_polygons,
+        style_rules: args.style_rules.clone(),
+        figure: args.figure,
+        projection: args.projection,
+        keep_aspect: args.keep_aspect,
+        cluster_distance: args.cluster_distance,
+        inset: args.inset,
+        time_range: args.time_range.clone(),
+        simplify_tolerance: args.simplify_tolerance,
+        auto_simplify: args.auto_simplify,
+        caption: args
+            .title
+            .clone()
+            .unwrap_or_else(|| String::from(plot::DEFAULT_TITLE)),
+        subtitle: args.subtitle.clone(),
+        attribution: args.attribution.clone(),
+        scale_bar: args.scale_bar,
+        north_arrow: args.north_arrow,
+        basemap: if args.basemap {
+            Some(batch_basemap(args)?)
+        } else {
+            None
+        },
+        underlay: match &args.underlay {
+            Some(path) => Some(Arc::new(Raster::open(Path::new(path))?)),
+            None => None,
+        },
+        extent: args.extent,
+        markers: args.markers.clone(),
+        thumbnail: args.thumbnail,
+        focus_layer: args
+            .focus
+            .as_ref()
+            .and_then(|focus| files.iter().position(|f| *f == focus)),
+        clip_mask: match &args.clip_mask {
+            Some(file) => Some(batch_clip_mask(args, file)?),
+            None => None,
+        },
+        failed_layers: failed.clone(),
+        warning_banner: args.warning_banner,
+        ..PlotOptions::default()
+    };
+
+    if args.stream {
+        let layers: Vec<StreamedLayer> = files
+            .iter()
+            .zip(layers)
+            .map(|(file, layer)| StreamedLayer {
+                path: loader::resolve_input(file),
+                crs: crs_override(args, file).map(String::from),
+                filter: filter_for(args, file).cloned(),
+                layer,
+            })
+            .collect();
+        let output = PathBuf::from(OUTPUT_DIR).join(&args.output_template);
+        let reports = plot::render_plot_streamed(&output, &layers, &options)?;
+        for (layer, report) in layers.iter().zip(&reports) {
+            match report {
+                Ok(parsed) => report_read(&layer.path, parsed),
+                Err(failure) => failed.push(failure.clone()),
+            }
+        }
+        println!("Combined plot generated to {}", output.display());
+        if options.thumbnail.is_some() {
+            println!(
+                "Thumbnail generated to {}",
+                plot::thumbnail_path(&output).display()
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(zooms) = &args.tile_zooms {
+        let template = Path::new(&args.output_template);
+        let dir = PathBuf::from(OUTPUT_DIR).join(match template.extension() {
+            Some(ext) if ext == "png" => template.with_extension(""),
+            _ => template.to_path_buf(),
+        });
+        let count = plot::tile_count(&layers, &options, zooms.clone());
+        println!(
+            "Exporting {} tiles, zoom {}-{}",
+            count,
+            zooms.start(),
+            zooms.end()
+        );
+        if count > LARGE_TILE_COUNT
+            && io::stdin().is_terminal()
+            && !confirm("Many tiles. Continue?")?
+        {
+            println!("Cancelled.");
+            return Ok(());
+        }
+        let mut checkpoint = open_checkpoint(args, &dir.join(".checkpoint"))?;
+        let written = plot::export_tiles(
+            &dir,
+            &layers,
+            &options,
+            zooms.clone(),
+            Some(&mut checkpoint),
+        )?;
+        println!("{} tiles generated to {}", written, dir.display());
+        checkpoint.finish()?;
+        return Ok(());
+    }
+
+    let estimate_layers: Vec<(&[geojson::Feature], Option<f64>)> = layers
+        .iter()
+        .map(|layer| (&layer.features[..], layer.simplify_tolerance))
+        .collect();
+    let estimate = plot::estimate_render(&estimate_layers, &options);
+    println!("Estimate: {}", estimate.summary());
+    // Scripts are never blocked; only interactive runs are asked
+    if estimate.is_large() && io::stdin().is_terminal() && !confirm("Large plot. Continue?")? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    if let Some(animation) = args.animate {
+        return render_animation(args, animation, &files, &layers, options);
+    }
+
+    let Some(property) = &args.split_by else {
+        let output = PathBuf::from(OUTPUT_DIR).join(&args.output_template);
+        let report = plot::render_plot(&output, &layers, &options)?;
+        print_warnings(&report.warnings);
+        println!("Combined plot generated to {}", output.display());
+        if options.thumbnail.is_some() {
+            println!(
+                "Thumbnail generated to {}",
+                plot::thumbnail_path(&output).display()
+            );
+        }
+        return Ok(());
+    };
+
+    let values = plot::unique_property_values(&layers, property);
+    if values.is_empty() {
+        return Err(format!("No feature has a '{}' property.", property).into());
+    }
+
+    let checkpoint_name = format!(
+        ".{}.checkpoint",
+        args.output_template.replace(['/', '\\'], "_")
+    );
+    let mut checkpoint = open_checkpoint(args, &PathBuf::from(OUTPUT_DIR).join(checkpoint_name))?;
+
+    // Features without the property (e.g. context layers) appear in every image,
+    // while the extent follows the features carrying the current value unless
+    // one is given.
+    let names = output_names(&args.output_template, &values);
+    for (value, name) in values.iter().zip(names) {
+        if checkpoint.is_done(value) {
+            continue;
+        }
+        let output = PathBuf::from(OUTPUT_DIR).join(name);
+        let has_value = |feature: &geojson::Feature| match feature.property(property) {
+            Some(v) if !v.is_null() => plot::property_to_string(v) == *value,
+            _ => false,
+        };
+        options.caption = match &args.title {
+            Some(title) => title.replace("{value}", value),
+            None => value.clone(),
+        };
+        if args.extent.is_none() {
+            options.extent = plot::features_bbox(&layers, &has_value).map(plot::pad_bbox);
+        }
+        let report = plot::render_plot_filtered(&output, &layers, &options, &|feature| {
+            has_value(feature) || feature.property(property).is_none_or(|v| v.is_null())
+        })?;
+        print_warnings(&report.warnings);
+        checkpoint.mark_done(value)?;
+        println!(
+            "Plot for {} = {} generated to {}",
+            property,
+            value,
+            output.display()
+        );
+    }
+    checkpoint.finish()?;
+    if options.thumbnail.is_some() {
+        println!("{} plots and thumbnails generated.", values.len());
+    } else {
+        println!("{} plots generated.", values.len());
+    }
+    Ok(())
+}
+
+// Renders the frames of `animation` into an animated GIF or numbered PNGs, all
+// with the extent of every feature of every frame unless one is given
+fn render_animation(
+    args: &BatchArgs,
+    animation: Animation,
+    files: &[&String],
+    layers: &[PlotLayer],
+    mut options: PlotOptions,
+) -> Result<(), Box<dyn Error>> {
+    // The label of each frame with the layers and time range it shows
+    let frames: Vec<(String, &[PlotLayer], Option<TimeRange>)> = match animation {
+        Animation::Layers => files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| {
+                let name = loader::resolve_input(file)
+                    .file_stem()
+                    .map_or_else(|| file.to_string(), |s| s.to_string_lossy().into_owned());
+                (name, &layers[..=i], None)
+            })
+            .collect(),
+        Animation::Time(step) => {
+            let times = layers
+                .iter()
+                .flat_map(|layer| &layer.features)
+                .filter_map(temporal::feature_time);
+            let (first, last) = times
+                .fold(None, |extent: Option<(i64, i64)>, time| {
+                    Some(extent.map_or((time, time), |(first, last)| {
+                        (first.min(time), last.max(time))
+                    }))
+                })
+                .ok_or("No feature has a time (a `time`, `timestamp`, `date`... property)")?;
+            step.ranges(first, last, animation::MAX_FRAMES)?
+                .into_iter()
+                .map(|range| (range.to_string(), layers, Some(range)))
+                .collect()
+        }
+    };
+    if args.extent.is_none() {
+        options.extent = plot::features_bbox(layers, &|_| true).map(plot::pad_bbox);
+    }
+
+    let output = PathBuf::from(OUTPUT_DIR).join(&args.output_template);
+    let mut gif = animation::is_gif(&output).then(|| GifWriter::new(&output, args.frame_delay));
+    for (i, (label, layers, range)) in frames.iter().enumerate() {
+        options.caption = match &args.title {
+            Some(title) => title.replace("{value}", label),
+            None => label.clone(),
+        };
+        if range.is_some() {
+            options.time_range.clone_from(range);
+        }
+        let report = match &mut gif {
+            Some(gif) => {
+                let (png, report) = plot::render_plot_png(layers, &options)?;
+                gif.add_png(&png)?;
+                report
+            }
+            None => plot::render_plot(&animation::frame_path(&output, i + 1), layers, &options)?,
+        };
+        print_warnings(&report.warnings);
+        println!("Frame {} of {}: {}", i + 1, frames.len(), label);
+    }
+    match gif {
+        Some(gif) => {
+            gif.finish()?;
+            println!(
+                "Animation of {} frames generated to {}",
+                frames.len(),
+                output.display()
+            );
+        }
+        None => println!(
+            "{} frames generated to {} ... {}",
+            frames.len(),
+            animation::frame_path(&output, 1).display(),
+            animation::frame_path(&output, frames.len()).display()
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(args: &str) -> Result<Option<BatchArgs>, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn options_and_files_are_read_in_any_order() {
+        assert!(parsed("").unwrap().is_none());
+        let batch = parsed(
+            "--batch roads.geojson --no-points -o out.svg --layer-width roads.geojson=3 \
+             --simplify 0.01 rivers.geojson --resume --tiles 2-4",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(batch.files, ["roads.geojson", "rivers.geojson"]);
+        assert!(!batch.plot_points && batch.plot_lines);
+        assert_eq!(batch.output_template, "out.svg");
+        assert_eq!(
+            batch.layer_line_widths,
+            [(String::from("roads.geojson"), 3)]
+        );
+        assert_eq!(batch.simplify_tolerance, 0.01);
+        assert_eq!(batch.tile_zooms, Some(2..=4));
+        assert!(batch.resume);
+        assert!(
+            !batch.arguments.contains(&String::from("--resume")),
+            "resuming is the same job"
+        );
+    }
+
+    #[test]
+    fn mistakes_are_reported() {
+        let error = |args| match parsed(args) {
+            Err(e) => e,
+            Ok(_) => panic!("'{}' was accepted", args),
+        };
+        assert!(error("roads.geojson").starts_with("Unknown argument 'roads.geojson'"));
+        assert!(error("--batch --no-lines").starts_with("No input files given"));
+        assert!(error("--batch a.geojson --colour red").starts_with("Unknown option '--colour'"));
+        assert_eq!(error("--batch a.geojson -o"), "Missing value for '-o'.");
+        assert_eq!(
+            error("--batch a.geojson --layer-width b.geojson=2"),
+            "'b.geojson' in --layer-width is not an input file."
+        );
+        assert_eq!(
+            error("--batch a.geojson --tiles 3 --split-by NAME"),
+            "--tiles cannot be combined with --split-by."
+        );
+        assert_eq!(
+            error("--batch a.geojson --shade-outside"),
+            "--shade-outside needs --clip-mask."
+        );
+    }
+
+    #[test]
+    fn zooms_and_tolerances_are_checked() {
+        assert_eq!(parse_zooms("5"), Ok(5..=5));
+        assert_eq!(parse_zooms(" 0 - 6 "), Ok(0..=6));
+        assert!(parse_zooms("6-2").unwrap_err().contains("reversed"));
+        assert!(parse_zooms(&(plot::MAX_TILE_ZOOM + 1).to_string()).is_err());
+        assert_eq!(parse_tolerance(" 0.5"), Ok(0.5));
+        assert!(parse_tolerance("-1").is_err());
+    }
+
+    #[test]
+    fn values_that_sanitize_alike_get_their_own_files() {
+        let values = ["a b", "a_b", "A_B", "a_b_2", "c/d"].map(String::from);
+        assert_eq!(
+            output_names("plot_{value}.png", &values),
+            [
+                "plot_a_b.png",
+                "plot_a_b_2.png",
+                "plot_A_B_3.png",
+                "plot_a_b_2_2.png",
+                "plot_c_d.png"
+            ]
+        );
+        assert_eq!(
+            output_names("out.svg", &values[..2]),
+            ["out_a_b.svg", "out_a_b_2.svg"]
+        );
+    }
+}
@@ -0,0 +1,667 @@
+// clip.rs
+
+use geojson::{Feature, Geometry, Value};
+
+use crate::spatial::EdgeIndex;
+
+// Offset applied to the geometry being clipped while deciding what is inside
+// and where edges cross (in coordinate units, i.e. about 0.1 mm on the ground
+// for degrees). Shared borders, such as a country clipped by itself or by a
+// neighbour, otherwise put vertices exactly on mask edges, which the
+// intersection below cannot decide. The output keeps the original positions.
+const NUDGE: f64 = 1e-9;
+
+pub type Point = [f64; 2];
+
+/// Rings of a clip mask indexed for intersection tests. The inside follows the
+/// even-odd rule, so holes cut out of the mask stay outside.
+pub struct Mask {
+    rings: Vec<Vec<Point>>,
+    bbox: [f64; 4],
+    edges: EdgeIndex,
+}
+
+impl Mask {
+    pub fn new(rings: &[Vec<Vec<f64>>], bbox: [f64; 4]) -> Mask {
+        let rings: Vec<Vec<Point>> = rings.iter().map(|ring| open_ring(ring)).collect();
+        let edges = EdgeIndex::new(&rings);
+        Mask { rings, bbox, edges }
+    }
+
+    // Even-odd test counting the edges crossed by a ray from `p` towards +x,
+    // which can only be edges overlapping the ray's bbox
+    fn contains(&self, p: Point) -> bool {
+        let mut inside = false;
+        for (r, i) in self.edges.in_bbox([p[0], p[1], self.bbox[2], p[1]]) {
+            let ring = &self.rings[r];
+            if crosses_ray(ring[i], ring[(i + 1) % ring.len()], p) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    // Crossings of the segment a-b with mask edges, ordered along a-b
+    fn crossings(&self, a: Point, b: Point) -> Vec<Crossing> {
+        let bbox = [
+            a[0].min(b[0]),
+            a[1].min(b[1]),
+            a[0].max(b[0]),
+            a[1].max(b[1]),
+        ];
+        let mut found: Vec<Crossing> = self
+            .edges
+            .in_bbox(bbox)
+            .filter_map(|(r, i)| {
+                let ring = &self.rings[r];
+                let (c, d) = (ring[i], ring[(i + 1) % ring.len()]);
+                intersect(a, b, c, d).map(|(t, u)| Crossing {
+                    t,
+                    ring: r,
+                    edge: i,
+                    u,
+                })
+            })
+            .collect();
+        found.sort_by(|x, y| x.t.total_cmp(&y.t));
+        found
+    }
+}
+
+struct Crossing {
+    t: f64,
+    ring: usize,
+    edge: usize,
+    u: f64,
+}
+
+/// The parts of `features` inside `mask`: points outside are dropped, lines are
+/// cut where they leave it and polygons are intersected with it. Features left
+/// without geometry are removed; properties are kept as they are.
+pub fn clip_features(features: &[Feature], mask: &Mask) -> Vec<Feature> {
+    features
+        .iter()
+        .filter_map(|feature| {
+            let geometry = feature.geometry.as_ref()?;
+            let value = clip_value(&geometry.value, mask)?;
+            Some(Feature {
+                bbox: None,
+                geometry: Some(Geometry::new(value)),
+                ..feature.clone()
+            })
+        })
+        .collect()
+}
+
+fn clip_value(value: &Value, mask: &Mask) -> Option<Value> {
+    match value {
+        Value::Point(p) => mask.contains(nudged(p)).then(|| Value::Point(p.clone())),
+        Value::MultiPoint(points) => {
+            let inside: Vec<Vec<f64>> = points
+                .iter()
+                .filter(|p| p.len() >= 2 && mask.contains(nudged(p)))
+                .cloned()
+                .collect();
+            multi(inside, Value::Point, Value::MultiPoint)
+        }
+        Value::LineString(line) => multi(
+            clip_line(line, mask),
+            Value::LineString,
+            Value::MultiLineString,
+        ),
+        Value::MultiLineString(lines) => {
+            let pieces = lines
+                .iter()
+                .flat_map(|line| clip_line(line, mask))
+                .collect();
+            multi(pieces, Value::LineString, Value::MultiLineString)
+        }
+        Value::Polygon(rings) => multi(
+            clip_polygon(rings, mask),
+            Value::Polygon,
+            Value::MultiPolygon,
+        ),
+        // Polygons of a MultiPolygon never overlap, so their rings are clipped together
+        Value::MultiPolygon(polygons) => {
+            let rings: Vec<Vec<Vec<f64>>> = polygons.iter().flatten().cloned().collect();
+            multi(
+                clip_polygon(&rings, mask),
+                Value::Polygon,
+                Value::MultiPolygon,
+            )
+        }
+        Value::GeometryCollection(geometries) => {
+            let clipped: Vec<Geometry> = geometries
+                .iter()
+                .filter_map(|g| clip_value(&g.value, mask).map(Geometry::new))
+                .collect();
+            (!clipped.is_empty()).then_some(Value::GeometryCollection(clipped))
+        }
+    }
+}
+
+// `None` for no parts, the single variant for one and the multi variant otherwise
+fn multi<T>(mut parts: Vec<T>, single: fn(T) -> Value, many: fn(Vec<T>) -> Value) -> Option<Value> {
+    match parts.len() {
+        0 => None,
+        1 => parts.pop().map(single),
+        _ => Some(many(parts)),
+    }
+}
+
+// Pieces of `line` inside the mask; extra coordinates (e.g. elevation) of the
+// original positions are kept, and elevation is interpolated at the crossings
+fn clip_line(line: &[Vec<f64>], mask: &Mask) -> Vec<Vec<Vec<f64>>> {
+    let line: Vec<&Vec<f64>> = line.iter().filter(|p| p.len() >= 2).collect();
+    let Some(first) = line.first() else {
+        return Vec::new();
+    };
+    let mut inside = mask.contains(nudged(first));
+    let mut pieces = Vec::new();
+    let mut piece: Vec<Vec<f64>> = Vec::new();
+    if inside {
+        piece.push(first.to_vec());
+    }
+    for pair in line.windows(2) {
+        let (a, b) = (nudged(pair[0]), nudged(pair[1]));
+        for crossing in mask.crossings(a, b) {
+            let p = lerp(pair[0], pair[1], crossing.t);
+            let mut position = vec![p[0], p[1]];
+            if let (Some(za), Some(zb)) = (pair[0].get(2), pair[1].get(2)) {
+                position.push(za + (zb - za) * crossing.t);
+            }
+            piece.push(position);
+            if inside {
+                pieces.push(std::mem::take(&mut piece));
+            }
+            inside = !inside;
+        }
+        if inside {
+            piece.push(pair[1].to_vec());
+        }
+    }
+    if inside {
+        pieces.push(piece);
+    }
+    pieces.retain(|piece| piece.len() >= 2);
+    pieces
+}
+
+// Vertex of a ring taking part in the intersection: an original vertex or a
+// crossing of the subject and the mask, linked to its twin in the other polygon
+#[derive(Clone)]
+struct Node {
+    point: Point,       // Where the intersection sees it (subject vertices nudged)
+    position: Vec<f64>, // Written to the output, with any extra coordinates
+    next: usize,
+    prev: usize,
+    twin: Option<usize>, // Index of the same crossing in the other polygon
+    entry: bool,         // Crossing where the boundary walked forward enters the other polygon
+    visited: bool,
+}
+
+// Intersection of the polygon with rings `rings` and the mask (Greiner-Hormann,
+// extended to several rings per polygon with the even-odd rule). Returns the
+// resulting polygons, exterior ring first, as closed GeoJSON rings. Vertices of
+// the polygon keep their positions, including extra coordinates such as
+// elevation; crossings lie on the mask border, with the elevation of the
+// polygon edge there, and mask vertices take the extra coordinates of the
+// vertex before them.
+fn clip_polygon(rings: &[Vec<Vec<f64>>], mask: &Mask) -> Vec<Vec<Vec<Vec<f64>>>> {
+    let originals: Vec<Vec<Vec<f64>>> = rings
+        .iter()
+        .map(|ring| open_positions(ring))
+        .filter(|ring| ring.len() >= 3)
+        .collect();
+    if originals.is_empty() {
+        return Vec::new();
+    }
+    let subject: Vec<Vec<Point>> = originals
+        .iter()
+        .map(|ring| ring.iter().map(|p| nudged(p)).collect())
+        .collect();
+
+    // Crossings along every subject edge and every mask edge, as (parameter,
+    // crossing id); the id links both insertions
+    let mut points = Vec::new();
+    let mut positions = Vec::new();
+    let mut on_subject: Vec<Vec<Vec<(f64, usize)>>> = subject
+        .iter()
+        .map(|ring| vec![Vec::new(); ring.len()])
+        .collect();
+    let mut on_mask: Vec<Vec<Vec<(f64, usize)>>> = mask
+        .rings
+        .iter()
+        .map(|ring| vec![Vec::new(); ring.len()])
+        .collect();
+    for (r, ring) in subject.iter().enumerate() {
+        for i in 0..ring.len() {
+            let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+            let next = (i + 1) % ring.len();
+            for crossing in mask.crossings(a, b) {
+                let id = points.len();
+                points.push(lerp(&a, &b, crossing.t));
+                let edge = &mask.rings[crossing.ring];
+                let (c, d) = (edge[crossing.edge], edge[(crossing.edge + 1) % edge.len()]);
+                positions.push(crossing_position(
+                    &originals[r][i],
+                    &originals[r][next],
+                    crossing.t,
+                    lerp(&c, &d, crossing.u),
+                ));
+                on_subject[r][i].push((crossing.t, id));
+                on_mask[crossing.ring][crossing.edge].push((crossing.u, id));
+            }
+        }
+    }
+
+    let mut result: Vec<(Vec<Point>, Vec<Vec<f64>>)> = Vec::new();
+    // Rings without crossings lie entirely inside or outside the other polygon
+    for (r, ring) in subject.iter().enumerate() {
+        if on_subject[r].iter().all(Vec::is_empty) && mask.contains(ring[0]) {
+            result.push((ring.clone(), originals[r].clone()));
+        }
+    }
+    for (r, ring) in mask.rings.iter().enumerate() {
+        if on_mask[r].iter().all(Vec::is_empty) && contains(&subject, ring[0]) {
+            let positions = ring.iter().map(|p| p.to_vec()).collect();
+            result.push((ring.clone(), positions));
+        }
+    }
+
+    if !points.is_empty() {
+        let mut crossing_nodes = vec![(0, 0); points.len()];
+        let subject_nodes = link(
+            (&subject, &originals),
+            &mut on_subject,
+            (&points, &positions),
+            &mask.rings,
+            &mut crossing_nodes,
+            true,
+        );
+        let mask_positions: Vec<Vec<Vec<f64>>> = mask
+            .rings
+            .iter()
+            .map(|ring| ring.iter().map(|p| p.to_vec()).collect())
+            .collect();
+        let mask_nodes = link(
+            (&mask.rings, &mask_positions),
+            &mut on_mask,
+            (&points, &positions),
+            &subject,
+            &mut crossing_nodes,
+            false,
+        );
+        let mut polygons = [subject_nodes, mask_nodes];
+        for &(s, m) in &crossing_nodes {
+            polygons[0][s].twin = Some(m);
+            polygons[1][m].twin = Some(s);
+        }
+        result.extend(traverse(&mut polygons, &crossing_nodes));
+    }
+    for (_, ring) in &mut result {
+        fill_extra_coordinates(ring);
+    }
+    nest_rings(result)
+}
+
+// Position of a crossing: `at` on the mask border, with the extra coordinates
+// of the subject edge a-b interpolated at `t`
+fn crossing_position(a: &[f64], b: &[f64], t: f64, at: Point) -> Vec<f64> {
+    let mut position = at.to_vec();
+    position.extend(a.iter().zip(b).skip(2).map(|(za, zb)| za + (zb - za) * t));
+    position
+}
+
+// Gives positions of a ring without the extra coordinates of the others (mask
+// vertices) those of the position before them
+fn fill_extra_coordinates(ring: &mut [Vec<f64>]) {
+    let dimensions = ring.iter().map(Vec::len).max().unwrap_or(2);
+    let Some(start) = ring.iter().position(|p| p.len() == dimensions) else {
+        return;
+    };
+    for step in 1..ring.len() {
+        let (previous, i) = ((start + step - 1) % ring.len(), (start + step) % ring.len());
+        if ring[i].len() < dimensions {
+            let extra = ring[previous][ring[i].len()..].to_vec();
+            ring[i].extend(extra);
+        }
+    }
+}
+
+// Builds the circular node lists of `rings` with their crossings inserted in
+// order, marking each crossing as entry or exit of `other`. Records the node of
+// every crossing in `crossing_nodes` (first for the subject, second for the mask).
+fn link(
+    (rings, originals): (&[Vec<Point>], &[Vec<Vec<f64>>]),
+    crossings: &mut [Vec<Vec<(f64, usize)>>],
+    (points, positions): (&[Point], &[Vec<f64>]),
+    other: &[Vec<Point>],
+    crossing_nodes: &mut [(usize, usize)],
+    subject: bool,
+) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    for (r, ring) in rings.iter().enumerate() {
+        let start = nodes.len();
+        let mut inside = contains(other, ring[0]);
+        for (i, &point) in ring.iter().enumerate() {
+            nodes.push(Node {
+                point,
+                position: originals[r][i].clone(),
+                next: 0,
+                prev: 0,
+                twin: None,
+                entry: false,
+                visited: false,
+            });
+            crossings[r][i].sort_by(|a, b| a.0.total_cmp(&b.0));
+            for &(_, id) in &crossings[r][i] {
+                let node = nodes.len();
+                if subject {
+                    crossing_nodes[id].0 = node;
+                } else {
+                    crossing_nodes[id].1 = node;
+                }
+                nodes.push(Node {
+                    point: points[id],
+                    position: positions[id].clone(),
+                    next: 0,
+                    prev: 0,
+                    twin: None,
+                    entry: !inside,
+                    visited: false,
+                });
+                inside = !inside;
+            }
+        }
+        let end = nodes.len();
+        for (n, node) in nodes.iter_mut().enumerate().skip(start) {
+            node.next = if n + 1 == end { start } else { n + 1 };
+            node.prev = if n == start { end - 1 } else { n - 1 };
+        }
+    }
+    nodes
+}
+
+// Walks from every unvisited crossing along the subject and mask boundaries,
+// forward after entering the other polygon and backward after leaving it,
+// switching polygons at each crossing until the ring closes
+fn traverse(
+    polygons: &mut [Vec<Node>; 2],
+    crossing_nodes: &[(usize, usize)],
+) -> Vec<(Vec<Point>, Vec<Vec<f64>>)> {
+    let mut rings = Vec::new();
+    for &(start, _) in crossing_nodes {
+        if polygons[0][start].visited {
+            continue;
+        }
+        let mut ring = vec![polygons[0][start].point];
+        let mut positions = vec![polygons[0][start].position.clone()];
+        let (mut side, mut node) = (0, start);
+        // Bounded by the node count, so inconsistent input cannot loop forever
+        let limit = polygons[0].len() + polygons[1].len();
+        for _ in 0..limit {
+            let forward = polygons[side][node].entry;
+            let twin = loop {
+                node = if forward {
+                    polygons[side][node].next
+                } else {
+                    polygons[side][node].prev
+                };
+                ring.push(polygons[side][node].point);
+                positions.push(polygons[side][node].position.clone());
+                if let Some(twin) = polygons[side][node].twin {
+                    break twin;
+                }
+            };
+            polygons[side][node].visited = true;
+            polygons[1 - side][twin].visited = true;
+            if (side == 0 && node == start) || (side == 1 && twin == start) {
+                break;
+            }
+            side = 1 - side;
+            node = twin;
+        }
+        polygons[0][start].visited = true;
+        ring.pop();
+        positions.pop();
+        if ring.len() >= 3 {
+            rings.push((ring, positions));
+        }
+    }
+    rings
+}
+
+/// Groups open rings into polygons by how deeply they are nested: rings inside
+/// an even number of others are exteriors, the rest holes of the closest ring
+/// around them. Slivers left along borders the subject shares with the mask,
+/// no wider than the nudge, are dropped.
+pub fn polygons_from_rings(rings: Vec<Vec<Point>>) -> Vec<Vec<Vec<Vec<f64>>>> {
+    nest_rings(
+        rings
+            .into_iter()
+            .map(|ring| {
+                let positions = ring.iter().map(|p| p.to_vec()).collect();
+                (ring, positions)
+            })
+            .collect(),
+    )
+}
+
+// `polygons_from_rings` for rings given both as the points the nesting is
+// decided on and the positions written out
+fn nest_rings(rings: Vec<(Vec<Point>, Vec<Vec<f64>>)>) -> Vec<Vec<Vec<Vec<f64>>>> {
+    let (rings, positions): (Vec<Vec<Point>>, Vec<Vec<Vec<f64>>>) = rings
+        .into_iter()
+        .filter(|(ring, _)| area(ring).abs() > perimeter(ring) * NUDGE * 4.0)
+        .unzip();
+    let parents: Vec<Vec<usize>> = rings
+        .iter()
+        .enumerate()
+        .map(|(i, ring)| {
+            (0..rings.len())
+                .filter(|&j| j != i && contains(std::slice::from_ref(&rings[j]), ring[0]))
+                .collect()
+        })
+        .collect();
+    let mut polygons: Vec<Vec<Vec<Vec<f64>>>> = Vec::new();
+    let mut polygon_of = vec![None; rings.len()];
+    for i in 0..rings.len() {
+        if parents[i].len().is_multiple_of(2) {
+            polygon_of[i] = Some(polygons.len());
+            polygons.push(vec![closed_ring(&positions[i])]);
+        }
+    }
+    for i in 0..rings.len() {
+        if !parents[i].len().is_multiple_of(2) {
+            // The closest exterior around a hole is nested one level less deep
+            let owner = parents[i]
+                .iter()
+                .find(|&&j| parents[j].len() + 1 == parents[i].len())
+                .and_then(|&j| polygon_of[j]);
+            if let Some(polygon) = owner {
+                polygons[polygon].push(closed_ring(&positions[i]));
+            }
+        }
+    }
+    polygons
+}
+
+// Position shifted by `NUDGE`, in a direction no straight border follows
+fn nudged(p: &[f64]) -> Point {
+    [p[0] + NUDGE, p[1] + NUDGE * 0.618]
+}
+
+// Full positions of a GeoJSON ring without its closing duplicate of the first one
+fn open_positions(ring: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let mut positions: Vec<Vec<f64>> = ring.iter().filter(|p| p.len() >= 2).cloned().collect();
+    if positions.len() > 1 && positions.first() == positions.last() {
+        positions.pop();
+    }
+    positions
+}
+
+// Positions of a GeoJSON ring without its closing duplicate of the first one
+fn open_ring(ring: &[Vec<f64>]) -> Vec<Point> {
+    let mut points: Vec<Point> = ring
+        .iter()
+        .filter(|p| p.len() >= 2)
+        .map(|p| [p[0], p[1]])
+        .collect();
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    points
+}
+
+fn closed_ring<P: AsRef<[f64]>>(ring: &[P]) -> Vec<Vec<f64>> {
+    ring.iter()
+        .chain(ring.first())
+        .map(|p| p.as_ref().to_vec())
+        .collect()
+}
+
+/// Whether `p` lies inside `rings` by the even-odd rule.
+pub fn contains(rings: &[Vec<Point>], p: Point) -> bool {
+    let mut inside = false;
+    for ring in rings {
+        for (i, &a) in ring.iter().enumerate() {
+            if crosses_ray(a, ring[(i + 1) % ring.len()], p) {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+// Whether the edge a-b crosses the ray from `p` towards +x
+fn crosses_ray(a: Point, b: Point, p: Point) -> bool {
+    (a[1] > p[1]) != (b[1] > p[1]) && p[0] < a[0] + (p[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0])
+}
+
+// Parameters along a-b and c-d at which the two segments properly cross
+fn intersect(a: Point, b: Point, c: Point, d: Point) -> Option<(f64, f64)> {
+    let (r, s) = ([b[0] - a[0], b[1] - a[1]], [d[0] - c[0], d[1] - c[1]]);
+    let denominator = r[0] * s[1] - r[1] * s[0];
+    if denominator == 0.0 {
+        return None;
+    }
+    let q = [c[0] - a[0], c[1] - a[1]];
+    let t = (q[0] * s[1] - q[1] * s[0]) / denominator;
+    let u = (q[0] * r[1] - q[1] * r[0]) / denominator;
+    (t > 0.0 && t < 1.0 && u > 0.0 && u < 1.0).then_some((t, u))
+}
+
+fn lerp(a: &[f64], b: &[f64], t: f64) -> Point {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+}
+
+fn perimeter(ring: &[Point]) -> f64 {
+    let mut sum = 0.0;
+    for (i, a) in ring.iter().enumerate() {
+        let b = ring[(i + 1) % ring.len()];
+        sum += (b[0] - a[0]).hypot(b[1] - a[1]);
+    }
+    sum
+}
+
+/// Shoelace area of an open ring, positive if it runs counterclockwise.
+pub fn area(ring: &[Point]) -> f64 {
+    let mut sum = 0.0;
+    for (i, a) in ring.iter().enumerate() {
+        let b = ring[(i + 1) % ring.len()];
+        sum += a[0] * b[1] - b[0] * a[1];
+    }
+    sum / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x0: f64, y0: f64, x1: f64, y1: f64) -> Vec<Vec<f64>> {
+        vec![
+            vec![x0, y0],
+            vec![x1, y0],
+            vec![x1, y1],
+            vec![x0, y1],
+            vec![x0, y0],
+        ]
+    }
+
+    fn unit_mask() -> Mask {
+        Mask::new(&[square(0.0, 0.0, 1.0, 1.0)], [0.0, 0.0, 1.0, 1.0])
+    }
+
+    fn ring_area(ring: &[Vec<f64>]) -> f64 {
+        area(&open_ring(ring))
+    }
+
+    #[test]
+    fn vertices_inside_keep_their_positions() {
+        let triangle = vec![
+            vec![0.5, 0.5, 10.0],
+            vec![2.0, 0.5, 20.0],
+            vec![0.5, 0.25, 30.0],
+            vec![0.5, 0.5, 10.0],
+        ];
+        let polygons = clip_polygon(&[triangle], &unit_mask());
+        assert_eq!(polygons.len(), 1);
+        let ring = &polygons[0][0];
+        assert!(ring.contains(&vec![0.5, 0.5, 10.0]));
+        assert!(ring.contains(&vec![0.5, 0.25, 30.0]));
+        // Crossings lie on the mask border with interpolated elevation
+        assert!(ring.iter().all(|p| p.len() == 3 && p[0] <= 1.0));
+        assert!(ring.iter().any(|p| p[0] == 1.0));
+    }
+
+    #[test]
+    fn polygon_is_cut_to_the_mask() {
+        let polygons = clip_polygon(&[square(0.5, -1.0, 2.0, 0.5)], &unit_mask());
+        assert_eq!(polygons.len(), 1);
+        assert!((ring_area(&polygons[0][0]).abs() - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn polygon_clipped_by_itself_is_unchanged() {
+        let polygons = clip_polygon(&[square(0.0, 0.0, 1.0, 1.0)], &unit_mask());
+        assert_eq!(polygons.len(), 1);
+        assert!((ring_area(&polygons[0][0]).abs() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mask_holes_stay_outside() {
+        let mask = Mask::new(
+            &[square(0.0, 0.0, 4.0, 4.0), square(1.0, 1.0, 3.0, 3.0)],
+            [0.0, 0.0, 4.0, 4.0],
+        );
+        let polygons = clip_polygon(&[square(-1.0, -1.0, 5.0, 5.0)], &mask);
+        assert_eq!(polygons.len(), 1);
+        assert_eq!(polygons[0].len(), 2);
+        assert!((ring_area(&polygons[0][0]).abs() - 16.0).abs() < 1e-9);
+        assert!((ring_area(&polygons[0][1]).abs() - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lines_are_cut_where_they_leave() {
+        let line = vec![
+            vec![-1.0, 0.5, 0.0],
+            vec![0.5, 0.5, 15.0],
+            vec![2.0, 0.5, 30.0],
+        ];
+        let pieces = clip_line(&line, &unit_mask());
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(pieces[0][1], vec![0.5, 0.5, 15.0]);
+        assert!((pieces[0][0][0]).abs() < 1e-6 && (pieces[0][0][2] - 10.0).abs() < 1e-6);
+        assert!((pieces[0][2][0] - 1.0).abs() < 1e-6 && (pieces[0][2][2] - 20.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn points_outside_are_dropped() {
+        let points = Value::MultiPoint(vec![vec![0.5, 0.5], vec![1.5, 0.5]]);
+        assert_eq!(
+            clip_value(&points, &unit_mask()),
+            Some(Value::Point(vec![0.5, 0.5]))
+        );
+    }
+}
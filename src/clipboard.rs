@@ -0,0 +1,77 @@
+// clipboard.rs
+
+use arboard::{Clipboard, ImageData};
+use std::{
+    borrow::Cow,
+    sync::{Mutex, OnceLock},
+};
+
+// Opened on first use and kept open while the app runs, since on X11 what was
+// copied is only offered to other programs as long as the clipboard is open
+static CLIPBOARD: OnceLock<Mutex<Option<Clipboard>>> = OnceLock::new();
+
+// Runs `action` on the system clipboard, opening it first if needed
+fn with_clipboard<T>(
+    action: impl FnOnce(&mut Clipboard) -> Result<T, arboard::Error>,
+) -> Result<T, String> {
+    let mut clipboard = CLIPBOARD
+        .get_or_init(|| Mutex::new(None))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if clipboard.is_none() {
+        let opened = Clipboard::new().map_err(|e| format!("No clipboard available: {}", e))?;
+        *clipboard = Some(opened);
+    }
+    let clipboard = clipboard.as_mut().expect("opened above");
+    action(clipboard).map_err(|e| format!("Clipboard error: {}", e))
+}
+
+/// Puts `text` on the system clipboard.
+pub fn copy_text(text: &str) -> Result<(), String> {
+    with_clipboard(|clipboard| clipboard.set_text(text))
+}
+
+/// Puts the PNG image `png` on the system clipboard.
+pub fn copy_png(png: &[u8]) -> Result<(), String> {
+    let image = image_data(png)?;
+    with_clipboard(|clipboard| clipboard.set_image(image))
+}
+
+/// The text on the system clipboard, if it holds any.
+pub fn paste_text() -> Option<String> {
+    with_clipboard(|clipboard| clipboard.get_text())
+        .ok()
+        .map(|text| text.trim().to_string())
+}
+
+// Decodes `png` into the RGBA pixels the clipboard takes
+fn image_data(png: &[u8]) -> Result<ImageData<'static>, String> {
+    let pixels = image::load_from_memory_with_format(png, image::ImageFormat::Png)
+        .map_err(|e| format!("Could not decode the plot image: {}", e))?
+        .to_rgba8();
+    Ok(ImageData {
+        width: pixels.width() as usize,
+        height: pixels.height() as usize,
+        bytes: Cow::Owned(pixels.into_raw()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageOutputFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+
+    #[test]
+    fn png_images_are_copied_as_rgba_pixels() {
+        let mut png = Vec::new();
+        RgbImage::from_pixel(3, 2, Rgb([10, 20, 30]))
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .unwrap();
+        let image = image_data(&png).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.bytes.len(), 3 * 2 * 4);
+        assert_eq!(&image.bytes[..4], &[10, 20, 30, 255]);
+        assert!(image_data(b"not a png").is_err());
+    }
+}
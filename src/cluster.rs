@@ -0,0 +1,93 @@
+// cluster.rs
+
+use std::collections::HashMap;
+
+/// Distance in pixels within which points are clustered when turned on in the TUI.
+pub const DEFAULT_CLUSTER_DISTANCE: u32 = 40;
+
+/// Points gathered around one of them, drawn as one marker.
+#[derive(Debug, Clone)]
+pub struct Cluster {
+    pub x: f64, // Mean position of the members
+    pub y: f64,
+    pub members: Vec<usize>, // Indexes into the clustered points
+}
+
+/// Groups `points` greedily: the first point not yet in a cluster starts one
+/// and takes every other free point within `width` horizontally and `height`
+/// vertically of it (an ellipse, as the two may differ in coordinate units;
+/// both above 0). Clusters come in the order of their first member.
+pub fn cluster_points(points: &[(f64, f64)], width: f64, height: f64) -> Vec<Cluster> {
+    // Grid of cells the size of the distance, so only the 3 x 3 cells around
+    // a point can hold its neighbours
+    let cell_of = |(x, y): (f64, f64)| ((x / width).floor() as i64, (y / height).floor() as i64);
+    let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, &point) in points.iter().enumerate() {
+        cells.entry(cell_of(point)).or_default().push(i);
+    }
+    let mut taken = vec![false; points.len()];
+    let mut clusters = Vec::new();
+    for (i, &(x, y)) in points.iter().enumerate() {
+        if taken[i] {
+            continue;
+        }
+        let (column, row) = cell_of((x, y));
+        let mut members = Vec::new();
+        for &neighbour in (column - 1..=column + 1)
+            .flat_map(|c| (row - 1..=row + 1).map(move |r| (c, r)))
+            .filter_map(|cell| cells.get(&cell))
+            .flatten()
+        {
+            let (dx, dy) = (
+                (points[neighbour].0 - x) / width,
+                (points[neighbour].1 - y) / height,
+            );
+            if !taken[neighbour] && dx * dx + dy * dy <= 1.0 {
+                taken[neighbour] = true;
+                members.push(neighbour);
+            }
+        }
+        members.sort_unstable();
+        let count = members.len() as f64;
+        let (sum_x, sum_y) = members.iter().fold((0.0, 0.0), |(sx, sy), &m| {
+            (sx + points[m].0, sy + points[m].1)
+        });
+        clusters.push(Cluster {
+            x: sum_x / count,
+            y: sum_y / count,
+            members,
+        });
+    }
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_join_the_first_free_point_within_reach() {
+        let points = [
+            (0.0, 0.0),
+            (30.0, 0.0),
+            (60.0, 0.0),
+            (0.0, 15.0),
+            (100.0, 100.0),
+        ];
+        let clusters = cluster_points(&points, 40.0, 20.0);
+        let members: Vec<&[usize]> = clusters.iter().map(|c| &c.members[..]).collect();
+        // (60, 0) is within reach of (30, 0) but not of the point it clustered around
+        assert_eq!(members, [&[0, 1, 3][..], &[2], &[4]]);
+        assert_eq!((clusters[0].x, clusters[0].y), (10.0, 5.0));
+        assert_eq!((clusters[2].x, clusters[2].y), (100.0, 100.0));
+    }
+
+    #[test]
+    fn the_reach_is_an_ellipse() {
+        // Within 40 across and 20 up, but not both at once
+        let points = [(0.0, 0.0), (39.0, 0.0), (0.0, -19.0), (30.0, 15.0)];
+        let clusters = cluster_points(&points, 40.0, 20.0);
+        assert_eq!(clusters[0].members, [0, 1, 2]);
+        assert_eq!(clusters[1].members, [3]);
+    }
+}
@@ -0,0 +1,259 @@
+// config.rs
+
+use std::{collections::HashMap, fs, io};
+
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// A single value from the config file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+    Str(String),
+    List(Vec<String>),
+    Bool(bool),
+    Number(f64),
+}
+
+impl ConfigValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ConfigValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Strings are treated as single-element lists.
+    pub fn as_list(&self) -> Option<Vec<String>> {
+        match self {
+            ConfigValue::Str(s) => Some(vec![s.clone()]),
+            ConfigValue::List(items) => Some(items.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ConfigValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ConfigValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+/// Parsed `config.toml`: `[section]` headers followed by `key = value` lines.
+/// Keys before the first header belong to the "" section.
+#[derive(Debug, Default, Clone)]
+pub struct Config {
+    pub sections: HashMap<String, HashMap<String, ConfigValue>>,
+}
+
+impl Config {
+    /// Returns all entries of `section`, if it exists.
+    pub fn section(&self, section: &str) -> Option<&HashMap<String, ConfigValue>> {
+        self.sections.get(section)
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&ConfigValue> {
+        self.sections.get(section)?.get(key)
+    }
+}
+
+/// Loads `config.toml` from the working directory. A missing file yields the default config.
+pub fn load_config() -> Result<Config, String> {
+    match fs::read_to_string(CONFIG_FILE) {
+        Ok(contents) => parse_config(&contents),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(format!("Failed to read {}: {}", CONFIG_FILE, e)),
+    }
+}
+
+/// Parses the small subset of TOML used by the config file: sections, strings,
+/// booleans, numbers and single-line arrays of strings.
+pub fn parse_config(contents: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    let mut current_section = String::new();
+
+    for (line_no, raw_line) in contents.lines().enumerate() {
+        let line = strip_comment(raw_line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |msg: &str| format!("{} line {}: {}", CONFIG_FILE, line_no + 1, msg);
+
+        if let Some(section) = line.strip_prefix('[') {
+            let section = section
+                .strip_suffix(']')
+                .ok_or_else(|| error("unterminated section header"))?;
+            current_section = section.trim().to_string();
+            config.sections.entry(current_section.clone()).or_default();
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected `key = value`"))?;
+        let key = key.trim().trim_matches('"').to_string();
+        let value = parse_value(value.trim()).map_err(|e| error(&e))?;
+        config
+            .sections
+            .entry(current_section.clone())
+            .or_default()
+            .insert(key, value);
+    }
+    Ok(config)
+}
+
+// Removes a trailing `# comment`, ignoring `#` inside quoted strings
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false; // The previous character of the string was a backslash
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// The text of a quoted string, where `\"` is a quote and `\\` a backslash;
+// other backslashes are kept, so Windows paths need no escaping
+fn parse_string(value: &str) -> Result<String, String> {
+    let inner = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(|| format!("invalid string {}", value))?;
+    let mut text = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(escaped @ ('"' | '\\')) => text.push(escaped),
+            Some(other) => {
+                text.push(c);
+                text.push(other);
+            }
+            // The closing quote was escaped
+            None => return Err(format!("unterminated string {}", value)),
+        }
+    }
+    Ok(text)
+}
+
+fn parse_value(value: &str) -> Result<ConfigValue, String> {
+    if value.starts_with('"') {
+        return parse_string(value).map(ConfigValue::Str);
+    }
+    if let Some(items) = value.strip_prefix('[') {
+        let items = items
+            .strip_suffix(']')
+            .ok_or_else(|| String::from("unterminated array"))?;
+        return split_array_items(items)
+            .into_iter()
+            .map(parse_string)
+            .collect::<Result<Vec<_>, _>>()
+            .map(ConfigValue::List);
+    }
+    match value {
+        "true" => Ok(ConfigValue::Bool(true)),
+        "false" => Ok(ConfigValue::Bool(false)),
+        _ => value
+            .parse::<f64>()
+            .map(ConfigValue::Number)
+            .map_err(|_| format!("invalid value {}", value)),
+    }
+}
+
+// Splits `"a", "b"` on commas outside of quotes
+fn split_array_items(items: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in items.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ',' if !in_string => {
+                result.push(items[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    result.push(items[start..].trim());
+    result.retain(|item| !item.is_empty());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_hold_typed_values() {
+        let config = parse_config(
+            "top = 1\n\n[plot]  # defaults\n  width = 1920.5\n\"dark mode\" = true\nlayers = []\n",
+        )
+        .unwrap();
+        assert_eq!(config.get("", "top").and_then(|v| v.as_f64()), Some(1.0));
+        assert_eq!(
+            config.get("plot", "width").and_then(|v| v.as_f64()),
+            Some(1920.5)
+        );
+        assert_eq!(
+            config.get("plot", "dark mode").and_then(|v| v.as_bool()),
+            Some(true)
+        );
+        assert_eq!(
+            config.get("plot", "layers").and_then(|v| v.as_list()),
+            Some(Vec::new())
+        );
+        assert_eq!(config.get("plot", "height"), None);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = |contents| parse_config(contents).unwrap_err();
+        assert_eq!(
+            error("[plot]\nwidth = wide"),
+            format!("{} line 2: invalid value wide", CONFIG_FILE)
+        );
+        assert!(error("[plot").ends_with("unterminated section header"));
+        assert!(error("width").ends_with("expected `key = value`"));
+        assert!(error("tags = [\"a\"").ends_with("unterminated array"));
+    }
+
+    #[test]
+    fn escaped_quotes_do_not_end_strings() {
+        let config = parse_config(
+            r#"
+[export]
+title = "a \" # b" # comment
+folder = "C:\data\\" # comment
+tags = ["x \", y", "z"]
+"#,
+        )
+        .unwrap();
+        let value = |key| config.get("export", key).unwrap();
+        assert_eq!(value("title").as_str(), Some(r#"a " # b"#));
+        assert_eq!(value("folder").as_str(), Some(r"C:\data\"));
+        assert_eq!(
+            value("tags").as_list(),
+            Some(vec![String::from(r#"x ", y"#), String::from("z")])
+        );
+        assert!(parse_config(r#"title = "a \""#).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+// crs.rs
+
+use geojson::{Feature, Value};
+
+const EARTH_RADIUS_M: f64 = 6_378_137.0; // Spherical (Web) Mercator radius
+const MAX_MERCATOR_LATITUDE: f64 = 85.051_128_78; // Latitude of the square world's edge
+
+/// Half the width (and height) of the Web Mercator world in metres.
+pub const WEB_MERCATOR_EXTENT: f64 = EARTH_RADIUS_M * std::f64::consts::PI;
+
+// EPSG codes of Web Mercator, which is reprojected without proj
+const WEB_MERCATOR_CODES: [u32; 4] = [3857, 3785, 900913, 102100];
+
+// WGS 84 ellipsoid and UTM constants, for reprojecting UTM zones without proj
+const WGS84_FLATTENING: f64 = 1.0 / 298.257_223_563;
+const UTM_SCALE: f64 = 0.9996;
+const UTM_FALSE_EASTING: f64 = 500_000.0;
+const UTM_FALSE_NORTHING_SOUTH: f64 = 10_000_000.0;
+
+/// Name of the coordinate reference system declared by the legacy (GeoJSON
+/// 2008) `crs` member of `value`, e.g. `urn:ogc:def:crs:EPSG::3857`.
+pub fn crs_name(value: &serde_json::Value) -> Option<String> {
+    let crs = value.get("crs")?;
+    let properties = crs.get("properties")?;
+    match crs.get("type")?.as_str()? {
+        "name" => Some(properties.get("name")?.as_str()?.to_string()),
+        "EPSG" => Some(format!("EPSG:{}", properties.get("code")?)),
+        "link" => Some(properties.get("href")?.as_str()?.to_string()),
+        _ => None,
+    }
+}
+
+/// EPSG code of a CRS name such as `EPSG:3857` or `urn:ogc:def:crs:EPSG::3857`.
+pub fn epsg_code(name: &str) -> Option<u32> {
+    if !name.to_uppercase().contains("EPSG") {
+        return None;
+    }
+    name.rsplit([':', '/']).next()?.trim().parse().ok()
+}
+
+/// Whether `name` is longitude/latitude on WGS 84, i.e. what GeoJSON coordinates
+/// are without a `crs` member.
+pub fn is_wgs84(name: &str) -> bool {
+    name.to_uppercase().ends_with("CRS84") || epsg_code(name) == Some(4326)
+}
+
+// UTM zone and hemisphere of the WGS 84 / UTM codes EPSG:32601-32660 (north)
+// and EPSG:32701-32760 (south)
+fn utm_zone(code: u32) -> Option<(u32, bool)> {
+    match code {
+        32601..=32660 => Some((code - 32600, true)),
+        32701..=32760 => Some((code - 32700, false)),
+        _ => None,
+    }
+}
+
+/// Whether `crs` can be reprojected without the `proj` feature.
+pub fn is_builtin(crs: &str) -> bool {
+    is_wgs84(crs)
+        || epsg_code(crs)
+            .is_some_and(|code| WEB_MERCATOR_CODES.contains(&code) || utm_zone(code).is_some())
+}
+
+/// Parses a CRS typed by the user: an EPSG code (`32633` or `EPSG:32633`), a
+/// UTM zone (`UTM33N`, `utm 33s`) or, with the `proj` feature, anything PROJ
+/// understands. Returns the name to store, e.g. `EPSG:32633`.
+pub fn parse_crs(text: &str) -> Result<String, String> {
+    let text = text.trim();
+    let compact = text.to_lowercase().replace(' ', "");
+    if let Some(zone) = compact.strip_prefix("utm") {
+        let (number, north) = match zone.strip_suffix('n') {
+            Some(number) => (number, true),
+            None => (zone.strip_suffix('s').unwrap_or("?"), false),
+        };
+        return match number.parse::<u32>() {
+            Ok(zone @ 1..=60) => Ok(format!("EPSG:{}", if north { 32600 } else { 32700 } + zone)),
+            _ => Err(format!(
+                "'{}' is not a UTM zone like UTM33N or UTM19S",
+                text
+            )),
+        };
+    }
+    let name = match text.parse::<u32>() {
+        Ok(code) => format!("EPSG:{}", code),
+        Err(_) => text.to_string(),
+    };
+    if name.is_empty() {
+        return Err(String::from("Empty CRS"));
+    }
+    if !is_builtin(&name) && !cfg!(feature = "proj") {
+        return Err(format!(
+            "{} needs proj support (build with `--features proj`); without it only WGS 84, Web Mercator and UTM work",
+            name
+        ));
+    }
+    Ok(name)
+}
+
+/// Converts every position of `features` from `crs` to WGS 84 longitude/latitude.
+/// Web Mercator and the WGS 84 UTM zones are always supported; other systems
+/// need the `proj` feature.
+pub fn reproject(features: &mut [Feature], crs: &str) -> Result<(), String> {
+    if is_wgs84(crs) {
+        return Ok(());
+    }
+    let code = epsg_code(crs);
+    if code.is_some_and(|code| WEB_MERCATOR_CODES.contains(&code)) {
+        return transform(features, &mut |x, y| Ok(from_web_mercator(x, y)));
+    }
+    if let Some((zone, north)) = code.and_then(utm_zone) {
+        return transform(features, &mut |x, y| Ok(from_utm(zone, north, x, y)));
+    }
+    reproject_with_proj(features, crs)
+}
+
+#[cfg(feature = "proj")]
+fn reproject_with_proj(features: &mut [Feature], crs: &str) -> Result<(), String> {
+    let source = epsg_code(crs).map_or_else(|| crs.to_string(), |code| format!("EPSG:{}", code));
+    let projection = proj::Proj::new_known_crs(&source, "EPSG:4326", None)
+        .map_err(|e| format!("Cannot reproject from {}: {}", crs, e))?;
+    transform(features, &mut |x, y| {
+        projection.convert((x, y)).map_err(|e| e.to_string())
+    })
+}
+
+#[cfg(not(feature = "proj"))]
+fn reproject_with_proj(_features: &mut [Feature], crs: &str) -> Result<(), String> {
+    Err(format!(
+        "Cannot reproject from {} without proj support (build with `--features proj`)",
+        crs
+    ))
+}
+
+/// Converts every position of `features` from WGS 84 to Web Mercator metres,
+/// clamping latitudes to the edge of the square world.
+pub fn to_web_mercator(features: &mut [Feature]) {
+    let _ = transform(features, &mut |lon, lat| {
+        Ok(lonlat_to_web_mercator(lon, lat))
+    });
+}
+
+pub fn lonlat_to_web_mercator(lon: f64, lat: f64) -> (f64, f64) {
+    let lat = lat
+        .clamp(-MAX_MERCATOR_LATITUDE, MAX_MERCATOR_LATITUDE)
+        .to_radians();
+    let y = (std::f64::consts::FRAC_PI_4 + lat / 2.0).tan().ln();
+    (lon.to_radians() * EARTH_RADIUS_M, y * EARTH_RADIUS_M)
+}
+
+/// Polar stereographic metres of a position, on the sphere of Web Mercator and
+/// true to scale at the pole. Longitude 0 points down from the North Pole and
+/// up from the South Pole, as in EPSG:3995 and EPSG:3031.
+pub fn lonlat_to_polar_stereographic(lon: f64, lat: f64, north: bool) -> (f64, f64) {
+    let (lon, lat) = (lon.to_radians(), lat.to_radians());
+    let toward_pole = if north { -lat } else { lat };
+    let rho = 2.0 * EARTH_RADIUS_M * (std::f64::consts::FRAC_PI_4 + toward_pole / 2.0).tan();
+    if north {
+        (rho * lon.sin(), -rho * lon.cos())
+    } else {
+        (rho * lon.sin(), rho * lon.cos())
+    }
+}
+
+fn from_web_mercator(x: f64, y: f64) -> (f64, f64) {
+    let lon = (x / EARTH_RADIUS_M).to_degrees();
+    let lat = (2.0 * (y / EARTH_RADIUS_M).exp().atan() - std::f64::consts::FRAC_PI_2).to_degrees();
+    (lon, lat)
+}
+
+// Inverse transverse Mercator on the WGS 84 ellipsoid (Snyder, "Map
+// Projections: A Working Manual", eq. 8-12 to 8-25); accurate to well below a
+// metre inside the zone
+fn from_utm(zone: u32, north: bool, easting: f64, northing: f64) -> (f64, f64) {
+    let e2 = WGS84_FLATTENING * (2.0 - WGS84_FLATTENING);
+    let ep2 = e2 / (1.0 - e2);
+    let x = easting - UTM_FALSE_EASTING;
+    let y = if north {
+        northing
+    } else {
+        northing - UTM_FALSE_NORTHING_SOUTH
+    };
+
+    let m = y / UTM_SCALE;
+    let mu = m
+        / (EARTH_RADIUS_M * (1.0 - e2 / 4.0 - 3.0 * e2.powi(2) / 64.0 - 5.0 * e2.powi(3) / 256.0));
+    let e1 = (1.0 - (1.0 - e2).sqrt()) / (1.0 + (1.0 - e2).sqrt());
+    let phi1 = mu
+        + (3.0 * e1 / 2.0 - 27.0 * e1.powi(3) / 32.0) * (2.0 * mu).sin()
+        + (21.0 * e1.powi(2) / 16.0 - 55.0 * e1.powi(4) / 32.0) * (4.0 * mu).sin()
+        + (151.0 * e1.powi(3) / 96.0) * (6.0 * mu).sin()
+        + (1097.0 * e1.powi(4) / 512.0) * (8.0 * mu).sin();
+
+    let (sin1, cos1, tan1) = (phi1.sin(), phi1.cos(), phi1.tan());
+    let n1 = EARTH_RADIUS_M / (1.0 - e2 * sin1 * sin1).sqrt();
+    let t1 = tan1 * tan1;
+    let c1 = ep2 * cos1 * cos1;
+    let r1 = EARTH_RADIUS_M * (1.0 - e2) / (1.0 - e2 * sin1 * sin1).powf(1.5);
+    let d = x / (n1 * UTM_SCALE);
+
+    let lat = phi1
+        - (n1 * tan1 / r1)
+            * (d.powi(2) / 2.0
+                - (5.0 + 3.0 * t1 + 10.0 * c1 - 4.0 * c1 * c1 - 9.0 * ep2) * d.powi(4) / 24.0
+                + (61.0 + 90.0 * t1 + 298.0 * c1 + 45.0 * t1 * t1 - 252.0 * ep2 - 3.0 * c1 * c1)
+                    * d.powi(6)
+                    / 720.0);
+    let lon = (d - (1.0 + 2.0 * t1 + c1) * d.powi(3) / 6.0
+        + (5.0 - 2.0 * c1 + 28.0 * t1 - 3.0 * c1 * c1 + 8.0 * ep2 + 24.0 * t1 * t1) * d.powi(5)
+            / 120.0)
+        / cos1;
+    let central_meridian = zone as f64 * 6.0 - 183.0;
+    (central_meridian + lon.to_degrees(), lat.to_degrees())
+}
+
+type Transform<'a> = dyn FnMut(f64, f64) -> Result<(f64, f64), String> + 'a;
+
+// Applies `f` to the x/y of every position; feature bboxes are dropped since
+// they are in the old system
+fn transform(features: &mut [Feature], f: &mut Transform) -> Result<(), String> {
+    for feature in features {
+        feature.bbox = None;
+        if let Some(geometry) = &mut feature.geometry {
+            geometry.bbox = None;
+            transform_value(&mut geometry.value, f)?;
+        }
+    }
+    Ok(())
+}
+
+fn transform_value(value: &mut Value, f: &mut Transform) -> Result<(), String> {
+    let mut positions = |positions: &mut Vec<Vec<f64>>| -> Result<(), String> {
+        for position in positions {
+            transform_position(position, f)?;
+        }
+        Ok(())
+    };
+    match value {
+        Value::Point(position) => transform_position(position, f)?,
+        Value::MultiPoint(points) | Value::LineString(points) => positions(points)?,
+        Value::MultiLineString(lines) | Value::Polygon(lines) => {
+            for line in lines {
+                positions(line)?;
+            }
+        }
+        Value::MultiPolygon(polygons) => {
+            for ring in polygons.iter_mut().flatten() {
+                positions(ring)?;
+            }
+        }
+        Value::GeometryCollection(geometries) => {
+            for geometry in geometries {
+                geometry.bbox = None;
+                transform_value(&mut geometry.value, f)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn transform_position(position: &mut [f64], f: &mut Transform) -> Result<(), String> {
+    if position.len() >= 2 {
+        (position[0], position[1]) = f(position[0], position[1])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: (f64, f64), expected: (f64, f64)) {
+        let tolerance = 1e-6; // About 0.1 m in degrees
+        assert!(
+            (actual.0 - expected.0).abs() < tolerance && (actual.1 - expected.1).abs() < tolerance,
+            "{:?} is not {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn utm_on_the_central_meridian() {
+        assert_near(from_utm(33, true, 500_000.0, 0.0), (15.0, 0.0));
+        assert_near(from_utm(33, true, 500_000.0, 4_982_950.400), (15.0, 45.0));
+    }
+
+    // Reference positions from a Krüger-series forward transverse Mercator,
+    // exact to the millimetre
+    #[test]
+    fn utm_away_from_the_central_meridian() {
+        assert_near(from_utm(33, true, 602_972.982, 5_762_100.490), (16.5, 52.0));
+        assert_near(
+            from_utm(34, false, 259_583.222, 6_245_888.045),
+            (18.4, -33.9),
+        );
+    }
+
+    #[test]
+    fn utm_is_symmetric_about_the_meridian_and_equator() {
+        let east = from_utm(31, true, 600_000.0, 5_000_000.0);
+        let west = from_utm(31, true, 400_000.0, 5_000_000.0);
+        assert_near((east.0 - 3.0, east.1), (3.0 - west.0, west.1));
+        let south = from_utm(31, false, 600_000.0, 5_000_000.0);
+        assert_near(south, (east.0, -east.1));
+    }
+
+    #[test]
+    fn utm_codes_reproject_features() {
+        let mut features = vec![Feature {
+            bbox: Some(vec![0.0; 4]),
+            geometry: Some(geojson::Geometry::new(Value::Point(vec![
+                500_000.0,
+                4_982_950.400,
+                12.0,
+            ]))),
+            id: None,
+            properties: None,
+            foreign_members: None,
+        }];
+        reproject(&mut features, &parse_crs("UTM33N").unwrap()).unwrap();
+        let Some(Value::Point(position)) = features[0].geometry.as_ref().map(|g| &g.value) else {
+            panic!("not a point");
+        };
+        assert_near((position[0], position[1]), (15.0, 45.0));
+        assert_eq!(position[2], 12.0);
+        assert_eq!(features[0].bbox, None);
+    }
+
+    #[test]
+    fn crs_names() {
+        assert_eq!(parse_crs("utm 19s").unwrap(), "EPSG:32719");
+        assert_eq!(parse_crs("3857").unwrap(), "EPSG:3857");
+        assert!(parse_crs("UTM61N").is_err());
+        assert_eq!(epsg_code("urn:ogc:def:crs:EPSG::32633"), Some(32633));
+        assert!(is_wgs84("urn:ogc:def:crs:OGC:1.3:CRS84"));
+    }
+
+    #[test]
+    fn web_mercator_round_trip() {
+        let (x, y) = lonlat_to_web_mercator(-73.5, 40.7);
+        assert_near(from_web_mercator(x, y), (-73.5, 40.7));
+        assert_near(
+            (lonlat_to_web_mercator(180.0, 0.0).0, 0.0),
+            (WEB_MERCATOR_EXTENT, 0.0),
+        );
+    }
+}
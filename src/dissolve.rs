@@ -0,0 +1,377 @@
+// dissolve.rs
+
+use geojson::{Feature, Geometry, JsonObject, Value};
+use rstar::{AABB, RTree, primitives::GeomWithData};
+use std::collections::HashMap;
+
+use crate::clip::{self, Point};
+
+// Positions closer than this (in coordinate units, about 1 cm for degrees) are
+// treated as the same vertex, so borders stored with rounding differences in
+// neighbouring polygons still match
+const SNAP: f64 = 1e-7;
+
+type Vertex = (i64, i64);
+
+pub type Polygon = Vec<Vec<Vec<f64>>>;
+
+// Features sharing a value of the dissolve property
+struct Group {
+    value: serde_json::Value,
+    count: usize,
+    polygons: Vec<Polygon>,
+}
+
+/// Number of input features merged into a dissolved feature, added to its
+/// properties.
+pub const COUNT_PROPERTY: &str = "feature_count";
+
+/// Unions the polygons of all features sharing a value of `property` into one
+/// feature per value, e.g. to dissolve districts into their states. Borders
+/// shared by two polygons of a group are removed; polygons that only overlap
+/// are kept as separate parts. Features without the property form one group of
+/// their own. Points and lines are ignored. The result carries the property and
+/// the number of features merged.
+pub fn dissolve_features(features: &[Feature], property: &str) -> Vec<Feature> {
+    // Groups in order of their first feature
+    let mut groups: Vec<Group> = Vec::new();
+    let mut group_of: HashMap<String, usize> = HashMap::new();
+    for feature in features {
+        let Some(geometry) = &feature.geometry else {
+            continue;
+        };
+        let mut polygons = Vec::new();
+        collect_polygons(&geometry.value, &mut polygons);
+        if polygons.is_empty() {
+            continue;
+        }
+        let value = feature
+            .property(property)
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+        let index = *group_of.entry(value.to_string()).or_insert_with(|| {
+            groups.push(Group {
+                value,
+                count: 0,
+                polygons: Vec::new(),
+            });
+            groups.len() - 1
+        });
+        groups[index].count += 1;
+        groups[index].polygons.append(&mut polygons);
+    }
+
+    groups
+        .into_iter()
+        .filter_map(|group| {
+            let mut dissolved = dissolve_polygons(&group.polygons);
+            let geometry = match dissolved.len() {
+                0 => return None,
+                1 => Value::Polygon(dissolved.pop()?),
+                _ => Value::MultiPolygon(dissolved),
+            };
+            let mut properties = JsonObject::new();
+            properties.insert(property.to_string(), group.value);
+            properties.insert(COUNT_PROPERTY.to_string(), group.count.into());
+            Some(Feature {
+                bbox: None,
+                geometry: Some(Geometry::new(geometry)),
+                id: None,
+                properties: Some(properties),
+                foreign_members: None,
+            })
+        })
+        .collect()
+}
+
+/// Adds the polygons of `value`, including those inside collections, to `polygons`.
+pub fn collect_polygons(value: &Value, polygons: &mut Vec<Polygon>) {
+    match value {
+        Value::Polygon(rings) => polygons.push(rings.clone()),
+        Value::MultiPolygon(parts) => polygons.extend(parts.iter().cloned()),
+        Value::GeometryCollection(geometries) => {
+            for geometry in geometries {
+                collect_polygons(&geometry.value, polygons);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Merges `polygons` by cancelling every edge that another ring runs along in
+/// the opposite direction. Exteriors are oriented counterclockwise and holes
+/// clockwise first, so a border shared by two neighbours always cancels and the
+/// edges left over keep the inside on their left. Edges are first split at the
+/// vertices of other rings lying on them, so a border also cancels where one
+/// side has more vertices than the other (a T-junction).
+pub fn dissolve_polygons(polygons: &[Polygon]) -> Vec<Polygon> {
+    let mut positions: HashMap<Vertex, Point> = HashMap::new();
+    let mut rings: Vec<Vec<Vertex>> = Vec::new();
+    for polygon in polygons {
+        for (i, ring) in polygon.iter().enumerate() {
+            let mut vertices: Vec<Vertex> = Vec::with_capacity(ring.len());
+            for p in ring.iter().filter(|p| p.len() >= 2) {
+                let vertex = snap(p);
+                positions.entry(vertex).or_insert([p[0], p[1]]);
+                if vertices.last() != Some(&vertex) {
+                    vertices.push(vertex);
+                }
+            }
+            if vertices.len() > 1 && vertices.first() == vertices.last() {
+                vertices.pop();
+            }
+            if vertices.len() < 3 {
+                continue;
+            }
+            let points: Vec<Point> = vertices.iter().map(|v| positions[v]).collect();
+            // Exteriors counterclockwise, holes clockwise
+            if (clip::area(&points) > 0.0) != (i == 0) {
+                vertices.reverse();
+            }
+            rings.push(vertices);
+        }
+    }
+
+    let tree: RTree<GeomWithData<Point, Vertex>> = RTree::bulk_load(
+        positions
+            .keys()
+            .map(|&v| GeomWithData::new(grid_point(v), v))
+            .collect(),
+    );
+    let mut edges: HashMap<(Vertex, Vertex), usize> = HashMap::new();
+    for ring in &rings {
+        for (j, &a) in ring.iter().enumerate() {
+            let b = ring[(j + 1) % ring.len()];
+            let mut path = junctions(a, b, &tree);
+            path.push(b);
+            let mut from = a;
+            for to in path {
+                match edges.get_mut(&(to, from)) {
+                    Some(count) if *count > 0 => *count -= 1,
+                    _ => *edges.entry((from, to)).or_insert(0) += 1,
+                }
+                from = to;
+            }
+        }
+    }
+
+    let mut outgoing: HashMap<Vertex, Vec<Vertex>> = HashMap::new();
+    for (&(a, b), &count) in &edges {
+        for _ in 0..count {
+            outgoing.entry(a).or_default().push(b);
+        }
+    }
+    // Sorted so the result does not depend on the order of the hash map
+    let mut starts: Vec<Vertex> = outgoing.keys().copied().collect();
+    starts.sort_unstable();
+
+    let (mut exteriors, mut holes) = (Vec::new(), Vec::new());
+    for start in starts {
+        while outgoing.get(&start).is_some_and(|next| !next.is_empty()) {
+            let ring = trace_ring(start, &mut outgoing, &positions);
+            if ring.len() < 3 {
+                continue;
+            }
+            let area = clip::area(&ring);
+            if area > 0.0 {
+                exteriors.push((area, ring));
+            } else if area < 0.0 {
+                holes.push(ring);
+            }
+        }
+    }
+
+    // Each hole belongs to the smallest exterior around it
+    let mut polygons: Vec<Vec<Vec<Point>>> = exteriors
+        .iter()
+        .map(|(_, ring)| vec![ring.clone()])
+        .collect();
+    for hole in holes {
+        let owner = exteriors
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, ring))| clip::contains(std::slice::from_ref(ring), hole[0]))
+            .min_by(|a, b| a.1.0.total_cmp(&b.1.0))
+            .map(|(i, _)| i);
+        if let Some(i) = owner {
+            polygons[i].push(hole);
+        }
+    }
+    polygons
+        .into_iter()
+        .map(|rings| rings.iter().map(|ring| closed(ring)).collect())
+        .collect()
+}
+
+// Follows edges from `start` until it is reached again, removing them. Where
+// several edges leave a vertex (polygons touching at a corner), the sharpest
+// left turn is taken, which keeps touching parts in separate rings.
+fn trace_ring(
+    start: Vertex,
+    outgoing: &mut HashMap<Vertex, Vec<Vertex>>,
+    positions: &HashMap<Vertex, Point>,
+) -> Vec<Point> {
+    let mut ring = vec![positions[&start]];
+    let (mut previous, mut current) = (None, start);
+    while let Some(next) = outgoing.get_mut(&current).filter(|next| !next.is_empty()) {
+        let choice = match previous {
+            Some(previous) if next.len() > 1 => {
+                let [px, py] = positions[&previous];
+                let [cx, cy] = positions[&current];
+                let incoming = (cx - px, cy - py);
+                (0..next.len())
+                    .max_by(|&a, &b| {
+                        let turn = |v: Vertex| {
+                            let [nx, ny] = positions[&v];
+                            let out = (nx - cx, ny - cy);
+                            let cross = incoming.0 * out.1 - incoming.1 * out.0;
+                            let dot = incoming.0 * out.0 + incoming.1 * out.1;
+                            cross.atan2(dot)
+                        };
+                        turn(next[a]).total_cmp(&turn(next[b]))
+                    })
+                    .unwrap_or(0)
+            }
+            _ => 0,
+        };
+        let vertex = next.swap_remove(choice);
+        if vertex == start {
+            break;
+        }
+        ring.push(positions[&vertex]);
+        (previous, current) = (Some(current), vertex);
+    }
+    ring
+}
+
+// The vertices strictly between `a` and `b` within a snapping distance of the
+// edge, in order from `a`
+fn junctions(a: Vertex, b: Vertex, tree: &RTree<GeomWithData<Point, Vertex>>) -> Vec<Vertex> {
+    let ([ax, ay], [bx, by]) = (grid_point(a), grid_point(b));
+    let (dx, dy) = (bx - ax, by - ay);
+    let length_squared = dx * dx + dy * dy;
+    let envelope = AABB::from_corners(
+        [ax.min(bx) - 1.0, ay.min(by) - 1.0],
+        [ax.max(bx) + 1.0, ay.max(by) + 1.0],
+    );
+    let mut found: Vec<(f64, Vertex)> = tree
+        .locate_in_envelope(&envelope)
+        .filter(|entry| entry.data != a && entry.data != b)
+        .filter_map(|entry| {
+            let [px, py] = *entry.geom();
+            let t = ((px - ax) * dx + (py - ay) * dy) / length_squared;
+            // Distance from the line in grid units, one unit being `SNAP`
+            let distance = ((px - ax) * dy - (py - ay) * dx).abs() / length_squared.sqrt();
+            (t > 0.0 && t < 1.0 && distance <= 1.0).then_some((t, entry.data))
+        })
+        .collect();
+    found.sort_by(|x, y| x.0.total_cmp(&y.0));
+    found.into_iter().map(|(_, vertex)| vertex).collect()
+}
+
+fn grid_point(v: Vertex) -> Point {
+    [v.0 as f64, v.1 as f64]
+}
+
+fn snap(p: &[f64]) -> Vertex {
+    ((p[0] / SNAP).round() as i64, (p[1] / SNAP).round() as i64)
+}
+
+fn closed(ring: &[Point]) -> Vec<Vec<f64>> {
+    ring.iter()
+        .chain(ring.first())
+        .map(|p| p.to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x0: f64, y0: f64, x1: f64, y1: f64) -> Polygon {
+        vec![vec![
+            vec![x0, y0],
+            vec![x1, y0],
+            vec![x1, y1],
+            vec![x0, y1],
+            vec![x0, y0],
+        ]]
+    }
+
+    fn ring_area(ring: &[Vec<f64>]) -> f64 {
+        let points: Vec<Point> = ring.iter().map(|p| [p[0], p[1]]).collect();
+        clip::area(&points)
+    }
+
+    #[test]
+    fn shared_edge_is_removed() {
+        let dissolved =
+            dissolve_polygons(&[square(0.0, 0.0, 1.0, 1.0), square(1.0, 0.0, 2.0, 1.0)]);
+        assert_eq!(dissolved.len(), 1);
+        assert_eq!(dissolved[0].len(), 1);
+        assert!((ring_area(&dissolved[0][0]) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn t_junction_is_removed() {
+        let dissolved = dissolve_polygons(&[
+            square(0.0, 0.0, 1.0, 1.0),
+            square(1.0, 0.0, 2.0, 1.0),
+            square(0.0, 1.0, 2.0, 2.0),
+        ]);
+        assert_eq!(dissolved.len(), 1);
+        assert_eq!(dissolved[0].len(), 1);
+        assert!((ring_area(&dissolved[0][0]) - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ring_of_neighbours_keeps_its_hole() {
+        let mut squares = Vec::new();
+        for x in 0..3 {
+            for y in 0..3 {
+                if (x, y) != (1, 1) {
+                    let (x, y) = (x as f64, y as f64);
+                    squares.push(square(x, y, x + 1.0, y + 1.0));
+                }
+            }
+        }
+        let dissolved = dissolve_polygons(&squares);
+        assert_eq!(dissolved.len(), 1);
+        assert_eq!(dissolved[0].len(), 2);
+        assert!((ring_area(&dissolved[0][0]) - 9.0).abs() < 1e-9);
+        assert!((ring_area(&dissolved[0][1]) + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn separate_polygons_stay_apart() {
+        let dissolved =
+            dissolve_polygons(&[square(0.0, 0.0, 1.0, 1.0), square(2.0, 0.0, 3.0, 1.0)]);
+        assert_eq!(dissolved.len(), 2);
+    }
+
+    #[test]
+    fn features_are_grouped_by_property() {
+        let feature = |polygon: Polygon, state: &str| {
+            let mut properties = JsonObject::new();
+            properties.insert(String::from("state"), state.into());
+            Feature {
+                bbox: None,
+                geometry: Some(Geometry::new(Value::Polygon(polygon))),
+                id: None,
+                properties: Some(properties),
+                foreign_members: None,
+            }
+        };
+        let dissolved = dissolve_features(
+            &[
+                feature(square(0.0, 0.0, 1.0, 1.0), "A"),
+                feature(square(1.0, 0.0, 2.0, 1.0), "A"),
+                feature(square(2.0, 0.0, 3.0, 1.0), "B"),
+            ],
+            "state",
+        );
+        assert_eq!(dissolved.len(), 2);
+        assert_eq!(dissolved[0].property(COUNT_PROPERTY), Some(&2.into()));
+        assert_eq!(dissolved[1].property("state"), Some(&"B".into()));
+    }
+}
@@ -0,0 +1,184 @@
+// duplicates.rs
+
+use geojson::{Feature, Value};
+use std::collections::BTreeSet;
+
+use crate::plot;
+use crate::spatial::FeatureIndex;
+
+// Length of a degree of latitude, and of longitude at the equator, in metres
+const METRES_PER_DEGREE: f64 = 111_320.0;
+
+/// Tolerance used when none is typed, in degrees (about 1 m).
+pub const DEFAULT_TOLERANCE: f64 = 1.0 / METRES_PER_DEGREE;
+
+/// Parses the tolerance for near-duplicates into degrees: a plain number is in
+/// degrees, a number followed by `m` or `km` in metres (converted at the
+/// equator), e.g. `0.0001`, `5m`. `0` finds exact duplicates only and empty
+/// text gives `DEFAULT_TOLERANCE`.
+pub fn parse_tolerance(text: &str) -> Result<f64, String> {
+    let compact = text.trim().to_lowercase().replace(' ', "");
+    if compact.is_empty() {
+        return Ok(DEFAULT_TOLERANCE);
+    }
+    let (number, scale) = if let Some(number) = compact.strip_suffix("km") {
+        (number, 1000.0 / METRES_PER_DEGREE)
+    } else if let Some(number) = compact.strip_suffix('m') {
+        (number, 1.0 / METRES_PER_DEGREE)
+    } else {
+        (compact.strip_suffix("deg").unwrap_or(&compact), 1.0)
+    };
+    match number.parse::<f64>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => Ok(value * scale),
+        _ => Err(format!(
+            "'{}' is not a tolerance like 0.0001 (degrees), 5m or 0",
+            text.trim()
+        )),
+    }
+}
+
+/// A feature whose geometry repeats that of an earlier feature.
+#[derive(Debug, Clone)]
+pub struct Duplicate {
+    pub position: usize, // Zero-based position of the duplicate in the file
+    pub original: usize, // First feature with the same geometry, which is kept
+    pub exact: bool,     // Identical coordinates, rather than within the tolerance
+}
+
+/// Result of looking for duplicated geometries in one file.
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateReport {
+    pub file: String,
+    pub features: usize,
+    pub tolerance: f64, // In degrees; 0 looks for exact duplicates only
+    pub duplicates: Vec<Duplicate>, // In file order
+}
+
+impl DuplicateReport {
+    pub fn exact_count(&self) -> usize {
+        self.duplicates.iter().filter(|d| d.exact).count()
+    }
+
+    /// The tolerance in degrees and roughly in metres, e.g. "0.0000090° (about 1 m)".
+    pub fn tolerance_label(&self) -> String {
+        let metres = self.tolerance * METRES_PER_DEGREE;
+        if metres < 1000.0 {
+            format!("{:.7}° (about {:.3} m)", self.tolerance, metres)
+        } else {
+            format!("{:.7}° (about {:.3} km)", self.tolerance, metres / 1000.0)
+        }
+        .replace(".000 ", " ")
+    }
+
+    /// One-line outcome, e.g. for the notification bar.
+    pub fn summary(&self) -> String {
+        if self.duplicates.is_empty() {
+            return format!("{}: no duplicates in {} features", self.file, self.features);
+        }
+        let exact = self.exact_count();
+        format!(
+            "{}: {} exact and {} near duplicate{} in {} features",
+            self.file,
+            exact,
+            self.duplicates.len() - exact,
+            if self.duplicates.len() - exact == 1 {
+                ""
+            } else {
+                "s"
+            },
+            self.features
+        )
+    }
+
+    /// `features` without the duplicates found, the first of each kept.
+    pub fn remove_from(&self, features: Vec<Feature>) -> Vec<Feature> {
+        let duplicates: BTreeSet<usize> = self.duplicates.iter().map(|d| d.position).collect();
+        features
+            .into_iter()
+            .enumerate()
+            .filter(|(position, _)| !duplicates.contains(position))
+            .map(|(_, feature)| feature)
+            .collect()
+    }
+}
+
+/// Finds the features of `file` whose geometry repeats an earlier one: exactly,
+/// or with every longitude and latitude within `tolerance` degrees of the
+/// corresponding position. Geometries must have the same type and number of
+/// positions to match; properties are not compared.
+pub fn find_duplicates(file: &str, features: &[Feature], tolerance: f64) -> DuplicateReport {
+    let index = FeatureIndex::new(features);
+    let mut found = vec![false; features.len()];
+    let mut duplicates = Vec::new();
+    for (original, feature) in features.iter().enumerate() {
+        let Some(geometry) = feature.geometry.as_ref().filter(|_| !found[original]) else {
+            continue;
+        };
+        let mut bbox = plot::empty_bbox();
+        plot::extend_bbox(&mut bbox, &geometry.value);
+        let near = [
+            bbox[0] - tolerance,
+            bbox[1] - tolerance,
+            bbox[2] + tolerance,
+            bbox[3] + tolerance,
+        ];
+        for position in index.in_bbox(near) {
+            if position <= original || found[position] {
+                continue;
+            }
+            let Some(other) = &features[position].geometry else {
+                continue;
+            };
+            if same_shape(&geometry.value, &other.value, tolerance) {
+                found[position] = true;
+                duplicates.push(Duplicate {
+                    position,
+                    original,
+                    exact: geometry.value == other.value,
+                });
+            }
+        }
+    }
+    duplicates.sort_by_key(|d| d.position);
+    DuplicateReport {
+        file: file.to_string(),
+        features: features.len(),
+        tolerance,
+        duplicates,
+    }
+}
+
+fn same_shape(a: &Value, b: &Value, tolerance: f64) -> bool {
+    match (a, b) {
+        (Value::Point(p), Value::Point(q)) => same_position(p, q, tolerance),
+        (Value::MultiPoint(a), Value::MultiPoint(b))
+        | (Value::LineString(a), Value::LineString(b)) => same_line(a, b, tolerance),
+        (Value::MultiLineString(a), Value::MultiLineString(b))
+        | (Value::Polygon(a), Value::Polygon(b)) => same_lines(a, b, tolerance),
+        (Value::MultiPolygon(a), Value::MultiPolygon(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_lines(a, b, tolerance))
+        }
+        (Value::GeometryCollection(a), Value::GeometryCollection(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|(a, b)| same_shape(&a.value, &b.value, tolerance))
+        }
+        _ => false,
+    }
+}
+
+fn same_lines(a: &[Vec<Vec<f64>>], b: &[Vec<Vec<f64>>], tolerance: f64) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_line(a, b, tolerance))
+}
+
+fn same_line(a: &[Vec<f64>], b: &[Vec<f64>], tolerance: f64) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(p, q)| same_position(p, q, tolerance))
+}
+
+fn same_position(p: &[f64], q: &[f64], tolerance: f64) -> bool {
+    p.len() >= 2
+        && q.len() >= 2
+        && (p[0] - q[0]).abs() <= tolerance
+        && (p[1] - q[1]).abs() <= tolerance
+}
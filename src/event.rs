@@ -1,11 +1,12 @@
 // event.rs
 use std::{
-    sync::mpsc::{self, Receiver},
+    sync::mpsc::{self, Receiver, Sender},
     thread,
     time::{Duration, Instant},
 };
 
 use crate::app::TerminalEvent;
+use crate::plot::RenderReport;
 use crossterm::event::{self, Event as CrosstermEvent, KeyEvent, MouseEvent};
 
 pub enum Event {
@@ -13,9 +14,16 @@ pub enum Event {
     Input(KeyEvent),
     Mouse(MouseEvent),
     TerminalEvent(TerminalEvent),
+    PlotProgress(usize, usize), // Features drawn and to draw by the plot rendering in the background
+    PlotFinished(PlotResult),
 }
 
+/// Outcome of a plot rendered in the background: what the render reported and
+/// whether the image was copied to the clipboard.
+pub type PlotResult = Result<(RenderReport, bool), String>;
+
 pub struct EventHandler {
+    sender: Sender<Event>,
     receiver: Receiver<Event>,
     #[allow(dead_code)]
     event_thread: thread::JoinHandle<()>,
@@ -24,7 +32,9 @@ pub struct EventHandler {
 impl EventHandler {
     pub fn new(tick_rate: Duration) -> EventHandler {
         let (sender, receiver) = mpsc::channel();
+        let event_sender = sender.clone();
         let event_thread = thread::spawn(move || {
+            let sender = event_sender;
             let mut last_tick = Instant::now();
             loop {
                 let timeout = tick_rate
@@ -55,11 +65,17 @@ impl EventHandler {
             }
         });
         EventHandler {
+            sender,
             receiver,
             event_thread,
         }
     }
 
+    /// Lets background work, such as a plot being rendered, send its own events.
+    pub fn sender(&self) -> Sender<Event> {
+        self.sender.clone()
+    }
+
     pub fn next(&self, timeout: Duration) -> Result<Option<Event>, mpsc::RecvTimeoutError> {
         self.receiver.recv_timeout(timeout).map(Some)
     }
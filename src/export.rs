@@ -0,0 +1,1066 @@
+// export.rs
+
+use geojson::{Feature, FeatureCollection, Value};
+use serde_json::Map;
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::clip;
+use crate::plot::{self, ClipMask};
+use crate::simplify;
+use crate::wkt;
+use plotters::style::RGBColor;
+
+const WGS84_WKT: &str = "GEOGCS[\"WGS 84\",DATUM[\"WGS_1984\",SPHEROID[\"WGS 84\",6378137,298.257223563]],PRIMEM[\"Greenwich\",0],UNIT[\"degree\",0.0174532925199433]]";
+const MAX_TEXT_WIDTH: usize = 254; // Longest text a dBASE field holds
+const MAX_NUMBER_WIDTH: usize = 20; // Wider numbers are written as text
+
+/// A layer to write: a name (e.g. the stem of its input file) and its features.
+pub struct ExportLayer {
+    pub name: String,
+    pub features: Vec<Feature>,
+    pub color: RGBColor, // Assigned plot color, used by HTML maps
+}
+
+/// The formats layers can be written in, chosen by the output extension.
+pub fn is_export_file(path: &Path) -> bool {
+    export_format(path).is_some()
+}
+
+fn export_format(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "geojson" | "json" => Some("geojson"),
+        "shp" => Some("shp"),
+        "gpkg" => Some("gpkg"),
+        "html" | "htm" => Some("html"),
+        _ => None,
+    }
+}
+
+/// `features` as plotted: cut by `mask` and simplified with `tolerance`.
+pub fn prepare_features(
+    features: Vec<Feature>,
+    mask: Option<&ClipMask>,
+    tolerance: f64,
+) -> Vec<Feature> {
+    let mut features = match mask {
+        Some(mask) => mask.clip(&features),
+        None => features,
+    };
+    if tolerance > 0.0 {
+        for geometry in features.iter_mut().filter_map(|f| f.geometry.as_mut()) {
+            geometry.value = simplify::simplify_geometry(&geometry.value, tolerance);
+        }
+    }
+    features
+}
+
+/// Writes `layers` to `path` as GeoJSON, an ESRI Shapefile or a GeoPackage,
+/// by its extension, and returns the files written. GeoJSON and Shapefile
+/// merge the layers, telling them apart by a `layer` attribute if there are
+/// several (`layer_2` and so on if the features have one of their own); a Shapefile holds a single kind of geometry, so mixed layers
+/// become `<name>_points.shp`, `<name>_lines.shp` and `<name>_polygons.shp`.
+/// A GeoPackage gets a table per layer and is written through the `sqlite3`
+/// command, which has to be installed.
+/// HTML writes an interactive Leaflet map titled `title`, with the layers
+/// embedded so the page works as a single file.
+pub fn write_layers(
+    path: &Path,
+    layers: &[ExportLayer],
+    title: &str,
+) -> Result<Vec<PathBuf>, String> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    match export_format(path) {
+        Some("geojson") => write_geojson(path, layers).map(|()| vec![path.to_path_buf()]),
+        Some("shp") => write_shapefiles(path, layers),
+        Some("gpkg") => write_geopackage(path, layers).map(|()| vec![path.to_path_buf()]),
+        Some("html") => write_html(path, layers, title).map(|()| vec![path.to_path_buf()]),
+        _ => Err(format!(
+            "{}: export to .geojson, .shp, .gpkg or .html",
+            path.display()
+        )),
+    }
+}
+
+// The features of all layers, with their layer name as property `layer` if
+// there is more than one. If features already have a `layer` property, the
+// name goes in the first of `layer_2`, `layer_3`... that none of them has.
+fn merged_features(layers: &[ExportLayer]) -> impl Iterator<Item = Feature> + '_ {
+    let key = layer_key(layers);
+    layers.iter().flat_map(move |layer| {
+        let key = key.clone();
+        layer.features.iter().map(move |feature| {
+            let mut feature = feature.clone();
+            if layers.len() > 1 {
+                feature
+                    .properties
+                    .get_or_insert_with(Map::new)
+                    .insert(key.clone(), layer.name.clone().into());
+            }
+            feature
+        })
+    })
+}
+
+// The property that tells merged layers apart, clear of their own properties
+fn layer_key(layers: &[ExportLayer]) -> String {
+    let taken = |key: &str| {
+        layers
+            .iter()
+            .flat_map(|layer| &layer.features)
+            .any(|feature| feature.contains_property(key))
+    };
+    let mut key = String::from("layer");
+    let mut n = 2;
+    while taken(&key) {
+        key = format!("layer_{}", n);
+        n += 1;
+    }
+    key
+}
+
+fn write_geojson(path: &Path, layers: &[ExportLayer]) -> Result<(), String> {
+    let collection = FeatureCollection {
+        bbox: None,
+        features: merged_features(layers).collect(),
+        foreign_members: None,
+    };
+    fs::write(path, collection.to_string()).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// How a property is stored in a dBASE or SQLite column
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnType {
+    Integer,
+    Real,
+    Boolean,
+    Text,
+}
+
+struct Column {
+    key: String, // Property name
+    kind: ColumnType,
+}
+
+// A column per property of `features`, in the order they first appear,
+// typed by the values they hold
+fn columns<'a>(features: impl Iterator<Item = &'a Feature>) -> Vec<Column> {
+    let mut columns: Vec<Column> = Vec::new();
+    for properties in features.filter_map(|f| f.properties.as_ref()) {
+        for (key, value) in properties {
+            let kind = match value {
+                serde_json::Value::Null => continue,
+                serde_json::Value::Bool(_) => ColumnType::Boolean,
+                serde_json::Value::Number(n) if n.is_i64() || n.is_u64() => ColumnType::Integer,
+                serde_json::Value::Number(_) => ColumnType::Real,
+                _ => ColumnType::Text,
+            };
+            match columns.iter_mut().find(|column| column.key == *key) {
+                Some(column) => {
+                    column.kind = match (column.kind, kind) {
+                        (a, b) if a == b => a,
+                        (ColumnType::Integer, ColumnType::Real)
+                        | (ColumnType::Real, ColumnType::Integer) => ColumnType::Real,
+                        _ => ColumnType::Text,
+                    }
+                }
+                None => columns.push(Column {
+                    key: key.clone(),
+                    kind,
+                }),
+            }
+        }
+    }
+    columns
+}
+
+// A property as text, as stored in text columns
+fn text_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+// --- Leaflet map ---
+
+const LEAFLET_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{{title}}</title>
+<link rel="stylesheet" href="https://unpkg.com/leaflet@1.9.4/dist/leaflet.css">
+<script src="https://unpkg.com/leaflet@1.9.4/dist/leaflet.js"></script>
+<style>
+html, body, #map { height: 100%; margin: 0; }
+.map-title { background: white; padding: 4px 10px; font: bold 16px sans-serif; border-radius: 4px; }
+.leaflet-popup-content th { text-align: left; padding-right: 8px; }
+</style>
+</head>
+<body>
+<div id="map"></div>
+<script>
+const layers = {{layers}};
+const escapeHtml = (value) => String(value).replace(/[&<>"]/g, (c) => ({ '&': '&amp;', '<': '&lt;', '>': '&gt;', '"': '&quot;' })[c]);
+const map = L.map('map');
+L.tileLayer('https://tile.openstreetmap.org/{z}/{x}/{y}.png', {
+  maxZoom: 19,
+  attribution: '&copy; <a href="https://www.openstreetmap.org/copyright">OpenStreetMap</a> contributors'
+}).addTo(map);
+const overlays = {};
+const bounds = L.latLngBounds([]);
+for (const layer of layers) {
+  const geojson = L.geoJSON(layer.data, {
+    style: { color: layer.color, weight: 2, fillOpacity: 0.3 },
+    pointToLayer: (feature, latlng) => L.circleMarker(latlng, { radius: 5, color: layer.color, fillOpacity: 0.8 }),
+    onEachFeature: (feature, item) => {
+      const rows = Object.entries(feature.properties || {})
+        .map(([key, value]) => `<tr><th>${escapeHtml(key)}</th><td>${escapeHtml(value)}</td></tr>`)
+        .join('');
+      if (rows) item.bindPopup(`<table>${rows}</table>`, { maxHeight: 300 });
+    }
+  }).addTo(map);
+  overlays[`<span style="color:${layer.color}">&#9632;</span> ${escapeHtml(layer.name)}`] = geojson;
+  if (geojson.getBounds().isValid()) bounds.extend(geojson.getBounds());
+}
+const title = L.control({ position: 'topleft' });
+title.onAdd = () => {
+  const div = L.DomUtil.create('div', 'map-title');
+  div.textContent = {{title_json}};
+  return div;
+};
+if ({{title_json}}) title.addTo(map);
+L.control.layers(null, overlays, { collapsed: false }).addTo(map);
+L.control.scale().addTo(map);
+if (bounds.isValid()) map.fitBounds(bounds, { padding: [20, 20] }); else map.setView([0, 0], 2);
+</script>
+</body>
+</html>
+"#;
+
+fn write_html(path: &Path, layers: &[ExportLayer], title: &str) -> Result<(), String> {
+    let data: Vec<serde_json::Value> = layers
+        .iter()
+        .map(|layer| {
+            let RGBColor(r, g, b) = layer.color;
+            serde_json::json!({
+                "name": layer.name,
+                "color": format!("#{:02x}{:02x}{:02x}", r, g, b),
+                "data": FeatureCollection {
+                    bbox: None,
+                    features: layer.features.clone(),
+                    foreign_members: None,
+                },
+            })
+        })
+        .collect();
+    // `</` would end the script element early
+    let script_safe = |json: String| json.replace("</", "<\\/");
+    let title_html = title
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    let page = LEAFLET_PAGE
+        .replace("{{title}}", &title_html)
+        .replace(
+            "{{title_json}}",
+            &script_safe(serde_json::Value::from(title).to_string()),
+        )
+        .replace(
+            "{{layers}}",
+            &script_safe(serde_json::Value::from(data).to_string()),
+        );
+    fs::write(path, page).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// --- ESRI Shapefile ---
+
+// What a Shapefile can hold; one kind per file
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ShapeKind {
+    Points,
+    Lines,
+    Polygons,
+}
+
+impl ShapeKind {
+    const ALL: [ShapeKind; 3] = [ShapeKind::Points, ShapeKind::Lines, ShapeKind::Polygons];
+
+    fn suffix(self) -> &'static str {
+        match self {
+            ShapeKind::Points => "points",
+            ShapeKind::Lines => "lines",
+            ShapeKind::Polygons => "polygons",
+        }
+    }
+}
+
+// One record of a Shapefile: its parts (points, lines or rings) and attributes
+struct Shape<'a> {
+    parts: Vec<Vec<[f64; 2]>>,
+    feature: &'a Feature,
+}
+
+// Splits a geometry into the shapes it adds to each kind of file; members of
+// a collection become shapes of their own
+fn shapes_of<'a>(value: &Value, feature: &'a Feature, out: &mut Vec<(ShapeKind, Shape<'a>)>) {
+    let xy = |positions: &[Vec<f64>]| -> Vec<[f64; 2]> {
+        positions
+            .iter()
+            .filter(|p| p.len() >= 2)
+            .map(|p| [p[0], p[1]])
+            .collect()
+    };
+    let mut push = |kind, parts: Vec<Vec<[f64; 2]>>| {
+        if parts.iter().any(|part| !part.is_empty()) {
+            out.push((kind, Shape { parts, feature }));
+        }
+    };
+    match value {
+        Value::Point(position) => push(ShapeKind::Points, vec![xy(std::slice::from_ref(position))]),
+        Value::MultiPoint(points) => push(ShapeKind::Points, vec![xy(points)]),
+        Value::LineString(line) => push(ShapeKind::Lines, vec![xy(line)]),
+        Value::MultiLineString(lines) => push(
+            ShapeKind::Lines,
+            lines.iter().map(|line| xy(line)).collect(),
+        ),
+        Value::Polygon(rings) => push(ShapeKind::Polygons, shapefile_rings(rings)),
+        Value::MultiPolygon(polygons) => push(
+            ShapeKind::Polygons,
+            polygons
+                .iter()
+                .flat_map(|rings| shapefile_rings(rings))
+                .collect(),
+        ),
+        Value::GeometryCollection(geometries) => {
+            for geometry in geometries {
+                shapes_of(&geometry.value, feature, out);
+            }
+        }
+    }
+}
+
+// Closed rings turning the Shapefile way: exterior rings clockwise, holes counterclockwise
+fn shapefile_rings(rings: &[Vec<Vec<f64>>]) -> Vec<Vec<[f64; 2]>> {
+    rings
+        .iter()
+        .enumerate()
+        .filter_map(|(i, ring)| {
+            let mut ring: Vec<[f64; 2]> = ring
+                .iter()
+                .filter(|p| p.len() >= 2)
+                .map(|p| [p[0], p[1]])
+                .collect();
+            if ring.len() < 3 {
+                return None;
+            }
+            if ring.first() != ring.last() {
+                ring.push(ring[0]);
+            }
+            let counterclockwise = clip::area(&ring[..ring.len() - 1]) > 0.0;
+            if counterclockwise == (i == 0) {
+                ring.reverse();
+            }
+            Some(ring)
+        })
+        .collect()
+}
+
+fn write_shapefiles(path: &Path, layers: &[ExportLayer]) -> Result<Vec<PathBuf>, String> {
+    let features: Vec<Feature> = merged_features(layers).collect();
+    let mut shapes = Vec::new();
+    for feature in &features {
+        if let Some(geometry) = &feature.geometry {
+            shapes_of(&geometry.value, feature, &mut shapes);
+        }
+    }
+    let kinds: Vec<ShapeKind> = ShapeKind::ALL
+        .into_iter()
+        .filter(|kind| shapes.iter().any(|(k, _)| k == kind))
+        .collect();
+    if kinds.is_empty() {
+        return Err(String::from(
+            "Nothing to export: the layers have no geometries",
+        ));
+    }
+    let mut written = Vec::new();
+    for &kind in &kinds {
+        let shp_path = if kinds.len() == 1 {
+            path.to_path_buf()
+        } else {
+            let stem = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("export");
+            path.with_file_name(format!("{}_{}.shp", stem, kind.suffix()))
+        };
+        let records: Vec<&Shape> = shapes
+            .iter()
+            .filter(|(k, _)| *k == kind)
+            .map(|(_, shape)| shape)
+            .collect();
+        write_shapefile(&shp_path, kind, &records)?;
+        written.push(shp_path);
+    }
+    Ok(written)
+}
+
+// Writes the .shp, .shx, .dbf, .prj and .cpg files of one Shapefile
+fn write_shapefile(path: &Path, kind: ShapeKind, shapes: &[&Shape]) -> Result<(), String> {
+    // Points are written as MultiPoints if any feature has several
+    let shape_type: i32 = match kind {
+        ShapeKind::Points if shapes.iter().all(|s| s.parts[0].len() == 1) => 1,
+        ShapeKind::Points => 8,
+        ShapeKind::Lines => 3,
+        ShapeKind::Polygons => 5,
+    };
+    let mut contents = Vec::new(); // Record contents, without their headers
+    let mut bbox = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+    for shape in shapes {
+        let points: Vec<[f64; 2]> = shape.parts.iter().flatten().copied().collect();
+        let mut shape_bbox = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+        for [x, y] in &points {
+            shape_bbox = [
+                shape_bbox[0].min(*x),
+                shape_bbox[1].min(*y),
+                shape_bbox[2].max(*x),
+                shape_bbox[3].max(*y),
+            ];
+        }
+        bbox = [
+            bbox[0].min(shape_bbox[0]),
+            bbox[1].min(shape_bbox[1]),
+            bbox[2].max(shape_bbox[2]),
+            bbox[3].max(shape_bbox[3]),
+        ];
+        let mut content = shape_type.to_le_bytes().to_vec();
+        if shape_type != 1 {
+            content.extend(shape_bbox.iter().flat_map(|v| v.to_le_bytes()));
+        }
+        if shape_type == 3 || shape_type == 5 {
+            content.extend((shape.parts.len() as i32).to_le_bytes());
+        }
+        if shape_type != 1 {
+            content.extend((points.len() as i32).to_le_bytes());
+        }
+        if shape_type == 3 || shape_type == 5 {
+            let mut start = 0;
+            for part in &shape.parts {
+                content.extend((start as i32).to_le_bytes());
+                start += part.len();
+            }
+        }
+        content.extend(points.iter().flatten().flat_map(|v| v.to_le_bytes()));
+        contents.push(content);
+    }
+
+    // Lengths and offsets are counted in 16-bit words
+    let shp_length = 50 + contents.iter().map(|c| 4 + c.len() / 2).sum::<usize>();
+    let mut shp = shapefile_header(shp_length, shape_type, bbox);
+    let mut shx = shapefile_header(50 + 4 * contents.len(), shape_type, bbox);
+    for (i, content) in contents.iter().enumerate() {
+        shx.extend(((shp.len() / 2) as i32).to_be_bytes());
+        shx.extend(((content.len() / 2) as i32).to_be_bytes());
+        shp.extend((i as i32 + 1).to_be_bytes());
+        shp.extend(((content.len() / 2) as i32).to_be_bytes());
+        shp.extend(content);
+    }
+
+    let features: Vec<&Feature> = shapes.iter().map(|shape| shape.feature).collect();
+    let files = [
+        (path.with_extension("shp"), shp),
+        (path.with_extension("shx"), shx),
+        (path.with_extension("dbf"), dbase_table(&features)),
+        (path.with_extension("prj"), WGS84_WKT.as_bytes().to_vec()),
+        (path.with_extension("cpg"), b"UTF-8".to_vec()),
+    ];
+    for (file, bytes) in files {
+        fs::write(&file, bytes).map_err(|e| format!("{}: {}", file.display(), e))?;
+    }
+    Ok(())
+}
+
+fn shapefile_header(length_words: usize, shape_type: i32, bbox: [f64; 4]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(100);
+    header.extend(9994i32.to_be_bytes());
+    header.extend([0; 20]);
+    header.extend((length_words as i32).to_be_bytes());
+    header.extend(1000i32.to_le_bytes());
+    header.extend(shape_type.to_le_bytes());
+    header.extend(bbox.iter().flat_map(|v| v.to_le_bytes()));
+    header.extend([0; 32]); // Z and M ranges
+    header
+}
+
+// A dBASE field: its column, name, type letter, width and decimals
+struct Field<'a> {
+    column: &'a Column,
+    kind: u8,
+    width: usize,
+    decimals: usize,
+}
+
+impl Field<'_> {
+    fn format(&self, value: Option<&serde_json::Value>) -> Vec<u8> {
+        let text = match (self.kind, value) {
+            (_, None | Some(serde_json::Value::Null)) => String::new(),
+            (b'L', Some(value)) => String::from(if value.as_bool() == Some(true) {
+                "T"
+            } else {
+                "F"
+            }),
+            (b'N', Some(value)) => format!(
+                "{:>width$.decimals$}",
+                value.as_f64().unwrap_or_default(),
+                width = self.width,
+                decimals = self.decimals
+            ),
+            (_, Some(value)) => text_value(value),
+        };
+        // Cut to the field at a character boundary, then pad with spaces
+        let mut end = text.len().min(self.width);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut bytes = text.as_bytes()[..end].to_vec();
+        bytes.resize(self.width, b' ');
+        bytes
+    }
+}
+
+fn dbase_table(features: &[&Feature]) -> Vec<u8> {
+    let columns = columns(features.iter().copied());
+    let mut fields: Vec<Field> = Vec::new();
+    for column in &columns {
+        let values: Vec<&serde_json::Value> = features
+            .iter()
+            .filter_map(|f| f.properties.as_ref()?.get(&column.key))
+            .filter(|value| !value.is_null())
+            .collect();
+        let mut field = Field {
+            column,
+            kind: b'C',
+            width: 1,
+            decimals: 0,
+        };
+        match column.kind {
+            ColumnType::Boolean => field.kind = b'L',
+            ColumnType::Integer | ColumnType::Real => {
+                // As many decimals as the values need, up to 8
+                field.decimals = values
+                    .iter()
+                    .map(|v| {
+                        let text = v.to_string();
+                        text.split_once('.')
+                            .map_or(0, |(_, decimals)| decimals.len())
+                    })
+                    .max()
+                    .unwrap_or(0)
+                    .min(8);
+                field.width = values
+                    .iter()
+                    .map(|v| format!("{:.*}", field.decimals, v.as_f64().unwrap_or_default()).len())
+                    .max()
+                    .unwrap_or(1);
+                field.kind = b'N';
+                if field.width > MAX_NUMBER_WIDTH
+                    || values.iter().any(|v| v.to_string().contains('e'))
+                {
+                    field.kind = b'C';
+                }
+            }
+            ColumnType::Text => {}
+        }
+        if field.kind == b'C' {
+            field.width = values
+                .iter()
+                .map(|v| text_value(v).len())
+                .max()
+                .unwrap_or(1)
+                .clamp(1, MAX_TEXT_WIDTH);
+            field.decimals = 0;
+        }
+        fields.push(field);
+    }
+
+    let record_length = 1 + fields.iter().map(|f| f.width).sum::<usize>();
+    let header_length = 32 + 32 * fields.len() + 1;
+    let today = chrono::Local::now().date_naive();
+    let mut table = vec![
+        0x03,
+        (chrono::Datelike::year(&today) - 1900) as u8,
+        chrono::Datelike::month(&today) as u8,
+        chrono::Datelike::day(&today) as u8,
+    ];
+    table.extend((features.len() as u32).to_le_bytes());
+    table.extend((header_length as u16).to_le_bytes());
+    table.extend((record_length as u16).to_le_bytes());
+    table.extend([0; 20]);
+    for (field, name) in fields.iter().zip(field_names(&columns)) {
+        let mut descriptor = [0u8; 32];
+        descriptor[..name.len()].copy_from_slice(name.as_bytes());
+        descriptor[11] = field.kind;
+        descriptor[16] = field.width as u8;
+        descriptor[17] = field.decimals as u8;
+        table.extend(descriptor);
+    }
+    table.push(0x0d);
+    for feature in features {
+        table.push(b' '); // Not deleted
+        for field in &fields {
+            let value = feature
+                .properties
+                .as_ref()
+                .and_then(|p| p.get(&field.column.key));
+            table.extend(field.format(value));
+        }
+    }
+    table.push(0x1a);
+    table
+}
+
+// dBASE field names: at most 10 ASCII characters and unique
+fn field_names(columns: &[Column]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for column in columns {
+        let base: String = column
+            .key
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .take(10)
+            .collect();
+        let mut name = base.clone();
+        let mut n = 1;
+        while names.iter().any(|taken| taken.eq_ignore_ascii_case(&name)) {
+            let suffix = n.to_string();
+            name = format!("{}{}", &base[..base.len().min(10 - suffix.len())], suffix);
+            n += 1;
+        }
+        names.push(name);
+    }
+    names
+}
+
+// --- GeoPackage ---
+
+fn write_geopackage(path: &Path, layers: &[ExportLayer]) -> Result<(), String> {
+    // Checked first, so a missing command does not cost the file being replaced
+    check_sqlite3()?;
+    let mut sql = String::from(
+        "PRAGMA application_id = 1196444487;
+PRAGMA user_version = 10300;
+BEGIN;
+CREATE TABLE gpkg_spatial_ref_sys (srs_name TEXT NOT NULL, srs_id INTEGER PRIMARY KEY, organization TEXT NOT NULL, organization_coordsys_id INTEGER NOT NULL, definition TEXT NOT NULL, description TEXT);
+CREATE TABLE gpkg_contents (table_name TEXT NOT NULL PRIMARY KEY, data_type TEXT NOT NULL, identifier TEXT UNIQUE, description TEXT DEFAULT '', last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')), min_x DOUBLE, min_y DOUBLE, max_x DOUBLE, max_y DOUBLE, srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id));
+CREATE TABLE gpkg_geometry_columns (table_name TEXT NOT NULL, column_name TEXT NOT NULL, geometry_type_name TEXT NOT NULL, srs_id INTEGER NOT NULL, z TINYINT NOT NULL, m TINYINT NOT NULL, CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name));
+INSERT INTO gpkg_spatial_ref_sys VALUES ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', NULL);
+INSERT INTO gpkg_spatial_ref_sys VALUES ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', NULL);
+",
+    );
+    sql.push_str(&format!(
+        "INSERT INTO gpkg_spatial_ref_sys VALUES ('WGS 84 geodetic', 4326, 'EPSG', 4326, {}, NULL);\n",
+        quote(WGS84_WKT)
+    ));
+    let mut tables: Vec<String> = Vec::new();
+    for layer in layers {
+        // Table names are unique and simple, whatever the file was called
+        let base: String = layer
+            .name
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        let mut table = base.clone();
+        let mut n = 2;
+        while tables.iter().any(|t| t.eq_ignore_ascii_case(&table)) {
+            table = format!("{}_{}", base, n);
+            n += 1;
+        }
+        tables.push(table.clone());
+
+        let columns = columns(layer.features.iter());
+        let column_names = geopackage_column_names(&columns);
+        let definitions: String = columns
+            .iter()
+            .zip(&column_names)
+            .map(|(column, name)| {
+                let kind = match column.kind {
+                    ColumnType::Integer => "INTEGER",
+                    ColumnType::Real => "REAL",
+                    ColumnType::Boolean => "BOOLEAN",
+                    ColumnType::Text => "TEXT",
+                };
+                format!(", {} {}", identifier(name), kind)
+            })
+            .collect();
+        sql.push_str(&format!(
+            "CREATE TABLE {} (fid INTEGER PRIMARY KEY AUTOINCREMENT, geom GEOMETRY{});\n",
+            identifier(&table),
+            definitions
+        ));
+        let mut bbox = plot::empty_bbox();
+        for geometry in layer.features.iter().filter_map(|f| f.geometry.as_ref()) {
+            plot::extend_bbox(&mut bbox, &geometry.value);
+        }
+        let bbox_values = if bbox[0] == f64::MAX {
+            String::from("NULL, NULL, NULL, NULL")
+        } else {
+            format!("{}, {}, {}, {}", bbox[0], bbox[1], bbox[2], bbox[3])
+        };
+        sql.push_str(&format!(
+            "INSERT INTO gpkg_contents (table_name, data_type, identifier, min_x, min_y, max_x, max_y, srs_id) VALUES ({0}, 'features', {0}, {1}, 4326);\n",
+            quote(&table),
+            bbox_values
+        ));
+        sql.push_str(&format!(
+            "INSERT INTO gpkg_geometry_columns VALUES ({}, 'geom', 'GEOMETRY', 4326, 0, 0);\n",
+            quote(&table)
+        ));
+        let names: String = column_names
+            .iter()
+            .map(|name| format!(", {}", identifier(name)))
+            .collect();
+        for feature in &layer.features {
+            let geometry = feature.geometry.as_ref().map_or(String::from("NULL"), |g| {
+                format!("X'{}'", hex(&geopackage_blob(&g.value)))
+            });
+            let values: String = columns
+                .iter()
+                .map(|column| {
+                    let value = feature.properties.as_ref().and_then(|p| p.get(&column.key));
+                    match (column.kind, value) {
+                        (_, None | Some(serde_json::Value::Null)) => String::from(", NULL"),
+                        (ColumnType::Boolean, Some(value)) => {
+                            format!(", {}", u8::from(value.as_bool() == Some(true)))
+                        }
+                        (ColumnType::Integer | ColumnType::Real, Some(value)) => {
+                            format!(", {}", value)
+                        }
+                        (ColumnType::Text, Some(value)) => {
+                            format!(", {}", quote(&text_value(value)))
+                        }
+                    }
+                })
+                .collect();
+            sql.push_str(&format!(
+                "INSERT INTO {} (geom{}) VALUES ({}{});\n",
+                identifier(&table),
+                names,
+                geometry,
+                values
+            ));
+        }
+    }
+    sql.push_str("COMMIT;\n");
+
+    // sqlite3 would add the tables to an existing file
+    if path.exists() {
+        fs::remove_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    let mut child = Command::new("sqlite3")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Writing a GeoPackage needs the sqlite3 command: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(sql.as_bytes())
+            .map_err(|e| format!("sqlite3: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("sqlite3: {}", e))?;
+    if !output.status.success() || !output.stderr.is_empty() {
+        let _ = fs::remove_file(path);
+        return Err(format!(
+            "sqlite3: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+// Why the sqlite3 command cannot be run, if it cannot
+fn check_sqlite3() -> Result<(), String> {
+    match Command::new("sqlite3").arg("-version").output() {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(format!(
+            "sqlite3 -version failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(String::from(
+            "Writing a GeoPackage needs the sqlite3 command, which is not installed \
+             (e.g. `sudo apt install sqlite3`); export to .geojson or .shp instead",
+        )),
+        Err(e) => Err(format!("Cannot run sqlite3: {}", e)),
+    }
+}
+
+// Column names for `columns` next to `fid` and `geom`: SQLite compares names
+// without regard to ASCII case, so clashing properties get a number
+fn geopackage_column_names(columns: &[Column]) -> Vec<String> {
+    let mut names = vec![String::from("fid"), String::from("geom")];
+    for column in columns {
+        let mut name = column.key.clone();
+        let mut n = 2;
+        while names.iter().any(|taken| taken.eq_ignore_ascii_case(&name)) {
+            name = format!("{}_{}", column.key, n);
+            n += 1;
+        }
+        names.push(name);
+    }
+    names.split_off(2)
+}
+
+// A GeoPackage geometry: a header with the SRS and envelope, then WKB
+fn geopackage_blob(value: &Value) -> Vec<u8> {
+    let mut bbox = plot::empty_bbox();
+    plot::extend_bbox(&mut bbox, value);
+    let empty = bbox[0] == f64::MAX;
+    // Little endian, with an x/y envelope unless the geometry is empty
+    let flags: u8 = if empty { 0b0001_0001 } else { 0b0000_0011 };
+    let mut blob = vec![b'G', b'P', 0, flags];
+    blob.extend(4326i32.to_le_bytes());
+    if !empty {
+        for v in [bbox[0], bbox[2], bbox[1], bbox[3]] {
+            blob.extend(v.to_le_bytes());
+        }
+    }
+    blob.extend(wkt::to_wkb(value));
+    blob
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+// An SQL string literal
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+// An SQL identifier
+fn identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn text_columns(keys: &[&str]) -> Vec<Column> {
+        keys.iter()
+            .map(|key| Column {
+                key: key.to_string(),
+                kind: ColumnType::Text,
+            })
+            .collect()
+    }
+
+    fn feature(value: Value, properties: serde_json::Value) -> Feature {
+        Feature {
+            bbox: None,
+            geometry: Some(geojson::Geometry::new(value)),
+            id: None,
+            properties: properties.as_object().cloned(),
+            foreign_members: None,
+        }
+    }
+
+    // A fresh directory for the files of one test
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("plots_export_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn be_i32(bytes: &[u8], at: usize) -> i32 {
+        i32::from_be_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    fn le_i32(bytes: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn dbase_fields_and_records() {
+        let features = [
+            feature(
+                Value::Point(vec![0.0, 0.0]),
+                json!({ "name": "Pune", "pop": 3124458, "area": 331.26, "capital": false }),
+            ),
+            feature(
+                Value::Point(vec![1.0, 1.0]),
+                json!({ "name": "Mumbai", "pop": null, "area": 603.4, "capital": true }),
+            ),
+        ];
+        let table = dbase_table(&features.iter().collect::<Vec<_>>());
+        assert_eq!(table[0], 0x03);
+        assert_eq!(u32::from_le_bytes(table[4..8].try_into().unwrap()), 2);
+        let header_length = u16::from_le_bytes(table[8..10].try_into().unwrap()) as usize;
+        let record_length = u16::from_le_bytes(table[10..12].try_into().unwrap()) as usize;
+        assert_eq!(header_length, 32 + 32 * 4 + 1);
+        assert_eq!(table.len(), header_length + 2 * record_length + 1);
+        assert_eq!(table[header_length - 1], 0x0d);
+        assert_eq!(table.last(), Some(&0x1a));
+
+        // Name, type, width and decimals of each field, in key order
+        let fields: Vec<(String, char, u8, u8)> = table[32..header_length - 1]
+            .chunks(32)
+            .map(|d| {
+                let name = String::from_utf8_lossy(&d[..11])
+                    .trim_end_matches('\0')
+                    .to_string();
+                (name, d[11] as char, d[16], d[17])
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                (String::from("area"), 'N', 6, 2),
+                (String::from("capital"), 'L', 1, 0),
+                (String::from("name"), 'C', 6, 0),
+                (String::from("pop"), 'N', 7, 0),
+            ]
+        );
+        let records = &table[header_length..table.len() - 1];
+        assert_eq!(&records[..record_length], b" 331.26FPune  3124458");
+        assert_eq!(&records[record_length..], b" 603.40TMumbai       ");
+    }
+
+    #[test]
+    fn dbase_field_names_are_short_and_unique() {
+        let columns = text_columns(&["population_2020", "population_2021", "name-en", "NAME_EN"]);
+        assert_eq!(
+            field_names(&columns),
+            ["population", "populatio1", "name_en", "NAME_EN1"]
+        );
+    }
+
+    #[test]
+    fn shapefile_rings_turn_the_shapefile_way() {
+        let square = vec![
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+        ];
+        let rings = shapefile_rings(&[square.clone(), square]);
+        assert_eq!(rings[0].first(), rings[0].last());
+        assert!(clip::area(&rings[0][..4]) < 0.0);
+        assert!(clip::area(&rings[1][..4]) > 0.0);
+    }
+
+    #[test]
+    fn mixed_layers_become_one_shapefile_per_kind() {
+        let dir = scratch_dir("shp");
+        let layers = [ExportLayer {
+            name: String::from("mixed"),
+            features: vec![
+                feature(Value::Point(vec![1.0, 2.0]), json!({ "id": 1 })),
+                feature(
+                    Value::LineString(vec![vec![0.0, 0.0], vec![3.0, 4.0]]),
+                    json!({ "id": 2 }),
+                ),
+                feature(
+                    Value::Polygon(vec![vec![
+                        vec![0.0, 0.0],
+                        vec![2.0, 0.0],
+                        vec![2.0, 2.0],
+                        vec![0.0, 0.0],
+                    ]]),
+                    json!({ "id": 3 }),
+                ),
+            ],
+            color: RGBColor(0, 0, 0),
+        }];
+        let written = write_layers(&dir.join("out.shp"), &layers, "").unwrap();
+        let names: Vec<_> = written
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            ["out_points.shp", "out_lines.shp", "out_polygons.shp"]
+        );
+
+        for (path, shape_type) in written.iter().zip([1, 3, 5]) {
+            let shp = fs::read(path).unwrap();
+            let shx = fs::read(path.with_extension("shx")).unwrap();
+            assert_eq!(be_i32(&shp, 0), 9994);
+            assert_eq!(be_i32(&shp, 24) as usize * 2, shp.len());
+            assert_eq!(le_i32(&shp, 28), 1000);
+            assert_eq!(le_i32(&shp, 32), shape_type);
+            assert_eq!(be_i32(&shx, 24) as usize * 2, shx.len());
+            // The index points at the only record
+            assert_eq!(be_i32(&shx, 100), 50);
+            assert_eq!(be_i32(&shp, 100), 1);
+            for extension in ["dbf", "prj", "cpg"] {
+                assert!(path.with_extension(extension).exists());
+            }
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn several_layers_are_told_apart_in_geojson() {
+        let dir = scratch_dir("geojson");
+        let layer = |name: &str| ExportLayer {
+            name: name.to_string(),
+            features: vec![feature(Value::Point(vec![1.0, 2.0]), json!({}))],
+            color: RGBColor(0, 0, 0),
+        };
+        let path = dir.join("out.geojson");
+        write_layers(&path, &[layer("roads"), layer("rivers")], "").unwrap();
+        let collection: FeatureCollection = fs::read_to_string(&path).unwrap().parse().unwrap();
+        let names: Vec<_> = collection
+            .features
+            .iter()
+            .map(|f| {
+                f.property("layer")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+            })
+            .collect();
+        assert_eq!(names, ["roads", "rivers"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn layer_names_do_not_replace_a_layer_property() {
+        let layer = |name: &str, properties| ExportLayer {
+            name: name.to_string(),
+            features: vec![feature(Value::Point(vec![1.0, 2.0]), properties)],
+            color: RGBColor(0, 0, 0),
+        };
+        let layers = [
+            layer("roads", json!({"layer": "surface"})),
+            layer("rivers", json!({"layer_2": 1})),
+        ];
+        let merged: Vec<Feature> = merged_features(&layers).collect();
+        assert_eq!(merged[0].property("layer"), Some(&json!("surface")));
+        assert_eq!(merged[0].property("layer_3"), Some(&json!("roads")));
+        assert_eq!(merged[1].property("layer_3"), Some(&json!("rivers")));
+    }
+
+    #[test]
+    fn geopackage_columns_are_unique_regardless_of_case() {
+        let columns = text_columns(&["fid", "GEOM", "Name", "name", "name_2"]);
+        assert_eq!(
+            geopackage_column_names(&columns),
+            ["fid_2", "GEOM_2", "Name", "name_2", "name_2_2"]
+        );
+    }
+}
@@ -0,0 +1,572 @@
+// filter.rs
+
+use geojson::Feature;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::plot::property_to_string;
+
+/// Comparison in a filter expression or a `where` style rule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains, // `~`, case-insensitive substring
+}
+
+impl CompareOp {
+    pub fn parse(text: &str) -> Option<CompareOp> {
+        Some(match text {
+            "==" | "=" => CompareOp::Eq,
+            "!=" => CompareOp::Ne,
+            "<" => CompareOp::Lt,
+            "<=" => CompareOp::Le,
+            ">" => CompareOp::Gt,
+            ">=" => CompareOp::Ge,
+            "~" => CompareOp::Contains,
+            _ => return None,
+        })
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+            CompareOp::Contains => "~",
+        }
+    }
+}
+
+/// A side of a comparison.
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Property(String), // `properties.name`, `properties["a name"]` or just `name`
+    GeometryType,     // `geometry.type`
+    Literal(Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Compare(Operand, CompareOp, Operand),
+    Truthy(Operand), // An operand on its own, e.g. `properties.is_capital`
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// Expression restricting which features of a layer are counted, previewed
+/// and plotted, e.g. `properties.pop > 100000 && properties.country == "IN"`.
+///
+/// Comparisons are `==`, `!=`, `<`, `<=`, `>`, `>=` and `~` (contains); they
+/// combine with `&&`/`and`, `||`/`or`, `!`/`not` and parentheses. Numbers,
+/// including numbers stored as strings, compare numerically, everything else
+/// as text. A missing property is `null`, which only equals `null`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureFilter {
+    text: String,
+    expr: Expr,
+}
+
+impl FeatureFilter {
+    pub fn parse(text: &str) -> Result<FeatureFilter, String> {
+        let tokens = tokenize(text)?;
+        if tokens.is_empty() {
+            return Err(String::from("Empty filter"));
+        }
+        let mut parser = Parser { tokens, next: 0 };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected {} in filter", token));
+        }
+        Ok(FeatureFilter {
+            text: text.trim().to_string(),
+            expr,
+        })
+    }
+
+    /// Whether `feature` satisfies the expression.
+    pub fn accepts(&self, feature: &Feature) -> bool {
+        evaluate(&self.expr, feature)
+    }
+
+    /// Number of `features` the expression accepts.
+    pub fn count(&self, features: &[Feature]) -> usize {
+        features.iter().filter(|f| self.accepts(f)).count()
+    }
+}
+
+// Written back as typed, e.g. for sessions and the file info panel
+impl fmt::Display for FeatureFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// Moves the features `accepts` rejects, given their position and the
+/// feature, out of `features`, keeping track of where they were so
+/// `SplitFeatures::rejoin` can put them back.
+pub fn split_features(
+    features: Vec<Feature>,
+    mut accepts: impl FnMut(usize, &Feature) -> bool,
+) -> SplitFeatures {
+    let mut split = SplitFeatures {
+        accepted: Vec::new(),
+        rejected: Vec::new(),
+        matches: Vec::with_capacity(features.len()),
+    };
+    for (position, feature) in features.into_iter().enumerate() {
+        let accepted = accepts(position, &feature);
+        split.matches.push(accepted);
+        if accepted {
+            split.accepted.push(feature);
+        } else {
+            split.rejected.push(feature);
+        }
+    }
+    split
+}
+
+/// Features of a layer split by `split_features`.
+pub struct SplitFeatures {
+    pub accepted: Vec<Feature>,
+    pub rejected: Vec<Feature>,
+    matches: Vec<bool>, // Whether each original feature was accepted, in file order
+}
+
+impl SplitFeatures {
+    /// The features of the layer in their original order, after `accepted`
+    /// has been used (and handed back unchanged).
+    pub fn rejoin(self, accepted: Vec<Feature>) -> Vec<Feature> {
+        let mut accepted = accepted.into_iter();
+        let mut rejected = self.rejected.into_iter();
+        self.matches
+            .iter()
+            .filter_map(|&matched| {
+                if matched {
+                    accepted.next()
+                } else {
+                    rejected.next()
+                }
+            })
+            .collect()
+    }
+}
+
+fn evaluate(expr: &Expr, feature: &Feature) -> bool {
+    match expr {
+        Expr::Compare(left, op, right) => compare(&left.value(feature), *op, &right.value(feature)),
+        Expr::Truthy(operand) => match operand.value(feature).as_ref() {
+            Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+            Value::String(s) => !s.is_empty(),
+            _ => true,
+        },
+        Expr::Not(inner) => !evaluate(inner, feature),
+        Expr::And(left, right) => evaluate(left, feature) && evaluate(right, feature),
+        Expr::Or(left, right) => evaluate(left, feature) || evaluate(right, feature),
+    }
+}
+
+impl Operand {
+    fn value<'a>(&'a self, feature: &'a Feature) -> Cow<'a, Value> {
+        match self {
+            Operand::Property(name) => match feature.property(name) {
+                Some(value) => Cow::Borrowed(value),
+                None => Cow::Owned(Value::Null),
+            },
+            Operand::GeometryType => match &feature.geometry {
+                Some(geometry) => Cow::Owned(Value::from(geometry.value.type_name())),
+                None => Cow::Owned(Value::Null),
+            },
+            Operand::Literal(value) => Cow::Borrowed(value),
+        }
+    }
+}
+
+/// A value as a number, also when the number is stored as a string. Strings
+/// such as `"NaN"` or `"inf"` are text: Nan is a province, not a number.
+pub fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok().filter(|n: &f64| n.is_finite()),
+        _ => None,
+    }
+}
+
+/// Compares two values the way filters and `where` style rules do: numbers,
+/// including numbers stored as strings, numerically and everything else as
+/// text; `~` is a case-insensitive substring test. `null` (a missing
+/// property) only equals `null` and is neither less nor greater than anything.
+pub fn compare(left: &Value, op: CompareOp, right: &Value) -> bool {
+    if left.is_null() || right.is_null() {
+        return match op {
+            CompareOp::Eq => left.is_null() && right.is_null(),
+            CompareOp::Ne => !(left.is_null() && right.is_null()),
+            _ => false,
+        };
+    }
+    if op == CompareOp::Contains {
+        return property_to_string(left)
+            .to_lowercase()
+            .contains(&property_to_string(right).to_lowercase());
+    }
+    let ordering = match (as_number(left), as_number(right)) {
+        (Some(a), Some(b)) => a.partial_cmp(&b),
+        _ => Some(property_to_string(left).cmp(&property_to_string(right))),
+    };
+    let Some(ordering) = ordering else {
+        return false;
+    };
+    match op {
+        CompareOp::Eq => ordering == Ordering::Equal,
+        CompareOp::Ne => ordering != Ordering::Equal,
+        CompareOp::Lt => ordering == Ordering::Less,
+        CompareOp::Le => ordering != Ordering::Greater,
+        CompareOp::Gt => ordering == Ordering::Greater,
+        CompareOp::Ge => ordering != Ordering::Less,
+        CompareOp::Contains => unreachable!(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Op(CompareOp),
+    And,
+    Or,
+    Not,
+    Dot,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Str(text) => write!(f, "\"{}\"", text),
+            Token::Num(n) => write!(f, "{}", n),
+            Token::Op(_) => write!(f, "operator"),
+            Token::And => write!(f, "'&&'"),
+            Token::Or => write!(f, "'||'"),
+            Token::Not => write!(f, "'!'"),
+            Token::Dot => write!(f, "'.'"),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::LBracket => write!(f, "'['"),
+            Token::RBracket => write!(f, "']'"),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        // Two-character operators first
+        let pair = match (c, next) {
+            ('&', Some('&')) => Some(Token::And),
+            ('|', Some('|')) => Some(Token::Or),
+            ('=', Some('=')) => Some(Token::Op(CompareOp::Eq)),
+            ('!', Some('=')) => Some(Token::Op(CompareOp::Ne)),
+            ('<', Some('=')) => Some(Token::Op(CompareOp::Le)),
+            ('>', Some('=')) => Some(Token::Op(CompareOp::Ge)),
+            _ => None,
+        };
+        if let Some(token) = pair {
+            tokens.push(token);
+            i += 2;
+            continue;
+        }
+        let single = match c {
+            '=' => Some(Token::Op(CompareOp::Eq)),
+            '<' => Some(Token::Op(CompareOp::Lt)),
+            '>' => Some(Token::Op(CompareOp::Gt)),
+            '~' => Some(Token::Op(CompareOp::Contains)),
+            '!' => Some(Token::Not),
+            '(' => Some(Token::LParen),
+            ')' => Some(Token::RParen),
+            '[' => Some(Token::LBracket),
+            ']' => Some(Token::RBracket),
+            _ => None,
+        };
+        if let Some(token) = single {
+            tokens.push(token);
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let start = i + 1;
+            let end = (start..chars.len())
+                .find(|&j| chars[j] == c)
+                .ok_or_else(|| format!("Unterminated string starting at {}", i + 1))?;
+            tokens.push(Token::Str(chars[start..end].iter().collect()));
+            i = end + 1;
+        } else if c.is_ascii_digit()
+            || (c == '-' || c == '.') && next.is_some_and(|n| n.is_ascii_digit())
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len() {
+                let d = chars[i];
+                let exponent_sign = (d == '-' || d == '+') && matches!(chars[i - 1], 'e' | 'E');
+                if d.is_ascii_digit() || d == '.' || d == 'e' || d == 'E' || exponent_sign {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            let number: String = chars[start..i].iter().collect();
+            tokens.push(Token::Num(
+                number
+                    .parse()
+                    .map_err(|_| format!("Invalid number '{}'", number))?,
+            ));
+        } else if c == '.' {
+            tokens.push(Token::Dot);
+            i += 1;
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            tokens.push(match word.to_lowercase().as_str() {
+                "and" => Token::And,
+                "or" => Token::Or,
+                "not" => Token::Not,
+                _ => Token::Ident(word),
+            });
+        } else {
+            return Err(format!("Unexpected '{}' at {}", c, i + 1));
+        }
+    }
+    Ok(tokens)
+}
+
+// Recursive descent over the tokens: `||` binds looser than `&&`, which binds
+// looser than `!` and comparisons
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat(&Token::LParen) {
+            let expr = self.or()?;
+            if !self.eat(&Token::RParen) {
+                return Err(String::from("Missing ')' in filter"));
+            }
+            return Ok(expr);
+        }
+        let left = self.operand()?;
+        match self.peek() {
+            Some(&Token::Op(op)) => {
+                self.next += 1;
+                Ok(Expr::Compare(left, op, self.operand()?))
+            }
+            _ => Ok(Expr::Truthy(left)),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.advance() {
+            Some(Token::Str(text)) => Ok(Operand::Literal(Value::String(text))),
+            Some(Token::Num(n)) => Ok(Operand::Literal(Value::from(n))),
+            Some(Token::Ident(word)) => match word.as_str() {
+                "true" => Ok(Operand::Literal(Value::Bool(true))),
+                "false" => Ok(Operand::Literal(Value::Bool(false))),
+                "null" => Ok(Operand::Literal(Value::Null)),
+                "properties" if self.eat(&Token::Dot) => match self.advance() {
+                    Some(Token::Ident(name)) => Ok(Operand::Property(name)),
+                    _ => Err(String::from("Expected a property name after 'properties.'")),
+                },
+                "properties" if self.eat(&Token::LBracket) => {
+                    let name = match self.advance() {
+                        Some(Token::Str(name)) => name,
+                        _ => return Err(String::from("Expected properties[\"name\"]")),
+                    };
+                    if !self.eat(&Token::RBracket) {
+                        return Err(String::from("Missing ']' after the property name"));
+                    }
+                    Ok(Operand::Property(name))
+                }
+                "geometry" if self.eat(&Token::Dot) => match self.advance() {
+                    Some(Token::Ident(field)) if field == "type" => Ok(Operand::GeometryType),
+                    _ => Err(String::from("Only geometry.type can be filtered on")),
+                },
+                _ => Ok(Operand::Property(word)),
+            },
+            Some(token) => Err(format!("Expected a property or value, found {}", token)),
+            None => Err(String::from("Filter ends too early")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::{self, StyleRule};
+
+    fn feature(properties: Value) -> Feature {
+        Feature {
+            bbox: None,
+            geometry: None,
+            id: None,
+            properties: properties.as_object().cloned(),
+            foreign_members: None,
+        }
+    }
+
+    fn accepts(filter: &str, properties: Value) -> bool {
+        FeatureFilter::parse(filter)
+            .unwrap()
+            .accepts(&feature(properties))
+    }
+
+    #[test]
+    fn numbers_compare_numerically_even_as_strings() {
+        let city = serde_json::json!({ "pop": "120000", "code": 7 });
+        assert!(accepts("properties.pop > 100000", city.clone()));
+        assert!(accepts("code == 7.0", city.clone()));
+        assert!(!accepts("pop < 9", city));
+    }
+
+    #[test]
+    fn text_and_contains() {
+        let city = serde_json::json!({ "name": "New Delhi", "country": "IN" });
+        assert!(accepts("country == \"IN\"", city.clone()));
+        assert!(accepts("name ~ \"delhi\"", city.clone()));
+        assert!(!accepts("name ~ \"mumbai\"", city));
+    }
+
+    #[test]
+    fn only_finite_numbers_compare_numerically() {
+        let province = serde_json::json!({ "name": "Nan", "alias": "Inf" });
+        assert!(accepts("properties.name == \"Nan\"", province.clone()));
+        assert!(accepts("name != \"NAN\"", province.clone()));
+        assert!(accepts("name > \"Lampang\"", province.clone()));
+        assert!(!accepts("alias == \"infinity\"", province));
+        assert_eq!(as_number(&Value::from(" 1e3 ")), Some(1000.0));
+        assert_eq!(as_number(&Value::from("-inf")), None);
+    }
+
+    #[test]
+    fn missing_properties_are_null() {
+        let city = serde_json::json!({ "name": "Pune" });
+        assert!(accepts("pop == null", city.clone()));
+        assert!(accepts("pop != 5", city.clone()));
+        assert!(!accepts("pop < 5", city.clone()));
+        assert!(!accepts("pop > 5", city));
+    }
+
+    #[test]
+    fn precedence_and_parentheses() {
+        let city = serde_json::json!({ "a": 1, "b": 2, "c": 3 });
+        assert!(accepts("a == 1 || b == 0 && c == 0", city.clone()));
+        assert!(!accepts("(a == 1 || b == 0) && c == 0", city.clone()));
+        assert!(accepts("not (a > 1) and c >= 3", city));
+    }
+
+    #[test]
+    fn malformed_filters_are_rejected() {
+        for text in ["", "a ==", "(a == 1", "a == 1 b", "a == \"x"] {
+            assert!(FeatureFilter::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn display_keeps_the_text() {
+        let filter = FeatureFilter::parse("  pop > 5 ").unwrap();
+        assert_eq!(filter.to_string(), "pop > 5");
+    }
+
+    #[test]
+    fn where_rules_agree_with_filters() {
+        let cities = [
+            serde_json::json!({ "pop": "120000", "name": "New Delhi" }),
+            serde_json::json!({ "pop": 900, "name": "Leh" }),
+            serde_json::json!({ "name": "Unknown" }),
+            serde_json::json!({ "pop": 10, "name": "NaN" }),
+        ];
+        let cases = [
+            ("pop > 1000", "where pop > 1000"),
+            ("pop != 900", "where pop != 900"),
+            ("pop <= 900", "where pop <= 900"),
+            ("name ~ \"delhi\"", "where name ~ delhi"),
+            ("name == \"Leh\"", "where name == Leh"),
+            ("name == \"NaN\"", "where name == NaN"),
+        ];
+        for (filter, rule) in cases {
+            let filter = FeatureFilter::parse(filter).unwrap();
+            let rules = [StyleRule::parse(rule).unwrap()];
+            for city in &cities {
+                let city = feature(city.clone());
+                assert_eq!(
+                    filter.accepts(&city),
+                    style::accepts(&rules, &city),
+                    "{} on {:?}",
+                    rule,
+                    city.properties
+                );
+            }
+        }
+    }
+}
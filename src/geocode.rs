@@ -0,0 +1,335 @@
+// geocode.rs
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    ffi::OsStr,
+    fs,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use crate::config::Config;
+use crate::{loader, plot, remote};
+
+pub const DEFAULT_NOMINATIM_URL: &str = "https://nominatim.openstreetmap.org";
+pub const EARTH_RADIUS_KM: f64 = 6371.0; // Mean radius of the Earth
+pub const PLACE_CACHE_FILE: &str = "place_cache.json";
+const GEOCODING_TIMEOUT: u32 = 10; // Seconds
+
+// Places found with a smaller bbox, such as a single building, are shown with
+// at least this many degrees around them
+const MIN_PLACE_SPAN: f64 = 0.02;
+
+// A named location from the gazetteer file
+struct Place {
+    lon: f64,
+    lat: f64,
+    name: String,
+}
+
+enum Source {
+    Nominatim(String), // Base URL of the Nominatim server
+    Gazetteer(Vec<Place>),
+}
+
+/// Looks up place names for coordinates identified in the preview.
+pub struct ReverseGeocoder {
+    source: Source,
+    pending: Option<Receiver<Result<String, String>>>, // Lookup not yet returned by `poll`
+}
+
+/// Great-circle distance between two positions in kilometres.
+pub fn haversine_km(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+impl ReverseGeocoder {
+    /// Builds the geocoder configured in the `[geocoding]` section, if any:
+    /// `reverse = "nominatim"` (optionally with `nominatim_url`) or
+    /// `reverse = "gazetteer"` with a `gazetteer` file and its `name_property`.
+    pub fn from_config(config: &Config) -> Result<Option<ReverseGeocoder>, Box<dyn Error>> {
+        let setting = |key: &str| config.get("geocoding", key).and_then(|v| v.as_str());
+        let source = match setting("reverse") {
+            None | Some("off") => return Ok(None),
+            Some("nominatim") => Source::Nominatim(
+                setting("nominatim_url")
+                    .unwrap_or(DEFAULT_NOMINATIM_URL)
+                    .trim_end_matches('/')
+                    .to_string(),
+            ),
+            Some("gazetteer") => {
+                let file = setting("gazetteer").ok_or("[geocoding] gazetteer file not set")?;
+                let name_property = setting("name_property").unwrap_or("name");
+                let path = loader::resolve_input(file);
+                let features = loader::read_features(&path)
+                    .map_err(|e| format!("Failed to read gazetteer {}: {}", path.display(), e))?;
+
+                // Every named feature is represented by the center of its bbox
+                let places: Vec<Place> = features
+                    .iter()
+                    .filter_map(|feature| {
+                        let name = plot::property_to_string(feature.property(name_property)?);
+                        let mut bbox = plot::empty_bbox();
+                        plot::extend_bbox(&mut bbox, &feature.geometry.as_ref()?.value);
+                        (bbox[0] != f64::MAX).then(|| Place {
+                            lon: (bbox[0] + bbox[2]) / 2.0,
+                            lat: (bbox[1] + bbox[3]) / 2.0,
+                            name,
+                        })
+                    })
+                    .collect();
+                if places.is_empty() {
+                    return Err(format!(
+                        "Gazetteer {} has no features with a '{}' property",
+                        path.display(),
+                        name_property
+                    )
+                    .into());
+                }
+                Source::Gazetteer(places)
+            }
+            Some(other) => {
+                return Err(format!(
+                    "Unknown reverse geocoder '{}' (use nominatim, gazetteer or off)",
+                    other
+                )
+                .into());
+            }
+        };
+        Ok(Some(ReverseGeocoder {
+            source,
+            pending: None,
+        }))
+    }
+
+    /// Starts looking up the place at `lon`/`lat`, replacing any running lookup.
+    /// The result is returned by `poll`.
+    pub fn request(&mut self, lon: f64, lat: f64) {
+        let (sender, receiver) = mpsc::channel();
+        match &self.source {
+            Source::Gazetteer(places) => {
+                let nearest = places.iter().min_by(|a, b| {
+                    haversine_km(lon, lat, a.lon, a.lat)
+                        .total_cmp(&haversine_km(lon, lat, b.lon, b.lat))
+                });
+                let result = nearest
+                    .map(|place| {
+                        format!(
+                            "near {} ({:.0} km)",
+                            place.name,
+                            haversine_km(lon, lat, place.lon, place.lat)
+                        )
+                    })
+                    .ok_or_else(|| String::from("No places in gazetteer"));
+                let _ = sender.send(result);
+            }
+            Source::Nominatim(base_url) => {
+                let url = format!(
+                    "{}/reverse?format=jsonv2&lat={}&lon={}&zoom=10",
+                    base_url, lat, lon
+                );
+                // Requests run on a thread so the UI stays responsive
+                thread::spawn(move || {
+                    let _ = sender.send(nominatim_lookup(&url));
+                });
+            }
+        }
+        self.pending = Some(receiver);
+    }
+
+    /// Whether a lookup is still running.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Returns the result of the last request once it is available.
+    pub fn poll(&mut self) -> Option<Result<String, String>> {
+        let result = match self.pending.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(String::from("Lookup failed")),
+        };
+        self.pending = None;
+        Some(result)
+    }
+}
+
+/// A place found by name, with the extent to show it in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FoundPlace {
+    pub name: String,
+    pub bbox: [f64; 4], // min_lon, min_lat, max_lon, max_lat
+}
+
+/// Looks up places by name on a Nominatim server to go to them. Results are
+/// kept in `place_cache.json` in the working directory between runs, so going
+/// back to a place works offline and does not query the server again.
+pub struct PlaceSearch {
+    base_url: String,
+    cache: HashMap<String, FoundPlace>, // By normalized query
+    changed: bool,                      // Places were found since the cache was read
+    pending: Option<(String, Receiver<Result<FoundPlace, String>>)>, // Query not yet returned by `poll`
+}
+
+impl Default for PlaceSearch {
+    fn default() -> Self {
+        PlaceSearch {
+            base_url: String::from(DEFAULT_NOMINATIM_URL),
+            cache: HashMap::new(),
+            changed: false,
+            pending: None,
+        }
+    }
+}
+
+impl PlaceSearch {
+    /// Searches the server set as `nominatim_url` in the `[geocoding]` section,
+    /// the public OpenStreetMap one by default.
+    pub fn from_config(config: &Config) -> PlaceSearch {
+        let url = config
+            .get("geocoding", "nominatim_url")
+            .and_then(|v| v.as_str())
+            .unwrap_or(DEFAULT_NOMINATIM_URL);
+        PlaceSearch {
+            base_url: url.trim_end_matches('/').to_string(),
+            ..PlaceSearch::default()
+        }
+    }
+
+    /// Reads the places found by earlier runs; none if there is no cache.
+    pub fn load_cache(&mut self) -> Result<(), Box<dyn Error>> {
+        match fs::read_to_string(PLACE_CACHE_FILE) {
+            Ok(contents) => self.cache = serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    /// The place named `query` if it was found before; otherwise starts
+    /// looking it up, replacing any running lookup, for `poll` to return.
+    pub fn search(&mut self, query: &str) -> Option<FoundPlace> {
+        let key = cache_key(query);
+        if let Some(place) = self.cache.get(&key) {
+            return Some(place.clone());
+        }
+        let (sender, receiver) = mpsc::channel();
+        let url = format!("{}/search", self.base_url);
+        let query = query.trim().to_string();
+        // Requests run on a thread so the UI stays responsive
+        thread::spawn(move || {
+            let _ = sender.send(nominatim_search(&url, &query));
+        });
+        self.pending = Some((key, receiver));
+        None
+    }
+
+    /// Returns the result of the last lookup once it is available, adding a
+    /// found place to the cache.
+    pub fn poll(&mut self) -> Option<Result<FoundPlace, String>> {
+        let (key, receiver) = self.pending.as_ref()?;
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(String::from("Lookup failed")),
+        };
+        if let Ok(place) = &result {
+            self.cache.insert(key.clone(), place.clone());
+            self.changed = true;
+        }
+        self.pending = None;
+        Some(result)
+    }
+
+    /// Writes the cache to `place_cache.json` if places were found since it
+    /// was read.
+    pub fn save_cache(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.changed {
+            fs::write(PLACE_CACHE_FILE, serde_json::to_string(&self.cache)?)?;
+            self.changed = false;
+        }
+        Ok(())
+    }
+}
+
+// Queries are looked up in the cache without regard to case or spacing
+fn cache_key(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// Requests `url` with `args` and parses the JSON it returns
+fn request_json(url: &str, args: &[&OsStr]) -> Result<serde_json::Value, String> {
+    let body = remote::request(url, args, GEOCODING_TIMEOUT)?;
+    serde_json::from_slice(&body).map_err(|e| format!("{}: {}", url, e))
+}
+
+// Asks the Nominatim search at `url` for the best match of `query`
+fn nominatim_search(url: &str, query: &str) -> Result<FoundPlace, String> {
+    let query_data = format!("q={}", query);
+    let response = request_json(
+        url,
+        &[
+            "-G".as_ref(),
+            "--data-urlencode".as_ref(),
+            query_data.as_ref(),
+            "--data".as_ref(),
+            "format=jsonv2&limit=1".as_ref(),
+        ],
+    )?;
+    let result = response
+        .as_array()
+        .and_then(|results| results.first())
+        .ok_or_else(|| format!("No place named '{}' found", query))?;
+    let number =
+        |value: Option<&serde_json::Value>| -> Option<f64> { value?.as_str()?.parse().ok() };
+    // Nominatim gives the bbox as strings: min_lat, max_lat, min_lon, max_lon
+    let bbox = result.get("boundingbox").and_then(|b| b.as_array());
+    let corner = |i: usize| number(bbox.and_then(|b| b.get(i)));
+    let (lon, lat) = (number(result.get("lon")), number(result.get("lat")));
+    let [min_lon, min_lat, max_lon, max_lat] = match (corner(2), corner(0), corner(3), corner(1)) {
+        (Some(min_lon), Some(min_lat), Some(max_lon), Some(max_lat)) => {
+            [min_lon, min_lat, max_lon, max_lat]
+        }
+        _ => match (lon, lat) {
+            (Some(lon), Some(lat)) => [lon, lat, lon, lat],
+            _ => return Err(format!("No position for '{}' in the response", query)),
+        },
+    };
+    let grow = |min: f64, max: f64| {
+        let pad = ((MIN_PLACE_SPAN - (max - min)) / 2.0).max(0.0);
+        (min - pad, max + pad)
+    };
+    let (min_lon, max_lon) = grow(min_lon, max_lon);
+    let (min_lat, max_lat) = grow(min_lat, max_lat);
+    Ok(FoundPlace {
+        name: result
+            .get("display_name")
+            .and_then(|name| name.as_str())
+            .unwrap_or(query)
+            .to_string(),
+        bbox: [min_lon, min_lat, max_lon, max_lat],
+    })
+}
+
+// Queries Nominatim, returning the display name of the result
+fn nominatim_lookup(url: &str) -> Result<String, String> {
+    let response = request_json(url, &[])?;
+    match response.get("display_name").and_then(|name| name.as_str()) {
+        Some(name) => Ok(name.to_string()),
+        None => Err(response
+            .get("error")
+            .and_then(|e| e.as_str())
+            .unwrap_or("No place found")
+            .to_string()),
+    }
+}
@@ -0,0 +1,295 @@
+// gps.rs
+
+use geojson::{Feature, FeatureCollection, Geometry, Value};
+use std::{
+    error::Error,
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    path::Path,
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+};
+
+pub const DEFAULT_GPSD_ADDRESS: &str = "localhost:2947";
+
+/// Where NMEA sentences are read from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GpsSource {
+    Serial(String), // Device path, e.g. /dev/ttyUSB0 (baud rate set beforehand with stty)
+    Gpsd(String),   // host:port of a gpsd daemon
+}
+
+impl GpsSource {
+    /// Parses the `source` value of the `[gps]` config section: `"gpsd"`,
+    /// `"gpsd:HOST:PORT"` or a serial device path.
+    pub fn parse(text: &str) -> GpsSource {
+        match text.strip_prefix("gpsd") {
+            Some("") => GpsSource::Gpsd(String::from(DEFAULT_GPSD_ADDRESS)),
+            Some(address) if address.starts_with(':') => GpsSource::Gpsd(address[1..].to_string()),
+            _ => GpsSource::Serial(text.to_string()),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            GpsSource::Serial(path) => path.clone(),
+            GpsSource::Gpsd(address) => format!("gpsd at {}", address),
+        }
+    }
+}
+
+impl Default for GpsSource {
+    fn default() -> Self {
+        GpsSource::Gpsd(String::from(DEFAULT_GPSD_ADDRESS))
+    }
+}
+
+/// A position reported by a GGA or RMC sentence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsFix {
+    pub lon: f64,
+    pub lat: f64,
+    pub altitude: Option<f64>, // Metres above mean sea level, from GGA sentences
+}
+
+/// Fixes read by a single `GpsReader::poll`.
+pub struct GpsUpdate {
+    pub fixes: Vec<GpsFix>,
+    pub error: Option<String>, // Set once the source failed or closed
+}
+
+// Messages sent from the reader thread
+enum GpsMessage {
+    Fix(GpsFix),
+    Error(String),
+}
+
+/// Reads NMEA sentences on a background thread.
+pub struct GpsReader {
+    pub source: GpsSource,
+    receiver: Receiver<GpsMessage>,
+}
+
+impl GpsReader {
+    /// Opens `source` and starts reading from it. The thread exits once the
+    /// source closes, or with the first sentence it reads after the reader was
+    /// dropped; until then it stays blocked on a silent source.
+    pub fn start(source: GpsSource) -> Result<GpsReader, Box<dyn Error>> {
+        let reader: Box<dyn BufRead + Send> = match &source {
+            GpsSource::Serial(path) => Box::new(BufReader::new(fs::File::open(path)?)),
+            GpsSource::Gpsd(address) => {
+                let mut stream = TcpStream::connect(address)?;
+                // Ask gpsd to pass through the raw NMEA sentences
+                stream.write_all(b"?WATCH={\"enable\":true,\"nmea\":true};\n")?;
+                Box::new(BufReader::new(stream))
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || read_sentences(reader, sender));
+        Ok(GpsReader { source, receiver })
+    }
+
+    /// Returns the fixes received since the last call, together with the
+    /// error that stopped the reader thread, if any.
+    pub fn poll(&self) -> GpsUpdate {
+        let mut update = GpsUpdate {
+            fixes: Vec::new(),
+            error: None,
+        };
+        loop {
+            match self.receiver.try_recv() {
+                Ok(GpsMessage::Fix(fix)) => update.fixes.push(fix),
+                Ok(GpsMessage::Error(e)) => update.error = Some(e),
+                Err(TryRecvError::Empty) => return update,
+                Err(TryRecvError::Disconnected) => {
+                    update
+                        .error
+                        .get_or_insert_with(|| String::from("GPS reader stopped"));
+                    return update;
+                }
+            }
+        }
+    }
+}
+
+// Sends the fixes read from `reader` until it closes or fails, or the receiver
+// is dropped. Receivers that send both GGA and RMC sentences report each
+// position twice, so RMC fixes are only used until a GGA sentence was seen.
+fn read_sentences(mut reader: Box<dyn BufRead + Send>, sender: Sender<GpsMessage>) {
+    let mut line = Vec::new();
+    let mut seen_gga = false;
+    loop {
+        line.clear();
+        // Serial lines may carry noise, so bytes are decoded lossily
+        let message = match reader.read_until(b'\n', &mut line) {
+            Ok(0) => GpsMessage::Error(String::from("GPS source closed")),
+            Ok(_) => {
+                let sentence = String::from_utf8_lossy(&line);
+                match parse_nmea(&sentence) {
+                    Some(fix) if is_gga(&sentence) => {
+                        seen_gga = true;
+                        GpsMessage::Fix(fix)
+                    }
+                    Some(fix) if !seen_gga => GpsMessage::Fix(fix),
+                    _ => continue,
+                }
+            }
+            Err(e) => GpsMessage::Error(e.to_string()),
+        };
+        let is_error = matches!(message, GpsMessage::Error(_));
+        // Sending fails once the reader was dropped
+        if sender.send(message).is_err() || is_error {
+            return;
+        }
+    }
+}
+
+// Whether `sentence` is a GGA sentence of any talker
+fn is_gga(sentence: &str) -> bool {
+    sentence.trim().get(3..6) == Some("GGA")
+}
+
+/// Extracts the position from a GGA or RMC sentence. Sentences with a bad
+/// checksum or without a valid fix yield `None`.
+pub fn parse_nmea(sentence: &str) -> Option<GpsFix> {
+    let body = sentence.trim().strip_prefix('$')?;
+    let body = match body.split_once('*') {
+        Some((body, checksum)) => {
+            let expected = u8::from_str_radix(checksum.get(..2)?, 16).ok()?;
+            if body.bytes().fold(0, |acc, b| acc ^ b) != expected {
+                return None;
+            }
+            body
+        }
+        None => body,
+    };
+
+    let fields: Vec<&str> = body.split(',').collect();
+    let sentence_type = fields.first()?.get(2..)?; // Skip the talker ID, e.g. "GP" or "GN"
+    let (lat_idx, lon_idx) = match sentence_type {
+        // Fix quality 0 means no fix
+        "GGA" if fields.get(6).is_some_and(|q| !q.is_empty() && *q != "0") => (2, 4),
+        // Status A means the data is valid
+        "RMC" if fields.get(2) == Some(&"A") => (3, 5),
+        _ => return None,
+    };
+    let lat = parse_coordinate(fields.get(lat_idx)?, fields.get(lat_idx + 1)?, 2)?;
+    let lon = parse_coordinate(fields.get(lon_idx)?, fields.get(lon_idx + 1)?, 3)?;
+    let altitude = match sentence_type {
+        "GGA" => fields.get(9).and_then(|a| a.parse().ok()),
+        _ => None,
+    };
+    Some(GpsFix { lon, lat, altitude })
+}
+
+// Converts NMEA "ddmm.mmmm" / "dddmm.mmmm" plus hemisphere to decimal degrees
+fn parse_coordinate(value: &str, hemisphere: &str, degree_digits: usize) -> Option<f64> {
+    let degrees: f64 = value.get(..degree_digits)?.parse().ok()?;
+    let minutes: f64 = value.get(degree_digits..)?.parse().ok()?;
+    let decimal = degrees + minutes / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(decimal),
+        "S" | "W" => Some(-decimal),
+        _ => None,
+    }
+}
+
+/// Writes the recorded track as a GeoJSON LineString feature, with altitudes
+/// as third coordinate if any fix has one. Fixes without an altitude take the
+/// one of the fix before them, or of the first fix with one.
+pub fn save_track(path: &Path, track: &[GpsFix]) -> Result<(), Box<dyn Error>> {
+    let mut altitude = track.iter().find_map(|fix| fix.altitude);
+    let coordinates = track
+        .iter()
+        .map(|fix| {
+            altitude = fix.altitude.or(altitude);
+            match altitude {
+                Some(altitude) => vec![fix.lon, fix.lat, altitude],
+                None => vec![fix.lon, fix.lat],
+            }
+        })
+        .collect();
+    let collection = FeatureCollection {
+        bbox: None,
+        features: vec![Feature::from(Geometry::new(Value::LineString(coordinates)))],
+        foreign_members: None,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, collection.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const GGA: &str = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+    const RMC: &str = "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+
+    fn fixes_read_from(text: &str) -> Vec<GpsFix> {
+        let (sender, receiver) = mpsc::channel();
+        read_sentences(Box::new(Cursor::new(text.to_string())), sender);
+        receiver
+            .try_iter()
+            .filter_map(|message| match message {
+                GpsMessage::Fix(fix) => Some(fix),
+                GpsMessage::Error(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gga_and_rmc_sentences_give_the_same_position() {
+        let gga = parse_nmea(GGA).unwrap();
+        let rmc = parse_nmea(RMC).unwrap();
+        assert!((gga.lat - 48.1173).abs() < 1e-9);
+        assert!((gga.lon - 11.516_666_666).abs() < 1e-6);
+        assert_eq!(gga.altitude, Some(545.4));
+        assert_eq!((rmc.lon, rmc.lat, rmc.altitude), (gga.lon, gga.lat, None));
+        // A corrupted checksum or a void RMC is not a fix
+        assert_eq!(parse_nmea(&GGA.replace("*47", "*48")), None);
+        assert_eq!(
+            parse_nmea(&RMC.replace(",A,", ",V,").replace("*6A", "")),
+            None
+        );
+    }
+
+    #[test]
+    fn each_epoch_is_recorded_once() {
+        let both = format!("{}\n{}\n{}\n{}\n", RMC, GGA, RMC, GGA);
+        let fixes = fixes_read_from(&both);
+        // The RMC before the first GGA is used, later ones are not
+        assert_eq!(fixes.len(), 3);
+        assert_eq!(fixes[1].altitude, Some(545.4));
+        assert_eq!(fixes_read_from(&format!("{}\n{}\n", RMC, RMC)).len(), 2);
+    }
+
+    #[test]
+    fn fixes_without_altitude_take_the_previous_one() {
+        let path = std::env::temp_dir().join("plots_gps_track_test.geojson");
+        let at = |altitude| GpsFix {
+            lon: 11.5,
+            lat: 48.1,
+            altitude,
+        };
+        save_track(
+            &path,
+            &[at(None), at(Some(500.0)), at(None), at(Some(510.0))],
+        )
+        .unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let collection: FeatureCollection = text.parse().unwrap();
+        let geometry = collection.features[0].geometry.as_ref().unwrap();
+        let Value::LineString(coordinates) = &geometry.value else {
+            panic!("not a line");
+        };
+        let altitudes: Vec<f64> = coordinates.iter().map(|c| c[2]).collect();
+        assert_eq!(altitudes, vec![500.0, 500.0, 500.0, 510.0]);
+    }
+}
@@ -0,0 +1,125 @@
+// history.rs
+
+use crate::app::App;
+use crate::session::Session;
+
+// Changes kept for undoing; the oldest are dropped first
+const MAX_UNDO_STEPS: usize = 100;
+
+/// Undo and redo stacks of the selection, colors, output filename and plot
+/// options, each state recorded as a serialized `Session`. A change is recorded
+/// once per key or click, or once per text field or popup for edits that take
+/// several keys.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<String>,
+    redo: Vec<String>,
+    current: Option<String>, // State after the last recorded change
+}
+
+/// The state of `app` that undo restores.
+pub fn snapshot(app: &App) -> String {
+    serde_json::to_string(&Session::from_app(app)).unwrap_or_default()
+}
+
+/// Puts `app` back into a state taken by `snapshot`. Returns the files of
+/// that state that are no longer listed.
+pub fn restore(app: &mut App, state: &str) -> Result<Vec<String>, String> {
+    let session: Session = serde_json::from_str(state).map_err(|e| e.to_string())?;
+    session.apply(app)
+}
+
+impl History {
+    /// Whether the state before the next change is unknown, so it has to be
+    /// taken with `begin` first.
+    pub fn needs_baseline(&self) -> bool {
+        self.current.is_none()
+    }
+
+    /// Remembers `state` as the one before the change about to be made.
+    pub fn begin(&mut self, state: String) {
+        self.current = Some(state);
+    }
+
+    /// Forgets the remembered state after something other than a key or click
+    /// changed it, such as a download being listed.
+    pub fn invalidate(&mut self) {
+        self.current = None;
+    }
+
+    /// Records a change if `state` differs from the one before it, and keeps
+    /// `state` as the one before the next change.
+    pub fn commit(&mut self, state: String) {
+        let Some(before) = self.current.replace(state) else {
+            return;
+        };
+        if self.current.as_ref() == Some(&before) {
+            return;
+        }
+        self.undo.push(before);
+        if self.undo.len() > MAX_UNDO_STEPS {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Steps back from the state after the last change; returns the state to
+    /// restore, if any.
+    pub fn undo(&mut self) -> Option<String> {
+        let previous = self.undo.pop()?;
+        // Undoing is not a change of its own: the state it restores is taken
+        // as the one before the next change
+        self.redo.extend(self.current.take());
+        Some(previous)
+    }
+
+    /// Steps forward again after `undo`.
+    pub fn redo(&mut self) -> Option<String> {
+        let next = self.redo.pop()?;
+        self.undo.extend(self.current.take());
+        Some(next)
+    }
+
+    pub fn undo_steps(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_steps(&self) -> usize {
+        self.redo.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changes_are_recorded() {
+        let mut history = History::default();
+        history.begin(String::from("a"));
+        history.commit(String::from("a"));
+        assert_eq!(history.undo_steps(), 0);
+        // The state after a change is the one before the next, without a new baseline
+        history.commit(String::from("b"));
+        assert!(!history.needs_baseline());
+        history.commit(String::from("c"));
+        assert_eq!(history.undo_steps(), 2);
+        assert_eq!(history.undo().as_deref(), Some("b"));
+        history.commit(String::from("b"));
+        assert_eq!(history.redo().as_deref(), Some("c"));
+        history.commit(String::from("c"));
+        assert_eq!((history.undo_steps(), history.redo_steps()), (2, 0));
+    }
+
+    #[test]
+    fn changes_made_in_the_background_are_not_undone() {
+        let mut history = History::default();
+        history.begin(String::from("a"));
+        history.commit(String::from("b"));
+        history.invalidate();
+        assert!(history.needs_baseline());
+        history.begin(String::from("b with download"));
+        history.commit(String::from("c"));
+        assert_eq!(history.undo().as_deref(), Some("b with download"));
+    }
+}
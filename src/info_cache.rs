@@ -0,0 +1,85 @@
+// info_cache.rs
+
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error, fs, path::Path, time::UNIX_EPOCH};
+
+use crate::app::GeoJsonInfo;
+use crate::loader;
+
+pub const INFO_CACHE_FILE: &str = "info_cache.json";
+
+/// Metadata of the files parsed by earlier runs, by path, so the info panel of
+/// a large file and selections by metadata do not need to parse it again after
+/// a restart. An entry is only used while its file keeps the size and
+/// modification time it was parsed with.
+#[derive(Default, Serialize, Deserialize)]
+pub struct InfoCache {
+    entries: HashMap<String, CachedInfo>,
+    #[serde(skip)]
+    changed: bool, // Entries were added since the cache was read
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedInfo {
+    size: u64,
+    modified: u64, // Nanoseconds since the Unix epoch
+    info: GeoJsonInfo,
+}
+
+impl InfoCache {
+    /// Reads the cache written by the last run; empty if there is none.
+    pub fn load() -> Result<InfoCache, Box<dyn Error>> {
+        match fs::read_to_string(INFO_CACHE_FILE) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(InfoCache::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the cache to `info_cache.json` in the working directory if it
+    /// changed, leaving out files that were changed or removed since.
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        if !self.changed {
+            return Ok(());
+        }
+        self.entries
+            .retain(|path, entry| stamp(Path::new(path)) == Some((entry.size, entry.modified)));
+        fs::write(INFO_CACHE_FILE, serde_json::to_string(self)?)?;
+        self.changed = false;
+        Ok(())
+    }
+
+    /// Metadata of `path` as it was parsed, unless the file changed since.
+    pub fn get(&self, path: &Path) -> Option<GeoJsonInfo> {
+        let entry = self.entries.get(&path.to_string_lossy().into_owned())?;
+        (stamp(path)? == (entry.size, entry.modified)).then(|| entry.info.clone())
+    }
+
+    /// Remembers `info`, the metadata of `path` as just parsed. Files that
+    /// failed to parse are left out, to be tried again, and so are OSM
+    /// extracts, whose features depend on the `[osm] tags` of the run.
+    pub fn insert(&mut self, path: &Path, info: &GeoJsonInfo) {
+        if info.parse_error.is_some() || info.header_only || loader::is_osm_pbf(path) {
+            return;
+        }
+        let Some((size, modified)) = stamp(path) else {
+            return;
+        };
+        self.entries.insert(
+            path.to_string_lossy().into_owned(),
+            CachedInfo {
+                size,
+                modified,
+                info: info.clone(),
+            },
+        );
+        self.changed = true;
+    }
+}
+
+// Size and modification time of the file at `path`
+fn stamp(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_nanos() as u64))
+}
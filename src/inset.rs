@@ -0,0 +1,123 @@
+// inset.rs
+
+use geojson::Value;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::plot;
+
+// Natural Earth 1:110m coastlines, small enough to build into the program so
+// the inset never depends on the data directory
+const WORLD_COASTLINES: &str = include_str!("../data/geojson/ne_110m_coastline.geojson");
+
+/// Widths of the inset in percent of the map width, cycled through in the TUI.
+pub const INSET_SIZES: [u32; 4] = [15, 20, 25, 33];
+pub const DEFAULT_INSET_SIZE: u32 = 20;
+
+/// Corner of the map holding the overview inset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InsetCorner {
+    UpperLeft,
+    #[default]
+    UpperRight,
+    LowerLeft,
+    LowerRight,
+}
+
+impl InsetCorner {
+    pub fn parse(text: &str) -> Option<InsetCorner> {
+        match text {
+            "upper-left" | "top-left" => Some(InsetCorner::UpperLeft),
+            "upper-right" | "top-right" => Some(InsetCorner::UpperRight),
+            "lower-left" | "bottom-left" => Some(InsetCorner::LowerLeft),
+            "lower-right" | "bottom-right" => Some(InsetCorner::LowerRight),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            InsetCorner::UpperLeft => "upper-left",
+            InsetCorner::UpperRight => "upper-right",
+            InsetCorner::LowerLeft => "lower-left",
+            InsetCorner::LowerRight => "lower-right",
+        }
+    }
+}
+
+/// Overview map of the world in a corner of the plot, with the plotted extent
+/// outlined on it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Inset {
+    pub corner: InsetCorner,
+    pub size: u32, // Width in percent of the map width
+}
+
+impl Default for Inset {
+    fn default() -> Self {
+        Inset {
+            corner: InsetCorner::default(),
+            size: DEFAULT_INSET_SIZE,
+        }
+    }
+}
+
+impl Inset {
+    /// The next setting of the TUI toggle: off, then every corner in turn.
+    pub fn next(inset: Option<Inset>) -> Option<Inset> {
+        let size = inset.map_or(DEFAULT_INSET_SIZE, |inset| inset.size);
+        let corner = match inset.map(|inset| inset.corner) {
+            None => Some(InsetCorner::UpperLeft),
+            Some(InsetCorner::UpperLeft) => Some(InsetCorner::UpperRight),
+            Some(InsetCorner::UpperRight) => Some(InsetCorner::LowerLeft),
+            Some(InsetCorner::LowerLeft) => Some(InsetCorner::LowerRight),
+            Some(InsetCorner::LowerRight) => None,
+        };
+        corner.map(|corner| Inset { corner, size })
+    }
+
+    /// The same inset at the next of `INSET_SIZES`.
+    pub fn larger(self) -> Inset {
+        let next = INSET_SIZES
+            .iter()
+            .position(|&size| size == self.size)
+            .map_or(0, |i| (i + 1) % INSET_SIZES.len());
+        Inset {
+            size: INSET_SIZES[next],
+            ..self
+        }
+    }
+
+    /// Description for the Style tab and notifications, e.g. `upper-right, 20%`.
+    pub fn label(inset: Option<Inset>) -> String {
+        match inset {
+            Some(inset) => format!("{}, {}%", inset.corner.name(), inset.size),
+            None => String::from("Off"),
+        }
+    }
+}
+
+/// Coastlines of the world as lon/lat lines, parsed once.
+pub fn world_coastlines() -> &'static [Vec<(f64, f64)>] {
+    static LINES: OnceLock<Vec<Vec<(f64, f64)>>> = OnceLock::new();
+    LINES.get_or_init(|| {
+        let Ok(geojson) = WORLD_COASTLINES.parse() else {
+            return Vec::new();
+        };
+        let mut lines = Vec::new();
+        for feature in plot::into_features(geojson) {
+            let Some(geometry) = feature.geometry else {
+                continue;
+            };
+            let mut add =
+                |line: &[Vec<f64>]| lines.push(line.iter().map(|p| (p[0], p[1])).collect());
+            match &geometry.value {
+                Value::LineString(line) => add(line),
+                Value::MultiLineString(parts) => parts.iter().for_each(|line| add(line)),
+                _ => {}
+            }
+        }
+        lines
+    })
+}
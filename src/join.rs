@@ -0,0 +1,267 @@
+// join.rs
+
+use geojson::{Feature, JsonObject};
+use serde_json::Value;
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::plot;
+
+// Delimiters told apart by which of them the header line has most of
+const DELIMITERS: [char; 3] = [',', ';', '\t'];
+
+/// A table read from a CSV file: the header and the rows under it, each with
+/// as many cells as the header (missing cells are empty).
+#[derive(Debug, Clone)]
+pub struct CsvTable {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Reads a CSV file separated by commas, semicolons or tabs.
+pub fn read_csv(path: &Path) -> Result<CsvTable, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_csv(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Parses CSV text whose first line is the header. Fields may be quoted with
+/// `"`, holding delimiters, line breaks and `""` for a quote.
+pub fn parse_csv(text: &str) -> Result<CsvTable, String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let header = text.lines().next().unwrap_or("");
+    let delimiter = DELIMITERS
+        .into_iter()
+        .max_by_key(|&d| header.matches(d).count())
+        .unwrap_or(',');
+
+    let mut records: Vec<Vec<String>> = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            c if quoted => field.push(c),
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(String::from("a quoted field is never closed"));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    // Blank lines, e.g. at the end, are not rows
+    records.retain(|record| !(record.len() == 1 && record[0].trim().is_empty()));
+
+    let mut records = records.into_iter();
+    let columns: Vec<String> = records
+        .next()
+        .ok_or("the file is empty")?
+        .into_iter()
+        .map(|column| column.trim().to_string())
+        .collect();
+    let rows = records
+        .map(|mut row| {
+            row.resize(columns.len(), String::new());
+            row
+        })
+        .collect();
+    Ok(CsvTable { columns, rows })
+}
+
+/// What to join: a CSV file and the feature property matched against one of
+/// its columns, written `<file.csv> <property>[=<column>]`; the column has the
+/// name of the property unless given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinSpec {
+    pub csv: String,
+    pub property: String,
+    pub column: String,
+}
+
+impl JoinSpec {
+    pub fn parse(text: &str) -> Result<JoinSpec, String> {
+        let text = text.trim();
+        let (csv, key) = text
+            .rsplit_once(char::is_whitespace)
+            .ok_or_else(|| format!("Expected <file.csv> <property>[=<column>], got '{}'", text))?;
+        JoinSpec::from_parts(csv.trim(), key)
+    }
+
+    /// The spec of `csv` joined on `key`, `<property>[=<column>]`.
+    pub fn from_parts(csv: &str, key: &str) -> Result<JoinSpec, String> {
+        let (property, column) = key.split_once('=').unwrap_or((key, key));
+        let (property, column) = (property.trim(), column.trim());
+        if csv.is_empty() || property.is_empty() || column.is_empty() {
+            return Err(format!(
+                "Expected <file.csv> <property>[=<column>], got '{} {}'",
+                csv, key
+            ));
+        }
+        Ok(JoinSpec {
+            csv: csv.to_string(),
+            property: property.to_string(),
+            column: column.to_string(),
+        })
+    }
+}
+
+/// Outcome of a join, for the notification or console.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JoinReport {
+    pub matched: usize, // Features that found a row
+    pub total: usize,
+    pub columns: usize, // Columns added to the matched features
+}
+
+/// Adds the cells of the row of `table` whose `column` equals the `property`
+/// of each feature to its properties, e.g. population figures to the
+/// countries they are keyed by. Keys are compared as text, with numbers
+/// compared by value so `7`, `7.0` and `007` match. Cells that are numbers
+/// become numbers and empty cells null; a column overwrites a property of the
+/// same name. When several rows share a key the first is used.
+pub fn join_features(
+    features: &mut [Feature],
+    table: &CsvTable,
+    property: &str,
+    column: &str,
+) -> Result<JoinReport, String> {
+    let key_column = table
+        .columns
+        .iter()
+        .position(|c| c == column)
+        .ok_or_else(|| {
+            format!(
+                "The CSV has no '{}' column (it has {})",
+                column,
+                table.columns.join(", ")
+            )
+        })?;
+    let mut rows: HashMap<String, &Vec<String>> = HashMap::new();
+    for row in &table.rows {
+        rows.entry(key_text(&row[key_column])).or_insert(row);
+    }
+
+    let mut matched = 0;
+    for feature in features.iter_mut() {
+        let Some(row) = feature
+            .property(property)
+            .filter(|value| !value.is_null())
+            .and_then(|value| rows.get(&key_text(&plot::property_to_string(value))))
+        else {
+            continue;
+        };
+        let properties = feature.properties.get_or_insert_with(JsonObject::new);
+        for (i, (name, cell)) in table.columns.iter().zip(row.iter()).enumerate() {
+            if i != key_column {
+                properties.insert(name.clone(), cell_value(cell));
+            }
+        }
+        matched += 1;
+    }
+    Ok(JoinReport {
+        matched,
+        total: features.len(),
+        columns: table.columns.len() - 1,
+    })
+}
+
+// A key as compared: trimmed, and numbers written the same whatever their
+// leading zeros or decimals
+fn key_text(text: &str) -> String {
+    let text = text.trim();
+    match text.parse::<f64>() {
+        Ok(number) if number.is_finite() => number.to_string(),
+        _ => text.to_string(),
+    }
+}
+
+fn cell_value(cell: &str) -> Value {
+    let cell = cell.trim();
+    if cell.is_empty() {
+        return Value::Null;
+    }
+    match serde_json::from_str::<serde_json::Number>(cell) {
+        Ok(number) => Value::Number(number),
+        Err(_) => Value::String(cell.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn quoted_fields_keep_delimiters_and_quotes() {
+        let table =
+            parse_csv("\u{feff}code;name;note\r\n7;\"Lyon; Rhône\";\"a \"\"b\"\"\nc\"\n8;Nice\n\n")
+                .unwrap();
+        assert_eq!(table.columns, ["code", "name", "note"]);
+        assert_eq!(table.rows[0], ["7", "Lyon; Rhône", "a \"b\"\nc"]);
+        assert_eq!(table.rows[1], ["8", "Nice", ""]);
+        assert!(parse_csv("a,b\n\"1,2").is_err());
+    }
+
+    #[test]
+    fn rows_are_joined_by_key_with_numbers_compared_by_value() {
+        let table = parse_csv("id,pop,label\n007,1200,\n9,3,x\n9,4,y").unwrap();
+        let feature = |id| Feature {
+            properties: json!({"code": id}).as_object().cloned(),
+            ..Default::default()
+        };
+        let mut features = [
+            feature(json!(7.0)),
+            feature(json!("9")),
+            feature(json!(null)),
+        ];
+        let report = join_features(&mut features, &table, "code", "id").unwrap();
+        assert_eq!(
+            report,
+            JoinReport {
+                matched: 2,
+                total: 3,
+                columns: 2
+            }
+        );
+        assert_eq!(features[0].property("pop"), Some(&json!(1200)));
+        assert_eq!(features[0].property("label"), Some(&Value::Null));
+        assert_eq!(
+            features[1].property("label"),
+            Some(&json!("x")),
+            "the first row wins"
+        );
+        assert!(!features[2].contains_property("pop"));
+        assert!(join_features(&mut features, &table, "code", "iso").is_err());
+    }
+
+    #[test]
+    fn specs_name_the_property_and_optionally_the_column() {
+        let spec = JoinSpec::parse("data/pop 2020.csv ISO=iso_a3").unwrap();
+        assert_eq!(
+            (
+                spec.csv.as_str(),
+                spec.property.as_str(),
+                spec.column.as_str()
+            ),
+            ("data/pop 2020.csv", "ISO", "iso_a3")
+        );
+        assert_eq!(JoinSpec::parse("pop.csv ISO").unwrap().column, "ISO");
+        assert!(JoinSpec::parse("pop.csv").is_err());
+        assert!(JoinSpec::parse("pop.csv ISO=").is_err());
+    }
+}
@@ -0,0 +1,199 @@
+// json_preview.rs
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+// Bytes read from the start of a file; the rest of larger files is not shown
+pub const MAX_PREVIEW_BYTES: u64 = 64 * 1024;
+
+/// The start of a file as indented JSON text.
+pub struct JsonPreview {
+    pub lines: Vec<String>,
+    pub truncated: bool, // The file is longer than `MAX_PREVIEW_BYTES`
+}
+
+/// What a piece of a preview line is, for highlighting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JsonToken {
+    Key,
+    String,
+    Number,
+    Literal, // true, false or null
+    Punctuation,
+    Space,
+}
+
+impl JsonPreview {
+    /// Reads at most `MAX_PREVIEW_BYTES` of `path` and indents them. Works on
+    /// text cut off in the middle, and on JSON Lines, one value after another.
+    pub fn read(path: &Path) -> io::Result<JsonPreview> {
+        let length = path.metadata()?.len();
+        let mut bytes = Vec::new();
+        File::open(path)?
+            .take(MAX_PREVIEW_BYTES)
+            .read_to_end(&mut bytes)?;
+        Ok(JsonPreview {
+            lines: pretty_lines(&String::from_utf8_lossy(&bytes)),
+            truncated: length > MAX_PREVIEW_BYTES,
+        })
+    }
+}
+
+/// Breaks `text` into lines indented by two spaces per level. Arrays without
+/// objects or arrays inside, such as positions, stay on one line.
+pub fn pretty_lines(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut depth = 0usize;
+    let mut inline_depth = 0usize; // Nesting of the one-line array being written
+    let mut i = 0;
+    let new_line = |lines: &mut Vec<String>, line: &mut String, depth: usize| {
+        if !line.trim().is_empty() {
+            lines.push(std::mem::take(line));
+        }
+        *line = "  ".repeat(depth);
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '"' => {
+                let end = string_end(&chars, i);
+                line.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+            '{' | '[' if inline_depth > 0 => {
+                inline_depth += 1;
+                line.push(c);
+            }
+            '[' if is_flat_array(&chars, i) => {
+                inline_depth = 1;
+                line.push(c);
+            }
+            '{' | '[' => {
+                line.push(c);
+                depth += 1;
+                new_line(&mut lines, &mut line, depth);
+            }
+            '}' | ']' if inline_depth > 0 => {
+                inline_depth -= 1;
+                line.push(c);
+            }
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                new_line(&mut lines, &mut line, depth);
+                line.push(c);
+            }
+            ',' if inline_depth > 0 => line.push_str(", "),
+            ',' => {
+                line.push(c);
+                new_line(&mut lines, &mut line, depth);
+            }
+            ':' => line.push_str(": "),
+            c if c.is_whitespace() => {
+                // Values of JSON Lines files start on a line of their own
+                if c == '\n' && depth == 0 {
+                    new_line(&mut lines, &mut line, 0);
+                }
+            }
+            c => line.push(c),
+        }
+        i += 1;
+    }
+    if !line.trim().is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+// Index after the string starting with the quote at `start`, or the end of the text
+fn string_end(chars: &[char], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+// Whether the array opened at `start` closes before any object or array opens
+fn is_flat_array(chars: &[char], start: usize) -> bool {
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '"' => {
+                i = string_end(chars, i);
+                continue;
+            }
+            '[' | '{' => return false,
+            ']' => return true,
+            _ => {}
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Splits a line of `pretty_lines` into highlighted pieces.
+pub fn tokens(line: &str) -> Vec<(JsonToken, &str)> {
+    let mut tokens = Vec::new();
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let token = match bytes[i] {
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i = (i + 1).min(bytes.len());
+                if line[i..].starts_with(':') {
+                    JsonToken::Key
+                } else {
+                    JsonToken::String
+                }
+            }
+            b' ' => {
+                while i < bytes.len() && bytes[i] == b' ' {
+                    i += 1;
+                }
+                JsonToken::Space
+            }
+            b'{' | b'}' | b'[' | b']' | b',' | b':' => {
+                i += 1;
+                JsonToken::Punctuation
+            }
+            b'-' | b'0'..=b'9' => {
+                while i < bytes.len()
+                    && matches!(bytes[i], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+                {
+                    i += 1;
+                }
+                JsonToken::Number
+            }
+            _ => {
+                while i < bytes.len() && bytes[i].is_ascii_alphabetic() {
+                    i += 1;
+                }
+                if i == start {
+                    // Anything else, e.g. text of a file that is not JSON
+                    i = line[start..]
+                        .char_indices()
+                        .nth(1)
+                        .map_or(line.len(), |(offset, _)| start + offset);
+                }
+                JsonToken::Literal
+            }
+        };
+        tokens.push((token, &line[start..i]));
+    }
+    tokens
+}
@@ -0,0 +1,690 @@
+// keymap.rs
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+
+use crate::config::ConfigValue;
+
+/// Actions that can be triggered from the file list (Navigation mode).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Down,
+    Up,
+    ToggleSelection,
+    SelectDown,
+    SelectUp,
+    SelectAll,
+    DeselectAll,
+    InvertSelection,
+    SelectWhere,
+    OpenFile,
+    Refetch,
+    Wfs,
+    Serve,
+    Undo,
+    Redo,
+    Plot,
+    CopyPlot,
+    Export,
+    CycleColor,
+    Rename,
+    EditTitle,
+    Search,
+    TogglePoints,
+    ToggleLines,
+    TogglePolygons,
+    ToggleScaleBar,
+    ToggleNorthArrow,
+    ToggleBasemap,
+    ToggleThumbnail,
+    FigureMode,
+    Projection,
+    ToggleAspect,
+    ToggleClusters,
+    Inset,
+    InsetSize,
+    SimplifyLayer,
+    SetCrs,
+    FilterLayer,
+    SelectFeatures,
+    AttributeTable,
+    Sessions,
+    Styling,
+    Follow,
+    Gps,
+    RecordTrack,
+    AddMarker,
+    ClearMarkers,
+    ExportExtent,
+    NavigatePreview,
+    EditExtent,
+    GoToPlace,
+    JumpTo,
+    TimeRange,
+    TimeEarlier,
+    TimeLater,
+    ClipMask,
+    FocusLayer,
+    LayerProperties,
+    Validate,
+    FindDuplicates,
+    CheckTopology,
+    Buffer,
+    Dissolve,
+    JoinCsv,
+    Points,
+    Minify,
+    SnapToGrid,
+    DividerLeft,
+    DividerRight,
+    PanelTab,
+    InfoTab,
+    InfoScrollDown,
+    InfoScrollUp,
+    Quit,
+    Logs,
+    Help,
+}
+
+impl Action {
+    // Order in which actions are listed on the Help screen
+    pub const ALL: [Action; 76] = [
+        Action::Down,
+        Action::Up,
+        Action::ToggleSelection,
+        Action::SelectDown,
+        Action::SelectUp,
+        Action::SelectAll,
+        Action::DeselectAll,
+        Action::InvertSelection,
+        Action::SelectWhere,
+        Action::OpenFile,
+        Action::Refetch,
+        Action::Wfs,
+        Action::Serve,
+        Action::Undo,
+        Action::Redo,
+        Action::Plot,
+        Action::CopyPlot,
+        Action::Export,
+        Action::CycleColor,
+        Action::Rename,
+        Action::EditTitle,
+        Action::Search,
+        Action::TogglePoints,
+        Action::ToggleLines,
+        Action::TogglePolygons,
+        Action::ToggleScaleBar,
+        Action::ToggleNorthArrow,
+        Action::ToggleBasemap,
+        Action::ToggleThumbnail,
+        Action::FigureMode,
+        Action::Projection,
+        Action::ToggleAspect,
+        Action::ToggleClusters,
+        Action::Inset,
+        Action::InsetSize,
+        Action::SimplifyLayer,
+        Action::SetCrs,
+        Action::FilterLayer,
+        Action::SelectFeatures,
+        Action::AttributeTable,
+        Action::Sessions,
+        Action::Styling,
+        Action::Follow,
+        Action::Gps,
+        Action::RecordTrack,
+        Action::AddMarker,
+        Action::ClearMarkers,
+        Action::ExportExtent,
+        Action::NavigatePreview,
+        Action::EditExtent,
+        Action::GoToPlace,
+        Action::JumpTo,
+        Action::TimeRange,
+        Action::TimeEarlier,
+        Action::TimeLater,
+        Action::ClipMask,
+        Action::FocusLayer,
+        Action::LayerProperties,
+        Action::Validate,
+        Action::FindDuplicates,
+        Action::CheckTopology,
+        Action::Buffer,
+        Action::Dissolve,
+        Action::JoinCsv,
+        Action::Points,
+        Action::Minify,
+        Action::SnapToGrid,
+        Action::DividerLeft,
+        Action::DividerRight,
+        Action::PanelTab,
+        Action::InfoTab,
+        Action::InfoScrollDown,
+        Action::InfoScrollUp,
+        Action::Quit,
+        Action::Logs,
+        Action::Help,
+    ];
+
+    /// Name used for the action in the `[keys]` section of the config file.
+    pub fn config_name(self) -> &'static str {
+        match self {
+            Action::Down => "down",
+            Action::Up => "up",
+            Action::ToggleSelection => "toggle_selection",
+            Action::SelectDown => "select_down",
+            Action::SelectUp => "select_up",
+            Action::SelectAll => "select_all",
+            Action::DeselectAll => "deselect_all",
+            Action::InvertSelection => "invert_selection",
+            Action::SelectWhere => "select_where",
+            Action::OpenFile => "open_file",
+            Action::Refetch => "refetch",
+            Action::Wfs => "wfs",
+            Action::Serve => "serve",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Plot => "plot",
+            Action::CopyPlot => "copy_plot",
+            Action::Export => "export_layers",
+            Action::CycleColor => "cycle_color",
+            Action::Rename => "rename",
+            Action::EditTitle => "edit_title",
+            Action::Search => "search",
+            Action::TogglePoints => "toggle_points",
+            Action::ToggleLines => "toggle_lines",
+            Action::TogglePolygons => "toggle_polygons",
+            Action::ToggleScaleBar => "toggle_scale_bar",
+            Action::ToggleNorthArrow => "toggle_north_arrow",
+            Action::ToggleBasemap => "toggle_basemap",
+            Action::ToggleThumbnail => "toggle_thumbnail",
+            Action::FigureMode => "figure_mode",
+            Action::Projection => "projection",
+            Action::ToggleAspect => "toggle_aspect",
+            Action::ToggleClusters => "toggle_clusters",
+            Action::Inset => "inset",
+            Action::InsetSize => "inset_size",
+            Action::SimplifyLayer => "simplify_layer",
+            Action::SetCrs => "set_crs",
+            Action::FilterLayer => "filter_layer",
+            Action::SelectFeatures => "select_features",
+            Action::AttributeTable => "attribute_table",
+            Action::Sessions => "sessions",
+            Action::Styling => "styling",
+            Action::Follow => "follow",
+            Action::Gps => "gps",
+            Action::RecordTrack => "record_track",
+            Action::AddMarker => "add_marker",
+            Action::ClearMarkers => "clear_markers",
+            Action::ExportExtent => "export_extent",
+            Action::NavigatePreview => "navigate_preview",
+            Action::EditExtent => "edit_extent",
+            Action::GoToPlace => "go_to_place",
+            Action::JumpTo => "jump_to",
+            Action::TimeRange => "time_range",
+            Action::TimeEarlier => "time_earlier",
+            Action::TimeLater => "time_later",
+            Action::ClipMask => "clip_mask",
+            Action::FocusLayer => "focus_layer",
+            Action::LayerProperties => "layer_properties",
+            Action::Validate => "validate",
+            Action::FindDuplicates => "find_duplicates",
+            Action::CheckTopology => "check_topology",
+            Action::Buffer => "buffer",
+            Action::Dissolve => "dissolve",
+            Action::JoinCsv => "join_csv",
+            Action::Points => "points",
+            Action::Minify => "minify",
+            Action::SnapToGrid => "snap_to_grid",
+            Action::DividerLeft => "divider_left",
+            Action::DividerRight => "divider_right",
+            Action::PanelTab => "panel_tab",
+            Action::InfoTab => "info_tab",
+            Action::InfoScrollDown => "info_scroll_down",
+            Action::InfoScrollUp => "info_scroll_up",
+            Action::Quit => "quit",
+            Action::Logs => "logs",
+            Action::Help => "help",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Down => "Move down the file list",
+            Action::Up => "Move up the file list",
+            Action::ToggleSelection => "Toggle file selection",
+            Action::SelectDown => "Extend selection downwards",
+            Action::SelectUp => "Extend selection upwards",
+            Action::SelectAll => "Select all listed files",
+            Action::DeselectAll => "Deselect all listed files",
+            Action::InvertSelection => "Invert selection of listed files",
+            Action::SelectWhere => "Select listed files whose metadata matches an expression",
+            Action::OpenFile => {
+                "Open a GeoJSON file outside the data directory or at a URL, or an underlay image"
+            }
+            Action::Refetch => "Download the highlighted remote file again",
+            Action::Wfs => "List the feature types of a WFS service and fetch one as a layer",
+            Action::Serve => "Start or stop serving the selected layers to web browsers",
+            Action::Undo => "Undo the last selection, color, filename or option change",
+            Action::Redo => "Redo the last undone change",
+            Action::Plot => "Plot selected files",
+            Action::CopyPlot => "Plot selected files to the clipboard as a PNG image",
+            Action::Export => "Save selected layers as GeoJSON, a Shapefile or a GeoPackage",
+            Action::CycleColor => "Cycle next assignment color",
+            Action::Rename => "Rename output plot",
+            Action::EditTitle => "Edit plot title, subtitle and attribution",
+            Action::Search => "Start fuzzy search",
+            Action::TogglePoints => "Toggle Points visibility",
+            Action::ToggleLines => "Toggle Lines visibility",
+            Action::TogglePolygons => "Toggle Polygons visibility",
+            Action::ToggleScaleBar => "Toggle scale bar on output",
+            Action::ToggleNorthArrow => "Toggle north arrow on output",
+            Action::ToggleBasemap => "Toggle basemap tiles underneath the layers",
+            Action::ToggleThumbnail => "Toggle writing a thumbnail next to the plot",
+            Action::FigureMode => "Cycle figure mode (off/white/transparent)",
+            Action::Projection => "Cycle map projection (lon/lat/north polar/south polar)",
+            Action::ToggleAspect => "Toggle true aspect ratio on output",
+            Action::ToggleClusters => "Toggle clustering of nearby points",
+            Action::Inset => "Cycle the corner of the inset world map (or off)",
+            Action::InsetSize => "Cycle the size of the inset world map",
+            Action::SimplifyLayer => "Set simplification tolerance of the highlighted file",
+            Action::SetCrs => "Override the coordinate system of the highlighted file",
+            Action::FilterLayer => "Filter features of the highlighted file by an expression",
+            Action::SelectFeatures => {
+                "Check the individual features of the highlighted file to plot"
+            }
+            Action::AttributeTable => {
+                "Show the features of the highlighted file in a sortable table"
+            }
+            Action::Sessions => "Open Sessions (save/restore selections)",
+            Action::Styling => "Open Styling screen (attribute-based rules)",
+            Action::Follow => "Follow a growing GeoJSONL file",
+            Action::Gps => "Connect/disconnect the GPS receiver",
+            Action::RecordTrack => "Start/stop recording the GPS track",
+            Action::AddMarker => "Drop a marker at typed coordinates",
+            Action::ClearMarkers => "Remove all markers",
+            Action::ExportExtent => "Save the preview extent as a GeoJSON polygon",
+            Action::NavigatePreview => "Pan/zoom the preview and lock the plot extent",
+            Action::EditExtent => "Type an explicit plot extent (empty for auto)",
+            Action::GoToPlace => "Look up a place by name and zoom the preview and extent to it",
+            Action::JumpTo => "Center the preview on typed or copied coordinates",
+            Action::TimeRange => "Type the time range of features to plot (empty for all)",
+            Action::TimeEarlier => "Move the time range back by its length",
+            Action::TimeLater => "Move the time range forward by its length",
+            Action::ClipMask => "Use highlighted file as clip mask (cycle clip/shade/off)",
+            Action::FocusLayer => "Focus highlighted file, drawing the others grey",
+            Action::LayerProperties => {
+                "Set line and point symbols, opacity and geometry types of highlighted file"
+            }
+            Action::Validate => "Check highlighted file against the GeoJSON spec",
+            Action::FindDuplicates => "List duplicated geometries of highlighted file",
+            Action::CheckTopology => "List overlaps and gaps between polygons of highlighted file",
+            Action::Buffer => "Buffer highlighted file by a distance into a new file",
+            Action::Dissolve => "Union polygons of highlighted file by a property into a new file",
+            Action::JoinCsv => {
+                "Join columns of a CSV file to highlighted file by a key into a new file"
+            }
+            Action::Points => "Write centroids or label points of highlighted file into a new file",
+            Action::Minify => {
+                "Round coordinates of highlighted file and strip whitespace into a new file"
+            }
+            Action::SnapToGrid => "Snap coordinates of highlighted file to a grid into a new file",
+            Action::DividerLeft => "Move the divider left (narrower file list)",
+            Action::DividerRight => "Move the divider right (wider file list)",
+            Action::PanelTab => {
+                "Switch the right panel between Info, Properties, Style, JSON and Log"
+            }
+            Action::InfoTab => "Switch Info between details and attributes",
+            Action::InfoScrollDown => "Scroll the right panel down",
+            Action::InfoScrollUp => "Scroll the right panel up",
+            Action::Quit => "Quit the application",
+            Action::Logs => "Show the log of notifications, warnings and errors",
+            Action::Help => "Show Help screen",
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Down => &["j", "Down"],
+            Action::Up => &["k", "Up"],
+            Action::ToggleSelection => &["Space"],
+            Action::SelectDown => &["Shift+Down"],
+            Action::SelectUp => &["Shift+Up"],
+            Action::SelectAll => &["a", "A"],
+            Action::DeselectAll => &["d", "D"],
+            Action::InvertSelection => &["i", "I"],
+            Action::SelectWhere => &["Ctrl+a"],
+            Action::OpenFile => &["Ctrl+x"],
+            Action::Refetch => &["F5"],
+            Action::Wfs => &["F6"],
+            Action::Serve => &["F7"],
+            Action::Undo => &["u", "Ctrl+z"],
+            Action::Redo => &["Ctrl+r"],
+            Action::Plot => &["Enter"],
+            Action::CopyPlot => &["Ctrl+y"],
+            Action::Export => &["Ctrl+s"],
+            Action::CycleColor => &["c", "C"],
+            Action::Rename => &["r", "R"],
+            Action::EditTitle => &["Ctrl+t"],
+            Action::Search => &["/"],
+            Action::TogglePoints => &["p", "P"],
+            Action::ToggleLines => &["l", "L"],
+            Action::TogglePolygons => &["o", "O"],
+            Action::ToggleScaleBar => &["e", "E"],
+            Action::ToggleNorthArrow => &["n", "N"],
+            Action::ToggleBasemap => &["Alt+b"],
+            Action::ToggleThumbnail => &["Ctrl+g"],
+            Action::FigureMode => &["b", "B"],
+            Action::Projection => &["Alt+p"],
+            Action::ToggleAspect => &["Alt+a"],
+            Action::ToggleClusters => &["Alt+c"],
+            Action::Inset => &["Alt+i"],
+            Action::InsetSize => &["Alt+s"],
+            Action::SimplifyLayer => &["z", "Z"],
+            Action::SetCrs => &["Alt+r"],
+            Action::FilterLayer => &["Ctrl+l"],
+            Action::SelectFeatures => &["Right"],
+            Action::AttributeTable => &["F3"],
+            Action::Sessions => &["s", "S"],
+            Action::Styling => &["y", "Y"],
+            Action::Follow => &["f", "F"],
+            Action::Gps => &["g", "G"],
+            Action::RecordTrack => &["t", "T"],
+            Action::AddMarker => &["m", "M"],
+            Action::ClearMarkers => &["x", "X"],
+            Action::ExportExtent => &["w", "W"],
+            Action::NavigatePreview => &["v", "V"],
+            Action::EditExtent => &["Ctrl+e"],
+            Action::GoToPlace => &["Alt+g"],
+            Action::JumpTo => &["Alt+l"],
+            Action::TimeRange => &["Alt+t"],
+            Action::TimeEarlier => &["Alt+Left"],
+            Action::TimeLater => &["Alt+Right"],
+            Action::ClipMask => &["Ctrl+k"],
+            Action::FocusLayer => &["Ctrl+f"],
+            Action::LayerProperties => &["Ctrl+p"],
+            Action::Validate => &["Ctrl+v"],
+            Action::FindDuplicates => &["F4"],
+            Action::CheckTopology => &["F8"],
+            Action::Buffer => &["Ctrl+b"],
+            Action::Dissolve => &["Ctrl+d"],
+            Action::JoinCsv => &["Alt+j"],
+            Action::Points => &["Ctrl+o"],
+            Action::Minify => &["Ctrl+w"],
+            Action::SnapToGrid => &["Ctrl+u"],
+            Action::DividerLeft => &["Ctrl+Left"],
+            Action::DividerRight => &["Ctrl+Right"],
+            Action::PanelTab => &["Ctrl+n"],
+            Action::InfoTab => &["Tab"],
+            Action::InfoScrollDown => &["]"],
+            Action::InfoScrollUp => &["["],
+            Action::Quit => &["q", "Q"],
+            Action::Logs => &["F2"],
+            Action::Help => &["h", "H"],
+        }
+    }
+}
+
+/// A key together with the modifiers that must be held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Parses names like `"j"`, `"Enter"`, `"Ctrl+Left"` or `"F5"`.
+    pub fn parse(text: &str) -> Result<KeyBinding, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        // A lone "+" is a key, not a modifier separator
+        while let Some((modifier, key)) = rest.split_once('+').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("Unknown modifier '{}' in '{}'", modifier, text)),
+            };
+            rest = key;
+        }
+
+        let code = match rest.to_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            name => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                        Some(n @ 1..=12) => KeyCode::F(n),
+                        _ => return Err(format!("Unknown key '{}'", text)),
+                    },
+                }
+            }
+        };
+        Ok(KeyBinding { code, modifiers })
+    }
+
+    /// Whether `event` triggers this binding. Shift is implied by the case of
+    /// character keys, so it is ignored for them.
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        if event.code != self.code {
+            return false;
+        }
+        match self.code {
+            KeyCode::Char(_) => {
+                event.modifiers.difference(KeyModifiers::SHIFT)
+                    == self.modifiers.difference(KeyModifiers::SHIFT)
+            }
+            _ => event.modifiers == self.modifiers,
+        }
+    }
+
+    /// Human readable name, e.g. "Ctrl+Left".
+    pub fn display(&self) -> String {
+        let mut name = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            name.push_str("Ctrl+");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            name.push_str("Alt+");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            name.push_str("Shift+");
+        }
+        name.push_str(&match self.code {
+            KeyCode::Char(' ') => String::from("Space"),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Up => String::from("↑"),
+            KeyCode::Down => String::from("↓"),
+            KeyCode::Left => String::from("←"),
+            KeyCode::Right => String::from("→"),
+            KeyCode::F(n) => format!("F{}", n),
+            other => format!("{:?}", other),
+        });
+        name
+    }
+}
+
+/// Mapping from actions to the keys that trigger them.
+pub struct KeyMap {
+    bindings: Vec<(Action, Vec<KeyBinding>)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .iter()
+            .map(|&action| {
+                let keys = action
+                    .default_keys()
+                    .iter()
+                    .map(|key| KeyBinding::parse(key).expect("Invalid default key binding"))
+                    .collect();
+                (action, keys)
+            })
+            .collect();
+        KeyMap { bindings }
+    }
+}
+
+impl KeyMap {
+    /// Overrides bindings from the `[keys]` config section, e.g. `plot = "Enter"`
+    /// or `down = ["j", "Down"]`. A key bound in the config is removed from any
+    /// other action. Returns a message for every entry that could not be applied.
+    pub fn apply_config(&mut self, keys: &HashMap<String, ConfigValue>) -> Vec<String> {
+        let mut errors = Vec::new();
+        // Sorted so that conflicting entries resolve the same way on every start
+        let mut entries: Vec<_> = keys.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (name, value) in entries {
+            let Some(action) = Action::ALL.iter().find(|a| a.config_name() == name) else {
+                errors.push(format!("Unknown action '{}' in [keys]", name));
+                continue;
+            };
+            let Some(key_names) = value.as_list() else {
+                errors.push(format!("Expected a key or list of keys for '{}'", name));
+                continue;
+            };
+            let mut new_keys = Vec::new();
+            for key_name in key_names {
+                match KeyBinding::parse(&key_name) {
+                    Ok(binding) => new_keys.push(binding),
+                    Err(e) => errors.push(e),
+                }
+            }
+            for (_, keys) in self.bindings.iter_mut() {
+                keys.retain(|k| !new_keys.contains(k));
+            }
+            if let Some((_, keys)) = self.bindings.iter_mut().find(|(a, _)| a == action) {
+                *keys = new_keys;
+            }
+        }
+        errors
+    }
+
+    /// Returns the action bound to `event`, if any.
+    pub fn action_for(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.iter().any(|k| k.matches(event)))
+            .map(|(action, _)| *action)
+    }
+
+    /// Keys bound to `action` joined for display, e.g. "J/↓". Upper- and
+    /// lowercase variants of a letter are shown once.
+    pub fn keys_for(&self, action: Action) -> String {
+        let mut names: Vec<String> = Vec::new();
+        if let Some((_, keys)) = self.bindings.iter().find(|(a, _)| *a == action) {
+            for key in keys {
+                let name = match key.code {
+                    KeyCode::Char(c) if c.is_alphabetic() && key.modifiers.is_empty() => {
+                        c.to_uppercase().to_string()
+                    }
+                    _ => key.display(),
+                };
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        if names.is_empty() {
+            String::from("(unbound)")
+        } else {
+            names.join("/")
+        }
+    }
+
+    /// One "keys: description" line per action, in Help screen order.
+    pub fn help_lines(&self) -> Vec<String> {
+        Action::ALL
+            .iter()
+            .map(|&action| format!("{}: {}", self.keys_for(action), action.description()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    fn event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn key_names_are_parsed() {
+        let parsed = |text| KeyBinding::parse(text).unwrap();
+        assert_eq!(parsed("ctrl+Left").code, KeyCode::Left);
+        assert_eq!(
+            parsed("Ctrl+Alt+x").modifiers,
+            KeyModifiers::CONTROL | KeyModifiers::ALT
+        );
+        assert_eq!(parsed("+").code, KeyCode::Char('+'));
+        assert_eq!(parsed("Ctrl++").code, KeyCode::Char('+'));
+        assert_eq!(parsed("F12").code, KeyCode::F(12));
+        assert!(KeyBinding::parse("F13").is_err());
+        assert!(KeyBinding::parse("Hyper+x").is_err());
+        assert_eq!(parsed("Ctrl+Left").display(), "Ctrl+←");
+    }
+
+    #[test]
+    fn no_key_triggers_two_actions_by_default() {
+        let keymap = KeyMap::default();
+        for (i, (action, keys)) in keymap.bindings.iter().enumerate() {
+            for (other, other_keys) in &keymap.bindings[i + 1..] {
+                let shared: Vec<_> = keys.iter().filter(|k| other_keys.contains(k)).collect();
+                assert!(
+                    shared.is_empty(),
+                    "{:?} and {:?} share {:?}",
+                    action,
+                    other,
+                    shared
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn configured_keys_move_from_other_actions() {
+        let config = config::parse_config(
+            "[keys]\nplot = \"j\"\ndown = [\"Ctrl+n\", \"Hyper+n\"]\nfly = \"x\"",
+        )
+        .unwrap();
+        let mut keymap = KeyMap::default();
+        let errors = keymap.apply_config(config.section("keys").unwrap());
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert_eq!(
+            keymap.action_for(&event(KeyCode::Char('j'), KeyModifiers::NONE)),
+            Some(Action::Plot)
+        );
+        assert_eq!(
+            keymap.action_for(&event(KeyCode::Char('n'), KeyModifiers::CONTROL)),
+            Some(Action::Down)
+        );
+        assert_eq!(
+            keymap.action_for(&event(KeyCode::Down, KeyModifiers::NONE)),
+            None
+        );
+        // Shift is part of an upper-case letter, not a modifier to match
+        assert_eq!(
+            keymap.action_for(&event(KeyCode::Char('Q'), KeyModifiers::SHIFT)),
+            Some(Action::Quit)
+        );
+    }
+}
@@ -0,0 +1,273 @@
+// label.rs
+
+use geojson::Feature;
+use std::fmt;
+
+use crate::plot::property_to_string;
+
+// Piece of a label template
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Property { name: String, format: Format },
+}
+
+// Formatting of a substituted property, written after a `:`
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Format {
+    thousands: bool,          // `,`: group digits of numbers, e.g. 1,234,567
+    precision: Option<usize>, // `.N`: fixed number of decimals
+    case: Case,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Case {
+    #[default]
+    Unchanged,
+    Upper,
+    Lower,
+}
+
+/// Text with `{property}` placeholders rendered per feature, e.g.
+/// `{NAME} ({POP_EST:,})`. Placeholders may be formatted with `,` (thousands
+/// separators), `.N` (N decimals), both (`,.1`), `upper` or `lower`; `{{` and
+/// `}}` are literal braces. Missing properties render as empty text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelTemplate {
+    text: String, // Template as written by the user
+    parts: Vec<Part>,
+}
+
+impl LabelTemplate {
+    pub fn parse(text: &str) -> Result<LabelTemplate, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        placeholder.push(c);
+                    }
+                    if !closed {
+                        return Err(format!("Unclosed '{{' in label '{}'", text));
+                    }
+                    let (name, format) = match placeholder.split_once(':') {
+                        Some((name, spec)) => (name.trim(), parse_format(spec)?),
+                        None => (placeholder.trim(), Format::default()),
+                    };
+                    if name.is_empty() {
+                        return Err(format!("Empty placeholder in label '{}'", text));
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Property {
+                        name: name.to_string(),
+                        format,
+                    });
+                }
+                '}' => return Err(format!("Unmatched '}}' in label '{}'", text)),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+        if parts.is_empty() {
+            return Err(String::from("Empty label template"));
+        }
+        Ok(LabelTemplate {
+            text: text.to_string(),
+            parts,
+        })
+    }
+
+    /// Label of `feature`; empty if all its placeholders are missing.
+    pub fn render(&self, feature: &Feature) -> String {
+        let mut label = String::new();
+        let mut any_value = false;
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => label.push_str(text),
+                Part::Property { name, format } => {
+                    if let Some(value) = feature.property(name).filter(|v| !v.is_null()) {
+                        label.push_str(&format_property(value, format));
+                        any_value = true;
+                    }
+                }
+            }
+        }
+        let has_placeholders = self
+            .parts
+            .iter()
+            .any(|part| matches!(part, Part::Property { .. }));
+        if has_placeholders && !any_value {
+            return String::new();
+        }
+        label.trim().to_string()
+    }
+}
+
+impl fmt::Display for LabelTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+fn parse_format(spec: &str) -> Result<Format, String> {
+    let spec = spec.trim();
+    let mut format = Format::default();
+    match spec {
+        "upper" => format.case = Case::Upper,
+        "lower" => format.case = Case::Lower,
+        _ => {
+            let rest = match spec.strip_prefix(',') {
+                Some(rest) => {
+                    format.thousands = true;
+                    rest
+                }
+                None => spec,
+            };
+            if !rest.is_empty() {
+                let digits = rest
+                    .strip_prefix('.')
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .filter(|n| *n <= 12)
+                    .ok_or_else(|| {
+                        format!("Unknown format ':{}' (use ',', '.N', upper or lower)", spec)
+                    })?;
+                format.precision = Some(digits);
+            }
+        }
+    }
+    Ok(format)
+}
+
+fn format_property(value: &serde_json::Value, format: &Format) -> String {
+    let number = match value {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) if format.thousands || format.precision.is_some() => {
+            s.trim().parse().ok()
+        }
+        _ => None,
+    };
+    let text = match number {
+        Some(n) if format.thousands || format.precision.is_some() => {
+            let text = match format.precision {
+                Some(digits) => format!("{:.*}", digits, n),
+                // Whole numbers stored as floats (e.g. 1234.0) have no decimals
+                None if n.fract() == 0.0 => format!("{:.0}", n),
+                None => n.to_string(),
+            };
+            if format.thousands {
+                group_thousands(&text)
+            } else {
+                text
+            }
+        }
+        _ => property_to_string(value),
+    };
+    match format.case {
+        Case::Unchanged => text,
+        Case::Upper => text.to_uppercase(),
+        Case::Lower => text.to_lowercase(),
+    }
+}
+
+// Inserts commas between groups of three digits of the integer part
+fn group_thousands(number: &str) -> String {
+    let (sign, unsigned) = match number.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", number),
+    };
+    let (integer, fraction) = match unsigned.find(['.', 'e', 'E']) {
+        Some(i) => unsigned.split_at(i),
+        None => (unsigned, ""),
+    };
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    format!("{}{}{}", sign, grouped, fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, properties: serde_json::Value) -> String {
+        let feature = Feature {
+            bbox: None,
+            geometry: None,
+            id: None,
+            properties: properties.as_object().cloned(),
+            foreign_members: None,
+        };
+        LabelTemplate::parse(template).unwrap().render(&feature)
+    }
+
+    #[test]
+    fn placeholders_are_substituted() {
+        let city = json!({ "NAME": "Pune", "POP": 3124458 });
+        assert_eq!(render("{NAME} ({POP})", city.clone()), "Pune (3124458)");
+        assert_eq!(render("{ NAME }", city), "Pune");
+    }
+
+    #[test]
+    fn numbers_are_formatted() {
+        let city = json!({ "pop": 3124458, "area": 331.26, "text": "1234.5", "neg": -1234567.891 });
+        assert_eq!(render("{pop:,}", city.clone()), "3,124,458");
+        assert_eq!(render("{area:.1}", city.clone()), "331.3");
+        assert_eq!(render("{text:,.2}", city.clone()), "1,234.50");
+        assert_eq!(render("{neg:,.1}", city), "-1,234,567.9");
+        assert_eq!(render("{n:,}", json!({ "n": 1234.0 })), "1,234");
+    }
+
+    #[test]
+    fn case_and_literal_braces() {
+        let city = json!({ "name": "Pune" });
+        assert_eq!(render("{name:upper}", city.clone()), "PUNE");
+        assert_eq!(render("{name:lower}", city.clone()), "pune");
+        assert_eq!(render("{{{name}}}", city), "{Pune}");
+    }
+
+    #[test]
+    fn missing_properties_render_empty() {
+        let city = json!({ "name": "Pune", "note": null });
+        assert_eq!(render("{name} {note}", city.clone()), "Pune");
+        assert_eq!(render("Pop: {pop}", city.clone()), "");
+        assert_eq!(render("Capital", city), "Capital");
+    }
+
+    #[test]
+    fn malformed_templates_are_rejected() {
+        for text in ["", "{name", "name}", "{}", "{name:.x}", "{name:bold}"] {
+            assert!(LabelTemplate::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn display_keeps_the_text() {
+        let template = LabelTemplate::parse("{NAME} ({POP:,})").unwrap();
+        assert_eq!(template.to_string(), "{NAME} ({POP:,})");
+    }
+}
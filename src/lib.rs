@@ -0,0 +1,63 @@
+// lib.rs
+
+//! GeoJSON plotting library behind the `plots` TUI.
+//!
+//! The [`plot`] module can be embedded in other programs; [`plot::render_golden`]
+//! produces deterministic pixel buffers for golden-image tests.
+
+pub mod animation;
+pub mod app;
+pub mod basemap;
+pub mod buffer;
+pub mod centroid;
+pub mod checkpoint;
+pub mod cli;
+pub mod clip;
+pub mod clipboard;
+pub mod cluster;
+pub mod config;
+pub mod crs;
+pub mod dissolve;
+pub mod duplicates;
+pub mod event;
+pub mod export;
+pub mod filter;
+pub mod geocode;
+pub mod gps;
+pub mod history;
+pub mod info_cache;
+pub mod inset;
+pub mod join;
+pub mod json_preview;
+pub mod keymap;
+pub mod label;
+pub mod loader;
+pub mod lod;
+pub mod measure;
+pub mod minify;
+pub mod osm;
+pub mod plot;
+pub mod projection;
+pub mod ramp;
+pub mod raster;
+pub mod regions;
+pub mod remote;
+pub mod search;
+pub mod serve;
+pub mod session;
+pub mod simplify;
+pub mod snap;
+pub mod spatial;
+pub mod style;
+pub mod table;
+pub mod temporal;
+pub mod text_input;
+pub mod topology;
+pub mod ui;
+pub mod validate;
+pub mod watch;
+pub mod wfs;
+pub mod wkt;
+
+pub const GEOJSON_DIR: &str = "data/geojson/";
+pub const OUTPUT_DIR: &str = "output/";
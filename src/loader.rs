@@ -0,0 +1,883 @@
+// loader.rs
+
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry, Value};
+use memmap2::Mmap;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use std::{
+    error::Error,
+    fmt, fs,
+    io::{self, BufRead, IsTerminal, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use crate::GEOJSON_DIR;
+use crate::app::GeoJsonInfo;
+use crate::crs;
+use crate::osm;
+use crate::plot::into_features;
+use crate::raster;
+use crate::wkt;
+
+// Extensions of newline-delimited GeoJSON files (one feature per line)
+const LINE_DELIMITED_EXTENSIONS: [&str; 3] = ["geojsonl", "geojsons", "ndjson"];
+// Bare geometries: one WKT per line, or WKB either binary or as hex lines
+const GEOMETRY_DUMP_EXTENSIONS: [&str; 2] = ["wkt", "wkb"];
+
+/// Resolves an input file name, falling back to the GeoJSON data directory.
+pub fn resolve_input(file: &str) -> PathBuf {
+    let path = PathBuf::from(file);
+    if path.exists() {
+        path
+    } else {
+        PathBuf::from(GEOJSON_DIR).join(file)
+    }
+}
+
+/// Expands a leading `~` to the home directory.
+pub fn expand_home(text: &str) -> PathBuf {
+    match (text.strip_prefix('~'), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(text),
+    }
+}
+
+/// Completes the last component of a typed path against its directory,
+/// offering subdirectories (with a trailing `/`) and supported files. Returns
+/// the completed text and, if several entries share the typed prefix, their
+/// names; the text is then completed as far as they agree.
+pub fn complete_path(text: &str) -> (String, Vec<String>) {
+    let (dir, prefix) = match text.rfind('/') {
+        Some(i) => text.split_at(i + 1),
+        None => ("", text),
+    };
+    let listed = if dir.is_empty() {
+        PathBuf::from(".")
+    } else {
+        expand_home(dir)
+    };
+    let mut candidates: Vec<String> = fs::read_dir(listed)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // Hidden entries only when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let path = entry.path();
+            if path.is_dir() {
+                Some(format!("{}/", name))
+            } else if is_supported_file(&path) || raster::is_raster_file(&path) {
+                Some(name)
+            } else {
+                None
+            }
+        })
+        .collect();
+    candidates.sort();
+
+    match candidates.as_slice() {
+        [] => (text.to_string(), Vec::new()),
+        [only] => (format!("{}{}", dir, only), Vec::new()),
+        [first, rest @ ..] => {
+            let mut common = first.len();
+            for other in rest {
+                common = first
+                    .char_indices()
+                    .zip(other.chars())
+                    .take_while(|((_, a), b)| a == b)
+                    .map(|((i, a), _)| i + a.len_utf8())
+                    .last()
+                    .unwrap_or(0)
+                    .min(common);
+            }
+            (format!("{}{}", dir, &first[..common]), candidates)
+        }
+    }
+}
+
+/// Copies GeoJSON piped on standard input to a temporary file, so it can be
+/// read like any other input. Several lines of which the first is a complete
+/// JSON value are taken as GeoJSONL, anything else as a single GeoJSON object.
+pub fn read_stdin_to_temp() -> Result<PathBuf, Box<dyn Error>> {
+    if io::stdin().is_terminal() {
+        return Err(
+            "Nothing is piped on standard input, e.g. cat foo.geojson | plots --stdin".into(),
+        );
+    }
+    let mut contents = String::new();
+    io::stdin().read_to_string(&mut contents)?;
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+    let line_delimited = match (lines.next(), lines.next()) {
+        (Some(first), Some(_)) => {
+            let first = first.trim().trim_start_matches('\u{1e}');
+            serde_json::from_str::<serde_json::Value>(first).is_ok()
+        }
+        (None, _) => return Err("Standard input is empty".into()),
+        _ => false,
+    };
+    let extension = if line_delimited {
+        "geojsonl"
+    } else {
+        "geojson"
+    };
+    let path =
+        std::env::temp_dir().join(format!("plots-stdin-{}.{}", std::process::id(), extension));
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+/// Writes `features` as a FeatureCollection, e.g. the result of a geoprocessing
+/// operation.
+pub fn save_features(path: &Path, features: Vec<Feature>) -> Result<(), Box<dyn Error>> {
+    let collection = FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, collection.to_string())?;
+    Ok(())
+}
+
+/// Whether `path` looks like a file the application can plot.
+pub fn is_supported_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ext == "geojson"
+                || LINE_DELIMITED_EXTENSIONS.contains(&ext)
+                || GEOMETRY_DUMP_EXTENSIONS.contains(&ext)
+        })
+        || is_osm_pbf(path)
+}
+
+/// Whether `path` is an OpenStreetMap extract (`.osm.pbf`). Other `.pbf`
+/// files, such as vector tiles, are not.
+pub fn is_osm_pbf(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.to_lowercase().ends_with(".osm.pbf"))
+}
+
+/// Whether `path` holds bare WKT or WKB geometries instead of GeoJSON.
+pub fn is_geometry_dump(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| GEOMETRY_DUMP_EXTENSIONS.contains(&ext))
+}
+
+// Where a geometry is in a dump ("line 3", "geometry 2") and the geometry
+type DumpRecord = (String, Result<Value, String>);
+
+/// Reads the geometries of a WKT or WKB file, each with where it is in the
+/// file ("line 3", "geometry 2") or why it could not be parsed. A binary WKB
+/// dump ends at its first invalid geometry, since the next one cannot be found.
+pub fn read_geometry_dump(path: &Path) -> Result<Vec<DumpRecord>, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    let is_text = bytes
+        .iter()
+        .all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace());
+    let mut records = Vec::new();
+    if path.extension().is_some_and(|ext| ext == "wkt") || is_text {
+        let text = String::from_utf8_lossy(&bytes);
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let value = if path.extension().is_some_and(|ext| ext == "wkt") {
+                wkt::parse_wkt(line)
+            } else {
+                wkt::decode_hex(line).and_then(|bytes| Ok(wkt::parse_wkb(&bytes)?.0))
+            };
+            records.push((format!("line {}", line_no + 1), value));
+        }
+        return Ok(records);
+    }
+    let mut at = 0;
+    while at < bytes.len() {
+        let location = format!("geometry {}", records.len() + 1);
+        match wkt::parse_wkb(&bytes[at..]) {
+            Ok((value, length)) => {
+                records.push((location, Ok(value)));
+                at += length;
+            }
+            Err(e) => {
+                records.push((location, Err(format!("byte {}: {}", at, e))));
+                break;
+            }
+        }
+    }
+    Ok(records)
+}
+
+// Features of a WKT or WKB file, with where each came from as property `source`
+fn parse_geometry_dump(path: &Path) -> Result<ParsedFeatures, Box<dyn Error>> {
+    let mut parsed = ParsedFeatures::default();
+    for (location, value) in read_geometry_dump(path)? {
+        match value {
+            Ok(value) => {
+                let mut properties = serde_json::Map::new();
+                properties.insert(String::from("source"), location.into());
+                parsed.features.push(Feature {
+                    geometry: Some(Geometry::new(value)),
+                    properties: Some(properties),
+                    ..Feature::default()
+                });
+            }
+            Err(e) => parsed.skip(format!("{}: {}", location, e)),
+        }
+    }
+    Ok(parsed)
+}
+
+// Features of an OSM extract; elements without a geometry count as skipped
+fn parse_osm_pbf(path: &Path) -> Result<ParsedFeatures, Box<dyn Error>> {
+    let osm = osm::read_osm_pbf(path)?;
+    let mut parsed = ParsedFeatures {
+        features: osm.features,
+        ..ParsedFeatures::default()
+    };
+    for error in osm.skipped {
+        parsed.skip(error);
+    }
+    Ok(parsed)
+}
+
+/// Whether `path` is a newline-delimited GeoJSON file.
+pub fn is_line_delimited(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| LINE_DELIMITED_EXTENSIONS.contains(&ext))
+}
+
+// Parses one line of a GeoJSONL file; blank lines yield no features
+fn parse_line(line: &str) -> Result<Vec<Feature>, Box<dyn Error>> {
+    let line = line.trim().trim_start_matches('\u{1e}'); // RFC 8142 record separator
+    if line.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(into_features(line.parse::<GeoJson>()?))
+}
+
+/// Features of a file together with the records that had to be skipped.
+#[derive(Default)]
+pub struct ParsedFeatures {
+    pub features: Vec<Feature>,
+    pub skipped: usize,
+    pub first_error: Option<String>, // Why the first skipped record was rejected
+    pub crs: Option<String>,         // Legacy `crs` member of the file or its override, if any
+    pub crs_overridden: bool,        // `crs` was set by the user instead of the file
+    pub crs_warning: Option<String>, // Set if the coordinates could not be reprojected
+}
+
+impl ParsedFeatures {
+    fn skip(&mut self, error: String) {
+        self.skipped += 1;
+        self.first_error.get_or_insert(error);
+    }
+
+    /// Describes the skipped records, e.g. for a warning.
+    pub fn skipped_message(&self) -> Option<String> {
+        let error = self.first_error.as_deref()?;
+        Some(format!(
+            "Skipped {} invalid feature{} (first: {})",
+            self.skipped,
+            if self.skipped == 1 { "" } else { "s" },
+            error
+        ))
+    }
+}
+
+/// Reads the features of a GeoJSON or GeoJSONL file, skipping features of a
+/// FeatureCollection (or lines of a GeoJSONL file) that are not valid GeoJSON.
+/// WKT and WKB files give a feature per geometry, OSM extracts a feature per
+/// tagged node, way or multipolygon.
+/// Fails only if the file itself cannot be read or is not valid JSON.
+pub fn read_features_lenient(path: &Path) -> Result<ParsedFeatures, Box<dyn Error>> {
+    read_features_with_crs(path, None)
+}
+
+/// Like `read_features_lenient`, but treats the coordinates as being in
+/// `crs_override` (if set) whatever the file declares.
+pub fn read_features_with_crs(
+    path: &Path,
+    crs_override: Option<&str>,
+) -> Result<ParsedFeatures, Box<dyn Error>> {
+    let mut parsed = parse_features(path)?;
+    if let Some(name) = crs_override {
+        parsed.crs = Some(name.to_string());
+        parsed.crs_overridden = true;
+    }
+
+    // Plotting assumes WGS 84 longitude/latitude, so other systems are converted
+    if let Some(name) = parsed.crs.as_deref().filter(|name| !crs::is_wgs84(name)) {
+        let mut features = parsed.features.clone();
+        match crs::reproject(&mut features, name) {
+            Ok(()) => parsed.features = features,
+            Err(e) => {
+                parsed.crs_warning = Some(format!("{}; coordinates are plotted as lon/lat", e))
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+// Reads the features as they are stored in the file
+fn parse_features(path: &Path) -> Result<ParsedFeatures, Box<dyn Error>> {
+    if is_geometry_dump(path) {
+        return parse_geometry_dump(path);
+    }
+    if is_osm_pbf(path) {
+        return parse_osm_pbf(path);
+    }
+    let mut parsed = ParsedFeatures::default();
+    if is_line_delimited(path) {
+        let contents = fs::read_to_string(path)?;
+        for (line_no, line) in contents.lines().enumerate() {
+            match parse_line(line) {
+                Ok(features) => parsed.features.extend(features),
+                Err(e) => parsed.skip(format!("line {}: {}", line_no + 1, e)),
+            }
+        }
+        return Ok(parsed);
+    }
+
+    let file = fs::File::open(path)?;
+    let mut value: serde_json::Value = serde_json::from_reader(io::BufReader::new(file))?;
+    parsed.crs = crs::crs_name(&value);
+    let is_collection = value.get("type").and_then(|t| t.as_str()) == Some("FeatureCollection");
+    match value.get_mut("features") {
+        Some(serde_json::Value::Array(members)) if is_collection => {
+            for (index, member) in std::mem::take(members).into_iter().enumerate() {
+                match Feature::from_json_value(member) {
+                    Ok(feature) => parsed.features.push(feature),
+                    Err(e) => parsed.skip(format!("feature {}: {}", index, e)),
+                }
+            }
+        }
+        // Anything but a FeatureCollection is a single record that parses or not
+        _ => parsed.features = into_features(GeoJson::from_json_value(value)?),
+    }
+    Ok(parsed)
+}
+
+/// Hands the features of a GeoJSON, GeoJSONL, WKT, WKB or OSM PBF file to `emit` one at a time,
+/// reprojected like `read_features_with_crs` does, without ever holding more
+/// than one feature of a FeatureCollection in memory. Invalid features are
+/// skipped and counted; the returned summary has no `features`.
+pub fn stream_features(
+    path: &Path,
+    crs_override: Option<&str>,
+    emit: &mut dyn FnMut(Feature) -> Result<(), Box<dyn Error>>,
+) -> Result<ParsedFeatures, Box<dyn Error>> {
+    let mut parsed = ParsedFeatures::default();
+    if is_geometry_dump(path) {
+        // Bare geometries are small next to GeoJSON of the same data, so they are read at once
+        parsed = parse_geometry_dump(path)?;
+    } else if is_osm_pbf(path) {
+        // Ways need every node of the file, so an extract is read as a whole too
+        parsed = parse_osm_pbf(path)?;
+    }
+    if let Some(name) = crs_override {
+        parsed.crs = Some(name.to_string());
+        parsed.crs_overridden = true;
+    } else if !is_line_delimited(path) && !is_geometry_dump(path) && !is_osm_pbf(path) {
+        // The `crs` member may follow the features, so it is looked up first
+        parsed.crs = scan_crs(path)?;
+    }
+    let mut sink = Sink {
+        reproject_from: parsed.crs.clone().filter(|name| !crs::is_wgs84(name)),
+        parsed,
+        emit,
+        error: None,
+    };
+
+    if is_geometry_dump(path) || is_osm_pbf(path) {
+        for feature in std::mem::take(&mut sink.parsed.features) {
+            sink.feature(Ok(feature));
+            if let Some(e) = sink.error.take() {
+                return Err(e);
+            }
+        }
+        return Ok(sink.parsed);
+    }
+    if is_line_delimited(path) {
+        let reader = io::BufReader::new(fs::File::open(path)?);
+        for (line_no, line) in reader.lines().enumerate() {
+            match parse_line(&line?) {
+                Ok(features) => {
+                    for feature in features {
+                        sink.feature(Ok(feature));
+                    }
+                }
+                Err(e) => sink.parsed.skip(format!("line {}: {}", line_no + 1, e)),
+            }
+            if let Some(e) = sink.error.take() {
+                return Err(e);
+            }
+        }
+        return Ok(sink.parsed);
+    }
+
+    let file = fs::File::open(path)?;
+    let mut deserializer = serde_json::Deserializer::from_reader(io::BufReader::new(file));
+    let members = deserializer.deserialize_map(Document { sink: &mut sink });
+    if let Some(e) = sink.error.take() {
+        return Err(e);
+    }
+    let members = members?;
+    deserializer.end()?;
+    // Anything but a FeatureCollection is a single record, read as a whole
+    let is_collection = members.get("type").and_then(|t| t.as_str()) == Some("FeatureCollection");
+    if !is_collection {
+        let geojson = GeoJson::from_json_value(serde_json::Value::Object(members))?;
+        for feature in into_features(geojson) {
+            sink.feature(Ok(feature));
+        }
+        if let Some(e) = sink.error.take() {
+            return Err(e);
+        }
+    }
+    Ok(sink.parsed)
+}
+
+// Receives the features of a streamed file
+struct Sink<'e> {
+    parsed: ParsedFeatures,
+    reproject_from: Option<String>, // Cleared if reprojecting fails
+    emit: &'e mut dyn FnMut(Feature) -> Result<(), Box<dyn Error>>,
+    error: Option<Box<dyn Error>>, // Set if `emit` failed, which ends the stream
+}
+
+impl Sink<'_> {
+    // Returns false once the stream has to stop
+    fn feature(&mut self, feature: Result<Feature, String>) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
+        let mut feature = match feature {
+            Ok(feature) => feature,
+            Err(e) => {
+                self.parsed.skip(e);
+                return true;
+            }
+        };
+        if let Some(name) = self.reproject_from.as_deref()
+            && let Err(e) = crs::reproject(std::slice::from_mut(&mut feature), name)
+        {
+            self.parsed.crs_warning = Some(format!("{}; coordinates are plotted as lon/lat", e));
+            self.reproject_from = None;
+        }
+        if let Err(e) = (self.emit)(feature) {
+            self.error = Some(e);
+            return false;
+        }
+        true
+    }
+}
+
+// Top-level object of a streamed file: `features` go to the sink one by one,
+// the other members are returned
+struct Document<'s, 'e> {
+    sink: &'s mut Sink<'e>,
+}
+
+impl<'de> Visitor<'de> for Document<'_, '_> {
+    type Value = serde_json::Map<String, serde_json::Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a GeoJSON object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut members = serde_json::Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "features" {
+                map.next_value_seed(FeatureArray {
+                    sink: &mut *self.sink,
+                })?;
+            } else {
+                members.insert(key, map.next_value()?);
+            }
+        }
+        Ok(members)
+    }
+}
+
+struct FeatureArray<'s, 'e> {
+    sink: &'s mut Sink<'e>,
+}
+
+impl<'de> DeserializeSeed<'de> for FeatureArray<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FeatureArray<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of features")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let mut index = 0;
+        while let Some(member) = seq.next_element::<serde_json::Value>()? {
+            let feature =
+                Feature::from_json_value(member).map_err(|e| format!("feature {}: {}", index, e));
+            if !self.sink.feature(feature) {
+                return Err(de::Error::custom("stopped"));
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+}
+
+// Legacy `crs` member of a GeoJSON file, skipping over everything else
+fn scan_crs(path: &Path) -> Result<Option<String>, Box<dyn Error>> {
+    struct CrsMember;
+
+    impl<'de> Visitor<'de> for CrsMember {
+        type Value = Option<serde_json::Value>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a GeoJSON object")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut crs = None;
+            while let Some(key) = map.next_key::<String>()? {
+                if key == "crs" {
+                    crs = Some(map.next_value()?);
+                } else {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+            Ok(crs)
+        }
+    }
+
+    let file = fs::File::open(path)?;
+    let mut deserializer = serde_json::Deserializer::from_reader(io::BufReader::new(file));
+    let crs = deserializer.deserialize_map(CrsMember)?;
+    Ok(crs.and_then(|crs| crs::crs_name(&serde_json::json!({ "crs": crs }))))
+}
+
+/// Reads all valid features of a GeoJSON or GeoJSONL file; see `read_features_lenient`.
+pub fn read_features(path: &Path) -> Result<Vec<Feature>, Box<dyn Error>> {
+    Ok(read_features_lenient(path)?.features)
+}
+
+/// Fills the size and modification time of `info` from the file system.
+pub fn read_file_metadata(path: &Path, info: &mut GeoJsonInfo) {
+    if let Ok(metadata) = fs::metadata(path) {
+        info.file_size_kb = metadata.len() / 1024;
+        if let Ok(time) = metadata.modified() {
+            let datetime: chrono::DateTime<chrono::Local> = time.into();
+            info.modified_time = format!("{}", datetime.format("%Y-%m-%d %H:%M"));
+        } else {
+            info.modified_time = String::from("N/A");
+        }
+    } else {
+        info.parse_error = Some(String::from("File info: Not available"));
+    }
+}
+
+/// Files at least this large are summarised from their top-level members when
+/// highlighted, if they declare a `bbox`, instead of being parsed.
+pub const HEADER_ONLY_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Reads the metadata of a GeoJSON FeatureCollection that declares its `bbox`
+/// before its `features`, without parsing a single feature: the extent and CRS
+/// come from the top-level members and the features are counted by scanning
+/// the bytes of the array for the objects in it. The file is memory-mapped,
+/// so the scan reads it straight from the page cache without copying it and
+/// nothing of it is held once the count is done. `None` if the file is not
+/// such a collection, or its CRS needs reprojecting, so it has to be parsed.
+pub fn read_header(path: &Path) -> Option<GeoJsonInfo> {
+    if is_line_delimited(path) || is_geometry_dump(path) || is_osm_pbf(path) {
+        return None;
+    }
+    let file = fs::File::open(path).ok()?;
+    // SAFETY: the map is only read, and dropped before returning. A file
+    // truncated by another program while it is scanned is not guarded against,
+    // as with any memory-mapped reader.
+    let bytes = unsafe { Mmap::map(&file) }.ok()?;
+    let mut scan = HeaderScan::default();
+    for &byte in bytes.iter() {
+        match scan.feed(byte) {
+            Some(true) => continue,
+            Some(false) => break,
+            None => return None,
+        }
+    }
+    drop(bytes);
+    if !scan.closed || scan.members.get("type")?.as_str()? != "FeatureCollection" {
+        return None;
+    }
+    let bbox: Vec<f64> = scan
+        .members
+        .get("bbox")?
+        .as_array()?
+        .iter()
+        .map(|v| v.as_f64())
+        .collect::<Option<_>>()?;
+    // Three-dimensional boxes list the minimum and maximum elevation too
+    let bbox = match bbox[..] {
+        [min_x, min_y, max_x, max_y] | [min_x, min_y, _, max_x, max_y, _] => {
+            [min_x, min_y, max_x, max_y]
+        }
+        _ => return None,
+    };
+
+    let mut info = GeoJsonInfo::default();
+    read_file_metadata(path, &mut info);
+    if let Some(crs) = scan.members.get("crs") {
+        info.crs = crs::crs_name(&serde_json::json!({ "crs": crs }));
+    }
+    if info.crs.as_deref().is_some_and(|name| !crs::is_wgs84(name)) {
+        return None;
+    }
+    info.feature_count = scan.features;
+    info.bbox = Some(bbox);
+    info.header_only = true;
+    Some(info)
+}
+
+// Walks the bytes of a JSON document, keeping the top-level members that
+// describe a collection and counting the objects in its `features` array
+#[derive(Default)]
+struct HeaderScan {
+    depth: usize, // Objects and arrays open
+    in_string: bool,
+    escaped: bool,          // The previous byte of the string was a backslash
+    expect_key: bool,       // At the top level, before a member's name
+    reading_key: bool,      // Inside the name of a top-level member
+    key: Vec<u8>,           // Name of the top-level member being read
+    value: Option<Vec<u8>>, // Text of a kept member's value, as it is read
+    in_features: bool,
+    bbox_seen: bool,
+    features: usize,
+    members: serde_json::Map<String, serde_json::Value>,
+    closed: bool, // The top-level object ended
+}
+
+impl HeaderScan {
+    // `Some(true)` to go on, `Some(false)` once the document ended and `None`
+    // if it cannot be summarised from its header
+    fn feed(&mut self, byte: u8) -> Option<bool> {
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if byte == b'\\' {
+                self.escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+                if self.reading_key {
+                    self.reading_key = false;
+                    return Some(true);
+                }
+            }
+            if self.reading_key {
+                self.key.push(byte);
+            }
+            if let Some(value) = &mut self.value {
+                value.push(byte);
+            }
+            return Some(true);
+        }
+        if self.depth == 0 {
+            return match byte {
+                b'{' if !self.closed => {
+                    self.depth = 1;
+                    self.expect_key = true;
+                    Some(true)
+                }
+                _ if byte.is_ascii_whitespace() => Some(true),
+                _ if self.closed => Some(false),
+                _ => None,
+            };
+        }
+        if self.depth == 1 {
+            match byte {
+                b'"' if self.expect_key => {
+                    self.in_string = true;
+                    self.reading_key = true;
+                    self.key.clear();
+                    return Some(true);
+                }
+                b':' => {
+                    self.expect_key = false;
+                    match &self.key[..] {
+                        b"type" | b"crs" => self.value = Some(Vec::new()),
+                        b"bbox" => {
+                            self.bbox_seen = true;
+                            self.value = Some(Vec::new());
+                        }
+                        // Without a bbox ahead of them the features would have to be read
+                        b"features" if !self.bbox_seen => return None,
+                        b"features" => self.in_features = true,
+                        _ => {}
+                    }
+                    return Some(true);
+                }
+                b',' | b'}' => {
+                    self.end_member();
+                    if byte == b'}' {
+                        self.depth = 0;
+                        self.closed = true;
+                    } else {
+                        self.expect_key = true;
+                    }
+                    return Some(true);
+                }
+                _ => {}
+            }
+        }
+        match byte {
+            b'"' => self.in_string = true,
+            b'{' | b'[' => {
+                if byte == b'{' && self.depth == 2 && self.in_features {
+                    self.features += 1;
+                }
+                self.depth += 1;
+            }
+            b'}' | b']' => self.depth -= 1,
+            _ => {}
+        }
+        if let Some(value) = &mut self.value {
+            value.push(byte);
+        }
+        Some(true)
+    }
+
+    // Keeps the value of the top-level member that just ended, if it is wanted
+    fn end_member(&mut self) {
+        self.in_features = false;
+        if let Some(value) = self.value.take()
+            && let Ok(value) = serde_json::from_slice(&value)
+        {
+            self.members
+                .insert(String::from_utf8_lossy(&self.key).into_owned(), value);
+        }
+    }
+}
+
+/// Reads a file for the info panel, returning its metadata and, if it parsed,
+/// its features; see `read_features_with_crs` for `crs_override`.
+pub fn load_file(path: &Path, crs_override: Option<&str>) -> (GeoJsonInfo, Option<Vec<Feature>>) {
+    let mut info = GeoJsonInfo::default();
+    read_file_metadata(path, &mut info);
+
+    match read_features_with_crs(path, crs_override) {
+        Ok(parsed) => {
+            info.record_features(&parsed.features);
+            info.skipped_message = parsed.skipped_message();
+            info.crs = parsed.crs;
+            info.crs_overridden = parsed.crs_overridden;
+            info.crs_warning = parsed.crs_warning;
+            (info, Some(parsed.features))
+        }
+        Err(e) => {
+            info.parse_error = Some(format!("GeoJSON Parse Error: {}", e));
+            (info, None)
+        }
+    }
+}
+
+/// Features read by a single `FollowState::poll`.
+pub struct FollowUpdate {
+    pub features: Vec<Feature>,
+    pub skipped_lines: usize, // Lines that were not valid GeoJSON
+}
+
+/// Tracks how much of a growing GeoJSONL file has already been ingested.
+pub struct FollowState {
+    pub file_index: usize, // Index into `App::layers`
+    offset: u64,
+    partial_line: Vec<u8>, // Bytes after the last complete line
+}
+
+impl FollowState {
+    /// Starts following a file from its beginning, so the first poll returns
+    /// every complete line currently in it.
+    pub fn new(file_index: usize) -> FollowState {
+        FollowState {
+            file_index,
+            offset: 0,
+            partial_line: Vec::new(),
+        }
+    }
+
+    /// Reads lines appended since the last poll. Returns `Ok(None)` if the file
+    /// shrank (it was truncated or replaced); previously returned features are
+    /// then stale and the next poll starts over from the beginning.
+    pub fn poll(&mut self, path: &Path) -> Result<Option<FollowUpdate>, Box<dyn Error>> {
+        let len = fs::metadata(path)?.len();
+        if len < self.offset {
+            self.offset = 0;
+            self.partial_line.clear();
+            return Ok(None);
+        }
+        let mut update = FollowUpdate {
+            features: Vec::new(),
+            skipped_lines: 0,
+        };
+        if len == self.offset {
+            return Ok(Some(update));
+        }
+
+        let mut file = fs::File::open(path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        file.take(len - self.offset)
+            .read_to_end(&mut self.partial_line)?;
+        self.offset = len;
+
+        // Only complete lines are parsed; the remainder waits for the writer
+        while let Some(newline) = self.partial_line.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial_line.drain(..=newline).collect();
+            match parse_line(&String::from_utf8_lossy(&line)) {
+                Ok(features) => update.features.extend(features),
+                Err(_) => update.skipped_lines += 1,
+            }
+        }
+        Ok(Some(update))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collections_with_a_bbox_are_summarised_from_their_header() {
+        let path =
+            std::env::temp_dir().join(format!("plots_header_{}.geojson", std::process::id()));
+        let point = r#"{"type": "Feature", "properties": {"name": "{[\"}"}, "geometry": {"type": "Point", "coordinates": [1, 2]}}"#;
+        let text = format!(
+            r#"{{"type": "FeatureCollection", "bbox": [0, 1, 2, 3], "features": [{point}, {point}]}}"#
+        );
+        fs::write(&path, &text).unwrap();
+        let info = read_header(&path).unwrap();
+        assert_eq!(
+            (info.feature_count, info.bbox),
+            (2, Some([0.0, 1.0, 2.0, 3.0]))
+        );
+
+        // Without a bbox first the features would have to be parsed
+        fs::write(&path, text.replace(r#""bbox": [0, 1, 2, 3], "#, "")).unwrap();
+        assert!(read_header(&path).is_none());
+        let _ = fs::remove_file(&path);
+    }
+}
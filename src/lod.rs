@@ -0,0 +1,82 @@
+// lod.rs
+
+use geojson::{Feature, Geometry};
+use std::sync::OnceLock;
+
+use crate::plot;
+use crate::simplify;
+
+// Douglas-Peucker tolerances of the simplified copies, in degrees: about 10 m,
+// 100 m, 1 km and 10 km
+const LEVEL_TOLERANCES: [f64; 4] = [0.0001, 0.001, 0.01, 0.1];
+
+// A copy keeping more than this share of the vertices saves too little to be
+// worth its memory
+const MIN_REDUCTION: f64 = 0.8;
+
+/// Simplified copies of the geometries of a layer at a few fixed tolerances.
+/// Each is built from the original geometries the first time a render can use
+/// it and reused by later renders of the same features, so a world-scale plot
+/// draws a copy with a fraction of the vertices instead of simplifying every
+/// feature again.
+#[derive(Default)]
+pub struct LevelsOfDetail {
+    levels: [OnceLock<Level>; LEVEL_TOLERANCES.len()],
+}
+
+struct Level {
+    features: usize, // Features of the layer the copy was made from
+    geometries: Option<Vec<Option<Geometry>>>, // By feature position; `None` if not worth keeping
+}
+
+impl LevelsOfDetail {
+    /// Whether the copies made so far were made from `features`.
+    pub fn fits(&self, features: &[Feature]) -> bool {
+        self.levels
+            .iter()
+            .filter_map(OnceLock::get)
+            .all(|level| level.features == features.len())
+    }
+
+    /// The geometries of `features`, the layer these copies belong to, at the
+    /// coarsest level simplified by at most `tolerance`, by feature position,
+    /// with the tolerance left to simplify them by. `None` if no level is
+    /// coarse enough or simplifying that much saves little on this layer.
+    pub fn geometries(
+        &self,
+        features: &[Feature],
+        tolerance: f64,
+    ) -> Option<(&[Option<Geometry>], f64)> {
+        if !self.fits(features) {
+            return None;
+        }
+        let index = LEVEL_TOLERANCES.iter().rposition(|&t| t <= tolerance)?;
+        let level_tolerance = LEVEL_TOLERANCES[index];
+        let level = self.levels[index].get_or_init(|| build(features, level_tolerance));
+        let geometries = level.geometries.as_deref()?;
+        Some((geometries, tolerance - level_tolerance))
+    }
+}
+
+fn build(features: &[Feature], tolerance: f64) -> Level {
+    let geometries: Vec<Option<Geometry>> = features
+        .iter()
+        .map(|feature| {
+            let geometry = feature.geometry.as_ref()?;
+            Some(Geometry::new(simplify::simplify_geometry(
+                &geometry.value,
+                tolerance,
+            )))
+        })
+        .collect();
+    let vertices = count(features.iter().filter_map(|f| f.geometry.as_ref()));
+    let kept = count(geometries.iter().flatten());
+    Level {
+        features: features.len(),
+        geometries: (kept as f64 <= vertices as f64 * MIN_REDUCTION).then_some(geometries),
+    }
+}
+
+fn count<'a>(geometries: impl Iterator<Item = &'a Geometry>) -> usize {
+    geometries.map(|g| plot::count_vertices(&g.value)).sum()
+}
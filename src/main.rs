@@ -1,70 +1,3928 @@
 // main.rs
-use chrono;
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture, KeyCode, MouseButton, MouseEventKind},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, KeyModifiers, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
-use geojson::{GeoJson, Value};
+use geojson::Feature;
 use plotters::prelude::*;
 use ratatui::{Terminal, backend::CrosstermBackend};
 use std::cmp;
-use std::collections::HashMap;
 use std::{
-    error::Error,
+    collections::BTreeSet,
     fs, io,
     path::{Path, PathBuf},
-    time::Duration,
+    process::{Command, Stdio},
+    sync::{Arc, mpsc::Sender},
+    thread,
+    time::{Duration, Instant},
 };
 
-mod app;
-mod event;
-mod ui;
+use plots::app::{
+    App, AppMode, CurrentScreen, GeoJsonInfo, LayerProperty, LayerSummary, LogLevel, PanelTab,
+    PlotJob, PlotSummary, TerminalEvent, TitleField,
+};
+use plots::basemap::Basemap;
+use plots::buffer::{self, BufferDistance};
+use plots::centroid::{self, PointKind};
+use plots::clipboard;
+use plots::cluster;
+use plots::crs;
+use plots::event::{Event, EventHandler, PlotResult};
+use plots::export::{self, ExportLayer};
+use plots::filter::{self, FeatureFilter};
+use plots::geocode::{self, FoundPlace, PlaceSearch, ReverseGeocoder};
+use plots::gps::{self, GpsReader, GpsSource};
+use plots::history;
+use plots::info_cache::{self, InfoCache};
+use plots::inset::Inset;
+use plots::join::{self, JoinSpec};
+use plots::json_preview::JsonPreview;
+use plots::keymap::{Action, KeyMap};
+use plots::loader::{self, FollowState};
+use plots::osm::{self, TagFilter};
+use plots::plot::{self, ClipMask, FigureBackground, MapMarker, PlotLayer, RenderProgress};
+use plots::raster::{self, Raster};
+use plots::regions::Region;
+use plots::remote::{self, Download};
+use plots::search::{self, PropertySearch, SearchMode};
+use plots::serve::{PreviewServer, ServedLayer};
+use plots::session::{self, Session, Workspace};
+use plots::spatial;
+use plots::style::StyleRule;
+use plots::table::{self, AttributeTable};
+use plots::temporal::{self, TimeRange};
+use plots::watch::DirectoryWatcher;
+use plots::wfs::{self, CapabilitiesRequest};
+use plots::{
+    GEOJSON_DIR, OUTPUT_DIR, cli, config, dissolve, duplicates, measure, minify, snap, topology,
+    ui, validate,
+};
+
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+const SCROLL_ROWS: isize = 3; // File list rows per mouse wheel step
+const PREVIEW_PAN_STEP: f64 = 0.2; // Fraction of the preview moved per arrow key
+const PREVIEW_ZOOM_STEP: f64 = 1.5;
+const JUMP_SPAN: f64 = 2.0; // Degrees across the preview at most after jumping to coordinates
+const DIVIDER_STEP: i32 = 5; // Percent of the terminal width per divider key press
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100); // Between progress events of a plot
+// Completions named in the notification when Tab is pressed on a path
+const MAX_LISTED_COMPLETIONS: usize = 12;
+
+const PREVIEW_NAVIGATION_HINT: &str = "Preview: Arrows pan, Shift+arrows move the crosshair, Enter identifies, +/- zoom, 0 reset, L lock extent, M measure, Esc done";
+const MEASURE_HINT: &str = "Measure: Shift+arrows or a click place the crosshair, Enter adds a vertex, Backspace removes one, C closes the polygon, Esc done";
+
+// Notification shown while typing a search in `mode`
+fn search_prompt(mode: SearchMode) -> String {
+    match mode {
+        SearchMode::FileNames => String::from(
+            "Enter search query. Press Enter to apply, Tab to search property values, Escape to cancel.",
+        ),
+        SearchMode::Properties => String::from(
+            "Enter a property value to find. Press Enter to search, Tab to search file names, Escape to cancel.",
+        ),
+    }
+}
+
+// Lists the files matching the search: those of the last property search,
+// or those whose name fuzzily matches the query, best match first
+fn refilter_files(app: &mut App) {
+    app.filtered_geojson_indices.clear();
+    if let Some(search) = &app.property_search {
+        app.filtered_geojson_indices
+            .extend(search.files.iter().map(|f| f.file_index));
+    } else if app.search_mode == SearchMode::Properties || app.search_query_input.text.is_empty() {
+        app.filtered_geojson_indices.extend(0..app.layers.len());
+    } else {
+        // Best matches first, ties shorter names first; the best one is highlighted
+        // whenever the query changes
+        if app.search_query_input.text != app.previous_search_query_buffer {
+            app.selected_file_index = 0;
+        }
+        let mut ranked: Vec<(i32, usize)> = app
+            .layers
+            .iter()
+            .enumerate()
+            .filter_map(|(i, layer)| {
+                Some((
+                    search::fuzzy_match(&app.search_query_input.text, &layer.file)?.score,
+                    i,
+                ))
+            })
+            .collect();
+        ranked.sort_by_key(|&(score, i)| (cmp::Reverse(score), app.layers[i].file.len(), i));
+        app.filtered_geojson_indices
+            .extend(ranked.into_iter().map(|(_, i)| i));
+    }
+    if app.filtered_geojson_indices.is_empty() {
+        app.selected_file_index = 0;
+    } else {
+        app.selected_file_index = cmp::min(
+            app.selected_file_index,
+            app.filtered_geojson_indices.len().saturating_sub(1),
+        );
+    }
+    app.previous_search_query_buffer
+        .clone_from(&app.search_query_input.text);
+}
+
+// Searches the property values of every file for the query, using the
+// features in memory and reading the other files, then lists the files with
+// matches
+fn search_properties(app: &mut App) {
+    let mut search = PropertySearch::new(&app.search_query_input.text);
+    for index in 0..app.layers.len() {
+        if let Some(features) = &app.layers[index].features {
+            search.add_file(index, features);
+            continue;
+        }
+        let full_filepath = PathBuf::from(GEOJSON_DIR).join(&app.layers[index].file);
+        match loader::read_features(&full_filepath) {
+            Ok(features) => search.add_file(index, &features),
+            Err(_) => search.failed.push(app.layers[index].file.clone()),
+        }
+    }
+
+    let mut notification = if search.files.is_empty() {
+        format!("No property value contains '{}'", search.query)
+    } else {
+        format!(
+            "'{}' found in {} features of {} files",
+            search.query,
+            search.feature_count(),
+            search.files.len()
+        )
+    };
+    if !search.failed.is_empty() {
+        notification.push_str(&format!(
+            " ({} could not be read: {})",
+            search.failed.len(),
+            search.failed.join(", ")
+        ));
+    }
+    app.notification = notification;
+    app.property_search = Some(search);
+    refilter_files(app);
+}
+
+// Draws the image at `path` underneath the layers of every plot, or stops
+// drawing it when it is the underlay already
+fn open_underlay(app: &mut App, path: &Path) -> Result<String, String> {
+    if app
+        .underlay
+        .as_ref()
+        .is_some_and(|raster| raster.path == path)
+    {
+        app.underlay = None;
+        return Ok(format!("Removed the underlay {}.", path.display()));
+    }
+    let raster = Raster::open(path)?;
+    app.underlay = Some(Arc::new(raster));
+    Ok(format!(
+        "Drawing {} underneath the layers (open it again to remove it).",
+        path.display()
+    ))
+}
+
+// Lists the file at the typed path and highlights it. Files outside the data
+// directory are listed by their absolute path; returns the notification to show
+fn open_external_file(app: &mut App, text: &str) -> Result<String, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(String::from("Type the path of a file to open."));
+    }
+    let path = fs::canonicalize(loader::expand_home(text))
+        .map_err(|e| format!("Cannot open {}: {}", text, e))?;
+    if path.is_file() && raster::is_raster_file(&path) {
+        return open_underlay(app, &path);
+    }
+    if !path.is_file() || !loader::is_supported_file(&path) {
+        return Err(format!(
+            "{} is not a GeoJSON, GeoJSONL, WKT, WKB or OSM PBF file, or a PNG or JPEG image",
+            path.display()
+        ));
+    }
+    // Files of the data directory are listed by their name already
+    let in_data_dir = fs::canonicalize(GEOJSON_DIR).ok().as_deref() == path.parent();
+    let name = if in_data_dir {
+        path.file_name().and_then(|f| f.to_str())
+    } else {
+        path.to_str()
+    }
+    .ok_or_else(|| format!("{} is not a valid UTF-8 path", path.display()))?
+    .to_string();
+
+    let index = match app.file_index(&name) {
+        Some(index) => index,
+        None => app.add_geojson_file(name.clone()),
+    };
+    // A search hiding the file is cleared
+    if !app.filtered_geojson_indices.contains(&index) {
+        app.search_query_input.clear();
+        app.property_search = None;
+        refilter_files(app);
+    }
+    if let Some(position) = app
+        .filtered_geojson_indices
+        .iter()
+        .position(|&i| i == index)
+    {
+        app.selected_file_index = position;
+    }
+    Ok(if app.is_external(index) {
+        format!("Opened {} (external).", name)
+    } else {
+        format!("{} is in the data directory; highlighted it.", name)
+    })
+}
+
+// Selects the listed files whose metadata `filter` accepts, reading the
+// metadata of the files that have not been looked at yet into the cache
+fn select_where(app: &mut App, filter: &FeatureFilter) {
+    let mut matched = 0;
+    for index in app.filtered_geojson_indices.clone() {
+        if app.layers[index].info.is_none() {
+            let full_filepath = PathBuf::from(GEOJSON_DIR).join(&app.layers[index].file);
+            let cached = app.layers[index]
+                .crs
+                .is_none()
+                .then(|| app.info_cache.get(&full_filepath))
+                .flatten();
+            let info = cached.unwrap_or_else(|| {
+                let (info, _) = loader::load_file(&full_filepath, app.layers[index].crs.as_deref());
+                if app.layers[index].crs.is_none() {
+                    app.info_cache.insert(&full_filepath, &info);
+                }
+                info
+            });
+            app.layers[index].info = Some(info);
+        }
+        let Some(info) = &app.layers[index].info else {
+            continue;
+        };
+        if filter.accepts(&info.metadata_feature(&app.layers[index].file)) {
+            app.set_selected(index, true);
+            matched += 1;
+        }
+    }
+    load_selected_files(app);
+    app.notification = format!(
+        "{} of {} listed files match {}; {} files selected.",
+        matched,
+        app.filtered_geojson_indices.len(),
+        filter,
+        app.selected_count()
+    );
+}
+
+// Toggles the selection of a file and reports its assigned color
+fn toggle_file_selection(app: &mut App, original_index: usize) {
+    app.set_selected(original_index, !app.layers[original_index].selected);
+    // The highlighted file may only have been summarised from its header
+    if app.layers[original_index].selected && app.layers[original_index].features.is_none() {
+        load_file_into_app(app, original_index);
+    }
+    app.notification = match app.layers[original_index].color {
+        Some(color) => format!(
+            "Selected: {} (Color: R{} G{} B{})",
+            app.layers[original_index].file, color.0, color.1, color.2
+        ),
+        None => format!("Deselected: {}", app.layers[original_index].file),
+    };
+}
+
+// Moves the highlight by one row and selects both the old and the new file
+fn extend_selection(app: &mut App, down: bool) {
+    let from = app.selected_file_index;
+    let to = if down {
+        (from + 1).min(app.filtered_geojson_indices.len().saturating_sub(1))
+    } else {
+        from.saturating_sub(1)
+    };
+    app.select_range(from, to);
+    app.selected_file_index = to;
+    load_selected_files(app);
+    app.notification = format!("{} files selected.", app.selected_count());
+}
+
+// Highlights the clicked file; a click on the checkbox or a double-click toggles it,
+// and a Shift+click selects every file from the highlighted one to the clicked one
+fn click_file_list(app: &mut App, column: u16, row: u16, shift: bool) {
+    let area = app.file_list_area;
+    if row <= area.y || row >= area.y + area.height.saturating_sub(1) {
+        return; // Border rows
+    }
+    let list_index = app.scroll_offset + (row - area.y - 1) as usize;
+    let Some(&original_index) = app.filtered_geojson_indices.get(list_index) else {
+        return;
+    };
+    if shift {
+        app.select_range(app.selected_file_index, list_index);
+        app.selected_file_index = list_index;
+        load_selected_files(app);
+        app.notification = format!("{} files selected.", app.selected_count());
+        return;
+    }
+    app.selected_file_index = list_index;
+
+    let now = Instant::now();
+    let on_checkbox = column > area.x && column <= area.x + 3; // "[x]"
+    let is_double_click = app.last_click.is_some_and(|(time, index)| {
+        index == original_index && now.duration_since(time) <= DOUBLE_CLICK_INTERVAL
+    });
+    if on_checkbox || is_double_click {
+        toggle_file_selection(app, original_index);
+        app.last_click = None;
+    } else {
+        app.last_click = Some((now, original_index));
+    }
+}
+
+// Parses a file into the info cache and the loaded preview features
+fn load_file_into_app(app: &mut App, index: usize) {
+    let full_filepath = PathBuf::from(GEOJSON_DIR).join(&app.layers[index].file);
+    let (info, features) = loader::load_file(&full_filepath, app.layers[index].crs.as_deref());
+    if app.layers[index].crs.is_none() {
+        app.info_cache.insert(&full_filepath, &info);
+    }
+    if let Some(message) = info.crs_warning.as_ref().or(info.skipped_message.as_ref()) {
+        app.notification = format!("{}: {}", app.layers[index].file, message);
+    }
+    for (level, message) in [
+        (LogLevel::Error, &info.parse_error),
+        (LogLevel::Warning, &info.skipped_message),
+        (LogLevel::Warning, &info.crs_warning),
+    ] {
+        if let Some(message) = message {
+            app.log(level, format!("{}: {}", app.layers[index].file, message));
+        }
+    }
+    let layer = &mut app.layers[index];
+    layer.forget_contents();
+    layer.info = Some(info);
+    layer.features = features;
+    app.refresh_filter_count(index);
+}
+
+// Makes sure every selected file is parsed, e.g. after restoring a session or a
+// bulk selection
+fn load_selected_files(app: &mut App) {
+    for index in 0..app.layers.len() {
+        let layer = &app.layers[index];
+        if layer.selected && layer.features.is_none() {
+            load_file_into_app(app, index);
+        }
+    }
+    app.release_unused_features();
+}
+
+// Reads the file at `index` for a geoprocessing operation. Results are written
+// in WGS 84, so coordinates that could not be reprojected are refused.
+fn read_for_processing(app: &App, index: usize) -> Result<Vec<Feature>, String> {
+    let name = &app.layers[index].file;
+    let full_filepath = PathBuf::from(GEOJSON_DIR).join(name);
+    match loader::read_features_with_crs(&full_filepath, app.layers[index].crs.as_deref()) {
+        Ok(parsed) => match parsed.crs_warning {
+            Some(warning) => Err(format!("Cannot process {}: {}", name, warning)),
+            None => Ok(parsed.features),
+        },
+        Err(e) => Err(format!("Failed to read {}: {}", name, e)),
+    }
+}
+
+// The clip mask chosen in the file list, if any
+fn read_clip_mask(app: &App) -> Option<Result<ClipMask, String>> {
+    let mask_idx = app.clip_mask_index?;
+    let mask_path = PathBuf::from(GEOJSON_DIR).join(&app.layers[mask_idx].file);
+    let features = match &app.layers[mask_idx].features {
+        Some(features) => Ok(features.clone()),
+        None => loader::read_features_with_crs(&mask_path, app.layers[mask_idx].crs.as_deref())
+            .map(|parsed| parsed.features),
+    };
+    Some(match features {
+        Ok(features) => ClipMask::from_features(&features, app.clip_mask_shade)
+            .ok_or_else(|| format!("Clip mask {} has no polygons", mask_path.display())),
+        Err(e) => Err(format!(
+            "Error reading clip mask from {}: {}",
+            mask_path.display(),
+            e
+        )),
+    })
+}
+
+// Writes the selected files to `text` in the output directory as they would
+// be plotted: filtered, cut by the clip mask, simplified and colored. Returns the
+// notification to show.
+fn export_selected(app: &App, text: &str) -> Result<String, String> {
+    let path = PathBuf::from(OUTPUT_DIR).join(text.trim());
+    if !export::is_export_file(&path) {
+        return Err(format!(
+            "Cannot export to '{}': use .geojson, .shp, .gpkg or .html.",
+            text.trim()
+        ));
+    }
+    let mask = read_clip_mask(app).transpose()?;
+    let mut layers = Vec::new();
+    for index in (0..app.layers.len()).filter(|&i| app.layers[i].selected) {
+        let features =
+            filter::split_features(read_for_processing(app, index)?, |position, feature| {
+                app.layer_accepts(index, position, feature)
+            })
+            .accepted;
+        let name = &app.layers[index].file;
+        layers.push(ExportLayer {
+            name: Path::new(name)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or(name)
+                .to_string(),
+            features: export::prepare_features(
+                features,
+                mask.as_ref(),
+                app.layers[index]
+                    .simplify_tolerance
+                    .unwrap_or(app.simplify_tolerance),
+            ),
+            color: app.layers[index].color.unwrap_or(RGBColor(0, 0, 0)),
+        });
+    }
+    if layers.is_empty() {
+        return Err(String::from("Select the files to export first."));
+    }
+    let count: usize = layers.iter().map(|layer| layer.features.len()).sum();
+    let written = export::write_layers(&path, &layers, &app.title_input.text)?;
+    let names: Vec<String> = written.iter().map(|p| p.display().to_string()).collect();
+    Ok(format!(
+        "Exported {} features of {} layers to {}",
+        count,
+        layers.len(),
+        names.join(", ")
+    ))
+}
+
+// Stores the value typed for the highlighted cell of the attribute table
+fn edit_table_cell(app: &mut App) {
+    let Some(table) = app.attribute_table.as_mut() else {
+        return;
+    };
+    let index = table.file_index;
+    let Some(features) = app.layers[index].features.as_mut() else {
+        return;
+    };
+    let (Some(position), Some(column)) = (table.feature(), table.columns.get(table.column)) else {
+        return;
+    };
+    let previous = features[position]
+        .properties
+        .as_ref()
+        .and_then(|p| p.get(column))
+        .cloned();
+    match table::parse_cell(&app.table_input.text, previous.as_ref()) {
+        Ok(value) => {
+            app.notification = format!(
+                "Set {} of feature {}; W saves the file.",
+                column,
+                position + 1
+            );
+            table.set_cell(features, value);
+            app.current_mode = AppMode::AttributeTable;
+            app.refresh_filter_count(index);
+        }
+        Err(e) => app.notification = e,
+    }
+}
+
+// Writes the features of the attribute table, as edited, to `name` in the data
+// directory: a new file that is listed, or the file itself. Returns the
+// notification to show.
+fn save_table(app: &mut App, name: &str) -> Result<String, String> {
+    let Some(table) = app.attribute_table.as_mut() else {
+        return Err(String::from("No attribute table is open."));
+    };
+    let index = table.file_index;
+    if Path::new(name)
+        .extension()
+        .is_none_or(|ext| ext != "geojson")
+    {
+        return Err(format!("Cannot save to '{}': use a .geojson file.", name));
+    }
+    let features = app.layers[index].features.clone().unwrap_or_default();
+    let count = features.len();
+    loader::save_features(&PathBuf::from(GEOJSON_DIR).join(name), features)
+        .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+    table.modified = false;
+    if app.layers[index].file == name {
+        // The features were written in WGS 84, so an override no longer applies
+        app.layers[index].crs = None;
+        app.layers[index].info = None;
+    } else {
+        app.add_geojson_file(name.to_string());
+    }
+    Ok(format!("Saved {} features to {}.", count, name))
+}
+
+// Writes the result of processing the file at `index` as
+// `<stem>_<suffix>.geojson` next to it, then lists and highlights it.
+// Returns the name of the new file.
+fn write_derived_file(
+    app: &mut App,
+    index: usize,
+    suffix: &str,
+    features: Vec<Feature>,
+) -> Result<String, String> {
+    let name = &app.layers[index].file;
+    let stem = Path::new(name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(name);
+    let output_name = format!("{}_{}.geojson", stem, suffix);
+    loader::save_features(&PathBuf::from(GEOJSON_DIR).join(&output_name), features)
+        .map_err(|e| format!("Failed to write {}: {}", output_name, e))?;
+    let new_index = app.add_geojson_file(output_name.clone());
+    if let Some(position) = app
+        .filtered_geojson_indices
+        .iter()
+        .position(|&i| i == new_index)
+    {
+        app.selected_file_index = position;
+    }
+    Ok(output_name)
+}
+
+// Writes the file at `index` buffered by `distance` next to it; returns the
+// notification to show
+fn buffer_file(app: &mut App, index: usize, distance: BufferDistance) -> String {
+    let features = match read_for_processing(app, index) {
+        Ok(features) => features,
+        Err(e) => return e,
+    };
+    let buffered = buffer::buffer_features(&features, distance);
+    let count = buffered.len();
+    match write_derived_file(
+        app,
+        index,
+        &format!("buffer_{}", distance.label()),
+        buffered,
+    ) {
+        Ok(output_name) => format!(
+            "Buffered {} of {} features by {} into {}",
+            count,
+            features.len(),
+            distance.label(),
+            output_name
+        ),
+        Err(e) => e,
+    }
+}
+
+// Writes the file at `index` with coordinates rounded to `decimals` places and
+// no whitespace next to it; returns the notification to show, with the sizes
+fn minify_file(app: &mut App, index: usize, decimals: u32) -> String {
+    let mut features = match read_for_processing(app, index) {
+        Ok(features) => features,
+        Err(e) => return e,
+    };
+    minify::round_features(&mut features, decimals);
+    let file_size = |name: &str| {
+        fs::metadata(PathBuf::from(GEOJSON_DIR).join(name)).map_or(0, |metadata| metadata.len())
+    };
+    let before = file_size(&app.layers[index].file);
+    match write_derived_file(app, index, "min", features) {
+        Ok(output_name) => {
+            let after = file_size(&output_name);
+            let saved = if before > 0 {
+                100.0 * (1.0 - after as f64 / before as f64)
+            } else {
+                0.0
+            };
+            format!(
+                "Minified to {} decimals into {}: {} -> {} ({:.0}% smaller)",
+                decimals,
+                output_name,
+                minify::format_size(before),
+                minify::format_size(after),
+                saved
+            )
+        }
+        Err(e) => e,
+    }
+}
+
+// Looks for duplicated geometries in the file at `index` and shows them on the
+// Duplicates screen
+fn find_duplicates(app: &mut App, index: usize, tolerance: f64) {
+    match read_for_processing(app, index) {
+        Ok(features) => {
+            let report = duplicates::find_duplicates(&app.layers[index].file, &features, tolerance);
+            app.notification = report.summary();
+            app.duplicate_report = Some(report);
+            app.duplicate_scroll = 0;
+            app.current_screen = CurrentScreen::Duplicates;
+            app.current_mode = AppMode::Duplicates;
+        }
+        Err(e) => {
+            app.notification = e;
+            app.current_mode = AppMode::Navigation;
+        }
+    }
+}
+
+// Writes the file of the duplicate report without its duplicates next to it;
+// returns the notification to show
+fn write_deduplicated(app: &mut App) -> String {
+    let Some(report) = app.duplicate_report.clone() else {
+        return String::from("No duplicates were looked for.");
+    };
+    if report.duplicates.is_empty() {
+        return format!("{} has no duplicates to remove.", report.file);
+    }
+    let Some(index) = app.file_index(&report.file) else {
+        return format!("{} is no longer listed.", report.file);
+    };
+    let features = match read_for_processing(app, index) {
+        Ok(features) if features.len() == report.features => features,
+        Ok(_) => {
+            return format!(
+                "{} changed since it was checked; check it again.",
+                report.file
+            );
+        }
+        Err(e) => return e,
+    };
+    let kept = report.remove_from(features);
+    let count = kept.len();
+    match write_derived_file(app, index, "dedup", kept) {
+        Ok(output_name) => format!(
+            "Wrote {} features to {}, {} duplicates left out.",
+            count,
+            output_name,
+            report.duplicates.len()
+        ),
+        Err(e) => e,
+    }
+}
+
+// Checks the polygons of the file at `index`, alone or against the other
+// selected files, for overlaps and gaps and shows them on the Topology screen
+fn check_topology(app: &mut App, index: usize, against_selected: bool) {
+    app.current_mode = AppMode::Navigation;
+    let mut indices = vec![index];
+    if against_selected {
+        indices.extend((0..app.layers.len()).filter(|&i| i != index && app.layers[i].selected));
+        if indices.len() == 1 {
+            app.notification = String::from("Select the files to check against first.");
+            return;
+        }
+    }
+    let mut layers = Vec::new();
+    for i in indices {
+        match read_for_processing(app, i) {
+            Ok(features) => layers.push((app.layers[i].file.clone(), features)),
+            Err(e) => {
+                app.notification = e;
+                return;
+            }
+        }
+    }
+    let report = topology::check_topology(&layers);
+    app.notification = report.summary();
+    app.topology_report = Some(report);
+    app.topology_selected = 0;
+    app.current_screen = CurrentScreen::Topology;
+    app.current_mode = AppMode::Topology;
+}
+
+// Writes the file at `index` with every coordinate snapped to `grid` next to
+// it; returns the notification to show
+fn snap_file(app: &mut App, index: usize, grid: f64) -> String {
+    let mut features = match read_for_processing(app, index) {
+        Ok(features) => features,
+        Err(e) => return e,
+    };
+    let before = features.len();
+    let removed = snap::snap_features(&mut features, grid);
+    let count = features.len();
+    match write_derived_file(app, index, &format!("snap_{}", grid), features) {
+        Ok(output_name) => format!(
+            "Snapped {} features to a grid of {} into {}: {} positions merged, {} features collapsed",
+            count,
+            grid,
+            output_name,
+            removed,
+            before - count
+        ),
+        Err(e) => e,
+    }
+}
+
+// Shown when asking for the dissolve property, with the properties of the
+// file's first feature if it is loaded
+fn dissolve_prompt(app: &App, index: usize) -> String {
+    let properties: Vec<&str> = app.layers[index]
+        .features
+        .as_ref()
+        .and_then(|features| features.first()?.properties.as_ref())
+        .map(|properties| properties.keys().take(6).map(String::as_str).collect())
+        .unwrap_or_default();
+    if properties.is_empty() {
+        format!("Dissolve {} by property:", app.layers[index].file)
+    } else {
+        format!(
+            "Dissolve {} by property ({}, ...):",
+            app.layers[index].file,
+            properties.join(", ")
+        )
+    }
+}
+
+// Writes the polygons of the file at `index` unioned by `property` next to it;
+// returns the notification to show
+fn dissolve_file(app: &mut App, index: usize, property: &str) -> String {
+    let features = match read_for_processing(app, index) {
+        Ok(features) => features,
+        Err(e) => return e,
+    };
+    if !features.iter().any(|f| f.property(property).is_some()) {
+        return format!(
+            "No feature of {} has a '{}' property.",
+            app.layers[index].file, property
+        );
+    }
+    let dissolved = dissolve::dissolve_features(&features, property);
+    let count = dissolved.len();
+    let suffix = format!("dissolve_{}", property.replace(['/', '\\', ' '], "_"));
+    match write_derived_file(app, index, &suffix, dissolved) {
+        Ok(output_name) => format!(
+            "Dissolved {} features into {} by {} as {}",
+            features.len(),
+            count,
+            property,
+            output_name
+        ),
+        Err(e) => e,
+    }
+}
+
+// Writes the file at `index` with the columns of a CSV file joined to its
+// features next to it; returns the notification to show
+fn join_file(app: &mut App, index: usize, spec: &JoinSpec) -> String {
+    let mut features = match read_for_processing(app, index) {
+        Ok(features) => features,
+        Err(e) => return e,
+    };
+    let csv_path = loader::resolve_input(&spec.csv);
+    let report = match join::read_csv(&csv_path)
+        .and_then(|table| join::join_features(&mut features, &table, &spec.property, &spec.column))
+    {
+        Ok(report) => report,
+        Err(e) => return e,
+    };
+    if report.matched == 0 {
+        return format!(
+            "No feature of {} has a '{}' matching the '{}' column of {}.",
+            app.layers[index].file, spec.property, spec.column, spec.csv
+        );
+    }
+    let csv_stem = csv_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("csv")
+        .replace(' ', "_");
+    match write_derived_file(app, index, &format!("join_{}", csv_stem), features) {
+        Ok(output_name) => format!(
+            "Joined {} of {} features to {} ({} columns) as {}",
+            report.matched, report.total, spec.csv, report.columns, output_name
+        ),
+        Err(e) => e,
+    }
+}
+
+// Starts plotting the selected files into the output image, or onto the
+// clipboard, on a worker thread that reports its progress and result through
+// `events`, so the TUI stays responsive; Esc in the file list cancels it. The
+// layers get copies of the loaded features, which stay with the preview.
+// Returns the summary at once when nothing could be rendered.
+fn start_plot(app: &mut App, to_clipboard: bool, events: Sender<Event>) -> Option<PlotSummary> {
+    let mut summary = PlotSummary::default();
+    let output_filename = PathBuf::from(OUTPUT_DIR).join(&app.output_filename_input.text);
+    let reading = Instant::now();
+
+    let mut options = app.plot_options();
+    match read_clip_mask(app) {
+        Some(Ok(mask)) => options.clip_mask = Some(mask),
+        Some(Err(e)) => summary.warnings.push(format!("{}; plotted unclipped", e)),
+        None => {}
+    }
+
+    let mut plotted = Vec::new(); // File index of every layer
+    let mut layers = Vec::new();
+    let mut counts = Vec::new(); // Name, features and features left out by the filter of every layer
+    for file_idx in 0..app.layers.len() {
+        if !app.layers[file_idx].selected {
+            continue;
+        }
+        let full_filepath = PathBuf::from(GEOJSON_DIR).join(&app.layers[file_idx].file);
+        let plot_color_for_file = app.layers[file_idx]
+            .color
+            // Fallback to black if for some reason color wasn't assigned
+            .unwrap_or(RGBColor(0, 0, 0));
+
+        // Reuse features already parsed for the preview
+        let features = match &app.layers[file_idx].features {
+            Some(features) => Ok(features.clone()),
+            None => {
+                loader::read_features_with_crs(&full_filepath, app.layers[file_idx].crs.as_deref())
+                    .map(|parsed| parsed.features)
+            }
+        };
+        match features {
+            Ok(features) => {
+                if app.focus_index == Some(file_idx) {
+                    options.focus_layer = Some(layers.len());
+                }
+                // Copies simplified for earlier plots fit only the whole layer
+                let (features, rejected, levels) = if app.layers[file_idx].filter.is_some()
+                    || app.layers[file_idx].feature_selection.is_some()
+                {
+                    let split = filter::split_features(features, |position, feature| {
+                        app.layer_accepts(file_idx, position, feature)
+                    });
+                    (split.accepted, split.rejected.len(), None)
+                } else {
+                    (features, 0, app.levels_of_detail(file_idx))
+                };
+                let layer = &app.layers[file_idx];
+                plotted.push(file_idx);
+                counts.push((layer.file.clone(), features.len(), rejected));
+                layers.push(PlotLayer {
+                    features,
+                    color: plot_color_for_file,
+                    simplify_tolerance: layer.simplify_tolerance,
+                    line_width: layer.line_width,
+                    line_style: layer.line_style,
+                    point_size: layer.point_size,
+                    point_shape: layer.point_shape,
+                    opacity: layer.opacity.map(|p| p as f64 / 100.0),
+                    geometry_types: layer.geometry_types,
+                    levels,
+                });
+            }
+            // Left out and listed with the results instead of stopping the plot
+            Err(e) => options
+                .failed_layers
+                .push(format!("{}: {}", full_filepath.display(), e)),
+        }
+    }
+    summary.read_time = reading.elapsed();
+    for failure in &options.failed_layers {
+        summary
+            .warnings
+            .push(format!("Layer not drawn: {}", failure));
+    }
+    for &file_idx in &plotted {
+        let layer = &app.layers[file_idx];
+        let Some(info) = &layer.info else {
+            continue;
+        };
+        for message in [&info.skipped_message, &info.crs_warning]
+            .into_iter()
+            .flatten()
+        {
+            summary
+                .warnings
+                .push(format!("{}: {}", layer.file, message));
+        }
+    }
+
+    if layers.is_empty() {
+        let error = String::from("None of the selected files could be read.");
+        for warning in &summary.warnings {
+            app.log(LogLevel::Warning, warning.clone());
+        }
+        app.log(LogLevel::Error, format!("Plot failed: {}", error));
+        summary.error = Some(error);
+        return Some(summary);
+    }
+    // Listed in case the render succeeds
+    if !to_clipboard {
+        summary.outputs.push(output_filename.clone());
+        if options.thumbnail.is_some() {
+            summary.outputs.push(plot::thumbnail_path(&output_filename));
+        }
+    }
+
+    let progress = Arc::new(RenderProgress::default());
+    options.progress = Some(Arc::clone(&progress));
+    let watched = Arc::clone(&progress);
+    thread::spawn(move || {
+        let result = thread::scope(|scope| {
+            let render = scope.spawn(|| {
+                if to_clipboard {
+                    let (png, report) =
+                        plot::render_plot_png(&layers, &options).map_err(|e| e.to_string())?;
+                    clipboard::copy_png(&png)?;
+                    Ok((report, true))
+                } else {
+                    plot::render_plot(&output_filename, &layers, &options)
+                        .map(|report| (report, false))
+                        .map_err(|e| e.to_string())
+                }
+            });
+            while !render.is_finished() {
+                thread::sleep(PROGRESS_INTERVAL);
+                let (drawn, total) = watched.counts();
+                // Nobody is listening once the TUI has quit
+                if events.send(Event::PlotProgress(drawn, total)).is_err() {
+                    watched.cancel();
+                }
+            }
+            render
+                .join()
+                .unwrap_or_else(|_| Err(String::from("Rendering failed unexpectedly.")))
+        });
+        let _ = events.send(Event::PlotFinished(result));
+    });
+    app.plot_job = Some(PlotJob {
+        progress,
+        counts: (0, 0),
+        to_clipboard,
+        layers: counts,
+        summary,
+        started: Instant::now(),
+    });
+    None
+}
+
+// Completes the summary of the plot rendering in the background with its result
+fn finish_plot(app: &mut App, result: PlotResult) -> Option<(PlotSummary, bool)> {
+    let job = app.plot_job.take()?;
+    let mut summary = job.summary;
+    summary.render_time = job.started.elapsed();
+    match result {
+        Ok((report, copied)) => {
+            summary.copied = copied;
+            summary.layers = job
+                .layers
+                .into_iter()
+                .zip(report.features_drawn)
+                .map(|((name, features, rejected), drawn)| LayerSummary {
+                    name,
+                    drawn,
+                    skipped: features.saturating_sub(drawn) + rejected,
+                })
+                .collect();
+            summary.warnings.extend(report.warnings);
+        }
+        Err(e) => {
+            summary.outputs.clear();
+            summary.error = Some(e);
+        }
+    }
+    for warning in &summary.warnings {
+        app.log(LogLevel::Warning, warning.clone());
+    }
+    if let Some(error) = summary.error.as_ref().filter(|e| *e != plot::CANCELLED) {
+        app.log(LogLevel::Error, format!("Plot failed: {}", error));
+    }
+    Some((summary, job.to_clipboard))
+}
+
+// Reports a finished plot: a copy to the clipboard in the notification, an
+// image on the plot summary screen, unless the user has moved on to something
+// else, which the plot does not interrupt
+fn show_plot_summary(app: &mut App, summary: PlotSummary, to_clipboard: bool) {
+    if to_clipboard {
+        app.notification = match (&summary.error, summary.copied) {
+            (Some(error), _) if error == plot::CANCELLED => String::from("Plot cancelled."),
+            (Some(error), _) => format!("Copying the plot failed: {}", error),
+            (None, true) => format!(
+                "Copied the plot of {} layers to the clipboard.",
+                summary.layers.len()
+            ),
+            (None, false) => String::from("The plot was not copied."),
+        };
+        return;
+    }
+    if summary.error.as_deref() == Some(plot::CANCELLED) {
+        app.notification = String::from("Plot cancelled.");
+        return;
+    }
+    let seconds = (summary.read_time + summary.render_time).as_secs_f64();
+    let idle = matches!(app.current_mode, AppMode::Navigation | AppMode::PlotSummary);
+    app.notification = match (&summary.error, summary.outputs.first()) {
+        (Some(error), _) => format!("Plot failed: {}", error),
+        (None, _) if idle => format!(
+            "Plot finished in {:.2} s. Press O to open it, C to copy its path, R to plot again.",
+            seconds
+        ),
+        (None, Some(output)) => {
+            format!("Plot finished in {:.2} s: {}", seconds, output.display())
+        }
+        (None, None) => format!("Plot finished in {:.2} s.", seconds),
+    };
+    app.plot_summary = Some(summary);
+    if idle {
+        app.current_screen = CurrentScreen::PlotSummary;
+        app.current_mode = AppMode::PlotSummary;
+    }
+}
+
+// Keys of the plot summary screen: open or copy the image, or plot again
+fn handle_plot_summary_key(app: &mut App, key_event: KeyEvent, requests: &mut Requests) {
+    let output = app
+        .plot_summary
+        .as_ref()
+        .and_then(|summary| summary.outputs.first().cloned());
+    match key_event.code {
+        KeyCode::Char('o') | KeyCode::Char('O') => match &output {
+            Some(output) => match open_in_viewer(output) {
+                Ok(()) => app.notification = format!("Opening {}", output.display()),
+                Err(e) => app.notification = format!("Failed to open {}: {}", output.display(), e),
+            },
+            None => app.notification = String::from("No image to open."),
+        },
+        KeyCode::Char('c') | KeyCode::Char('C') => match &output {
+            Some(output) => {
+                let path = fs::canonicalize(output).unwrap_or_else(|_| output.clone());
+                app.notification = match clipboard::copy_text(&path.to_string_lossy()) {
+                    Ok(()) => format!("Copied {} to the clipboard.", path.display()),
+                    Err(e) => e,
+                };
+            }
+            None => app.notification = String::from("No image to copy."),
+        },
+        KeyCode::Char('r') | KeyCode::Char('R') => {
+            load_selected_files(app);
+            requests.plot = true;
+            app.notification = String::from("Plotting selected files again...");
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.current_screen = CurrentScreen::GeoJsonMapper;
+            app.current_mode = AppMode::Navigation;
+        }
+        _ => {}
+    }
+}
+
+// Opens `path` with the desktop's default application
+fn open_in_viewer(path: &Path) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Reaped in the background so the viewer never blocks the TUI
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+// Writes a point for every polygon of the file at `index` next to it;
+// returns the notification to show
+fn points_file(app: &mut App, index: usize, kind: PointKind) -> String {
+    let features = match read_for_processing(app, index) {
+        Ok(features) => features,
+        Err(e) => return e,
+    };
+    let points = centroid::representative_points(&features, kind);
+    if points.is_empty() {
+        return format!("{} has no polygons.", app.layers[index].file);
+    }
+    let count = points.len();
+    match write_derived_file(app, index, &format!("{}s", kind.label()), points) {
+        Ok(output_name) => format!(
+            "Wrote {} {} points of {} features into {}",
+            count,
+            kind.label(),
+            features.len(),
+            output_name
+        ),
+        Err(e) => e,
+    }
+}
+
+// What the event loop does after the next draw, so that the notification
+// announcing it is shown first
+#[derive(Default)]
+struct Requests {
+    plot: bool,                       // Plot the selected files
+    copy: bool,                       // Likewise, onto the clipboard
+    search: bool,                     // Search the property values for the query
+    selection: Option<FeatureFilter>, // Select the files whose metadata matches
+    quit: bool,
+}
+
+// Runs `action` on the file list, whether its key was pressed or the widget
+// bound to it was clicked
+fn run_action(app: &mut App, action: Action, requests: &mut Requests) {
+    match action {
+        Action::Down => {
+            if app.selected_file_index + 1 < app.filtered_geojson_indices.len() {
+                app.selected_file_index += 1;
+            }
+        }
+        Action::Up => {
+            if app.selected_file_index > 0 {
+                app.selected_file_index -= 1;
+            }
+        }
+        Action::ToggleSelection => {
+            // Space
+            match app.highlighted_file_index() {
+                Some(original_index) => toggle_file_selection(app, original_index),
+                None => {
+                    app.notification = String::from("No files to select in current view.");
+                }
+            }
+        }
+        Action::SelectDown => extend_selection(app, true),
+        Action::SelectUp => extend_selection(app, false),
+        Action::SelectAll => {
+            let count = app.update_filtered_selection(|_| true);
+            load_selected_files(app);
+            app.notification = format!("Selected {} files.", count);
+        }
+        Action::DeselectAll => {
+            app.update_filtered_selection(|_| false);
+            app.release_unused_features();
+            app.notification = String::from("Deselected all files in the list.");
+        }
+        Action::OpenFile => {
+            app.open_path_input.move_to_end();
+            app.current_mode = AppMode::EditingOpenPath;
+            app.notification = String::from(
+                "Path or URL of a GeoJSON file, or an image to draw underneath, to open. Tab completes, Enter opens, Escape cancels.",
+            );
+        }
+        Action::Export => {
+            app.export_path_input.move_to_end();
+            app.current_mode = AppMode::EditingExport;
+            app.notification = String::from(
+                "File in the output directory to save the selected layers to: .geojson, .shp, .gpkg or .html (interactive map). Enter saves, Escape cancels.",
+            );
+        }
+        Action::Refetch => {
+            if let Some(idx) = app.highlighted_file_index() {
+                let path = PathBuf::from(GEOJSON_DIR).join(&app.layers[idx].file);
+                app.notification = match remote::source_url(&path) {
+                    Some(url) => start_download(app, &url),
+                    None => format!("{} was not downloaded from a URL.", app.layers[idx].file),
+                };
+            }
+        }
+        Action::Wfs => {
+            if app.wfs_service.is_some() || app.wfs_request.is_some() {
+                app.current_mode = AppMode::Wfs;
+                app.notification = String::from(
+                    "WFS: Enter fetches the highlighted feature type within the preview extent, A all of it.",
+                );
+            } else {
+                app.wfs_url_input.move_to_end();
+                app.current_mode = AppMode::EditingWfsUrl;
+                app.notification = String::from(
+                    "URL of a WFS service, e.g. https://example.org/geoserver/wfs. Enter lists its feature types, Escape cancels.",
+                );
+            }
+        }
+        Action::Serve => {
+            app.notification = match app.preview_server.take() {
+                Some(server) => format!("Stopped serving on {}.", server.url()),
+                None => match PreviewServer::start(&app.serve_address) {
+                    Ok(server) => {
+                        let notification = format!(
+                            "Serving the selected layers on {} (port {} for others on the network); F7 stops.",
+                            server.url(),
+                            server.address.port()
+                        );
+                        app.preview_server = Some(server);
+                        update_preview_server(app);
+                        notification
+                    }
+                    Err(e) => e,
+                },
+            };
+        }
+        Action::SelectWhere => {
+            app.selection_input.move_to_end();
+            app.current_mode = AppMode::EditingSelection;
+            app.notification = String::from(
+                "Select files where, e.g. polygons && features < 10000 (also points, lines, size_kb, name, crs, error)",
+            );
+        }
+        Action::InvertSelection => {
+            let count = app.update_filtered_selection(|selected| !selected);
+            load_selected_files(app);
+            app.notification = format!("Inverted selection: {} files selected.", count);
+        }
+        Action::Plot => {
+            let num_selected = app.selected_count();
+            if num_selected > 0 {
+                load_selected_files(app);
+                let estimate = app.estimate_plot();
+                if estimate.is_large() {
+                    app.notification =
+                        format!("Large plot: {}. Plot anyway? (y/n)", estimate.summary());
+                    app.current_mode = AppMode::ConfirmPlot;
+                } else {
+                    requests.plot = true;
+                    app.notification = format!("Plotting {} selected files...", num_selected);
+                }
+            } else {
+                app.notification = String::from("No files selected to plot. Use Space to select.");
+            }
+        }
+        Action::Undo | Action::Redo => {
+            let (state, verb) = if action == Action::Undo {
+                (app.history.undo(), "Undid")
+            } else {
+                (app.history.redo(), "Redid")
+            };
+            app.notification = match state.map(|state| history::restore(app, &state)) {
+                Some(Ok(_)) => {
+                    load_selected_files(app);
+                    format!(
+                        "{} the last change ({} to undo, {} to redo).",
+                        verb,
+                        app.history.undo_steps(),
+                        app.history.redo_steps()
+                    )
+                }
+                Some(Err(e)) => format!("Could not restore the change: {}", e),
+                None if action == Action::Undo => String::from("Nothing to undo."),
+                None => String::from("Nothing to redo."),
+            };
+        }
+        Action::CopyPlot => {
+            let num_selected = app.selected_count();
+            if num_selected > 0 {
+                load_selected_files(app);
+                requests.copy = true;
+                app.notification = format!(
+                    "Plotting {} selected files to the clipboard...",
+                    num_selected
+                );
+            } else {
+                app.notification = String::from("No files selected to plot. Use Space to select.");
+            }
+        }
+        Action::CycleColor => {
+            app.current_color_index_for_assignment =
+                (app.current_color_index_for_assignment + 1) % app.plot_colors.len();
+            app.notification = format!(
+                "Next assignment color set to R{} G{} B{}",
+                app.plot_colors[app.current_color_index_for_assignment].0,
+                app.plot_colors[app.current_color_index_for_assignment].1,
+                app.plot_colors[app.current_color_index_for_assignment].2
+            );
+        }
+        Action::Rename => {
+            app.current_mode = AppMode::EditingFilename;
+            app.previous_output_filename_buffer
+                .clone_from(&app.output_filename_input.text);
+            app.notification =
+                String::from("Editing filename. Press Enter to confirm, Escape to cancel.");
+        }
+        Action::EditTitle => {
+            app.previous_title_buffers = [
+                app.title_input.text.clone(),
+                app.subtitle_input.text.clone(),
+                app.attribution_input.text.clone(),
+            ];
+            app.title_field = TitleField::Title;
+            app.title_input.move_to_end();
+            app.current_mode = AppMode::EditingTitle;
+            app.notification =
+                String::from("Editing title. Tab: Next field, Enter: Confirm, Escape: Cancel.");
+        }
+        Action::Search => {
+            app.current_mode = AppMode::Searching;
+            app.previous_search_query_buffer
+                .clone_from(&app.search_query_input.text);
+            app.notification = search_prompt(app.search_mode);
+        }
+        Action::TogglePoints => {
+            app.plot_points = !app.plot_points;
+            app.notification = format!(
+                "Points visibility: {}",
+                if app.plot_points { "ON" } else { "OFF" }
+            );
+        }
+        Action::ToggleLines => {
+            app.plot_lines = !app.plot_lines;
+            app.notification = format!(
+                "Lines visibility: {}",
+                if app.plot_lines { "ON" } else { "OFF" }
+            );
+        }
+        Action::TogglePolygons => {
+            app.plot_polygons = !app.plot_polygons;
+            app.notification = format!(
+                "Polygons visibility: {}",
+                if app.plot_polygons { "ON" } else { "OFF" }
+            );
+        }
+        Action::ToggleScaleBar => {
+            app.scale_bar = !app.scale_bar;
+            app.notification = format!("Scale bar: {}", if app.scale_bar { "ON" } else { "OFF" });
+        }
+        Action::ToggleNorthArrow => {
+            app.north_arrow = !app.north_arrow;
+            app.notification = format!(
+                "North arrow: {}",
+                if app.north_arrow { "ON" } else { "OFF" }
+            );
+        }
+        Action::ToggleBasemap => {
+            app.basemap_enabled = !app.basemap_enabled;
+            app.notification = format!(
+                "Basemap: {}",
+                if app.basemap_enabled { "ON" } else { "OFF" }
+            );
+        }
+        Action::ToggleThumbnail => {
+            app.thumbnails = !app.thumbnails;
+            app.notification = if app.thumbnails {
+                format!(
+                    "Thumbnail: ON ({} px, saved next to the plot)",
+                    app.thumbnail_size
+                )
+            } else {
+                String::from("Thumbnail: OFF")
+            };
+        }
+        Action::FigureMode => {
+            app.figure_mode = match app.figure_mode {
+                None => Some(FigureBackground::White),
+                Some(FigureBackground::White) => Some(FigureBackground::Transparent),
+                Some(FigureBackground::Transparent) => None,
+            };
+            app.notification = format!(
+                "Figure mode: {}",
+                app.figure_mode.map_or("OFF", FigureBackground::name)
+            );
+        }
+        Action::Projection => {
+            app.projection = app.projection.next();
+            app.notification = format!("Projection: {}", app.projection.name());
+        }
+        Action::ToggleAspect => {
+            app.keep_aspect = !app.keep_aspect;
+            app.notification = format!(
+                "True aspect ratio: {}",
+                if app.keep_aspect { "ON" } else { "OFF" }
+            );
+        }
+        Action::Inset => {
+            app.inset = Inset::next(app.inset);
+            app.notification = format!("Inset map: {}", Inset::label(app.inset));
+        }
+        Action::InsetSize => match app.inset {
+            Some(inset) => {
+                app.inset = Some(inset.larger());
+                app.notification = format!("Inset map: {}", Inset::label(app.inset));
+            }
+            None => app.notification = String::from("No inset map (Alt+I to add one)."),
+        },
+        Action::ToggleClusters => {
+            app.cluster_distance = match app.cluster_distance {
+                Some(_) => None,
+                None => Some(cluster::DEFAULT_CLUSTER_DISTANCE),
+            };
+            app.notification = format!(
+                "Point clustering: {}",
+                if app.cluster_distance.is_some() {
+                    "ON"
+                } else {
+                    "OFF"
+                }
+            );
+        }
+        Action::SimplifyLayer => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.tolerance_input.set(
+                    app.layers[idx]
+                        .simplify_tolerance
+                        .map(|t| t.to_string())
+                        .unwrap_or_default(),
+                );
+                app.current_mode = AppMode::EditingTolerance;
+                app.notification = format!(
+                    "Simplification tolerance for {} (0 = full detail, empty = global {}).",
+                    app.layers[idx].file,
+                    app.simplify_label()
+                );
+            }
+        }
+        Action::SetCrs => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.crs_input
+                    .set(app.layers[idx].crs.clone().unwrap_or_default());
+                app.current_mode = AppMode::EditingCrs;
+                app.notification = format!(
+                    "CRS of {} (e.g. EPSG:3857, 32633 or UTM33N; empty = as declared by the file).",
+                    app.layers[idx].file
+                );
+            }
+        }
+        Action::FilterLayer => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.filter_input.set(
+                    app.layers[idx]
+                        .filter
+                        .as_ref()
+                        .map(FeatureFilter::to_string)
+                        .unwrap_or_default(),
+                );
+                app.current_mode = AppMode::EditingFilter;
+                app.notification = format!(
+                    "Filter {}, e.g. properties.pop > 100000 && properties.country == \"IN\" (empty = all features).",
+                    app.layers[idx].file
+                );
+            }
+        }
+        Action::SelectFeatures => {
+            if let Some(idx) = app.highlighted_file_index() {
+                if app.layers[idx].features.is_none() {
+                    load_file_into_app(app, idx);
+                }
+                if app.layers[idx]
+                    .features
+                    .as_ref()
+                    .is_some_and(|f| !f.is_empty())
+                {
+                    app.selected_feature = 0;
+                    app.current_mode = AppMode::SelectingFeatures;
+                    app.notification = format!("Features of {} to plot.", app.layers[idx].file);
+                } else {
+                    app.notification = format!("{} has no features.", app.layers[idx].file);
+                }
+            }
+        }
+        Action::AttributeTable => {
+            if let Some(idx) = app.highlighted_file_index() {
+                if app.layers[idx].features.is_none() {
+                    load_file_into_app(app, idx);
+                }
+                match &app.layers[idx].features {
+                    Some(features) => {
+                        app.attribute_table = Some(AttributeTable::new(idx, features));
+                        app.current_screen = CurrentScreen::AttributeTable;
+                        app.current_mode = AppMode::AttributeTable;
+                        app.notification = String::from(
+                            "Attribute table: S sorts by the highlighted column, Esc to go back.",
+                        );
+                    }
+                    None => app.notification = format!("Could not read {}.", app.layers[idx].file),
+                }
+            }
+        }
+        Action::Sessions => {
+            app.session_names = session::list_sessions();
+            app.selected_session_index = 0;
+            app.current_mode = AppMode::Sessions;
+            app.notification =
+                String::from("Sessions: Enter to restore, N to save current, D to delete.");
+        }
+        Action::Styling => {
+            app.current_screen = CurrentScreen::Styling;
+            app.current_mode = AppMode::Styling;
+            app.notification =
+                String::from("Styling: A to add a rule, D to delete, Esc to go back.");
+        }
+        Action::Follow => toggle_follow(app),
+        Action::Gps => toggle_gps(app),
+        Action::RecordTrack => toggle_track_recording(app),
+        Action::AddMarker => {
+            app.marker_input.clear();
+            app.current_mode = AppMode::EditingMarker;
+            app.notification = String::from(
+                "Enter \"lat, lon\" or \"lon lat\", optionally followed by \"; label\".",
+            );
+        }
+        Action::ClearMarkers => {
+            app.notification = format!("Removed {} markers.", app.markers.len());
+            app.markers.clear();
+            app.jump_point = None;
+        }
+        Action::JumpTo => {
+            // Coordinates copied elsewhere, e.g. from a web map, are filled in
+            let pasted = clipboard::paste_text().filter(|text| MapMarker::parse(text).is_ok());
+            app.jump_input.set(pasted.clone().unwrap_or_default());
+            app.current_mode = AppMode::EditingJump;
+            app.notification = if pasted.is_some() {
+                String::from("Jump to the coordinates pasted from the clipboard? (Enter to jump)")
+            } else {
+                String::from("Enter \"lat, lon\" or \"lon lat\" to center the preview on.")
+            };
+        }
+        Action::ExportExtent => {
+            let filename = format!(
+                "extent_{}.geojson",
+                chrono::Local::now().format("%Y%m%d_%H%M%S")
+            );
+            let path = PathBuf::from(OUTPUT_DIR).join(filename);
+            let [min_lon, min_lat, max_lon, max_lat] = app.preview_bounds;
+            app.notification = match plot::save_extent(&path, app.preview_bounds) {
+                Ok(()) => format!(
+                    "Saved extent {:.4}, {:.4} to {:.4}, {:.4} as {}",
+                    min_lon,
+                    min_lat,
+                    max_lon,
+                    max_lat,
+                    path.display()
+                ),
+                Err(e) => format!("Failed to save extent: {}", e),
+            };
+        }
+        Action::NavigatePreview => {
+            app.preview_cursor = None;
+            app.move_preview_cursor(0, 0);
+            app.current_mode = AppMode::NavigatingPreview;
+            app.notification = String::from(PREVIEW_NAVIGATION_HINT);
+        }
+        Action::GoToPlace => {
+            app.place_input.clear();
+            app.current_mode = AppMode::EditingPlace;
+            app.notification =
+                String::from("Type the name of a place to go to, e.g. Pune or Lake Geneva.");
+        }
+        Action::EditExtent => {
+            app.extent_input.set(if app.extent_locked {
+                let [min_lon, min_lat, max_lon, max_lat] =
+                    app.preview_view.unwrap_or(app.preview_bounds);
+                format!("{}, {}, {}, {}", min_lon, min_lat, max_lon, max_lat)
+            } else {
+                String::new()
+            });
+            app.current_mode = AppMode::EditingExtent;
+            app.notification = String::from(
+                "Enter \"min_lon, min_lat, max_lon, max_lat\"; empty for the automatic extent.",
+            );
+        }
+        Action::TimeRange => {
+            app.time_input.set(
+                app.time_range
+                    .as_ref()
+                    .map(TimeRange::to_string)
+                    .unwrap_or_default(),
+            );
+            app.current_mode = AppMode::EditingTime;
+            let data = match app.time_extent() {
+                Some((first, last)) => format!(
+                    " Selected files run from {} to {}.",
+                    temporal::format_time(first),
+                    temporal::format_time(last)
+                ),
+                None => String::new(),
+            };
+            app.notification = format!(
+                "Time range, e.g. 2020-01..2020-06, 2021 or 2020-06-30..; empty = all times.{}",
+                data
+            );
+        }
+        Action::TimeEarlier | Action::TimeLater => {
+            let steps = if action == Action::TimeLater { 1 } else { -1 };
+            match &app.time_range {
+                Some(range) => match range.shifted(steps) {
+                    Some(shifted) => {
+                        app.notification = format!("Time range: {}", shifted);
+                        app.time_range = Some(shifted);
+                    }
+                    None => {
+                        app.notification =
+                            String::from("Only a time range with both ends can be moved.")
+                    }
+                },
+                None => app.notification = String::from("No time range set (Alt+T to set one)."),
+            }
+        }
+        Action::ClipMask => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.cycle_clip_mask(idx);
+                app.notification = match app.clip_mask_index {
+                    Some(_) => format!(
+                        "Clipping layers to {}{}.",
+                        app.layers[idx].file,
+                        if app.clip_mask_shade {
+                            ", outside shaded"
+                        } else {
+                            ""
+                        }
+                    ),
+                    None => String::from("Clip mask removed."),
+                };
+            }
+        }
+        Action::FocusLayer => {
+            if let Some(idx) = app.highlighted_file_index() {
+                if app.focus_index == Some(idx) {
+                    app.focus_index = None;
+                    app.notification = String::from("Focus removed, all layers in color.");
+                } else {
+                    app.focus_index = Some(idx);
+                    app.notification = format!(
+                        "Focusing {}, other layers drawn grey.",
+                        app.layers[idx].file
+                    );
+                }
+            }
+        }
+        Action::LayerProperties => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.layer_property = LayerProperty::LineWidth;
+                app.current_mode = AppMode::LayerProperties;
+                app.notification = format!("Layer properties of {}.", app.layers[idx].file);
+            }
+        }
+        Action::Validate => {
+            if let Some(idx) = app.highlighted_file_index() {
+                let path = PathBuf::from(GEOJSON_DIR).join(&app.layers[idx].file);
+                let report = validate::validate_file(&path);
+                app.notification = report.summary();
+                app.validation_report = Some(report);
+                app.validation_scroll = 0;
+                app.current_screen = CurrentScreen::Validation;
+                app.current_mode = AppMode::Validation;
+            }
+        }
+        Action::FindDuplicates => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.duplicate_input.clear();
+                app.current_mode = AppMode::EditingDuplicates;
+                app.notification = format!(
+                    "Find duplicates in {} within (degrees, or 5m / 1km; Enter for 1m, 0 for exact only):",
+                    app.layers[idx].file
+                );
+            }
+        }
+        Action::CheckTopology => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.current_mode = AppMode::ChoosingTopology;
+                app.notification = format!(
+                    "Check {}: L for overlaps and gaps within it, S against the other selected files, Esc cancel",
+                    app.layers[idx].file
+                );
+            }
+        }
+        Action::Buffer => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.buffer_input.clear();
+                app.current_mode = AppMode::EditingBuffer;
+                app.notification = format!(
+                    "Buffer {} by (degrees, or 250m / 10km; negative shrinks polygons).",
+                    app.layers[idx].file
+                );
+            }
+        }
+        Action::Dissolve => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.dissolve_input.clear();
+                app.current_mode = AppMode::EditingDissolve;
+                app.notification = dissolve_prompt(app, idx);
+            }
+        }
+        Action::JoinCsv => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.join_input.clear();
+                app.current_mode = AppMode::EditingJoin;
+                app.notification = format!(
+                    "Join to {}: <file.csv> <property>[=<column>], e.g. stats.csv ISO_A3=iso",
+                    app.layers[idx].file
+                );
+            }
+        }
+        Action::Minify => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.minify_input.clear();
+                app.current_mode = AppMode::EditingMinify;
+                app.notification = format!(
+                    "Round coordinates of {} to how many decimals? (Enter for {}, about 10 cm)",
+                    app.layers[idx].file,
+                    minify::DEFAULT_DECIMALS
+                );
+            }
+        }
+        Action::SnapToGrid => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.snap_input.clear();
+                app.current_mode = AppMode::EditingSnap;
+                app.notification = format!(
+                    "Snap coordinates of {} to a grid of (Enter for {}, about 1 m in degrees):",
+                    app.layers[idx].file,
+                    snap::DEFAULT_GRID
+                );
+            }
+        }
+        Action::Points => {
+            if let Some(idx) = app.highlighted_file_index() {
+                app.current_mode = AppMode::ChoosingPoints;
+                app.notification = format!(
+                    "Points of {}: C centroids, P poles of inaccessibility (inside, for labels), Esc cancel",
+                    app.layers[idx].file
+                );
+            }
+        }
+        action @ (Action::DividerLeft | Action::DividerRight) => {
+            let step = if action == Action::DividerLeft {
+                -DIVIDER_STEP
+            } else {
+                DIVIDER_STEP
+            };
+            app.set_left_pane_width(app.left_pane_width_percentage as i32 + step);
+            app.notification = format!("File list width: {}%", app.left_pane_width_percentage);
+        }
+        Action::PanelTab => {
+            app.panel_tab = app.panel_tab.next();
+            app.info_scroll = 0;
+        }
+        Action::InfoTab => {
+            // Brings the Info tab back first if another tab is shown
+            if app.panel_tab == PanelTab::Info {
+                app.info_tab = app.info_tab.next();
+            }
+            app.panel_tab = PanelTab::Info;
+            app.info_scroll = 0;
+        }
+        Action::InfoScrollDown => app.info_scroll = app.info_scroll.saturating_add(1),
+        Action::InfoScrollUp => app.info_scroll = app.info_scroll.saturating_sub(1),
+        Action::Quit => {
+            requests.quit = true;
+            app.notification = String::from("Exiting...");
+        }
+        Action::Logs => {
+            app.log_scroll = 0;
+            app.current_screen = CurrentScreen::Logs;
+            app.current_mode = AppMode::Logs;
+        }
+        Action::Help => {
+            app.current_screen = CurrentScreen::Help;
+            app.notification = String::from("Showing Help screen.");
+        }
+    }
+}
+// Starts or stops following the highlighted GeoJSONL file
+fn toggle_follow(app: &mut App) {
+    let Some(index) = app.highlighted_file_index() else {
+        app.notification = String::from("No file to follow in current view.");
+        return;
+    };
+    if let Some(follow) = app.follow.take() {
+        app.notification = format!("Stopped following {}.", app.layers[follow.file_index].file);
+        app.release_unused_features();
+        if follow.file_index == index {
+            return;
+        }
+    }
+    let full_filepath = PathBuf::from(GEOJSON_DIR).join(&app.layers[index].file);
+    if !loader::is_line_delimited(&full_filepath) {
+        app.notification = String::from(
+            "Follow mode needs a line-delimited file (.geojsonl, .geojsons, .ndjson).",
+        );
+        return;
+    }
+
+    // The first poll re-reads the whole file, so start from an empty layer
+    let mut info = GeoJsonInfo::default();
+    loader::read_file_metadata(&full_filepath, &mut info);
+    app.layers[index].info = Some(info);
+    app.layers[index].features = Some(Vec::new());
+    app.follow = Some(FollowState::new(index));
+    poll_follow(app);
+    app.notification = format!(
+        "Following {} ({} features). Press F again to stop.",
+        app.layers[index].file,
+        app.layers[index].features.as_ref().map_or(0, Vec::len)
+    );
+}
+
+// Ingests features appended to the followed file since the last tick
+fn poll_follow(app: &mut App) {
+    let Some(follow) = app.follow.as_mut() else {
+        return;
+    };
+    let index = follow.file_index;
+    let full_filepath = PathBuf::from(GEOJSON_DIR).join(&app.layers[index].file);
+    match follow.poll(&full_filepath) {
+        Ok(Some(update)) => {
+            if update.features.is_empty() && update.skipped_lines == 0 {
+                return;
+            }
+            let info = app.layers[index]
+                .info
+                .get_or_insert_with(GeoJsonInfo::default);
+            loader::read_file_metadata(&full_filepath, info);
+            info.record_features(&update.features);
+            let total = info.feature_count;
+            let added = update.features.len();
+            app.layers[index]
+                .features
+                .get_or_insert_with(Vec::new)
+                .extend(update.features);
+            app.refresh_filter_count(index);
+            app.notification = if update.skipped_lines > 0 {
+                format!(
+                    "Following {}: +{} features ({} total), {} invalid lines skipped",
+                    app.layers[index].file, added, total, update.skipped_lines
+                )
+            } else {
+                format!(
+                    "Following {}: +{} features ({} total)",
+                    app.layers[index].file, added, total
+                )
+            };
+        }
+        Ok(None) => {
+            let mut info = GeoJsonInfo::default();
+            loader::read_file_metadata(&full_filepath, &mut info);
+            app.layers[index].info = Some(info);
+            app.layers[index].features = Some(Vec::new());
+            app.notification = format!("{} was truncated; reloading it.", app.layers[index].file);
+        }
+        Err(e) => {
+            app.notification = format!("Follow error: {}", e);
+        }
+    }
+}
+
+// Lists files added to the data directory, forgets removed ones and reloads
+// the ones that changed (except the followed file, which is read as it grows)
+fn poll_data_directory(app: &mut App) {
+    let Some(watcher) = app.data_watcher.as_mut() else {
+        return;
+    };
+    let changes = match watcher.poll() {
+        Ok(Some(changes)) => changes,
+        Ok(None) => return,
+        Err(e) => {
+            app.notification = format!("Could not watch {}: {}", GEOJSON_DIR, e);
+            return;
+        }
+    };
+
+    let mut removed = 0;
+    for name in &changes.removed {
+        if let Some(index) = app.file_index(name) {
+            app.remove_geojson_file(index);
+            removed += 1;
+        }
+    }
+    let mut added = 0;
+    for name in changes.added {
+        // Files the app wrote itself are listed already
+        if app.file_index(&name).is_none() {
+            app.add_geojson_file(name);
+            added += 1;
+        }
+    }
+    let followed = app.follow.as_ref().map(|f| f.file_index);
+    let mut modified = 0;
+    for name in &changes.modified {
+        if let Some(index) = app.file_index(name)
+            && Some(index) != followed
+        {
+            forget_file_contents(app, index);
+            modified += 1;
+        }
+    }
+    if added + removed + modified == 0 {
+        return;
+    }
+    app.history.invalidate(); // Not undone with the next change
+    load_selected_files(app);
+    refilter_files(app);
+    app.notification = format!(
+        "Data directory changed: {} added, {} removed, {} modified.",
+        added, removed, modified
+    );
+}
+
+// Drops what was read from a file that changed on disk, so it is read again
+fn forget_file_contents(app: &mut App, index: usize) {
+    app.layers[index].forget_contents();
+    if app
+        .json_preview
+        .as_ref()
+        .is_some_and(|(file, _)| *file == app.layers[index].file)
+    {
+        app.json_preview = None;
+    }
+}
+
+// Starts fetching `url` into the remote cache; the file is listed once it arrives
+fn start_download(app: &mut App, url: &str) -> String {
+    if let Some(download) = app.downloads.iter().find(|d| d.url == url.trim()) {
+        return download.status();
+    }
+    let download = Download::start(url);
+    let status = download.status();
+    app.downloads.push(download);
+    status
+}
+
+// Lists finished downloads, or reloads them if they were fetched again, and
+// keeps the spinner of the ones still running turning
+fn poll_downloads(app: &mut App) {
+    if app.downloads.is_empty() {
+        return;
+    }
+    let mut index = 0;
+    while index < app.downloads.len() {
+        let Some(result) = app.downloads[index].poll() else {
+            index += 1;
+            continue;
+        };
+        let download = app.downloads.remove(index);
+        app.history.invalidate(); // The listed file is not undone with the next change
+        let outcome = result.and_then(|()| {
+            let path = fs::canonicalize(&download.path)
+                .map_err(|e| format!("{}: {}", download.path.display(), e))?;
+            let name = path.to_string_lossy().to_string();
+            match app.file_index(&name) {
+                Some(listed) => {
+                    forget_file_contents(app, listed);
+                    load_selected_files(app);
+                    Ok(format!("Fetched {} again.", download.url))
+                }
+                None => open_external_file(app, &name)
+                    .map(|_| format!("Downloaded {} to {}.", download.url, name)),
+            }
+        });
+        // Each outcome is logged, as the status line moves on to the next download
+        let (level, message) = match outcome {
+            Ok(message) => (LogLevel::Info, message),
+            Err(e) => (
+                LogLevel::Error,
+                format!("Download of {} failed: {}", download.url, e),
+            ),
+        };
+        app.log(level, message.clone());
+        app.finished_downloads.push(message);
+    }
+    let finished = app.finished_downloads.len();
+    if let Some(download) = app.downloads.last() {
+        app.notification = if finished == 0 {
+            download.status()
+        } else {
+            format!(
+                "{} ({} of {} done)",
+                download.status(),
+                finished,
+                finished + app.downloads.len()
+            )
+        };
+    } else if finished > 0 {
+        let messages = std::mem::take(&mut app.finished_downloads);
+        app.notification = if finished == 1 {
+            messages.concat()
+        } else {
+            format!("{} downloads finished: {}", finished, messages.join(" "))
+        };
+    }
+}
+
+// Shows the feature types of the WFS service once its capabilities arrived
+fn poll_wfs(app: &mut App) {
+    let Some(request) = &app.wfs_request else {
+        return;
+    };
+    let Some(result) = request.poll() else {
+        return;
+    };
+    let service = request.service.clone();
+    app.wfs_request = None;
+    match result {
+        Ok(capabilities) => {
+            app.notification = format!(
+                "{} offers {} feature types.",
+                service,
+                capabilities.feature_types.len()
+            );
+            app.wfs_service = Some((service, capabilities));
+            app.selected_wfs_type = 0;
+        }
+        Err(e) => {
+            app.notification = format!("Could not list the feature types of {}: {}", service, e);
+            app.wfs_service = None;
+        }
+    }
+}
+
+// Offers the selected layers, as currently styled, to the preview server
+fn update_preview_server(app: &mut App) {
+    let Some(server) = &app.preview_server else {
+        return;
+    };
+    let errors = server.take_errors();
+    let layers = app
+        .layers
+        .iter()
+        .filter(|layer| layer.selected)
+        .map(|layer| ServedLayer {
+            name: layer.file.clone(),
+            path: PathBuf::from(GEOJSON_DIR).join(&layer.file),
+            color: layer.color.unwrap_or(RGBColor(0, 0, 0)),
+            crs: layer.crs.clone(),
+            filter: layer.filter.clone(),
+            checked: layer.feature_selection.clone(),
+            time_range: app.time_range.clone(),
+        })
+        .collect();
+    server.set_layers(&app.title_input.text, layers);
+    for error in errors {
+        app.log(LogLevel::Error, format!("Preview server: {}", error));
+    }
+}
+
+// Downloads the highlighted WFS feature type as a remote layer, within the
+// preview extent unless `all` is set or the preview shows the whole world
+fn fetch_wfs_type(app: &mut App, all: bool) {
+    let Some((service, capabilities)) = &app.wfs_service else {
+        return;
+    };
+    let Some(feature_type) = capabilities.feature_types.get(app.selected_wfs_type) else {
+        return;
+    };
+    let bbox =
+        Some(app.preview_bounds).filter(|bbox| !all && *bbox != [-180.0, -90.0, 180.0, 90.0]);
+    let url = wfs::get_feature_url(
+        service,
+        &feature_type.name,
+        &capabilities.output_format,
+        bbox,
+    );
+    app.notification = start_download(app, &url);
+    app.current_mode = AppMode::Navigation;
+}
+
+// Connects to or disconnects from the configured GPS source
+fn toggle_gps(app: &mut App) {
+    if let Some(reader) = app.gps.take() {
+        app.gps_position = None;
+        app.notification = format!("Disconnected from {}.", reader.source.describe());
+        return;
+    }
+    match GpsReader::start(app.gps_source.clone()) {
+        Ok(reader) => {
+            app.notification = format!(
+                "Connected to {}. Waiting for a fix...",
+                reader.source.describe()
+            );
+            app.gps = Some(reader);
+        }
+        Err(e) => {
+            app.notification = format!(
+                "Failed to open GPS source {}: {}",
+                app.gps_source.describe(),
+                e
+            );
+        }
+    }
+}
+
+// Starts recording the GPS track, or stops and saves it to the output directory
+fn toggle_track_recording(app: &mut App) {
+    if !app.gps_recording {
+        if app.gps.is_none() {
+            app.notification = String::from("Connect the GPS first (G).");
+            return;
+        }
+        app.gps_track = app.gps_position.into_iter().collect();
+        app.gps_recording = true;
+        app.notification = String::from("Recording GPS track. Press T again to stop and save.");
+        return;
+    }
+
+    app.gps_recording = false;
+    if app.gps_track.len() < 2 {
+        app.gps_track.clear();
+        app.notification = String::from("Recording stopped; not enough fixes to save a track.");
+        return;
+    }
+    let filename = format!(
+        "gps_track_{}.geojson",
+        chrono::Local::now().format("%Y%m%d_%H%M%S")
+    );
+    let path = PathBuf::from(OUTPUT_DIR).join(filename);
+    app.notification = match gps::save_track(&path, &app.gps_track) {
+        Ok(()) => format!(
+            "Saved GPS track ({} fixes) to {}",
+            app.gps_track.len(),
+            path.display()
+        ),
+        Err(e) => format!("Failed to save GPS track: {}", e),
+    };
+    app.gps_track.clear();
+}
+
+// Moves the GPS marker to the latest fix and extends the recorded track
+fn poll_gps(app: &mut App) {
+    let Some(reader) = app.gps.as_ref() else {
+        return;
+    };
+    let update = reader.poll();
+    if app.gps_recording {
+        app.gps_track.extend(&update.fixes);
+    }
+    if let Some(fix) = update.fixes.last() {
+        app.gps_position = Some(*fix);
+    }
+    if let Some(e) = update.error {
+        app.notification = format!("GPS error: {}", e);
+        app.gps = None;
+        app.gps_position = None;
+    }
+}
+
+// Shows the coordinates of a point clicked in the preview and looks up its place name
+fn identify_point(app: &mut App, lon: f64, lat: f64) {
+    app.identified_point = Some((lon, lat));
+    app.notification = format!("Point: {:.5}, {:.5}", lat, lon);
+    if let Some(geocoder) = app.geocoder.as_mut() {
+        geocoder.request(lon, lat);
+        poll_geocoder(app);
+        if app.geocoder.as_ref().is_some_and(|g| g.is_pending()) {
+            app.notification.push_str(" (looking up place...)");
+        }
+    }
+}
+
+// Lists the features of the loaded layers under the preview crosshair: the
+// polygons containing it and the lines and points within half a cell of it
+fn identify_features(app: &mut App) {
+    let Some((lon, lat)) = app.preview_cursor_position() else {
+        return;
+    };
+    let [min_lon, min_lat, max_lon, max_lat] = app.preview_bounds;
+    let area = app.preview_area;
+    let tolerance = ((max_lon - min_lon) / area.width.saturating_sub(2).max(1) as f64)
+        .max((max_lat - min_lat) / area.height.saturating_sub(2).max(1) as f64)
+        / 2.0;
+    app.update_feature_indexes();
+    let mut found = Vec::new();
+    for (i, layer) in app.layers.iter().enumerate() {
+        let (Some(features), Some(index)) = (&layer.features, &layer.feature_index) else {
+            continue;
+        };
+        for j in index.near(lon, lat, tolerance) {
+            let feature = &features[j];
+            let hit = feature
+                .geometry
+                .as_ref()
+                .is_some_and(|g| spatial::hits(&g.value, [lon, lat], tolerance));
+            if hit && app.layer_accepts(i, j, feature) {
+                found.push((layer.file.clone(), feature.clone()));
+            }
+        }
+    }
+    if found.is_empty() {
+        app.notification = format!("No features at {:.5}, {:.5}.", lat, lon);
+        return;
+    }
+    app.notification = format!("{} features at {:.5}, {:.5}.", found.len(), lat, lon);
+    app.identified_features = found;
+    app.identify_scroll = 0;
+    app.current_mode = AppMode::Identify;
+}
+
+// Adds the position under the preview crosshair to the measured path
+fn add_measure_point(app: &mut App) {
+    if let Some(position) = app.preview_cursor_position() {
+        app.measure_points.push(position);
+        app.notification = measure_status(app);
+    }
+}
+
+// Length of the measured path and, once closed, the area it encloses
+fn measure_status(app: &App) -> String {
+    let points = &app.measure_points;
+    if points.is_empty() {
+        return String::from(MEASURE_HINT);
+    }
+    let closed = app.measure_closed && points.len() > 2;
+    let length = measure::format_length(measure::length_km(points, closed));
+    let count = format!(
+        "{} point{}",
+        points.len(),
+        if points.len() == 1 { "" } else { "s" }
+    );
+    if closed {
+        format!(
+            "Measure: {}, perimeter {}, area {}",
+            count,
+            length,
+            measure::format_area(measure::area_km2(points))
+        )
+    } else {
+        format!("Measure: {}, length {}", count, length)
+    }
+}
+
+// Appends the place name to the status line once the lookup finished
+fn poll_geocoder(app: &mut App) {
+    let Some(result) = app.geocoder.as_mut().and_then(|g| g.poll()) else {
+        return;
+    };
+    let Some((lon, lat)) = app.identified_point else {
+        return;
+    };
+    app.notification = match result {
+        Ok(place) => format!("Point: {:.5}, {:.5}: {}", lat, lon, place),
+        Err(e) => format!("Point: {:.5}, {:.5} (place lookup failed: {})", lat, lon, e),
+    };
+}
+
+// Zooms the preview to `place` and locks the plot extent to it
+fn go_to_place(app: &mut App, place: &FoundPlace) {
+    app.preview_view = Some(place.bbox);
+    app.extent_locked = true;
+    app.notification = format!("Extent locked to {}", place.name);
+}
+
+// Goes to the place looked up by name once the lookup finished
+fn poll_place_search(app: &mut App) {
+    match app.place_search.poll() {
+        Some(Ok(place)) => {
+            app.history.invalidate(); // The locked extent is not undone with the next change
+            go_to_place(app, &place);
+        }
+        Some(Err(e)) => app.notification = format!("Place lookup failed: {}", e),
+        None => {}
+    }
+}
+
+// Applies the user configuration, reporting problems in the notification
+fn apply_config(app: &mut App) {
+    match config::load_config() {
+        Ok(config) => {
+            let mut keymap = KeyMap::default();
+            if let Some(keys) = config.section("keys") {
+                let errors = keymap.apply_config(keys);
+                if !errors.is_empty() {
+                    app.notification = format!("Config: {}", errors.join("; "));
+                }
+            }
+            app.set_keymap(keymap);
+            match config.get("plot", "simplify_tolerance") {
+                Some(value) if value.as_str() == Some("auto") => app.auto_simplify = true,
+                Some(value) => match value.as_f64() {
+                    Some(tolerance) => app.simplify_tolerance = tolerance.max(0.0),
+                    None => {
+                        app.notification = String::from(
+                            "Config: [plot] simplify_tolerance must be a number or \"auto\"",
+                        )
+                    }
+                },
+                None => {}
+            }
+            match config.get("plot", "warning_banner").map(|v| v.as_bool()) {
+                Some(Some(enabled)) => app.warning_banner = enabled,
+                Some(None) => {
+                    app.notification =
+                        String::from("Config: [plot] warning_banner must be true or false")
+                }
+                None => {}
+            }
+            match config.get("plot", "thumbnail_size").map(|v| v.as_f64()) {
+                Some(Some(size)) if size >= 1.0 => app.thumbnail_size = size as u32,
+                Some(_) => {
+                    app.notification =
+                        String::from("Config: [plot] thumbnail_size must be a positive number")
+                }
+                None => {}
+            }
+            if let Some(address) = config.get("serve", "address").and_then(|v| v.as_str()) {
+                app.serve_address = address.to_string();
+            }
+            if let Some(source) = config.get("gps", "source").and_then(|v| v.as_str()) {
+                app.gps_source = GpsSource::parse(source);
+            }
+            if let Some(tags) = config.get("osm", "tags").and_then(|v| v.as_list()) {
+                match TagFilter::parse(&tags.join(",")) {
+                    Ok(filter) => osm::set_tag_filter(filter),
+                    Err(e) => app.notification = format!("Config: [osm] {}", e),
+                }
+            }
+            match Basemap::from_config(&config) {
+                Ok(basemap) => app.basemap = basemap,
+                Err(e) => app.notification = format!("Config: {}", e),
+            }
+            match ReverseGeocoder::from_config(&config) {
+                Ok(geocoder) => app.geocoder = geocoder,
+                Err(e) => app.notification = format!("Config: {}", e),
+            }
+            app.place_search = PlaceSearch::from_config(&config);
+        }
+        Err(e) => app.notification = e,
+    }
+}
+
+// Brings the file list and the panels up to date before drawing
+fn prepare_frame(app: &mut App) {
+    // Re-filter files if search query changed or just entered/exited search mode
+    if app.current_mode == AppMode::Searching
+        || app.search_query_input.text != app.previous_search_query_buffer
+    {
+        refilter_files(app);
+    }
+
+    // Adjust scroll_offset to keep selected_file_index in view
+    let current_list_len = app.filtered_geojson_indices.len();
+    let estimated_max_visible_items = app.visible_file_rows(); // From the last draw
+    if app.selected_file_index >= app.scroll_offset + estimated_max_visible_items
+        && estimated_max_visible_items > 0
+    {
+        app.scroll_offset = app.selected_file_index - estimated_max_visible_items + 1;
+    }
+    if app.selected_file_index < app.scroll_offset {
+        app.scroll_offset = app.selected_file_index;
+    }
+    if current_list_len <= estimated_max_visible_items {
+        app.scroll_offset = 0; // All files fit, no scrolling needed
+    } else if app.scroll_offset > current_list_len.saturating_sub(estimated_max_visible_items) {
+        app.scroll_offset = current_list_len.saturating_sub(estimated_max_visible_items);
+    }
+
+    // --- Cache GeoJSON Info for selected file ---
+    if let Some(current_original_file_index) = app.highlighted_file_index()
+        && (current_original_file_index != app.previous_selected_file_index_in_filtered
+            || app.layers[current_original_file_index].info.is_none())
+    {
+        // Selected and followed files keep their features loaded; large
+        // files parsed by an earlier run, or declaring their extent, are
+        // summarised until their features are needed
+        if app.layers[current_original_file_index].features.is_none() {
+            let path =
+                PathBuf::from(GEOJSON_DIR).join(&app.layers[current_original_file_index].file);
+            let header = (app.layers[current_original_file_index].crs.is_none()
+                && fs::metadata(&path).is_ok_and(|m| m.len() >= loader::HEADER_ONLY_BYTES))
+            .then(|| {
+                app.info_cache
+                    .get(&path)
+                    .or_else(|| loader::read_header(&path))
+            })
+            .flatten();
+            match header {
+                Some(info) => {
+                    app.layers[current_original_file_index].info = Some(info);
+                }
+                None => load_file_into_app(app, current_original_file_index),
+            }
+        }
+        app.release_unused_features();
+        if current_original_file_index != app.previous_selected_file_index_in_filtered {
+            app.info_scroll = 0;
+        }
+        app.previous_selected_file_index_in_filtered = current_original_file_index;
+    }
+
+    // The JSON tab reads the start of the highlighted file once
+    if app.panel_tab == PanelTab::Json
+        && let Some(index) = app.highlighted_file_index()
+        && app
+            .json_preview
+            .as_ref()
+            .is_none_or(|(file, _)| *file != app.layers[index].file)
+    {
+        let file = app.layers[index].file.clone();
+        let preview =
+            JsonPreview::read(&PathBuf::from(GEOJSON_DIR).join(&file)).map_err(|e| e.to_string());
+        app.json_preview = Some((file, preview));
+    }
+}
+
+// A click on a button acts like pressing its key
+fn click_as_key(app: &App, event: Event) -> Event {
+    match event {
+        Event::Mouse(mouse_event)
+            if mouse_event.kind == MouseEventKind::Down(MouseButton::Left) =>
+        {
+            match app.regions.at(mouse_event.column, mouse_event.row) {
+                Some((_, Region::Key(code))) => {
+                    Event::Input(KeyEvent::new(code, KeyModifiers::NONE))
+                }
+                _ => Event::Mouse(mouse_event),
+            }
+        }
+        event => event,
+    }
+}
+
+// Handles a key press in the current mode
+fn handle_key(app: &mut App, key_event: KeyEvent, requests: &mut Requests) {
+    match app.current_mode {
+        AppMode::Navigation if key_event.code == KeyCode::Esc && app.plot_job.is_some() => {
+            if let Some(job) = &app.plot_job {
+                job.progress.cancel();
+            }
+            app.notification = String::from("Cancelling the plot...");
+        }
+        AppMode::Navigation => {
+            if let Some(action) = app.keymap.action_for(&key_event) {
+                run_action(app, action, requests);
+            }
+        }
+        AppMode::EditingFilename => handle_filename_key(app, key_event),
+        AppMode::EditingTitle => handle_title_key(app, key_event),
+        AppMode::Searching => handle_search_key(app, key_event, requests),
+        AppMode::Sessions => handle_sessions_key(app, key_event, requests),
+        AppMode::Wfs => handle_wfs_key(app, key_event),
+        AppMode::EditingWfsUrl => handle_wfs_url_key(app, key_event),
+        AppMode::EditingSessionName => handle_session_name_key(app, key_event),
+        AppMode::Validation => handle_validation_key(app, key_event),
+        AppMode::Duplicates => handle_duplicates_key(app, key_event),
+        AppMode::ChoosingTopology => {
+            let against_selected = match key_event.code {
+                KeyCode::Char('l') | KeyCode::Char('L') => Some(false),
+                KeyCode::Char('s') | KeyCode::Char('S') => Some(true),
+                _ => None,
+            };
+            match (against_selected, app.highlighted_file_index()) {
+                (Some(against_selected), Some(idx)) => check_topology(app, idx, against_selected),
+                (None, _) if key_event.code != KeyCode::Esc => {}
+                _ => {
+                    app.current_mode = AppMode::Navigation;
+                    app.notification = String::from("Topology check cancelled.");
+                }
+            }
+        }
+        AppMode::Topology => handle_topology_key(app, key_event),
+        AppMode::AttributeTable => handle_attribute_table_key(app, key_event),
+        AppMode::EditingCell => match key_event.code {
+            KeyCode::Enter => edit_table_cell(app),
+            KeyCode::Esc => {
+                app.current_mode = AppMode::AttributeTable;
+                app.notification = String::from("Edit cancelled.");
+            }
+            code => app.table_input.edit(code),
+        },
+        AppMode::AddingColumn => handle_adding_column_key(app, key_event),
+        AppMode::SavingTable => handle_saving_table_key(app, key_event),
+        AppMode::Logs => handle_logs_key(app, key_event),
+        AppMode::PlotSummary => handle_plot_summary_key(app, key_event, requests),
+        AppMode::Styling => handle_styling_key(app, key_event),
+        AppMode::EditingRule => handle_rule_key(app, key_event),
+        AppMode::ConfirmPlot => match key_event.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                requests.plot = true;
+                app.current_mode = AppMode::Navigation;
+                app.notification = String::from("Plotting selected files...");
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                app.current_mode = AppMode::Navigation;
+                app.notification = String::from("Plot cancelled.");
+            }
+            _ => {}
+        },
+        AppMode::NavigatingPreview | AppMode::Measuring => handle_preview_key(app, key_event),
+        AppMode::Identify => handle_identify_key(app, key_event),
+        AppMode::SelectingFeatures => handle_selecting_features_key(app, key_event),
+        AppMode::EditingTolerance => handle_tolerance_key(app, key_event),
+        AppMode::EditingCrs => handle_crs_key(app, key_event),
+        AppMode::EditingFilter => handle_filter_key(app, key_event),
+        AppMode::EditingBuffer => handle_buffer_key(app, key_event),
+        AppMode::EditingDuplicates => handle_duplicates_tolerance_key(app, key_event),
+        AppMode::EditingExport => handle_export_key(app, key_event),
+        AppMode::EditingOpenPath => handle_open_path_key(app, key_event),
+        AppMode::EditingSelection => handle_selection_key(app, key_event, requests),
+        AppMode::EditingMinify => handle_minify_key(app, key_event),
+        AppMode::EditingSnap => handle_snap_key(app, key_event),
+        AppMode::EditingDissolve => handle_dissolve_key(app, key_event),
+        AppMode::EditingJoin => handle_join_key(app, key_event),
+        AppMode::ChoosingPoints => handle_points_key(app, key_event),
+        AppMode::LayerProperties => handle_layer_properties_key(app, key_event),
+        AppMode::EditingExtent => handle_extent_key(app, key_event),
+        AppMode::EditingPlace => handle_place_key(app, key_event),
+        AppMode::EditingTime => handle_time_key(app, key_event),
+        AppMode::EditingJump => handle_jump_key(app, key_event),
+        AppMode::EditingMarker => handle_marker_key(app, key_event),
+    }
+}
+
+// Keys while typing the output filename, which must name an image
+fn handle_filename_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            if app.output_filename_input.text.is_empty() {
+                app.notification = String::from("Filename cannot be empty. Reverted.");
+                app.output_filename_input
+                    .text
+                    .clone_from(&app.previous_output_filename_buffer);
+            } else if !app.output_filename_input.text.ends_with(".png")
+                && !app.output_filename_input.text.ends_with(".jpg")
+                && !app.output_filename_input.text.ends_with(".jpeg")
+                && !app.output_filename_input.text.ends_with(".bmp")
+            {
+                app.notification =
+                    String::from("Filename must end with .png, .jpg, .jpeg, or .bmp. Reverted.");
+                app.output_filename_input
+                    .text
+                    .clone_from(&app.previous_output_filename_buffer);
+            } else {
+                app.notification =
+                    format!("Output filename set to: {}", app.output_filename_input.text);
+            }
+            app.current_mode = AppMode::Navigation;
+        }
+        KeyCode::Esc => {
+            // Escape key
+            app.output_filename_input
+                .text
+                .clone_from(&app.previous_output_filename_buffer);
+            app.notification = String::from("Filename editing cancelled. Reverted to previous.");
+            app.current_mode = AppMode::Navigation;
+        }
+        code => app.output_filename_input.edit(code),
+    }
+}
+
+// Keys while typing the title, subtitle or attribution of the output image
+fn handle_title_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            app.notification = if app.title_input.text.is_empty() {
+                String::from("Title removed from the plot.")
+            } else {
+                format!("Title set to: {}", app.title_input.text)
+            };
+            app.current_mode = AppMode::Navigation;
+        }
+        KeyCode::Tab => {
+            app.title_field = app.title_field.next();
+            app.title_field_input().move_to_end();
+        }
+        KeyCode::Esc => {
+            let [title, subtitle, attribution] = std::mem::take(&mut app.previous_title_buffers);
+            app.title_input.set(title);
+            app.subtitle_input.set(subtitle);
+            app.attribution_input.set(attribution);
+            app.notification = String::from("Title editing cancelled. Reverted to previous.");
+            app.current_mode = AppMode::Navigation;
+        }
+        code => app.title_field_input().edit(code),
+    }
+}
+
+// Keys while typing a search of the file names or property values
+fn handle_search_key(app: &mut App, key_event: KeyEvent, requests: &mut Requests) {
+    match key_event.code {
+        KeyCode::Enter => {
+            if app.search_query_input.text.is_empty() {
+                app.property_search = None;
+                refilter_files(app);
+                app.notification = String::from("Search cleared. Showing all files.");
+            } else if app.search_mode == SearchMode::Properties {
+                app.notification = format!(
+                    "Searching property values for '{}'...",
+                    app.search_query_input.text
+                );
+                requests.search = true;
+            } else {
+                app.notification = format!(
+                    "Searching for: '{}' ({} results)",
+                    app.search_query_input.text,
+                    app.filtered_geojson_indices.len()
+                );
+            }
+            app.current_mode = AppMode::Navigation;
+        }
+        KeyCode::Tab => {
+            app.search_mode = app.search_mode.next();
+            app.property_search = None;
+            app.notification = search_prompt(app.search_mode);
+        }
+        KeyCode::Esc => {
+            // Escape key
+            app.search_query_input
+                .text
+                .clone_from(&app.previous_search_query_buffer);
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Search cancelled. Showing all files.");
+        }
+        code => app.search_query_input.edit(code),
+    }
+}
+
+// Keys of the Sessions popup: restore, plot, save or delete a session
+fn handle_sessions_key(app: &mut App, key_event: KeyEvent, requests: &mut Requests) {
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j')
+            if app.selected_session_index + 1 < app.session_names.len() =>
+        {
+            app.selected_session_index += 1;
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            app.selected_session_index = app.selected_session_index.saturating_sub(1);
+        }
+        // P restores the session and plots it right away
+        KeyCode::Enter | KeyCode::Char('p') | KeyCode::Char('P') => {
+            if let Some(name) = app.session_names.get(app.selected_session_index).cloned() {
+                match Session::load(&name)
+                    .map_err(|e| e.to_string())
+                    .and_then(|session| session.apply(app))
+                {
+                    Ok(missing) => {
+                        load_selected_files(app);
+                        app.notification = if missing.is_empty() {
+                            format!("Restored session '{}'.", name)
+                        } else {
+                            format!(
+                                "Restored session '{}'. Missing files: {}",
+                                name,
+                                missing.join(", ")
+                            )
+                        };
+                        app.current_mode = AppMode::Navigation;
+                        if key_event.code != KeyCode::Enter
+                            && app.layers.iter().any(|layer| layer.selected)
+                        {
+                            requests.plot = true;
+                            app.notification.push_str(" Plotting...");
+                        }
+                    }
+                    Err(e) => {
+                        app.notification = format!("Failed to load session '{}': {}", name, e);
+                    }
+                }
+            }
+        }
+        KeyCode::Char('n') | KeyCode::Char('N') => {
+            app.session_name_input.clear();
+            app.current_mode = AppMode::EditingSessionName;
+            app.notification =
+                String::from("Enter session name. Press Enter to save, Escape to cancel.");
+        }
+        KeyCode::Char('d') | KeyCode::Char('D') => {
+            if let Some(name) = app.session_names.get(app.selected_session_index).cloned() {
+                app.notification = match session::delete_session(&name) {
+                    Ok(()) => format!("Deleted session '{}'.", name),
+                    Err(e) => {
+                        format!("Failed to delete session '{}': {}", name, e)
+                    }
+                };
+                app.session_names = session::list_sessions();
+                app.selected_session_index = app
+                    .selected_session_index
+                    .min(app.session_names.len().saturating_sub(1));
+            }
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.current_mode = AppMode::Navigation;
+        }
+        _ => {}
+    }
+}
+
+// Keys of the WFS popup listing the feature types of a service
+fn handle_wfs_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j')
+            if app
+                .wfs_service
+                .as_ref()
+                .is_some_and(|(_, c)| app.selected_wfs_type + 1 < c.feature_types.len()) =>
+        {
+            app.selected_wfs_type += 1;
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            app.selected_wfs_type = app.selected_wfs_type.saturating_sub(1);
+        }
+        KeyCode::Enter => fetch_wfs_type(app, false),
+        KeyCode::Char('a') | KeyCode::Char('A') => fetch_wfs_type(app, true),
+        KeyCode::Char('u') | KeyCode::Char('U') => {
+            app.wfs_url_input.move_to_end();
+            app.current_mode = AppMode::EditingWfsUrl;
+            app.notification = String::from(
+                "URL of a WFS service. Enter lists its feature types, Escape cancels.",
+            );
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.current_mode = AppMode::Navigation;
+        }
+        _ => {}
+    }
+}
+
+// Keys while typing the URL of a WFS service
+fn handle_wfs_url_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            let url = app.wfs_url_input.text.trim().to_string();
+            if remote::is_url(&url) {
+                let request = CapabilitiesRequest::start(&url);
+                app.notification = request.status();
+                app.wfs_request = Some(request);
+                app.current_mode = AppMode::Wfs;
+            } else {
+                app.notification =
+                    String::from("A WFS service URL starts with http:// or https://.");
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = if app.wfs_service.is_some() || app.wfs_request.is_some() {
+                AppMode::Wfs
+            } else {
+                AppMode::Navigation
+            };
+            app.notification = String::from("WFS service entry cancelled.");
+        }
+        code => app.wfs_url_input.edit(code),
+    }
+}
+
+// Keys while typing a name to save the current session under
+fn handle_session_name_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            let name = app.session_name_input.text.trim().to_string();
+            if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+                app.notification = String::from("Invalid session name.");
+            } else {
+                app.notification = match Session::from_app(app).save(&name) {
+                    Ok(()) => format!("Saved session '{}'.", name),
+                    Err(e) => {
+                        format!("Failed to save session '{}': {}", name, e)
+                    }
+                };
+                app.session_names = session::list_sessions();
+                app.selected_session_index = app
+                    .session_names
+                    .iter()
+                    .position(|n| *n == name)
+                    .unwrap_or(0);
+                app.current_mode = AppMode::Sessions;
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Sessions;
+            app.notification = String::from("Session saving cancelled.");
+        }
+        code => app.session_name_input.edit(code),
+    }
+}
+
+// Keys while scrolling the validation report on the Validation screen
+fn handle_validation_key(app: &mut App, key_event: KeyEvent) {
+    let last = app
+        .validation_report
+        .as_ref()
+        .map_or(0, |report| report.issues.len().saturating_sub(1));
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => {
+            app.validation_scroll = (app.validation_scroll + 1).min(last);
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            app.validation_scroll = app.validation_scroll.saturating_sub(1);
+        }
+        KeyCode::PageDown => {
+            app.validation_scroll = (app.validation_scroll + 10).min(last);
+        }
+        KeyCode::PageUp => {
+            app.validation_scroll = app.validation_scroll.saturating_sub(10);
+        }
+        KeyCode::Home => app.validation_scroll = 0,
+        KeyCode::End => app.validation_scroll = last,
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.current_screen = CurrentScreen::GeoJsonMapper;
+            app.current_mode = AppMode::Navigation;
+        }
+        _ => {}
+    }
+}
+
+// Keys of the Duplicates screen: scroll the duplicates found or write the file without them
+fn handle_duplicates_key(app: &mut App, key_event: KeyEvent) {
+    let last = app
+        .duplicate_report
+        .as_ref()
+        .map_or(0, |report| report.duplicates.len().saturating_sub(1));
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => {
+            app.duplicate_scroll = (app.duplicate_scroll + 1).min(last);
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            app.duplicate_scroll = app.duplicate_scroll.saturating_sub(1);
+        }
+        KeyCode::PageDown => {
+            app.duplicate_scroll = (app.duplicate_scroll + 10).min(last);
+        }
+        KeyCode::PageUp => {
+            app.duplicate_scroll = app.duplicate_scroll.saturating_sub(10);
+        }
+        KeyCode::Home => app.duplicate_scroll = 0,
+        KeyCode::End => app.duplicate_scroll = last,
+        KeyCode::Char('w') | KeyCode::Char('W') => {
+            app.notification = write_deduplicated(app);
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.current_screen = CurrentScreen::GeoJsonMapper;
+            app.current_mode = AppMode::Navigation;
+        }
+        _ => {}
+    }
+}
+
+// Keys while browsing the overlaps and gaps found on the Topology screen
+fn handle_topology_key(app: &mut App, key_event: KeyEvent) {
+    let last = app
+        .topology_report
+        .as_ref()
+        .map_or(0, |report| report.issues.len().saturating_sub(1));
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => {
+            app.topology_selected = (app.topology_selected + 1).min(last);
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            app.topology_selected = app.topology_selected.saturating_sub(1);
+        }
+        KeyCode::PageDown => {
+            app.topology_selected = (app.topology_selected + 10).min(last);
+        }
+        KeyCode::PageUp => {
+            app.topology_selected = app.topology_selected.saturating_sub(10);
+        }
+        KeyCode::Home => app.topology_selected = 0,
+        KeyCode::End => app.topology_selected = last,
+        KeyCode::Enter => {
+            if let Some(issue) = app
+                .topology_report
+                .as_ref()
+                .and_then(|report| report.issues.get(app.topology_selected))
+            {
+                app.preview_view = Some(plot::pad_bbox(issue.bbox));
+                app.notification = format!(
+                    "{} at {:.5}, {:.5}. {}",
+                    issue.kind.label(),
+                    issue.at[1],
+                    issue.at[0],
+                    PREVIEW_NAVIGATION_HINT
+                );
+                app.current_screen = CurrentScreen::GeoJsonMapper;
+                app.current_mode = AppMode::NavigatingPreview;
+            }
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.current_screen = CurrentScreen::GeoJsonMapper;
+            app.current_mode = AppMode::Navigation;
+        }
+        _ => {}
+    }
+}
+
+// Keys while browsing the attribute table of a file
+fn handle_attribute_table_key(app: &mut App, key_event: KeyEvent) {
+    match app.attribute_table.as_mut() {
+        Some(table) => {
+            let features = &mut app.layers[table.file_index].features;
+            let page = table.page_rows.max(1) as isize;
+            let leaving = std::mem::take(&mut table.leaving);
+            match key_event.code {
+                KeyCode::Esc | KeyCode::Char('q') if table.modified && !leaving => {
+                    table.leaving = true;
+                    app.notification =
+                        String::from("Unsaved edits: W saves them, Esc again discards them.");
+                }
+                KeyCode::Esc | KeyCode::Char('q') => {
+                    let (index, modified) = (table.file_index, table.modified);
+                    app.attribute_table = None;
+                    app.current_screen = CurrentScreen::GeoJsonMapper;
+                    app.current_mode = AppMode::Navigation;
+                    if modified {
+                        load_file_into_app(app, index);
+                        app.notification =
+                            format!("Edits of {} discarded.", app.layers[index].file);
+                    }
+                }
+                KeyCode::Down | KeyCode::Char('j') => table.move_by(1, 0),
+                KeyCode::Up | KeyCode::Char('k') => table.move_by(-1, 0),
+                KeyCode::Right | KeyCode::Char('l') => table.move_by(0, 1),
+                KeyCode::Left | KeyCode::Char('h') => table.move_by(0, -1),
+                KeyCode::PageDown => table.move_by(page, 0),
+                KeyCode::PageUp => table.move_by(-page, 0),
+                KeyCode::Home => table.row = 0,
+                KeyCode::End => table.move_by(isize::MAX, 0),
+                KeyCode::Char('s') | KeyCode::Char('S') => {
+                    if let Some(features) = features {
+                        table.cycle_sort(features);
+                    }
+                }
+                KeyCode::Enter | KeyCode::Char('e') | KeyCode::Char('E') => {
+                    match (features, table.feature()) {
+                        (Some(features), Some(position)) if table.column < table.columns.len() => {
+                            let column = &table.columns[table.column];
+                            app.table_input
+                                .set(table::cell(&features[position], column));
+                            app.current_mode = AppMode::EditingCell;
+                            app.notification = format!(
+                                "{} of feature {}; empty removes it. Enter saves, Escape cancels.",
+                                column,
+                                position + 1
+                            );
+                        }
+                        _ => app.notification = String::from("Add a property with A first."),
+                    }
+                }
+                KeyCode::Delete => {
+                    if let Some(features) = features {
+                        table.set_cell(features, None);
+                    }
+                }
+                KeyCode::Char('a') | KeyCode::Char('A') => {
+                    app.table_input.clear();
+                    app.current_mode = AppMode::AddingColumn;
+                    app.notification =
+                        String::from("Name of the property to add. Enter adds it, Escape cancels.");
+                }
+                KeyCode::Char('x') | KeyCode::Char('X') => {
+                    if let (Some(features), Some(column)) =
+                        (features, table.columns.get(table.column).cloned())
+                    {
+                        table.remove_column(features);
+                        app.notification =
+                            format!("Removed property {} from every feature.", column);
+                    }
+                }
+                KeyCode::Char('w') | KeyCode::Char('W') => {
+                    let name = &app.layers[table.file_index].file;
+                    let stem = Path::new(name)
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .unwrap_or(name);
+                    app.table_input.set(format!("{}_edited.geojson", stem));
+                    app.current_mode = AppMode::SavingTable;
+                    app.notification = format!(
+                        "File in {} to save to; type {} to overwrite it. Enter saves, Escape cancels.",
+                        GEOJSON_DIR, name
+                    );
+                }
+                _ => {}
+            }
+        }
+        None => {
+            app.current_screen = CurrentScreen::GeoJsonMapper;
+            app.current_mode = AppMode::Navigation;
+        }
+    }
+}
+
+// Keys while typing the name of a property to add to the attribute table
+fn handle_adding_column_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            if let Some(table) = app.attribute_table.as_mut() {
+                match table.add_column(&app.table_input.text) {
+                    Ok(()) => {
+                        app.current_mode = AppMode::AttributeTable;
+                        app.notification = format!(
+                            "Added property {}; Enter types its values.",
+                            app.table_input.text.trim()
+                        );
+                    }
+                    Err(e) => app.notification = e,
+                }
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::AttributeTable;
+            app.notification = String::from("No property added.");
+        }
+        code => app.table_input.edit(code),
+    }
+}
+
+// Keys while typing the file to save the edited attribute table to
+fn handle_saving_table_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            let name = app.table_input.text.trim().to_string();
+            match save_table(app, &name) {
+                Ok(notification) => {
+                    app.notification = notification;
+                    app.current_mode = AppMode::AttributeTable;
+                }
+                Err(e) => app.notification = e,
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::AttributeTable;
+            app.notification = String::from("Not saved.");
+        }
+        code => app.table_input.edit(code),
+    }
+}
+
+// Keys while scrolling the log on the Logs screen
+fn handle_logs_key(app: &mut App, key_event: KeyEvent) {
+    let last = app.log.len().saturating_sub(1);
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => {
+            app.log_scroll = (app.log_scroll + 1).min(last);
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            app.log_scroll = app.log_scroll.saturating_sub(1);
+        }
+        KeyCode::PageDown => {
+            app.log_scroll = (app.log_scroll + 10).min(last);
+        }
+        KeyCode::PageUp => {
+            app.log_scroll = app.log_scroll.saturating_sub(10);
+        }
+        KeyCode::Home => app.log_scroll = 0,
+        KeyCode::End => app.log_scroll = last,
+        KeyCode::Char('f') | KeyCode::Char('F') => {
+            app.log_min_level = app.log_min_level.next();
+            app.log_scroll = 0;
+        }
+        KeyCode::Char('c') | KeyCode::Char('C') => {
+            app.log.clear();
+            app.log_scroll = 0;
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.current_screen = CurrentScreen::GeoJsonMapper;
+            app.current_mode = AppMode::Navigation;
+        }
+        _ => {}
+    }
+}
+
+// Keys while browsing the rules on the Styling screen
+fn handle_styling_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j')
+            if app.selected_rule_index + 1 < app.style_rules.len() =>
+        {
+            app.selected_rule_index += 1;
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            app.selected_rule_index = app.selected_rule_index.saturating_sub(1);
+        }
+        KeyCode::Char('a') | KeyCode::Char('A') => {
+            app.rule_input.clear();
+            app.current_mode = AppMode::EditingRule;
+            app.notification = String::from("Enter a rule. Press Enter to add, Escape to cancel.");
+        }
+        KeyCode::Char('d') | KeyCode::Char('D')
+            if app.selected_rule_index < app.style_rules.len() =>
+        {
+            let rule = app.style_rules.remove(app.selected_rule_index);
+            app.selected_rule_index = app
+                .selected_rule_index
+                .min(app.style_rules.len().saturating_sub(1));
+            app.notification = format!("Deleted rule '{}'.", rule);
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.current_screen = CurrentScreen::GeoJsonMapper;
+            app.current_mode = AppMode::Navigation;
+            app.notification = format!("{} styling rules active.", app.style_rules.len());
+        }
+        _ => {}
+    }
+}
+
+// Keys while typing a new styling rule
+fn handle_rule_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => match StyleRule::parse(&app.rule_input.text) {
+            Ok(rule) => {
+                app.notification = format!("Added rule '{}'.", rule);
+                app.style_rules.push(rule);
+                app.selected_rule_index = app.style_rules.len() - 1;
+                app.current_mode = AppMode::Styling;
+            }
+            Err(e) => app.notification = format!("Invalid rule: {}", e),
+        },
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Styling;
+            app.notification = String::from("Rule entry cancelled.");
+        }
+        code => app.rule_input.edit(code),
+    }
+}
+
+// Keys while panning and zooming the preview, or measuring in it
+fn handle_preview_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down
+            if key_event.modifiers.contains(KeyModifiers::SHIFT) =>
+        {
+            let (dx, dy) = match key_event.code {
+                KeyCode::Left => (-1, 0),
+                KeyCode::Right => (1, 0),
+                KeyCode::Up => (0, -1),
+                _ => (0, 1),
+            };
+            app.move_preview_cursor(dx, dy);
+            if let Some((lon, lat)) = app.preview_cursor_position() {
+                let enter = if app.current_mode == AppMode::Measuring {
+                    "Enter adds a vertex"
+                } else {
+                    "Enter identifies"
+                };
+                app.notification = format!("Crosshair: {:.5}, {:.5} ({})", lat, lon, enter);
+            }
+        }
+        KeyCode::Enter | KeyCode::Char(' ') if app.current_mode == AppMode::Measuring => {
+            add_measure_point(app)
+        }
+        KeyCode::Backspace if app.current_mode == AppMode::Measuring => {
+            app.measure_points.pop();
+            app.notification = measure_status(app);
+        }
+        KeyCode::Char('c') | KeyCode::Char('C') if app.current_mode == AppMode::Measuring => {
+            app.measure_closed = !app.measure_closed;
+            app.notification = measure_status(app);
+        }
+        KeyCode::Esc | KeyCode::Char('m') | KeyCode::Char('M')
+            if app.current_mode == AppMode::Measuring =>
+        {
+            app.measure_points.clear();
+            app.current_mode = AppMode::NavigatingPreview;
+            app.notification = String::from(PREVIEW_NAVIGATION_HINT);
+        }
+        KeyCode::Char('m') | KeyCode::Char('M') => {
+            app.measure_points.clear();
+            app.measure_closed = false;
+            app.current_mode = AppMode::Measuring;
+            app.notification = String::from(MEASURE_HINT);
+        }
+        KeyCode::Enter => identify_features(app),
+        KeyCode::Left => app.pan_preview(-PREVIEW_PAN_STEP, 0.0),
+        KeyCode::Right => app.pan_preview(PREVIEW_PAN_STEP, 0.0),
+        KeyCode::Up => app.pan_preview(0.0, PREVIEW_PAN_STEP),
+        KeyCode::Down => app.pan_preview(0.0, -PREVIEW_PAN_STEP),
+        KeyCode::Char('+') | KeyCode::Char('=') => app.zoom_preview(1.0 / PREVIEW_ZOOM_STEP),
+        KeyCode::Char('-') | KeyCode::Char('_') => app.zoom_preview(PREVIEW_ZOOM_STEP),
+        KeyCode::Char('0') => {
+            app.preview_view = None;
+            app.extent_locked = false;
+            app.notification = String::from("Preview follows the data again; extent unlocked.");
+        }
+        KeyCode::Char('l') | KeyCode::Char('L') => {
+            app.toggle_extent_lock();
+            app.notification = if app.extent_locked {
+                String::from("Extent locked: plots use the previewed viewport.")
+            } else {
+                String::from("Extent unlocked: plots use the data extent.")
+            };
+        }
+        KeyCode::Esc | KeyCode::Char('v') | KeyCode::Char('V') => {
+            app.preview_cursor = None;
+            app.measure_points.clear();
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Left preview navigation.");
+        }
+        _ => {}
+    }
+}
+
+// Keys while looking at the features under the preview crosshair
+fn handle_identify_key(app: &mut App, key_event: KeyEvent) {
+    // A heading and a blank line around the properties of each feature
+    let last = app
+        .identified_features
+        .iter()
+        .map(|(_, f)| 2 + f.properties.as_ref().map_or(0, |p| p.len()))
+        .sum::<usize>()
+        .saturating_sub(1);
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => {
+            app.identify_scroll = (app.identify_scroll + 1).min(last);
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            app.identify_scroll = app.identify_scroll.saturating_sub(1);
+        }
+        KeyCode::PageDown => {
+            app.identify_scroll = (app.identify_scroll + 10).min(last);
+        }
+        KeyCode::PageUp => {
+            app.identify_scroll = app.identify_scroll.saturating_sub(10);
+        }
+        KeyCode::Home => app.identify_scroll = 0,
+        KeyCode::End => app.identify_scroll = last,
+        KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => {
+            app.current_mode = AppMode::NavigatingPreview;
+            app.notification = String::from(PREVIEW_NAVIGATION_HINT);
+        }
+        _ => {}
+    }
+}
+
+// Keys while checking the features of the highlighted file to plot
+fn handle_selecting_features_key(app: &mut App, key_event: KeyEvent) {
+    match app.highlighted_file_index() {
+        // The popup lists the features of the highlighted file
+        Some(idx) => {
+            let count = app.layers[idx].features.as_ref().map_or(0, Vec::len);
+            let last = count.saturating_sub(1);
+            let row = app.selected_feature;
+            match key_event.code {
+                KeyCode::Down | KeyCode::Char('j') => {
+                    app.selected_feature = (row + 1).min(last);
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    app.selected_feature = row.saturating_sub(1);
+                }
+                KeyCode::PageDown => app.selected_feature = (row + 10).min(last),
+                KeyCode::PageUp => app.selected_feature = row.saturating_sub(10),
+                KeyCode::Home => app.selected_feature = 0,
+                KeyCode::End => app.selected_feature = last,
+                KeyCode::Char(' ') => {
+                    let checked = app.layers[idx]
+                        .feature_selection
+                        .as_ref()
+                        .is_none_or(|checked| checked.contains(&row));
+                    app.set_features_checked(idx, count, [row], !checked);
+                    app.selected_feature = (row + 1).min(last);
+                }
+                KeyCode::Char('a') | KeyCode::Char('A') => {
+                    app.layers[idx].feature_selection = None;
+                }
+                KeyCode::Char('d') | KeyCode::Char('D') => {
+                    app.layers[idx].feature_selection = Some(BTreeSet::new());
+                }
+                KeyCode::Char('i') | KeyCode::Char('I') => {
+                    let checked: BTreeSet<usize> = (0..count)
+                        .filter(|position| {
+                            app.layers[idx]
+                                .feature_selection
+                                .as_ref()
+                                .is_some_and(|checked| !checked.contains(position))
+                        })
+                        .collect();
+                    app.layers[idx].feature_selection =
+                        Some(checked).filter(|checked| checked.len() < count);
+                }
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Left | KeyCode::Char('q') => {
+                    app.current_mode = AppMode::Navigation;
+                    app.notification = match &app.layers[idx].feature_selection {
+                        Some(checked) => format!(
+                            "{} of {} features of {} checked for plotting.",
+                            checked.range(..count).count(),
+                            count,
+                            app.layers[idx].file
+                        ),
+                        None => format!("All features of {} are plotted.", app.layers[idx].file),
+                    };
+                }
+                _ => {}
+            }
+        }
+        None => app.current_mode = AppMode::Navigation,
+    }
+}
+
+// Keys while typing the simplification tolerance of the highlighted file
+fn handle_tolerance_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            let text = app.tolerance_input.text.trim();
+            let parsed = match text {
+                "" => Ok(None),
+                _ => match text.parse::<f64>() {
+                    Ok(t) if t >= 0.0 => Ok(Some(t)),
+                    _ => Err(format!("'{}' is not a tolerance >= 0", text)),
+                },
+            };
+            match (parsed, app.highlighted_file_index()) {
+                (Ok(tolerance), Some(idx)) => {
+                    app.layers[idx].simplify_tolerance = tolerance;
+                    app.notification = match tolerance {
+                        Some(t) => {
+                            format!("{} simplified with tolerance {}", app.layers[idx].file, t)
+                        }
+                        None => format!("{} uses the global tolerance", app.layers[idx].file),
+                    };
+                    app.current_mode = AppMode::Navigation;
+                }
+                (Ok(_), None) => app.current_mode = AppMode::Navigation,
+                (Err(e), _) => app.notification = e,
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Tolerance entry cancelled.");
+        }
+        code => app.tolerance_input.edit(code),
+    }
+}
+
+// Keys while typing the CRS override of the highlighted file
+fn handle_crs_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            let text = app.crs_input.text.trim();
+            let parsed = match text {
+                "" => Ok(None),
+                _ => crs::parse_crs(text).map(Some),
+            };
+            match (parsed, app.highlighted_file_index()) {
+                (Ok(crs), Some(idx)) => {
+                    app.layers[idx].crs = crs;
+                    // Reparse, so the preview and bbox use the new system
+                    load_file_into_app(app, idx);
+                    let info = app.layers[idx].info.as_ref();
+                    app.notification = match (
+                        info.and_then(|i| i.crs_warning.as_ref()),
+                        &app.layers[idx].crs,
+                    ) {
+                        (Some(warning), _) => {
+                            format!("{}: {}", app.layers[idx].file, warning)
+                        }
+                        (None, Some(crs)) => {
+                            format!("{} reprojected from {}", app.layers[idx].file, crs)
+                        }
+                        (None, None) => {
+                            format!("{} uses the CRS it declares", app.layers[idx].file)
+                        }
+                    };
+                    app.current_mode = AppMode::Navigation;
+                }
+                (Ok(_), None) => app.current_mode = AppMode::Navigation,
+                (Err(e), _) => app.notification = e,
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("CRS entry cancelled.");
+        }
+        code => app.crs_input.edit(code),
+    }
+}
+
+// Keys while typing the feature filter of the highlighted file
+fn handle_filter_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            let text = app.filter_input.text.trim();
+            let parsed = match text {
+                "" => Ok(None),
+                _ => FeatureFilter::parse(text).map(Some),
+            };
+            match (parsed, app.highlighted_file_index()) {
+                (Ok(filter), Some(idx)) => {
+                    app.layers[idx].filter = filter;
+                    app.refresh_filter_count(idx);
+                    let info = app.layers[idx].info.as_ref();
+                    app.notification = match (
+                        &app.layers[idx].filter,
+                        info.and_then(|i| i.filtered_count.map(|n| (n, i))),
+                    ) {
+                        (Some(filter), Some((count, info))) => format!(
+                            "{}: {} of {} features match {}",
+                            app.layers[idx].file, count, info.feature_count, filter
+                        ),
+                        (Some(filter), None) => {
+                            format!("{} filtered by {}", app.layers[idx].file, filter)
+                        }
+                        (None, _) => format!("{} uses all features", app.layers[idx].file),
+                    };
+                    app.current_mode = AppMode::Navigation;
+                }
+                (Ok(_), None) => app.current_mode = AppMode::Navigation,
+                (Err(e), _) => app.notification = format!("Invalid filter: {}", e),
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Filter entry cancelled.");
+        }
+        code => app.filter_input.edit(code),
+    }
+}
+
+// Keys while typing the distance to buffer the highlighted file by
+fn handle_buffer_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            match (
+                BufferDistance::parse(&app.buffer_input.text),
+                app.highlighted_file_index(),
+            ) {
+                (Ok(distance), Some(idx)) => {
+                    app.notification = buffer_file(app, idx, distance);
+                    app.current_mode = AppMode::Navigation;
+                }
+                (Ok(_), None) => app.current_mode = AppMode::Navigation,
+                (Err(e), _) => app.notification = e,
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Buffering cancelled.");
+        }
+        code => app.buffer_input.edit(code),
+    }
+}
+
+// Keys while typing the tolerance to find near-duplicate features with
+fn handle_duplicates_tolerance_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            match (
+                duplicates::parse_tolerance(&app.duplicate_input.text),
+                app.highlighted_file_index(),
+            ) {
+                (Ok(tolerance), Some(idx)) => find_duplicates(app, idx, tolerance),
+                (Ok(_), None) => app.current_mode = AppMode::Navigation,
+                (Err(e), _) => app.notification = e,
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Duplicate search cancelled.");
+        }
+        code => app.duplicate_input.edit(code),
+    }
+}
+
+// Keys while typing the file to save the selected layers to
+fn handle_export_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => match export_selected(app, &app.export_path_input.text) {
+            Ok(notification) => {
+                app.notification = notification;
+                app.current_mode = AppMode::Navigation;
+            }
+            Err(e) => app.notification = e,
+        },
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Export cancelled.");
+        }
+        code => app.export_path_input.edit(code),
+    }
+}
+
+// Keys while typing the path of a file outside the data directory
+fn handle_open_path_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            let text = app.open_path_input.text.clone();
+            let opened = if remote::is_url(&text) {
+                Ok(start_download(app, &text))
+            } else {
+                open_external_file(app, &text)
+            };
+            match opened {
+                Ok(notification) => {
+                    app.notification = notification;
+                    app.current_mode = AppMode::Navigation;
+                }
+                Err(e) => app.notification = e,
+            }
+        }
+        KeyCode::Tab => {
+            let (completed, candidates) = loader::complete_path(&app.open_path_input.text);
+            app.notification = match candidates.len() {
+                0 if completed == app.open_path_input.text => {
+                    String::from("No matching directories or GeoJSON files.")
+                }
+                0 => String::new(),
+                n if n > MAX_LISTED_COMPLETIONS => format!(
+                    "{} ... ({} more)",
+                    candidates[..MAX_LISTED_COMPLETIONS].join("  "),
+                    n - MAX_LISTED_COMPLETIONS
+                ),
+                _ => candidates.join("  "),
+            };
+            app.open_path_input.set(completed);
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Opening cancelled.");
+        }
+        code => app.open_path_input.edit(code),
+    }
+}
+
+// Keys while typing an expression selecting files by their metadata
+fn handle_selection_key(app: &mut App, key_event: KeyEvent, requests: &mut Requests) {
+    match key_event.code {
+        KeyCode::Enter => match FeatureFilter::parse(&app.selection_input.text) {
+            Ok(filter) => {
+                app.notification = String::from("Reading file metadata...");
+                requests.selection = Some(filter);
+                app.current_mode = AppMode::Navigation;
+            }
+            Err(e) => app.notification = format!("Invalid expression: {}", e),
+        },
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Selection cancelled.");
+        }
+        code => app.selection_input.edit(code),
+    }
+}
+
+// Keys while typing the decimals to round the highlighted file to
+fn handle_minify_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            match (
+                minify::parse_decimals(&app.minify_input.text),
+                app.highlighted_file_index(),
+            ) {
+                (Ok(decimals), Some(idx)) => {
+                    app.notification = minify_file(app, idx, decimals);
+                    app.current_mode = AppMode::Navigation;
+                }
+                (Ok(_), None) => app.current_mode = AppMode::Navigation,
+                (Err(e), _) => app.notification = e,
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Minifying cancelled.");
+        }
+        code => app.minify_input.edit(code),
+    }
+}
+
+// Keys while typing the grid to snap the highlighted file to
+fn handle_snap_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            match (
+                snap::parse_grid(&app.snap_input.text),
+                app.highlighted_file_index(),
+            ) {
+                (Ok(grid), Some(idx)) => {
+                    app.notification = snap_file(app, idx, grid);
+                    app.current_mode = AppMode::Navigation;
+                }
+                (Ok(_), None) => app.current_mode = AppMode::Navigation,
+                (Err(e), _) => app.notification = e,
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Snapping cancelled.");
+        }
+        code => app.snap_input.edit(code),
+    }
+}
+
+// Keys while typing the property to dissolve the highlighted file by
+fn handle_dissolve_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            let property = app.dissolve_input.text.trim().to_string();
+            match app.highlighted_file_index() {
+                _ if property.is_empty() => {
+                    app.notification = String::from("Type the property to dissolve by.")
+                }
+                Some(idx) => {
+                    app.notification = dissolve_file(app, idx, &property);
+                    app.current_mode = AppMode::Navigation;
+                }
+                None => app.current_mode = AppMode::Navigation,
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Dissolve cancelled.");
+        }
+        code => app.dissolve_input.edit(code),
+    }
+}
+
+// Keys while typing the CSV file and key to join to the highlighted file
+fn handle_join_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => {
+            match (
+                JoinSpec::parse(&app.join_input.text),
+                app.highlighted_file_index(),
+            ) {
+                (Ok(spec), Some(idx)) => {
+                    app.notification = join_file(app, idx, &spec);
+                    app.current_mode = AppMode::Navigation;
+                }
+                (Ok(_), None) => app.current_mode = AppMode::Navigation,
+                (Err(e), _) => app.notification = e,
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Join cancelled.");
+        }
+        code => app.join_input.edit(code),
+    }
+}
+
+// Keys while asking which point to compute for each polygon
+fn handle_points_key(app: &mut App, key_event: KeyEvent) {
+    let kind = match key_event.code {
+        KeyCode::Char('c') | KeyCode::Char('C') => Some(PointKind::Centroid),
+        KeyCode::Char('p') | KeyCode::Char('P') => Some(PointKind::Pole),
+        _ => None,
+    };
+    match (kind, app.highlighted_file_index()) {
+        (Some(kind), Some(idx)) => {
+            app.notification = points_file(app, idx, kind);
+            app.current_mode = AppMode::Navigation;
+        }
+        (None, _) if key_event.code != KeyCode::Esc => {}
+        _ => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Points cancelled.");
+        }
+    }
+}
 
-use app::{App, AppMode, CurrentScreen, GeoJsonInfo, TerminalEvent};
-use event::{Event, EventHandler};
+// Keys of the layer properties popup of the highlighted file
+fn handle_layer_properties_key(app: &mut App, key_event: KeyEvent) {
+    match app.highlighted_file_index() {
+        None => app.current_mode = AppMode::Navigation,
+        Some(idx) => match key_event.code {
+            KeyCode::Up => app.layer_property = app.layer_property.previous(),
+            KeyCode::Down | KeyCode::Tab => {
+                app.layer_property = app.layer_property.next();
+            }
+            KeyCode::Left | KeyCode::Char('-') | KeyCode::Char('_') => {
+                app.adjust_layer_property(idx, -1);
+            }
+            KeyCode::Right | KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Char(' ') => {
+                app.adjust_layer_property(idx, 1);
+            }
+            KeyCode::Char('0') | KeyCode::Backspace | KeyCode::Delete => {
+                app.reset_layer_property(idx);
+            }
+            KeyCode::Enter | KeyCode::Esc => {
+                let summary: Vec<String> = LayerProperty::ALL
+                    .iter()
+                    .map(|&property| {
+                        format!(
+                            "{} {}",
+                            property.label().to_lowercase(),
+                            property.format(
+                                app.layer_property_value(idx, property)
+                                    .unwrap_or(property.default_value())
+                            )
+                        )
+                    })
+                    .collect();
+                app.current_mode = AppMode::Navigation;
+                app.notification = format!("{}: {}.", app.layers[idx].file, summary.join(", "));
+            }
+            _ => {}
+        },
+    }
+}
 
-const GEOJSON_DIR: &str = "data/geojson/";
-const OUTPUT_DIR: &str = "output/";
+// Keys while typing an explicit plot extent
+fn handle_extent_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter if app.extent_input.text.trim().is_empty() => {
+            app.extent_locked = false;
+            app.preview_view = None;
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Extent follows the data again.");
+        }
+        KeyCode::Enter => match plot::parse_extent(&app.extent_input.text) {
+            Ok(extent) => {
+                app.preview_view = Some(extent);
+                app.extent_locked = true;
+                app.current_mode = AppMode::Navigation;
+                let [min_lon, min_lat, max_lon, max_lat] = extent;
+                app.notification = format!(
+                    "Extent locked to {}, {} to {}, {}",
+                    min_lon, min_lat, max_lon, max_lat
+                );
+            }
+            Err(e) => app.notification = format!("Invalid extent: {}", e),
+        },
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Extent entry cancelled.");
+        }
+        code => app.extent_input.edit(code),
+    }
+}
 
-// Helper function to read GeoJSON
-fn read_geojson(filepath: &str) -> Result<GeoJson, Box<dyn Error>> {
-    let file = fs::File::open(filepath)?;
-    let reader = io::BufReader::new(file);
-    let geojson = GeoJson::from_reader(reader)?;
-    Ok(geojson)
+// Keys while typing the name of a place to go to
+fn handle_place_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter if app.place_input.text.trim().is_empty() => {
+            app.notification = String::from("Type the name of a place.")
+        }
+        KeyCode::Enter => {
+            let query = app.place_input.text.trim().to_string();
+            app.current_mode = AppMode::Navigation;
+            match app.place_search.search(&query) {
+                Some(place) => go_to_place(app, &place),
+                None => app.notification = format!("Looking up '{}'...", query),
+            }
+        }
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Go to place cancelled.");
+        }
+        code => app.place_input.edit(code),
+    }
 }
 
-// Basic fuzzy matching function
-fn fuzzy_match(pattern: &str, text: &str) -> bool {
-    if pattern.is_empty() {
-        return true;
+// Keys while typing the time range of the features to plot
+fn handle_time_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter if app.time_input.text.trim().is_empty() => {
+            app.time_range = None;
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Plotting features of all times.");
+        }
+        KeyCode::Enter => match TimeRange::parse(&app.time_input.text) {
+            Ok(range) => {
+                app.notification = format!("Time range: {}", range);
+                app.time_range = Some(range);
+                app.current_mode = AppMode::Navigation;
+            }
+            Err(e) => app.notification = format!("Invalid time range: {}", e),
+        },
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Time range entry cancelled.");
+        }
+        code => app.time_input.edit(code),
     }
+}
 
-    let pattern_lower = pattern.to_lowercase();
-    let text_lower = text.to_lowercase();
+// Keys while typing the coordinates to center the preview on
+fn handle_jump_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => match MapMarker::parse(&app.jump_input.text) {
+            Ok(MapMarker { lon, lat, .. }) => {
+                app.jump_point = Some((lon, lat));
+                app.center_preview(lon, lat, JUMP_SPAN);
+                // Zooming and locking there is left to preview navigation
+                app.preview_cursor = None;
+                app.move_preview_cursor(0, 0);
+                app.current_mode = AppMode::NavigatingPreview;
+                app.notification = format!(
+                    "Centered on {:.5}, {:.5}: +/- zoom, L locks the plot extent around it, Esc done",
+                    lat, lon
+                );
+            }
+            Err(e) => app.notification = format!("Invalid coordinates: {}", e),
+        },
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Jump cancelled.");
+        }
+        code => app.jump_input.edit(code),
+    }
+}
 
-    let mut pattern_chars = pattern_lower.chars().peekable();
-    let mut text_chars = text_lower.chars();
-    let mut current_pattern_char = pattern_chars.next();
+// Keys while typing the coordinates of a new marker
+fn handle_marker_key(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter => match MapMarker::parse(&app.marker_input.text) {
+            Ok(marker) => {
+                app.notification = format!(
+                    "Dropped marker {}at {:.5}, {:.5}",
+                    if marker.label.is_empty() {
+                        String::new()
+                    } else {
+                        format!("'{}' ", marker.label)
+                    },
+                    marker.lat,
+                    marker.lon
+                );
+                app.markers.push(marker);
+                app.current_mode = AppMode::Navigation;
+            }
+            Err(e) => app.notification = format!("Invalid marker: {}", e),
+        },
+        KeyCode::Esc => {
+            app.current_mode = AppMode::Navigation;
+            app.notification = String::from("Marker entry cancelled.");
+        }
+        code => app.marker_input.edit(code),
+    }
+}
 
-    while let Some(p_char) = current_pattern_char {
-        let mut found_char = false;
-        while let Some(t_char) = text_chars.next() {
-            if t_char == p_char {
-                found_char = true;
-                break;
+// Handles a click, scroll or drag on the regions the last draw registered
+fn handle_mouse(
+    app: &mut App,
+    mouse_event: MouseEvent,
+    terminal_width: u16,
+    requests: &mut Requests,
+) {
+    let hit = app
+        .regions
+        .at(mouse_event.column, mouse_event.row)
+        .map(|(_, region)| region);
+    let navigating = app.current_mode == AppMode::Navigation;
+    match mouse_event.kind {
+        MouseEventKind::Down(MouseButton::Left) => match hit {
+            Some(Region::Divider) => app.is_resizing = true,
+            Some(Region::FileList) if navigating => click_file_list(
+                app,
+                mouse_event.column,
+                mouse_event.row,
+                mouse_event.modifiers.contains(KeyModifiers::SHIFT),
+            ),
+            // Clicks place the crosshair while navigating the preview,
+            // identifying what is under it or adding a measured vertex
+            Some(Region::Preview)
+                if matches!(
+                    app.current_mode,
+                    AppMode::NavigatingPreview | AppMode::Measuring
+                ) =>
+            {
+                let area = app.preview_area;
+                if app
+                    .preview_position(mouse_event.column, mouse_event.row)
+                    .is_some()
+                {
+                    app.preview_cursor = Some((
+                        mouse_event.column - area.x - 1,
+                        mouse_event.row - area.y - 1,
+                    ));
+                    if app.current_mode == AppMode::Measuring {
+                        add_measure_point(app);
+                    } else {
+                        identify_features(app);
+                    }
+                }
+            }
+            Some(Region::Preview) if navigating => {
+                if let Some((lon, lat)) = app.preview_position(mouse_event.column, mouse_event.row)
+                {
+                    identify_point(app, lon, lat);
+                }
+            }
+            Some(Region::Action(action)) if navigating => {
+                app.notification.clear();
+                run_action(app, action, requests);
+            }
+            Some(Region::PanelTab(tab)) => {
+                app.panel_tab = tab;
+                app.info_scroll = 0;
             }
+            Some(Region::InfoTab(tab)) => {
+                app.info_tab = tab;
+                app.info_scroll = 0;
+            }
+            _ => {}
+        },
+        MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+            let down = mouse_event.kind == MouseEventKind::ScrollDown;
+            match hit {
+                Some(Region::FileList) => {
+                    app.scroll_file_list(if down { SCROLL_ROWS } else { -SCROLL_ROWS })
+                }
+                Some(Region::Panel) if down => app.info_scroll = app.info_scroll.saturating_add(1),
+                Some(Region::Panel) => app.info_scroll = app.info_scroll.saturating_sub(1),
+                _ => {}
+            }
+        }
+        MouseEventKind::Drag(MouseButton::Left) if app.is_resizing && terminal_width > 0 => {
+            let new_width_percent = (mouse_event.column as f64 / terminal_width as f64) * 100.0;
+            app.set_left_pane_width(new_width_percent.round() as i32);
         }
-        if !found_char {
-            return false;
+        MouseEventKind::Up(MouseButton::Left) => {
+            app.is_resizing = false;
         }
-        current_pattern_char = pattern_chars.next();
+        _ => {} // Ignore other mouse events
     }
-    true
+}
+
+// Periodic updates of the work running in the background
+fn poll_background_work(app: &mut App) {
+    poll_follow(app);
+    poll_data_directory(app);
+    poll_gps(app);
+    poll_geocoder(app);
+    poll_place_search(app);
+    poll_downloads(app);
+    poll_wfs(app);
+    update_preview_server(app);
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Non-interactive batch mode skips the TUI entirely
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // `--stdin` starts the TUI with the piped GeoJSON as a temporary layer
+    let stdin_file = if args.len() == 1 && args[0] == "--stdin" {
+        Some(loader::read_stdin_to_temp()?)
+    } else {
+        if let Some(batch_args) = cli::parse_args(&args)? {
+            return cli::run_batch(&batch_args);
+        }
+        None
+    };
+
     // Ensure output directory exists
     fs::create_dir_all(OUTPUT_DIR)?;
 
@@ -78,6 +3936,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // --- Initialize Application State ---
     let mut app = App::new();
 
+    apply_config(&mut app);
+
     // Load GeoJSON file names
     let mut geojson_files_loaded: Vec<String> = Vec::new();
     let path = Path::new(GEOJSON_DIR);
@@ -92,16 +3952,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         for entry in fs::read_dir(path)? {
             let entry = entry?;
             let entry_path = entry.path();
-            if entry_path.is_file() {
-                if let Some(extension) = entry_path.extension() {
-                    if extension == "geojson" {
-                        if let Some(file_name) = entry_path.file_name() {
-                            if let Some(name_str) = file_name.to_str() {
-                                geojson_files_loaded.push(name_str.to_string());
-                            }
-                        }
-                    }
-                }
+            if entry_path.is_file()
+                && loader::is_supported_file(&entry_path)
+                && let Some(name_str) = entry_path.file_name().and_then(|f| f.to_str())
+            {
+                geojson_files_loaded.push(name_str.to_string());
             }
         }
         geojson_files_loaded.sort(); // Sort alphabetically
@@ -113,752 +3968,147 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     app.setup_geojson_data(geojson_files_loaded);
+    if path.is_dir() {
+        match DirectoryWatcher::new(path) {
+            Ok(watcher) => app.data_watcher = Some(watcher),
+            Err(e) => app.log(LogLevel::Warning, e),
+        }
+    }
+    if let Some(name) = stdin_file.as_ref().and_then(|path| path.to_str()) {
+        let index = app.add_geojson_file(name.to_string());
+        app.set_selected(index, true);
+        load_selected_files(&mut app);
+        if let Some(position) = app
+            .filtered_geojson_indices
+            .iter()
+            .position(|&i| i == index)
+        {
+            app.selected_file_index = position;
+        }
+        app.stdin_file = Some(name.to_string());
+        app.notification = String::from("Loaded standard input as a temporary layer.");
+    }
+
+    match InfoCache::load() {
+        Ok(cache) => app.info_cache = cache,
+        Err(e) => {
+            app.notification = format!("Could not read {}: {}", info_cache::INFO_CACHE_FILE, e)
+        }
+    }
+
+    if let Err(e) = app.place_search.load_cache() {
+        app.notification = format!("Could not read {}: {}", geocode::PLACE_CACHE_FILE, e)
+    }
+
+    // Restore the layout the last run was closed with
+    match Workspace::load() {
+        Ok(Some(workspace)) => workspace.apply(&mut app),
+        Ok(None) => {}
+        Err(e) => {
+            app.notification = format!("Could not restore {}: {}", session::WORKSPACE_FILE, e)
+        }
+    }
 
     // --- Initialize Event Handler ---
     let tick_rate = Duration::from_millis(250);
     let event_handler = EventHandler::new(tick_rate);
 
     // --- Main TUI Loop ---
-    let mut quit_app = false; // Separate flag to break main loop for plotting
-    while !quit_app {
-        // --- Pre-rendering state updates ---
+    let mut requests = Requests::default();
+    while !requests.quit {
+        prepare_frame(&mut app);
 
-        // Re-filter files if search query changed or just entered/exited search mode
-        let prev_search_query = app.previous_search_query_buffer.clone();
-        if app.current_mode == AppMode::Searching || app.search_query_buffer.ne(&prev_search_query)
-        {
-            app.filtered_geojson_indices.clear();
-            if app.search_query_buffer.is_empty() {
-                for i in 0..app.geojson_files.len() {
-                    app.filtered_geojson_indices.push(i);
-                }
-            } else {
-                for (i, filename) in app.geojson_files.iter().enumerate() {
-                    if fuzzy_match(&app.search_query_buffer, filename) {
-                        app.filtered_geojson_indices.push(i);
-                    }
-                }
-            }
-            if app.filtered_geojson_indices.is_empty() {
-                app.selected_file_index = 0;
-            } else {
-                app.selected_file_index = cmp::min(
-                    app.selected_file_index,
-                    app.filtered_geojson_indices.len().saturating_sub(1),
-                );
-            }
-            app.previous_search_query_buffer
-                .clone_from(&app.search_query_buffer);
-        }
+        // --- Draw UI ---
+        app.log_notification();
+        terminal.draw(|f| ui::render(f, &mut app))?;
 
-        // Adjust scroll_offset to keep selected_file_index in view
-        let current_list_len = app.filtered_geojson_indices.len();
-        let estimated_max_visible_items = terminal.size()?.height.saturating_sub(5) as usize;
-        if app.selected_file_index >= app.scroll_offset + estimated_max_visible_items
-            && estimated_max_visible_items > 0
-        {
-            app.scroll_offset = app.selected_file_index - estimated_max_visible_items + 1;
+        if let Some(filter) = requests.selection.take() {
+            select_where(&mut app, &filter);
+            continue;
         }
-        if app.selected_file_index < app.scroll_offset {
-            app.scroll_offset = app.selected_file_index;
-        }
-        if current_list_len <= estimated_max_visible_items {
-            app.scroll_offset = 0; // All files fit, no scrolling needed
-        } else if app.scroll_offset > current_list_len.saturating_sub(estimated_max_visible_items) {
-            app.scroll_offset = current_list_len.saturating_sub(estimated_max_visible_items);
-        }
-
-        // --- Cache GeoJSON Info for selected file ---
-        let current_original_file_index = if app.filtered_geojson_indices.is_empty() {
-            0
-        } else {
-            app.filtered_geojson_indices[app.selected_file_index]
-        };
-
-        if current_original_file_index != app.previous_selected_file_index_in_filtered
-            || app.cached_geojson_info[current_original_file_index].is_none()
-        {
-            let mut info = GeoJsonInfo::default();
-            if let Some(chosen_filename_str) = app.geojson_files.get(current_original_file_index) {
-                let full_filepath = PathBuf::from(GEOJSON_DIR).join(chosen_filename_str);
-                if let Ok(metadata) = fs::metadata(&full_filepath) {
-                    info.file_size_kb = metadata.len() / 1024;
-                    if let Ok(time) = metadata.modified() {
-                        let datetime: chrono::DateTime<chrono::Local> = time.into();
-                        info.modified_time = format!("{}", datetime.format("%Y-%m-%d %H:%M"));
-                    } else {
-                        info.modified_time = String::from("N/A");
-                    }
-                } else {
-                    info.parse_error = Some(String::from("File info: Not available"));
-                }
-
-                match read_geojson(
-                    full_filepath
-                        .to_str()
-                        .expect("Failed to convert path to string"),
-                ) {
-                    Ok(geojson) => {
-                        let mut min_lon = f64::MAX;
-                        let mut min_lat = f64::MAX;
-                        let mut max_lon = f64::MIN;
-                        let mut max_lat = f64::MIN;
-
-                        let mut process_geometry_for_info = |geometry: &geojson::Geometry| {
-                            let geom_type = geometry.value.type_name().to_string();
-                            *info.geometry_counts.entry(geom_type).or_insert(0) += 1;
-
-                            match &geometry.value {
-                                Value::Point(c) => {
-                                    min_lon = min_lon.min(c[0]);
-                                    min_lat = min_lat.min(c[1]);
-                                    max_lon = max_lon.max(c[0]);
-                                    max_lat = max_lat.max(c[1]);
-                                }
-                                Value::MultiPoint(coords_vec) => {
-                                    for c in coords_vec {
-                                        min_lon = min_lon.min(c[0]);
-                                        min_lat = min_lat.min(c[1]);
-                                        max_lon = max_lon.max(c[0]);
-                                        max_lat = max_lat.max(c[1]);
-                                    }
-                                }
-                                Value::LineString(line) => {
-                                    for c in line {
-                                        min_lon = min_lon.min(c[0]);
-                                        min_lat = min_lat.min(c[1]);
-                                        max_lon = max_lon.max(c[0]);
-                                        max_lat = max_lat.max(c[1]);
-                                    }
-                                }
-                                Value::MultiLineString(multi_line) => {
-                                    for line in multi_line {
-                                        for c in line {
-                                            min_lon = min_lon.min(c[0]);
-                                            min_lat = min_lat.min(c[1]);
-                                            max_lon = max_lon.max(c[0]);
-                                            max_lat = max_lat.max(c[1]);
-                                        }
-                                    }
-                                }
-                                Value::Polygon(polygon) => {
-                                    for ring in polygon {
-                                        for c in ring {
-                                            min_lon = min_lon.min(c[0]);
-                                            min_lat = min_lat.min(c[1]);
-                                            max_lon = max_lon.max(c[0]);
-                                            max_lat = max_lat.max(c[1]);
-                                        }
-                                    }
-                                }
-                                Value::MultiPolygon(multi_polygon) => {
-                                    for polygon in multi_polygon {
-                                        for ring in polygon {
-                                            for c in ring {
-                                                min_lon = min_lon.min(c[0]);
-                                                min_lat = min_lat.min(c[1]);
-                                                max_lon = max_lon.max(c[0]);
-                                                max_lat = max_lat.max(c[1]);
-                                            }
-                                        }
-                                    }
-                                }
-                                _ => {}
-                            }
-                        };
 
-                        match geojson {
-                            GeoJson::FeatureCollection(collection) => {
-                                info.feature_count = collection.features.len();
-                                for feature in collection.features {
-                                    if let Some(geometry) = feature.geometry {
-                                        process_geometry_for_info(&geometry);
-                                    }
-                                }
-                            }
-                            GeoJson::Feature(feature) => {
-                                info.feature_count = 1;
-                                if let Some(geometry) = feature.geometry {
-                                    process_geometry_for_info(&geometry);
-                                }
-                            }
-                            GeoJson::Geometry(geometry) => {
-                                info.feature_count = 1;
-                                process_geometry_for_info(&geometry);
-                            }
-                        }
+        if requests.search {
+            requests.search = false;
+            search_properties(&mut app);
+            continue;
+        }
 
-                        if info.feature_count > 0 && min_lon != f64::MAX {
-                            info.bbox = Some([min_lon, min_lat, max_lon, max_lat]);
-                        }
-                    }
-                    Err(e) => {
-                        info.parse_error = Some(format!("GeoJSON Parse Error: {}", e));
-                    }
-                }
-            } else {
-                info.parse_error = Some(String::from("Info: No file selected"));
+        if requests.plot || requests.copy {
+            let to_clipboard = requests.copy;
+            requests.plot = false;
+            requests.copy = false;
+            if app.plot_job.is_some() {
+                app.notification =
+                    String::from("A plot is already rendering. Press Esc to cancel it.");
+            } else if let Some(summary) = start_plot(&mut app, to_clipboard, event_handler.sender())
+            {
+                show_plot_summary(&mut app, summary, to_clipboard);
             }
-            app.cached_geojson_info[current_original_file_index] = Some(info);
-            app.previous_selected_file_index_in_filtered = current_original_file_index;
+            continue;
         }
 
-        // --- Draw UI ---
-        terminal.draw(|f| ui::render(f, &mut app))?;
-
         // --- Handle Events ---
         if let Some(event) = event_handler.next(tick_rate)? {
+            let event = click_as_key(&app, event);
+            // Keys and clicks may change what undo restores
+            let recorded = match &event {
+                Event::Input(_) => true,
+                Event::Mouse(mouse_event) => mouse_event.kind != MouseEventKind::Moved,
+                _ => false,
+            };
+            if recorded && app.history.needs_baseline() {
+                app.history.begin(history::snapshot(&app));
+            }
             match event {
                 Event::Input(key_event) => {
                     app.notification.clear(); // Clear notification on new input
-
-                    match app.current_mode {
-                        AppMode::Navigation => {
-                            match key_event.code {
-                                KeyCode::Down | KeyCode::Char('j') => {
-                                    if app.selected_file_index + 1
-                                        < app.filtered_geojson_indices.len()
-                                    {
-                                        app.selected_file_index += 1;
-                                    }
-                                }
-                                KeyCode::Up | KeyCode::Char('k') => {
-                                    if app.selected_file_index > 0 {
-                                        app.selected_file_index -= 1;
-                                    }
-                                }
-                                KeyCode::Char(' ') => {
-                                    // Space
-                                    if !app.filtered_geojson_indices.is_empty() {
-                                        let original_index =
-                                            app.filtered_geojson_indices[app.selected_file_index];
-                                        app.selected_files_status[original_index] =
-                                            !app.selected_files_status[original_index];
-                                        if app.selected_files_status[original_index] {
-                                            app.assigned_plot_colors[original_index] = Some(
-                                                app.plot_colors
-                                                    [app.current_color_index_for_assignment],
-                                            );
-                                            app.notification = format!(
-                                                "Selected: {} (Color: R{} G{} B{})",
-                                                app.geojson_files[original_index],
-                                                app.plot_colors
-                                                    [app.current_color_index_for_assignment]
-                                                    .0,
-                                                app.plot_colors
-                                                    [app.current_color_index_for_assignment]
-                                                    .1,
-                                                app.plot_colors
-                                                    [app.current_color_index_for_assignment]
-                                                    .2
-                                            );
-                                            app.current_color_index_for_assignment =
-                                                (app.current_color_index_for_assignment + 1)
-                                                    % app.plot_colors.len();
-                                        } else {
-                                            app.assigned_plot_colors[original_index] = None;
-                                            app.notification = format!(
-                                                "Deselected: {}",
-                                                app.geojson_files[original_index]
-                                            );
-                                        }
-                                    } else {
-                                        app.notification =
-                                            String::from("No files to select in current view.");
-                                    }
-                                }
-                                KeyCode::Enter => {
-                                    let num_selected =
-                                        app.selected_files_status.iter().filter(|&&s| s).count();
-                                    if num_selected > 0 {
-                                        quit_app = true; // Exit loop to process selection
-                                        app.notification =
-                                            format!("Plotting {} selected files...", num_selected);
-                                    } else {
-                                        app.notification = String::from(
-                                            "No files selected to plot. Use Space to select.",
-                                        );
-                                    }
-                                }
-                                KeyCode::Char('c') | KeyCode::Char('C') => {
-                                    app.current_color_index_for_assignment =
-                                        (app.current_color_index_for_assignment + 1)
-                                            % app.plot_colors.len();
-                                    app.notification = format!(
-                                        "Next assignment color set to R{} G{} B{}",
-                                        app.plot_colors[app.current_color_index_for_assignment].0,
-                                        app.plot_colors[app.current_color_index_for_assignment].1,
-                                        app.plot_colors[app.current_color_index_for_assignment].2
-                                    );
-                                }
-                                KeyCode::Char('r') | KeyCode::Char('R') => {
-                                    app.current_mode = AppMode::EditingFilename;
-                                    app.previous_output_filename_buffer
-                                        .clone_from(&app.output_filename_buffer);
-                                    app.notification = String::from(
-                                        "Editing filename. Press Enter to confirm, Escape to cancel.",
-                                    );
-                                }
-                                KeyCode::Char('/') => {
-                                    app.current_mode = AppMode::Searching;
-                                    app.previous_search_query_buffer
-                                        .clone_from(&app.search_query_buffer);
-                                    app.notification = String::from(
-                                        "Enter search query. Press Enter to apply, Escape to cancel.",
-                                    );
-                                }
-                                KeyCode::Char('p') | KeyCode::Char('P') => {
-                                    app.plot_points = !app.plot_points;
-                                    app.notification = format!(
-                                        "Points visibility: {}",
-                                        if app.plot_points { "ON" } else { "OFF" }
-                                    );
-                                }
-                                KeyCode::Char('l') | KeyCode::Char('L') => {
-                                    app.plot_lines = !app.plot_lines;
-                                    app.notification = format!(
-                                        "Lines visibility: {}",
-                                        if app.plot_lines { "ON" } else { "OFF" }
-                                    );
-                                }
-                                KeyCode::Char('o') | KeyCode::Char('O') => {
-                                    app.plot_polygons = !app.plot_polygons;
-                                    app.notification = format!(
-                                        "Polygons visibility: {}",
-                                        if app.plot_polygons { "ON" } else { "OFF" }
-                                    );
-                                }
-                                KeyCode::Char('q') | KeyCode::Char('Q') => {
-                                    quit_app = true;
-                                    app.notification = String::from("Exiting...");
-                                }
-                                KeyCode::Char('h') | KeyCode::Char('H') => {
-                                    app.current_screen = CurrentScreen::Help;
-                                    app.notification = String::from("Showing Help screen.");
-                                }
-                                _ => { /* Ignore other key events */ }
-                            }
-                        }
-                        AppMode::EditingFilename => {
-                            match key_event.code {
-                                KeyCode::Enter => {
-                                    if app.output_filename_buffer.is_empty() {
-                                        app.notification =
-                                            String::from("Filename cannot be empty. Reverted.");
-                                        app.output_filename_buffer
-                                            .clone_from(&app.previous_output_filename_buffer);
-                                    } else if !app.output_filename_buffer.ends_with(".png")
-                                        && !app.output_filename_buffer.ends_with(".jpg")
-                                        && !app.output_filename_buffer.ends_with(".jpeg")
-                                        && !app.output_filename_buffer.ends_with(".bmp")
-                                    {
-                                        app.notification = String::from(
-                                            "Filename must end with .png, .jpg, .jpeg, or .bmp. Reverted.",
-                                        );
-                                        app.output_filename_buffer
-                                            .clone_from(&app.previous_output_filename_buffer);
-                                    } else {
-                                        app.notification = format!(
-                                            "Output filename set to: {}",
-                                            app.output_filename_buffer
-                                        );
-                                    }
-                                    app.current_mode = AppMode::Navigation;
-                                }
-                                KeyCode::Esc => {
-                                    // Escape key
-                                    app.output_filename_buffer
-                                        .clone_from(&app.previous_output_filename_buffer);
-                                    app.notification = String::from(
-                                        "Filename editing cancelled. Reverted to previous.",
-                                    );
-                                    app.current_mode = AppMode::Navigation;
-                                }
-                                KeyCode::Backspace => {
-                                    if app.output_filename_cursor > 0 {
-                                        app.output_filename_cursor -= 1;
-                                        if app.output_filename_cursor
-                                            < app.output_filename_buffer.len()
-                                        {
-                                            app.output_filename_buffer
-                                                .remove(app.output_filename_cursor);
-                                        }
-                                    }
-                                }
-                                KeyCode::Delete => {
-                                    if app.output_filename_cursor < app.output_filename_buffer.len()
-                                    {
-                                        app.output_filename_buffer
-                                            .remove(app.output_filename_cursor);
-                                    }
-                                }
-                                KeyCode::Left => {
-                                    if app.output_filename_cursor > 0 {
-                                        app.output_filename_cursor -= 1;
-                                    }
-                                }
-                                KeyCode::Right => {
-                                    if app.output_filename_cursor < app.output_filename_buffer.len()
-                                    {
-                                        app.output_filename_cursor += 1;
-                                    }
-                                }
-                                KeyCode::Char(c) => {
-                                    if app.output_filename_cursor
-                                        >= app.output_filename_buffer.len()
-                                    {
-                                        app.output_filename_buffer.push(c);
-                                    } else {
-                                        app.output_filename_buffer
-                                            .insert(app.output_filename_cursor, c);
-                                    }
-                                    app.output_filename_cursor += 1;
-                                }
-                                _ => {}
-                            }
-                        }
-                        AppMode::Searching => {
-                            match key_event.code {
-                                KeyCode::Enter => {
-                                    if app.search_query_buffer.is_empty() {
-                                        app.notification =
-                                            String::from("Search cleared. Showing all files.");
-                                    } else {
-                                        app.notification = format!(
-                                            "Searching for: '{}' ({} results)",
-                                            app.search_query_buffer,
-                                            app.filtered_geojson_indices.len()
-                                        );
-                                    }
-                                    app.current_mode = AppMode::Navigation;
-                                }
-                                KeyCode::Esc => {
-                                    // Escape key
-                                    app.search_query_buffer
-                                        .clone_from(&app.previous_search_query_buffer);
-                                    app.current_mode = AppMode::Navigation;
-                                    app.notification =
-                                        String::from("Search cancelled. Showing all files.");
-                                }
-                                KeyCode::Backspace => {
-                                    if app.search_query_cursor > 0 {
-                                        app.search_query_cursor -= 1;
-                                        if app.search_query_cursor < app.search_query_buffer.len() {
-                                            app.search_query_buffer.remove(app.search_query_cursor);
-                                        }
-                                    }
-                                }
-                                KeyCode::Delete => {
-                                    if app.search_query_cursor < app.search_query_buffer.len() {
-                                        app.search_query_buffer.remove(app.search_query_cursor);
-                                    }
-                                }
-                                KeyCode::Left => {
-                                    if app.search_query_cursor > 0 {
-                                        app.search_query_cursor -= 1;
-                                    }
-                                }
-                                KeyCode::Right => {
-                                    if app.search_query_cursor < app.search_query_buffer.len() {
-                                        app.search_query_cursor += 1;
-                                    }
-                                }
-                                KeyCode::Char(c) => {
-                                    if app.search_query_cursor >= app.search_query_buffer.len() {
-                                        app.search_query_buffer.push(c);
-                                    } else {
-                                        app.search_query_buffer.insert(app.search_query_cursor, c);
-                                    }
-                                    app.search_query_cursor += 1;
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
+                    handle_key(&mut app, key_event, &mut requests);
                 }
                 Event::TerminalEvent(TerminalEvent::Resize) => {
                     // ratatui handles resize redrawing automatically
                 }
-                Event::Tick => {
-                    // Periodic updates
+                Event::PlotProgress(drawn, total) => {
+                    if let Some(job) = app.plot_job.as_mut() {
+                        job.counts = (drawn, total);
+                    }
                 }
-                Event::Mouse(mouse_event) => {
-                    // Resizing logic GeoJsonMapper screen
-                    if app.current_screen == CurrentScreen::GeoJsonMapper {
-                        let terminal_width = terminal.size()?.width;
-                        // Calculate divider position based on current app.left_pane_width_percentage
-                        let divider_col = (terminal_width as f64
-                            * (app.left_pane_width_percentage as f64 / 100.0))
-                            as u16;
-
-                        match mouse_event.kind {
-                            MouseEventKind::Down(MouseButton::Left) => {
-                                // Check if mouse click is near the divider (within a small range)
-                                if mouse_event.column >= divider_col.saturating_sub(1)
-                                    && mouse_event.column <= divider_col.saturating_add(1)
-                                {
-                                    app.is_resizing = true;
-                                }
-                            }
-                            MouseEventKind::Drag(MouseButton::Left) => {
-                                if app.is_resizing {
-                                    if terminal_width > 0 {
-                                        let new_width_percent = (mouse_event.column as f64
-                                            / terminal_width as f64)
-                                            * 100.0;
-                                        // Clamp to a reasonable range
-                                        app.left_pane_width_percentage =
-                                            (new_width_percent.round() as u16).clamp(10, 90);
-                                    }
-                                }
-                            }
-                            MouseEventKind::Up(MouseButton::Left) => {
-                                app.is_resizing = false;
-                            }
-                            _ => {} // Ignore other mouse events
-                        }
+                Event::PlotFinished(result) => {
+                    if let Some((summary, to_clipboard)) = finish_plot(&mut app, result) {
+                        show_plot_summary(&mut app, summary, to_clipboard);
                     }
                 }
+                Event::Tick => poll_background_work(&mut app),
+                Event::Mouse(mouse_event) => {
+                    handle_mouse(&mut app, mouse_event, terminal.size()?.width, &mut requests)
+                }
+            }
+            if recorded && !app.current_mode.is_editing() {
+                app.history.commit(history::snapshot(&app));
             }
         }
     }
 
-    // --- Plotting Logic (after TUI loop exits via Enter) ---
+    let workspace_result = Workspace::from_app(&app).save();
+    let cache_result = app.info_cache.save();
+    let place_cache_result = app.place_search.save_cache();
+    if let Some(path) = &stdin_file {
+        let _ = fs::remove_file(path);
+    }
+
+    // --- Restore the terminal ---
     execute!(terminal.backend_mut(), DisableMouseCapture)?; // Disable mouse capture here
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
 
-    let files_to_plot: Vec<(usize, &String)> = app
-        .geojson_files
-        .iter()
-        .enumerate()
-        .filter(|(i, _)| app.selected_files_status[*i])
-        .collect();
-
-    if files_to_plot.is_empty() {
-        println!("No files selected for plotting. Exited without generating a plot.");
-    } else {
-        let output_filename = PathBuf::from(OUTPUT_DIR).join(&app.output_filename_buffer);
-
-        // --- Calculate combined BBox for selected files ---
-        let mut overall_min_lon = f64::MAX;
-        let mut overall_min_lat = f64::MAX;
-        let mut overall_max_lon = f64::MIN;
-        let mut overall_max_lat = f64::MIN;
-        let mut bbox_found = false;
-
-        for (file_idx, _) in &files_to_plot {
-            if let Some(info) = &app.cached_geojson_info[*file_idx] {
-                if let Some(bbox) = info.bbox {
-                    overall_min_lon = overall_min_lon.min(bbox[0]);
-                    overall_min_lat = overall_min_lat.min(bbox[1]);
-                    overall_max_lon = overall_max_lon.max(bbox[2]);
-                    overall_max_lat = overall_max_lat.max(bbox[3]);
-                    bbox_found = true;
-                }
-            }
-        }
-
-        let mut x_range = -180.0f64..180.0f64;
-        let mut y_range = -90.0f64..90.0f64;
-
-        if bbox_found {
-            let padding_percentage = 0.1; // 10% padding
-            let epsilon = 0.001;
-
-            let mut lon_range = overall_max_lon - overall_min_lon;
-            if lon_range < epsilon {
-                lon_range = epsilon;
-            }
-            let mut lat_range = overall_max_lat - overall_min_lat;
-            if lat_range < epsilon {
-                lat_range = epsilon;
-            }
-
-            let lon_padding = lon_range * padding_percentage;
-            let lat_padding = lat_range * padding_percentage;
-
-            let padded_min_lon = (overall_min_lon - lon_padding).max(-180.0);
-            let padded_max_lon = (overall_max_lon + lon_padding).min(180.0);
-            let padded_min_lat = (overall_min_lat - lat_padding).max(-90.0);
-            let padded_max_lat = (overall_max_lat + lat_padding).min(90.0);
-
-            x_range = padded_min_lon..padded_max_lon;
-            y_range = padded_min_lat..padded_max_lat;
-        } else {
-            println!(
-                "Warning: No valid bounding box found for selected files. Using default global view."
-            );
-        }
-
-        // Setup drawing area only if files are selected and not quitting
-        let chart_caption = format!("GeoJSON Plot");
-
-        let width = 1024;
-        let height = 768;
-        let root = BitMapBackend::new(
-            output_filename
-                .to_str()
-                .expect("Failed to convert path to string"),
-            (width, height),
-        )
-        .into_drawing_area();
-        root.fill(&RGBColor(173, 216, 230))?; // Light blue ocean background
-
-        let mut chart = ChartBuilder::on(&root)
-            .margin(10)
-            .caption(&chart_caption, ("sans-serif", 40).into_font())
-            .build_cartesian_2d(x_range, y_range)?;
-
-        chart.configure_mesh().draw()?;
-
-        for (file_idx, chosen_filename_str) in files_to_plot {
-            let full_filepath = PathBuf::from(GEOJSON_DIR).join(chosen_filename_str);
-            let plot_color_for_file = app.assigned_plot_colors[file_idx].unwrap_or_else(|| {
-                // Fallback to black if for some reason color wasn't assigned
-                RGBColor(0, 0, 0)
-            });
-
-            match read_geojson(
-                full_filepath
-                    .to_str()
-                    .expect("Failed to convert path to string"),
-            ) {
-                Ok(geojson) => {
-                    let mut draw_geometry = |geometry: geojson::Geometry,
-                                             color: &RGBColor,
-                                             plot_points_flag: bool,
-                                             plot_lines_flag: bool,
-                                             plot_polygons_flag: bool|
-                     -> Result<(), Box<dyn Error>> {
-                        match geometry.value {
-                            Value::Point(c) => {
-                                if plot_points_flag {
-                                    chart.draw_series(PointSeries::of_element(
-                                        vec![(c[0], c[1])],
-                                        5, // Point size
-                                        color.filled(),
-                                        &|c, s, st| {
-                                            return EmptyElement::at(c)
-                                                + Circle::new((0, 0), s, st);
-                                        },
-                                    ))?;
-                                }
-                            }
-                            Value::MultiPoint(coords_vec) => {
-                                if plot_points_flag {
-                                    chart.draw_series(PointSeries::of_element(
-                                        coords_vec.into_iter().map(|c| (c[0], c[1])),
-                                        5,
-                                        color.filled(),
-                                        &|c, s, st| {
-                                            return EmptyElement::at(c)
-                                                + Circle::new((0, 0), s, st);
-                                        },
-                                    ))?;
-                                }
-                            }
-                            Value::LineString(lines) => {
-                                if plot_lines_flag {
-                                    chart.draw_series(LineSeries::new(
-                                        lines
-                                            .into_iter()
-                                            .map(|line_coord| (line_coord[0], line_coord[1])),
-                                        color,
-                                    ))?;
-                                }
-                            }
-                            Value::MultiLineString(multi_lines) => {
-                                if plot_lines_flag {
-                                    for lines_segment in multi_lines {
-                                        chart.draw_series(LineSeries::new(
-                                            lines_segment
-                                                .into_iter()
-                                                .map(|line_coord| (line_coord[0], line_coord[1])),
-                                            color,
-                                        ))?;
-                                    }
-                                }
-                            }
-                            Value::Polygon(polygon_rings) => {
-                                if plot_polygons_flag {
-                                    // Draw the exterior ring of the polygon
-                                    if let Some(exterior_ring) = polygon_rings.get(0) {
-                                        chart.draw_series(LineSeries::new(
-                                            exterior_ring
-                                                .into_iter()
-                                                .map(|point| (point[0], point[1])),
-                                            color,
-                                        ))?;
-                                    }
-                                }
-                            }
-                            Value::MultiPolygon(multi_polygon) => {
-                                if plot_polygons_flag {
-                                    for polygon in multi_polygon {
-                                        if let Some(exterior_ring) = polygon.get(0) {
-                                            chart.draw_series(LineSeries::new(
-                                                exterior_ring
-                                                    .into_iter()
-                                                    .map(|point| (point[0], point[1])),
-                                                color,
-                                            ))?;
-                                        }
-                                    }
-                                }
-                            }
-                            _ => {}
-                        }
-                        Ok(())
-                    };
-
-                    match geojson {
-                        GeoJson::FeatureCollection(collection) => {
-                            for feature in collection.features {
-                                if let Some(geometry) = feature.geometry {
-                                    draw_geometry(
-                                        geometry,
-                                        &plot_color_for_file,
-                                        app.plot_points,
-                                        app.plot_lines,
-                                        app.plot_polygons,
-                                    )?;
-                                }
-                            }
-                        }
-                        GeoJson::Feature(feature) => {
-                            if let Some(geometry) = feature.geometry {
-                                draw_geometry(
-                                    geometry,
-                                    &plot_color_for_file,
-                                    app.plot_points,
-                                    app.plot_lines,
-                                    app.plot_polygons,
-                                )?;
-                            }
-                        }
-                        GeoJson::Geometry(geometry) => {
-                            draw_geometry(
-                                geometry,
-                                &plot_color_for_file,
-                                app.plot_points,
-                                app.plot_lines,
-                                app.plot_polygons,
-                            )?;
-                        }
-                    }
-                }
-                Err(e) => eprintln!(
-                    "Error reading GeoJSON from {}: {}",
-                    full_filepath.display(),
-                    e
-                ),
-            }
-        }
-
-        root.present()?;
-        println!("Combined plot generated to {}", output_filename.display());
+    if let Err(e) = workspace_result {
+        eprintln!("Could not save {}: {}", session::WORKSPACE_FILE, e);
+    }
+    if let Err(e) = cache_result {
+        eprintln!("Could not save {}: {}", info_cache::INFO_CACHE_FILE, e);
+    }
+    if let Err(e) = place_cache_result {
+        eprintln!("Could not save {}: {}", geocode::PLACE_CACHE_FILE, e);
     }
 
     Ok(())
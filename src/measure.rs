@@ -0,0 +1,106 @@
+// measure.rs
+
+use geojson::Value;
+
+use crate::geocode::{self, EARTH_RADIUS_KM};
+
+/// Great-circle length in kilometres of the path through `points` (lon, lat),
+/// back to the first point when `closed`.
+pub fn length_km(points: &[(f64, f64)], closed: bool) -> f64 {
+    let closing = if closed && points.len() > 2 {
+        points.last().zip(points.first())
+    } else {
+        None
+    };
+    points
+        .windows(2)
+        .map(|pair| (pair[0], pair[1]))
+        .chain(closing.map(|(a, b)| (*a, *b)))
+        .map(|((lon1, lat1), (lon2, lat2))| geocode::haversine_km(lon1, lat1, lon2, lat2))
+        .sum()
+}
+
+/// Area in square kilometres enclosed by `points` (lon, lat) on a spherical
+/// Earth, after Chamberlain and Duquette, "Some algorithms for polygons on a
+/// sphere" (2007).
+pub fn area_km2(points: &[(f64, f64)]) -> f64 {
+    let n = points.len();
+    if n < 3 {
+        return 0.0;
+    }
+    let sum: f64 = (0..n)
+        .map(|i| {
+            let (lon1, lat1) = points[i];
+            let (lon2, lat2) = points[(i + 1) % n];
+            // Crossing the antimeridian takes the short way round
+            let d_lon = (lon2 - lon1 + 540.0).rem_euclid(360.0) - 180.0;
+            d_lon.to_radians() * (2.0 + lat1.to_radians().sin() + lat2.to_radians().sin())
+        })
+        .sum();
+    (sum * EARTH_RADIUS_KM * EARTH_RADIUS_KM / 2.0).abs()
+}
+
+/// Formats a length in metres below one kilometre, in kilometres above.
+pub fn format_length(km: f64) -> String {
+    if km < 1.0 {
+        format!("{:.0} m", km * 1000.0)
+    } else {
+        format!("{:.2} km", km)
+    }
+}
+
+/// Formats an area in square metres below a square kilometre, in square
+/// kilometres above.
+pub fn format_area(km2: f64) -> String {
+    if km2 < 1.0 {
+        format!("{:.0} m²", km2 * 1_000_000.0)
+    } else {
+        format!("{:.2} km²", km2)
+    }
+}
+
+/// Great-circle length in kilometres of the lines in `value`; points and
+/// polygons count as zero.
+pub fn geometry_length_km(value: &Value) -> f64 {
+    match value {
+        Value::LineString(line) => length_km(&positions(line), false),
+        Value::MultiLineString(lines) => lines
+            .iter()
+            .map(|line| length_km(&positions(line), false))
+            .sum(),
+        Value::GeometryCollection(geometries) => geometries
+            .iter()
+            .map(|geometry| geometry_length_km(&geometry.value))
+            .sum(),
+        _ => 0.0,
+    }
+}
+
+/// Spherical area in square kilometres of the polygons in `value`, holes
+/// left out; points and lines count as zero.
+pub fn geometry_area_km2(value: &Value) -> f64 {
+    let polygon_area = |rings: &[Vec<Vec<f64>>]| -> f64 {
+        let mut areas = rings.iter().map(|ring| area_km2(&positions(ring)));
+        let outer = areas.next().unwrap_or(0.0);
+        (outer - areas.sum::<f64>()).max(0.0)
+    };
+    match value {
+        Value::Polygon(rings) => polygon_area(rings),
+        Value::MultiPolygon(polygons) => polygons.iter().map(|rings| polygon_area(rings)).sum(),
+        Value::GeometryCollection(geometries) => geometries
+            .iter()
+            .map(|geometry| geometry_area_km2(&geometry.value))
+            .sum(),
+        _ => 0.0,
+    }
+}
+
+// The (lon, lat) of `coordinates`, leaving out positions with fewer than two
+// values. A ring keeps its closing position, whose edge adds no area.
+fn positions(coordinates: &[Vec<f64>]) -> Vec<(f64, f64)> {
+    coordinates
+        .iter()
+        .filter(|q| q.len() >= 2)
+        .map(|q| (q[0], q[1]))
+        .collect()
+}
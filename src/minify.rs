@@ -0,0 +1,89 @@
+// minify.rs
+
+use geojson::{Feature, Geometry, Value};
+
+/// Decimal places kept when none are typed; 6 is about 10 cm in degrees.
+pub const DEFAULT_DECIMALS: u32 = 6;
+
+// An f64 holds no more decimals of a longitude than this
+const MAX_DECIMALS: u32 = 15;
+
+/// Parses the number of decimal places to round coordinates to; empty text
+/// gives `DEFAULT_DECIMALS`.
+pub fn parse_decimals(text: &str) -> Result<u32, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(DEFAULT_DECIMALS);
+    }
+    match text.parse::<u32>() {
+        Ok(decimals) if decimals <= MAX_DECIMALS => Ok(decimals),
+        _ => Err(format!(
+            "'{}' is not a number of decimals from 0 to {}",
+            text, MAX_DECIMALS
+        )),
+    }
+}
+
+/// Rounds every coordinate of `features`, and their bounding boxes, to
+/// `decimals` places. Serialized without whitespace, as `loader::save_features`
+/// does, this is what makes a file small enough to publish on the web.
+pub fn round_features(features: &mut [Feature], decimals: u32) {
+    let factor = 10f64.powi(decimals as i32);
+    for feature in features {
+        if let Some(bbox) = &mut feature.bbox {
+            round_position(bbox, factor);
+        }
+        if let Some(geometry) = &mut feature.geometry {
+            round_geometry(geometry, factor);
+        }
+    }
+}
+
+fn round_geometry(geometry: &mut Geometry, factor: f64) {
+    if let Some(bbox) = &mut geometry.bbox {
+        round_position(bbox, factor);
+    }
+    match &mut geometry.value {
+        Value::Point(p) => round_position(p, factor),
+        Value::MultiPoint(points) | Value::LineString(points) => {
+            points.iter_mut().for_each(|p| round_position(p, factor))
+        }
+        Value::MultiLineString(lines) | Value::Polygon(lines) => lines
+            .iter_mut()
+            .flatten()
+            .for_each(|p| round_position(p, factor)),
+        Value::MultiPolygon(polygons) => polygons
+            .iter_mut()
+            .flatten()
+            .flatten()
+            .for_each(|p| round_position(p, factor)),
+        Value::GeometryCollection(geometries) => {
+            for geometry in geometries {
+                round_geometry(geometry, factor);
+            }
+        }
+    }
+}
+
+fn round_position(position: &mut [f64], factor: f64) {
+    for coordinate in position {
+        let rounded = (*coordinate * factor).round() / factor;
+        // Very large values (projected coordinates with many decimals) would
+        // overflow the scaling; they are kept as they are
+        if rounded.is_finite() {
+            // Adding zero turns -0.0 into 0.0, which is shorter
+            *coordinate = rounded + 0.0;
+        }
+    }
+}
+
+/// File size for messages, e.g. "840 B", "12.5 KB" or "3.2 MB".
+pub fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1 << 20 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1u64 << 20) as f64)
+    }
+}
@@ -0,0 +1,790 @@
+// osm.rs
+
+use flate2::read::ZlibDecoder;
+use geojson::{Feature, Geometry, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Read,
+    path::Path,
+    sync::RwLock,
+};
+
+use crate::clip;
+
+// Optional features of the OSMHeader block this reader understands
+const SUPPORTED_FEATURES: [&str; 2] = ["OsmSchema-V0.6", "DenseNodes"];
+
+// Keys making a closed way an area rather than a ring-shaped line
+const AREA_KEYS: [&str; 11] = [
+    "building",
+    "building:part",
+    "landuse",
+    "leisure",
+    "amenity",
+    "shop",
+    "tourism",
+    "historic",
+    "military",
+    "place",
+    "water",
+];
+
+// Elements read from extracts, as set by `set_tag_filter`; `None` reads all
+static TAG_FILTER: RwLock<Option<TagFilter>> = RwLock::new(None);
+
+/// Which elements of an extract are read: those with one of a list of tags,
+/// each a key such as `highway` or a pair such as `building=house`, so a city
+/// extract can be cut down to its roads, rivers and buildings while reading.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TagFilter {
+    tags: Vec<(String, Option<String>)>, // Key and, if only one is wanted, its value
+}
+
+impl TagFilter {
+    /// Parses a comma-separated list such as `highway,waterway,building`.
+    pub fn parse(text: &str) -> Result<TagFilter, String> {
+        let mut filter = TagFilter::default();
+        for item in text
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+        {
+            let (key, value) = match item.split_once('=') {
+                Some((key, value)) => (key.trim(), Some(value.trim().to_string())),
+                None => (item, None),
+            };
+            if key.is_empty() {
+                return Err(format!("OSM tag '{}' has no key", item));
+            }
+            filter.tags.push((key.to_string(), value));
+        }
+        Ok(filter)
+    }
+
+    /// Whether an element with `tags` is read; an empty filter reads all.
+    pub fn keeps(&self, tags: &[(String, String)]) -> bool {
+        self.tags.is_empty()
+            || tags.iter().any(|(key, value)| {
+                self.tags.iter().any(|(wanted, wanted_value)| {
+                    wanted == key && wanted_value.as_ref().is_none_or(|v| v == value)
+                })
+            })
+    }
+}
+
+/// Makes `read_osm_pbf` read only the elements `filter` keeps, from the
+/// `[osm] tags` config value or `--osm-tags` in batch mode.
+pub fn set_tag_filter(filter: TagFilter) {
+    *TAG_FILTER.write().unwrap_or_else(|e| e.into_inner()) = Some(filter);
+}
+
+/// The tagged nodes, ways and multipolygon relations of an OSM extract as
+/// features, with the elements that had no drawable geometry.
+#[derive(Default)]
+pub struct OsmFeatures {
+    pub features: Vec<Feature>,
+    pub skipped: Vec<String>, // e.g. "way/12: its nodes are not in the file"
+}
+
+/// Reads an `.osm.pbf` extract. Tagged nodes become points, tagged ways lines
+/// or, when closed and tagged as an area (`building`, `landuse`, `area=yes`,
+/// ...), polygons; multipolygon and boundary relations are assembled from
+/// their member ways. Tags are kept as properties, along with `@id` such as
+/// `way/123`, so features can be picked out with a layer filter. Only the
+/// elements kept by the filter given to `set_tag_filter` are read.
+pub fn read_osm_pbf(path: &Path) -> Result<OsmFeatures, String> {
+    let filter = TAG_FILTER
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default();
+    read_osm_pbf_with(path, filter)
+}
+
+/// Reads an `.osm.pbf` extract like `read_osm_pbf`, keeping the elements
+/// `filter` keeps.
+pub fn read_osm_pbf_with(path: &Path, filter: TagFilter) -> Result<OsmFeatures, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    read_blocks(&bytes, filter)
+}
+
+// The features of the blocks of an extract read into memory
+fn read_blocks(bytes: &[u8], filter: TagFilter) -> Result<OsmFeatures, String> {
+    let mut data = OsmData {
+        filter,
+        ..OsmData::default()
+    };
+    let mut at = 0;
+    while at < bytes.len() {
+        // Each block is a length-prefixed BlobHeader followed by its Blob
+        let header_length = bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_be_bytes(b.try_into().expect("4 bytes")) as usize)
+            .ok_or_else(|| format!("the file ends inside a block header at byte {}", at))?;
+        at += 4;
+        let header = slice(bytes, at, header_length)
+            .ok_or_else(|| format!("the file ends inside a block header at byte {}", at))?;
+        at += header_length;
+        let (mut kind, mut data_size) = (String::new(), 0);
+        let mut fields = Message::new(header);
+        while let Some((number, field)) = fields.next()? {
+            match (number, field) {
+                (1, Field::Bytes(text)) => kind = String::from_utf8_lossy(text).to_string(),
+                (3, Field::Varint(size)) => data_size = size as usize,
+                _ => {}
+            }
+        }
+        let blob = slice(bytes, at, data_size)
+            .ok_or_else(|| format!("the file ends inside a {} block at byte {}", kind, at))?;
+        at += data_size;
+        match kind.as_str() {
+            "OSMHeader" => check_header(&decompress(blob)?)?,
+            "OSMData" => data.read_block(&decompress(blob)?)?,
+            _ => {} // Unknown blocks may be skipped
+        }
+    }
+    Ok(data.into_features())
+}
+
+// The `length` bytes from `at`, if the data is that long; lengths read from a
+// file may be anything, so the end is computed without overflowing
+fn slice(bytes: &[u8], at: usize, length: usize) -> Option<&[u8]> {
+    bytes.get(at..at.checked_add(length)?)
+}
+
+// The contents of a Blob, stored raw or zlib-compressed
+fn decompress(blob: &[u8]) -> Result<Vec<u8>, String> {
+    let mut fields = Message::new(blob);
+    let mut raw_size = 0;
+    while let Some((number, field)) = fields.next()? {
+        match (number, field) {
+            (1, Field::Bytes(raw)) => return Ok(raw.to_vec()),
+            (2, Field::Varint(size)) => raw_size = size as usize,
+            (3, Field::Bytes(compressed)) => {
+                let mut data = Vec::with_capacity(raw_size);
+                ZlibDecoder::new(compressed)
+                    .read_to_end(&mut data)
+                    .map_err(|e| format!("corrupt zlib data: {}", e))?;
+                return Ok(data);
+            }
+            (4..=7, Field::Bytes(_)) => {
+                return Err(String::from(
+                    "only zlib-compressed blocks can be read; recompress the file with `osmium cat`",
+                ));
+            }
+            _ => {}
+        }
+    }
+    Err(String::from("a block has no data"))
+}
+
+fn check_header(header: &[u8]) -> Result<(), String> {
+    let mut fields = Message::new(header);
+    while let Some((number, field)) = fields.next()? {
+        if let (4, Field::Bytes(feature)) = (number, field) {
+            let feature = String::from_utf8_lossy(feature);
+            if !SUPPORTED_FEATURES.contains(&feature.as_ref()) {
+                return Err(format!("the file needs unsupported feature '{}'", feature));
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Way {
+    id: i64,
+    refs: Vec<i64>,
+    tags: Vec<(String, String)>,
+}
+
+struct Relation {
+    id: i64,
+    members: Vec<(i64, String)>, // Member ways and their roles; other members are left out
+    tags: Vec<(String, String)>,
+}
+
+#[derive(Default)]
+struct OsmData {
+    filter: TagFilter,
+    nodes: HashMap<i64, [f64; 2]>,
+    points: Vec<Feature>,
+    ways: Vec<Way>,
+    relations: Vec<Relation>,
+}
+
+impl OsmData {
+    // A PrimitiveBlock: a string table, groups of elements and the coordinate encoding
+    fn read_block(&mut self, block: &[u8]) -> Result<(), String> {
+        let mut strings = Vec::new();
+        let mut groups = Vec::new();
+        let mut grid = Grid {
+            granularity: 100,
+            lat_offset: 0,
+            lon_offset: 0,
+        };
+        let mut fields = Message::new(block);
+        while let Some((number, field)) = fields.next()? {
+            match (number, field) {
+                (1, Field::Bytes(table)) => {
+                    let mut entries = Message::new(table);
+                    while let Some((number, field)) = entries.next()? {
+                        if let (1, Field::Bytes(text)) = (number, field) {
+                            strings.push(String::from_utf8_lossy(text).to_string());
+                        }
+                    }
+                }
+                (2, Field::Bytes(group)) => groups.push(group),
+                (17, Field::Varint(value)) => grid.granularity = value as i64,
+                (19, Field::Varint(value)) => grid.lat_offset = value as i64,
+                (20, Field::Varint(value)) => grid.lon_offset = value as i64,
+                _ => {}
+            }
+        }
+        // The coordinate encoding is written after the groups, so they are read last
+        for group in groups {
+            let mut elements = Message::new(group);
+            while let Some((number, field)) = elements.next()? {
+                match (number, field) {
+                    (1, Field::Bytes(node)) => self.read_node(node, &strings, &grid)?,
+                    (2, Field::Bytes(dense)) => self.read_dense_nodes(dense, &strings, &grid)?,
+                    (3, Field::Bytes(way)) => self.read_way(way, &strings)?,
+                    (4, Field::Bytes(relation)) => self.read_relation(relation, &strings)?,
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_node(&mut self, id: i64, position: [f64; 2], tags: Vec<(String, String)>) {
+        self.nodes.insert(id, position);
+        if !tags.is_empty() && self.filter.keeps(&tags) {
+            let point = Value::Point(position.to_vec());
+            self.points
+                .push(feature(point, format!("node/{}", id), &tags));
+        }
+    }
+
+    fn read_node(&mut self, node: &[u8], strings: &[String], grid: &Grid) -> Result<(), String> {
+        let (mut id, mut lat, mut lon) = (0, 0, 0);
+        let (mut keys, mut values) = (Vec::new(), Vec::new());
+        let mut fields = Message::new(node);
+        while let Some((number, field)) = fields.next()? {
+            match (number, field) {
+                (1, Field::Varint(value)) => id = zigzag(value),
+                (2, Field::Bytes(packed)) => keys = varints(packed)?,
+                (3, Field::Bytes(packed)) => values = varints(packed)?,
+                (8, Field::Varint(value)) => lat = zigzag(value),
+                (9, Field::Varint(value)) => lon = zigzag(value),
+                _ => {}
+            }
+        }
+        let tags = tags(&keys, &values, strings)?;
+        self.add_node(id, grid.position(lat, lon), tags);
+        Ok(())
+    }
+
+    // Delta-coded ids and coordinates, with the tags of all nodes in one
+    // list of key and value indices where 0 ends the tags of a node
+    fn read_dense_nodes(
+        &mut self,
+        dense: &[u8],
+        strings: &[String],
+        grid: &Grid,
+    ) -> Result<(), String> {
+        let (mut ids, mut lats, mut lons, mut keys_values) =
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let mut fields = Message::new(dense);
+        while let Some((number, field)) = fields.next()? {
+            match (number, field) {
+                (1, Field::Bytes(packed)) => ids = deltas(packed)?,
+                (8, Field::Bytes(packed)) => lats = deltas(packed)?,
+                (9, Field::Bytes(packed)) => lons = deltas(packed)?,
+                (10, Field::Bytes(packed)) => keys_values = varints(packed)?,
+                _ => {}
+            }
+        }
+        if lats.len() != ids.len() || lons.len() != ids.len() {
+            return Err(String::from("dense nodes with missing coordinates"));
+        }
+        let mut pairs = keys_values.into_iter();
+        for i in 0..ids.len() {
+            let mut tags = Vec::new();
+            while let Some(key) = pairs.next().filter(|key| *key != 0) {
+                let value = pairs
+                    .next()
+                    .ok_or_else(|| format!("node/{} has a key without a value", ids[i]))?;
+                tags.push((string(strings, key)?, string(strings, value)?));
+            }
+            self.add_node(ids[i], grid.position(lats[i], lons[i]), tags);
+        }
+        Ok(())
+    }
+
+    fn read_way(&mut self, way: &[u8], strings: &[String]) -> Result<(), String> {
+        let mut id = 0;
+        let (mut keys, mut values, mut refs) = (Vec::new(), Vec::new(), Vec::new());
+        let mut fields = Message::new(way);
+        while let Some((number, field)) = fields.next()? {
+            match (number, field) {
+                (1, Field::Varint(value)) => id = value as i64,
+                (2, Field::Bytes(packed)) => keys = varints(packed)?,
+                (3, Field::Bytes(packed)) => values = varints(packed)?,
+                (8, Field::Bytes(packed)) => refs = deltas(packed)?,
+                _ => {}
+            }
+        }
+        let tags = tags(&keys, &values, strings)?;
+        self.ways.push(Way { id, refs, tags });
+        Ok(())
+    }
+
+    fn read_relation(&mut self, relation: &[u8], strings: &[String]) -> Result<(), String> {
+        let mut id = 0;
+        let (mut keys, mut values) = (Vec::new(), Vec::new());
+        let (mut roles, mut member_ids, mut types) = (Vec::new(), Vec::new(), Vec::new());
+        let mut fields = Message::new(relation);
+        while let Some((number, field)) = fields.next()? {
+            match (number, field) {
+                (1, Field::Varint(value)) => id = value as i64,
+                (2, Field::Bytes(packed)) => keys = varints(packed)?,
+                (3, Field::Bytes(packed)) => values = varints(packed)?,
+                (8, Field::Bytes(packed)) => roles = varints(packed)?,
+                (9, Field::Bytes(packed)) => member_ids = deltas(packed)?,
+                (10, Field::Bytes(packed)) => types = varints(packed)?,
+                _ => {}
+            }
+        }
+        let tags = tags(&keys, &values, strings)?;
+        let is_area = tags
+            .iter()
+            .any(|(k, v)| k == "type" && (v == "multipolygon" || v == "boundary"));
+        if !is_area {
+            return Ok(());
+        }
+        let mut members = Vec::new();
+        for ((member, role), kind) in member_ids.iter().zip(&roles).zip(&types) {
+            // Types are 0 for nodes, 1 for ways and 2 for relations
+            if *kind == 1 {
+                members.push((*member, string(strings, *role)?));
+            }
+        }
+        self.relations.push(Relation { id, members, tags });
+        Ok(())
+    }
+
+    // Builds the geometries of ways and relations once all nodes are known
+    fn into_features(self) -> OsmFeatures {
+        let mut result = OsmFeatures {
+            features: self.points,
+            skipped: Vec::new(),
+        };
+        let mut member_refs: HashMap<i64, &[i64]> = HashMap::new();
+        let wanted: HashSet<i64> = self
+            .relations
+            .iter()
+            .flat_map(|relation| relation.members.iter().map(|(id, _)| *id))
+            .collect();
+        for way in &self.ways {
+            if wanted.contains(&way.id) {
+                member_refs.insert(way.id, &way.refs);
+            }
+            // Ways left out are still read as members of relations
+            if way.tags.is_empty() || !self.filter.keeps(&way.tags) {
+                continue;
+            }
+            let positions: Vec<Vec<f64>> = way
+                .refs
+                .iter()
+                .filter_map(|id| self.nodes.get(id))
+                .map(|position| position.to_vec())
+                .collect();
+            let id = format!("way/{}", way.id);
+            if positions.len() < 2 {
+                result
+                    .skipped
+                    .push(format!("{}: its nodes are not in the file", id));
+                continue;
+            }
+            let complete = positions.len() == way.refs.len();
+            let closed = way.refs.len() > 3 && way.refs.first() == way.refs.last();
+            let geometry = if complete && closed && is_area(&way.tags) {
+                Value::Polygon(vec![positions])
+            } else {
+                Value::LineString(positions)
+            };
+            result.features.push(feature(geometry, id, &way.tags));
+        }
+
+        for relation in self.relations.iter().filter(|r| self.filter.keeps(&r.tags)) {
+            let id = format!("relation/{}", relation.id);
+            let rings = |inner: bool| {
+                let parts: Option<Vec<&[i64]>> = relation
+                    .members
+                    .iter()
+                    .filter(|(_, role)| (role == "inner") == inner)
+                    .map(|(member, _)| member_refs.get(member).copied())
+                    .collect();
+                join_rings(parts?)?
+                    .iter()
+                    .map(|ring| {
+                        ring.iter()
+                            .map(|node| self.nodes.get(node).copied())
+                            .collect::<Option<Vec<_>>>()
+                    })
+                    .collect::<Option<Vec<_>>>()
+            };
+            let (Some(outers), Some(inners)) = (rings(false), rings(true)) else {
+                result
+                    .skipped
+                    .push(format!("{}: its member ways are not all in the file", id));
+                continue;
+            };
+            if outers.is_empty() {
+                result.skipped.push(format!("{}: it has no outer ring", id));
+                continue;
+            }
+            let mut polygons: Vec<Vec<Vec<[f64; 2]>>> =
+                outers.into_iter().map(|outer| vec![outer]).collect();
+            // Holes go into the first outer ring around them
+            for inner in inners {
+                if let Some(polygon) = polygons
+                    .iter_mut()
+                    .find(|polygon| clip::contains(&polygon[..1], inner[0]))
+                {
+                    polygon.push(inner);
+                }
+            }
+            let mut polygons: Vec<Vec<Vec<Vec<f64>>>> = polygons
+                .into_iter()
+                .map(|rings| {
+                    rings
+                        .into_iter()
+                        .map(|ring| ring.iter().map(|p| p.to_vec()).collect())
+                        .collect()
+                })
+                .collect();
+            let geometry = if polygons.len() == 1 {
+                Value::Polygon(polygons.remove(0))
+            } else {
+                Value::MultiPolygon(polygons)
+            };
+            result.features.push(feature(geometry, id, &relation.tags));
+        }
+        result
+    }
+}
+
+// Joins ways end to end into closed rings of node ids, or `None` if some way
+// cannot be closed, as happens with relations cut off at the extract edge
+fn join_rings(mut parts: Vec<&[i64]>) -> Option<Vec<Vec<i64>>> {
+    let mut rings = Vec::new();
+    while let Some(first) = parts.pop() {
+        let mut ring = first.to_vec();
+        while ring.len() < 4 || ring.first() != ring.last() {
+            let end = *ring.last()?;
+            let next = parts
+                .iter()
+                .position(|part| part.first() == Some(&end) || part.last() == Some(&end))?;
+            let part = parts.swap_remove(next);
+            if part.first() == Some(&end) {
+                ring.extend(&part[1..]);
+            } else {
+                ring.extend(part.iter().rev().skip(1));
+            }
+        }
+        rings.push(ring);
+    }
+    Some(rings)
+}
+
+fn is_area(tags: &[(String, String)]) -> bool {
+    tags.iter().any(|(key, value)| match key.as_str() {
+        "area" => value == "yes",
+        "natural" => !matches!(value.as_str(), "coastline" | "cliff" | "ridge" | "tree_row"),
+        "waterway" => matches!(value.as_str(), "riverbank" | "dock"),
+        _ => AREA_KEYS.contains(&key.as_str()),
+    }) && !tags
+        .iter()
+        .any(|(key, value)| key == "area" && value == "no")
+}
+
+fn feature(geometry: Value, id: String, tags: &[(String, String)]) -> Feature {
+    let mut properties = serde_json::Map::new();
+    properties.insert(String::from("@id"), id.into());
+    for (key, value) in tags {
+        properties.insert(key.clone(), value.clone().into());
+    }
+    Feature {
+        geometry: Some(Geometry::new(geometry)),
+        properties: Some(properties),
+        ..Feature::default()
+    }
+}
+
+// How a block stores coordinates: nanodegrees = offset + granularity * value
+struct Grid {
+    granularity: i64,
+    lat_offset: i64,
+    lon_offset: i64,
+}
+
+impl Grid {
+    fn position(&self, lat: i64, lon: i64) -> [f64; 2] {
+        [
+            (self.lon_offset + self.granularity * lon) as f64 * 1e-9,
+            (self.lat_offset + self.granularity * lat) as f64 * 1e-9,
+        ]
+    }
+}
+
+fn tags(keys: &[u64], values: &[u64], strings: &[String]) -> Result<Vec<(String, String)>, String> {
+    keys.iter()
+        .zip(values)
+        .map(|(key, value)| Ok((string(strings, *key)?, string(strings, *value)?)))
+        .collect()
+}
+
+fn string(strings: &[String], index: u64) -> Result<String, String> {
+    strings
+        .get(index as usize)
+        .cloned()
+        .ok_or_else(|| format!("string {} is not in the string table", index))
+}
+
+// Protocol Buffers field contents, by wire type
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed, // 32 and 64 bit values, which OSM data does not use
+}
+
+// The fields of an encoded Protocol Buffers message, in order
+struct Message<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Message<'a> {
+    fn new(bytes: &'a [u8]) -> Message<'a> {
+        Message { bytes, at: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .bytes
+                .get(self.at)
+                .ok_or_else(|| String::from("a message ends inside a number"))?;
+            self.at += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(String::from("a number is longer than 64 bits"))
+    }
+
+    fn next(&mut self) -> Result<Option<(u64, Field<'a>)>, String> {
+        if self.at >= self.bytes.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let field = match key & 7 {
+            0 => Field::Varint(self.varint()?),
+            1 | 5 => {
+                self.at += if key & 7 == 1 { 8 } else { 4 };
+                Field::Fixed
+            }
+            2 => {
+                let length = self.varint()? as usize;
+                let bytes = slice(self.bytes, self.at, length)
+                    .ok_or_else(|| String::from("a message ends inside a field"))?;
+                self.at += length;
+                Field::Bytes(bytes)
+            }
+            other => return Err(format!("unknown wire type {}", other)),
+        };
+        Ok(Some((key >> 3, field)))
+    }
+}
+
+// A packed repeated field of unsigned numbers
+fn varints(packed: &[u8]) -> Result<Vec<u64>, String> {
+    let mut message = Message::new(packed);
+    let mut values = Vec::new();
+    while message.at < packed.len() {
+        values.push(message.varint()?);
+    }
+    Ok(values)
+}
+
+// A packed repeated field of signed numbers, each stored as the difference to the previous one
+fn deltas(packed: &[u8]) -> Result<Vec<i64>, String> {
+    let mut last = 0i64;
+    Ok(varints(packed)?
+        .into_iter()
+        .map(|value| {
+            last += zigzag(value);
+            last
+        })
+        .collect())
+}
+
+fn zigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        while value >= 0x80 {
+            bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        bytes.push(value as u8);
+        bytes
+    }
+
+    fn number(field: u64, value: u64) -> Vec<u8> {
+        [varint(field << 3), varint(value)].concat()
+    }
+
+    fn bytes(field: u64, data: &[u8]) -> Vec<u8> {
+        [
+            varint(field << 3 | 2),
+            varint(data.len() as u64),
+            data.to_vec(),
+        ]
+        .concat()
+    }
+
+    fn packed(field: u64, values: &[u64]) -> Vec<u8> {
+        bytes(
+            field,
+            &values.iter().flat_map(|v| varint(*v)).collect::<Vec<u8>>(),
+        )
+    }
+
+    fn signed(value: i64) -> u64 {
+        ((value << 1) ^ (value >> 63)) as u64
+    }
+
+    // An extract of one uncompressed data block
+    fn extract(block: Vec<u8>) -> Vec<u8> {
+        let blob = bytes(1, &block);
+        let header = [bytes(1, b"OSMData"), number(3, blob.len() as u64)].concat();
+        [(header.len() as u32).to_be_bytes().to_vec(), header, blob].concat()
+    }
+
+    // A street, a building and a café, each with the tags given by string index
+    fn city_block() -> Vec<u8> {
+        let strings: Vec<&str> = vec![
+            "",
+            "highway",
+            "residential",
+            "building",
+            "yes",
+            "amenity",
+            "cafe",
+        ];
+        let table: Vec<u8> = strings
+            .iter()
+            .flat_map(|s| bytes(1, s.as_bytes()))
+            .collect();
+        let node = |id: i64, lat: i64, lon: i64, tags: &[u64]| {
+            let (keys, values): (Vec<u64>, Vec<u64>) =
+                tags.chunks(2).map(|pair| (pair[0], pair[1])).unzip();
+            bytes(
+                1,
+                &[
+                    number(1, signed(id)),
+                    packed(2, &keys),
+                    packed(3, &values),
+                    number(8, signed(lat)),
+                    number(9, signed(lon)),
+                ]
+                .concat(),
+            )
+        };
+        let way = |id: u64, tags: [u64; 2], refs: &[i64]| {
+            let mut last = 0;
+            let deltas: Vec<u64> = refs
+                .iter()
+                .map(|r| {
+                    let delta = r - last;
+                    last = *r;
+                    signed(delta)
+                })
+                .collect();
+            bytes(
+                3,
+                &[
+                    number(1, id),
+                    packed(2, &[tags[0]]),
+                    packed(3, &[tags[1]]),
+                    packed(8, &deltas),
+                ]
+                .concat(),
+            )
+        };
+        let group = [
+            node(1, 0, 0, &[]),
+            node(2, 0, 1000, &[]),
+            node(3, 1000, 1000, &[]),
+            node(4, 1000, 0, &[5, 6]),
+            way(10, [1, 2], &[1, 2]),
+            way(11, [3, 4], &[1, 2, 3, 4, 1]),
+        ]
+        .concat();
+        [bytes(1, &table), bytes(2, &group)].concat()
+    }
+
+    fn ids(osm: &OsmFeatures) -> Vec<String> {
+        osm.features
+            .iter()
+            .map(|f| f.property("@id").unwrap().as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn extracts_are_read_into_points_lines_and_polygons() {
+        let osm = read_blocks(&extract(city_block()), TagFilter::default()).unwrap();
+        assert_eq!(ids(&osm), vec!["node/4", "way/10", "way/11"]);
+        let kinds: Vec<&str> = osm
+            .features
+            .iter()
+            .map(|f| f.geometry.as_ref().unwrap().value.type_name())
+            .collect();
+        assert_eq!(kinds, vec!["Point", "LineString", "Polygon"]);
+        assert_eq!(osm.features[0].property("amenity").unwrap(), "cafe");
+    }
+
+    #[test]
+    fn only_elements_with_the_chosen_tags_are_read() {
+        let read = |tags: &str| {
+            let filter = TagFilter::parse(tags).unwrap();
+            ids(&read_blocks(&extract(city_block()), filter).unwrap())
+        };
+        assert_eq!(read("highway, building"), vec!["way/10", "way/11"]);
+        assert_eq!(read("building=yes"), vec!["way/11"]);
+        assert_eq!(read("building=house"), Vec::<String>::new());
+        assert_eq!(read("amenity"), vec!["node/4"]);
+        assert!(TagFilter::parse("=cafe").is_err());
+    }
+
+    #[test]
+    fn lengths_past_the_end_are_errors() {
+        // A field claiming the largest length there is
+        let field = [varint(1 << 3 | 2), varint(u64::MAX)].concat();
+        assert!(Message::new(&field).next().is_err());
+        let mut file = extract(city_block());
+        file.truncate(file.len() - 10);
+        assert!(read_blocks(&file, TagFilter::default()).is_err());
+    }
+}
@@ -0,0 +1,2935 @@
+// plot.rs
+
+use geojson::{Feature, GeoJson, Geometry, Value};
+use plotters::coord::{Shift, cartesian::Cartesian2d, types::RangedCoordf64};
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    error::Error,
+    fs, io,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+
+use crate::basemap::Basemap;
+use crate::checkpoint::Checkpoint;
+use crate::clip;
+use crate::cluster::{self, Cluster};
+use crate::crs;
+use crate::filter::FeatureFilter;
+use crate::geocode;
+use crate::inset::{self, Inset, InsetCorner};
+use crate::loader;
+use crate::lod::LevelsOfDetail;
+use crate::projection::Projection;
+use crate::ramp;
+use crate::raster::Raster;
+use crate::simplify;
+use crate::style::{self, Legend, SizeLegend, StyleRule, Styler};
+use crate::temporal::TimeRange;
+
+// Colors cycled through when assigning a color to each plotted file
+pub const PLOT_COLORS: [RGBColor; 7] = [
+    RGBColor(0, 0, 0),     // Black
+    RGBColor(255, 0, 0),   // Red
+    RGBColor(0, 255, 0),   // Green
+    RGBColor(0, 0, 255),   // Blue
+    RGBColor(255, 255, 0), // Yellow
+    RGBColor(255, 0, 255), // Magenta
+    RGBColor(0, 255, 255), // Cyan
+];
+
+type GeoChart<'a, 'b> =
+    ChartContext<'a, BitMapBackend<'b>, Cartesian2d<RangedCoordf64, RangedCoordf64>>;
+
+// Helper function to read GeoJSON
+pub fn read_geojson(filepath: &str) -> Result<GeoJson, Box<dyn Error>> {
+    let file = fs::File::open(filepath)?;
+    let reader = io::BufReader::new(file);
+    let geojson = GeoJson::from_reader(reader)?;
+    Ok(geojson)
+}
+
+/// Flattens any GeoJSON object into a list of features.
+pub fn into_features(geojson: GeoJson) -> Vec<Feature> {
+    match geojson {
+        GeoJson::FeatureCollection(collection) => collection.features,
+        GeoJson::Feature(feature) => vec![feature],
+        GeoJson::Geometry(geometry) => vec![Feature::from(geometry)],
+    }
+}
+
+/// A GeoJSON file loaded into memory together with the color it is drawn in.
+#[derive(Clone)]
+pub struct PlotLayer {
+    pub features: Vec<Feature>,
+    pub color: RGBColor,
+    pub simplify_tolerance: Option<f64>, // Overrides `PlotOptions::simplify_tolerance`
+    pub line_width: Option<u32>,         // Stroke width in pixels (default: 1)
+    pub line_style: Option<LineStyle>,   // Dash pattern of lines and outlines (default: solid)
+    pub point_size: Option<u32>,         // Point radius in pixels (default: 5)
+    pub point_shape: Option<PointShape>, // Symbol drawn for points (default: circle)
+    // From 0 (invisible) to 1 (the default); translucent layers also fill their
+    // polygons so that overlapping layers blend
+    pub opacity: Option<f64>,
+    // Drawn on top of the `PlotOptions` toggles; `None` draws every type
+    pub geometry_types: Option<GeometryTypes>,
+    // Simplified copies of `features` drawn instead when simplifying; share it
+    // between renders of the same features to build them only once
+    pub levels: Option<Arc<LevelsOfDetail>>,
+}
+
+/// Dash pattern of a layer's lines and polygon outlines.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineStyle {
+    Solid,
+    Dashed,
+    Dotted,
+    DashDot,
+}
+
+impl LineStyle {
+    pub const ALL: [LineStyle; 4] = [
+        LineStyle::Solid,
+        LineStyle::Dashed,
+        LineStyle::Dotted,
+        LineStyle::DashDot,
+    ];
+
+    pub fn parse(text: &str) -> Option<LineStyle> {
+        match text.trim().to_lowercase().as_str() {
+            "solid" => Some(LineStyle::Solid),
+            "dashed" | "dash" => Some(LineStyle::Dashed),
+            "dotted" | "dot" => Some(LineStyle::Dotted),
+            "dash-dot" | "dash_dot" | "dashdot" => Some(LineStyle::DashDot),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineStyle::Solid => "solid",
+            LineStyle::Dashed => "dashed",
+            LineStyle::Dotted => "dotted",
+            LineStyle::DashDot => "dash-dot",
+        }
+    }
+
+    // Lengths of the dashes and gaps in turn, in multiples of the line width
+    fn pattern(self) -> &'static [f64] {
+        match self {
+            LineStyle::Solid => &[],
+            LineStyle::Dashed => &[8.0, 5.0],
+            LineStyle::Dotted => &[1.5, 3.0],
+            LineStyle::DashDot => &[8.0, 3.0, 1.5, 3.0],
+        }
+    }
+}
+
+/// Symbol a layer draws for its points.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PointShape {
+    Circle,
+    Square,
+    Triangle,
+    Cross,
+}
+
+impl PointShape {
+    pub const ALL: [PointShape; 4] = [
+        PointShape::Circle,
+        PointShape::Square,
+        PointShape::Triangle,
+        PointShape::Cross,
+    ];
+
+    pub fn parse(text: &str) -> Option<PointShape> {
+        match text.trim().to_lowercase().as_str() {
+            "circle" => Some(PointShape::Circle),
+            "square" => Some(PointShape::Square),
+            "triangle" => Some(PointShape::Triangle),
+            "cross" => Some(PointShape::Cross),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PointShape::Circle => "circle",
+            PointShape::Square => "square",
+            PointShape::Triangle => "triangle",
+            PointShape::Cross => "cross",
+        }
+    }
+}
+
+/// Which kinds of geometry a layer draws.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeometryTypes {
+    pub points: bool,
+    pub lines: bool,
+    pub polygons: bool,
+}
+
+impl GeometryTypes {
+    pub const ALL: GeometryTypes = GeometryTypes {
+        points: true,
+        lines: true,
+        polygons: true,
+    };
+
+    /// Parses a comma-separated list such as `points,lines`; `none` hides
+    /// every type.
+    pub fn parse(text: &str) -> Result<GeometryTypes, String> {
+        let mut types = GeometryTypes {
+            points: false,
+            lines: false,
+            polygons: false,
+        };
+        for name in text.split(',').map(str::trim) {
+            match name.to_lowercase().as_str() {
+                "points" | "point" => types.points = true,
+                "lines" | "line" => types.lines = true,
+                "polygons" | "polygon" => types.polygons = true,
+                "all" => types = GeometryTypes::ALL,
+                "none" | "" => {}
+                other => {
+                    return Err(format!(
+                        "Unknown geometry type '{}' (use points, lines, polygons, all or none)",
+                        other
+                    ));
+                }
+            }
+        }
+        Ok(types)
+    }
+
+    /// The shown types in the syntax of `parse`.
+    pub fn names(self) -> String {
+        let names: Vec<&str> = [
+            (self.points, "points"),
+            (self.lines, "lines"),
+            (self.polygons, "polygons"),
+        ]
+        .iter()
+        .filter(|(shown, _)| *shown)
+        .map(|(_, name)| *name)
+        .collect();
+        if names.is_empty() {
+            String::from("none")
+        } else {
+            names.join(",")
+        }
+    }
+
+    /// Types shown by both `self` and `other`.
+    pub fn and(self, other: GeometryTypes) -> GeometryTypes {
+        GeometryTypes {
+            points: self.points && other.points,
+            lines: self.lines && other.lines,
+            polygons: self.polygons && other.polygons,
+        }
+    }
+}
+
+pub const DEFAULT_LINE_WIDTH: u32 = 1;
+pub const DEFAULT_POINT_SIZE: u32 = 5;
+/// Largest line width or point size a layer can be given.
+pub const MAX_SYMBOL_SIZE: u32 = 30;
+
+impl PlotLayer {
+    /// Reads and parses the GeoJSON (or GeoJSONL) file at `path`.
+    pub fn load(path: &Path, color: RGBColor) -> Result<PlotLayer, Box<dyn Error>> {
+        Ok(PlotLayer {
+            features: loader::read_features(path)?,
+            color,
+            simplify_tolerance: None,
+            line_width: None,
+            line_style: None,
+            point_size: None,
+            point_shape: None,
+            opacity: None,
+            geometry_types: None,
+            levels: None,
+        })
+    }
+
+    /// Wraps already parsed GeoJSON, e.g. built in memory by an embedding program.
+    pub fn from_geojson(geojson: GeoJson, color: RGBColor) -> PlotLayer {
+        PlotLayer {
+            features: into_features(geojson),
+            color,
+            simplify_tolerance: None,
+            line_width: None,
+            line_style: None,
+            point_size: None,
+            point_shape: None,
+            opacity: None,
+            geometry_types: None,
+            levels: None,
+        }
+    }
+}
+
+/// A labeled location drawn on top of all layers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapMarker {
+    pub lon: f64,
+    pub lat: f64,
+    pub label: String,
+}
+
+impl MapMarker {
+    /// Parses `"lat, lon"` (as copied from Google Maps) or `"lon lat"`,
+    /// optionally followed by `; label`.
+    pub fn parse(text: &str) -> Result<MapMarker, String> {
+        let (coordinates, label) = match text.split_once(';') {
+            Some((coordinates, label)) => (coordinates.trim(), label.trim()),
+            None => (text.trim(), ""),
+        };
+        let parse_number = |s: &str| {
+            s.trim()
+                .parse::<f64>()
+                .map_err(|_| format!("'{}' is not a number", s.trim()))
+        };
+        let (lon, lat) = match coordinates.split_once(',') {
+            Some((lat, lon)) => (parse_number(lon)?, parse_number(lat)?),
+            None => match coordinates.split_whitespace().collect::<Vec<_>>()[..] {
+                [lon, lat] => (parse_number(lon)?, parse_number(lat)?),
+                _ => return Err(String::from("Expected \"lat, lon\" or \"lon lat\"")),
+            },
+        };
+        if !(-180.0..=180.0).contains(&lon) || !(-90.0..=90.0).contains(&lat) {
+            return Err(format!(
+                "Coordinates out of range: lon {}, lat {}",
+                lon, lat
+            ));
+        }
+        Ok(MapMarker {
+            lon,
+            lat,
+            label: label.to_string(),
+        })
+    }
+}
+
+pub const DEFAULT_TITLE: &str = "GeoJSON Plot";
+const OCEAN_COLOR: RGBColor = RGBColor(173, 216, 230); // Light blue map background
+const OUTSIDE_SHADE: RGBColor = RGBColor(200, 200, 200); // Fill outside a shaded clip mask
+
+/// Polygons outside of which nothing is drawn.
+#[derive(Debug, Clone)]
+pub struct ClipMask {
+    rings: Vec<Vec<Vec<f64>>>, // Exterior rings and holes of every mask polygon
+    bbox: [f64; 4],
+    pub shade_outside: bool, // Fill the outside grey instead of with the background
+}
+
+impl ClipMask {
+    /// Builds a mask from the polygons of `features`, or `None` if there are none.
+    pub fn from_features(features: &[Feature], shade_outside: bool) -> Option<ClipMask> {
+        let mut rings = Vec::new();
+        let mut bbox = empty_bbox();
+        for geometry in features.iter().filter_map(|f| f.geometry.as_ref()) {
+            let polygons = match &geometry.value {
+                Value::Polygon(polygon) => std::slice::from_ref(polygon),
+                Value::MultiPolygon(polygons) => polygons.as_slice(),
+                _ => continue,
+            };
+            for ring in polygons.iter().flatten().filter(|ring| ring.len() >= 3) {
+                extend_bbox(&mut bbox, &Value::LineString(ring.clone()));
+                rings.push(ring.clone());
+            }
+        }
+        (!rings.is_empty()).then_some(ClipMask {
+            rings,
+            bbox,
+            shade_outside,
+        })
+    }
+
+    /// Copies of `layers` holding only the parts of their features inside the
+    /// mask, so outlines, labels and classes follow the cut geometry.
+    pub fn clip_layers(&self, layers: &[PlotLayer]) -> Vec<PlotLayer> {
+        let mask = self.index();
+        layers
+            .iter()
+            .map(|layer| PlotLayer {
+                features: clip::clip_features(&layer.features, &mask),
+                levels: None,
+                ..layer.clone()
+            })
+            .collect()
+    }
+
+    /// The parts of `features` inside the mask.
+    pub fn clip(&self, features: &[Feature]) -> Vec<Feature> {
+        clip::clip_features(features, &self.index())
+    }
+
+    fn index(&self) -> clip::Mask {
+        clip::Mask::new(&self.rings, self.bbox)
+    }
+
+    // The same mask in Web Mercator metres
+    fn to_web_mercator(&self) -> ClipMask {
+        let project = |position: &Vec<f64>| {
+            let (x, y) = crs::lonlat_to_web_mercator(position[0], position[1]);
+            vec![x, y]
+        };
+        let (min_x, min_y) = crs::lonlat_to_web_mercator(self.bbox[0], self.bbox[1]);
+        let (max_x, max_y) = crs::lonlat_to_web_mercator(self.bbox[2], self.bbox[3]);
+        ClipMask {
+            rings: self
+                .rings
+                .iter()
+                .map(|ring| ring.iter().map(project).collect())
+                .collect(),
+            bbox: [min_x, min_y, max_x, max_y],
+            shade_outside: self.shade_outside,
+        }
+    }
+
+    // The same mask in the coordinates of a polar projection, cut to its hemisphere
+    fn projected(&self, projection: Projection) -> ClipMask {
+        let polygons: Vec<Feature> = self
+            .rings
+            .iter()
+            .map(|ring| Feature::from(Geometry::new(Value::Polygon(vec![ring.clone()]))))
+            .collect();
+        let mut rings = Vec::new();
+        let mut bbox = empty_bbox();
+        for feature in projection.project_features(&polygons) {
+            let Some(geometry) = feature.geometry else {
+                continue;
+            };
+            extend_bbox(&mut bbox, &geometry.value);
+            match geometry.value {
+                Value::Polygon(polygon) => rings.extend(polygon),
+                Value::MultiPolygon(polygons) => rings.extend(polygons.into_iter().flatten()),
+                _ => {}
+            }
+        }
+        ClipMask {
+            rings,
+            bbox,
+            shade_outside: self.shade_outside,
+        }
+    }
+
+    // Longitudes at which the mask boundary crosses `lat`, sorted; between
+    // pairs of crossings lies the inside of the mask (even-odd rule)
+    fn crossings(&self, lat: f64) -> Vec<f64> {
+        let mut crossings = Vec::new();
+        for ring in &self.rings {
+            for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+                if (a[1] > lat) != (b[1] > lat) {
+                    crossings.push(a[0] + (lat - a[1]) / (b[1] - a[1]) * (b[0] - a[0]));
+                }
+            }
+        }
+        crossings.sort_by(f64::total_cmp);
+        crossings
+    }
+}
+
+/// Background of a figure-mode image.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FigureBackground {
+    White,
+    Transparent,
+}
+
+impl FigureBackground {
+    pub fn parse(text: &str) -> Option<FigureBackground> {
+        match text {
+            "white" | "plain" => Some(FigureBackground::White),
+            "transparent" => Some(FigureBackground::Transparent),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FigureBackground::White => "white",
+            FigureBackground::Transparent => "transparent",
+        }
+    }
+}
+
+/// Settings that apply to the whole output image.
+pub struct PlotOptions {
+    pub caption: String,     // Title above the map; empty for none
+    pub subtitle: String,    // Smaller line below the title
+    pub attribution: String, // Source/credits line in the lower right
+    pub width: u32,
+    pub height: u32,
+    pub plot_points: bool,
+    pub plot_lines: bool,
+    pub plot_polygons: bool,
+    // Explicit [min_lon, min_lat, max_lon, max_lat], or `None` for the padded data bbox
+    pub extent: Option<[f64; 4]>,
+    pub markers: Vec<MapMarker>, // Drawn above all layers and included in the extent
+    pub style_rules: Vec<StyleRule>, // Attribute-based filters, colors and sizes
+    // Geometry only (no axes, mesh, margins or caption) in an image with the aspect
+    // ratio of the extent, fitted into `width` x `height`
+    pub figure: Option<FigureBackground>,
+    // Douglas-Peucker tolerance in coordinate units for layers without their own (0 = off)
+    pub simplify_tolerance: f64,
+    // Derive that tolerance from the size of an output pixel instead
+    pub auto_simplify: bool,
+    pub scale_bar: bool,               // Ground distance bar in the lower right
+    pub north_arrow: bool,             // North arrow in the upper right
+    pub basemap: Option<Basemap>,      // Raster tiles drawn underneath the layers
+    pub underlay: Option<Arc<Raster>>, // Georeferenced image drawn above the basemap
+    // Features are cut to the mask before drawing; also sets the extent unless one is given
+    pub clip_mask: Option<ClipMask>,
+    // Index of the layer drawn in full color on top; all others are drawn muted grey
+    pub focus_layer: Option<usize>,
+    // Longest side in pixels of a thumbnail written next to every image (see `thumbnail_path`)
+    pub thumbnail: Option<u32>,
+    // Layers left out because they could not be read, as "file: error"
+    pub failed_layers: Vec<String>,
+    pub warning_banner: bool, // List the failed layers in a strip across the top of the image
+    // Counts the features drawn from layers in memory; cancelling it fails the render
+    pub progress: Option<Arc<RenderProgress>>,
+    // Layout of the map; tolerances, the extent and markers stay in degrees
+    pub projection: Projection,
+    // Grow the extent to the proportions of the image, so both axes have the same ground scale
+    pub keep_aspect: bool,
+    pub time_range: Option<TimeRange>, // Only features without a time or one inside it are drawn
+    // Point features closer than this many pixels are drawn as one marker showing their number
+    pub cluster_distance: Option<u32>,
+    pub inset: Option<Inset>, // World overview with the extent outlined, in a corner of the map
+}
+
+impl Default for PlotOptions {
+    fn default() -> Self {
+        PlotOptions {
+            caption: String::from(DEFAULT_TITLE),
+            subtitle: String::new(),
+            attribution: String::new(),
+            width: 1024,
+            height: 768,
+            plot_points: true,
+            plot_lines: true,
+            plot_polygons: true,
+            extent: None,
+            markers: Vec::new(),
+            style_rules: Vec::new(),
+            figure: None,
+            simplify_tolerance: 0.0,
+            auto_simplify: false,
+            scale_bar: false,
+            north_arrow: false,
+            basemap: None,
+            underlay: None,
+            clip_mask: None,
+            focus_layer: None,
+            thumbnail: None,
+            failed_layers: Vec::new(),
+            warning_banner: false,
+            progress: None,
+            projection: Projection::LonLat,
+            keep_aspect: false,
+            time_range: None,
+            cluster_distance: None,
+            inset: None,
+        }
+    }
+}
+
+impl PlotOptions {
+    /// Geometry types shown by the P/L/O toggles.
+    pub fn geometry_types(&self) -> GeometryTypes {
+        GeometryTypes {
+            points: self.plot_points,
+            lines: self.plot_lines,
+            polygons: self.plot_polygons,
+        }
+    }
+
+    /// Whether `feature` passes the `where` rules and the time range.
+    pub fn accepts(&self, feature: &Feature) -> bool {
+        style::accepts(&self.style_rules, feature)
+            && self
+                .time_range
+                .as_ref()
+                .is_none_or(|range| range.accepts(feature))
+    }
+}
+
+/// Grows `bbox` ([min_lon, min_lat, max_lon, max_lat]) to include every position of `value`.
+pub fn extend_bbox(bbox: &mut [f64; 4], value: &Value) {
+    let mut include = |c: &Vec<f64>| {
+        bbox[0] = bbox[0].min(c[0]);
+        bbox[1] = bbox[1].min(c[1]);
+        bbox[2] = bbox[2].max(c[0]);
+        bbox[3] = bbox[3].max(c[1]);
+    };
+    match value {
+        Value::Point(c) => include(c),
+        Value::MultiPoint(coords) | Value::LineString(coords) => coords.iter().for_each(include),
+        Value::MultiLineString(lines) | Value::Polygon(lines) => {
+            lines.iter().flatten().for_each(include)
+        }
+        Value::MultiPolygon(polygons) => polygons.iter().flatten().flatten().for_each(include),
+        Value::GeometryCollection(geometries) => {
+            for geometry in geometries {
+                extend_bbox(bbox, &geometry.value);
+            }
+        }
+    }
+}
+
+/// An inverted bbox that any call to `extend_bbox` will replace.
+pub fn empty_bbox() -> [f64; 4] {
+    [f64::MAX, f64::MAX, f64::MIN, f64::MIN]
+}
+
+/// Parses an extent typed as `"min_lon, min_lat, max_lon, max_lat"`; the
+/// numbers may also be separated by spaces.
+pub fn parse_extent(text: &str) -> Result<[f64; 4], String> {
+    let numbers = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<f64>()
+                .map_err(|_| format!("'{}' is not a number", s))
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+    let [min_lon, min_lat, max_lon, max_lat] = numbers[..] else {
+        return Err(String::from(
+            "Expected \"min_lon, min_lat, max_lon, max_lat\"",
+        ));
+    };
+    if !(-180.0..=180.0).contains(&min_lon)
+        || !(-180.0..=180.0).contains(&max_lon)
+        || !(-90.0..=90.0).contains(&min_lat)
+        || !(-90.0..=90.0).contains(&max_lat)
+    {
+        return Err(String::from(
+            "Longitudes must be within ±180 and latitudes within ±90",
+        ));
+    }
+    if min_lon >= max_lon || min_lat >= max_lat {
+        return Err(String::from("Minimum must be less than maximum"));
+    }
+    Ok([min_lon, min_lat, max_lon, max_lat])
+}
+
+/// Polygon feature of the rectangle `[min_lon, min_lat, max_lon, max_lat]`,
+/// with the bounds as properties.
+pub fn extent_feature(extent: [f64; 4]) -> Feature {
+    let [min_lon, min_lat, max_lon, max_lat] = extent;
+    let ring = vec![
+        vec![min_lon, min_lat],
+        vec![max_lon, min_lat],
+        vec![max_lon, max_lat],
+        vec![min_lon, max_lat],
+        vec![min_lon, min_lat],
+    ];
+    let properties = serde_json::json!({
+        "min_lon": min_lon,
+        "min_lat": min_lat,
+        "max_lon": max_lon,
+        "max_lat": max_lat,
+    });
+    Feature {
+        bbox: Some(extent.to_vec()),
+        geometry: Some(Geometry::new(Value::Polygon(vec![ring]))),
+        id: None,
+        properties: properties.as_object().cloned(),
+        foreign_members: None,
+    }
+}
+
+/// Writes `extent` as a FeatureCollection with a single polygon feature.
+pub fn save_extent(path: &Path, extent: [f64; 4]) -> Result<(), Box<dyn Error>> {
+    let collection = geojson::FeatureCollection {
+        bbox: None,
+        features: vec![extent_feature(extent)],
+        foreign_members: None,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, collection.to_string())?;
+    Ok(())
+}
+
+/// Pads a bbox by 10% on every side, clamped to the globe.
+pub fn pad_bbox([min_lon, min_lat, max_lon, max_lat]: [f64; 4]) -> [f64; 4] {
+    let padding_percentage = 0.1; // 10% padding
+    let epsilon = 0.001;
+
+    let lon_range = (max_lon - min_lon).max(epsilon);
+    let lat_range = (max_lat - min_lat).max(epsilon);
+
+    let lon_padding = lon_range * padding_percentage;
+    let lat_padding = lat_range * padding_percentage;
+
+    [
+        (min_lon - lon_padding).max(-180.0),
+        (min_lat - lat_padding).max(-90.0),
+        (max_lon + lon_padding).min(180.0),
+        (max_lat + lat_padding).min(90.0),
+    ]
+}
+
+/// Computes the bbox of the features of `layers` accepted by `filter`.
+pub fn features_bbox(layers: &[PlotLayer], filter: &dyn Fn(&Feature) -> bool) -> Option<[f64; 4]> {
+    let mut bbox = empty_bbox();
+    for layer in layers {
+        for feature in layer.features.iter().filter(|f| filter(f)) {
+            if let Some(geometry) = &feature.geometry {
+                extend_bbox(&mut bbox, &geometry.value);
+            }
+        }
+    }
+    (bbox[0] != f64::MAX).then_some(bbox)
+}
+
+// Fill of proportional circles relative to their outline, so overlaps stay readable
+const PROPORTIONAL_FILL_OPACITY: f64 = 0.6;
+
+// Rough render cost of a debug build on a typical machine, used by `estimate_render`
+const SECONDS_BASE: f64 = 0.3; // Background, mesh and PNG encoding
+const SECONDS_PER_VERTEX: f64 = 2.5e-7;
+const SECONDS_PER_FEATURE: f64 = 2e-5;
+const BYTES_PER_VERTEX: usize = 48; // Heap-allocated position vector of a loaded feature
+
+/// Expected cost of rendering a set of layers, shown before big plots.
+pub struct RenderEstimate {
+    pub features: usize,
+    pub vertices: usize,       // As stored in the files
+    pub drawn_vertices: usize, // After simplification
+    pub memory_bytes: usize,   // Image buffer plus the loaded geometry
+    pub seconds: f64,
+}
+
+impl RenderEstimate {
+    // Renders above these limits ask for confirmation first
+    const CONFIRM_SECONDS: f64 = 10.0;
+    const CONFIRM_BYTES: usize = 1 << 30;
+
+    /// Whether the render is big enough to confirm before starting it.
+    pub fn is_large(&self) -> bool {
+        self.seconds >= Self::CONFIRM_SECONDS || self.memory_bytes >= Self::CONFIRM_BYTES
+    }
+
+    /// One-line summary, e.g. "1204 features, 2.1M of 5.3M vertices, ~150 MB, ~8.5 s".
+    pub fn summary(&self) -> String {
+        format!(
+            "{} features, {} of {} vertices, ~{} MB, ~{:.1} s",
+            self.features,
+            ramp::format_value(self.drawn_vertices as f64),
+            ramp::format_value(self.vertices as f64),
+            self.memory_bytes.div_ceil(1 << 20),
+            self.seconds
+        )
+    }
+}
+
+/// Number of positions in `value`.
+pub fn count_vertices(value: &Value) -> usize {
+    match value {
+        Value::Point(_) => 1,
+        Value::MultiPoint(points) | Value::LineString(points) => points.len(),
+        Value::MultiLineString(lines) | Value::Polygon(lines) => lines.iter().map(Vec::len).sum(),
+        Value::MultiPolygon(polygons) => polygons.iter().flatten().map(Vec::len).sum(),
+        Value::GeometryCollection(geometries) => geometries
+            .iter()
+            .map(|geometry| count_vertices(&geometry.value))
+            .sum(),
+    }
+}
+
+/// Estimates the cost of rendering `layers`, given as their features and
+/// simplification tolerance override, with `options`.
+pub fn estimate_render(
+    layers: &[(&[Feature], Option<f64>)],
+    options: &PlotOptions,
+) -> RenderEstimate {
+    let accepted = |feature: &&Feature| options.accepts(feature);
+    let global_tolerance = if options.auto_simplify {
+        let mut bbox = empty_bbox();
+        for (features, _) in layers {
+            for geometry in features
+                .iter()
+                .filter(accepted)
+                .filter_map(|f| f.geometry.as_ref())
+            {
+                extend_bbox(&mut bbox, &geometry.value);
+            }
+        }
+        let extent = options
+            .extent
+            .or((bbox[0] != f64::MAX).then(|| pad_bbox(bbox)))
+            .unwrap_or(WORLD_EXTENT);
+        pixel_tolerance(extent, options.width, options.height)
+    } else {
+        options.simplify_tolerance
+    };
+
+    let mut estimate = RenderEstimate {
+        features: 0,
+        vertices: 0,
+        drawn_vertices: 0,
+        memory_bytes: options.width as usize * options.height as usize * 3,
+        seconds: 0.0,
+    };
+    for (features, tolerance) in layers {
+        let tolerance = tolerance.unwrap_or(global_tolerance);
+        for feature in features.iter().filter(accepted) {
+            let Some(geometry) = &feature.geometry else {
+                continue;
+            };
+            let vertices = count_vertices(&geometry.value);
+            let drawn = if tolerance > 0.0 {
+                count_vertices(&simplify::simplify_geometry(&geometry.value, tolerance))
+            } else {
+                vertices
+            };
+            estimate.memory_bytes += vertices * BYTES_PER_VERTEX;
+            estimate.features += 1;
+            estimate.vertices += vertices;
+            estimate.drawn_vertices += drawn;
+        }
+    }
+    estimate.seconds = SECONDS_BASE
+        + estimate.drawn_vertices as f64 * SECONDS_PER_VERTEX
+        + estimate.features as f64 * SECONDS_PER_FEATURE;
+    estimate
+}
+
+const NO_BBOX_WARNING: &str =
+    "No valid bounding box found for selected files. Using default global view.";
+
+/// Error of a render stopped by `RenderProgress::cancel`.
+pub const CANCELLED: &str = "Plot cancelled";
+
+/// Progress of drawing the layers, shared with the thread waiting for the
+/// render, which can also cancel it.
+#[derive(Debug, Default)]
+pub struct RenderProgress {
+    drawn: AtomicUsize,
+    total: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl RenderProgress {
+    /// Features drawn so far and the features to draw.
+    pub fn counts(&self) -> (usize, usize) {
+        (
+            self.drawn.load(Ordering::Relaxed),
+            self.total.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Makes the render fail with `CANCELLED` before the next feature.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// What a finished render reports besides the image.
+#[derive(Debug, Default)]
+pub struct RenderReport {
+    pub features_drawn: Vec<usize>, // Per layer, after the clip mask and style filters
+    pub warnings: Vec<String>,      // Parts of the plot that fell back or were left out
+}
+
+/// Renders every feature of `layers` into a single image at `output`.
+pub fn render_plot(
+    output: &Path,
+    layers: &[PlotLayer],
+    options: &PlotOptions,
+) -> Result<RenderReport, Box<dyn Error>> {
+    render_plot_filtered(output, layers, options, &|_| true)
+}
+
+/// Renders the features of `layers` accepted by `filter` into a single image at `output`.
+/// Unless `options.extent` is set, the extent is computed from the accepted features only.
+pub fn render_plot_filtered(
+    output: &Path,
+    layers: &[PlotLayer],
+    options: &PlotOptions,
+    filter: &dyn Fn(&Feature) -> bool,
+) -> Result<RenderReport, Box<dyn Error>> {
+    render_loaded(ImageOutput::File(output), layers, options, filter)
+}
+
+fn render_loaded(
+    output: ImageOutput,
+    layers: &[PlotLayer],
+    options: &PlotOptions,
+    filter: &dyn Fn(&Feature) -> bool,
+) -> Result<RenderReport, Box<dyn Error>> {
+    let clipped = options
+        .clip_mask
+        .as_ref()
+        .map(|mask| mask.clip_layers(layers));
+    let layers = clipped.as_deref().unwrap_or(layers);
+    let projected = options
+        .projection
+        .is_polar()
+        .then(|| project_layers(layers, options.projection));
+    let layers = projected.as_deref().unwrap_or(layers);
+    let filter = |f: &Feature| filter(f) && options.accepts(f);
+    let mut report = RenderReport {
+        features_drawn: layers
+            .iter()
+            .map(|layer| layer.features.iter().filter(|f| filter(f)).count())
+            .collect(),
+        warnings: Vec::new(),
+    };
+    let extent = resolve_extent(layers, options, &filter).unwrap_or_else(|| {
+        report.warnings.push(NO_BBOX_WARNING.to_string());
+        WORLD_EXTENT
+    });
+    let mut warnings = render_image(output, &Features::Loaded(layers, &filter), options, extent)?;
+    report.warnings.append(&mut warnings);
+    Ok(report)
+}
+
+/// Renders every feature of `layers` like `render_plot`, into PNG bytes instead
+/// of a file. No thumbnail is made.
+pub fn render_plot_png(
+    layers: &[PlotLayer],
+    options: &PlotOptions,
+) -> Result<(Vec<u8>, RenderReport), Box<dyn Error>> {
+    let mut png = Vec::new();
+    let report = render_loaded(ImageOutput::Png(&mut png), layers, options, &|_| true)?;
+    Ok((png, report))
+}
+
+/// A layer that `render_plot_streamed` reads from its file while drawing,
+/// instead of holding its features in memory.
+pub struct StreamedLayer {
+    pub path: PathBuf,
+    pub crs: Option<String>, // Coordinate system overriding the file's own
+    pub filter: Option<FeatureFilter>, // Features of the file to draw; all if `None`
+    pub layer: PlotLayer,    // Color and symbols; its `features` are not used
+}
+
+// Streamed layers with what the first pass over them collected
+struct Stream<'a> {
+    layers: &'a [StreamedLayer],
+    mask: Option<clip::Mask>,
+    styled: Vec<Feature>, // The properties and elevations read by the styler of every drawn feature
+    failed: Vec<Option<String>>, // Why each layer that could not be read is left out
+}
+
+impl Stream<'_> {
+    // `feature` of `streamed` cut to the clip mask, or `None` if nothing of it is drawn
+    fn prepare(
+        &self,
+        streamed: &StreamedLayer,
+        feature: Feature,
+        options: &PlotOptions,
+    ) -> Option<Feature> {
+        if streamed
+            .filter
+            .as_ref()
+            .is_some_and(|filter| !filter.accepts(&feature))
+        {
+            return None;
+        }
+        let feature = match &self.mask {
+            Some(mask) => clip::clip_features(std::slice::from_ref(&feature), mask).pop()?,
+            None => feature,
+        };
+        let feature = match options.projection {
+            Projection::LonLat => feature,
+            projection => projection
+                .project_features(std::slice::from_ref(&feature))
+                .pop()?,
+        };
+        options.accepts(&feature).then_some(feature)
+    }
+}
+
+/// Renders `layers` like `render_plot`, but reads every file feature by feature
+/// instead of loading it: once for the extent and the style classes and once
+/// more to draw. Slower, but the memory used does not grow with the geometry,
+/// so files larger than the available memory can be plotted. Returns what
+/// reading each file reported (without its features), or why it could not be
+/// read; such layers are left out, and the plot fails only if all of them are.
+pub fn render_plot_streamed(
+    output: &Path,
+    layers: &[StreamedLayer],
+    options: &PlotOptions,
+) -> Result<Vec<Result<loader::ParsedFeatures, String>>, Box<dyn Error>> {
+    let mut stream = Stream {
+        layers,
+        mask: options.clip_mask.as_ref().map(ClipMask::index),
+        styled: Vec::new(),
+        failed: Vec::new(),
+    };
+    let properties = style::value_properties(&options.style_rules);
+    let by_elevation = style::colors_by_elevation(&options.style_rules);
+    let mut styled = Vec::new();
+    let mut bbox = empty_bbox();
+    let mut reports = Vec::new();
+    for streamed in layers {
+        let path = &streamed.path;
+        // Collected per layer, so a layer failing half-way leaves no trace
+        let mut layer_styled = Vec::new();
+        let mut layer_bbox = empty_bbox();
+        let report = loader::stream_features(path, streamed.crs.as_deref(), &mut |feature| {
+            let Some(feature) = stream.prepare(streamed, feature, options) else {
+                return Ok(());
+            };
+            if let Some(geometry) = &feature.geometry {
+                extend_bbox(&mut layer_bbox, &geometry.value);
+            }
+            if !properties.is_empty() || by_elevation {
+                let mut values = Feature::default();
+                for property in &properties {
+                    if let Some(value) = feature.property(property) {
+                        values.set_property(*property, value.clone());
+                    }
+                }
+                // The lowest and highest position stand in for the elevations
+                if by_elevation
+                    && let Some((low, high)) = feature
+                        .geometry
+                        .as_ref()
+                        .and_then(|g| style::elevation_range(&g.value))
+                {
+                    let positions = vec![vec![0.0, 0.0, low], vec![0.0, 0.0, high]];
+                    values.geometry = Some(Geometry::new(Value::MultiPoint(positions)));
+                }
+                layer_styled.push(values);
+            }
+            Ok(())
+        });
+        match report {
+            Ok(report) => {
+                styled.append(&mut layer_styled);
+                bbox = [
+                    bbox[0].min(layer_bbox[0]),
+                    bbox[1].min(layer_bbox[1]),
+                    bbox[2].max(layer_bbox[2]),
+                    bbox[3].max(layer_bbox[3]),
+                ];
+                stream.failed.push(None);
+                reports.push(Ok(report));
+            }
+            Err(e) => {
+                let failure = format!("{}: {}", path.display(), e);
+                stream.failed.push(Some(failure.clone()));
+                reports.push(Err(failure));
+            }
+        }
+    }
+    if stream.failed.iter().all(Option::is_some) {
+        return Err("None of the layers could be read".into());
+    }
+    stream.styled = styled;
+
+    let extent = explicit_extent(options)
+        .or_else(|| extent_around(options, bbox))
+        .unwrap_or_else(|| {
+            println!("Warning: {}", NO_BBOX_WARNING);
+            WORLD_EXTENT
+        });
+    for warning in render_image(
+        ImageOutput::File(output),
+        &Features::Streamed(&stream),
+        options,
+        extent,
+    )? {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(reports)
+}
+
+// Where a rendered image goes: a PNG file (with its thumbnail, if requested)
+// or PNG bytes in memory
+enum ImageOutput<'a> {
+    File(&'a Path),
+    Png(&'a mut Vec<u8>),
+}
+
+// Draws `features` into the image (or figure) at `output`. Returns the
+// warnings of drawing it.
+fn render_image(
+    output: ImageOutput,
+    features: &Features,
+    options: &PlotOptions,
+    extent: [f64; 4],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    if let Some(background) = options.figure {
+        return render_figure(output, features, options, extent, background);
+    }
+    let (width, height) = (options.width, options.height);
+    let mut buffer = vec![0u8; width as usize * height as usize * 3];
+    let warnings = {
+        let root = BitMapBackend::with_buffer(&mut buffer, (width, height)).into_drawing_area();
+        let warnings = draw_plot(&root, features, options, extent, true, None)?;
+        root.present()?;
+        warnings
+    };
+    let image = image::RgbImage::from_raw(width, height, buffer).ok_or("Invalid image buffer")?;
+    save_image(output, image.into(), options.thumbnail)?;
+    Ok(warnings)
+}
+
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+
+/// Where the thumbnail of `output` is written: `<name>_thumb.png` next to it.
+pub fn thumbnail_path(output: &Path) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("plot");
+    output.with_file_name(format!("{}_thumb.png", stem))
+}
+
+// Saves `image` at `output` and, if requested, a copy downscaled to fit into
+// `thumbnail` x `thumbnail` pixels at its `thumbnail_path`
+fn save_image(
+    output: ImageOutput,
+    image: image::DynamicImage,
+    thumbnail: Option<u32>,
+) -> Result<(), Box<dyn Error>> {
+    let output = match output {
+        ImageOutput::File(path) => path,
+        ImageOutput::Png(bytes) => {
+            image.write_to(&mut io::Cursor::new(bytes), image::ImageOutputFormat::Png)?;
+            return Ok(());
+        }
+    };
+    image.save(output)?;
+    if let Some(size) = thumbnail {
+        // Images already within the size are copied as they are
+        let small = if image.width().max(image.height()) > size {
+            image.resize(size, size, image::imageops::FilterType::Triangle)
+        } else {
+            image
+        };
+        small.save(thumbnail_path(output))?;
+    }
+    Ok(())
+}
+
+// Fraction of an output pixel below which detail is dropped by automatic simplification
+const AUTO_SIMPLIFY_PIXELS: f64 = 0.5;
+
+/// Tolerance that removes only detail smaller than half a pixel when `extent`
+/// is drawn into `width` x `height` pixels.
+pub fn pixel_tolerance(extent: [f64; 4], width: u32, height: u32) -> f64 {
+    let units_per_pixel = ((extent[2] - extent[0]) / width.max(1) as f64)
+        .max((extent[3] - extent[1]) / height.max(1) as f64);
+    units_per_pixel * AUTO_SIMPLIFY_PIXELS
+}
+
+/// Largest size with the aspect ratio `aspect` (width over height) that fits
+/// into `width` x `height`.
+pub fn figure_size(aspect: f64, width: u32, height: u32) -> (u32, u32) {
+    if !aspect.is_finite() || aspect <= 0.0 {
+        return (width, height);
+    }
+    if aspect >= width as f64 / height as f64 {
+        (width, ((width as f64 / aspect).round() as u32).max(1))
+    } else {
+        (((height as f64 * aspect).round() as u32).max(1), height)
+    }
+}
+
+/// Width over height of `extent` on the ground. A degree of longitude is
+/// shortened by the cosine of the central latitude; projected extents are in
+/// metres along both axes.
+pub fn ground_aspect(extent: [f64; 4], projection: Projection) -> f64 {
+    let aspect = (extent[2] - extent[0]) / (extent[3] - extent[1]);
+    match projection {
+        Projection::LonLat => aspect * ((extent[1] + extent[3]) / 2.0).to_radians().cos(),
+        _ => aspect,
+    }
+}
+
+/// `extent` grown around its center along one axis until it has the aspect
+/// ratio of `width` x `height` pixels on the ground, so shapes are drawn with
+/// their true proportions.
+pub fn fit_aspect(extent: [f64; 4], projection: Projection, width: u32, height: u32) -> [f64; 4] {
+    let aspect = ground_aspect(extent, projection);
+    let target = width as f64 / height as f64;
+    if !aspect.is_finite() || aspect <= 0.0 || !target.is_finite() || target <= 0.0 {
+        return extent;
+    }
+    let [min_x, min_y, max_x, max_y] = extent;
+    if aspect < target {
+        let grow = (max_x - min_x) * (target / aspect - 1.0) / 2.0;
+        [min_x - grow, min_y, max_x + grow, max_y]
+    } else {
+        let grow = (max_y - min_y) * (aspect / target - 1.0) / 2.0;
+        [min_x, min_y - grow, max_x, max_y + grow]
+    }
+}
+
+// The middle of `area` with the aspect ratio `aspect`, leaving the rest empty
+fn letterbox<'a>(
+    area: &DrawingArea<BitMapBackend<'a>, Shift>,
+    aspect: f64,
+) -> DrawingArea<BitMapBackend<'a>, Shift> {
+    let (width, height) = area.dim_in_pixel();
+    let (fit_width, fit_height) = figure_size(aspect, width, height);
+    let (side, top) = (
+        (width.saturating_sub(fit_width) / 2) as i32,
+        (height.saturating_sub(fit_height) / 2) as i32,
+    );
+    area.margin(top, top, side, side)
+}
+
+fn render_figure(
+    output: ImageOutput,
+    features: &Features,
+    options: &PlotOptions,
+    extent: [f64; 4],
+    background: FigureBackground,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let aspect = if options.keep_aspect {
+        ground_aspect(extent, options.projection)
+    } else {
+        (extent[2] - extent[0]) / (extent[3] - extent[1])
+    };
+    let (width, height) = figure_size(aspect, options.width, options.height);
+    // Those of the last rendering, as a transparent figure is rendered twice
+    let warnings = RefCell::new(Vec::new());
+    let render = |background: &RGBColor| -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut buffer = vec![0u8; width as usize * height as usize * 3];
+        {
+            let root = BitMapBackend::with_buffer(&mut buffer, (width, height)).into_drawing_area();
+            *warnings.borrow_mut() =
+                draw_plot(&root, features, options, extent, true, Some(*background))?;
+            root.present()?;
+        }
+        Ok(buffer)
+    };
+    save_figure(
+        output,
+        width,
+        height,
+        background,
+        options.thumbnail,
+        &render,
+    )?;
+    Ok(warnings.into_inner())
+}
+
+type RenderOn<'a> = dyn Fn(&RGBColor) -> Result<Vec<u8>, Box<dyn Error>> + 'a;
+
+// Saves the RGB buffer produced by `render` for the given background color as
+// PNG, with an alpha channel for transparent backgrounds
+fn save_figure(
+    output: ImageOutput,
+    width: u32,
+    height: u32,
+    background: FigureBackground,
+    thumbnail: Option<u32>,
+    render: &RenderOn,
+) -> Result<(), Box<dyn Error>> {
+    let invalid = || "Invalid image buffer";
+    match background {
+        FigureBackground::White => {
+            let image =
+                image::RgbImage::from_raw(width, height, render(&WHITE)?).ok_or_else(invalid)?;
+            save_image(output, image.into(), thumbnail)
+        }
+        FigureBackground::Transparent => {
+            // Rendering on black and on white recovers the coverage of anti-aliased
+            // edges: a pixel differs between the two by (1 - alpha) * 255
+            let on_black = render(&BLACK)?;
+            let on_white = render(&WHITE)?;
+            let rgba: Vec<u8> = on_black
+                .chunks_exact(3)
+                .zip(on_white.chunks_exact(3))
+                .flat_map(|(black, white)| {
+                    let alpha = 255 - (white[1] as i32 - black[1] as i32).clamp(0, 255);
+                    let unblend = |c: u8| match alpha {
+                        0 => 0,
+                        _ => (c as i32 * 255 / alpha).min(255) as u8,
+                    };
+                    [
+                        unblend(black[0]),
+                        unblend(black[1]),
+                        unblend(black[2]),
+                        alpha as u8,
+                    ]
+                })
+                .collect();
+            let image = image::RgbaImage::from_raw(width, height, rgba).ok_or_else(invalid)?;
+            save_image(output, image.into(), thumbnail)
+        }
+    }
+}
+
+const TILE_SIZE: u32 = 256;
+pub const MAX_TILE_ZOOM: u32 = 22;
+
+/// Number of tiles `export_tiles` writes for `zooms`.
+pub fn tile_count(
+    layers: &[PlotLayer],
+    options: &PlotOptions,
+    zooms: RangeInclusive<u32>,
+) -> usize {
+    let filter = |f: &Feature| options.accepts(f);
+    let Some(bbox) = resolve_extent(layers, options, &filter) else {
+        return 0;
+    };
+    zooms
+        .map(|zoom| {
+            let (columns, rows) = tile_range(bbox, zoom);
+            columns.count() * rows.count()
+        })
+        .sum()
+}
+
+// Columns and rows of the tiles at `zoom` covering the lon/lat `bbox`
+fn tile_range(bbox: [f64; 4], zoom: u32) -> (RangeInclusive<u32>, RangeInclusive<u32>) {
+    let world = crs::WEB_MERCATOR_EXTENT;
+    let tiles_across = 1i64 << zoom;
+    let tile = |x: f64| {
+        ((x / (2.0 * world) * tiles_across as f64).floor() as i64).clamp(0, tiles_across - 1) as u32
+    };
+    let (min_x, min_y) = crs::lonlat_to_web_mercator(bbox[0].max(-180.0), bbox[1]);
+    let (max_x, max_y) = crs::lonlat_to_web_mercator(bbox[2].min(180.0), bbox[3]);
+    (
+        tile(min_x + world)..=tile(max_x + world),
+        tile(world - max_y)..=tile(world - min_y),
+    )
+}
+
+/// Renders `layers` into a `{z}/{x}/{y}.png` pyramid of 256 px Web Mercator
+/// tiles below `dir`, as served to Leaflet or MapLibre. Only tiles within the
+/// extent are written; they show geometry and markers only, on the figure
+/// background (transparent by default). Tiles recorded in `checkpoint` are
+/// skipped and every written one is recorded. Returns the number of tiles written.
+pub fn export_tiles(
+    dir: &Path,
+    layers: &[PlotLayer],
+    options: &PlotOptions,
+    zooms: RangeInclusive<u32>,
+    mut checkpoint: Option<&mut Checkpoint>,
+) -> Result<usize, Box<dyn Error>> {
+    let clipped = options
+        .clip_mask
+        .as_ref()
+        .map(|mask| mask.clip_layers(layers));
+    let layers = clipped.as_deref().unwrap_or(layers);
+    let filter = |f: &Feature| options.accepts(f);
+    let extent = resolve_extent(layers, options, &filter).ok_or("No features to export")?;
+    // Tolerances are given in degrees, but tiles are drawn in metres
+    let metres_per_degree = crs::WEB_MERCATOR_EXTENT / 180.0;
+
+    // Layers in Web Mercator, each feature carrying its bbox to skip it in other tiles
+    let layers: Vec<PlotLayer> = layers
+        .iter()
+        .map(|layer| {
+            let mut features: Vec<Feature> = layer
+                .features
+                .iter()
+                .filter(|f| filter(f))
+                .cloned()
+                .collect();
+            crs::to_web_mercator(&mut features);
+            for feature in &mut features {
+                let mut bbox = empty_bbox();
+                if let Some(geometry) = &feature.geometry {
+                    extend_bbox(&mut bbox, &geometry.value);
+                }
+                feature.bbox = (bbox[0] != f64::MAX).then(|| bbox.to_vec());
+            }
+            PlotLayer {
+                features,
+                color: layer.color,
+                simplify_tolerance: layer.simplify_tolerance.map(|t| t * metres_per_degree),
+                line_width: layer.line_width,
+                line_style: layer.line_style,
+                point_size: layer.point_size,
+                point_shape: layer.point_shape,
+                opacity: layer.opacity,
+                geometry_types: layer.geometry_types,
+                levels: None, // Built in degrees
+            }
+        })
+        .collect();
+    // Marker positions in metres as well
+    let markers: Vec<MapMarker> = options
+        .markers
+        .iter()
+        .map(|marker| {
+            let (x, y) = crs::lonlat_to_web_mercator(marker.lon, marker.lat);
+            MapMarker {
+                lon: x,
+                lat: y,
+                label: marker.label.clone(),
+            }
+        })
+        .collect();
+    // Classes and gradients are resolved once so they match across tiles
+    let styler = Styler::new(
+        &options.style_rules,
+        layers.iter().flat_map(|layer| layer.features.iter()),
+    );
+    let background = options.figure.unwrap_or(FigureBackground::Transparent);
+    let mask = options.clip_mask.as_ref().map(ClipMask::to_web_mercator);
+
+    let world = crs::WEB_MERCATOR_EXTENT;
+    let mut written = 0;
+    for zoom in zooms {
+        let tile_span = 2.0 * world / (1u64 << zoom) as f64;
+        let (columns, rows) = tile_range(extent, zoom);
+        for x in columns {
+            for y in rows.clone() {
+                let key = format!("{}/{}/{}", zoom, x, y);
+                if checkpoint.as_ref().is_some_and(|c| c.is_done(&key)) {
+                    continue;
+                }
+                let left = -world + x as f64 * tile_span;
+                let top = world - y as f64 * tile_span;
+                let bounds = [left, top - tile_span, left + tile_span, top];
+                // Features just outside still reach in with their strokes and point markers
+                let margin = tile_span * 0.05;
+                let overlaps = |f: &Feature| {
+                    f.bbox.as_ref().is_some_and(|b| {
+                        b[0] <= bounds[2] + margin
+                            && b[2] >= bounds[0] - margin
+                            && b[1] <= bounds[3] + margin
+                            && b[3] >= bounds[1] - margin
+                    })
+                };
+                let global_tolerance = if options.auto_simplify {
+                    pixel_tolerance(bounds, TILE_SIZE, TILE_SIZE)
+                } else {
+                    options.simplify_tolerance * metres_per_degree
+                };
+                let render = |color: &RGBColor| -> Result<Vec<u8>, Box<dyn Error>> {
+                    let mut buffer = vec![0u8; (TILE_SIZE * TILE_SIZE * 3) as usize];
+                    {
+                        let root = BitMapBackend::with_buffer(&mut buffer, (TILE_SIZE, TILE_SIZE))
+                            .into_drawing_area();
+                        root.fill(color)?;
+                        let mut chart = ChartBuilder::on(&root)
+                            .build_cartesian_2d(bounds[0]..bounds[2], bounds[1]..bounds[3])?;
+                        draw_layers(
+                            &mut chart,
+                            &layers,
+                            options,
+                            &overlaps,
+                            &styler,
+                            global_tolerance,
+                        )?;
+                        if let Some(mask) = &mask {
+                            let outside = if mask.shade_outside {
+                                OUTSIDE_SHADE
+                            } else {
+                                *color
+                            };
+                            draw_clip_mask(&chart, mask, bounds, outside)?;
+                        }
+                        draw_markers(&mut chart, &markers, false)?;
+                        root.present()?;
+                    }
+                    Ok(buffer)
+                };
+                let column_dir = dir.join(zoom.to_string()).join(x.to_string());
+                fs::create_dir_all(&column_dir)?;
+                let output = column_dir.join(format!("{}.png", y));
+                save_figure(
+                    ImageOutput::File(&output),
+                    TILE_SIZE,
+                    TILE_SIZE,
+                    background,
+                    None,
+                    &render,
+                )?;
+                if let Some(checkpoint) = checkpoint.as_mut() {
+                    checkpoint.mark_done(&key)?;
+                }
+                written += 1;
+            }
+        }
+    }
+    Ok(written)
+}
+
+/// Renders `layers` into an in-memory RGB buffer (3 bytes per pixel, row-major,
+/// `options.width` x `options.height`) intended for golden-image comparisons.
+///
+/// The output only depends on the inputs: no caption or axis labels are drawn
+/// (so system fonts never influence the pixels), nothing time-dependent is
+/// rendered, and layers and features are drawn strictly in the order given.
+pub fn render_golden(
+    layers: &[PlotLayer],
+    options: &PlotOptions,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let clipped = options
+        .clip_mask
+        .as_ref()
+        .map(|mask| mask.clip_layers(layers));
+    let layers = clipped.as_deref().unwrap_or(layers);
+    let projected = options
+        .projection
+        .is_polar()
+        .then(|| project_layers(layers, options.projection));
+    let layers = projected.as_deref().unwrap_or(layers);
+    let filter = |f: &Feature| options.accepts(f);
+    let extent = resolve_extent(layers, options, &filter).unwrap_or(WORLD_EXTENT);
+    let mut buffer = vec![0u8; options.width as usize * options.height as usize * 3];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, (options.width, options.height))
+            .into_drawing_area();
+        draw_plot(
+            &root,
+            &Features::Loaded(layers, &filter),
+            options,
+            extent,
+            false,
+            None,
+        )?;
+        root.present()?;
+    }
+    Ok(buffer)
+}
+
+const WORLD_EXTENT: [f64; 4] = [-180.0, -90.0, 180.0, 90.0];
+
+// Uses the explicit extent if given, otherwise the padded bbox of the accepted
+// features and the markers. `layers` are in the coordinates of the projection.
+fn resolve_extent(
+    layers: &[PlotLayer],
+    options: &PlotOptions,
+    filter: &dyn Fn(&Feature) -> bool,
+) -> Option<[f64; 4]> {
+    if let Some(extent) = explicit_extent(options) {
+        return Some(extent);
+    }
+    extent_around(
+        options,
+        features_bbox(layers, filter).unwrap_or_else(empty_bbox),
+    )
+}
+
+// The extent of the options in the coordinates of the projection
+fn explicit_extent(options: &PlotOptions) -> Option<[f64; 4]> {
+    options
+        .extent
+        .map(|extent| options.projection.project_extent(extent))
+}
+
+// The padded bbox of the mask if there is one, otherwise that of `bbox` and the markers
+fn extent_around(options: &PlotOptions, mut bbox: [f64; 4]) -> Option<[f64; 4]> {
+    let projection = options.projection;
+    let pad = |bbox: [f64; 4]| match projection {
+        Projection::LonLat => pad_bbox(bbox),
+        // Metres, which pad_bbox would clamp to the globe in degrees
+        _ => {
+            let padding = [bbox[2] - bbox[0], bbox[3] - bbox[1]].map(|range| range.max(1.0) * 0.1);
+            [
+                bbox[0] - padding[0],
+                bbox[1] - padding[1],
+                bbox[2] + padding[0],
+                bbox[3] + padding[1],
+            ]
+        }
+    };
+    if let Some(mask) = &options.clip_mask {
+        return Some(pad(match projection {
+            Projection::LonLat => mask.bbox,
+            _ => mask.projected(projection).bbox,
+        }));
+    }
+    for marker in &options.markers {
+        let (x, y) = projection.project(marker.lon, marker.lat);
+        extend_bbox(&mut bbox, &Value::Point(vec![x, y]));
+    }
+    (bbox[0] != f64::MAX).then(|| pad(bbox))
+}
+
+// Copies of `layers` in the coordinates of `projection`, with their
+// tolerances converted from degrees
+fn project_layers(layers: &[PlotLayer], projection: Projection) -> Vec<PlotLayer> {
+    layers
+        .iter()
+        .map(|layer| PlotLayer {
+            features: projection.project_features(&layer.features),
+            simplify_tolerance: layer
+                .simplify_tolerance
+                .map(|t| t * projection.units_per_degree()),
+            levels: None, // Built in degrees
+            ..layer.clone()
+        })
+        .collect()
+}
+
+// What `draw_plot` draws: layers in memory with the filter selecting their
+// features, or layers read from disk while drawing
+enum Features<'a> {
+    Loaded(&'a [PlotLayer], &'a dyn Fn(&Feature) -> bool),
+    Streamed(&'a Stream<'a>),
+}
+
+// Draws the whole plot into `root`. Returns warnings about what could not be
+// drawn, which do not fail the plot.
+fn draw_plot(
+    root: &DrawingArea<BitMapBackend, Shift>,
+    features: &Features,
+    options: &PlotOptions,
+    extent: [f64; 4],
+    draw_text: bool,
+    figure: Option<RGBColor>, // Background of a figure, which shows nothing but the geometry
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let background = figure.unwrap_or(OCEAN_COLOR);
+    root.fill(&background)?;
+
+    let mut area = root.clone();
+    if draw_text && options.warning_banner {
+        let mut failed = options.failed_layers.clone();
+        if let Features::Streamed(stream) = features {
+            failed.extend(stream.failed.iter().flatten().cloned());
+        }
+        area = draw_warning_banner(&area, &failed)?;
+    }
+    if figure.is_none() {
+        area = area.margin(10, 10, 10, 10);
+        if draw_text {
+            area = draw_titles(&area, options)?;
+        }
+    }
+    let extent = if options.keep_aspect {
+        let (width, height) = area.dim_in_pixel();
+        fit_aspect(extent, options.projection, width, height)
+    } else {
+        extent
+    };
+    // Polar plots are in metres along both axes, which stretching would distort
+    if options.projection.is_polar() {
+        area = letterbox(&area, ground_aspect(extent, options.projection));
+    }
+    let mut chart =
+        ChartBuilder::on(&area).build_cartesian_2d(extent[0]..extent[2], extent[1]..extent[3])?;
+
+    let mut warnings = Vec::new();
+    let projection = options.projection;
+    if options.cluster_distance.is_some() && matches!(features, Features::Streamed(_)) {
+        warnings.push(String::from("Points are not clustered in streamed plots"));
+    }
+    if projection.is_polar() {
+        // Tiles and georeferenced images are laid out in degrees
+        if options.basemap.is_some() || options.underlay.is_some() {
+            warnings.push(String::from(
+                "The basemap and underlay are only drawn in the lon/lat projection",
+            ));
+        }
+    } else {
+        if let Some(basemap) = &options.basemap {
+            warnings.extend(draw_basemap(&chart, basemap, extent)?);
+        }
+        if let Some(underlay) = &options.underlay {
+            draw_underlay(&chart, underlay, extent)?;
+        }
+    }
+    if projection.is_polar() {
+        if figure.is_none() {
+            draw_graticule(&mut chart, projection)?;
+        }
+    } else if figure.is_none() {
+        if draw_text {
+            chart.configure_mesh().draw()?;
+        } else {
+            chart
+                .configure_mesh()
+                .disable_x_axis()
+                .disable_y_axis()
+                .draw()?;
+        }
+    }
+
+    let styler = match features {
+        Features::Loaded(layers, filter) => {
+            Styler::new(&options.style_rules, accepted_features(layers, *filter))
+        }
+        Features::Streamed(stream) => Styler::new(&options.style_rules, stream.styled.iter()),
+    };
+    let global_tolerance = if options.auto_simplify {
+        let (width, height) = chart.plotting_area().dim_in_pixel();
+        pixel_tolerance(extent, width, height)
+    } else {
+        options.simplify_tolerance * projection.units_per_degree()
+    };
+    let streamed_labels = match features {
+        Features::Loaded(layers, filter) => {
+            draw_layers(
+                &mut chart,
+                layers,
+                options,
+                *filter,
+                &styler,
+                global_tolerance,
+            )?;
+            Vec::new()
+        }
+        Features::Streamed(stream) => {
+            draw_streamed(&mut chart, stream, options, &styler, global_tolerance)?
+        }
+    };
+    if let Some(mask) = &options.clip_mask {
+        let outside = if mask.shade_outside {
+            OUTSIDE_SHADE
+        } else {
+            background
+        };
+        match projection {
+            Projection::LonLat => draw_clip_mask(&chart, mask, extent, outside)?,
+            _ => draw_clip_mask(&chart, &mask.projected(projection), extent, outside)?,
+        }
+    }
+
+    // Labels go above every layer so later layers never hide them
+    if draw_text {
+        if let Features::Loaded(layers, filter) = features {
+            for feature in accepted_features(layers, *filter) {
+                if let Some(label) = styler.label(feature) {
+                    draw_label(&chart, feature, label, extent)?;
+                }
+            }
+        }
+        for (feature, label) in streamed_labels {
+            draw_label(&chart, &feature, label, extent)?;
+        }
+    }
+
+    let markers: Vec<MapMarker> = options
+        .markers
+        .iter()
+        .map(|marker| {
+            let (lon, lat) = projection.project(marker.lon, marker.lat);
+            MapMarker {
+                lon,
+                lat,
+                label: marker.label.clone(),
+            }
+        })
+        .collect();
+    draw_markers(&mut chart, &markers, draw_text)?;
+
+    if draw_text
+        && figure.is_none()
+        && let Some(legend) = styler.legend()
+    {
+        draw_legend(&chart, &legend)?;
+    }
+    if draw_text
+        && figure.is_none()
+        && let Some(legend) = styler.size_legend()
+    {
+        draw_size_legend(&chart, &legend)?;
+    }
+    if let Some(inset) = options.inset
+        && figure.is_none()
+    {
+        if projection.is_polar() {
+            warnings.push(String::from(
+                "The inset map is only drawn in the lon/lat projection",
+            ));
+        } else {
+            draw_inset(&chart, inset, extent)?;
+        }
+    }
+    if draw_text && options.scale_bar {
+        draw_scale_bar(&chart, extent, projection)?;
+    }
+    if draw_text && options.north_arrow {
+        if projection.is_polar() {
+            warnings.push(String::from(
+                "No north arrow: north points to the pole in polar projections",
+            ));
+        } else {
+            draw_north_arrow(&chart)?;
+        }
+    }
+    Ok(warnings)
+}
+
+fn accepted_features<'a>(
+    layers: &'a [PlotLayer],
+    filter: &'a dyn Fn(&Feature) -> bool,
+) -> impl Iterator<Item = &'a Feature> {
+    layers
+        .iter()
+        .flat_map(|layer| layer.features.iter())
+        .filter(move |f| filter(f))
+}
+
+// Draws the features of `layers` accepted by `filter`, simplified with the layer's
+// tolerance or `global_tolerance`
+fn draw_layers(
+    chart: &mut GeoChart,
+    layers: &[PlotLayer],
+    options: &PlotOptions,
+    filter: &dyn Fn(&Feature) -> bool,
+    styler: &Styler,
+    global_tolerance: f64,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(progress) = &options.progress {
+        let total = accepted_features(layers, filter).count();
+        progress.total.store(total, Ordering::Relaxed);
+        progress.drawn.store(0, Ordering::Relaxed);
+    }
+    // The clustering distance in coordinate units along each axis
+    let reach = options.cluster_distance.map(|distance| {
+        let area = chart.plotting_area();
+        let (width, height) = area.dim_in_pixel();
+        let (x, y) = (area.get_x_range(), area.get_y_range());
+        (
+            (x.end - x.start) * distance.max(1) as f64 / width.max(1) as f64,
+            (y.end - y.start) * distance.max(1) as f64 / height.max(1) as f64,
+        )
+    });
+    // The focus layer is drawn last so the muted layers never hide it
+    let focus = options.focus_layer.filter(|&i| i < layers.len());
+    let order = (0..layers.len()).filter(|&i| Some(i) != focus).chain(focus);
+    for i in order {
+        let layer = &layers[i];
+        let muted = focus.is_some_and(|focus| focus != i);
+        let draws_points = options
+            .geometry_types()
+            .and(layer.geometry_types.unwrap_or(GeometryTypes::ALL))
+            .points;
+        let clusters = match reach {
+            Some(reach) if draws_points => point_clusters(layer, filter, reach),
+            _ => Vec::new(),
+        };
+        let clustered: HashSet<usize> = clusters
+            .iter()
+            .flat_map(|cluster| cluster.members.iter().copied())
+            .collect();
+        let tolerance = layer.simplify_tolerance.unwrap_or(global_tolerance);
+        // The coarsest copy the tolerance allows saves simplifying the full detail
+        let (geometries, tolerance) = match layer
+            .levels
+            .as_ref()
+            .and_then(|levels| levels.geometries(&layer.features, tolerance))
+        {
+            Some((geometries, left)) => (Some(geometries), left),
+            None => (None, tolerance),
+        };
+        // Proportional circles are drawn largest first so the small ones stay visible
+        let mut positions: Vec<usize> = (0..layer.features.len()).collect();
+        if styler.draws_proportional() {
+            let radius = |position: usize| {
+                styler
+                    .proportional_radius(&layer.features[position])
+                    .unwrap_or(0.0)
+            };
+            positions.sort_by(|&a, &b| radius(b).total_cmp(&radius(a)));
+        }
+        for position in positions {
+            let feature = &layer.features[position];
+            if !filter(feature) {
+                continue;
+            }
+            if let Some(progress) = &options.progress {
+                if progress.is_cancelled() {
+                    return Err(CANCELLED.into());
+                }
+                progress.drawn.fetch_add(1, Ordering::Relaxed);
+            }
+            if clustered.contains(&position) {
+                continue;
+            }
+            let geometry = match geometries {
+                Some(geometries) => geometries[position].as_ref(),
+                None => feature.geometry.as_ref(),
+            };
+            draw_feature(
+                chart,
+                feature,
+                (geometry, tolerance),
+                layer,
+                muted,
+                styler,
+                options,
+            )?;
+        }
+        if !clusters.is_empty() {
+            let color = if muted {
+                mute(layer.color)
+            } else {
+                layer.color
+            };
+            draw_clusters(
+                chart,
+                &clusters,
+                &color.mix(layer.opacity.unwrap_or(1.0).clamp(0.0, 1.0)),
+                layer.point_size.unwrap_or(DEFAULT_POINT_SIZE),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// Groups of more than one accepted point feature of `layer` within `width` and
+// `height` of each other, with the positions of the features as members
+fn point_clusters(
+    layer: &PlotLayer,
+    filter: &dyn Fn(&Feature) -> bool,
+    (width, height): (f64, f64),
+) -> Vec<Cluster> {
+    let (positions, points): (Vec<usize>, Vec<(f64, f64)>) = layer
+        .features
+        .iter()
+        .enumerate()
+        .filter(|(_, feature)| filter(feature))
+        .filter_map(
+            |(position, feature)| match feature.geometry.as_ref()?.value {
+                Value::Point(ref p) => Some((position, (p[0], p[1]))),
+                _ => None,
+            },
+        )
+        .unzip();
+    cluster::cluster_points(&points, width, height)
+        .into_iter()
+        .filter(|cluster| cluster.members.len() > 1)
+        .map(|mut cluster| {
+            for member in &mut cluster.members {
+                *member = positions[*member];
+            }
+            cluster
+        })
+        .collect()
+}
+
+// Draws every cluster as a circle growing with the number of its points and
+// labeled with it
+fn draw_clusters(
+    chart: &mut GeoChart,
+    clusters: &[Cluster],
+    color: &RGBAColor,
+    point_size: u32,
+) -> Result<(), Box<dyn Error>> {
+    let RGBAColor(r, g, b, _) = *color;
+    let luminance = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+    let text_color = if luminance > 140.0 { BLACK } else { WHITE };
+    let font = ("sans-serif", 12)
+        .into_font()
+        .color(&text_color)
+        .pos(Pos::new(HPos::Center, VPos::Center));
+    chart.draw_series(clusters.iter().map(|cluster| {
+        let count = cluster.members.len();
+        let label = count.to_string();
+        // Wide enough for the count, and doubling the points adds a few pixels
+        let radius = (point_size as f64 + 3.0 * (count as f64).log2())
+            .min(MAX_SYMBOL_SIZE as f64)
+            .max(4.0 * label.len() as f64 + 2.0)
+            .round() as i32;
+        EmptyElement::at((cluster.x, cluster.y))
+            + Circle::new((0, 0), radius, color.filled())
+            + Circle::new((0, 0), radius, BLACK.stroke_width(1))
+            + Text::new(label, (0, 0), font.clone())
+    }))?;
+    Ok(())
+}
+
+// Draws `geometry`, that of `feature` or a simplified copy, in the color,
+// symbols and opacity of `layer` unless style rules override them, simplified
+// further with `tolerance`
+fn draw_feature(
+    chart: &mut GeoChart,
+    feature: &Feature,
+    (geometry, tolerance): (Option<&Geometry>, f64),
+    layer: &PlotLayer,
+    muted: bool,
+    styler: &Styler,
+    options: &PlotOptions,
+) -> Result<(), Box<dyn Error>> {
+    let Some(geometry) = geometry else {
+        return Ok(());
+    };
+    let opacity = layer.opacity.unwrap_or(1.0).clamp(0.0, 1.0);
+    let mut color = styler.color(feature, layer.color);
+    // A `size` rule overrides the layer's own sizes
+    let size = styler.size(feature);
+    let point_size = size.map_or(layer.point_size.unwrap_or(DEFAULT_POINT_SIZE), |s| {
+        s.round() as u32
+    });
+    let line_width = size.map_or(layer.line_width.unwrap_or(DEFAULT_LINE_WIDTH), |s| {
+        s.round().max(1.0) as u32
+    });
+    let symbol = (point_size, line_width);
+    let dashes = layer.line_style.unwrap_or(LineStyle::Solid);
+    let shape = layer.point_shape.unwrap_or(PointShape::Circle);
+    let types = options
+        .geometry_types()
+        .and(layer.geometry_types.unwrap_or(GeometryTypes::ALL));
+    let mut fill = styler.fill(feature);
+    if muted {
+        color = mute(color);
+        fill = fill.map(mute);
+    }
+    let simplified;
+    let geometry = if tolerance > 0.0 {
+        simplified = Geometry::new(simplify::simplify_geometry(&geometry.value, tolerance));
+        &simplified
+    } else {
+        geometry
+    };
+    if let Some(radius) = styler.proportional_radius(feature)
+        && types.points
+        && draw_proportional(chart, &geometry.value, radius, &color, opacity)?
+    {
+        return Ok(());
+    }
+    if !muted
+        && styler.colors_by_elevation()
+        && draw_by_elevation(
+            chart,
+            &geometry.value,
+            styler,
+            opacity,
+            (symbol, shape),
+            types,
+        )?
+    {
+        return Ok(());
+    }
+    draw_geometry(
+        chart,
+        geometry,
+        &color.mix(opacity),
+        symbol,
+        fill.map(|f| f.mix(opacity)),
+        (dashes, shape),
+        types,
+    )
+}
+
+// Draws points as translucent circles of `radius` pixels outlined in `color`.
+// Returns false, drawing nothing, for other geometries.
+fn draw_proportional(
+    chart: &mut GeoChart,
+    value: &Value,
+    radius: f64,
+    color: &RGBColor,
+    opacity: f64,
+) -> Result<bool, Box<dyn Error>> {
+    let positions: Vec<(f64, f64)> = match value {
+        Value::Point(p) => vec![(p[0], p[1])],
+        Value::MultiPoint(points) => points.iter().map(|p| (p[0], p[1])).collect(),
+        _ => return Ok(false),
+    };
+    let radius = radius.round() as i32;
+    let fill = color.mix(opacity * PROPORTIONAL_FILL_OPACITY).filled();
+    let outline = color.mix(opacity).stroke_width(1);
+    chart.draw_series(positions.into_iter().map(|position| {
+        EmptyElement::at(position)
+            + Circle::new((0, 0), radius, fill)
+            + Circle::new((0, 0), radius, outline)
+    }))?;
+    Ok(true)
+}
+
+// Draws points and lines whose positions all have an elevation, each point and
+// segment in the color of its elevation (segments that of the mean of their
+// ends). Segments are solid, as a dash pattern would restart at each of them.
+// Returns false, drawing nothing, for other geometries.
+fn draw_by_elevation(
+    chart: &mut GeoChart,
+    value: &Value,
+    styler: &Styler,
+    opacity: f64,
+    (symbol, shape): ((u32, u32), PointShape),
+    types: GeometryTypes,
+) -> Result<bool, Box<dyn Error>> {
+    let three_d = |positions: &[Vec<f64>]| positions.iter().all(|p| p.len() >= 3);
+    let color = |z: f64| styler.elevation_color(z).map(|c| c.mix(opacity));
+    let (point_size, line_width) = symbol;
+    let draw_points_of = |chart: &mut GeoChart, positions: &[Vec<f64>]| {
+        for p in positions {
+            if let Some(color) = color(p[2]) {
+                draw_points(chart, vec![(p[0], p[1])], point_size, &color, shape)?;
+            }
+        }
+        Ok::<_, Box<dyn Error>>(())
+    };
+    match value {
+        Value::Point(p) if types.points && three_d(std::slice::from_ref(p)) => {
+            draw_points_of(chart, std::slice::from_ref(p))?
+        }
+        Value::MultiPoint(points) if types.points && three_d(points) => {
+            draw_points_of(chart, points)?
+        }
+        Value::LineString(line) if types.lines && three_d(line) => {
+            draw_elevation_segments(chart, std::slice::from_ref(line), &color, line_width)?
+        }
+        Value::MultiLineString(lines) if types.lines && lines.iter().all(|l| three_d(l)) => {
+            draw_elevation_segments(chart, lines, &color, line_width)?
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn draw_elevation_segments(
+    chart: &mut GeoChart,
+    lines: &[Vec<Vec<f64>>],
+    color: &dyn Fn(f64) -> Option<RGBAColor>,
+    line_width: u32,
+) -> Result<(), Box<dyn Error>> {
+    let segments = lines
+        .iter()
+        .flat_map(|line| line.windows(2))
+        .filter_map(|pair| {
+            let (a, b) = (&pair[0], &pair[1]);
+            let color = color((a[2] + b[2]) / 2.0)?;
+            Some(PathElement::new(
+                vec![(a[0], a[1]), (b[0], b[1])],
+                color.stroke_width(line_width),
+            ))
+        });
+    chart.draw_series(segments)?;
+    Ok(())
+}
+
+// Reads the streamed layers feature by feature and draws them in the order of
+// `draw_layers`. Returns stand-ins for the labeled features, to draw their
+// labels on top of everything afterwards.
+fn draw_streamed(
+    chart: &mut GeoChart,
+    stream: &Stream,
+    options: &PlotOptions,
+    styler: &Styler,
+    global_tolerance: f64,
+) -> Result<Vec<(Feature, String)>, Box<dyn Error>> {
+    let layers = stream.layers;
+    let mut labels = Vec::new();
+    let focus = options.focus_layer.filter(|&i| i < layers.len());
+    let order = (0..layers.len()).filter(|&i| Some(i) != focus).chain(focus);
+    for i in order.filter(|&i| stream.failed[i].is_none()) {
+        let streamed = &layers[i];
+        let muted = focus.is_some_and(|focus| focus != i);
+        let tolerance = streamed
+            .layer
+            .simplify_tolerance
+            .map_or(global_tolerance, |t| {
+                t * options.projection.units_per_degree()
+            });
+        loader::stream_features(&streamed.path, streamed.crs.as_deref(), &mut |feature| {
+            if let Some(feature) = stream.prepare(streamed, feature, options) {
+                draw_feature(
+                    chart,
+                    &feature,
+                    (feature.geometry.as_ref(), tolerance),
+                    &streamed.layer,
+                    muted,
+                    styler,
+                    options,
+                )?;
+                if let Some(label) = styler.label(&feature) {
+                    labels.push((label_stand_in(&feature), label));
+                }
+            }
+            Ok(())
+        })?;
+    }
+    Ok(labels)
+}
+
+// Feature without properties whose label lands where that of `feature` does:
+// the corners of its bbox, as points for point features
+fn label_stand_in(feature: &Feature) -> Feature {
+    let geometry = feature.geometry.as_ref().map(|geometry| {
+        let mut bbox = empty_bbox();
+        extend_bbox(&mut bbox, &geometry.value);
+        let corners = vec![vec![bbox[0], bbox[1]], vec![bbox[2], bbox[3]]];
+        Geometry::new(match geometry.value {
+            Value::Point(_) | Value::MultiPoint(_) => Value::MultiPoint(corners),
+            _ => Value::LineString(corners),
+        })
+    });
+    Feature {
+        geometry,
+        ..Feature::default()
+    }
+}
+
+// Light grey of the same brightness as `color`, for layers out of focus
+fn mute(color: RGBColor) -> RGBColor {
+    let RGBColor(r, g, b) = color;
+    let luminance = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+    // Halfway to white keeps dark colors from competing with the focus layer
+    let grey = ((luminance + 255.0) / 2.0).round() as u8;
+    RGBColor(grey, grey, grey)
+}
+
+fn draw_markers(
+    chart: &mut GeoChart,
+    markers: &[MapMarker],
+    draw_text: bool,
+) -> Result<(), Box<dyn Error>> {
+    for marker in markers {
+        let position = (marker.lon, marker.lat);
+        chart.draw_series(std::iter::once(
+            EmptyElement::at(position)
+                + Circle::new((0, 0), 7, RED.filled())
+                + Circle::new((0, 0), 7, BLACK.stroke_width(2)),
+        ))?;
+        if draw_text && !marker.label.is_empty() {
+            // Offset so the label sits to the upper right of the marker
+            chart.draw_series(std::iter::once(
+                EmptyElement::at(position)
+                    + Text::new(
+                        marker.label.clone(),
+                        (10, -20),
+                        ("sans-serif", 18).into_font(),
+                    ),
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+// Most failed layers listed by name in the warning banner
+const BANNER_LINES: usize = 3;
+
+// Lists the layers missing from the image in a red strip across the top of
+// `area`, returning the area below it
+fn draw_warning_banner<'a>(
+    area: &DrawingArea<BitMapBackend<'a>, Shift>,
+    failed: &[String],
+) -> Result<DrawingArea<BitMapBackend<'a>, Shift>, Box<dyn Error>> {
+    if failed.is_empty() {
+        return Ok(area.clone());
+    }
+    let mut lines: Vec<String> = failed
+        .iter()
+        .take(BANNER_LINES)
+        .map(|failure| {
+            let failure: String = failure.chars().take(120).collect();
+            format!("Layer not drawn: {}", failure)
+        })
+        .collect();
+    if failed.len() > BANNER_LINES {
+        lines.push(format!(
+            "... and {} more layers not drawn",
+            failed.len() - BANNER_LINES
+        ));
+    }
+    let height = lines.len() as i32 * 20 + 8;
+    let (banner, rest) = area.split_vertically(height);
+    banner.fill(&RGBColor(255, 228, 225))?;
+    let (width, _) = banner.dim_in_pixel();
+    banner.draw(&Rectangle::new(
+        [(0, height - 2), (width as i32, height)],
+        RGBColor(200, 30, 30).filled(),
+    ))?;
+    let font = ("sans-serif", 16).into_font().color(&RGBColor(160, 20, 20));
+    for (i, line) in lines.into_iter().enumerate() {
+        banner.draw(&Text::new(line, (8, 4 + i as i32 * 20), font.clone()))?;
+    }
+    Ok(rest)
+}
+
+/// Describes the layers left out of a plot, for the summary printed after it.
+pub fn failed_layers_summary(failed: &[String]) -> Option<String> {
+    if failed.is_empty() {
+        return None;
+    }
+    let mut summary = format!(
+        "Left out {} layer{} that could not be read:",
+        failed.len(),
+        if failed.len() == 1 { "" } else { "s" }
+    );
+    for failure in failed {
+        summary.push_str("\n  ");
+        summary.push_str(failure);
+    }
+    Some(summary)
+}
+
+// Draws the title and subtitle above and the attribution below the chart,
+// returning the area left for the chart. Empty texts take no space.
+fn draw_titles<'a>(
+    area: &DrawingArea<BitMapBackend<'a>, Shift>,
+    options: &PlotOptions,
+) -> Result<DrawingArea<BitMapBackend<'a>, Shift>, Box<dyn Error>> {
+    let mut area = area.clone();
+    // Tile sources usually require credit, so theirs is used unless the plot has its own
+    let attribution = match &options.basemap {
+        Some(basemap) if options.attribution.is_empty() => &basemap.attribution,
+        _ => &options.attribution,
+    };
+    if !attribution.is_empty() {
+        let (width, height) = area.dim_in_pixel();
+        let (upper, strip) = area.split_vertically(height as i32 - 22);
+        let font = ("sans-serif", 16)
+            .into_font()
+            .color(&RGBColor(60, 60, 60))
+            .pos(Pos::new(HPos::Right, VPos::Top));
+        strip.draw(&Text::new(attribution.clone(), (width as i32 - 4, 4), font))?;
+        area = upper;
+    }
+    if !options.caption.is_empty() {
+        area = area.titled(&options.caption, ("sans-serif", 40).into_font())?;
+    }
+    if !options.subtitle.is_empty() {
+        area = area.titled(&options.subtitle, ("sans-serif", 24).into_font())?;
+    }
+    Ok(area)
+}
+
+// Fills the plotting area with the basemap tiles; tiles that cannot be loaded
+// leave the background visible. Returns a warning if there were any.
+fn draw_basemap(
+    chart: &GeoChart,
+    basemap: &Basemap,
+    extent: [f64; 4],
+) -> Result<Option<String>, Box<dyn Error>> {
+    let area = chart.plotting_area().strip_coord_spec();
+    let (width, height) = area.dim_in_pixel();
+    let image = basemap.render(extent, width, height);
+    for (i, pixel) in image.pixels.iter().enumerate() {
+        if let Some(color) = pixel {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            area.draw_pixel((x as i32, y as i32), color)?;
+        }
+    }
+    Ok(image.first_error.map(|error| {
+        format!(
+            "{} basemap tile(s) could not be loaded (first: {})",
+            image.missing_tiles, error
+        )
+    }))
+}
+
+// Draws the part of the `underlay` image inside `extent` into the plotting area
+fn draw_underlay(
+    chart: &GeoChart,
+    underlay: &Raster,
+    extent: [f64; 4],
+) -> Result<(), Box<dyn Error>> {
+    let area = chart.plotting_area().strip_coord_spec();
+    let (width, height) = area.dim_in_pixel();
+    for (i, pixel) in underlay.render(extent, width, height).iter().enumerate() {
+        if let Some(color) = pixel {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            area.draw_pixel((x as i32, y as i32), color)?;
+        }
+    }
+    Ok(())
+}
+
+// Centers `label` on the bbox of the feature, above it for points; labels of
+// features outside the extent are skipped
+fn draw_label(
+    chart: &GeoChart,
+    feature: &Feature,
+    label: String,
+    extent: [f64; 4],
+) -> Result<(), Box<dyn Error>> {
+    let Some(geometry) = &feature.geometry else {
+        return Ok(());
+    };
+    let mut bbox = empty_bbox();
+    extend_bbox(&mut bbox, &geometry.value);
+    let center = ((bbox[0] + bbox[2]) / 2.0, (bbox[1] + bbox[3]) / 2.0);
+    if bbox[0] == f64::MAX
+        || !(extent[0]..=extent[2]).contains(&center.0)
+        || !(extent[1]..=extent[3]).contains(&center.1)
+    {
+        return Ok(());
+    }
+    let offset = match geometry.value {
+        Value::Point(_) | Value::MultiPoint(_) => -14,
+        _ => 0,
+    };
+    let font = ("sans-serif", 14)
+        .into_font()
+        .color(&BLACK)
+        .pos(Pos::new(HPos::Center, VPos::Center));
+    chart
+        .plotting_area()
+        .draw(&(EmptyElement::at(center) + Text::new(label, (0, offset), font)))?;
+    Ok(())
+}
+
+// Covers everything drawn outside `mask` with `outside`, one pixel row at a time
+fn draw_clip_mask(
+    chart: &GeoChart,
+    mask: &ClipMask,
+    extent: [f64; 4],
+    outside: RGBColor,
+) -> Result<(), Box<dyn Error>> {
+    let area = chart.plotting_area().strip_coord_spec();
+    let (width, height) = area.dim_in_pixel();
+    let (width, height) = (width as i32, height as i32);
+    let to_column = |lon: f64| {
+        let column = (lon - extent[0]) / (extent[2] - extent[0]) * width as f64;
+        (column.round() as i32).clamp(0, width)
+    };
+    let style = outside.filled();
+    // Fills the columns from `start` up to, but excluding, `end` (the bitmap
+    // backend fills rectangles without their lower right edge)
+    let fill = |start: i32, end: i32, row: i32| -> Result<(), Box<dyn Error>> {
+        if end > start {
+            area.draw(&Rectangle::new([(start, row), (end, row + 1)], style))?;
+        }
+        Ok(())
+    };
+    for row in 0..height {
+        let lat = extent[3] - (row as f64 + 0.5) / height as f64 * (extent[3] - extent[1]);
+        // Outside spans run from the left edge to the first crossing, between
+        // pairs of crossings and from the last crossing to the right edge
+        let mut start = 0;
+        for pair in mask.crossings(lat).chunks(2) {
+            fill(start, to_column(pair[0]), row)?;
+            start = pair.get(1).map_or(width, |&lon| to_column(lon));
+        }
+        fill(start, width, row)?;
+    }
+    Ok(())
+}
+
+// Draws the parallels and meridians of a polar plot in place of the mesh
+fn draw_graticule(chart: &mut GeoChart, projection: Projection) -> Result<(), Box<dyn Error>> {
+    let style = BLACK.mix(0.2);
+    for line in projection.graticule() {
+        chart.draw_series(std::iter::once(PathElement::new(line, style)))?;
+    }
+    Ok(())
+}
+
+// Rounds `value` down to 1, 2 or 5 times a power of ten
+fn nice_length(value: f64) -> f64 {
+    let magnitude = 10f64.powf(value.log10().floor());
+    let step = [5.0, 2.0, 1.0]
+        .into_iter()
+        .find(|step| step * magnitude <= value)
+        .unwrap_or(1.0);
+    step * magnitude
+}
+
+// Draws a four-segment scale bar in the lower right of the plot. The ground
+// distance is measured along the central latitude of `extent`, as a degree of
+// longitude shrinks with the cosine of the latitude; polar plots are measured
+// at the pole.
+fn draw_scale_bar(
+    chart: &GeoChart,
+    extent: [f64; 4],
+    projection: Projection,
+) -> Result<(), Box<dyn Error>> {
+    const SEGMENTS: i32 = 4;
+    let area = chart.plotting_area().strip_coord_spec();
+    let (width, height) = area.dim_in_pixel();
+    let center_lat = (extent[1] + extent[3]) / 2.0;
+    // One degree of longitude along the central parallel, or a metre
+    let km_per_unit = match projection {
+        Projection::LonLat => geocode::haversine_km(0.0, center_lat, 1.0, center_lat),
+        _ => 0.001,
+    };
+    let km_per_pixel = km_per_unit * (extent[2] - extent[0]) / width as f64;
+    if !km_per_pixel.is_finite() || km_per_pixel <= 0.0 {
+        return Ok(());
+    }
+
+    // Aim for about a fifth of the plot width
+    let mut length_km = nice_length(km_per_pixel * width as f64 / 5.0);
+    let metres = length_km < 1.0;
+    if metres {
+        length_km = nice_length(length_km * 1000.0) / 1000.0;
+    }
+    let bar_pixels = (length_km / km_per_pixel).round() as i32;
+    let segment = (bar_pixels / SEGMENTS).max(1);
+    let (right, bottom) = (width as i32 - 50, height as i32 - 20);
+    let (left, top) = (right - segment * SEGMENTS, bottom - 8);
+    let font = ("sans-serif", 16).into_font();
+
+    area.draw(&Rectangle::new(
+        [(left - 40, top - 26), (right + 40, bottom + 8)],
+        WHITE.mix(0.85).filled(),
+    ))?;
+    for i in 0..SEGMENTS {
+        let x = left + segment * i;
+        let fill = if i % 2 == 0 { BLACK } else { WHITE };
+        area.draw(&Rectangle::new(
+            [(x, top), (x + segment, bottom)],
+            fill.filled(),
+        ))?;
+    }
+    area.draw(&Rectangle::new([(left, top), (right, bottom)], BLACK))?;
+    let label = if metres {
+        format!("{} m", length_km * 1000.0)
+    } else {
+        format!("{} km", length_km)
+    };
+    // Labels are centered above the ends of the bar
+    let font = font.color(&BLACK).pos(Pos::new(HPos::Center, VPos::Top));
+    area.draw(&Text::new("0", (left, top - 22), font.clone()))?;
+    area.draw(&Text::new(label, (right, top - 22), font))?;
+    Ok(())
+}
+
+// Draws an arrow pointing to the top of the map, which is north in
+// longitude/latitude coordinates, in the upper right of the plot
+// Draws a world map of the coastlines in a corner of the plot, with `extent`
+// outlined in red, or marked by a cross where it would be too small to see
+fn draw_inset(chart: &GeoChart, inset: Inset, extent: [f64; 4]) -> Result<(), Box<dyn Error>> {
+    const MARGIN: i32 = 10;
+    let area = chart.plotting_area().strip_coord_spec();
+    let (map_width, map_height) = area.dim_in_pixel();
+    let width = (map_width as i32 * inset.size.clamp(5, 50) as i32 / 100).max(60);
+    let height = width / 2; // The world is twice as wide as high in degrees
+    let left = match inset.corner {
+        InsetCorner::UpperLeft | InsetCorner::LowerLeft => MARGIN,
+        InsetCorner::UpperRight | InsetCorner::LowerRight => map_width as i32 - MARGIN - width,
+    };
+    let top = match inset.corner {
+        InsetCorner::UpperLeft | InsetCorner::UpperRight => MARGIN,
+        InsetCorner::LowerLeft | InsetCorner::LowerRight => map_height as i32 - MARGIN - height,
+    };
+    let pixel = |(lon, lat): (f64, f64)| {
+        (
+            left + ((lon.clamp(-180.0, 180.0) + 180.0) / 360.0 * width as f64).round() as i32,
+            top + ((90.0 - lat.clamp(-90.0, 90.0)) / 180.0 * height as f64).round() as i32,
+        )
+    };
+
+    let frame = [(left, top), (left + width, top + height)];
+    area.draw(&Rectangle::new(frame, WHITE.filled()))?;
+    for line in inset::world_coastlines() {
+        area.draw(&PathElement::new(
+            line.iter()
+                .map(|&position| pixel(position))
+                .collect::<Vec<_>>(),
+            RGBColor(90, 90, 90),
+        ))?;
+    }
+    let (low, high) = (pixel((extent[0], extent[1])), pixel((extent[2], extent[3])));
+    if high.0 - low.0 < 4 && low.1 - high.1 < 4 {
+        let (x, y) = ((low.0 + high.0) / 2, (low.1 + high.1) / 2);
+        let red = RED.stroke_width(2);
+        area.draw(&PathElement::new(vec![(x - 5, y), (x + 5, y)], red))?;
+        area.draw(&PathElement::new(vec![(x, y - 5), (x, y + 5)], red))?;
+    } else {
+        area.draw(&Rectangle::new(
+            [(low.0, high.1), (high.0, low.1)],
+            RED.stroke_width(2),
+        ))?;
+    }
+    area.draw(&Rectangle::new(frame, BLACK))?;
+    Ok(())
+}
+
+fn draw_north_arrow(chart: &GeoChart) -> Result<(), Box<dyn Error>> {
+    let area = chart.plotting_area().strip_coord_spec();
+    let (width, _) = area.dim_in_pixel();
+    let (x, top) = (width as i32 - 40, 30);
+    let tip = (x, top + 20);
+    area.draw(&Polygon::new(
+        vec![tip, (x - 12, top + 60), (x, top + 50)],
+        BLACK.filled(),
+    ))?;
+    area.draw(&Polygon::new(
+        vec![tip, (x + 12, top + 60), (x, top + 50)],
+        WHITE.filled(),
+    ))?;
+    area.draw(&PathElement::new(
+        vec![
+            tip,
+            (x + 12, top + 60),
+            (x, top + 50),
+            (x - 12, top + 60),
+            tip,
+        ],
+        BLACK,
+    ))?;
+    area.draw(&Text::new(
+        "N",
+        (x - 7, top - 4),
+        ("sans-serif", 22).into_font().style(FontStyle::Bold),
+    ))?;
+    Ok(())
+}
+
+// Draws a color bar with one swatch per class in the lower left of the plot
+fn draw_legend(chart: &GeoChart, legend: &Legend) -> Result<(), Box<dyn Error>> {
+    const ROW_HEIGHT: i32 = 24;
+    const SWATCH_WIDTH: i32 = 36;
+    let area = chart.plotting_area().strip_coord_spec();
+    let (_, height) = area.dim_in_pixel();
+    let (left, bottom) = (20, height as i32 - 20);
+    let rows = legend.entries.len() as i32 + 1 + legend.no_data.is_some() as i32;
+    let top = bottom - ROW_HEIGHT * rows - 10;
+    let right = left + SWATCH_WIDTH + 200;
+    let font = ("sans-serif", 18).into_font();
+
+    area.draw(&Rectangle::new(
+        [(left, top), (right, bottom)],
+        WHITE.mix(0.85).filled(),
+    ))?;
+    area.draw(&Rectangle::new([(left, top), (right, bottom)], BLACK))?;
+    area.draw(&Text::new(
+        legend.title.clone(),
+        (left + 8, top + 6),
+        font.clone(),
+    ))?;
+    // Highest class at the top, like a vertical color bar
+    for (row, (color, label)) in legend.entries.iter().rev().enumerate() {
+        let y = top + ROW_HEIGHT * (row as i32 + 1) + 4;
+        let swatch = [(left + 8, y), (left + 8 + SWATCH_WIDTH, y + ROW_HEIGHT)];
+        area.draw(&Rectangle::new(swatch, color.filled()))?;
+        area.draw(&Text::new(
+            label.clone(),
+            (left + SWATCH_WIDTH + 16, y + 3),
+            font.clone(),
+        ))?;
+    }
+    if let Some(color) = legend.no_data {
+        let y = top + ROW_HEIGHT * (legend.entries.len() as i32 + 1) + 8; // Set apart from the bar
+        let swatch = [(left + 8, y), (left + 8 + SWATCH_WIDTH, y + ROW_HEIGHT - 4)];
+        area.draw(&Rectangle::new(swatch, color.filled()))?;
+        area.draw(&Rectangle::new(swatch, BLACK.mix(0.4)))?;
+        area.draw(&Text::new(
+            "No data",
+            (left + SWATCH_WIDTH + 16, y + 1),
+            font.clone(),
+        ))?;
+    }
+    // Diverging classes are symmetric around the midpoint, so it is the middle of the bar
+    if legend.midpoint.is_some() {
+        let y = top + ROW_HEIGHT + 4 + ROW_HEIGHT * legend.entries.len() as i32 / 2;
+        area.draw(&Rectangle::new(
+            [(left + 4, y - 1), (left + 12 + SWATCH_WIDTH, y + 1)],
+            BLACK.filled(),
+        ))?;
+    }
+    Ok(())
+}
+
+// Nested circles standing on a common base line in the upper left, each
+// labeled with its value at the end of a line from its top
+fn draw_size_legend(chart: &GeoChart, legend: &SizeLegend) -> Result<(), Box<dyn Error>> {
+    const TITLE_HEIGHT: i32 = 28;
+    const LABEL_WIDTH: i32 = 90;
+    let area = chart.plotting_area().strip_coord_spec();
+    let largest = legend.entries.first().map_or(0, |(r, _)| r.round() as i32);
+    let (left, top) = (20, 20);
+    let center_x = left + 10 + largest;
+    let base = top + TITLE_HEIGHT + 2 * largest + 4;
+    let right = center_x + largest + 16 + LABEL_WIDTH;
+    let font = ("sans-serif", 18).into_font();
+
+    area.draw(&Rectangle::new(
+        [(left, top), (right, base + 10)],
+        WHITE.mix(0.85).filled(),
+    ))?;
+    area.draw(&Rectangle::new([(left, top), (right, base + 10)], BLACK))?;
+    area.draw(&Text::new(
+        legend.title.clone(),
+        (left + 8, top + 6),
+        font.clone(),
+    ))?;
+    for (radius, label) in &legend.entries {
+        let radius = radius.round() as i32;
+        let circle_top = base - 2 * radius;
+        area.draw(&Circle::new(
+            (center_x, base - radius),
+            radius,
+            BLACK.stroke_width(1),
+        ))?;
+        area.draw(&PathElement::new(
+            vec![
+                (center_x, circle_top),
+                (center_x + largest + 10, circle_top),
+            ],
+            BLACK.mix(0.6),
+        ))?;
+        area.draw(&Text::new(
+            label.clone(),
+            (center_x + largest + 14, circle_top - 8),
+            ("sans-serif", 14).into_font(),
+        ))?;
+    }
+    Ok(())
+}
+
+// `symbol` is the point radius and line width in pixels
+fn draw_geometry(
+    chart: &mut GeoChart,
+    geometry: &Geometry,
+    color: &RGBAColor,
+    symbol: (u32, u32),
+    fill: Option<RGBAColor>, // Polygon fill, e.g. from a `choropleth` style rule
+    (dashes, shape): (LineStyle, PointShape),
+    types: GeometryTypes,
+) -> Result<(), Box<dyn Error>> {
+    let (point_size, line_width) = symbol;
+    let line_style = color.stroke_width(line_width);
+    // Filled rings get a thin grey outline so neighbouring classes stay
+    // distinguishable; translucent layers are filled with their own color
+    let (ring_fill, ring_outline) = match fill {
+        Some(fill) => (
+            Some(fill),
+            RGBColor(90, 90, 90).mix(color.3).stroke_width(1),
+        ),
+        None => ((color.3 < 1.0).then_some(*color), line_style),
+    };
+    match &geometry.value {
+        Value::Point(c) if types.points => {
+            draw_points(chart, vec![(c[0], c[1])], point_size, color, shape)?;
+        }
+        Value::MultiPoint(coords_vec) if types.points => {
+            let positions = coords_vec.iter().map(|c| (c[0], c[1])).collect();
+            draw_points(chart, positions, point_size, color, shape)?;
+        }
+        Value::LineString(lines) if types.lines => {
+            draw_line(chart, lines, line_style, dashes)?;
+        }
+        Value::MultiLineString(multi_lines) if types.lines => {
+            for lines_segment in multi_lines {
+                draw_line(chart, lines_segment, line_style, dashes)?;
+            }
+        }
+        Value::Polygon(polygon_rings) if types.polygons => {
+            // Draw the exterior ring of the polygon
+            if let Some(exterior_ring) = polygon_rings.first() {
+                draw_exterior_ring(chart, exterior_ring, ring_outline, ring_fill, dashes)?;
+            }
+        }
+        Value::MultiPolygon(multi_polygon) if types.polygons => {
+            for polygon in multi_polygon {
+                if let Some(exterior_ring) = polygon.first() {
+                    draw_exterior_ring(chart, exterior_ring, ring_outline, ring_fill, dashes)?;
+                }
+            }
+        }
+        Value::GeometryCollection(geometries) => {
+            for geometry in geometries {
+                draw_geometry(chart, geometry, color, symbol, fill, (dashes, shape), types)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Draws a `shape` of radius `size` pixels at each of `positions`
+fn draw_points(
+    chart: &mut GeoChart,
+    positions: Vec<(f64, f64)>,
+    size: u32,
+    color: &RGBAColor,
+    shape: PointShape,
+) -> Result<(), Box<dyn Error>> {
+    let style = color.filled();
+    match shape {
+        PointShape::Circle => chart.draw_series(PointSeries::of_element(
+            positions,
+            size,
+            style,
+            &|c, s, st| EmptyElement::at(c) + Circle::new((0, 0), s, st),
+        ))?,
+        PointShape::Square => chart.draw_series(PointSeries::of_element(
+            positions,
+            size as i32,
+            style,
+            &|c, s, st| EmptyElement::at(c) + Rectangle::new([(-s, -s), (s, s)], st),
+        ))?,
+        PointShape::Triangle => chart.draw_series(PointSeries::of_element(
+            positions,
+            size as f64,
+            style,
+            &|c, s, st| {
+                // Pointing up, with its centroid on the position
+                let half_base = (s * 0.866).round() as i32;
+                let corners = vec![
+                    (0, -s.round() as i32),
+                    (-half_base, (s / 2.0).round() as i32),
+                    (half_base, (s / 2.0).round() as i32),
+                ];
+                EmptyElement::at(c) + Polygon::new(corners, st)
+            },
+        ))?,
+        PointShape::Cross => {
+            // Strokes as thick as a third of the size keep small crosses visible
+            let stroke = color.stroke_width((size / 3).max(1));
+            chart.draw_series(PointSeries::of_element(
+                positions,
+                size as i32,
+                stroke,
+                &|c, s, st| {
+                    EmptyElement::at(c)
+                        + PathElement::new(vec![(-s, -s), (s, s)], st)
+                        + PathElement::new(vec![(-s, s), (s, -s)], st)
+                },
+            ))?
+        }
+    };
+    Ok(())
+}
+
+fn draw_exterior_ring(
+    chart: &mut GeoChart,
+    ring: &[Vec<f64>],
+    outline: ShapeStyle,
+    fill: Option<RGBAColor>,
+    dashes: LineStyle,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(fill) = fill {
+        chart.draw_series(std::iter::once(Polygon::new(
+            ring.iter()
+                .map(|point| (point[0], point[1]))
+                .collect::<Vec<_>>(),
+            fill.filled(),
+        )))?;
+    }
+    draw_line(chart, ring, outline, dashes)
+}
+
+// Draws `line` solid, or as the dashes of `dashes` cut out of it with a
+// pattern measured in pixels along the line
+fn draw_line(
+    chart: &mut GeoChart,
+    line: &[Vec<f64>],
+    style: ShapeStyle,
+    dashes: LineStyle,
+) -> Result<(), Box<dyn Error>> {
+    let points = line.iter().map(|point| (point[0], point[1]));
+    if dashes == LineStyle::Solid {
+        chart.draw_series(LineSeries::new(points, style))?;
+        return Ok(());
+    }
+    let scale = style.stroke_width.max(1) as f64;
+    let pattern: Vec<f64> = dashes.pattern().iter().map(|l| l * scale).collect();
+    let mut pieces: Vec<Vec<(f64, f64)>> = Vec::new();
+    let mut piece = Vec::new();
+    let mut step = 0; // Even steps of the pattern are dashes, odd ones gaps
+    let mut left = pattern[0]; // Pixels until the current step ends
+    let positions: Vec<(f64, f64)> = points.collect();
+    for pair in positions.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        if step % 2 == 0 && piece.is_empty() {
+            piece.push(a);
+        }
+        let (pa, pb) = (chart.backend_coord(&a), chart.backend_coord(&b));
+        let length = ((pb.0 - pa.0) as f64).hypot((pb.1 - pa.1) as f64);
+        let mut along = 0.0;
+        while length - along > left {
+            along += left;
+            // The chart is linear, so the fraction in pixels is the one in degrees
+            let t = along / length;
+            piece.push((a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t));
+            if step % 2 == 0 {
+                pieces.push(std::mem::take(&mut piece));
+            }
+            step = (step + 1) % pattern.len();
+            left = pattern[step];
+        }
+        left -= length - along;
+        if step % 2 == 0 {
+            piece.push(b);
+        }
+    }
+    if piece.len() > 1 {
+        pieces.push(piece);
+    }
+    chart.draw_series(
+        pieces
+            .into_iter()
+            .map(|piece| PathElement::new(piece, style)),
+    )?;
+    Ok(())
+}
+
+/// Converts a property value into the text used for grouping and file names.
+pub fn property_to_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Collects the sorted, unique values of `property` across all features of `layers`.
+pub fn unique_property_values(layers: &[PlotLayer], property: &str) -> Vec<String> {
+    let mut values: Vec<String> = layers
+        .iter()
+        .flat_map(|layer| layer.features.iter())
+        .filter_map(|feature| feature.property(property))
+        .filter(|value| !value.is_null())
+        .map(property_to_string)
+        .collect();
+    values.sort();
+    values.dedup();
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: RGBColor = RGBColor(255, 0, 0);
+
+    // A 100 x 80 map of [0, 0, 10, 10] with a thick horizontal line through the middle
+    fn line_plot(color: RGBColor) -> (Vec<PlotLayer>, PlotOptions) {
+        let line = Geometry::new(Value::LineString(vec![vec![0.0, 5.0], vec![10.0, 5.0]]));
+        let mut layer = PlotLayer::from_geojson(GeoJson::Geometry(line), color);
+        layer.line_width = Some(5);
+        let options = PlotOptions {
+            width: 100,
+            height: 80,
+            extent: Some([0.0, 0.0, 10.0, 10.0]),
+            ..PlotOptions::default()
+        };
+        (vec![layer], options)
+    }
+
+    fn pixel(buffer: &[u8], width: u32, x: u32, y: u32) -> RGBColor {
+        let at = (y * width + x) as usize * 3;
+        RGBColor(buffer[at], buffer[at + 1], buffer[at + 2])
+    }
+
+    #[test]
+    fn golden_renders_are_deterministic() {
+        let (layers, options) = line_plot(RED);
+        let first = render_golden(&layers, &options).unwrap();
+        assert_eq!(first.len(), 100 * 80 * 3);
+        assert_eq!(first, render_golden(&layers, &options).unwrap());
+    }
+
+    #[test]
+    fn golden_renders_draw_the_layers() {
+        let (layers, options) = line_plot(RED);
+        let buffer = render_golden(&layers, &options).unwrap();
+        assert_eq!(pixel(&buffer, 100, 50, 40), RED);
+        assert_ne!(pixel(&buffer, 100, 50, 20), RED);
+
+        let (layers, options) = line_plot(RGBColor(0, 0, 255));
+        let blue = render_golden(&layers, &options).unwrap();
+        assert_eq!(pixel(&blue, 100, 50, 40), RGBColor(0, 0, 255));
+    }
+
+    #[test]
+    fn golden_renders_follow_the_toggles() {
+        let (layers, mut options) = line_plot(RED);
+        options.plot_lines = false;
+        let buffer = render_golden(&layers, &options).unwrap();
+        assert!(buffer.chunks(3).all(|rgb| rgb != [255, 0, 0]));
+    }
+}
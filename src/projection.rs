@@ -0,0 +1,287 @@
+// projection.rs
+
+use geojson::{Feature, Geometry, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::clip;
+use crate::crs;
+use crate::plot;
+
+// Longest step in longitude between the vertices of a projected line. Lines
+// are straight in longitude/latitude, which makes an edge along a parallel an
+// arc around the pole
+const MAX_STEP_DEGREES: f64 = 1.0;
+
+// Spacing of the parallels and meridians drawn over polar plots
+const PARALLEL_SPACING: f64 = 10.0;
+const MERIDIAN_SPACING: f64 = 30.0;
+
+/// How longitude/latitude is laid out on plotted images.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Projection {
+    #[default]
+    LonLat, // Longitude across, latitude up (plate carrée)
+    NorthPolar, // Stereographic around the North Pole
+    SouthPolar, // Stereographic around the South Pole
+}
+
+impl Projection {
+    pub fn parse(text: &str) -> Option<Projection> {
+        match text {
+            "lonlat" | "plate-carree" => Some(Projection::LonLat),
+            "north-polar" | "arctic" => Some(Projection::NorthPolar),
+            "south-polar" | "antarctic" => Some(Projection::SouthPolar),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Projection::LonLat => "lonlat",
+            Projection::NorthPolar => "north-polar",
+            Projection::SouthPolar => "south-polar",
+        }
+    }
+
+    /// The projection after this one, cycling through all of them.
+    pub fn next(self) -> Projection {
+        match self {
+            Projection::LonLat => Projection::NorthPolar,
+            Projection::NorthPolar => Projection::SouthPolar,
+            Projection::SouthPolar => Projection::LonLat,
+        }
+    }
+
+    pub fn is_polar(self) -> bool {
+        self != Projection::LonLat
+    }
+
+    /// Plot coordinates of a longitude/latitude: degrees as they are, or polar
+    /// stereographic metres.
+    pub fn project(self, lon: f64, lat: f64) -> (f64, f64) {
+        match self {
+            Projection::LonLat => (lon, lat),
+            Projection::NorthPolar => crs::lonlat_to_polar_stereographic(lon, lat, true),
+            Projection::SouthPolar => crs::lonlat_to_polar_stereographic(lon, lat, false),
+        }
+    }
+
+    /// Plot units per degree, to convert tolerances given in degrees; exact at
+    /// the pole for the polar projections.
+    pub fn units_per_degree(self) -> f64 {
+        match self {
+            Projection::LonLat => 1.0,
+            _ => crs::WEB_MERCATOR_EXTENT / 180.0,
+        }
+    }
+
+    // The hemisphere drawn by a polar projection, whose opposite pole lies at
+    // infinity
+    fn hemisphere(self) -> [f64; 4] {
+        match self {
+            Projection::NorthPolar => [-180.0, 0.0, 180.0, 90.0],
+            _ => [-180.0, -90.0, 180.0, 0.0],
+        }
+    }
+
+    /// Copies of `features` in plot coordinates. For the polar projections the
+    /// parts outside the hemisphere are cut off and lines are given a vertex
+    /// every degree of longitude, so features along a pole, such as Antarctica
+    /// ending in an edge along latitude -90, close around it instead of
+    /// smearing across the plot.
+    pub fn project_features(self, features: &[Feature]) -> Vec<Feature> {
+        if !self.is_polar() {
+            return features.to_vec();
+        }
+        let [_, south, _, north] = self.hemisphere();
+        // Wider than any longitude and beyond the pole, so only the equator is
+        // an edge data can share
+        let beyond = if north > 0.0 { 91.0 } else { -91.0 };
+        let ring = vec![
+            vec![-361.0, 0.0],
+            vec![361.0, 0.0],
+            vec![361.0, beyond],
+            vec![-361.0, beyond],
+            vec![-361.0, 0.0],
+        ];
+        let mask = clip::Mask::new(
+            &[ring],
+            [-361.0, south.min(beyond), 361.0, north.max(beyond)],
+        );
+        features
+            .iter()
+            .filter_map(|feature| {
+                let geometry = feature.geometry.as_ref()?;
+                let mut bbox = plot::empty_bbox();
+                plot::extend_bbox(&mut bbox, &geometry.value);
+                let clipped;
+                let feature = if bbox[1] >= south && bbox[3] <= north {
+                    feature
+                } else if bbox[1] > north || bbox[3] < south {
+                    return None;
+                } else {
+                    clipped = clip::clip_features(std::slice::from_ref(feature), &mask).pop()?;
+                    &clipped
+                };
+                let value = &feature.geometry.as_ref()?.value;
+                Some(Feature {
+                    bbox: None,
+                    geometry: Some(Geometry::new(self.project_value(value))),
+                    ..feature.clone()
+                })
+            })
+            .collect()
+    }
+
+    /// Bbox in plot coordinates of the area `[min_lon, min_lat, max_lon,
+    /// max_lat]`, limited to the hemisphere of a polar projection.
+    pub fn project_extent(self, extent: [f64; 4]) -> [f64; 4] {
+        if !self.is_polar() {
+            return extent;
+        }
+        let [_, south, _, north] = self.hemisphere();
+        let (min_lat, max_lat) = (extent[1].max(south), extent[3].min(north));
+        // The outline of the area holds its extremes
+        let outline = vec![
+            vec![extent[0], min_lat],
+            vec![extent[2], min_lat],
+            vec![extent[2], max_lat],
+            vec![extent[0], max_lat],
+            vec![extent[0], min_lat],
+        ];
+        let mut bbox = plot::empty_bbox();
+        plot::extend_bbox(&mut bbox, &self.project_value(&Value::LineString(outline)));
+        bbox
+    }
+
+    /// Parallels and meridians of a polar projection, in plot coordinates.
+    pub fn graticule(self) -> Vec<Vec<(f64, f64)>> {
+        if !self.is_polar() {
+            return Vec::new();
+        }
+        let [_, south, _, north] = self.hemisphere();
+        let line =
+            |positions: Vec<Vec<f64>>| match self.project_value(&Value::LineString(positions)) {
+                Value::LineString(line) => line.iter().map(|p| (p[0], p[1])).collect(),
+                _ => Vec::new(),
+            };
+        let mut lines = Vec::new();
+        let mut lat = south;
+        while lat <= north {
+            if lat.abs() < 90.0 {
+                lines.push(line(vec![vec![-180.0, lat], vec![180.0, lat]]));
+            }
+            lat += PARALLEL_SPACING;
+        }
+        let mut lon = -180.0;
+        while lon < 180.0 {
+            lines.push(line(vec![vec![lon, south], vec![lon, north]]));
+            lon += MERIDIAN_SPACING;
+        }
+        lines
+    }
+
+    fn project_value(self, value: &Value) -> Value {
+        let point = |p: &Vec<f64>| self.project_position(p);
+        let line = |line: &Vec<Vec<f64>>| self.project_line(line, false);
+        let ring = |ring: &Vec<Vec<f64>>| self.project_line(ring, true);
+        match value {
+            Value::Point(p) => Value::Point(point(p)),
+            Value::MultiPoint(points) => Value::MultiPoint(points.iter().map(point).collect()),
+            Value::LineString(positions) => Value::LineString(line(positions)),
+            Value::MultiLineString(lines) => {
+                Value::MultiLineString(lines.iter().map(line).collect())
+            }
+            Value::Polygon(rings) => Value::Polygon(rings.iter().map(ring).collect()),
+            Value::MultiPolygon(polygons) => Value::MultiPolygon(
+                polygons
+                    .iter()
+                    .map(|rings| rings.iter().map(ring).collect())
+                    .collect(),
+            ),
+            Value::GeometryCollection(geometries) => Value::GeometryCollection(
+                geometries
+                    .iter()
+                    .map(|g| Geometry::new(self.project_value(&g.value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    // Further values of the position (e.g. elevation) are kept
+    fn project_position(self, position: &[f64]) -> Vec<f64> {
+        if position.len() < 2 {
+            return position.to_vec();
+        }
+        let (x, y) = self.project(position[0], position[1]);
+        let mut projected = vec![x, y];
+        projected.extend_from_slice(&position[2..]);
+        projected
+    }
+
+    // Projects `line`, adding vertices along parallels, with elevations
+    // interpolated if both ends have one. A ring closed around
+    // the pole by running up the antimeridian, along the pole and back down the
+    // other side, as Antarctica is in longitude/latitude, skips that detour:
+    // both sides of it project onto the same meridian, and drawn it would be a
+    // line from the coast to the pole
+    fn project_line(self, line: &[Vec<f64>], ring: bool) -> Vec<Vec<f64>> {
+        let mut projected = Vec::with_capacity(line.len());
+        let mut previous: Option<&Vec<f64>> = None;
+        let mut i = 0;
+        while i < line.len() {
+            let position = &line[i];
+            if ring && let Some(end) = self.seam_detour(&line[i..]) {
+                let last = &line[i + end - 1];
+                projected.push(self.project_position(position));
+                projected.push(self.project_position(last));
+                previous = Some(last);
+                i += end;
+                continue;
+            }
+            if let Some(previous) = previous
+                && previous.len() >= 2
+                && position.len() >= 2
+            {
+                let steps = ((position[0] - previous[0]).abs() / MAX_STEP_DEGREES).ceil() as usize;
+                for step in 1..steps {
+                    let t = step as f64 / steps as f64;
+                    let (x, y) = self.project(
+                        previous[0] + t * (position[0] - previous[0]),
+                        previous[1] + t * (position[1] - previous[1]),
+                    );
+                    let mut added = vec![x, y];
+                    if let (Some(za), Some(zb)) = (previous.get(2), position.get(2)) {
+                        added.push(za + t * (zb - za));
+                    }
+                    projected.push(added);
+                }
+            }
+            projected.push(self.project_position(position));
+            previous = Some(position);
+            i += 1;
+        }
+        projected
+    }
+
+    // Number of positions at the start of `positions` that run from one side
+    // of the antimeridian through the pole to the other, if they do
+    fn seam_detour(self, positions: &[Vec<f64>]) -> Option<usize> {
+        const EPSILON: f64 = 1e-9;
+        let pole = if self == Projection::NorthPolar {
+            90.0
+        } else {
+            -90.0
+        };
+        let at_pole = |p: &Vec<f64>| (p[1] - pole).abs() < EPSILON;
+        let on_antimeridian = |p: &Vec<f64>| (p[0].abs() - 180.0).abs() < EPSILON;
+        let run = positions
+            .iter()
+            .take_while(|p| p.len() >= 2 && (at_pole(p) || on_antimeridian(p)))
+            .count();
+        let (first, last) = (positions.first()?, &positions[run.checked_sub(1)?]);
+        let crosses = on_antimeridian(first) && on_antimeridian(last) && first[0] != last[0];
+        (crosses && positions[..run].iter().any(at_pole)).then_some(run)
+    }
+}
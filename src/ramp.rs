@@ -0,0 +1,376 @@
+// ramp.rs
+
+use plotters::prelude::RGBColor;
+use std::fmt;
+
+use crate::style::parse_color;
+
+// Built-in ramps, from low to high values
+const NAMED_RAMPS: [(&str, &[RGBColor]); 11] = [
+    (
+        "ylorrd",
+        &[
+            RGBColor(255, 255, 204),
+            RGBColor(254, 217, 118),
+            RGBColor(253, 141, 60),
+            RGBColor(227, 26, 28),
+            RGBColor(128, 0, 38),
+        ],
+    ),
+    ("blues", &[RGBColor(239, 243, 255), RGBColor(8, 69, 148)]),
+    ("greens", &[RGBColor(237, 248, 233), RGBColor(0, 109, 44)]),
+    ("greys", &[RGBColor(247, 247, 247), RGBColor(37, 37, 37)]),
+    (
+        "viridis",
+        &[
+            RGBColor(68, 1, 84),
+            RGBColor(59, 82, 139),
+            RGBColor(33, 145, 140),
+            RGBColor(94, 201, 98),
+            RGBColor(253, 231, 37),
+        ],
+    ),
+    (
+        "spectral",
+        &[
+            RGBColor(215, 25, 28),
+            RGBColor(253, 174, 97),
+            RGBColor(255, 255, 191),
+            RGBColor(171, 221, 164),
+            RGBColor(43, 131, 186),
+        ],
+    ),
+    // Lowland green through yellow to mountain brown, for elevations
+    (
+        "terrain",
+        &[
+            RGBColor(26, 150, 65),
+            RGBColor(166, 217, 106),
+            RGBColor(255, 255, 191),
+            RGBColor(223, 160, 90),
+            RGBColor(140, 81, 10),
+        ],
+    ),
+    // Diverging ramps, light in the middle, for use with a midpoint
+    (
+        "bwr",
+        &[
+            RGBColor(33, 102, 172),
+            RGBColor(146, 197, 222),
+            RGBColor(247, 247, 247),
+            RGBColor(244, 165, 130),
+            RGBColor(178, 24, 43),
+        ],
+    ),
+    (
+        "rdbu",
+        &[
+            RGBColor(178, 24, 43),
+            RGBColor(244, 165, 130),
+            RGBColor(247, 247, 247),
+            RGBColor(146, 197, 222),
+            RGBColor(33, 102, 172),
+        ],
+    ),
+    (
+        "brbg",
+        &[
+            RGBColor(140, 81, 10),
+            RGBColor(223, 194, 125),
+            RGBColor(245, 245, 245),
+            RGBColor(128, 205, 193),
+            RGBColor(1, 102, 94),
+        ],
+    ),
+    (
+        "piyg",
+        &[
+            RGBColor(197, 27, 125),
+            RGBColor(241, 182, 218),
+            RGBColor(247, 247, 247),
+            RGBColor(184, 225, 134),
+            RGBColor(77, 146, 33),
+        ],
+    ),
+];
+
+/// Colors interpolated between evenly spaced stops.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorRamp {
+    text: String, // Name or stop list as written by the user
+    stops: Vec<RGBColor>,
+}
+
+impl ColorRamp {
+    /// Parses a built-in ramp name (`ylorrd`, `blues`, `greens`, `greys`, `viridis`,
+    /// `spectral`, `terrain`, or the diverging `bwr`, `rdbu`, `brbg` and `piyg`) or a custom
+    /// ramp of two or more colors, e.g. `#ffffcc-#800026`.
+    pub fn parse(text: &str) -> Result<ColorRamp, String> {
+        let lower = text.to_lowercase();
+        if let Some((_, stops)) = NAMED_RAMPS.iter().find(|(name, _)| *name == lower) {
+            return Ok(ColorRamp {
+                text: lower,
+                stops: stops.to_vec(),
+            });
+        }
+        let stops = text
+            .split('-')
+            .map(parse_color)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| {
+                let names: Vec<&str> = NAMED_RAMPS.iter().map(|(name, _)| *name).collect();
+                format!(
+                    "Unknown ramp '{}' (use {} or #rrggbb-#rrggbb)",
+                    text,
+                    names.join(", ")
+                )
+            })?;
+        if stops.len() < 2 {
+            return Err(format!("Ramp '{}' needs at least two colors", text));
+        }
+        Ok(ColorRamp {
+            text: text.to_string(),
+            stops,
+        })
+    }
+
+    /// Color at position `t` in [0, 1].
+    pub fn sample(&self, t: f64) -> RGBColor {
+        let scaled = t.clamp(0.0, 1.0) * (self.stops.len() - 1) as f64;
+        let i = (scaled.floor() as usize).min(self.stops.len() - 2);
+        let (a, b) = (self.stops[i], self.stops[i + 1]);
+        let f = scaled - i as f64;
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * f).round() as u8;
+        RGBColor(mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+    }
+
+    /// Colors for `classes` classes, spread over the whole ramp.
+    pub fn class_colors(&self, classes: usize) -> Vec<RGBColor> {
+        (0..classes)
+            .map(|i| self.sample(i as f64 / (classes.max(2) - 1) as f64))
+            .collect()
+    }
+}
+
+impl fmt::Display for ColorRamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// How numeric values are divided into classes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClassMethod {
+    EqualInterval, // Classes span equal value ranges
+    Quantile,      // Classes hold (roughly) equal numbers of features
+    Log,           // Classes span equal ranges of the logarithm, for skewed data
+    Equalize,      // Unclassed: colored by percentile rank (histogram equalization)
+}
+
+impl ClassMethod {
+    pub fn parse(text: &str) -> Option<ClassMethod> {
+        match text {
+            "equal" | "equal_interval" => Some(ClassMethod::EqualInterval),
+            "quantile" => Some(ClassMethod::Quantile),
+            "log" => Some(ClassMethod::Log),
+            "equalize" | "histogram" => Some(ClassMethod::Equalize),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ClassMethod::EqualInterval => "equal",
+            ClassMethod::Quantile => "quantile",
+            ClassMethod::Log => "log",
+            ClassMethod::Equalize => "equalize",
+        }
+    }
+}
+
+/// Finite `values` in ascending order.
+pub fn sorted_values(values: &[f64]) -> Vec<f64> {
+    let mut sorted: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+    sorted.sort_by(f64::total_cmp);
+    sorted
+}
+
+/// Computes `classes + 1` ascending class boundaries for `values`. Returns an
+/// empty list if there are no values. `Equalize` gets quantile boundaries, which
+/// its legend is labelled with.
+pub fn class_breaks(values: &[f64], method: ClassMethod, classes: usize) -> Vec<f64> {
+    let sorted = sorted_values(values);
+    if sorted.is_empty() || classes == 0 {
+        return Vec::new();
+    }
+    let (min, max) = (sorted[0], sorted[sorted.len() - 1]);
+    // Zero and negative values are shifted so the log range starts at 1
+    let shift = if min > 0.0 { 0.0 } else { 1.0 - min };
+    let (log_min, log_max) = ((min + shift).ln(), (max + shift).ln());
+    (0..=classes)
+        .map(|i| match method {
+            _ if i == 0 => min,
+            _ if i == classes => max,
+            ClassMethod::EqualInterval => min + (max - min) * i as f64 / classes as f64,
+            ClassMethod::Quantile | ClassMethod::Equalize => {
+                sorted[((sorted.len() - 1) as f64 * i as f64 / classes as f64).round() as usize]
+            }
+            ClassMethod::Log => {
+                (log_min + (log_max - log_min) * i as f64 / classes as f64).exp() - shift
+            }
+        })
+        .collect()
+}
+
+/// Computes `classes + 1` equal-interval boundaries centered on `midpoint`,
+/// reaching as far on both sides as the farthest value, so the middle of the ramp
+/// falls on the midpoint and equal colors mean equal deviations from it.
+pub fn diverging_breaks(values: &[f64], midpoint: f64, classes: usize) -> Vec<f64> {
+    let sorted = sorted_values(values);
+    if sorted.is_empty() || classes == 0 {
+        return Vec::new();
+    }
+    let reach = (midpoint - sorted[0])
+        .max(sorted[sorted.len() - 1] - midpoint)
+        .max(f64::EPSILON);
+    (0..=classes)
+        .map(|i| midpoint + reach * (2.0 * i as f64 / classes as f64 - 1.0))
+        .collect()
+}
+
+/// Share of `sorted` values below `value`, in [0, 1]; ties count half, so
+/// equal values share one position.
+pub fn percentile_rank(sorted: &[f64], value: f64) -> f64 {
+    if sorted.len() < 2 {
+        return 1.0;
+    }
+    let below = sorted.partition_point(|v| *v < value);
+    let up_to = sorted.partition_point(|v| *v <= value);
+    let rank = (below + up_to) as f64 / 2.0 - 0.5;
+    (rank / (sorted.len() - 1) as f64).clamp(0.0, 1.0)
+}
+
+/// Index of the class containing `value`; values outside the breaks go to the
+/// first or last class.
+pub fn class_index(breaks: &[f64], value: f64) -> usize {
+    let classes = breaks.len().saturating_sub(1);
+    (1..breaks.len())
+        .position(|i| value <= breaks[i])
+        .unwrap_or(classes)
+        .min(classes.saturating_sub(1))
+}
+
+/// Formats a class boundary compactly for legends, e.g. 1.2M or 350k.
+pub fn format_value(value: f64) -> String {
+    let abs = value.abs();
+    if abs >= 1e9 {
+        format!("{:.1}B", value / 1e9)
+    } else if abs >= 1e6 {
+        format!("{:.1}M", value / 1e6)
+    } else if abs >= 1e4 {
+        format!("{:.0}k", value / 1e3)
+    } else if value.fract() == 0.0 {
+        format!("{}", value)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALUES: [f64; 8] = [1.0, 2.0, 3.0, 4.0, 10.0, 100.0, 1000.0, f64::NAN];
+
+    #[test]
+    fn equal_interval_breaks() {
+        assert_eq!(
+            class_breaks(&[0.0, 10.0, 5.0], ClassMethod::EqualInterval, 4),
+            [0.0, 2.5, 5.0, 7.5, 10.0]
+        );
+    }
+
+    #[test]
+    fn quantile_breaks_split_the_values() {
+        assert_eq!(
+            class_breaks(&VALUES, ClassMethod::Quantile, 2),
+            [1.0, 4.0, 1000.0]
+        );
+        assert_eq!(
+            class_breaks(&VALUES, ClassMethod::Equalize, 2),
+            class_breaks(&VALUES, ClassMethod::Quantile, 2)
+        );
+    }
+
+    #[test]
+    fn log_breaks_are_even_in_magnitude() {
+        let breaks = class_breaks(&[1.0, 1000.0], ClassMethod::Log, 3);
+        for (actual, expected) in breaks.iter().zip([1.0, 10.0, 100.0, 1000.0]) {
+            assert!((actual - expected).abs() < 1e-9, "{:?}", breaks);
+        }
+        // Zero and negative values shift the range instead of breaking it
+        let shifted = class_breaks(&[-5.0, 0.0, 50.0], ClassMethod::Log, 2);
+        assert_eq!((shifted[0], shifted[2]), (-5.0, 50.0));
+        assert!(shifted[1] > -5.0 && shifted[1] < 50.0);
+    }
+
+    #[test]
+    fn no_values_no_breaks() {
+        assert!(class_breaks(&[], ClassMethod::Quantile, 5).is_empty());
+        assert!(class_breaks(&[f64::NAN], ClassMethod::EqualInterval, 5).is_empty());
+        assert!(class_breaks(&[1.0], ClassMethod::EqualInterval, 0).is_empty());
+    }
+
+    #[test]
+    fn diverging_breaks_center_on_the_midpoint() {
+        assert_eq!(
+            diverging_breaks(&[-2.0, 1.0], 0.0, 4),
+            [-2.0, -1.0, 0.0, 1.0, 2.0]
+        );
+    }
+
+    #[test]
+    fn values_fall_into_classes() {
+        let breaks = [0.0, 10.0, 20.0, 30.0];
+        assert_eq!(class_index(&breaks, -5.0), 0);
+        assert_eq!(class_index(&breaks, 10.0), 0);
+        assert_eq!(class_index(&breaks, 10.5), 1);
+        assert_eq!(class_index(&breaks, 30.0), 2);
+        assert_eq!(class_index(&breaks, 99.0), 2);
+    }
+
+    #[test]
+    fn percentile_ranks_share_ties() {
+        let sorted = sorted_values(&[1.0, 2.0, 2.0, 3.0]);
+        assert_eq!(percentile_rank(&sorted, 1.0), 0.0);
+        assert_eq!(percentile_rank(&sorted, 2.0), 0.5);
+        assert_eq!(percentile_rank(&sorted, 3.0), 1.0);
+    }
+
+    #[test]
+    fn ramps_are_named_or_custom() {
+        let blues = ColorRamp::parse("Blues").unwrap();
+        assert_eq!(blues.sample(0.0), RGBColor(239, 243, 255));
+        assert_eq!(blues.sample(2.0), RGBColor(8, 69, 148));
+        let custom = ColorRamp::parse("#000000-#ffffff").unwrap();
+        assert_eq!(custom.sample(0.5), RGBColor(128, 128, 128));
+        assert_eq!(
+            custom.class_colors(3),
+            [
+                RGBColor(0, 0, 0),
+                RGBColor(128, 128, 128),
+                RGBColor(255, 255, 255)
+            ]
+        );
+        assert!(ColorRamp::parse("#000000").is_err());
+        assert!(ColorRamp::parse("rainbow").is_err());
+    }
+
+    #[test]
+    fn legend_values_are_compact() {
+        assert_eq!(format_value(1_250_000.0), "1.2M");
+        assert_eq!(format_value(35_000.0), "35k");
+        assert_eq!(format_value(120.0), "120");
+        assert_eq!(format_value(0.125), "0.12");
+    }
+}
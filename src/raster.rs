@@ -0,0 +1,136 @@
+// raster.rs
+
+use plotters::prelude::RGBColor;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Image formats that can be read, with the world file extensions tried for each
+const RASTER_EXTENSIONS: [(&str, &[&str]); 3] = [
+    ("png", &["pgw", "pngw"]),
+    ("jpg", &["jgw", "jpgw"]),
+    ("jpeg", &["jgw", "jpegw"]),
+];
+
+/// A georeferenced image drawn underneath the vector layers, read from a
+/// PNG or JPEG and the world file next to it.
+pub struct Raster {
+    pub path: PathBuf,
+    image: image::RgbaImage,
+    // Pixel (column, row) to lon/lat: x = a*col + b*row + c, y = d*col + e*row + f
+    transform: [f64; 6],
+}
+
+/// Whether `path` is an image that may be an underlay (or a GeoTIFF, which
+/// gets a helpful error).
+pub fn is_raster_file(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    matches!(extension.as_str(), "tif" | "tiff")
+        || RASTER_EXTENSIONS.iter().any(|(ext, _)| *ext == extension)
+}
+
+impl Raster {
+    /// Reads the image at `path` and its world file (`.pgw`, `.jgw`, ...,
+    /// or `.wld`) in lon/lat degrees.
+    pub fn open(path: &Path) -> Result<Raster, String> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase)
+            .unwrap_or_default();
+        if extension == "tif" || extension == "tiff" {
+            return Err(format!(
+                "{}: GeoTIFF cannot be read; convert it with `gdal_translate -of PNG -co WORLDFILE=YES` first",
+                path.display()
+            ));
+        }
+        let world_extensions = RASTER_EXTENSIONS
+            .iter()
+            .find(|(ext, _)| *ext == extension)
+            .map(|(_, world)| *world)
+            .ok_or_else(|| format!("{} is not a PNG or JPEG image", path.display()))?;
+        let world_file = world_extensions
+            .iter()
+            .chain(&["wld"])
+            .map(|ext| path.with_extension(ext))
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| {
+                format!(
+                    "{} has no world file (.{} or .wld) next to it",
+                    path.display(),
+                    world_extensions[0]
+                )
+            })?;
+        let transform = parse_world_file(
+            &fs::read_to_string(&world_file)
+                .map_err(|e| format!("{}: {}", world_file.display(), e))?,
+        )
+        .map_err(|e| format!("{}: {}", world_file.display(), e))?;
+        let image = image::open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?
+            .into_rgba8();
+        Ok(Raster {
+            path: path.to_path_buf(),
+            image,
+            transform,
+        })
+    }
+
+    /// Samples the image for `extent` into `width` x `height` row-major
+    /// pixels; `None` outside the image and where it is transparent.
+    pub fn render(&self, extent: [f64; 4], width: u32, height: u32) -> Vec<Option<RGBColor>> {
+        let [a, b, c, d, e, f] = self.transform;
+        let determinant = a * e - b * d;
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        for py in 0..height {
+            let lat = extent[3] - (extent[3] - extent[1]) * (py as f64 + 0.5) / height as f64;
+            for px in 0..width {
+                let lon = extent[0] + (extent[2] - extent[0]) * (px as f64 + 0.5) / width as f64;
+                // Inverse of the world file transform
+                let (dx, dy) = (lon - c, lat - f);
+                let column = ((e * dx - b * dy) / determinant).round();
+                let row = ((a * dy - d * dx) / determinant).round();
+                let inside = column >= 0.0
+                    && row >= 0.0
+                    && column < self.image.width() as f64
+                    && row < self.image.height() as f64;
+                pixels.push(
+                    inside
+                        .then(|| *self.image.get_pixel(column as u32, row as u32))
+                        .and_then(|pixel| {
+                            (pixel[3] >= 128).then_some(RGBColor(pixel[0], pixel[1], pixel[2]))
+                        }),
+                );
+            }
+        }
+        pixels
+    }
+}
+
+// The six lines of a world file, in the order a, d, b, e, c, f
+fn parse_world_file(text: &str) -> Result<[f64; 6], String> {
+    let values: Vec<f64> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.parse::<f64>()
+                .map_err(|_| format!("'{}' is not a number", line))
+        })
+        .collect::<Result<_, _>>()?;
+    let [a, d, b, e, c, f] = values[..] else {
+        return Err(format!(
+            "a world file has 6 lines, this one has {}",
+            values.len()
+        ));
+    };
+    if a * e - b * d == 0.0 {
+        return Err(String::from("the pixel size is zero"));
+    }
+    Ok([a, b, c, d, e, f])
+}
@@ -0,0 +1,51 @@
+// regions.rs
+
+use crossterm::event::KeyCode;
+use ratatui::layout::{Position, Rect};
+
+use crate::app::{InfoTab, PanelTab};
+use crate::keymap::Action;
+
+/// What a part of the screen does when clicked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    Divider,            // Dragged to resize the file list and the right panel
+    FileList,           // Rows are resolved from the click position
+    Panel,              // The right panel, scrolled with the wheel
+    Preview,            // Identifies the clicked point
+    Action(Action),     // Runs the action, e.g. a line of the plotting options
+    PanelTab(PanelTab), // Shows the tab of the right panel
+    InfoTab(InfoTab),   // Shows the tab of the Info tab
+    Key(KeyCode),       // Acts like pressing the key, e.g. a button of a screen
+    Popup,              // Covers what is below without reacting itself
+}
+
+/// Clickable regions of the last rendering, registered by the widgets as
+/// they are drawn so mouse events can be routed to the widget under the
+/// pointer.
+#[derive(Debug, Default)]
+pub struct Regions {
+    regions: Vec<(Rect, Region)>,
+}
+
+impl Regions {
+    /// Forgets the regions of the previous rendering.
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    pub fn add(&mut self, area: Rect, region: Region) {
+        self.regions.push((area, region));
+    }
+
+    /// The region at the given cell and its area. Regions registered later
+    /// are drawn on top (popups last), so they take precedence.
+    pub fn at(&self, column: u16, row: u16) -> Option<(Rect, Region)> {
+        let position = Position { x: column, y: row };
+        self.regions
+            .iter()
+            .rev()
+            .find(|(area, _)| area.contains(position))
+            .copied()
+    }
+}
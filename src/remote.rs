@@ -0,0 +1,176 @@
+// remote.rs
+
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::Instant,
+};
+
+use crate::loader;
+
+pub const REMOTE_CACHE_DIR: &str = "remote/";
+const USER_AGENT: &str = "rust-geojson-mapper";
+const DOWNLOAD_TIMEOUT: u32 = 300; // Seconds; remote layers may be large
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// A frame of a spinner turning since `started`.
+pub fn spinner(started: Instant) -> char {
+    SPINNER[(started.elapsed().as_millis() / 100) as usize % SPINNER.len()]
+}
+
+/// Whether `text` is an HTTP(S) URL rather than a path.
+pub fn is_url(text: &str) -> bool {
+    let text = text.trim().to_lowercase();
+    text.starts_with("http://") || text.starts_with("https://")
+}
+
+/// Where the file at `url` is cached: named after its host and last path
+/// segment (or the WFS feature type it asks for), with a hash of the whole
+/// URL so query strings never collide, and `.geojson` unless the URL names
+/// another supported format.
+pub fn cache_path(url: &str) -> PathBuf {
+    let (without_query, query) = url.split_once('?').unwrap_or((url, ""));
+    let mut parts = without_query.split("://").nth(1).unwrap_or(url).split('/');
+    let host = sanitize(parts.next().unwrap_or_default());
+    let type_name = query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        matches!(key.to_lowercase().as_str(), "typename" | "typenames").then_some(value)
+    });
+    let last = type_name
+        .or_else(|| parts.rfind(|part| !part.is_empty()))
+        .unwrap_or("index");
+    let mut name = format!("{:08x}-{}", fnv1a(url) as u32, sanitize(last));
+    if !loader::is_supported_file(Path::new(&name)) {
+        name.push_str(".geojson");
+    }
+    PathBuf::from(REMOTE_CACHE_DIR).join(host).join(name)
+}
+
+/// The URL a cached file was downloaded from, if it was.
+pub fn source_url(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(url_file(path)).ok()?;
+    Some(text.trim().to_string()).filter(|url| is_url(url))
+}
+
+// Kept next to a cached file so it can be fetched again after a restart
+fn url_file(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".url");
+    PathBuf::from(name)
+}
+
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// 64-bit FNV-1a, stable across builds unlike the standard hasher
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// A file being downloaded into the cache on a background thread.
+pub struct Download {
+    pub url: String,
+    pub path: PathBuf, // Cached file, replaced only once the download is complete
+    started: Instant,
+    result: Receiver<Result<(), String>>,
+}
+
+impl Download {
+    /// Starts downloading `url` to its `cache_path`.
+    pub fn start(url: &str) -> Download {
+        let url = url.trim().to_string();
+        let path = cache_path(&url);
+        let (sender, result) = mpsc::channel();
+        let (thread_url, thread_path) = (url.clone(), path.clone());
+        thread::spawn(move || {
+            let _ = sender.send(fetch(&thread_url, &thread_path));
+        });
+        Download {
+            url,
+            path,
+            started: Instant::now(),
+            result,
+        }
+    }
+
+    /// Describes the download with a spinner and the bytes received so far.
+    pub fn status(&self) -> String {
+        let received = fs::metadata(partial_path(&self.path)).map_or(0, |m| m.len());
+        format!(
+            "{} Downloading {} ({} KB)",
+            spinner(self.started),
+            self.url,
+            received / 1024
+        )
+    }
+
+    /// Returns the outcome once the download finished.
+    pub fn poll(&self) -> Option<Result<(), String>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(String::from("Download failed"))),
+        }
+    }
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    path.with_extension("part")
+}
+
+// Downloads the file, recording the URL next to it
+fn fetch(url: &str, path: &Path) -> Result<(), String> {
+    download(url, path, DOWNLOAD_TIMEOUT)?;
+    fs::write(url_file(path), url).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Requests `url` through `curl` and returns the body. `args` go before the
+/// URL, e.g. `-G --data-urlencode q=...` for a query. Redirects are followed,
+/// HTTP errors fail and the request gives up after `timeout` seconds; errors
+/// name the URL with curl's message.
+pub fn request(url: &str, args: &[&OsStr], timeout: u32) -> Result<Vec<u8>, String> {
+    let output = Command::new("curl")
+        .args(["-sSfL", "--compressed", "-A", USER_AGENT, "--max-time"])
+        .arg(timeout.to_string())
+        .args(args)
+        .arg(url)
+        .output()
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Downloads `url` to `path` like `request`, only moving the file there once
+/// it is complete.
+pub fn download(url: &str, path: &Path, timeout: u32) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    let partial = partial_path(path);
+    if let Err(e) = request(url, &["-o".as_ref(), partial.as_os_str()], timeout) {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    fs::rename(&partial, path).map_err(|e| format!("{}: {}", path.display(), e))
+}
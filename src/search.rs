@@ -0,0 +1,217 @@
+// search.rs
+
+use geojson::{Feature, JsonValue};
+use serde::{Deserialize, Serialize};
+
+// Matching values remembered per file for display; the rest are only counted
+const MAX_LISTED_MATCHES: usize = 50;
+
+// Fuzzy scoring in the style of fzf: every matched character scores, more so
+// at the start of a word or right after the previous match, and gaps between
+// matched characters cost
+const SCORE_MATCH: i32 = 16;
+const BONUS_BOUNDARY: i32 = 8; // Start of the text or of a word
+const BONUS_CONSECUTIVE: i32 = 4;
+const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2; // The first pattern character weighs more
+const PENALTY_GAP_START: i32 = 3;
+const PENALTY_GAP_EXTENSION: i32 = 1;
+
+/// What `/` searches.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    FileNames,  // Fuzzy match on the file names, as the query is typed
+    Properties, // Substring of the property values, once Enter is pressed
+}
+
+impl SearchMode {
+    pub fn next(self) -> SearchMode {
+        match self {
+            SearchMode::FileNames => SearchMode::Properties,
+            SearchMode::Properties => SearchMode::FileNames,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SearchMode::FileNames => "Search:",
+            SearchMode::Properties => "Search properties:",
+        }
+    }
+}
+
+/// How well a pattern matches a text, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    pub score: i32,
+    pub positions: Vec<usize>, // Character indices of the text matched by the pattern
+}
+
+/// Matches the characters of `pattern` in order anywhere in `text`, ignoring
+/// case, and scores the best alignment: `"ind"` scores higher on
+/// `india-osm.geojson` than on `ne_10m_admin_0_boundary_lines_land.geojson`.
+/// Returns `None` if some character cannot be matched. An empty pattern
+/// matches everything with a score of 0.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let original: Vec<char> = text.chars().collect();
+    // Lowercased per character so positions stay aligned with `text`
+    let text: Vec<char> = original
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    if pattern.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+
+    let bonus: Vec<i32> = (0..text.len())
+        .map(|j| {
+            let boundary = match j.checked_sub(1).map(|k| original[k]) {
+                None => true,
+                Some(previous) => {
+                    !previous.is_alphanumeric()
+                        || (previous.is_lowercase() && original[j].is_uppercase())
+                        || (!previous.is_ascii_digit() && original[j].is_ascii_digit())
+                }
+            };
+            if boundary { BONUS_BOUNDARY } else { 0 }
+        })
+        .collect();
+
+    // best[i][j]: best score with pattern[i] matched at text[j], and the
+    // position pattern[i - 1] was matched at for it
+    let mut best: Vec<Vec<Option<(i32, usize)>>> = vec![vec![None; text.len()]; pattern.len()];
+    for (i, &p) in pattern.iter().enumerate() {
+        for j in i..text.len() {
+            if text[j] != p {
+                continue;
+            }
+            if i == 0 {
+                best[0][j] = Some((SCORE_MATCH + bonus[j] * BONUS_FIRST_CHAR_MULTIPLIER, 0));
+                continue;
+            }
+            best[i][j] = (i - 1..j)
+                .filter_map(|k| {
+                    let (score, _) = best[i - 1][k]?;
+                    let transition = if k + 1 == j {
+                        BONUS_CONSECUTIVE.max(bonus[j])
+                    } else {
+                        bonus[j] - PENALTY_GAP_START - PENALTY_GAP_EXTENSION * (j - k - 2) as i32
+                    };
+                    Some((score + SCORE_MATCH + transition, k))
+                })
+                .max_by_key(|(score, _)| *score);
+        }
+    }
+
+    let last = pattern.len() - 1;
+    let (mut j, score) = best[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, cell)| cell.map(|(score, _)| (j, score)))
+        .max_by_key(|(_, score)| *score)?;
+    let mut positions = vec![0; pattern.len()];
+    for i in (0..pattern.len()).rev() {
+        positions[i] = j;
+        if let Some((_, previous)) = best[i][j] {
+            j = previous;
+        }
+    }
+    Some(FuzzyMatch { score, positions })
+}
+
+/// A property value containing the searched text.
+#[derive(Debug, Clone)]
+pub struct PropertyMatch {
+    pub feature: usize, // Index of the feature in its file
+    pub property: String,
+    pub value: String,
+}
+
+/// Features of one file with a property value containing the searched text.
+#[derive(Debug, Clone)]
+pub struct FileMatches {
+    pub file_index: usize,
+    pub feature_count: usize, // Features with at least one matching value
+    pub value_count: usize,   // Matching values, listed or not
+    pub matches: Vec<PropertyMatch>, // The first matching values
+}
+
+/// Result of searching the property values of every listed file.
+#[derive(Debug, Clone)]
+pub struct PropertySearch {
+    pub query: String,
+    pub files: Vec<FileMatches>, // Only files with matches, in file order
+    pub failed: Vec<String>,     // Files that could not be read
+}
+
+impl PropertySearch {
+    pub fn new(query: &str) -> PropertySearch {
+        PropertySearch {
+            query: query.to_string(),
+            files: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+
+    /// Searches the features of the file at `file_index` and keeps the result
+    /// if any of them match.
+    pub fn add_file(&mut self, file_index: usize, features: &[Feature]) {
+        let needle = self.query.to_lowercase();
+        let mut feature_count = 0;
+        let mut value_count = 0;
+        let mut matches = Vec::new();
+        for (feature_index, feature) in features.iter().enumerate() {
+            let Some(properties) = &feature.properties else {
+                continue;
+            };
+            let mut matched = false;
+            for (name, value) in properties {
+                let text = value_text(value);
+                if !text.to_lowercase().contains(&needle) {
+                    continue;
+                }
+                matched = true;
+                value_count += 1;
+                if matches.len() < MAX_LISTED_MATCHES {
+                    matches.push(PropertyMatch {
+                        feature: feature_index,
+                        property: name.clone(),
+                        value: text,
+                    });
+                }
+            }
+            if matched {
+                feature_count += 1;
+            }
+        }
+        if feature_count > 0 {
+            self.files.push(FileMatches {
+                file_index,
+                feature_count,
+                value_count,
+                matches,
+            });
+        }
+    }
+
+    pub fn for_file(&self, file_index: usize) -> Option<&FileMatches> {
+        self.files.iter().find(|f| f.file_index == file_index)
+    }
+
+    /// Features matched across all files.
+    pub fn feature_count(&self) -> usize {
+        self.files.iter().map(|f| f.feature_count).sum()
+    }
+}
+
+// Strings are searched without their quotes, other values as JSON
+fn value_text(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
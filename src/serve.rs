@@ -0,0 +1,416 @@
+// serve.rs
+
+use geojson::FeatureCollection;
+use plotters::style::RGBColor;
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+    },
+    thread,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::filter::FeatureFilter;
+use crate::loader;
+use crate::temporal::TimeRange;
+
+pub const DEFAULT_SERVE_ADDRESS: &str = "127.0.0.1:8080";
+
+// Connections answered at once, and waiting for a worker; more are closed
+const WORKERS: usize = 4;
+const QUEUED_CONNECTIONS: usize = 16;
+
+// Longest request line and headers read; the rest of a request is ignored
+const MAX_REQUEST_BYTES: u64 = 16 * 1024;
+
+const VIEWER_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>GeoJSON Mapper</title>
+<link rel="stylesheet" href="https://unpkg.com/leaflet@1.9.4/dist/leaflet.css">
+<script src="https://unpkg.com/leaflet@1.9.4/dist/leaflet.js"></script>
+<style>
+html, body, #map { height: 100%; margin: 0; }
+.map-title { background: white; padding: 4px 10px; font: bold 16px sans-serif; border-radius: 4px; }
+.leaflet-popup-content th { text-align: left; padding-right: 8px; }
+</style>
+</head>
+<body>
+<div id="map"></div>
+<script>
+const escapeHtml = (value) => String(value).replace(/[&<>"]/g, (c) => ({ '&': '&amp;', '<': '&lt;', '>': '&gt;', '"': '&quot;' })[c]);
+const map = L.map('map').setView([0, 0], 2);
+L.tileLayer('https://tile.openstreetmap.org/{z}/{x}/{y}.png', {
+  maxZoom: 19,
+  attribution: '&copy; <a href="https://www.openstreetmap.org/copyright">OpenStreetMap</a> contributors'
+}).addTo(map);
+const title = L.DomUtil.create('div', 'map-title');
+const titleControl = L.control({ position: 'topleft' });
+titleControl.onAdd = () => title;
+titleControl.addTo(map);
+let switcher = null;
+let shown = [];
+let listed = '';
+let fitted = false;
+
+// Shows the layers selected in the TUI, reloading them whenever the selection,
+// their style or their files change
+async function refresh() {
+  const response = await fetch('/layers.json', { cache: 'no-store' });
+  const text = await response.text();
+  if (text === listed) return;
+  listed = text;
+  const state = JSON.parse(text);
+  title.textContent = state.title;
+  title.style.display = state.title ? '' : 'none';
+  document.title = state.title || 'GeoJSON Mapper';
+  const loaded = await Promise.all(state.layers.map(async (layer) => {
+    const data = await (await fetch(layer.url)).json();
+    return [layer, L.geoJSON(data, {
+      style: { color: layer.color, weight: 2, fillOpacity: 0.3 },
+      pointToLayer: (feature, latlng) => L.circleMarker(latlng, { radius: 5, color: layer.color, fillOpacity: 0.8 }),
+      onEachFeature: (feature, item) => {
+        const rows = Object.entries(feature.properties || {})
+          .map(([key, value]) => `<tr><th>${escapeHtml(key)}</th><td>${escapeHtml(value)}</td></tr>`)
+          .join('');
+        if (rows) item.bindPopup(`<table>${rows}</table>`, { maxHeight: 300 });
+      }
+    })];
+  }));
+  shown.forEach((layer) => map.removeLayer(layer));
+  if (switcher) map.removeControl(switcher);
+  const overlays = {};
+  const bounds = L.latLngBounds([]);
+  shown = loaded.map(([layer, geojson]) => {
+    geojson.addTo(map);
+    overlays[`<span style="color:${layer.color}">&#9632;</span> ${escapeHtml(layer.name)}`] = geojson;
+    if (geojson.getBounds().isValid()) bounds.extend(geojson.getBounds());
+    return geojson;
+  });
+  switcher = L.control.layers(null, overlays, { collapsed: false }).addTo(map);
+  if (!fitted && bounds.isValid()) {
+    map.fitBounds(bounds, { padding: [20, 20] });
+    fitted = true;
+  }
+}
+L.control.scale().addTo(map);
+refresh().catch(console.error);
+setInterval(() => refresh().catch(console.error), 2000);
+</script>
+</body>
+</html>
+"#;
+
+/// A layer offered by the preview server, read from disk when requested so
+/// the browser always gets the current file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServedLayer {
+    pub name: String,
+    pub path: PathBuf,
+    pub color: RGBColor,
+    pub crs: Option<String>, // CRS override of the layer
+    pub filter: Option<FeatureFilter>,
+    pub checked: Option<BTreeSet<usize>>, // Positions of the features to serve; `None` serves all
+    pub time_range: Option<TimeRange>,
+}
+
+// What the server shows, replaced by the TUI as the selection changes
+#[derive(Default)]
+struct ServedState {
+    title: String,
+    layers: Vec<ServedLayer>,
+    generation: u64,     // Incremented whenever `title` or `layers` change
+    errors: Vec<String>, // Layers that could not be served, for the log
+}
+
+/// An HTTP server on a background thread offering the selected layers as
+/// GeoJSON at `/layers/<n>.geojson`, their list at `/layers.json` and a
+/// Leaflet page at `/` that follows the selection. A few worker threads answer
+/// the requests. Stops when dropped.
+pub struct PreviewServer {
+    pub address: SocketAddr,
+    state: Arc<Mutex<ServedState>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl PreviewServer {
+    /// Starts listening on `address`, e.g. `127.0.0.1:8080`, or `0.0.0.0:8080`
+    /// to be reachable from other machines.
+    pub fn start(address: &str) -> Result<PreviewServer, String> {
+        let listener = TcpListener::bind(address)
+            .map_err(|e| format!("Cannot listen on {}: {}", address, e))?;
+        let address = listener.local_addr().map_err(|e| e.to_string())?;
+        let state = Arc::new(Mutex::new(ServedState::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let (sender, receiver) = mpsc::sync_channel::<TcpStream>(QUEUED_CONNECTIONS);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..WORKERS {
+            let (receiver, state) = (Arc::clone(&receiver), Arc::clone(&state));
+            thread::spawn(move || work(&receiver, &state));
+        }
+        let thread_stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                // A connection beyond the queue is closed by dropping it
+                if let Ok(stream) = stream {
+                    let _ = sender.try_send(stream);
+                }
+            }
+            // Dropping the sender stops the workers
+        });
+        Ok(PreviewServer {
+            address,
+            state,
+            stop,
+        })
+    }
+
+    /// The address to open in a browser on this machine.
+    pub fn url(&self) -> String {
+        let host = if self.address.ip().is_unspecified() {
+            String::from("localhost")
+        } else {
+            self.address.ip().to_string()
+        };
+        format!("http://{}:{}/", host, self.address.port())
+    }
+
+    /// Replaces the layers and title shown.
+    pub fn set_layers(&self, title: &str, layers: Vec<ServedLayer>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.title != title || state.layers != layers {
+            state.title = title.to_string();
+            state.layers = layers;
+            state.generation += 1;
+        }
+    }
+
+    /// Takes the errors of the layers that could not be served since the last call.
+    pub fn take_errors(&self) -> Vec<String> {
+        std::mem::take(&mut self.state.lock().unwrap_or_else(|e| e.into_inner()).errors)
+    }
+}
+
+impl Drop for PreviewServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wake the accepting thread so it sees `stop`
+        let mut wake = self.address;
+        if wake.ip().is_unspecified() {
+            wake.set_ip([127, 0, 0, 1].into());
+        }
+        let _ = TcpStream::connect_timeout(&wake, Duration::from_millis(200));
+    }
+}
+
+// Answers the connections queued by the accepting thread until it stops
+fn work(receiver: &Mutex<Receiver<TcpStream>>, state: &Mutex<ServedState>) {
+    loop {
+        let stream = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+        match stream {
+            Ok(stream) => {
+                let _ = handle(stream, state);
+            }
+            Err(_) => return,
+        }
+    }
+}
+
+// Answers one GET request
+fn handle(stream: TcpStream, state: &Mutex<ServedState>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(30)))?;
+    let mut reader = BufReader::new((&stream).take(MAX_REQUEST_BYTES));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers are not needed, but are read so the client sees a clean close
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or("/"),
+    );
+    let path = target.split('?').next().unwrap_or_default();
+
+    let (status, content_type, body) = if method != "GET" {
+        (
+            "405 Method Not Allowed",
+            "text/plain",
+            String::from("Only GET is supported"),
+        )
+    } else if path == "/" || path == "/index.html" {
+        (
+            "200 OK",
+            "text/html; charset=utf-8",
+            String::from(VIEWER_PAGE),
+        )
+    } else if path == "/layers.json" {
+        ("200 OK", "application/json", layer_list(state))
+    } else if let Some(index) = path
+        .strip_prefix("/layers/")
+        .and_then(|name| name.strip_suffix(".geojson"))
+        .and_then(|index| index.parse::<usize>().ok())
+    {
+        let layer = state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .layers
+            .get(index)
+            .cloned();
+        match layer.map(|layer| read_layer(&layer)) {
+            Some(Ok(json)) => ("200 OK", "application/geo+json", json),
+            // The browser is not told where the files are
+            Some(Err(e)) => {
+                let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                state.errors.push(e);
+                (
+                    "500 Internal Server Error",
+                    "text/plain",
+                    String::from("The layer could not be read"),
+                )
+            }
+            None => ("404 Not Found", "text/plain", String::from("No such layer")),
+        }
+    } else {
+        ("404 Not Found", "text/plain", String::from("Not found"))
+    };
+
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
+
+// The title and the served layers with their style and URL. The URL changes
+// with the selection and with the modification time of the file, so the
+// viewer sees when to reload.
+fn layer_list(state: &Mutex<ServedState>) -> String {
+    let state = state.lock().unwrap_or_else(|e| e.into_inner());
+    let layers: Vec<serde_json::Value> = state
+        .layers
+        .iter()
+        .enumerate()
+        .map(|(i, layer)| {
+            let RGBColor(r, g, b) = layer.color;
+            let modified = fs::metadata(&layer.path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |time| time.as_millis());
+            serde_json::json!({
+                "name": layer.name,
+                "color": format!("#{:02x}{:02x}{:02x}", r, g, b),
+                "url": format!("/layers/{}.geojson?v={}-{}", i, state.generation, modified),
+            })
+        })
+        .collect();
+    serde_json::json!({ "title": state.title, "layers": layers }).to_string()
+}
+
+fn read_layer(layer: &ServedLayer) -> Result<String, String> {
+    let parsed = loader::read_features_with_crs(&layer.path, layer.crs.as_deref())
+        .map_err(|e| format!("{}: {}", layer.path.display(), e))?;
+    let features = parsed
+        .features
+        .into_iter()
+        .enumerate()
+        .filter(|(position, feature)| {
+            layer
+                .checked
+                .as_ref()
+                .is_none_or(|checked| checked.contains(position))
+                && layer
+                    .filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.accepts(feature))
+                && layer
+                    .time_range
+                    .as_ref()
+                    .is_none_or(|range| range.accepts(feature))
+        })
+        .map(|(_, feature)| feature)
+        .collect();
+    Ok(FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    }
+    .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(server: &PreviewServer, request: &str) -> String {
+        let mut stream = TcpStream::connect(server.address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn unreadable_layers_are_logged_not_shown() {
+        let server = PreviewServer::start("127.0.0.1:0").unwrap();
+        let path = std::env::temp_dir().join("plots-serve-test/missing.geojson");
+        server.set_layers(
+            "Test",
+            vec![ServedLayer {
+                name: String::from("missing.geojson"),
+                path: path.clone(),
+                color: RGBColor(0, 0, 0),
+                crs: None,
+                filter: None,
+                checked: None,
+                time_range: None,
+            }],
+        );
+        let response = get(&server, "GET /layers/0.geojson HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 500"), "{}", response);
+        assert!(!response.contains("plots-serve-test"));
+        let errors = server.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("plots-serve-test"));
+        assert!(get(&server, "GET /nothing HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn endless_requests_are_cut_short() {
+        let server = PreviewServer::start("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.address).unwrap();
+        stream
+            .set_write_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let line = format!("GET /{} HTTP/1.1", "a".repeat(64 * 1024));
+        // The server stops reading and answers, or resets the connection,
+        // while the client is still writing
+        let _ = stream.write_all(line.as_bytes());
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        assert!(response.is_empty() || response.starts_with("HTTP/1.1 404"));
+        assert!(get(&server, "GET / HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 200"));
+    }
+}
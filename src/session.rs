@@ -0,0 +1,512 @@
+// session.rs
+
+use plotters::prelude::RGBColor;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::app::{App, InfoTab, PanelTab};
+use crate::filter::FeatureFilter;
+use crate::inset::Inset;
+use crate::plot::{self, FigureBackground, GeometryTypes, LineStyle, MapMarker, PointShape};
+use crate::projection::Projection;
+use crate::raster::Raster;
+use crate::search::SearchMode;
+use crate::style::StyleRule;
+use crate::temporal::TimeRange;
+
+pub const SESSIONS_DIR: &str = "sessions/";
+pub const WORKSPACE_FILE: &str = "workspace.json";
+
+// A selected file and the color it was assigned
+#[derive(Serialize, Deserialize)]
+pub struct SessionLayer {
+    pub file: String,
+    pub color: [u8; 3],
+    #[serde(default)]
+    pub simplify_tolerance: Option<f64>, // Overrides the session's tolerance for this file
+    #[serde(default)]
+    pub line_width: Option<u32>,
+    #[serde(default)]
+    pub line_style: Option<LineStyle>,
+    #[serde(default)]
+    pub point_size: Option<u32>,
+    #[serde(default)]
+    pub point_shape: Option<PointShape>,
+    #[serde(default)]
+    pub opacity: Option<u32>, // Percent; `None` is opaque
+    #[serde(default)]
+    pub crs: Option<String>, // Overrides the CRS declared by the file
+    #[serde(default)]
+    pub filter: Option<String>, // In the syntax of `FeatureFilter::parse`
+    #[serde(default)]
+    pub features: Option<Vec<usize>>, // Positions of the features checked for plotting
+    #[serde(default)]
+    pub geometry_types: Option<String>, // In the syntax of `GeometryTypes::parse`
+}
+
+/// A saved selection set together with the plotting options, markers and styling rules.
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub layers: Vec<SessionLayer>,
+    pub plot_points: bool,
+    pub plot_lines: bool,
+    pub plot_polygons: bool,
+    pub output_filename: String,
+    #[serde(default)] // Sessions saved before markers existed have none
+    pub markers: Vec<MapMarker>,
+    #[serde(default)]
+    pub style_rules: Vec<String>, // In the syntax of `StyleRule::parse`
+    #[serde(default)]
+    pub figure_mode: Option<FigureBackground>,
+    #[serde(default)]
+    pub projection: Projection,
+    #[serde(default)]
+    pub keep_aspect: bool,
+    #[serde(default)]
+    pub cluster_distance: Option<u32>,
+    #[serde(default)]
+    pub inset: Option<Inset>,
+    #[serde(default)]
+    pub time_range: Option<String>, // In the syntax of `TimeRange::parse`
+    #[serde(default)]
+    pub simplify_tolerance: f64,
+    #[serde(default)]
+    pub auto_simplify: bool,
+    #[serde(default)]
+    pub scale_bar: bool,
+    #[serde(default)]
+    pub north_arrow: bool,
+    #[serde(default)]
+    pub basemap: bool,
+    #[serde(default)]
+    pub underlay: Option<String>, // Path of the raster image drawn underneath
+    #[serde(default)]
+    pub thumbnails: bool,
+    #[serde(default)]
+    pub extent: Option<[f64; 4]>, // Locked plot extent
+    #[serde(default)]
+    pub clip_mask: Option<String>, // File whose polygons clip the layers
+    #[serde(default)]
+    pub clip_mask_shade: bool,
+    #[serde(default)]
+    pub focus: Option<String>, // File drawn in color while the others are muted
+    #[serde(default = "default_title")]
+    pub title: String,
+    #[serde(default)]
+    pub subtitle: String,
+    #[serde(default)]
+    pub attribution: String,
+}
+
+// Sessions saved before titles were editable used the default title
+fn default_title() -> String {
+    String::from(plot::DEFAULT_TITLE)
+}
+
+fn session_path(name: &str) -> PathBuf {
+    PathBuf::from(SESSIONS_DIR).join(format!("{}.json", name))
+}
+
+/// Lists the names of all saved sessions, sorted alphabetically.
+pub fn list_sessions() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(SESSIONS_DIR)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Deletes the session file called `name`.
+pub fn delete_session(name: &str) -> Result<(), Box<dyn Error>> {
+    fs::remove_file(session_path(name))?;
+    Ok(())
+}
+
+impl Session {
+    /// Captures the current selection, colors and plot options of `app`.
+    pub fn from_app(app: &App) -> Session {
+        let layers = app
+            .layers
+            .iter()
+            .filter(|layer| layer.selected)
+            .map(|layer| {
+                let color = layer.color.unwrap_or(RGBColor(0, 0, 0));
+                SessionLayer {
+                    file: layer.file.clone(),
+                    color: [color.0, color.1, color.2],
+                    simplify_tolerance: layer.simplify_tolerance,
+                    line_width: layer.line_width,
+                    line_style: layer.line_style,
+                    point_size: layer.point_size,
+                    point_shape: layer.point_shape,
+                    opacity: layer.opacity,
+                    crs: layer.crs.clone(),
+                    filter: layer.filter.as_ref().map(FeatureFilter::to_string),
+                    features: layer
+                        .feature_selection
+                        .as_ref()
+                        .map(|checked| checked.iter().copied().collect()),
+                    geometry_types: layer.geometry_types.map(GeometryTypes::names),
+                }
+            })
+            .collect();
+        Session {
+            layers,
+            plot_points: app.plot_points,
+            plot_lines: app.plot_lines,
+            plot_polygons: app.plot_polygons,
+            output_filename: app.output_filename_input.text.clone(),
+            markers: app.markers.clone(),
+            style_rules: app.style_rules.iter().map(StyleRule::to_string).collect(),
+            figure_mode: app.figure_mode,
+            projection: app.projection,
+            keep_aspect: app.keep_aspect,
+            cluster_distance: app.cluster_distance,
+            inset: app.inset,
+            time_range: app.time_range.as_ref().map(TimeRange::to_string),
+            simplify_tolerance: app.simplify_tolerance,
+            auto_simplify: app.auto_simplify,
+            scale_bar: app.scale_bar,
+            north_arrow: app.north_arrow,
+            basemap: app.basemap_enabled,
+            underlay: app
+                .underlay
+                .as_ref()
+                .map(|raster| raster.path.display().to_string()),
+            thumbnails: app.thumbnails,
+            extent: app.preview_view.filter(|_| app.extent_locked),
+            clip_mask: app.clip_mask_index.map(|i| app.layers[i].file.clone()),
+            clip_mask_shade: app.clip_mask_shade,
+            focus: app.focus_index.map(|i| app.layers[i].file.clone()),
+            title: app.title_input.text.clone(),
+            subtitle: app.subtitle_input.text.clone(),
+            attribution: app.attribution_input.text.clone(),
+        }
+    }
+
+    /// Replaces the selection and plot options of `app` with this session.
+    /// Returns the files that are no longer present in the data directory.
+    /// A filter, style rule or time range that does not parse refuses the
+    /// whole session and leaves `app` as it was.
+    pub fn apply(&self, app: &mut App) -> Result<Vec<String>, String> {
+        let mut filters = Vec::with_capacity(self.layers.len());
+        let mut geometry_types = Vec::with_capacity(self.layers.len());
+        for saved in &self.layers {
+            let filter = saved
+                .filter
+                .as_deref()
+                .map(FeatureFilter::parse)
+                .transpose();
+            filters.push(filter.map_err(|e| format!("Filter of {}: {}", saved.file, e))?);
+            let types = saved
+                .geometry_types
+                .as_deref()
+                .map(GeometryTypes::parse)
+                .transpose();
+            geometry_types
+                .push(types.map_err(|e| format!("Geometry types of {}: {}", saved.file, e))?);
+        }
+        let time_range = self
+            .time_range
+            .as_deref()
+            .map(TimeRange::parse)
+            .transpose()
+            .map_err(|e| format!("Time range: {}", e))?;
+        let style_rules = self
+            .style_rules
+            .iter()
+            .map(|rule| StyleRule::parse(rule).map_err(|e| format!("Style rule '{}': {}", rule, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Files opened from outside the data directory are listed again
+        for layer in &self.layers {
+            if Path::new(&layer.file).is_absolute()
+                && Path::new(&layer.file).is_file()
+                && app.file_index(&layer.file).is_none()
+            {
+                app.add_geojson_file(layer.file.clone());
+            }
+        }
+        let previous_crs: Vec<Option<String>> = app
+            .layers
+            .iter_mut()
+            .map(|layer| layer.crs.take())
+            .collect();
+        for layer in &mut app.layers {
+            layer.selected = false;
+            layer.color = None;
+            layer.simplify_tolerance = None;
+            layer.line_width = None;
+            layer.line_style = None;
+            layer.point_size = None;
+            layer.point_shape = None;
+            layer.opacity = None;
+            layer.filter = None;
+            layer.feature_selection = None;
+            layer.geometry_types = None;
+        }
+
+        let mut missing = Vec::new();
+        for ((saved, filter), types) in self.layers.iter().zip(filters).zip(geometry_types) {
+            match app.file_index(&saved.file) {
+                Some(i) => {
+                    let layer = &mut app.layers[i];
+                    layer.selected = true;
+                    layer.color = Some(RGBColor(saved.color[0], saved.color[1], saved.color[2]));
+                    layer.simplify_tolerance = saved.simplify_tolerance;
+                    layer.line_width = saved.line_width;
+                    layer.line_style = saved.line_style;
+                    layer.point_size = saved.point_size;
+                    layer.point_shape = saved.point_shape;
+                    layer.opacity = saved.opacity;
+                    layer.crs.clone_from(&saved.crs);
+                    layer.filter = filter;
+                    layer.feature_selection = saved
+                        .features
+                        .as_ref()
+                        .map(|checked| checked.iter().copied().collect());
+                    layer.geometry_types = types;
+                }
+                None => missing.push(saved.file.clone()),
+            }
+        }
+        // Files parsed under another CRS are read again
+        for (layer, crs) in app.layers.iter_mut().zip(previous_crs) {
+            if crs != layer.crs {
+                layer.forget_contents();
+            }
+        }
+
+        app.plot_points = self.plot_points;
+        app.plot_lines = self.plot_lines;
+        app.plot_polygons = self.plot_polygons;
+        app.figure_mode = self.figure_mode;
+        app.projection = self.projection;
+        app.keep_aspect = self.keep_aspect;
+        app.cluster_distance = self.cluster_distance;
+        app.inset = self.inset;
+        app.time_range = time_range;
+        app.simplify_tolerance = self.simplify_tolerance;
+        app.auto_simplify = self.auto_simplify;
+        app.scale_bar = self.scale_bar;
+        app.north_arrow = self.north_arrow;
+        app.basemap_enabled = self.basemap;
+        // The image is only read again when the session names another one
+        let underlay = app
+            .underlay
+            .as_ref()
+            .map(|raster| raster.path.display().to_string());
+        if self.underlay != underlay {
+            app.underlay = None;
+            if let Some(path) = &self.underlay {
+                match Raster::open(Path::new(path)) {
+                    Ok(raster) => app.underlay = Some(Arc::new(raster)),
+                    Err(_) => missing.push(path.clone()),
+                }
+            }
+        }
+        app.thumbnails = self.thumbnails;
+        app.extent_locked = self.extent.is_some();
+        if self.extent.is_some() {
+            app.preview_view = self.extent;
+        }
+        app.clip_mask_index = None;
+        if let Some(mask) = &self.clip_mask {
+            match app.file_index(mask) {
+                Some(i) => app.clip_mask_index = Some(i),
+                None => missing.push(mask.clone()),
+            }
+        }
+        app.clip_mask_shade = self.clip_mask_shade;
+        app.focus_index = self.focus.as_ref().and_then(|focus| app.file_index(focus));
+        app.title_input.set(self.title.clone());
+        app.subtitle_input.set(self.subtitle.clone());
+        app.attribution_input.set(self.attribution.clone());
+        app.output_filename_input.set(self.output_filename.clone());
+        app.markers.clone_from(&self.markers);
+        app.style_rules = style_rules;
+        app.selected_rule_index = 0;
+        Ok(missing)
+    }
+
+    /// Writes the session to `sessions/<name>.json`, replacing any existing one.
+    pub fn save(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(SESSIONS_DIR)?;
+        fs::write(session_path(name), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Reads the session called `name`.
+    pub fn load(name: &str) -> Result<Session, Box<dyn Error>> {
+        let contents = fs::read_to_string(session_path(name))?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+/// How the TUI was laid out when it was last closed: pane width, tabs, search
+/// mode and highlighted file. Saved on quit and restored on the next start,
+/// independently of the named sessions. Missing entries keep the defaults.
+#[derive(Serialize, Deserialize)]
+pub struct Workspace {
+    #[serde(default)]
+    pub left_pane_width: Option<u16>, // Percent of the terminal width
+    #[serde(default)]
+    pub panel_tab: Option<PanelTab>,
+    #[serde(default)]
+    pub info_tab: Option<InfoTab>,
+    #[serde(default)]
+    pub search_mode: Option<SearchMode>,
+    #[serde(default)]
+    pub highlighted_file: Option<String>,
+}
+
+impl Workspace {
+    pub fn from_app(app: &App) -> Workspace {
+        Workspace {
+            left_pane_width: Some(app.left_pane_width_percentage),
+            panel_tab: Some(app.panel_tab),
+            info_tab: Some(app.info_tab),
+            search_mode: Some(app.search_mode),
+            highlighted_file: app
+                .highlighted_file_index()
+                .map(|i| app.layers[i].file.clone()),
+        }
+    }
+
+    /// Restores the layout in `app`; call once the file list is set up. A
+    /// highlighted file that is gone leaves the first file highlighted.
+    pub fn apply(&self, app: &mut App) {
+        if let Some(width) = self.left_pane_width {
+            app.set_left_pane_width(width as i32);
+        }
+        if let Some(tab) = self.panel_tab {
+            app.panel_tab = tab;
+        }
+        if let Some(tab) = self.info_tab {
+            app.info_tab = tab;
+        }
+        if let Some(mode) = self.search_mode {
+            app.search_mode = mode;
+        }
+        if let Some(position) = self.highlighted_file.as_ref().and_then(|file| {
+            app.filtered_geojson_indices
+                .iter()
+                .position(|&i| app.layers[i].file == *file)
+        }) {
+            app.selected_file_index = position;
+        }
+    }
+
+    /// Writes the layout to `workspace.json` in the working directory.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        fs::write(WORKSPACE_FILE, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Reads the layout saved by the last run, if there is one.
+    pub fn load() -> Result<Option<Workspace>, Box<dyn Error>> {
+        match fs::read_to_string(WORKSPACE_FILE) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listed_app(files: &[&str]) -> App {
+        let mut app = App::new();
+        app.setup_geojson_data(files.iter().map(|f| f.to_string()).collect());
+        app
+    }
+
+    #[test]
+    fn sessions_restore_what_they_saved() {
+        let mut app = listed_app(&["a.geojson", "b.geojson", "c.geojson"]);
+        app.layers[1].selected = true;
+        app.layers[1].color = Some(RGBColor(10, 20, 30));
+        app.layers[2].selected = true;
+        app.layers[2].color = Some(RGBColor(40, 50, 60));
+        app.layers[2].line_width = Some(3);
+        app.layers[2].filter = Some(FeatureFilter::parse("pop > 5").unwrap());
+        app.style_rules = vec![StyleRule::parse("size pop 2 10").unwrap()];
+        app.time_range = Some(TimeRange::parse("2020-01..2020-03").unwrap());
+        app.scale_bar = true;
+        app.title_input.set("Population");
+        let saved = serde_json::to_string(&Session::from_app(&app)).unwrap();
+
+        // Restored into a list in another order, with a file gone
+        let mut restored = listed_app(&["c.geojson", "a.geojson"]);
+        let session: Session = serde_json::from_str(&saved).unwrap();
+        assert_eq!(
+            session.apply(&mut restored),
+            Ok(vec![String::from("b.geojson")])
+        );
+        let layer = &restored.layers[0];
+        assert!(layer.selected && !restored.layers[1].selected);
+        assert_eq!(layer.color, Some(RGBColor(40, 50, 60)));
+        assert_eq!(layer.line_width, Some(3));
+        assert_eq!(
+            Session::from_app(&restored).layers[0].filter.as_deref(),
+            session.layers[1].filter.as_deref()
+        );
+        assert_eq!(restored.style_rules.len(), 1);
+        assert_eq!(restored.time_range, app.time_range);
+        assert!(restored.scale_bar);
+        assert_eq!(restored.title_input.text, "Population");
+    }
+
+    #[test]
+    fn sessions_saved_before_later_options_still_load() {
+        let saved = r#"{"layers": [{"file": "a.geojson", "color": [1, 2, 3]}],
+            "plot_points": true, "plot_lines": false, "plot_polygons": true,
+            "output_filename": "map.png"}"#;
+        let session: Session = serde_json::from_str(saved).unwrap();
+        let mut app = listed_app(&["a.geojson"]);
+        assert_eq!(session.apply(&mut app), Ok(Vec::new()));
+        assert!(app.layers[0].selected && !app.plot_lines);
+        assert_eq!(app.title_input.text, plot::DEFAULT_TITLE);
+        assert!(app.markers.is_empty() && app.style_rules.is_empty());
+    }
+
+    #[test]
+    fn sessions_with_unreadable_settings_are_refused() {
+        let mut app = listed_app(&["a.geojson"]);
+        let mut session = Session::from_app(&app);
+        let layer = r#"{"file": "a.geojson", "color": [1, 2, 3]}"#;
+        session.layers.push(serde_json::from_str(layer).unwrap());
+        session.style_rules.push(String::from("label"));
+        assert!(session.apply(&mut app).unwrap_err().contains("label"));
+        assert!(!app.layers[0].selected, "nothing is applied");
+
+        session.style_rules.clear();
+        session.time_range = Some(String::from("last tuesday"));
+        assert!(
+            session
+                .apply(&mut app)
+                .unwrap_err()
+                .starts_with("Time range")
+        );
+
+        session.time_range = None;
+        session.layers[0].filter = Some(String::from("pop >"));
+        assert!(session.apply(&mut app).unwrap_err().contains("a.geojson"));
+
+        session.layers[0].filter = Some(String::from("pop > 5"));
+        assert_eq!(session.apply(&mut app), Ok(Vec::new()));
+        assert!(app.layers[0].selected && app.layers[0].filter.is_some());
+    }
+}
@@ -0,0 +1,143 @@
+// simplify.rs
+
+use geojson::Value;
+
+/// Douglas-Peucker simplification of `points`: drops every position closer than
+/// `tolerance` (in coordinate units) to the simplified line. Endpoints are always kept.
+pub fn simplify_line(points: &[Vec<f64>], tolerance: f64) -> Vec<Vec<f64>> {
+    if tolerance <= 0.0 || points.len() < 3 {
+        return points.to_vec();
+    }
+    let last = points.len() - 1;
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[last] = true;
+
+    // Segments still to be checked, as (start, end) indices
+    let mut stack = vec![(0, last)];
+    while let Some((start, end)) = stack.pop() {
+        let mut farthest = (0.0, start);
+        for i in start + 1..end {
+            let distance = segment_distance(&points[i], &points[start], &points[end]);
+            if distance > farthest.0 {
+                farthest = (distance, i);
+            }
+        }
+        if farthest.0 > tolerance {
+            keep[farthest.1] = true;
+            stack.push((start, farthest.1));
+            stack.push((farthest.1, end));
+        }
+    }
+    points
+        .iter()
+        .zip(keep)
+        .filter(|(_, kept)| *kept)
+        .map(|(point, _)| point.clone())
+        .collect()
+}
+
+// Rings that would collapse below four positions are kept as they are
+fn simplify_ring(ring: &[Vec<f64>], tolerance: f64) -> Vec<Vec<f64>> {
+    let simplified = simplify_line(ring, tolerance);
+    if simplified.len() < 4 {
+        ring.to_vec()
+    } else {
+        simplified
+    }
+}
+
+/// Simplifies the lines and polygon rings of `value`; points are left untouched.
+pub fn simplify_geometry(value: &Value, tolerance: f64) -> Value {
+    let rings = |polygon: &Vec<Vec<Vec<f64>>>| {
+        polygon
+            .iter()
+            .map(|ring| simplify_ring(ring, tolerance))
+            .collect()
+    };
+    match value {
+        Value::LineString(line) => Value::LineString(simplify_line(line, tolerance)),
+        Value::MultiLineString(lines) => Value::MultiLineString(
+            lines
+                .iter()
+                .map(|line| simplify_line(line, tolerance))
+                .collect(),
+        ),
+        Value::Polygon(polygon) => Value::Polygon(rings(polygon)),
+        Value::MultiPolygon(polygons) => Value::MultiPolygon(polygons.iter().map(rings).collect()),
+        Value::GeometryCollection(geometries) => Value::GeometryCollection(
+            geometries
+                .iter()
+                .map(|geometry| {
+                    geojson::Geometry::new(simplify_geometry(&geometry.value, tolerance))
+                })
+                .collect(),
+        ),
+        Value::Point(_) | Value::MultiPoint(_) => value.clone(),
+    }
+}
+
+// Distance from `p` to the segment between `a` and `b`
+fn segment_distance(p: &[f64], a: &[f64], b: &[f64]) -> f64 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq == 0.0 {
+        0.0
+    } else {
+        (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / length_sq).clamp(0.0, 1.0)
+    };
+    let (x, y) = (a[0] + t * dx, a[1] + t * dy);
+    ((p[0] - x).powi(2) + (p[1] - y).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(points: &[[f64; 2]]) -> Vec<Vec<f64>> {
+        points.iter().map(|p| p.to_vec()).collect()
+    }
+
+    #[test]
+    fn positions_within_the_tolerance_are_dropped() {
+        let zigzag = line(&[
+            [0.0, 0.0],
+            [1.0, 0.1],
+            [2.0, -0.1],
+            [3.0, 5.0],
+            [4.0, 6.0],
+            [5.0, 7.0],
+        ]);
+        assert_eq!(
+            simplify_line(&zigzag, 0.5),
+            line(&[[0.0, 0.0], [2.0, -0.1], [3.0, 5.0], [5.0, 7.0]])
+        );
+        assert_eq!(simplify_line(&zigzag, 0.0), zigzag);
+        assert_eq!(
+            simplify_line(&zigzag, 100.0),
+            line(&[[0.0, 0.0], [5.0, 7.0]])
+        );
+    }
+
+    #[test]
+    fn rings_are_not_simplified_away() {
+        let ring = line(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [0.0, 0.0]]);
+        let polygon = Value::Polygon(vec![ring.clone()]);
+        assert_eq!(simplify_geometry(&polygon, 10.0), polygon);
+        let bumpy = line(&[
+            [0.0, 0.0],
+            [0.5, 0.01],
+            [1.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 1.0],
+            [0.0, 0.0],
+        ]);
+        let polygons = Value::MultiPolygon(vec![vec![bumpy]]);
+        assert_eq!(
+            simplify_geometry(&polygons, 0.1),
+            Value::MultiPolygon(vec![vec![ring]])
+        );
+        let point = Value::Point(vec![0.5, 0.5]);
+        assert_eq!(simplify_geometry(&point, 10.0), point);
+    }
+}
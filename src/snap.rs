@@ -0,0 +1,214 @@
+// snap.rs
+
+use geojson::{Feature, Geometry, Value};
+
+/// Grid spacing used when none is typed, in coordinate units (about 1 m for degrees).
+pub const DEFAULT_GRID: f64 = 1e-5;
+
+/// Parses the grid spacing to snap coordinates to, e.g. `0.0001` or `1e-5`;
+/// empty text gives `DEFAULT_GRID`.
+pub fn parse_grid(text: &str) -> Result<f64, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(DEFAULT_GRID);
+    }
+    match text.parse::<f64>() {
+        Ok(grid) if grid.is_finite() && grid > 0.0 => Ok(grid),
+        _ => Err(format!(
+            "'{}' is not a grid spacing like 0.00001 or 1e-5",
+            text
+        )),
+    }
+}
+
+/// Moves every coordinate of `features` to the nearest multiple of `grid`.
+/// Positions that end up on the same node as the one before are merged, so
+/// nearly coincident vertices of neighbouring features become shared and thin
+/// slivers collapse; rings and lines left too short, and features left
+/// without geometry, are removed. Returns the number of positions removed.
+pub fn snap_features(features: &mut Vec<Feature>, grid: f64) -> usize {
+    let grid = Grid::new(grid);
+    let mut removed = 0;
+    features.retain_mut(|feature| {
+        feature.bbox = None;
+        let Some(geometry) = &mut feature.geometry else {
+            return true;
+        };
+        match snap_value(&geometry.value, &grid, &mut removed) {
+            Some(value) => {
+                *geometry = Geometry::new(value);
+                true
+            }
+            None => false,
+        }
+    });
+    removed
+}
+
+fn snap_value(value: &Value, grid: &Grid, removed: &mut usize) -> Option<Value> {
+    match value {
+        Value::Point(p) => Some(Value::Point(snap_position(p, grid))),
+        Value::MultiPoint(points) => Some(Value::MultiPoint(
+            points.iter().map(|p| snap_position(p, grid)).collect(),
+        )),
+        Value::LineString(line) => snap_line(line, grid, 2, removed).map(Value::LineString),
+        Value::MultiLineString(lines) => {
+            let lines: Vec<_> = lines
+                .iter()
+                .filter_map(|line| snap_line(line, grid, 2, removed))
+                .collect();
+            (!lines.is_empty()).then_some(Value::MultiLineString(lines))
+        }
+        Value::Polygon(rings) => snap_polygon(rings, grid, removed).map(Value::Polygon),
+        Value::MultiPolygon(polygons) => {
+            let polygons: Vec<_> = polygons
+                .iter()
+                .filter_map(|rings| snap_polygon(rings, grid, removed))
+                .collect();
+            (!polygons.is_empty()).then_some(Value::MultiPolygon(polygons))
+        }
+        Value::GeometryCollection(geometries) => {
+            let geometries: Vec<Geometry> = geometries
+                .iter()
+                .filter_map(|g| snap_value(&g.value, grid, removed).map(Geometry::new))
+                .collect();
+            (!geometries.is_empty()).then_some(Value::GeometryCollection(geometries))
+        }
+    }
+}
+
+// A polygon whose exterior collapsed is removed; holes that collapsed are dropped
+fn snap_polygon(
+    rings: &[Vec<Vec<f64>>],
+    grid: &Grid,
+    removed: &mut usize,
+) -> Option<Vec<Vec<Vec<f64>>>> {
+    let mut snapped = rings.iter().map(|ring| snap_line(ring, grid, 4, removed));
+    let mut polygon = vec![snapped.next()??];
+    polygon.extend(snapped.flatten());
+    Some(polygon)
+}
+
+// Snaps `line` and merges repeated positions; `None` if fewer than `minimum` remain
+fn snap_line(
+    line: &[Vec<f64>],
+    grid: &Grid,
+    minimum: usize,
+    removed: &mut usize,
+) -> Option<Vec<Vec<f64>>> {
+    let mut snapped: Vec<Vec<f64>> = Vec::with_capacity(line.len());
+    for p in line.iter().filter(|p| p.len() >= 2) {
+        let p = snap_position(p, grid);
+        if snapped.last().is_some_and(|last| last[..2] == p[..2]) {
+            *removed += 1;
+        } else {
+            snapped.push(p);
+        }
+    }
+    if snapped.len() < minimum {
+        *removed += snapped.len();
+        return None;
+    }
+    Some(snapped)
+}
+
+// Spacing of the grid, with the power of ten its nodes are rounded to, so
+// they print without floating point noise (0.3, not 0.30000000000000004)
+struct Grid {
+    spacing: f64,
+    factor: f64,
+}
+
+impl Grid {
+    fn new(spacing: f64) -> Grid {
+        let decimals = ((-spacing.log10()).ceil() as i32 + 2).clamp(0, 15);
+        Grid {
+            spacing,
+            factor: 10f64.powi(decimals),
+        }
+    }
+}
+
+// Longitude and latitude are snapped; further values (e.g. elevation) are kept
+fn snap_position(p: &[f64], grid: &Grid) -> Vec<f64> {
+    p.iter()
+        .enumerate()
+        .map(|(i, &coordinate)| {
+            if i < 2 {
+                let node = (coordinate / grid.spacing).round() * grid.spacing;
+                // Adding zero turns -0.0 into 0.0, which is shorter
+                (node * grid.factor).round() / grid.factor + 0.0
+            } else {
+                coordinate
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(feature: &Feature) -> &Value {
+        &feature.geometry.as_ref().unwrap().value
+    }
+
+    #[test]
+    fn grid_spacings_are_positive_numbers() {
+        assert_eq!(parse_grid(" "), Ok(DEFAULT_GRID));
+        assert_eq!(parse_grid("1e-4"), Ok(0.0001));
+        assert!(parse_grid("0").is_err());
+        assert!(parse_grid("-1").is_err());
+        assert!(parse_grid("fine").is_err());
+    }
+
+    #[test]
+    fn coordinates_land_on_the_grid_without_noise() {
+        let mut features = vec![Feature::from(Geometry::new(Value::Point(vec![
+            0.29, -0.04, 123.456,
+        ])))];
+        assert_eq!(snap_features(&mut features, 0.1), 0);
+        assert_eq!(
+            geometry(&features[0]),
+            &Value::Point(vec![0.3, 0.0, 123.456])
+        );
+        assert!(
+            geometry(&features[0])
+                .to_string()
+                .contains("0.3,0.0,123.456")
+        );
+    }
+
+    #[test]
+    fn collapsed_vertices_rings_and_features_are_removed() {
+        let sliver = vec![
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![1.0, 0.01],
+            vec![0.0, 0.01],
+            vec![0.0, 0.0],
+        ];
+        let square = vec![
+            vec![0.0, 0.0],
+            vec![2.0, 0.0],
+            vec![2.01, 1.98],
+            vec![2.0, 2.0],
+            vec![0.0, 2.0],
+            vec![0.0, 0.0],
+        ];
+        let mut features = vec![
+            Feature::from(Geometry::new(Value::Polygon(vec![square, sliver.clone()]))),
+            Feature::from(Geometry::new(Value::Polygon(vec![sliver]))),
+            Feature::default(),
+        ];
+        // A corner of the square merges into its neighbour; every position of
+        // the two slivers goes
+        assert_eq!(snap_features(&mut features, 0.5), 1 + 5 + 5);
+        assert_eq!(features.len(), 2, "features without geometry are kept");
+        let Value::Polygon(rings) = geometry(&features[0]) else {
+            panic!("{:?}", features[0]);
+        };
+        assert_eq!(rings.len(), 1);
+        assert_eq!(rings[0].len(), 5);
+    }
+}
@@ -0,0 +1,177 @@
+// spatial.rs
+
+use geojson::{Feature, Value};
+use rstar::{
+    AABB, RTree,
+    primitives::{GeomWithData, Rectangle},
+};
+
+use crate::clip;
+use crate::plot;
+
+type Entry<T> = GeomWithData<Rectangle<[f64; 2]>, T>;
+
+fn entry<T>(bbox: [f64; 4], data: T) -> Entry<T> {
+    GeomWithData::new(
+        Rectangle::from_corners([bbox[0], bbox[1]], [bbox[2], bbox[3]]),
+        data,
+    )
+}
+
+fn envelope(bbox: [f64; 4]) -> AABB<[f64; 2]> {
+    AABB::from_corners([bbox[0], bbox[1]], [bbox[2], bbox[3]])
+}
+
+fn feature_bbox(feature: &Feature) -> Option<[f64; 4]> {
+    let mut bbox = plot::empty_bbox();
+    plot::extend_bbox(&mut bbox, &feature.geometry.as_ref()?.value);
+    (bbox[0] <= bbox[2]).then_some(bbox)
+}
+
+/// R-tree over the bounding boxes of features, telling which features may lie
+/// in an extent or under a point without looking at all of them.
+pub struct FeatureIndex {
+    tree: RTree<Entry<usize>>,
+    feature_count: usize, // Features indexed, to notice when more were appended
+}
+
+impl FeatureIndex {
+    /// Indexes `features` by position; features without geometry are left out.
+    pub fn new(features: &[Feature]) -> FeatureIndex {
+        let entries = features
+            .iter()
+            .enumerate()
+            .filter_map(|(i, feature)| Some(entry(feature_bbox(feature)?, i)))
+            .collect();
+        FeatureIndex {
+            tree: RTree::bulk_load(entries),
+            feature_count: features.len(),
+        }
+    }
+
+    /// Number of features the index was built from.
+    pub fn feature_count(&self) -> usize {
+        self.feature_count
+    }
+
+    /// Adds the features appended to `features` since the index was built,
+    /// e.g. lines of a followed file.
+    pub fn extend(&mut self, features: &[Feature]) {
+        for (i, feature) in features.iter().enumerate().skip(self.feature_count) {
+            if let Some(bbox) = feature_bbox(feature) {
+                self.tree.insert(entry(bbox, i));
+            }
+        }
+        self.feature_count = features.len();
+    }
+
+    /// Positions, in ascending order, of the features whose bbox overlaps
+    /// `bbox` (min x, min y, max x, max y).
+    pub fn in_bbox(&self, bbox: [f64; 4]) -> Vec<usize> {
+        let mut found: Vec<usize> = self
+            .tree
+            .locate_in_envelope_intersecting(&envelope(bbox))
+            .map(|entry| entry.data)
+            .collect();
+        found.sort_unstable();
+        found
+    }
+
+    /// Positions, in ascending order, of the features whose bbox comes within
+    /// `tolerance` of the point `x`, `y`.
+    pub fn near(&self, x: f64, y: f64, tolerance: f64) -> Vec<usize> {
+        self.in_bbox([x - tolerance, y - tolerance, x + tolerance, y + tolerance])
+    }
+}
+
+/// R-tree over the edges of rings, for intersection and point-in-polygon
+/// tests that only look at the edges nearby.
+pub struct EdgeIndex {
+    tree: RTree<Entry<(usize, usize)>>,
+}
+
+impl EdgeIndex {
+    /// Indexes the edges of the open `rings`, including the closing edge of each.
+    pub fn new(rings: &[Vec<[f64; 2]>]) -> EdgeIndex {
+        let mut entries = Vec::new();
+        for (r, ring) in rings.iter().enumerate() {
+            for i in 0..ring.len() {
+                let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+                let bbox = [
+                    a[0].min(b[0]),
+                    a[1].min(b[1]),
+                    a[0].max(b[0]),
+                    a[1].max(b[1]),
+                ];
+                entries.push(entry(bbox, (r, i)));
+            }
+        }
+        EdgeIndex {
+            tree: RTree::bulk_load(entries),
+        }
+    }
+
+    /// The edges (ring, start vertex) whose bbox overlaps `bbox`.
+    pub fn in_bbox(&self, bbox: [f64; 4]) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.tree
+            .locate_in_envelope_intersecting(&envelope(bbox))
+            .map(|entry| entry.data)
+    }
+}
+
+/// Whether `value` covers the point `p`: polygons containing it (holes left
+/// out), and lines and points within `tolerance` of it.
+pub fn hits(value: &Value, p: [f64; 2], tolerance: f64) -> bool {
+    match value {
+        Value::Point(q) => point_near(q, p, tolerance),
+        Value::MultiPoint(points) => points.iter().any(|q| point_near(q, p, tolerance)),
+        Value::LineString(line) => line_near(line, p, tolerance),
+        Value::MultiLineString(lines) => lines.iter().any(|line| line_near(line, p, tolerance)),
+        Value::Polygon(rings) => polygon_contains(rings, p),
+        Value::MultiPolygon(polygons) => polygons.iter().any(|rings| polygon_contains(rings, p)),
+        Value::GeometryCollection(geometries) => geometries
+            .iter()
+            .any(|geometry| hits(&geometry.value, p, tolerance)),
+    }
+}
+
+fn point_near(q: &[f64], p: [f64; 2], tolerance: f64) -> bool {
+    q.len() >= 2 && (q[0] - p[0]).hypot(q[1] - p[1]) <= tolerance
+}
+
+fn line_near(line: &[Vec<f64>], p: [f64; 2], tolerance: f64) -> bool {
+    match line {
+        [only] => point_near(only, p, tolerance),
+        _ => line.windows(2).any(|pair| {
+            pair[0].len() >= 2
+                && pair[1].len() >= 2
+                && segment_distance(p, [pair[0][0], pair[0][1]], [pair[1][0], pair[1][1]])
+                    <= tolerance
+        }),
+    }
+}
+
+fn polygon_contains(rings: &[Vec<Vec<f64>>], p: [f64; 2]) -> bool {
+    let open: Vec<Vec<[f64; 2]>> = rings
+        .iter()
+        .map(|ring| {
+            ring.iter()
+                .filter(|q| q.len() >= 2)
+                .map(|q| [q[0], q[1]])
+                .collect()
+        })
+        .collect();
+    clip::contains(&open, p)
+}
+
+// Distance from `p` to the segment a-b
+fn segment_distance(p: [f64; 2], a: [f64; 2], b: [f64; 2]) -> f64 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    (p[0] - (a[0] + t * dx)).hypot(p[1] - (a[1] + t * dy))
+}
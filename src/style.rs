@@ -0,0 +1,805 @@
+// style.rs
+
+use geojson::{Feature, Value};
+use plotters::prelude::RGBColor;
+use std::fmt;
+
+use crate::filter::{self, CompareOp};
+use crate::label::LabelTemplate;
+use crate::ramp::{self, ClassMethod, ColorRamp};
+
+// Bands of the elevation legend
+const ELEVATION_BANDS: usize = 6;
+
+// Radius in pixels of the largest circle of a `proportional` rule without one,
+// and the most it may be given
+const DEFAULT_PROPORTIONAL_RADIUS: f64 = 20.0;
+const MAX_PROPORTIONAL_RADIUS: f64 = 100.0;
+
+/// Fill of features without a (numeric) value for a coloring rule's property.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoData {
+    Color(RGBColor),
+    Hidden, // `nodata=none`: such features are not drawn
+}
+
+pub const NO_DATA_COLOR: RGBColor = RGBColor(204, 204, 204);
+
+impl Default for NoData {
+    fn default() -> Self {
+        NoData::Color(NO_DATA_COLOR)
+    }
+}
+
+impl NoData {
+    fn parse(text: &str) -> Result<NoData, String> {
+        match text {
+            "none" | "hide" => Ok(NoData::Hidden),
+            _ => parse_color(text).map(NoData::Color),
+        }
+    }
+
+    /// Color of features without a value, unless they are hidden.
+    pub fn color(self) -> Option<RGBColor> {
+        match self {
+            NoData::Color(color) => Some(color),
+            NoData::Hidden => None,
+        }
+    }
+}
+
+impl fmt::Display for NoData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NoData::Color(c) => write!(f, "#{:02x}{:02x}{:02x}", c.0, c.1, c.2),
+            NoData::Hidden => write!(f, "none"),
+        }
+    }
+}
+
+/// A single styling rule, written as one line of text:
+///
+/// - `where <property> <op> <value>`: only draw features matching the condition
+///   (`==`, `!=`, `<`, `<=`, `>`, `>=`, `~` for "contains")
+/// - `color <property> <#from> <#to>`: color features along a gradient of a numeric property
+/// - `size <property> <min> <max>`: scale point radius and line width by a numeric property
+/// - `proportional <property> [max_radius]`: draw points as translucent circles whose
+///   area is proportional to a numeric property, the largest value getting `max_radius`
+///   pixels (default 20), and add a legend of representative sizes
+/// - `choropleth <property> [ramp] [equal|quantile|log|equalize] [classes] [mid=<value>]`:
+///   fill polygons by class of a numeric property (defaults: `ylorrd quantile 5`) and add
+///   a legend to the image; `equalize` colors continuously by percentile rank instead,
+///   and `mid=` centers a diverging ramp such as `bwr` on a value
+/// - `label <template>`: label features on the image, e.g. `label {NAME} ({POP_EST:,})`
+/// - `elevation [ramp]`: color line segments and points by the third coordinate of
+///   their positions (default ramp: `terrain`) and add a legend; features without
+///   elevations keep their color
+///
+/// `color` and `choropleth` rules draw features without a value in light grey,
+/// listed as "No data" in the legend; `nodata=#rrggbb` changes the color and
+/// `nodata=none` hides them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StyleRule {
+    Where {
+        property: String,
+        op: CompareOp,
+        value: String,
+    },
+    Color {
+        property: String,
+        from: RGBColor,
+        to: RGBColor,
+        no_data: NoData,
+    },
+    Size {
+        property: String,
+        min: f64,
+        max: f64,
+    },
+    Proportional {
+        property: String,
+        max_radius: f64,
+    },
+    Choropleth {
+        property: String,
+        ramp: ColorRamp,
+        method: ClassMethod,
+        classes: usize,
+        midpoint: Option<f64>, // Center of a diverging ramp; classes are then equal intervals
+        no_data: NoData,
+    },
+    Label {
+        template: LabelTemplate,
+    },
+    Elevation {
+        ramp: ColorRamp,
+    },
+}
+
+impl StyleRule {
+    pub fn parse(text: &str) -> Result<StyleRule, String> {
+        // Templates contain spaces and braces, so they are taken as a whole
+        if let Some(template) = text.trim().strip_prefix("label") {
+            if template.trim().is_empty() {
+                return Err(String::from("Expected: label <template>"));
+            }
+            if template.starts_with(char::is_whitespace) {
+                return Ok(StyleRule::Label {
+                    template: LabelTemplate::parse(template.trim())?,
+                });
+            }
+        }
+        // The only rule without a property
+        if let Some(args) = text.trim().strip_prefix("elevation")
+            && (args.is_empty() || args.starts_with(char::is_whitespace))
+        {
+            let ramp = match args.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [] => ColorRamp::parse("terrain")?,
+                [ramp] => ColorRamp::parse(ramp)?,
+                _ => return Err(String::from("Expected: elevation [ramp]")),
+            };
+            return Ok(StyleRule::Elevation { ramp });
+        }
+        let mut words = text.split_whitespace();
+        let kind = words.next().ok_or("Empty rule")?;
+        let property = words
+            .next()
+            .ok_or_else(|| format!("Missing property in '{}'", text))?
+            .to_string();
+        let mut args: Vec<&str> = words.collect();
+        let mut no_data = NoData::default();
+        if matches!(kind, "color" | "choropleth")
+            && let Some(i) = args.iter().position(|arg| arg.starts_with("nodata="))
+        {
+            no_data = NoData::parse(&args.remove(i)["nodata=".len()..])?;
+        }
+        match (kind, args.as_slice()) {
+            ("where", [op, value @ ..]) if !value.is_empty() => Ok(StyleRule::Where {
+                property,
+                op: CompareOp::parse(op).ok_or_else(|| format!("Unknown operator '{}'", op))?,
+                value: value.join(" ").trim_matches('"').to_string(),
+            }),
+            ("color", [from, to]) => Ok(StyleRule::Color {
+                property,
+                from: parse_color(from)?,
+                to: parse_color(to)?,
+                no_data,
+            }),
+            ("size", [min, max]) => {
+                let parse = |s: &str| {
+                    s.parse::<f64>()
+                        .ok()
+                        .filter(|n| *n >= 0.0)
+                        .ok_or_else(|| format!("Invalid size '{}'", s))
+                };
+                Ok(StyleRule::Size {
+                    property,
+                    min: parse(min)?,
+                    max: parse(max)?,
+                })
+            }
+            ("proportional", []) => Ok(StyleRule::Proportional {
+                property,
+                max_radius: DEFAULT_PROPORTIONAL_RADIUS,
+            }),
+            ("proportional", [radius]) => Ok(StyleRule::Proportional {
+                property,
+                max_radius: radius
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|r| *r >= 1.0 && *r <= MAX_PROPORTIONAL_RADIUS)
+                    .ok_or_else(|| {
+                        format!(
+                            "Invalid radius '{}' (use 1 to {} pixels)",
+                            radius, MAX_PROPORTIONAL_RADIUS
+                        )
+                    })?,
+            }),
+            ("choropleth", args) => {
+                let mut ramp = ColorRamp::parse("ylorrd")?;
+                let mut method = None;
+                let mut classes = 5;
+                let mut midpoint = None;
+                // Optional arguments may be given in any order
+                for arg in args {
+                    if let Some(value) = arg.strip_prefix("mid=") {
+                        midpoint = Some(
+                            value
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|v| v.is_finite())
+                                .ok_or_else(|| format!("Invalid midpoint '{}'", value))?,
+                        );
+                    } else if let Ok(n) = arg.parse::<usize>() {
+                        if !(2..=12).contains(&n) {
+                            return Err(String::from("Choropleth needs 2 to 12 classes"));
+                        }
+                        classes = n;
+                    } else if let Some(m) = ClassMethod::parse(arg) {
+                        method = Some(m);
+                    } else {
+                        ramp = ColorRamp::parse(arg)?;
+                    }
+                }
+                let method = match (method, midpoint) {
+                    (None | Some(ClassMethod::EqualInterval), Some(_)) => {
+                        ClassMethod::EqualInterval
+                    }
+                    (Some(m), Some(_)) => {
+                        return Err(format!("A midpoint needs equal classes, not {}", m.name()));
+                    }
+                    (m, None) => m.unwrap_or(ClassMethod::Quantile),
+                };
+                Ok(StyleRule::Choropleth {
+                    property,
+                    ramp,
+                    method,
+                    classes,
+                    midpoint,
+                    no_data,
+                })
+            }
+            ("where", _) => Err(String::from("Expected: where <property> <op> <value>")),
+            ("color", _) => Err(String::from("Expected: color <property> <#from> <#to>")),
+            ("size", _) => Err(String::from("Expected: size <property> <min> <max>")),
+            ("proportional", _) => Err(String::from(
+                "Expected: proportional <property> [max_radius]",
+            )),
+            _ => Err(format!(
+                "Unknown rule '{}' (use where, color, size, proportional, choropleth, label or elevation)",
+                kind
+            )),
+        }
+    }
+
+    // How a coloring rule draws features without a value
+    fn no_data(&self) -> Option<NoData> {
+        match self {
+            StyleRule::Color { no_data, .. } | StyleRule::Choropleth { no_data, .. } => {
+                Some(*no_data)
+            }
+            _ => None,
+        }
+    }
+
+    // Property driving a gradient rule
+    fn property(&self) -> Option<&str> {
+        match self {
+            StyleRule::Color { property, .. }
+            | StyleRule::Size { property, .. }
+            | StyleRule::Proportional { property, .. }
+            | StyleRule::Choropleth { property, .. } => Some(property),
+            StyleRule::Where { .. } | StyleRule::Label { .. } | StyleRule::Elevation { .. } => None,
+        }
+    }
+}
+
+// Formats a rule in the syntax accepted by `StyleRule::parse`
+impl fmt::Display for StyleRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex = |c: &RGBColor| format!("#{:02x}{:02x}{:02x}", c.0, c.1, c.2);
+        match self {
+            StyleRule::Where {
+                property,
+                op,
+                value,
+            } => write!(f, "where {} {} {}", property, op.symbol(), value),
+            StyleRule::Color {
+                property,
+                from,
+                to,
+                no_data,
+            } => {
+                write!(f, "color {} {} {}", property, hex(from), hex(to))?;
+                write_no_data(f, *no_data)
+            }
+            StyleRule::Size { property, min, max } => {
+                write!(f, "size {} {} {}", property, min, max)
+            }
+            StyleRule::Proportional {
+                property,
+                max_radius,
+            } => write!(f, "proportional {} {}", property, max_radius),
+            StyleRule::Choropleth {
+                property,
+                ramp,
+                method,
+                classes,
+                midpoint,
+                no_data,
+            } => {
+                write!(
+                    f,
+                    "choropleth {} {} {} {}",
+                    property,
+                    ramp,
+                    method.name(),
+                    classes
+                )?;
+                if let Some(midpoint) = midpoint {
+                    write!(f, " mid={}", midpoint)?;
+                }
+                write_no_data(f, *no_data)
+            }
+            StyleRule::Label { template } => write!(f, "label {}", template),
+            StyleRule::Elevation { ramp } => write!(f, "elevation {}", ramp),
+        }
+    }
+}
+
+// Only a non-default no data style is written out
+fn write_no_data(f: &mut fmt::Formatter, no_data: NoData) -> fmt::Result {
+    if no_data == NoData::default() {
+        Ok(())
+    } else {
+        write!(f, " nodata={}", no_data)
+    }
+}
+
+/// Parses `#rrggbb` (the `#` is optional).
+pub fn parse_color(text: &str) -> Result<RGBColor, String> {
+    let hex = text.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or_else(|| format!("Invalid color '{}' (expected #rrggbb)", text))
+    };
+    if hex.len() != 6 {
+        return Err(format!("Invalid color '{}' (expected #rrggbb)", text));
+    }
+    Ok(RGBColor(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Reads a numeric property, accepting numbers stored as strings.
+pub fn numeric_property(feature: &Feature, property: &str) -> Option<f64> {
+    filter::as_number(feature.property(property)?)
+}
+
+// A `where` rule compares like the same comparison in a feature filter
+fn matches_condition(feature: &Feature, property: &str, op: CompareOp, value: &str) -> bool {
+    let actual = feature
+        .property(property)
+        .unwrap_or(&serde_json::Value::Null);
+    filter::compare(actual, op, &serde_json::Value::from(value))
+}
+
+// Size and proportional rules both set the point radius, so the last one of
+// them wins
+fn size_rule(rules: &[StyleRule]) -> Option<&StyleRule> {
+    rules
+        .iter()
+        .rev()
+        .find(|r| matches!(r, StyleRule::Size { .. } | StyleRule::Proportional { .. }))
+}
+
+// Gradients, choropleths and elevations all set the color, so the last one
+// of them wins
+fn coloring_rule(rules: &[StyleRule]) -> Option<&StyleRule> {
+    rules.iter().rev().find(|r| {
+        matches!(
+            r,
+            StyleRule::Color { .. } | StyleRule::Choropleth { .. } | StyleRule::Elevation { .. }
+        )
+    })
+}
+
+/// Whether the coloring rule of `rules` colors by elevation.
+pub fn colors_by_elevation(rules: &[StyleRule]) -> bool {
+    matches!(coloring_rule(rules), Some(StyleRule::Elevation { .. }))
+}
+
+/// Lowest and highest third coordinate of the positions of `value`, if any
+/// has one.
+pub fn elevation_range(value: &Value) -> Option<(f64, f64)> {
+    let mut range = (f64::MAX, f64::MIN);
+    extend_elevation_range(&mut range, value);
+    (range.0 <= range.1).then_some(range)
+}
+
+fn extend_elevation_range(range: &mut (f64, f64), value: &Value) {
+    let mut positions = |positions: &[Vec<f64>]| {
+        for z in positions.iter().filter_map(|p| p.get(2)) {
+            *range = (range.0.min(*z), range.1.max(*z));
+        }
+    };
+    match value {
+        Value::Point(p) => positions(std::slice::from_ref(p)),
+        Value::MultiPoint(points) | Value::LineString(points) => positions(points),
+        Value::MultiLineString(lines) | Value::Polygon(lines) => {
+            lines.iter().for_each(|line| positions(line))
+        }
+        Value::MultiPolygon(polygons) => polygons.iter().flatten().for_each(|r| positions(r)),
+        Value::GeometryCollection(geometries) => {
+            for geometry in geometries {
+                extend_elevation_range(range, &geometry.value);
+            }
+        }
+    }
+}
+
+/// Whether `feature` satisfies every `where` rule of `rules` and is not hidden
+/// for lacking a value of the coloring rule (`nodata=none`).
+pub fn accepts(rules: &[StyleRule], feature: &Feature) -> bool {
+    let hidden = coloring_rule(rules).is_some_and(|rule| {
+        rule.no_data() == Some(NoData::Hidden)
+            && rule
+                .property()
+                .is_some_and(|property| numeric_property(feature, property).is_none())
+    });
+    !hidden
+        && rules.iter().all(|rule| match rule {
+            StyleRule::Where {
+                property,
+                op,
+                value,
+            } => matches_condition(feature, property, *op, value),
+            _ => true,
+        })
+}
+
+/// Circles of representative values explaining a `proportional` rule.
+pub struct SizeLegend {
+    pub title: String,
+    pub entries: Vec<(f64, String)>, // Radius in pixels and value, largest first
+}
+
+/// Color swatches explaining a property-driven coloring.
+pub struct Legend {
+    pub title: String,
+    pub entries: Vec<(RGBColor, String)>,
+    pub midpoint: Option<f64>, // Value at the middle of a diverging color bar
+    pub no_data: Option<RGBColor>, // Shown below the classes if some features have no value
+}
+
+// Choropleth classes resolved against the data
+struct Classes<'a> {
+    property: &'a str,
+    breaks: Vec<f64>,
+    colors: Vec<RGBColor>,
+    midpoint: Option<f64>,
+    equalized: Option<(&'a ColorRamp, Vec<f64>)>, // Ramp and sorted values of an `equalize` choropleth
+}
+
+/// Styling rules resolved against the data they are applied to.
+pub struct Styler<'a> {
+    rules: &'a [StyleRule],
+    color: Option<(&'a StyleRule, f64, f64)>, // Last color rule and its property range
+    size: Option<(&'a StyleRule, f64, f64)>, // Last size or proportional rule and its property range
+    classes: Option<Classes<'a>>,            // Set if the last coloring rule is a choropleth
+    label: Option<&'a LabelTemplate>,        // Template of the last label rule
+    elevation: Option<(&'a ColorRamp, f64, f64)>, // Set if the last coloring rule is an elevation rule, with the elevation range
+    no_data: NoData,                              // Of the last coloring rule
+    missing: usize,                               // Features without a value for the coloring rule
+}
+
+/// Properties `Styler::new` reads from the features, i.e. those of the last
+/// coloring and size rules.
+pub fn value_properties(rules: &[StyleRule]) -> Vec<&str> {
+    coloring_rule(rules)
+        .into_iter()
+        .chain(size_rule(rules))
+        .filter_map(StyleRule::property)
+        .collect()
+}
+
+impl<'a> Styler<'a> {
+    /// Computes the property ranges and classes of the rules over `features`.
+    pub fn new<'f>(
+        rules: &'a [StyleRule],
+        features: impl Iterator<Item = &'f Feature>,
+    ) -> Styler<'a> {
+        let color_rule = coloring_rule(rules);
+        let size_rule = size_rule(rules);
+        let color_property = color_rule.and_then(StyleRule::property);
+        let size_property = size_rule.and_then(StyleRule::property);
+
+        let elevation_ramp = match color_rule {
+            Some(StyleRule::Elevation { ramp }) => Some(ramp),
+            _ => None,
+        };
+
+        let mut color_values = Vec::new();
+        let mut missing = 0;
+        let mut size_range = (f64::MAX, f64::MIN);
+        let mut elevations = (f64::MAX, f64::MIN);
+        if color_property.is_some() || size_property.is_some() || elevation_ramp.is_some() {
+            for feature in features {
+                if elevation_ramp.is_some()
+                    && let Some(geometry) = &feature.geometry
+                {
+                    extend_elevation_range(&mut elevations, &geometry.value);
+                }
+                if let Some(property) = color_property {
+                    match numeric_property(feature, property) {
+                        Some(n) => color_values.push(n),
+                        None => missing += 1,
+                    }
+                }
+                if let Some(n) = size_property.and_then(|p| numeric_property(feature, p)) {
+                    size_range.0 = size_range.0.min(n);
+                    size_range.1 = size_range.1.max(n);
+                }
+            }
+        }
+        let color_range = color_values
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(min, max), &n| {
+                (min.min(n), max.max(n))
+            });
+
+        let classes = match color_rule {
+            Some(StyleRule::Choropleth {
+                property,
+                ramp,
+                method,
+                classes,
+                ..
+            }) if *method == ClassMethod::Equalize => Some(Classes {
+                property,
+                breaks: ramp::class_breaks(&color_values, *method, *classes),
+                // Legend swatches show the middle of each percentile range
+                colors: (0..*classes)
+                    .map(|i| ramp.sample((i as f64 + 0.5) / *classes as f64))
+                    .collect(),
+                midpoint: None,
+                equalized: Some((ramp, ramp::sorted_values(&color_values))),
+            }),
+            Some(StyleRule::Choropleth {
+                property,
+                ramp,
+                method,
+                classes,
+                midpoint,
+                ..
+            }) => Some(Classes {
+                property,
+                breaks: match midpoint {
+                    Some(midpoint) => ramp::diverging_breaks(&color_values, *midpoint, *classes),
+                    None => ramp::class_breaks(&color_values, *method, *classes),
+                },
+                colors: ramp.class_colors(*classes),
+                midpoint: *midpoint,
+                equalized: None,
+            }),
+            _ => None,
+        };
+        Styler {
+            rules,
+            color: color_rule.map(|r| (r, color_range.0, color_range.1)),
+            size: size_rule.map(|r| (r, size_range.0, size_range.1)),
+            classes,
+            label: rules.iter().rev().find_map(|rule| match rule {
+                StyleRule::Label { template } => Some(template),
+                _ => None,
+            }),
+            elevation: elevation_ramp
+                .filter(|_| elevations.0 <= elevations.1)
+                .map(|ramp| (ramp, elevations.0, elevations.1)),
+            no_data: color_rule.and_then(StyleRule::no_data).unwrap_or_default(),
+            missing,
+        }
+    }
+
+    /// Whether `feature` satisfies every `where` rule.
+    pub fn accepts(&self, feature: &Feature) -> bool {
+        accepts(self.rules, feature)
+    }
+
+    /// Class or gradient color of `feature`, or `default` if no coloring rule applies to it.
+    pub fn color(&self, feature: &Feature, default: RGBColor) -> RGBColor {
+        if self.classes.is_some() {
+            return self.fill(feature).unwrap_or(default);
+        }
+        let Some((
+            StyleRule::Color {
+                property, from, to, ..
+            },
+            min,
+            max,
+        )) = self.color
+        else {
+            return default;
+        };
+        let Some(t) = numeric_property(feature, property).map(|n| normalize(n, min, max)) else {
+            return self.no_data.color().unwrap_or(default);
+        };
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        RGBColor(mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+    }
+
+    /// Choropleth fill of `feature`, if a choropleth rule applies to it.
+    pub fn fill(&self, feature: &Feature) -> Option<RGBColor> {
+        let classes = self.classes.as_ref()?;
+        let Some(value) = numeric_property(feature, classes.property) else {
+            return self.no_data.color();
+        };
+        if classes.breaks.is_empty() {
+            return None;
+        }
+        if let Some((ramp, sorted)) = &classes.equalized {
+            return Some(ramp.sample(ramp::percentile_rank(sorted, value)));
+        }
+        Some(classes.colors[ramp::class_index(&classes.breaks, value)])
+    }
+
+    /// Whether segments and points are colored by their elevation, i.e. the
+    /// last coloring rule is an elevation rule and some position has one.
+    pub fn colors_by_elevation(&self) -> bool {
+        self.elevation.is_some()
+    }
+
+    /// Color of the elevation `z` if segments and points are colored by elevation.
+    pub fn elevation_color(&self, z: f64) -> Option<RGBColor> {
+        let (ramp, min, max) = self.elevation?;
+        Some(ramp.sample(normalize(z, min, max)))
+    }
+
+    /// Legend for the choropleth classes or elevations, if any.
+    pub fn legend(&self) -> Option<Legend> {
+        if let Some((ramp, min, max)) = self.elevation {
+            return Some(elevation_legend(ramp, min, max));
+        }
+        let classes = self.classes.as_ref().filter(|c| !c.breaks.is_empty())?;
+        let entries = classes
+            .colors
+            .iter()
+            .zip(classes.breaks.windows(2))
+            .map(|(color, range)| {
+                let label = format!(
+                    "{} – {}",
+                    ramp::format_value(range[0]),
+                    ramp::format_value(range[1])
+                );
+                (*color, label)
+            })
+            .collect();
+        Some(Legend {
+            title: match classes.midpoint {
+                Some(midpoint) => format!(
+                    "{} (mid {})",
+                    classes.property,
+                    ramp::format_value(midpoint)
+                ),
+                None => classes.property.to_string(),
+            },
+            entries,
+            midpoint: classes.midpoint,
+            no_data: self.no_data.color().filter(|_| self.missing > 0),
+        })
+    }
+
+    /// Label of `feature`, or `None` if there is no label rule or it renders empty.
+    pub fn label(&self, feature: &Feature) -> Option<String> {
+        Some(self.label?.render(feature)).filter(|label| !label.is_empty())
+    }
+
+    /// Whether points are drawn as circles sized by a `proportional` rule.
+    pub fn draws_proportional(&self) -> bool {
+        matches!(self.size, Some((StyleRule::Proportional { .. }, _, _)))
+    }
+
+    /// Radius of the circle of `feature` under a `proportional` rule, or `None`
+    /// if there is none or the feature has no value.
+    pub fn proportional_radius(&self, feature: &Feature) -> Option<f64> {
+        let Some((
+            StyleRule::Proportional {
+                property,
+                max_radius,
+            },
+            _,
+            max,
+        )) = self.size
+        else {
+            return None;
+        };
+        let value = numeric_property(feature, property)?;
+        Some(circle_radius(value, max, *max_radius))
+    }
+
+    /// Legend of up to three circles of round values below the largest one
+    /// of a `proportional` rule, if any feature has a positive value.
+    pub fn size_legend(&self) -> Option<SizeLegend> {
+        let Some((
+            StyleRule::Proportional {
+                property,
+                max_radius,
+            },
+            _,
+            max,
+        )) = self.size
+        else {
+            return None;
+        };
+        if max <= 0.0 {
+            return None;
+        }
+        let mut values: Vec<f64> = [1.0, 0.25, 0.0625]
+            .iter()
+            .map(|share| round_down(max * share))
+            .collect();
+        values.dedup();
+        Some(SizeLegend {
+            title: property.clone(),
+            entries: values
+                .into_iter()
+                .map(|value| {
+                    (
+                        circle_radius(value, max, *max_radius),
+                        ramp::format_value(value),
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    /// Scaled size of `feature`, or `None` if no size rule applies to it.
+    pub fn size(&self, feature: &Feature) -> Option<f64> {
+        let Some((
+            StyleRule::Size {
+                property,
+                min: smin,
+                max: smax,
+            },
+            min,
+            max,
+        )) = self.size
+        else {
+            return None;
+        };
+        let t = normalize(numeric_property(feature, property)?, min, max);
+        Some(smin + (smax - smin) * t)
+    }
+}
+
+// Equal bands of the elevation range, each shown in the color of its middle,
+// so the legend reads as a color bar
+fn elevation_legend(ramp: &ColorRamp, min: f64, max: f64) -> Legend {
+    let bands = if max > min { ELEVATION_BANDS } else { 1 };
+    let entries = (0..bands)
+        .map(|i| {
+            let (low, high) = (i as f64 / bands as f64, (i + 1) as f64 / bands as f64);
+            let label = format!(
+                "{} – {}",
+                ramp::format_value(min + (max - min) * low),
+                ramp::format_value(min + (max - min) * high)
+            );
+            (ramp.sample((low + high) / 2.0), label)
+        })
+        .collect();
+    Legend {
+        title: String::from("Elevation (m)"),
+        entries,
+        midpoint: None,
+        no_data: None,
+    }
+}
+
+// Radius of a circle whose area is to that of one of `max_radius` as `value`
+// is to `max`; zero and negative values get the smallest visible circle
+fn circle_radius(value: f64, max: f64, max_radius: f64) -> f64 {
+    let share = if max > 0.0 {
+        (value / max).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+    (max_radius * share.sqrt()).max(1.0)
+}
+
+// The largest 1, 2 or 5 times a power of ten not above `value` (positive)
+fn round_down(value: f64) -> f64 {
+    let power = 10f64.powf(value.log10().floor());
+    [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|step| step * power)
+        .find(|round| *round <= value)
+        .unwrap_or(power)
+}
+
+// Maps `value` from [min, max] to [0, 1]; a single-valued range maps to 1
+fn normalize(value: f64, min: f64, max: f64) -> f64 {
+    if max > min {
+        ((value - min) / (max - min)).clamp(0.0, 1.0)
+    } else {
+        1.0
+    }
+}
@@ -0,0 +1,256 @@
+// table.rs
+
+use geojson::Feature;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+// Widest a column is drawn, in characters
+const MAX_COLUMN_WIDTH: usize = 30;
+
+/// Attribute table of one file: a row per feature and a column per property,
+/// with the highlighted cell and the sort order.
+pub struct AttributeTable {
+    pub file_index: usize,
+    pub columns: Vec<String>, // Property names in the order they first appear
+    pub widths: Vec<usize>,   // Characters needed by each column, capped
+    pub rows: Vec<usize>,     // Feature positions in the order shown
+    pub row: usize,           // Highlighted row, an index into `rows`
+    pub column: usize,        // Highlighted column
+    pub sort: Option<(usize, bool)>, // Column sorted by and whether descending; `None` is file order
+    pub page_rows: usize,            // Rows that fit on the screen, set when drawn
+    pub modified: bool,              // Whether values were edited since the file was read or saved
+    pub leaving: bool,               // Escape was pressed once with unsaved edits
+}
+
+impl AttributeTable {
+    /// Lists the properties of `features`, the features of `file_index`, in file order.
+    pub fn new(file_index: usize, features: &[Feature]) -> AttributeTable {
+        let mut columns: Vec<String> = Vec::new();
+        for feature in features {
+            for key in feature.properties.iter().flat_map(|p| p.keys()) {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+        }
+        let mut table = AttributeTable {
+            file_index,
+            widths: Vec::new(),
+            columns,
+            rows: (0..features.len()).collect(),
+            row: 0,
+            column: 0,
+            sort: None,
+            page_rows: 1,
+            modified: false,
+            leaving: false,
+        };
+        table.measure(features);
+        table
+    }
+
+    // Widens each column to its header, with room for the sort arrow, and its longest value
+    fn measure(&mut self, features: &[Feature]) {
+        self.widths = self
+            .columns
+            .iter()
+            .map(|column| {
+                features
+                    .iter()
+                    .map(|feature| cell(feature, column).chars().count())
+                    .chain([column.chars().count() + 1])
+                    .max()
+                    .unwrap_or(0)
+                    .min(MAX_COLUMN_WIDTH)
+            })
+            .collect();
+    }
+
+    /// Lists the features again when their number changed, e.g. after the
+    /// file was reloaded or a followed file grew.
+    pub fn sync(&mut self, features: &[Feature]) {
+        if self.rows.len() == features.len() {
+            return;
+        }
+        let sorted_by = self
+            .sort
+            .map(|(column, descending)| (self.columns[column].clone(), descending));
+        let fresh = AttributeTable::new(self.file_index, features);
+        self.columns = fresh.columns;
+        self.widths = fresh.widths;
+        self.sort = sorted_by.and_then(|(name, descending)| {
+            Some((self.columns.iter().position(|c| *c == name)?, descending))
+        });
+        self.resort(features);
+        self.move_by(0, 0);
+    }
+
+    /// Position of the feature in the highlighted row.
+    pub fn feature(&self) -> Option<usize> {
+        self.rows.get(self.row).copied()
+    }
+
+    /// Moves the highlight by `rows` rows and `columns` columns, staying in the table.
+    pub fn move_by(&mut self, rows: isize, columns: isize) {
+        let clamp = |value: usize, delta: isize, len: usize| {
+            value
+                .saturating_add_signed(delta)
+                .min(len.saturating_sub(1))
+        };
+        self.row = clamp(self.row, rows, self.rows.len());
+        self.column = clamp(self.column, columns, self.columns.len());
+    }
+
+    /// Sorts by the highlighted column, ascending first, then descending,
+    /// then back to file order. The highlighted feature stays highlighted.
+    pub fn cycle_sort(&mut self, features: &[Feature]) {
+        self.sort = match self.sort {
+            Some((column, false)) if column == self.column => Some((column, true)),
+            Some((column, true)) if column == self.column => None,
+            _ if self.columns.is_empty() => None,
+            _ => Some((self.column, false)),
+        };
+        self.resort(features);
+    }
+
+    /// Puts the rows back in the current sort order, e.g. after a value changed.
+    pub fn resort(&mut self, features: &[Feature]) {
+        let highlighted = self.feature();
+        self.rows = (0..features.len()).collect();
+        if let Some((column, descending)) = self.sort {
+            let name = &self.columns[column];
+            let value = |i: usize| features[i].properties.as_ref().and_then(|p| p.get(name));
+            // Stable, so equal values keep their file order
+            self.rows
+                .sort_by(|&a, &b| compare(value(a), value(b), descending));
+        }
+        if let Some(feature) = highlighted {
+            self.row = self.rows.iter().position(|&i| i == feature).unwrap_or(0);
+        }
+    }
+
+    /// Sets the highlighted cell of `features` to `value`, or removes the
+    /// property from the feature when `None`.
+    pub fn set_cell(&mut self, features: &mut [Feature], value: Option<Value>) {
+        let (Some(position), Some(name)) = (self.feature(), self.columns.get(self.column)) else {
+            return;
+        };
+        let Some(feature) = features.get_mut(position) else {
+            return;
+        };
+        match value {
+            Some(value) => {
+                feature
+                    .properties
+                    .get_or_insert_with(Map::new)
+                    .insert(name.clone(), value);
+            }
+            None => {
+                if let Some(properties) = feature.properties.as_mut() {
+                    properties.remove(name);
+                }
+            }
+        }
+        self.modified = true;
+        self.measure(features);
+        self.resort(features);
+    }
+
+    /// Adds an empty column called `name` after the highlighted one and highlights it.
+    pub fn add_column(&mut self, name: &str) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(String::from("A property needs a name."));
+        }
+        if self.columns.iter().any(|column| column == name) {
+            return Err(format!("There is a property called '{}' already.", name));
+        }
+        let at = (self.column + 1).min(self.columns.len());
+        self.columns.insert(at, name.to_string());
+        self.widths
+            .insert(at, (name.chars().count() + 1).min(MAX_COLUMN_WIDTH));
+        if let Some((column, _)) = self.sort.as_mut().filter(|(column, _)| *column >= at) {
+            *column += 1;
+        }
+        self.column = at;
+        Ok(())
+    }
+
+    /// Removes the highlighted column, and its property from every feature.
+    pub fn remove_column(&mut self, features: &mut [Feature]) {
+        if self.column >= self.columns.len() {
+            return;
+        }
+        let name = self.columns.remove(self.column);
+        self.widths.remove(self.column);
+        for properties in features.iter_mut().filter_map(|f| f.properties.as_mut()) {
+            properties.remove(&name);
+        }
+        self.sort = match self.sort {
+            Some((column, _)) if column == self.column => None,
+            Some((column, descending)) if column > self.column => Some((column - 1, descending)),
+            sort => sort,
+        };
+        self.modified = true;
+        self.move_by(0, 0);
+        self.resort(features);
+    }
+}
+
+/// Reads a value typed into a cell that held `previous`: text stays text and
+/// numbers must stay numbers, while new values are numbers, `true`, `false`
+/// or JSON when they parse as such and text otherwise. Empty removes the property.
+pub fn parse_cell(text: &str, previous: Option<&Value>) -> Result<Option<Value>, String> {
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let parsed = serde_json::from_str::<Value>(text.trim()).ok();
+    match (previous, parsed) {
+        (Some(Value::String(_)), _) => Ok(Some(Value::String(text.to_string()))),
+        (Some(Value::Number(_)), Some(number @ Value::Number(_))) => Ok(Some(number)),
+        (Some(Value::Number(_)), _) => Err(format!("'{}' is not a number.", text)),
+        (Some(Value::Bool(_)), Some(flag @ Value::Bool(_))) => Ok(Some(flag)),
+        (Some(Value::Bool(_)), _) => Err(format!("'{}' is not true or false.", text)),
+        (Some(Value::Array(_) | Value::Object(_)), Some(value)) => Ok(Some(value)),
+        (Some(Value::Array(_) | Value::Object(_)), None) => {
+            Err(format!("'{}' is not valid JSON.", text))
+        }
+        (_, Some(value)) if !value.is_string() => Ok(Some(value)),
+        _ => Ok(Some(Value::String(text.to_string()))),
+    }
+}
+
+/// Text shown for the `column` property of `feature`; empty when it is not set.
+pub fn cell(feature: &Feature, column: &str) -> String {
+    match feature.properties.as_ref().and_then(|p| p.get(column)) {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(text)) => text.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+// Numbers before text and other values, each in their natural order; unset
+// values come last in both directions
+fn compare(a: Option<&Value>, b: Option<&Value>, descending: bool) -> Ordering {
+    let unset = |value: Option<&Value>| matches!(value, None | Some(Value::Null));
+    let rank = |value: &Value| match value {
+        Value::Number(_) => 0,
+        Value::String(_) => 1,
+        _ => 2,
+    };
+    let (x, y) = match (a, b) {
+        (Some(x), Some(y)) if !unset(a) && !unset(b) => (x, y),
+        _ => return unset(a).cmp(&unset(b)),
+    };
+    let order = match (x, y) {
+        (Value::Number(x), Value::Number(y)) => x
+            .as_f64()
+            .unwrap_or(f64::NAN)
+            .total_cmp(&y.as_f64().unwrap_or(f64::NAN)),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        _ => rank(x)
+            .cmp(&rank(y))
+            .then_with(|| x.to_string().cmp(&y.to_string())),
+    };
+    if descending { order.reverse() } else { order }
+}
@@ -0,0 +1,382 @@
+// temporal.rs
+
+use chrono::{DateTime, Datelike, Months, NaiveDate, NaiveDateTime, TimeDelta, Timelike};
+use geojson::Feature;
+use serde_json::Value;
+use std::fmt;
+
+// Properties holding the time of a feature, in order of preference; names are
+// compared without regard to case
+const TIME_PROPERTIES: [&str; 9] = [
+    "time",
+    "timestamp",
+    "datetime",
+    "date",
+    "start_time",
+    "start",
+    "begin",
+    "acq_date",
+    "valid_time",
+];
+
+// Whole numbers up to this are years, like `2020` written as a string
+const LATEST_YEAR: i64 = 9999;
+
+// Numbers above this are milliseconds rather than seconds since the epoch
+// (the year 5138 in seconds)
+const MILLISECONDS_FROM: f64 = 1e11;
+
+/// Time of `feature` in seconds since the Unix epoch, read from the first of
+/// `time`, `timestamp`, `datetime`, `date`, `start_time`, `start`, `begin`,
+/// `acq_date` or `valid_time` it has. `None` if it has none or the value is
+/// not a time.
+pub fn feature_time(feature: &Feature) -> Option<i64> {
+    let properties = feature.properties.as_ref()?;
+    let (_, value) = properties
+        .iter()
+        .filter_map(|(name, value)| {
+            let name = name.to_lowercase();
+            let rank = TIME_PROPERTIES.iter().position(|p| *p == name)?;
+            Some((rank, value))
+        })
+        .min_by_key(|(rank, _)| *rank)?;
+    parse_time(value)
+}
+
+/// Seconds since the Unix epoch of a property value: an ISO 8601 date or date
+/// and time (UTC unless it has an offset), a year as a whole number up to
+/// 9999, or a larger number of seconds (or, if larger than 1e11, milliseconds)
+/// since the epoch.
+pub fn parse_time(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) if n.as_i64().is_some_and(|n| (0..=LATEST_YEAR).contains(&n)) => {
+            Some(midnight(NaiveDate::from_ymd_opt(n.as_i64()? as i32, 1, 1)?))
+        }
+        Value::Number(n) => {
+            let n = n.as_f64()?;
+            let seconds = if n.abs() > MILLISECONDS_FROM {
+                n / 1000.0
+            } else {
+                n
+            };
+            Some(seconds.floor() as i64)
+        }
+        Value::String(s) => parse_period(s).map(|(start, _)| start),
+        _ => None,
+    }
+}
+
+// The span `text` names, as its first second and the first second after it:
+// a year (`2020`), a month (`2020-06`), a day (`2020-06-30`) or a moment
+// (`2020-06-30T12:00:00Z`, also with a space instead of the `T`)
+fn parse_period(text: &str) -> Option<(i64, i64)> {
+    let text = text.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Some((time.timestamp(), time.timestamp() + 1));
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(text.trim_end_matches('Z'), format) {
+            let seconds = time.and_utc().timestamp();
+            return Some((seconds, seconds + 1));
+        }
+    }
+    let (first, months) = if let Ok(day) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Some((midnight(day), midnight(day.succ_opt()?)));
+    } else if text.len() == 7 {
+        (
+            NaiveDate::parse_from_str(&format!("{}-01", text), "%Y-%m-%d").ok()?,
+            1,
+        )
+    } else if text.len() == 4 && text.bytes().all(|b| b.is_ascii_digit()) {
+        (NaiveDate::from_ymd_opt(text.parse().ok()?, 1, 1)?, 12)
+    } else {
+        return None;
+    };
+    let next = first.checked_add_months(Months::new(months))?;
+    Some((midnight(first), midnight(next)))
+}
+
+fn midnight(day: NaiveDate) -> i64 {
+    day.and_hms_opt(0, 0, 0)
+        .map_or(0, |time| time.and_utc().timestamp())
+}
+
+fn utc(seconds: i64) -> Option<NaiveDateTime> {
+    DateTime::from_timestamp(seconds, 0).map(|time| time.naive_utc())
+}
+
+/// Formats seconds since the epoch as a date, with the time of day unless it
+/// is midnight, e.g. `2020-06-30` or `2020-06-30 12:00:00`.
+pub fn format_time(seconds: i64) -> String {
+    match utc(seconds) {
+        Some(time) if time.num_seconds_from_midnight() == 0 => time.format("%Y-%m-%d").to_string(),
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => seconds.to_string(),
+    }
+}
+
+/// Range of times restricting which features are plotted, written
+/// `<from>..<to>` with either end left open, or as a single year, month or day.
+/// Ends are years, months, days or moments in the syntax of `parse_time`, and
+/// both are included: `2020-01..2020-03` runs to the end of March. Features
+/// without a time (see `feature_time`) are always plotted, so borders and
+/// other static layers stay on the map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeRange {
+    text: String,
+    start: Option<i64>, // First second included
+    end: Option<i64>,   // First second after the range
+}
+
+impl TimeRange {
+    pub fn parse(text: &str) -> Result<TimeRange, String> {
+        let text = text.trim();
+        let period = |part: &str| {
+            parse_period(part).ok_or_else(|| {
+                format!(
+                    "'{}' is not a time (use e.g. 2020, 2020-06, 2020-06-30 or 2020-06-30T12:00)",
+                    part.trim()
+                )
+            })
+        };
+        let (start, end) = match text.split_once("..") {
+            Some((from, to)) => {
+                let start = match from.trim() {
+                    "" => None,
+                    from => Some(period(from)?.0),
+                };
+                let end = match to.trim() {
+                    "" => None,
+                    to => Some(period(to)?.1),
+                };
+                (start, end)
+            }
+            None if text.is_empty() => return Err(String::from("Empty time range")),
+            None => {
+                let (start, end) = period(text)?;
+                (Some(start), Some(end))
+            }
+        };
+        if let (Some(start), Some(end)) = (start, end)
+            && end <= start
+        {
+            return Err(format!("Time range '{}' ends before it starts", text));
+        }
+        Ok(TimeRange {
+            text: text.to_string(),
+            start,
+            end,
+        })
+    }
+
+    /// Whether `feature` has no time or one inside the range.
+    pub fn accepts(&self, feature: &Feature) -> bool {
+        feature_time(feature).is_none_or(|time| {
+            self.start.is_none_or(|start| time >= start) && self.end.is_none_or(|end| time < end)
+        })
+    }
+
+    /// The range of the same length `steps` lengths later (earlier if
+    /// negative), like moving a time slider. Ranges of whole months move by
+    /// months. `None` for ranges open at an end.
+    pub fn shifted(&self, steps: i32) -> Option<TimeRange> {
+        let (start, end) = (utc(self.start?)?, utc(self.end?)?);
+        let (start, end) = match months_between(start, end) {
+            Some(months) => {
+                let shift = |time: NaiveDateTime| {
+                    let months = Months::new(months.checked_mul(steps.unsigned_abs())?);
+                    if steps < 0 {
+                        time.checked_sub_months(months)
+                    } else {
+                        time.checked_add_months(months)
+                    }
+                };
+                (shift(start)?, shift(end)?)
+            }
+            None => {
+                let length = (end - start).checked_mul(steps)?;
+                (
+                    start.checked_add_signed(length)?,
+                    end.checked_add_signed(length)?,
+                )
+            }
+        };
+        let text = range_text(start, end);
+        Some(TimeRange {
+            text,
+            start: Some(start.and_utc().timestamp()),
+            end: Some(end.and_utc().timestamp()),
+        })
+    }
+}
+
+/// Length of the frames of a time animation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeStep {
+    Year,
+    Month,
+    Day,
+}
+
+impl TimeStep {
+    pub fn parse(text: &str) -> Option<TimeStep> {
+        match text.trim().to_lowercase().as_str() {
+            "year" | "years" => Some(TimeStep::Year),
+            "month" | "months" => Some(TimeStep::Month),
+            "day" | "days" => Some(TimeStep::Day),
+            _ => None,
+        }
+    }
+
+    /// The consecutive years, months or days from the one holding `first` to
+    /// the one holding `last`, or an error if there are more than `limit`.
+    pub fn ranges(self, first: i64, last: i64, limit: usize) -> Result<Vec<TimeRange>, String> {
+        let format = match self {
+            TimeStep::Year => "%Y",
+            TimeStep::Month => "%Y-%m",
+            TimeStep::Day => "%Y-%m-%d",
+        };
+        let first = utc(first).ok_or("Time out of range")?;
+        let mut range = TimeRange::parse(&first.format(format).to_string())?;
+        let mut ranges = Vec::new();
+        while range.start.is_some_and(|start| start <= last) {
+            if ranges.len() == limit {
+                return Err(format!(
+                    "More than {} frames; use a longer time step or a shorter --time range",
+                    limit
+                ));
+            }
+            let next = range.shifted(1).ok_or("Time out of range")?;
+            ranges.push(range);
+            range = next;
+        }
+        Ok(ranges)
+    }
+}
+
+// Number of months from `start` to `end` if both are the start of a month
+fn months_between(start: NaiveDateTime, end: NaiveDateTime) -> Option<u32> {
+    let month_start =
+        |time: NaiveDateTime| time.day() == 1 && time.num_seconds_from_midnight() == 0;
+    if !month_start(start) || !month_start(end) {
+        return None;
+    }
+    let months = (end.year() - start.year()) * 12 + end.month() as i32 - start.month() as i32;
+    u32::try_from(months).ok().filter(|&months| months > 0)
+}
+
+// Text of the range from `start` up to `end`, at the coarsest precision both
+// ends have: years, months, days or seconds
+fn range_text(start: NaiveDateTime, end: NaiveDateTime) -> String {
+    let (format, last) = match months_between(start, end) {
+        Some(months) if start.month() == 1 && months % 12 == 0 => {
+            ("%Y", end.checked_sub_months(Months::new(12)))
+        }
+        Some(_) => ("%Y-%m", end.checked_sub_months(Months::new(1))),
+        None if start.num_seconds_from_midnight() == 0 && end.num_seconds_from_midnight() == 0 => {
+            ("%Y-%m-%d", end.checked_sub_signed(TimeDelta::days(1)))
+        }
+        None => (
+            "%Y-%m-%dT%H:%M:%S",
+            end.checked_sub_signed(TimeDelta::seconds(1)),
+        ),
+    };
+    let first = start.format(format).to_string();
+    match last.map(|last| last.format(format).to_string()) {
+        Some(last) if last == first => first,
+        Some(last) => format!("{}..{}", first, last),
+        None => first,
+    }
+}
+
+// Written back as typed, e.g. for sessions
+impl fmt::Display for TimeRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn feature(properties: Value) -> Feature {
+        Feature {
+            bbox: None,
+            geometry: None,
+            id: None,
+            properties: properties.as_object().cloned(),
+            foreign_members: None,
+        }
+    }
+
+    #[test]
+    fn dates_and_moments() {
+        assert_eq!(parse_time(&json!("1970-01-02")), Some(86_400));
+        assert_eq!(
+            parse_time(&json!("2020-06")),
+            parse_time(&json!("2020-06-01"))
+        );
+        assert_eq!(parse_time(&json!("1970-01-01T01:00:00Z")), Some(3600));
+        assert_eq!(parse_time(&json!("1970-01-01 01:00")), Some(3600));
+        assert_eq!(parse_time(&json!("1970-01-01T02:00:00+01:00")), Some(3600));
+        assert_eq!(parse_time(&json!("not a date")), None);
+        assert_eq!(parse_time(&json!(true)), None);
+    }
+
+    #[test]
+    fn small_whole_numbers_are_years() {
+        assert_eq!(parse_time(&json!(2020)), parse_time(&json!("2020")));
+        assert_eq!(parse_time(&json!(1970)), Some(0));
+        assert_eq!(parse_time(&json!(9999)), parse_time(&json!("9999")));
+    }
+
+    #[test]
+    fn large_numbers_are_seconds_or_milliseconds() {
+        assert_eq!(parse_time(&json!(10_000)), Some(10_000));
+        assert_eq!(parse_time(&json!(1_600_000_000)), Some(1_600_000_000));
+        assert_eq!(
+            parse_time(&json!(1_600_000_000_500_i64)),
+            Some(1_600_000_000)
+        );
+        assert_eq!(parse_time(&json!(2020.5)), Some(2020));
+    }
+
+    #[test]
+    fn feature_time_prefers_the_first_property() {
+        let dated = feature(json!({ "Date": "2021-01-01", "time": "2020-01-01" }));
+        assert_eq!(feature_time(&dated), parse_time(&json!("2020-01-01")));
+        assert_eq!(feature_time(&feature(json!({ "name": "x" }))), None);
+    }
+
+    #[test]
+    fn ranges_include_both_ends() {
+        let range = TimeRange::parse("2020-01..2020-03").unwrap();
+        assert!(range.accepts(&feature(json!({ "date": "2020-03-31" }))));
+        assert!(!range.accepts(&feature(json!({ "date": "2020-04-01" }))));
+        assert!(range.accepts(&feature(json!({ "name": "border" }))));
+        assert!(TimeRange::parse("2020..2019").is_err());
+        assert!(TimeRange::parse("").is_err());
+    }
+
+    #[test]
+    fn ranges_shift_by_their_length() {
+        let range = TimeRange::parse("2020-01..2020-03").unwrap();
+        assert_eq!(range.shifted(1).unwrap().to_string(), "2020-04..2020-06");
+        assert_eq!(
+            TimeRange::parse("2020")
+                .unwrap()
+                .shifted(-1)
+                .unwrap()
+                .to_string(),
+            "2019"
+        );
+        assert!(TimeRange::parse("2020..").unwrap().shifted(1).is_none());
+    }
+}
@@ -0,0 +1,126 @@
+// text_input.rs
+
+use crossterm::event::KeyCode;
+use unicode_width::UnicodeWidthStr;
+
+/// A single-line text field: its text and the cursor, counted in characters
+/// so typing "São Paulo" moves it one place per letter.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextInput {
+    pub text: String,
+    cursor: usize,
+}
+
+impl TextInput {
+    /// A field holding `text`, with the cursor at its end.
+    pub fn new(text: impl Into<String>) -> TextInput {
+        let mut input = TextInput::default();
+        input.set(text);
+        input
+    }
+
+    /// Replaces the text and puts the cursor at its end.
+    pub fn set(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.cursor = self.text.chars().count();
+    }
+
+    pub fn clear(&mut self) {
+        self.set(String::new());
+    }
+
+    /// Puts the cursor after the last character.
+    pub fn move_to_end(&mut self) {
+        self.cursor = self.text.chars().count();
+    }
+
+    /// Terminal column of the cursor from the start of the field, which is
+    /// two columns per wide character.
+    pub fn cursor_column(&self) -> u16 {
+        self.text[..self.byte_index()].width() as u16
+    }
+
+    /// Applies a cursor movement or edit keystroke.
+    pub fn edit(&mut self, code: KeyCode) {
+        let length = self.text.chars().count();
+        // The text may have been changed directly since the cursor last moved
+        self.cursor = self.cursor.min(length);
+        match code {
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let at = self.byte_index();
+                self.text.remove(at);
+            }
+            KeyCode::Delete if self.cursor < length => {
+                let at = self.byte_index();
+                self.text.remove(at);
+            }
+            KeyCode::Left if self.cursor > 0 => self.cursor -= 1,
+            KeyCode::Right if self.cursor < length => self.cursor += 1,
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = length,
+            KeyCode::Char(c) => {
+                let at = self.byte_index();
+                self.text.insert(at, c);
+                self.cursor += 1;
+            }
+            _ => {}
+        }
+    }
+
+    // Byte offset of the character the cursor is on
+    fn byte_index(&self) -> usize {
+        self.text
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.text.len(), |(at, _)| at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(keys: &[KeyCode]) -> TextInput {
+        let mut input = TextInput::default();
+        for &key in keys {
+            input.edit(key);
+        }
+        input
+    }
+
+    #[test]
+    fn non_ascii_text_is_edited_by_character() {
+        let keys: Vec<KeyCode> = "São Paulo".chars().map(KeyCode::Char).collect();
+        let mut input = typed(&keys);
+        assert_eq!(input.text, "São Paulo");
+        for _ in 0..7 {
+            input.edit(KeyCode::Left);
+        }
+        input.edit(KeyCode::Backspace);
+        assert_eq!(input.text, "So Paulo");
+        input.edit(KeyCode::Char('ã'));
+        input.edit(KeyCode::Delete);
+        assert_eq!(input.text, "Sã Paulo");
+        assert_eq!(input.cursor_column(), 2);
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        let input = TextInput::new("東京");
+        assert_eq!(input.cursor_column(), 4);
+    }
+
+    #[test]
+    fn the_cursor_follows_direct_changes() {
+        let mut input = TextInput::new("Zürich");
+        input.text.truncate(1);
+        input.edit(KeyCode::Backspace);
+        assert_eq!(input.text, "");
+        input.edit(KeyCode::Home);
+        input.edit(KeyCode::Char('ö'));
+        input.edit(KeyCode::End);
+        input.edit(KeyCode::Char('x'));
+        assert_eq!(input.text, "öx");
+    }
+}
@@ -0,0 +1,345 @@
+// topology.rs
+
+use geojson::{Feature, Geometry, Value};
+use std::collections::HashSet;
+
+use crate::centroid::{self, PointKind};
+use crate::clip::{self, Mask};
+use crate::dissolve::{self, Polygon};
+use crate::measure;
+use crate::plot;
+use crate::spatial::FeatureIndex;
+
+// Overlaps and gaps smaller than this (1 m², in km²) are taken for rounding
+// noise along shared borders
+const MIN_AREA_KM2: f64 = 1e-6;
+
+// Vertices of a ring are compared rounded to this (about 1 cm for degrees),
+// as when dissolving
+const SNAP: f64 = 1e-7;
+
+/// Kind of problem found by `check_topology`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TopologyIssueKind {
+    Overlap, // Area covered by two polygon features
+    Gap,     // Area enclosed by polygon features but covered by none
+}
+
+impl TopologyIssueKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            TopologyIssueKind::Overlap => "Overlap",
+            TopologyIssueKind::Gap => "Gap",
+        }
+    }
+}
+
+/// One overlap or gap, with where it is.
+#[derive(Debug, Clone)]
+pub struct TopologyIssue {
+    pub kind: TopologyIssueKind,
+    pub features: String, // The overlapping features, e.g. "a.geojson feature 3 and feature 17"
+    pub area_km2: f64,
+    pub at: [f64; 2],   // A point of the overlap or gap, lon/lat
+    pub bbox: [f64; 4], // Extent of the overlap or gap
+}
+
+/// Result of checking polygon layers for overlaps and gaps.
+#[derive(Debug, Clone, Default)]
+pub struct TopologyReport {
+    pub files: Vec<String>,
+    pub polygons: usize,            // Features with polygons checked
+    pub issues: Vec<TopologyIssue>, // Overlaps, then gaps, each largest first
+}
+
+impl TopologyReport {
+    pub fn count(&self, kind: TopologyIssueKind) -> usize {
+        self.issues.iter().filter(|i| i.kind == kind).count()
+    }
+
+    pub fn area_km2(&self, kind: TopologyIssueKind) -> f64 {
+        self.issues
+            .iter()
+            .filter(|i| i.kind == kind)
+            .map(|i| i.area_km2)
+            .sum()
+    }
+
+    /// One-line outcome, e.g. for the notification bar.
+    pub fn summary(&self) -> String {
+        let (overlaps, gaps) = (
+            self.count(TopologyIssueKind::Overlap),
+            self.count(TopologyIssueKind::Gap),
+        );
+        if overlaps + gaps == 0 {
+            return format!(
+                "{}: no overlaps or gaps in {} polygon features",
+                self.files.join(" and "),
+                self.polygons
+            );
+        }
+        format!(
+            "{}: {} overlap{} ({}), {} gap{} ({})",
+            self.files.join(" and "),
+            overlaps,
+            if overlaps == 1 { "" } else { "s" },
+            measure::format_area(self.area_km2(TopologyIssueKind::Overlap)),
+            gaps,
+            if gaps == 1 { "" } else { "s" },
+            measure::format_area(self.area_km2(TopologyIssueKind::Gap))
+        )
+    }
+}
+
+// A polygon feature of one of the checked layers
+struct Subject {
+    layer: usize,
+    position: usize, // In the features of its layer
+    polygons: Vec<Polygon>,
+}
+
+/// Checks the polygon features of `layers` (file name and features, in WGS 84)
+/// for overlaps and gaps. With one layer every pair of its features is
+/// compared; with several only features of different layers are, e.g. two
+/// neighbouring boundary datasets. Gaps are holes of the union of all layers
+/// that no feature has as a hole of its own, so only gaps enclosed on all
+/// sides are found. Points and lines are ignored.
+pub fn check_topology(layers: &[(String, Vec<Feature>)]) -> TopologyReport {
+    let mut subjects = Vec::new();
+    for (layer, (_, features)) in layers.iter().enumerate() {
+        for (position, feature) in features.iter().enumerate() {
+            let mut polygons = Vec::new();
+            if let Some(geometry) = &feature.geometry {
+                dissolve::collect_polygons(&geometry.value, &mut polygons);
+            }
+            if !polygons.is_empty() {
+                subjects.push(Subject {
+                    layer,
+                    position,
+                    polygons,
+                });
+            }
+        }
+    }
+    let shapes: Vec<Feature> = subjects
+        .iter()
+        .map(|subject| feature(Value::MultiPolygon(subject.polygons.clone())))
+        .collect();
+    let index = FeatureIndex::new(&shapes);
+    let name = |subject: &Subject| {
+        if layers.len() == 1 {
+            format!("feature {}", subject.position)
+        } else {
+            format!("{} feature {}", layers[subject.layer].0, subject.position)
+        }
+    };
+
+    let mut issues = Vec::new();
+    for (i, subject) in subjects.iter().enumerate() {
+        let rings: Vec<Vec<Vec<f64>>> = subject.polygons.iter().flatten().cloned().collect();
+        let bbox = bbox_of(&shapes[i]);
+        let mask = Mask::new(&rings, bbox);
+        for j in index.in_bbox(bbox) {
+            if j <= i || (layers.len() > 1 && subjects[j].layer == subject.layer) {
+                continue;
+            }
+            for piece in clip::clip_features(std::slice::from_ref(&shapes[j]), &mask) {
+                if let Some(mut issue) = issue(TopologyIssueKind::Overlap, piece) {
+                    issue.features = format!("{} and {}", name(subject), name(&subjects[j]));
+                    issues.push(issue);
+                }
+            }
+        }
+    }
+
+    // Holes of the features themselves, which are not gaps of the layer
+    let holes: HashSet<Vec<(i64, i64)>> = subjects
+        .iter()
+        .flat_map(|subject| &subject.polygons)
+        .flat_map(|polygon| polygon.iter().skip(1))
+        .map(|ring| ring_key(ring))
+        .collect();
+    let all: Vec<Polygon> = subjects
+        .iter()
+        .flat_map(|subject| subject.polygons.iter().cloned())
+        .collect();
+    for polygon in dissolve::dissolve_polygons(&all) {
+        for hole in polygon.into_iter().skip(1) {
+            if holes.contains(&ring_key(&hole)) {
+                continue;
+            }
+            let Some(gap) = issue(TopologyIssueKind::Gap, feature(Value::Polygon(vec![hole])))
+            else {
+                continue;
+            };
+            // Polygons that only overlap are not merged, so a hole of one may be
+            // covered by another
+            let covered = index.near(gap.at[0], gap.at[1], 0.0).into_iter().any(|j| {
+                subjects[j].polygons.iter().any(|polygon| {
+                    let rings: Vec<Vec<clip::Point>> = polygon
+                        .iter()
+                        .map(|ring| {
+                            ring.iter()
+                                .filter(|p| p.len() >= 2)
+                                .map(|p| [p[0], p[1]])
+                                .collect()
+                        })
+                        .collect();
+                    clip::contains(&rings, gap.at)
+                })
+            });
+            if !covered {
+                issues.push(gap);
+            }
+        }
+    }
+
+    issues.sort_by(|a, b| {
+        a.kind
+            .cmp(&b.kind)
+            .then_with(|| b.area_km2.total_cmp(&a.area_km2))
+    });
+    TopologyReport {
+        files: layers.iter().map(|(name, _)| name.clone()).collect(),
+        polygons: subjects.len(),
+        issues,
+    }
+}
+
+fn feature(value: Value) -> Feature {
+    Feature {
+        bbox: None,
+        geometry: Some(Geometry::new(value)),
+        id: None,
+        properties: None,
+        foreign_members: None,
+    }
+}
+
+fn bbox_of(feature: &Feature) -> [f64; 4] {
+    let mut bbox = plot::empty_bbox();
+    if let Some(geometry) = &feature.geometry {
+        plot::extend_bbox(&mut bbox, &geometry.value);
+    }
+    bbox
+}
+
+// The overlap or gap covered by the polygons of `piece`, unless too small to matter
+fn issue(kind: TopologyIssueKind, piece: Feature) -> Option<TopologyIssue> {
+    let area_km2 = measure::geometry_area_km2(&piece.geometry.as_ref()?.value);
+    if area_km2 < MIN_AREA_KM2 {
+        return None;
+    }
+    // The pole of inaccessibility lies inside even a thin sliver
+    let point = centroid::representative_points(std::slice::from_ref(&piece), PointKind::Pole);
+    let at = match &point.first()?.geometry.as_ref()?.value {
+        Value::Point(p) if p.len() >= 2 => [p[0], p[1]],
+        _ => return None,
+    };
+    Some(TopologyIssue {
+        kind,
+        features: String::new(),
+        area_km2,
+        at,
+        bbox: bbox_of(&piece),
+    })
+}
+
+// The snapped vertices of `ring` in a canonical order, so a ring matches
+// itself whatever vertex it starts at and whichever way it runs
+fn ring_key(ring: &[Vec<f64>]) -> Vec<(i64, i64)> {
+    let mut vertices: Vec<(i64, i64)> = ring
+        .iter()
+        .filter(|p| p.len() >= 2)
+        .map(|p| ((p[0] / SNAP).round() as i64, (p[1] / SNAP).round() as i64))
+        .collect();
+    vertices.sort_unstable();
+    vertices.dedup();
+    vertices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The ring of the square from (x, y) to (x + size, y + size)
+    fn square(x: f64, y: f64, size: f64) -> Vec<Vec<f64>> {
+        vec![
+            vec![x, y],
+            vec![x + size, y],
+            vec![x + size, y + size],
+            vec![x, y + size],
+            vec![x, y],
+        ]
+    }
+
+    fn layer(name: &str, polygons: Vec<Vec<Vec<Vec<f64>>>>) -> (String, Vec<Feature>) {
+        let features = polygons
+            .into_iter()
+            .map(|rings| feature(Value::Polygon(rings)))
+            .collect();
+        (name.to_string(), features)
+    }
+
+    #[test]
+    fn overlapping_features_are_found() {
+        let layers = [layer(
+            "a.geojson",
+            vec![
+                vec![square(0.0, 0.0, 2.0)],
+                vec![square(1.0, 1.0, 2.0)],
+                vec![square(1.5, -0.5, 1.0)],
+                // Sharing an edge is not overlapping
+                vec![square(3.0, 1.0, 1.0)],
+            ],
+        )];
+        let report = check_topology(&layers);
+        assert_eq!(report.polygons, 4);
+        assert_eq!(report.count(TopologyIssueKind::Overlap), 2);
+        let largest = &report.issues[0];
+        assert_eq!(largest.features, "feature 0 and feature 1");
+        assert!(
+            (largest.area_km2 / 12_364.0 - 1.0).abs() < 0.01,
+            "{}",
+            largest.area_km2
+        );
+        assert!(largest.bbox[0] >= 1.0 - 1e-9 && largest.bbox[2] <= 2.0 + 1e-9);
+        assert!(report.summary().starts_with("a.geojson: 2 overlaps"));
+    }
+
+    #[test]
+    fn enclosed_gaps_are_found_but_holes_of_a_feature_are_not() {
+        // Eight squares around an empty one in the middle
+        let pieces = (0..9)
+            .filter(|&i| i != 4)
+            .map(|i| vec![square((i % 3) as f64, (i / 3) as f64, 1.0)])
+            .collect();
+        let report = check_topology(&[layer("tiles.geojson", pieces)]);
+        assert_eq!(report.count(TopologyIssueKind::Overlap), 0);
+        assert_eq!(report.count(TopologyIssueKind::Gap), 1);
+        let gap = report.issues[0].at;
+        assert!((1.0..2.0).contains(&gap[0]) && (1.0..2.0).contains(&gap[1]));
+
+        let mut hole = square(1.0, 1.0, 1.0);
+        hole.reverse();
+        let donut = layer("donut.geojson", vec![vec![square(0.0, 0.0, 3.0), hole]]);
+        assert!(check_topology(&[donut]).issues.is_empty());
+    }
+
+    #[test]
+    fn layers_are_only_compared_with_each_other() {
+        let layers = [
+            layer(
+                "a.geojson",
+                vec![vec![square(0.0, 0.0, 2.0)], vec![square(1.0, 0.0, 2.0)]],
+            ),
+            layer("b.geojson", vec![vec![square(5.0, 5.0, 1.0)]]),
+        ];
+        let report = check_topology(&layers);
+        assert!(report.issues.is_empty());
+        assert_eq!(
+            report.summary(),
+            "a.geojson and b.geojson: no overlaps or gaps in 3 polygon features"
+        );
+    }
+}
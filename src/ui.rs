@@ -2,15 +2,40 @@
 
 use ratatui::{
     Frame,
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
+    symbols::Marker,
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph, Wrap},
+    widgets::{
+        Block, Borders, Clear, Gauge, Paragraph, Wrap,
+        canvas::{Canvas, Context, Line as CanvasLine, Points},
+    },
 };
 
-use crate::app::{App, AppMode, CurrentScreen, GeoJsonInfo};
+use crossterm::event::KeyCode;
+use geojson::{Feature, Value};
+use plotters::prelude::RGBColor;
+
+use crate::app::{
+    App, AppMode, CurrentScreen, GeoJsonInfo, InfoTab, LayerProperty, LogEntry, LogLevel, PanelTab,
+    TitleField,
+};
+use crate::cluster;
+use crate::crs;
+use crate::inset::Inset;
+use crate::json_preview::{self, JsonToken};
+use crate::keymap::Action;
+use crate::measure;
+use crate::plot::{self, FigureBackground, GeometryTypes};
+use crate::regions::Region;
+use crate::search::{self, SearchMode};
+use crate::style::{self, Styler};
+use crate::table;
+use crate::temporal::{self, TimeRange};
+use crate::topology::TopologyIssueKind;
 
 pub fn render(frame: &mut Frame, app: &mut App) {
+    app.regions.clear();
     let main_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Min(0), Constraint::Length(3)]) // Main content, then footer
@@ -20,10 +45,435 @@ pub fn render(frame: &mut Frame, app: &mut App) {
         CurrentScreen::Main => render_main_screen(frame, app, main_layout[0]),
         CurrentScreen::Help => render_help_screen(frame, app, main_layout[0]),
         CurrentScreen::GeoJsonMapper => render_geojson_mapper_ui(frame, app, main_layout[0]), // GeoJSON Mapper is now the main screen
+        CurrentScreen::Styling => render_styling_screen(frame, app, main_layout[0]),
+        CurrentScreen::Validation => render_validation_screen(frame, app, main_layout[0]),
+        CurrentScreen::Duplicates => render_duplicates_screen(frame, app, main_layout[0]),
+        CurrentScreen::Topology => render_topology_screen(frame, app, main_layout[0]),
+        CurrentScreen::PlotSummary => render_plot_summary_screen(frame, app, main_layout[0]),
+        CurrentScreen::Logs => render_logs_screen(frame, app, main_layout[0]),
+        CurrentScreen::AttributeTable => render_attribute_table_screen(frame, app, main_layout[0]),
     }
 
     // Render the footer, common across all screens
     render_footer(frame, app, main_layout[1]);
+
+    // Popups are drawn last so they overlay the current screen
+    if matches!(
+        app.current_mode,
+        AppMode::Sessions | AppMode::EditingSessionName
+    ) {
+        render_sessions_popup(frame, app, frame.size());
+    }
+    if matches!(app.current_mode, AppMode::Wfs | AppMode::EditingWfsUrl) {
+        render_wfs_popup(frame, app, frame.size());
+    }
+    if app.current_mode == AppMode::LayerProperties {
+        render_layer_properties_popup(frame, app, frame.size());
+    }
+    if app.current_mode == AppMode::Identify {
+        render_identify_popup(frame, app, frame.size());
+    }
+    if app.current_mode == AppMode::SelectingFeatures {
+        render_feature_selection_popup(frame, app, frame.size());
+    }
+}
+
+// Draws `buttons` as a line of labels at the top of `area` and registers each
+// label as acting like its key when clicked
+fn render_buttons(frame: &mut Frame, app: &mut App, area: Rect, buttons: &[(KeyCode, &str)]) {
+    let mut spans = Vec::new();
+    let mut x = area.x;
+    for (i, (key, label)) in buttons.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" | "));
+            x += 3;
+        }
+        let width = (label.chars().count() as u16).min(area.right().saturating_sub(x));
+        app.regions
+            .add(Rect::new(x, area.y, width, 1), Region::Key(*key));
+        spans.push(Span::raw(label.to_string()));
+        x += width;
+    }
+    frame.render_widget(
+        Paragraph::new(Line::from(spans)).style(Style::default().fg(Color::Gray)),
+        area,
+    );
+}
+
+/// Returns a rectangle of the given percentage size centered in `area`.
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+/// Renders the Sessions popup listing saved sessions.
+fn render_sessions_popup(frame: &mut Frame, app: &mut App, area: Rect) {
+    let popup_area = centered_rect(50, 50, area);
+    frame.render_widget(Clear, popup_area);
+    app.regions.add(popup_area, Region::Popup);
+
+    let block = Block::default()
+        .title(" Sessions ")
+        .title_style(Style::default().fg(Color::LightYellow).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightYellow));
+    let inner_area = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let popup_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),    // Session list
+            Constraint::Length(1), // Name input
+            Constraint::Length(1), // Keybind hint
+        ])
+        .split(inner_area);
+
+    let mut session_lines: Vec<Line> = Vec::new();
+    if app.session_names.is_empty() {
+        session_lines.push(Line::from("No saved sessions.").fg(Color::Gray));
+    }
+    for (i, name) in app.session_names.iter().enumerate() {
+        let mut style = Style::default().fg(Color::White);
+        if i == app.selected_session_index {
+            style = style.bg(Color::DarkGray).add_modifier(Modifier::BOLD);
+        }
+        session_lines.push(Line::from(Span::styled(name.clone(), style)));
+    }
+    frame.render_widget(Paragraph::new(session_lines), popup_chunks[0]);
+
+    if app.current_mode == AppMode::EditingSessionName {
+        let name_layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Length(6), // "Name:" label
+                Constraint::Min(0),    // Input field
+            ])
+            .split(popup_chunks[1]);
+        frame.render_widget(
+            Paragraph::new("Name:").style(Style::default().fg(Color::LightCyan)),
+            name_layout[0],
+        );
+        frame.render_widget(
+            Paragraph::new(app.session_name_input.text.clone())
+                .style(Style::default().fg(Color::White).bg(Color::Blue)),
+            name_layout[1],
+        );
+        frame.set_cursor(
+            name_layout[1].x + app.session_name_input.cursor_column(),
+            name_layout[1].y,
+        );
+    }
+
+    let hint = Paragraph::new(
+        "Enter: Restore | P: Restore and plot | N: Save current | D: Delete | Esc: Close",
+    )
+    .style(Style::default().fg(Color::Gray));
+    frame.render_widget(hint, popup_chunks[2]);
+}
+
+/// Renders the WFS popup: the service URL and the feature types it offers.
+fn render_wfs_popup(frame: &mut Frame, app: &mut App, area: Rect) {
+    let popup_area = centered_rect(60, 50, area);
+    frame.render_widget(Clear, popup_area);
+    app.regions.add(popup_area, Region::Popup);
+
+    let block = Block::default()
+        .title(" WFS ")
+        .title_style(Style::default().fg(Color::LightYellow).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightYellow));
+    let inner_area = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let popup_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // Service URL
+            Constraint::Min(0),    // Feature types
+            Constraint::Length(1), // Keybind hint
+        ])
+        .split(inner_area);
+
+    let url_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Length(9), // "Service:" label
+            Constraint::Min(0),    // URL or input field
+        ])
+        .split(popup_chunks[0]);
+    frame.render_widget(
+        Paragraph::new("Service:").style(Style::default().fg(Color::LightCyan)),
+        url_layout[0],
+    );
+    if app.current_mode == AppMode::EditingWfsUrl {
+        frame.render_widget(
+            Paragraph::new(app.wfs_url_input.text.clone())
+                .style(Style::default().fg(Color::White).bg(Color::Blue)),
+            url_layout[1],
+        );
+        frame.set_cursor(
+            url_layout[1].x + app.wfs_url_input.cursor_column(),
+            url_layout[1].y,
+        );
+    } else {
+        let service = app
+            .wfs_service
+            .as_ref()
+            .map_or("(none)", |(url, _)| url.as_str());
+        frame.render_widget(Paragraph::new(service.to_string()), url_layout[1]);
+    }
+
+    let mut type_lines: Vec<Line> = Vec::new();
+    if let Some(request) = &app.wfs_request {
+        type_lines.push(Line::from(request.status()).fg(Color::Gray));
+    } else if let Some((_, capabilities)) = &app.wfs_service {
+        // The list scrolls to keep the highlighted type in view
+        let height = popup_chunks[1].height as usize;
+        let first = (app.selected_wfs_type + 1).saturating_sub(height);
+        for (i, feature_type) in capabilities
+            .feature_types
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(height)
+        {
+            let mut style = Style::default().fg(Color::White);
+            if i == app.selected_wfs_type {
+                style = style.bg(Color::DarkGray).add_modifier(Modifier::BOLD);
+            }
+            let mut spans = vec![Span::styled(feature_type.name.clone(), style)];
+            if !feature_type.title.is_empty() {
+                spans.push(Span::raw(format!("  {}", feature_type.title)).fg(Color::Gray));
+            }
+            type_lines.push(Line::from(spans));
+        }
+    } else {
+        type_lines.push(Line::from("Type the URL of a WFS service.").fg(Color::Gray));
+    }
+    frame.render_widget(Paragraph::new(type_lines), popup_chunks[1]);
+
+    let hint = Paragraph::new(
+        "Enter: Fetch in preview extent | A: Fetch all | U: Service URL | Esc: Close",
+    )
+    .style(Style::default().fg(Color::Gray));
+    frame.render_widget(hint, popup_chunks[2]);
+}
+
+/// Renders the features found under the preview crosshair with their properties.
+fn render_identify_popup(frame: &mut Frame, app: &mut App, area: Rect) {
+    let popup_area = centered_rect(60, 60, area);
+    frame.render_widget(Clear, popup_area);
+    app.regions.add(popup_area, Region::Popup);
+
+    let title = match app.preview_cursor_position() {
+        Some((lon, lat)) => format!(" Identify {:.5}, {:.5} ", lat, lon),
+        None => String::from(" Identify "),
+    };
+    let block = Block::default()
+        .title(title)
+        .title_style(Style::default().fg(Color::LightYellow).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightYellow));
+    let inner_area = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let popup_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),    // Features
+            Constraint::Length(1), // Keybind hint
+        ])
+        .split(inner_area);
+
+    let mut lines: Vec<Line> = Vec::new();
+    for (file, feature) in &app.identified_features {
+        let geometry_type = feature
+            .geometry
+            .as_ref()
+            .map_or("no geometry", |g| g.value.type_name());
+        lines.push(Line::from(vec![
+            Span::styled(file.clone(), Style::default().fg(Color::LightCyan).bold()),
+            Span::raw(format!("  {}", geometry_type)).fg(Color::Gray),
+        ]));
+        for (key, value) in feature.properties.iter().flatten() {
+            let value = match value {
+                serde_json::Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {}: ", key), Style::default().fg(Color::Yellow)),
+                Span::raw(value),
+            ]));
+        }
+        lines.push(Line::from(""));
+    }
+    frame.render_widget(
+        Paragraph::new(lines).scroll((app.identify_scroll.min(u16::MAX as usize) as u16, 0)),
+        popup_chunks[0],
+    );
+
+    let hint = Paragraph::new("↑/↓/PgUp/PgDn: Scroll | Esc: Back to the preview")
+        .style(Style::default().fg(Color::Gray));
+    frame.render_widget(hint, popup_chunks[1]);
+}
+
+/// Renders the features of the highlighted file with a checkbox each for
+/// choosing the ones plotted.
+fn render_feature_selection_popup(frame: &mut Frame, app: &mut App, area: Rect) {
+    let Some(idx) = app.highlighted_file_index() else {
+        return;
+    };
+    let popup_area = centered_rect(60, 60, area);
+    frame.render_widget(Clear, popup_area);
+    app.regions.add(popup_area, Region::Popup);
+
+    let features = app.layers[idx].features.as_deref().unwrap_or_default();
+    let checked = app.layers[idx].feature_selection.as_ref();
+    let checked_count = checked.map_or(features.len(), |c| c.range(..features.len()).count());
+    let block = Block::default()
+        .title(format!(
+            " Features of {} ({}/{} checked) ",
+            app.layers[idx].file,
+            checked_count,
+            features.len()
+        ))
+        .title_style(Style::default().fg(Color::LightYellow).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightYellow));
+    let inner_area = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let popup_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),    // Features
+            Constraint::Length(1), // Keybind hint
+        ])
+        .split(inner_area);
+
+    // The list scrolls to keep the highlighted feature in view
+    let height = popup_chunks[0].height as usize;
+    let first = (app.selected_feature + 1).saturating_sub(height);
+    let lines: Vec<Line> = features
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(height)
+        .map(|(i, feature)| {
+            let mark = if checked.is_none_or(|c| c.contains(&i)) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            let mut style = Style::default().fg(Color::White);
+            if i == app.selected_feature {
+                style = style.bg(Color::DarkGray).add_modifier(Modifier::BOLD);
+            }
+            Line::from(vec![
+                Span::styled(format!("{} {:>5}  ", mark, i + 1), style),
+                Span::styled(feature_name(feature), style.fg(Color::LightCyan)),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), popup_chunks[0]);
+
+    let hint = Paragraph::new("Space: Check/uncheck | A: All | D: None | I: Invert | Esc: Done")
+        .style(Style::default().fg(Color::Gray));
+    frame.render_widget(hint, popup_chunks[1]);
+}
+
+// A property telling features apart: the first one with "name" in its key,
+// otherwise the first text property
+fn feature_name(feature: &Feature) -> String {
+    let Some(properties) = &feature.properties else {
+        return String::new();
+    };
+    properties
+        .iter()
+        .find(|(key, value)| key.to_lowercase().contains("name") && !value.is_null())
+        .or_else(|| properties.iter().find(|(_, value)| value.is_string()))
+        .map(|(_, value)| match value {
+            serde_json::Value::String(text) => text.clone(),
+            other => other.to_string(),
+        })
+        .unwrap_or_default()
+}
+
+/// Renders the layer properties popup of the highlighted file.
+fn render_layer_properties_popup(frame: &mut Frame, app: &mut App, area: Rect) {
+    let Some(index) = app.highlighted_file_index() else {
+        return;
+    };
+    let popup_area = centered_rect(50, 40, area);
+    frame.render_widget(Clear, popup_area);
+    app.regions.add(popup_area, Region::Popup);
+
+    let block = Block::default()
+        .title(format!(" Layer: {} ", app.layers[index].file))
+        .title_style(Style::default().fg(Color::LightYellow).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightYellow));
+
+    let mut lines = layer_property_lines(app, index, Some(app.layer_property));
+    lines.push(Line::from(""));
+    lines.push(
+        Line::from("Up/Down: Select | Left/Right/Space: Change | 0: Default | Esc: Close")
+            .fg(Color::Gray),
+    );
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        popup_area,
+    );
+}
+
+// Color, sizes and drawn geometry types of a layer, the `selected` property highlighted
+fn layer_property_lines(
+    app: &App,
+    index: usize,
+    selected: Option<LayerProperty>,
+) -> Vec<Line<'static>> {
+    let mut lines = vec![match app.layers[index].color {
+        Some(c) => Line::from(vec![
+            Span::raw("Color: "),
+            Span::styled("■", Style::default().fg(Color::Rgb(c.0, c.1, c.2))),
+            Span::raw(format!(" R{} G{} B{}", c.0, c.1, c.2)),
+        ]),
+        None => Line::from("Color: not selected").fg(Color::Gray),
+    }];
+    for property in LayerProperty::ALL {
+        let mut style = Style::default().fg(Color::White);
+        if Some(property) == selected {
+            style = style.bg(Color::DarkGray).add_modifier(Modifier::BOLD);
+        }
+        let value = app.layer_property_value(index, property);
+        lines.push(Line::from(Span::styled(
+            format!(
+                "{}: {}{}",
+                property.label(),
+                property.format(value.unwrap_or(property.default_value())),
+                if value.is_some() { "" } else { " (default)" }
+            ),
+            style,
+        )));
+    }
+    lines
 }
 
 /// Renders the main application screen
@@ -34,13 +484,13 @@ fn render_main_screen(frame: &mut Frame, _app: &mut App, area: ratatui::layout::
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::Magenta));
 
-    let content = Paragraph::new(format!(
+    let content = Paragraph::new(
         "Welcome to the GeoJSON Mapper TUI!\n\n\
         This is the main application screen.\n\n\
         The GeoJSON Mapper UI is now the default view.\n\
         Press 'q' to quit (or return to this screen from Help).\n\
-        Press 'h' for help."
-    ))
+        Press 'h' for help.",
+    )
     .block(block)
     .wrap(Wrap { trim: false })
     .style(Style::default().fg(Color::White));
@@ -49,43 +499,936 @@ fn render_main_screen(frame: &mut Frame, _app: &mut App, area: ratatui::layout::
 }
 
 /// Renders the help screen.
-fn render_help_screen(frame: &mut Frame, _app: &mut App, area: ratatui::layout::Rect) {
+fn render_help_screen(frame: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
     let block = Block::default()
         .title(" Help Screen ")
         .title_style(Style::default().fg(Color::Yellow).bold())
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::Blue));
 
-    let help_text = Paragraph::new(
-        "Keybinds:\n\
-          J/K or ↑/↓: Navigate file list\n\
-          Space: Toggle file selection\n\
-          Enter: Plot selected files\n\
-          C: Cycle next assignment color\n\
-          R: Rename output plot\n\
-          /: Start fuzzy search\n\
-          P: Toggle Points visibility\n\
-          L: Toggle Lines visibility\n\
-          O: Toggle Polygons visibility\n\
-          Q: Quit the application\n\
-          H: Show this Help screen\n\n\
-          Click & Drag Divider: Resize panels in GeoJSON Mapper UI.",
-    )
-    .block(block)
-    .wrap(Wrap { trim: false })
-    .style(Style::default().fg(Color::LightGreen));
+    let mut help_lines = vec![Line::from("Keybinds:")];
+    help_lines.extend(app.help_keybinds.iter().map(|s| Line::from(s.clone())));
+    help_lines.push(Line::from(""));
+    help_lines.push(Line::from(
+        "Sessions popup: N: Save current, Enter: Restore, P: Restore and plot, D: Delete, Esc: Close",
+    ));
+    help_lines.push(Line::from(
+        "Key bindings can be changed in the [keys] section of config.toml.",
+    ));
+
+    let help_text = Paragraph::new(help_lines)
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .style(Style::default().fg(Color::LightGreen));
 
     frame.render_widget(help_text, area);
 }
 
+/// Renders the Styling screen listing the attribute-based rules.
+fn render_styling_screen(frame: &mut Frame, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .title(" Styling Rules ")
+        .title_style(Style::default().fg(Color::LightYellow).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightYellow));
+    let inner_area = block.inner(area);
+    frame.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),  // Notification
+            Constraint::Min(0),     // Rule list
+            Constraint::Length(1),  // Rule input
+            Constraint::Length(11), // Syntax help
+        ])
+        .split(inner_area);
+
+    frame.render_widget(
+        Paragraph::new(app.notification.clone())
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::White).bg(Color::DarkGray)),
+        chunks[0],
+    );
+
+    let mut rule_lines: Vec<Line> = Vec::new();
+    if app.style_rules.is_empty() {
+        rule_lines.push(Line::from("No rules. Press A to add one.").fg(Color::Gray));
+    }
+    for (i, rule) in app.style_rules.iter().enumerate() {
+        let mut style = Style::default().fg(Color::White);
+        if i == app.selected_rule_index {
+            style = style.bg(Color::DarkGray).add_modifier(Modifier::BOLD);
+        }
+        rule_lines.push(Line::from(Span::styled(
+            format!("{}. {}", i + 1, rule),
+            style,
+        )));
+    }
+    frame.render_widget(Paragraph::new(rule_lines), chunks[1]);
+
+    if app.current_mode == AppMode::EditingRule {
+        let input_layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Length(6), // "Rule:" label
+                Constraint::Min(0),    // Input field
+            ])
+            .split(chunks[2]);
+        frame.render_widget(
+            Paragraph::new("Rule:").style(Style::default().fg(Color::LightCyan)),
+            input_layout[0],
+        );
+        frame.render_widget(
+            Paragraph::new(app.rule_input.text.clone())
+                .style(Style::default().fg(Color::White).bg(Color::Blue)),
+            input_layout[1],
+        );
+        frame.set_cursor(
+            input_layout[1].x + app.rule_input.cursor_column(),
+            input_layout[1].y,
+        );
+    }
+
+    let syntax_lines = vec![
+        Line::from("A: Add rule | D: Delete rule | J/K: Move | Esc: Back"),
+        Line::from(
+            "where <property> <op> <value>   Only draw matching features (==, !=, <, <=, >, >=, ~)",
+        ),
+        Line::from("color <property> <#from> <#to>  Color by a numeric property along a gradient"),
+        Line::from(
+            "size <property> <min> <max>     Scale point radius and line width by a property",
+        ),
+        Line::from(
+            "proportional <property> [max_radius]  Draw points as circles with area by a property",
+        ),
+        Line::from(
+            "choropleth <property> [ramp] [equal|quantile|log|equalize] [classes]  Fill by class",
+        ),
+        Line::from(
+            "  ramps: ylorrd, blues, greens, greys, viridis, spectral, terrain, bwr, rdbu, brbg, piyg or #rrggbb-#rrggbb; mid=<value> nodata=<#rrggbb|none>",
+        ),
+        Line::from(
+            "label <template>                Label features, e.g. label {NAME} ({POP_EST:,})",
+        ),
+        Line::from("elevation [ramp]                Color segments and points by their altitude"),
+        Line::from(
+            "e.g.  where featurecla == River   color pop_est #ffffcc #800026   size scalerank 1 6",
+        ),
+    ];
+    frame.render_widget(
+        Paragraph::new(syntax_lines)
+            .block(Block::default().borders(Borders::TOP))
+            .style(Style::default().fg(Color::Gray)),
+        chunks[3],
+    );
+}
+
+/// Renders the Validation screen with the report of the last validated file.
+fn render_validation_screen(frame: &mut Frame, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .title(" Validation Report ")
+        .title_style(Style::default().fg(Color::LightYellow).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightYellow));
+    let inner_area = block.inner(area);
+    frame.render_widget(block, area);
+    let Some(report) = &app.validation_report else {
+        return;
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),                              // Notification
+            Constraint::Length(report.counts.len() as u16 + 3), // Summary
+            Constraint::Min(0),                                 // Issue list
+            Constraint::Length(2),                              // Key help
+        ])
+        .split(inner_area);
+
+    frame.render_widget(
+        Paragraph::new(report.summary())
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::White).bg(Color::DarkGray)),
+        chunks[0],
+    );
+
+    let mut summary_lines = vec![Line::from(format!(
+        "{} features, {} positions checked",
+        report.features, report.positions
+    ))];
+    if let Some(crs) = &report.crs {
+        summary_lines.push(
+            Line::from(format!(
+                "Coordinate ranges not checked: file declares {}",
+                crs
+            ))
+            .fg(Color::Gray),
+        );
+    }
+    for (kind, count) in &report.counts {
+        let color = if kind.is_error() {
+            Color::LightRed
+        } else {
+            Color::Yellow
+        };
+        summary_lines.push(Line::from(vec![
+            Span::styled(format!("{:>8}  ", count), Style::default().fg(color)),
+            Span::raw(kind.label()),
+        ]));
+    }
+    if report.error.is_none() && report.counts.is_empty() {
+        summary_lines.push(Line::from("No problems found.").fg(Color::LightGreen));
+    }
+    frame.render_widget(Paragraph::new(summary_lines), chunks[1]);
+
+    let visible_rows = chunks[2].height.saturating_sub(1) as usize; // Minus the border
+    let mut issue_lines: Vec<Line> = report
+        .issues
+        .iter()
+        .skip(app.validation_scroll)
+        .take(visible_rows)
+        .map(|issue| {
+            let color = if issue.kind.is_error() {
+                Color::LightRed
+            } else {
+                Color::Yellow
+            };
+            Line::from(vec![
+                Span::styled(
+                    format!("{:<18} ", issue.kind.label()),
+                    Style::default().fg(color),
+                ),
+                Span::styled(
+                    format!("{}: ", issue.location),
+                    Style::default().fg(Color::LightCyan),
+                ),
+                Span::raw(issue.message.clone()),
+            ])
+        })
+        .collect();
+    let hidden = report.total() - report.issues.len();
+    if hidden > 0 && app.validation_scroll + visible_rows >= report.issues.len() {
+        issue_lines.push(Line::from(format!("... and {} more", hidden)).fg(Color::Gray));
+    }
+    frame.render_widget(
+        Paragraph::new(issue_lines).block(Block::default().borders(Borders::TOP).title(format!(
+            " Issues {}-{} of {} ",
+            (app.validation_scroll + 1).min(report.issues.len()),
+            (app.validation_scroll + visible_rows).min(report.issues.len()),
+            report.total()
+        ))),
+        chunks[2],
+    );
+
+    frame.render_widget(
+        Paragraph::new("J/K or Up/Down: Scroll | PageUp/PageDown/Home/End | Esc: Back")
+            .block(Block::default().borders(Borders::TOP))
+            .style(Style::default().fg(Color::Gray)),
+        chunks[3],
+    );
+}
+
+/// Renders the Duplicates screen with the duplicated geometries of the last checked file.
+fn render_duplicates_screen(frame: &mut Frame, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .title(" Duplicate Features ")
+        .title_style(Style::default().fg(Color::LightYellow).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightYellow));
+    let inner_area = block.inner(area);
+    frame.render_widget(block, area);
+    let Some(report) = &app.duplicate_report else {
+        return;
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // Notification
+            Constraint::Length(4), // Summary
+            Constraint::Min(0),    // Duplicate list
+            Constraint::Length(2), // Key help
+        ])
+        .split(inner_area);
+
+    frame.render_widget(
+        Paragraph::new(app.notification.clone())
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::White).bg(Color::DarkGray)),
+        chunks[0],
+    );
+
+    let exact = report.exact_count();
+    let mut summary_lines = vec![Line::from(if report.tolerance > 0.0 {
+        format!(
+            "{} features checked, positions within {} count as the same",
+            report.features,
+            report.tolerance_label()
+        )
+    } else {
+        format!(
+            "{} features checked for identical geometries",
+            report.features
+        )
+    })];
+    if report.duplicates.is_empty() {
+        summary_lines.push(Line::from("No duplicates found.").fg(Color::LightGreen));
+    } else {
+        summary_lines.push(Line::from(vec![
+            Span::styled(
+                format!("{:>8}  ", exact),
+                Style::default().fg(Color::LightRed),
+            ),
+            Span::raw("Exact duplicates"),
+        ]));
+        summary_lines.push(Line::from(vec![
+            Span::styled(
+                format!("{:>8}  ", report.duplicates.len() - exact),
+                Style::default().fg(Color::Yellow),
+            ),
+            Span::raw("Near duplicates"),
+        ]));
+    }
+    frame.render_widget(Paragraph::new(summary_lines), chunks[1]);
+
+    let visible_rows = chunks[2].height.saturating_sub(1) as usize; // Minus the border
+    let duplicate_lines: Vec<Line> = report
+        .duplicates
+        .iter()
+        .skip(app.duplicate_scroll)
+        .take(visible_rows)
+        .map(|duplicate| {
+            let (label, color) = if duplicate.exact {
+                ("Exact", Color::LightRed)
+            } else {
+                ("Near", Color::Yellow)
+            };
+            Line::from(vec![
+                Span::styled(format!("{:<6} ", label), Style::default().fg(color)),
+                Span::styled(
+                    format!("feature {}: ", duplicate.position),
+                    Style::default().fg(Color::LightCyan),
+                ),
+                Span::raw(format!("same geometry as feature {}", duplicate.original)),
+            ])
+        })
+        .collect();
+    frame.render_widget(
+        Paragraph::new(duplicate_lines).block(Block::default().borders(Borders::TOP).title(
+            format!(
+                " Duplicates {}-{} of {} ",
+                (app.duplicate_scroll + 1).min(report.duplicates.len()),
+                (app.duplicate_scroll + visible_rows).min(report.duplicates.len()),
+                report.duplicates.len()
+            ),
+        )),
+        chunks[2],
+    );
+
+    frame.render_widget(
+        Paragraph::new(
+            "J/K or Up/Down: Scroll | PageUp/PageDown/Home/End | W: Write a copy without duplicates | Esc: Back",
+        )
+        .block(Block::default().borders(Borders::TOP))
+        .style(Style::default().fg(Color::Gray)),
+        chunks[3],
+    );
+}
+
+/// Renders the Topology screen with the overlaps and gaps of the last checked layers.
+fn render_topology_screen(frame: &mut Frame, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .title(" Topology Check ")
+        .title_style(Style::default().fg(Color::LightYellow).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightYellow));
+    let inner_area = block.inner(area);
+    frame.render_widget(block, area);
+    let Some(report) = &app.topology_report else {
+        return;
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // Notification
+            Constraint::Length(4), // Summary
+            Constraint::Min(0),    // Issue list
+            Constraint::Length(2), // Key help
+        ])
+        .split(inner_area);
+
+    frame.render_widget(
+        Paragraph::new(app.notification.clone())
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::White).bg(Color::DarkGray)),
+        chunks[0],
+    );
+
+    let mut summary_lines = vec![Line::from(format!(
+        "{} polygon features of {} checked",
+        report.polygons,
+        report.files.join(" and ")
+    ))];
+    if report.issues.is_empty() {
+        summary_lines.push(Line::from("No overlaps or gaps found.").fg(Color::LightGreen));
+    }
+    for (kind, color) in [
+        (TopologyIssueKind::Overlap, Color::LightRed),
+        (TopologyIssueKind::Gap, Color::Yellow),
+    ] {
+        let count = report.count(kind);
+        if count > 0 {
+            summary_lines.push(Line::from(vec![
+                Span::styled(format!("{:>8}  ", count), Style::default().fg(color)),
+                Span::raw(format!(
+                    "{}s, {} in total",
+                    kind.label(),
+                    measure::format_area(report.area_km2(kind))
+                )),
+            ]));
+        }
+    }
+    frame.render_widget(Paragraph::new(summary_lines), chunks[1]);
+
+    // The list scrolls to keep the highlighted issue in view
+    let visible_rows = chunks[2].height.saturating_sub(1).max(1) as usize; // Minus the border
+    let first = (app.topology_selected + 1).saturating_sub(visible_rows);
+    let issue_lines: Vec<Line> = report
+        .issues
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(visible_rows)
+        .map(|(i, issue)| {
+            let color = match issue.kind {
+                TopologyIssueKind::Overlap => Color::LightRed,
+                TopologyIssueKind::Gap => Color::Yellow,
+            };
+            let mut line = Line::from(vec![
+                Span::styled(
+                    format!("{:<8} ", issue.kind.label()),
+                    Style::default().fg(color),
+                ),
+                Span::styled(
+                    format!("{:>14}  ", measure::format_area(issue.area_km2)),
+                    Style::default().fg(Color::LightCyan),
+                ),
+                Span::raw(format!("at {:.5}, {:.5}  ", issue.at[1], issue.at[0])),
+                Span::raw(issue.features.clone()),
+            ]);
+            if i == app.topology_selected {
+                line = line.bg(Color::DarkGray);
+            }
+            line
+        })
+        .collect();
+    frame.render_widget(
+        Paragraph::new(issue_lines).block(Block::default().borders(Borders::TOP).title(format!(
+            " Issues {}-{} of {} ",
+            (first + 1).min(report.issues.len()),
+            (first + visible_rows).min(report.issues.len()),
+            report.issues.len()
+        ))),
+        chunks[2],
+    );
+
+    frame.render_widget(
+        Paragraph::new(
+            "J/K or Up/Down: Move | PageUp/PageDown/Home/End | Enter: Show in the preview | Esc: Back",
+        )
+        .block(Block::default().borders(Borders::TOP))
+        .style(Style::default().fg(Color::Gray)),
+        chunks[3],
+    );
+}
+
+/// Renders the Logs screen: every notification, warning and error of the
+/// session, newest first.
+fn render_logs_screen(frame: &mut Frame, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .title(" Logs ")
+        .title_style(Style::default().fg(Color::LightYellow).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightYellow));
+    let inner_area = block.inner(area);
+    frame.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),    // Entries
+            Constraint::Length(2), // Key help
+        ])
+        .split(inner_area);
+
+    let entries: Vec<&LogEntry> = app
+        .log
+        .iter()
+        .rev()
+        .filter(|entry| entry.level >= app.log_min_level)
+        .collect();
+    app.log_scroll = app.log_scroll.min(entries.len().saturating_sub(1));
+    let visible_rows = chunks[0].height.saturating_sub(1) as usize; // Minus the border
+    let mut lines: Vec<Line> = entries
+        .iter()
+        .skip(app.log_scroll)
+        .take(visible_rows)
+        .map(|entry| log_line(entry))
+        .collect();
+    if entries.is_empty() {
+        lines.push(Line::from("Nothing logged at this level.").fg(Color::Gray));
+    }
+    let shown = match app.log_min_level {
+        LogLevel::Info => "all",
+        LogLevel::Warning => "warnings and errors",
+        LogLevel::Error => "errors",
+    };
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::default().borders(Borders::TOP).title(format!(
+                " Entries {}-{} of {} ({}) ",
+                (app.log_scroll + 1).min(entries.len()),
+                (app.log_scroll + visible_rows).min(entries.len()),
+                entries.len(),
+                shown
+            )),
+        ),
+        chunks[0],
+    );
+
+    frame.render_widget(
+        Paragraph::new(
+            "J/K or Up/Down: Scroll | PageUp/PageDown/Home/End | F: Filter level | C: Clear | Esc: Back",
+        )
+        .block(Block::default().borders(Borders::TOP))
+        .style(Style::default().fg(Color::Gray)),
+        chunks[1],
+    );
+}
+
+/// Renders the Attribute Table screen: a row per feature of the file, a column
+/// per property.
+fn render_attribute_table_screen(frame: &mut Frame, app: &mut App, area: Rect) {
+    let Some(table) = app.attribute_table.as_mut() else {
+        return;
+    };
+    let features = app.layers[table.file_index]
+        .features
+        .as_deref()
+        .unwrap_or_default();
+    table.sync(features);
+
+    let block = Block::default()
+        .title(format!(
+            " Attributes of {} ",
+            app.layers[table.file_index].file
+        ))
+        .title_style(Style::default().fg(Color::LightYellow).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightYellow));
+    let inner_area = block.inner(area);
+    frame.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // Notification
+            Constraint::Min(0),    // Table
+            Constraint::Length(2), // Key help
+        ])
+        .split(inner_area);
+
+    frame.render_widget(
+        Paragraph::new(app.notification.clone())
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::White).bg(Color::DarkGray)),
+        chunks[0],
+    );
+
+    // Rows are numbered by their position in the file
+    let number_width = table.rows.len().max(1).to_string().len() + 1;
+    let width = chunks[1].width as usize;
+    // Columns are scrolled so the highlighted one is fully shown
+    let fits = |first: usize, last: usize| {
+        number_width + (first..=last).map(|i| table.widths[i] + 2).sum::<usize>() <= width
+    };
+    let mut first_column = table.column.min(table.columns.len().saturating_sub(1));
+    while first_column > 0 && fits(first_column - 1, table.column) {
+        first_column -= 1;
+    }
+    let mut last_column = first_column;
+    while last_column + 1 < table.columns.len() && fits(first_column, last_column + 1) {
+        last_column += 1;
+    }
+    let shown = first_column..(last_column + 1).min(table.columns.len());
+
+    let mut header = vec![Span::raw(format!("{:>w$} ", "#", w = number_width - 1))];
+    for i in shown.clone() {
+        let arrow = match table.sort {
+            Some((column, false)) if column == i => "▲",
+            Some((column, true)) if column == i => "▼",
+            _ => "",
+        };
+        let mut style = Style::default().fg(Color::LightCyan).bold();
+        if i == table.column {
+            style = style.bg(Color::DarkGray);
+        }
+        header.push(Span::styled(
+            fit_cell(&format!("{}{}", table.columns[i], arrow), table.widths[i]),
+            style,
+        ));
+        header.push(Span::raw("  "));
+    }
+    let mut lines = vec![Line::from(header)];
+
+    // The page scrolls to keep the highlighted row in view
+    table.page_rows = chunks[1].height.saturating_sub(2).max(1) as usize; // Minus border and header
+    let first_row = (table.row + 1).saturating_sub(table.page_rows);
+    for (i, &position) in table
+        .rows
+        .iter()
+        .enumerate()
+        .skip(first_row)
+        .take(table.page_rows)
+    {
+        let Some(feature) = features.get(position) else {
+            continue;
+        };
+        let row_style = if i == table.row {
+            Style::default().bg(Color::DarkGray)
+        } else {
+            Style::default()
+        };
+        let mut spans = vec![Span::styled(
+            format!("{:>w$} ", position + 1, w = number_width - 1),
+            row_style.fg(Color::Gray),
+        )];
+        for column in shown.clone() {
+            let value = table::cell(feature, &table.columns[column]);
+            let style = if i == table.row && column == table.column {
+                Style::default().fg(Color::Black).bg(Color::LightYellow)
+            } else {
+                row_style
+            };
+            spans.push(Span::styled(fit_cell(&value, table.widths[column]), style));
+            spans.push(Span::styled("  ", row_style));
+        }
+        lines.push(Line::from(spans));
+    }
+    if table.columns.is_empty() {
+        lines.push(Line::from("The features have no properties.").fg(Color::Gray));
+    }
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::TOP).title(format!(
+            " Rows {}-{} of {}, columns {}-{} of {} ",
+            (first_row + 1).min(table.rows.len()),
+            (first_row + table.page_rows).min(table.rows.len()),
+            table.rows.len(),
+            (shown.start + 1).min(table.columns.len()),
+            shown.end,
+            table.columns.len()
+        ))),
+        chunks[1],
+    );
+
+    let label = match app.current_mode {
+        AppMode::EditingCell => "Value:",
+        AppMode::AddingColumn => "New property:",
+        AppMode::SavingTable => "Save as:",
+        _ => {
+            frame.render_widget(
+                Paragraph::new(
+                    "Arrows or H/J/K/L: Move | PageUp/PageDown/Home/End | S: Sort (up/down/off) | Enter/E: Edit | Del: Clear | A/X: Add/remove property | W: Save | Esc: Back",
+                )
+                .block(Block::default().borders(Borders::TOP))
+                .style(Style::default().fg(Color::Gray)),
+                chunks[2],
+            );
+            return;
+        }
+    };
+    let input_area = Block::default().borders(Borders::TOP).inner(chunks[2]);
+    let input_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Length(label.len() as u16 + 1), // Label
+            Constraint::Min(0),                         // Input field
+        ])
+        .split(input_area);
+    frame.render_widget(Block::default().borders(Borders::TOP), chunks[2]);
+    frame.render_widget(
+        Paragraph::new(label).style(Style::default().fg(Color::LightCyan)),
+        input_layout[0],
+    );
+    frame.render_widget(
+        Paragraph::new(app.table_input.text.clone())
+            .style(Style::default().fg(Color::White).bg(Color::Blue)),
+        input_layout[1],
+    );
+    frame.set_cursor(
+        input_layout[1].x + app.table_input.cursor_column(),
+        input_layout[1].y,
+    );
+}
+
+// Pads or truncates `text` to `width` characters
+fn fit_cell(text: &str, width: usize) -> String {
+    let text = text.replace(['\n', '\t'], " ");
+    if text.chars().count() > width {
+        let short: String = text.chars().take(width.saturating_sub(1)).collect();
+        format!("{}…", short)
+    } else {
+        format!("{:<w$}", text, w = width)
+    }
+}
+
+/// Renders the Plot Summary screen with the results of the last plot.
+fn render_plot_summary_screen(frame: &mut Frame, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .title(" Plot Summary ")
+        .title_style(Style::default().fg(Color::LightGreen).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightGreen));
+    let inner_area = block.inner(area);
+    frame.render_widget(block, area);
+    let Some(summary) = &app.plot_summary else {
+        return;
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // Notification
+            Constraint::Min(0),    // Results
+            Constraint::Length(2), // Key help
+        ])
+        .split(inner_area);
+
+    frame.render_widget(
+        Paragraph::new(app.notification.clone())
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::White).bg(Color::DarkGray)),
+        chunks[0],
+    );
+
+    let heading = |text: &str| Line::from(text.to_string()).fg(Color::LightYellow).bold();
+    let mut lines = vec![heading("Output")];
+    if let Some(error) = &summary.error {
+        lines.push(Line::from(format!("  No image written: {}", error)).fg(Color::LightRed));
+    }
+    for output in &summary.outputs {
+        lines.push(Line::from(format!("  {}", output.display())).fg(Color::LightCyan));
+    }
+
+    if !summary.layers.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(heading("Layers"));
+        lines
+            .push(Line::from(format!("  {:>10} {:>10}  File", "Drawn", "Skipped")).fg(Color::Gray));
+        for layer in &summary.layers {
+            lines.push(Line::from(vec![
+                Span::raw(format!("  {:>10} ", layer.drawn)),
+                Span::styled(
+                    format!("{:>10}  ", layer.skipped),
+                    Style::default().fg(if layer.skipped > 0 {
+                        Color::Yellow
+                    } else {
+                        Color::Gray
+                    }),
+                ),
+                Span::raw(layer.name.clone()),
+            ]));
+        }
+    }
+
+    lines.push(Line::from(""));
+    lines.push(heading("Timings"));
+    lines.push(Line::from(format!(
+        "  Reading {:.2} s, rendering {:.2} s",
+        summary.read_time.as_secs_f64(),
+        summary.render_time.as_secs_f64()
+    )));
+
+    lines.push(Line::from(""));
+    lines.push(heading("Warnings"));
+    if summary.warnings.is_empty() {
+        lines.push(Line::from("  None").fg(Color::LightGreen));
+    }
+    for warning in &summary.warnings {
+        lines.push(Line::from(format!("  {}", warning)).fg(Color::Yellow));
+    }
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), chunks[1]);
+
+    frame.render_widget(Block::default().borders(Borders::TOP), chunks[2]);
+    let buttons = Block::default().borders(Borders::TOP).inner(chunks[2]);
+    render_buttons(
+        frame,
+        app,
+        buttons,
+        &[
+            (KeyCode::Char('o'), "O: Open image"),
+            (KeyCode::Char('c'), "C: Copy path"),
+            (KeyCode::Char('r'), "R: Plot again"),
+            (KeyCode::Esc, "Esc: Back"),
+        ],
+    );
+}
+
+// A toggle drawn as a checkbox button, filled in while it is on, followed by
+// its keys; clicking the line runs `action`
+fn toggle_control(app: &App, label: &str, on: bool, action: Action) -> Line<'static> {
+    let style = if on {
+        Style::default().fg(Color::Black).bg(Color::LightGreen)
+    } else {
+        Style::default().fg(Color::White).bg(Color::DarkGray)
+    };
+    Line::from(vec![
+        Span::styled(
+            format!(" [{}] {} ", if on { "x" } else { " " }, label),
+            style,
+        ),
+        key_hint(app, action),
+    ])
+}
+
+fn key_hint(app: &App, action: Action) -> Span<'static> {
+    Span::styled(
+        format!(" {}", app.keymap.keys_for(action)),
+        Style::default().fg(Color::Gray),
+    )
+}
+
+// Splits `text` into spans in `style`, the characters at `positions` (in
+// ascending order) underlined in yellow
+fn highlight_chars(text: &str, positions: &[usize], style: Style) -> Vec<Span<'static>> {
+    let highlight = style.fg(Color::Yellow).add_modifier(Modifier::UNDERLINED);
+    let mut spans: Vec<Span> = Vec::new();
+    let mut run = String::new();
+    let mut run_highlighted = false;
+    for (i, c) in text.chars().enumerate() {
+        let highlighted = positions.binary_search(&i).is_ok();
+        if highlighted != run_highlighted && !run.is_empty() {
+            let run_style = if run_highlighted { highlight } else { style };
+            spans.push(Span::styled(std::mem::take(&mut run), run_style));
+        }
+        run_highlighted = highlighted;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(
+            run,
+            if run_highlighted { highlight } else { style },
+        ));
+    }
+    spans
+}
+
+// Longest sample value shown on the Attributes tab
+const MAX_SAMPLE_CHARS: usize = 24;
+
+// Lines of the Attributes tab: one per property with its counts, then its range
+// and mean if it is numeric or some of its values otherwise
+fn attribute_lines(info: &GeoJsonInfo) -> Vec<Line<'static>> {
+    if info.property_stats.is_empty() {
+        return vec![Line::from("No properties.").fg(Color::Gray)];
+    }
+    let mut lines = Vec::new();
+    for stats in &info.property_stats {
+        let (distinct, capped) = stats.distinct();
+        lines.push(Line::from(vec![
+            Span::styled(stats.name.clone(), Style::default().fg(Color::LightCyan)),
+            Span::styled(
+                format!(
+                    "  {} of {} set, {}{} distinct",
+                    stats.count,
+                    info.feature_count,
+                    distinct,
+                    if capped { "+" } else { "" }
+                ),
+                Style::default().fg(Color::Gray),
+            ),
+        ]));
+        if stats.is_numeric() {
+            lines.push(Line::from(format!(
+                "  min {}  max {}  mean {}",
+                format_stat(stats.min),
+                format_stat(stats.max),
+                stats.mean().map_or_else(String::new, format_stat)
+            )));
+        } else if !stats.samples.is_empty() {
+            let samples: Vec<String> = stats
+                .samples
+                .iter()
+                .map(|sample| {
+                    if sample.chars().count() > MAX_SAMPLE_CHARS {
+                        let short: String = sample.chars().take(MAX_SAMPLE_CHARS - 1).collect();
+                        format!("{}…", short)
+                    } else {
+                        sample.clone()
+                    }
+                })
+                .collect();
+            lines.push(Line::from(format!("  e.g. {}", samples.join(", "))));
+        }
+    }
+    lines
+}
+
+// Whole numbers as they are, others with a few decimals or in scientific
+// notation when very large or small
+fn format_stat(value: f64) -> String {
+    let magnitude = value.abs();
+    if value.fract() == 0.0 && magnitude < 1e15 {
+        format!("{}", value)
+    } else if !(1e-3..1e6).contains(&magnitude) {
+        format!("{:.3e}", value)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
 // Renders the GeoJSON Mapper UI
 fn render_geojson_mapper_ui(frame: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
-    // Main vertical layout: Notification/Search, then Main Content, then Spacer
-    let main_layout_constraints = if app.current_mode == AppMode::Searching {
+    // Text input shown above the main content in the current mode, if any
+    let input_bar = match app.current_mode {
+        AppMode::Searching => Some((app.search_mode.label(), &app.search_query_input)),
+        AppMode::EditingMarker => Some(("Marker:", &app.marker_input)),
+        AppMode::EditingTitle => {
+            let input = match app.title_field {
+                TitleField::Title => &app.title_input,
+                TitleField::Subtitle => &app.subtitle_input,
+                TitleField::Attribution => &app.attribution_input,
+            };
+            Some((app.title_field.label(), input))
+        }
+        AppMode::EditingTolerance => Some(("Simplify:", &app.tolerance_input)),
+        AppMode::EditingExtent => Some(("Extent:", &app.extent_input)),
+        AppMode::EditingPlace => Some(("Go to:", &app.place_input)),
+        AppMode::EditingJump => Some(("Jump to:", &app.jump_input)),
+        AppMode::EditingTime => Some(("Time:", &app.time_input)),
+        AppMode::EditingCrs => Some(("CRS:", &app.crs_input)),
+        AppMode::EditingFilter => Some(("Filter:", &app.filter_input)),
+        AppMode::EditingBuffer => Some(("Buffer:", &app.buffer_input)),
+        AppMode::EditingDissolve => Some(("Dissolve by:", &app.dissolve_input)),
+        AppMode::EditingJoin => Some(("Join CSV:", &app.join_input)),
+        AppMode::EditingOpenPath => Some(("Open:", &app.open_path_input)),
+        AppMode::EditingExport => Some(("Export to:", &app.export_path_input)),
+        AppMode::EditingSelection => Some(("Select where:", &app.selection_input)),
+        AppMode::EditingMinify => Some(("Decimals:", &app.minify_input)),
+        AppMode::EditingSnap => Some(("Grid:", &app.snap_input)),
+        AppMode::EditingDuplicates => Some(("Tolerance:", &app.duplicate_input)),
+        _ => None,
+    };
+
+    // Main vertical layout: Notification/Input, then Main Content, then Spacer
+    let main_layout_constraints = if input_bar.is_some() {
         vec![
             Constraint::Length(1), // Notification
             Constraint::Length(1), // Spacer
-            Constraint::Length(1), // Search bar
+            Constraint::Length(1), // Search/marker bar
             Constraint::Min(0),    // Main content area
             Constraint::Length(1), // Spacer
         ]
@@ -116,30 +1459,24 @@ fn render_geojson_mapper_ui(frame: &mut Frame, app: &mut App, area: ratatui::lay
     frame.render_widget(Paragraph::new(""), chunks[current_chunk_idx]);
     current_chunk_idx += 1;
 
-    // Search Bar (conditional)
-    if app.current_mode == AppMode::Searching {
-        let search_layout = Layout::default()
+    // Search/Marker Bar (conditional)
+    if let Some((label, input)) = input_bar {
+        let input_layout = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
-                Constraint::Length(8), // "Search:" label
-                Constraint::Min(0),    // Input field
+                Constraint::Length(label.len() as u16 + 1), // e.g. "Search:" label
+                Constraint::Min(0),                         // Input field
             ])
             .split(chunks[current_chunk_idx]);
 
-        let search_label = Paragraph::new("Search:").style(Style::default().fg(Color::LightCyan));
-        frame.render_widget(search_label, search_layout[0]);
+        let input_label = Paragraph::new(label).style(Style::default().fg(Color::LightCyan));
+        frame.render_widget(input_label, input_layout[0]);
 
-        let search_input_paragraph = Paragraph::new(app.search_query_buffer.clone())
-            .style(Style::default().fg(Color::Yellow));
+        let input_paragraph =
+            Paragraph::new(input.text.clone()).style(Style::default().fg(Color::Yellow));
+        frame.render_widget(input_paragraph, input_layout[1]);
 
-        frame.render_widget(search_input_paragraph, search_layout[1]);
-
-        if app.current_mode == AppMode::Searching {
-            frame.set_cursor(
-                search_layout[1].x + app.search_query_cursor as u16,
-                search_layout[1].y,
-            );
-        }
+        frame.set_cursor(input_layout[1].x + input.cursor_column(), input_layout[1].y);
         current_chunk_idx += 1;
     }
 
@@ -172,73 +1509,313 @@ fn render_geojson_mapper_ui(frame: &mut Frame, app: &mut App, area: ratatui::lay
 
     // File List Items
     let mut list_items: Vec<Line> = Vec::new();
-    let max_visible_items_in_list = left_panel_chunks[1].height as usize;
+    let max_visible_items_in_list = left_panel_chunks[1].height.saturating_sub(2) as usize;
     let end_display_index =
         (app.scroll_offset + max_visible_items_in_list).min(app.filtered_geojson_indices.len());
 
+    // Characters matched by a file name search are highlighted
+    let name_search = app.property_search.is_none()
+        && app.search_mode == SearchMode::FileNames
+        && !app.search_query_input.text.is_empty();
     for i in app.scroll_offset..end_display_index {
         let original_index = app.filtered_geojson_indices[i];
-        let file_name = &app.geojson_files[original_index];
-        let selection_indicator = if app.selected_files_status[original_index] {
+        let file_name = &app.layers[original_index].file;
+        let selection_indicator = if app.layers[original_index].selected {
             "[x]"
         } else {
             "[ ]"
         };
-        let display_text = format!(
-            "{} {}. {}",
-            selection_indicator,
-            original_index + 1,
-            file_name
-        );
+        let prefix = format!("{} {}. ", selection_indicator, original_index + 1);
+        let mut suffix = String::new();
+        if app.stdin_file.as_ref() == Some(file_name) {
+            suffix.push_str(" [stdin]");
+        } else if app.is_external(original_index) {
+            suffix.push_str(" [external]");
+        }
+        if app.clip_mask_index == Some(original_index) {
+            suffix.push_str(" [mask]");
+        }
+        if app.focus_index == Some(original_index) {
+            suffix.push_str(" [focus]");
+        }
+        if app.layers[original_index].filter.is_some() {
+            suffix.push_str(" [filter]");
+        }
+        if let Some(checked) = &app.layers[original_index].feature_selection {
+            suffix.push_str(&format!(" [{} checked]", checked.len()));
+        }
+        if let Some(matches) = app
+            .property_search
+            .as_ref()
+            .and_then(|search| search.for_file(original_index))
+        {
+            suffix.push_str(&format!(" [{} found]", matches.feature_count));
+        }
         let mut style = Style::default().fg(Color::White);
         if i == app.selected_file_index {
             style = style.bg(Color::DarkGray).add_modifier(Modifier::BOLD);
         }
-        if app.selected_files_status[original_index] {
-            if let Some(color_rgb) = app.assigned_plot_colors[original_index] {
-                style = style.fg(Color::Rgb(color_rgb.0, color_rgb.1, color_rgb.2));
-            }
+        if app.layers[original_index].selected
+            && let Some(color_rgb) = app.layers[original_index].color
+        {
+            style = style.fg(Color::Rgb(color_rgb.0, color_rgb.1, color_rgb.2));
         }
-        list_items.push(Line::from(vec![Span::styled(display_text, style)]));
+        let matched = if name_search {
+            search::fuzzy_match(&app.search_query_input.text, file_name)
+                .map(|m| m.positions)
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let mut spans = vec![Span::styled(prefix, style)];
+        spans.extend(highlight_chars(file_name, &matched, style));
+        spans.push(Span::styled(suffix, style));
+        list_items.push(Line::from(spans));
     }
 
-    let file_list_paragraph = Paragraph::new(list_items)
-        .block(Block::default().borders(Borders::ALL).title("Files"))
-        .wrap(Wrap { trim: false });
+    // Rows are not wrapped so that each file occupies exactly one line for mouse clicks
+    let file_list_paragraph =
+        Paragraph::new(list_items).block(Block::default().borders(Borders::ALL).title("Files"));
     frame.render_widget(file_list_paragraph, left_panel_chunks[1]);
+    app.file_list_area = left_panel_chunks[1];
+    app.regions.add(left_panel_chunks[1], Region::FileList);
+
+    // --- Right Panel: tabs above the preview and the keybinds ---
+    let right_panel_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage(50), // Tabs
+            Constraint::Percentage(35), // Preview
+            Constraint::Min(0),         // Help/Keybinds
+        ])
+        .split(right_panel_area);
+
+    let tabs_area = right_panel_chunks[0];
+    // Below the tab names and controls registered next, so those stay clickable
+    app.regions.add(tabs_area, Region::Panel);
+    let mut tab_titles = vec![Span::raw(" ")];
+    let mut tab_x = tabs_area.x + 2;
+    for (i, tab) in PanelTab::ALL.into_iter().enumerate() {
+        if i > 0 {
+            tab_titles.push(Span::raw(" | "));
+            tab_x += 3;
+        }
+        let style = if app.panel_tab == tab {
+            Style::default().fg(Color::LightBlue).bold()
+        } else {
+            Style::default().fg(Color::Gray)
+        };
+        tab_titles.push(Span::styled(tab.name(), style));
+        let width = (tab.name().len() as u16).min(tabs_area.right().saturating_sub(tab_x));
+        app.regions.add(
+            Rect::new(tab_x, tabs_area.y, width, 1),
+            Region::PanelTab(tab),
+        );
+        tab_x += tab.name().len() as u16;
+    }
+    tab_titles.push(Span::raw(format!(
+        " ({}) ",
+        app.keymap.keys_for(Action::PanelTab)
+    )));
+    let tabs_block = Block::default()
+        .title(Line::from(tab_titles))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightBlue));
+    let tab_content_area = tabs_block.inner(tabs_area);
+    frame.render_widget(tabs_block, tabs_area);
+
+    match app.panel_tab {
+        PanelTab::Info => render_info_tab(frame, app, tab_content_area),
+        PanelTab::Properties => render_properties_tab(frame, app, tab_content_area),
+        PanelTab::Style => render_style_tab(frame, app, tab_content_area),
+        PanelTab::Json => render_json_tab(frame, app, tab_content_area),
+        PanelTab::Log => render_log_tab(frame, app, tab_content_area),
+    }
+
+    render_preview(frame, app, right_panel_chunks[1]);
+
+    // Dynamic Help / Keybinds
+    let help_block = Block::default()
+        .title(" Help / Keybinds ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightCyan));
+
+    let help_lines: Vec<Line> = app
+        .help_keybinds
+        .iter()
+        .map(|s| Line::from(s.clone()))
+        .collect();
+    let help_paragraph = Paragraph::new(help_lines)
+        .block(help_block)
+        .wrap(Wrap { trim: false });
+    frame.render_widget(help_paragraph, right_panel_chunks[2]);
+
+    // divider for resizing the main panels
+    let divider_x_pos = main_content_layout[0].x + main_content_layout[0].width;
+    for y in main_content_layout[0].y..(main_content_layout[0].y + main_content_layout[0].height) {
+        let style = if app.is_resizing {
+            Style::default().bg(Color::LightRed)
+        } else {
+            Style::default().bg(Color::DarkGray)
+        };
+        frame
+            .buffer_mut()
+            .get_mut(divider_x_pos, y)
+            .set_symbol("│")
+            .set_style(style);
+    }
+    // Grabbed within a column of it, above the panels' own regions
+    app.regions.add(
+        Rect::new(
+            divider_x_pos.saturating_sub(1),
+            main_content_layout[0].y,
+            3,
+            main_content_layout[0].height,
+        ),
+        Region::Divider,
+    );
+}
 
-    // --- Right Panel ---
-    let right_panel_chunks = Layout::default()
+/// Renders the Info tab: details or attribute statistics of the highlighted
+/// file, switched by the sub-tabs on its first line.
+fn render_info_tab(frame: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Percentage(35), // File Info
-            Constraint::Length(6),      // Plotting Options
-            Constraint::Min(0),         // Help/Keybinds
+            Constraint::Length(1), // Details | Attributes
+            Constraint::Min(0),    // Content
         ])
-        .split(right_panel_area);
+        .split(area);
 
-    // Section 1: Detailed File Information
-    let file_info_block = Block::default()
-        .title(" File Information ")
-        .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::LightBlue));
+    let tab_style = |tab: InfoTab| {
+        if app.info_tab == tab {
+            Style::default().fg(Color::LightCyan).bold()
+        } else {
+            Style::default().fg(Color::Gray)
+        }
+    };
+    let sub_tabs = Line::from(vec![
+        Span::styled("Details", tab_style(InfoTab::Details)),
+        Span::raw(" | "),
+        Span::styled("Attributes", tab_style(InfoTab::Attributes)),
+        key_hint(app, Action::InfoTab),
+    ]);
+    frame.render_widget(Paragraph::new(sub_tabs), chunks[0]);
+    app.regions.add(
+        Rect::new(chunks[0].x, chunks[0].y, 7.min(chunks[0].width), 1),
+        Region::InfoTab(InfoTab::Details),
+    );
+    app.regions.add(
+        Rect::new(
+            chunks[0].x + 10,
+            chunks[0].y,
+            10.min(chunks[0].width.saturating_sub(10)),
+            1,
+        ),
+        Region::InfoTab(InfoTab::Attributes),
+    );
 
-    let current_original_file_index = if app.filtered_geojson_indices.is_empty() {
-        0
+    let highlighted_index = app.highlighted_file_index();
+    let highlighted_info = highlighted_index.and_then(|i| app.layers[i].info.as_ref());
+    let lines = if app.info_tab == InfoTab::Attributes {
+        match highlighted_info {
+            Some(info) => attribute_lines(info),
+            None => vec![Line::from("Loading file info...").fg(Color::Gray)],
+        }
     } else {
-        app.filtered_geojson_indices[app
-            .selected_file_index
-            .min(app.filtered_geojson_indices.len().saturating_sub(1))]
+        detail_lines(app)
     };
+    render_scrolled(frame, app, lines, chunks[1]);
+}
+
+// Lines of the Details sub-tab: size, features, extent and CRS of the
+// highlighted file
+fn detail_lines(app: &App) -> Vec<Line<'static>> {
+    let highlighted_index = app.highlighted_file_index();
+    let highlighted_info = highlighted_index.and_then(|i| app.layers[i].info.as_ref());
 
     let mut file_info_text = Vec::new();
-    if let Some(info) = &app.cached_geojson_info[current_original_file_index] {
+    if let Some(search) = &app.property_search
+        && let Some(matches) = highlighted_index.and_then(|i| search.for_file(i))
+    {
+        file_info_text.push(
+            Line::from(format!(
+                "'{}' found in {} features:",
+                search.query, matches.feature_count
+            ))
+            .fg(Color::LightCyan),
+        );
+        for m in &matches.matches {
+            file_info_text.push(Line::from(format!(
+                "  #{} {}: {}",
+                m.feature + 1,
+                m.property,
+                m.value
+            )));
+        }
+        if matches.matches.len() < matches.value_count {
+            file_info_text.push(Line::from("  ...").fg(Color::Gray));
+        }
+    }
+    if let Some(tolerance) = highlighted_index.and_then(|i| app.layers[i].simplify_tolerance) {
+        file_info_text.push(Line::from(format!("Simplify: {} (layer)", tolerance)));
+    }
+    if let Some(info) = highlighted_info {
         file_info_text.push(Line::from(format!("Size: {} KB", info.file_size_kb)));
         file_info_text.push(Line::from(format!("Modified: {}", info.modified_time)));
         file_info_text.push(Line::from(format!("Features: {}", info.feature_count)));
+        if info.header_only {
+            file_info_text.push(
+                Line::from("Large file: extent from its header; select it to read the features.")
+                    .fg(Color::Gray),
+            );
+        } else if highlighted_index.is_some_and(|i| app.layers[i].features.is_none())
+            && info.parse_error.is_none()
+        {
+            file_info_text.push(
+                Line::from("Large file: details from an earlier run; select it to draw it.")
+                    .fg(Color::Gray),
+            );
+        }
+        if let Some(filter) = highlighted_index.and_then(|i| app.layers[i].filter.as_ref()) {
+            file_info_text.push(
+                Line::from(match info.filtered_count {
+                    Some(count) => format!("Filter: {} ({} match)", filter, count),
+                    None => format!("Filter: {}", filter),
+                })
+                .fg(Color::LightCyan),
+            );
+        }
+        if let Some(checked) =
+            highlighted_index.and_then(|i| app.layers[i].feature_selection.as_ref())
+        {
+            file_info_text.push(
+                Line::from(format!(
+                    "Checked: {} of {} features",
+                    checked.len(),
+                    info.feature_count
+                ))
+                .fg(Color::LightCyan),
+            );
+        }
         for (geom_type, count) in &info.geometry_counts {
             file_info_text.push(Line::from(format!("  {}: {}", geom_type, count)));
         }
+        // Lengths and areas are only meaningful in degrees of WGS 84
+        if info.crs_warning.is_none() {
+            if info.line_length_km > 0.0 {
+                file_info_text.push(Line::from(format!(
+                    "Line length: {}",
+                    measure::format_length(info.line_length_km)
+                )));
+            }
+            if info.polygon_area_km2 > 0.0 {
+                file_info_text.push(Line::from(format!(
+                    "Polygon area: {}",
+                    measure::format_area(info.polygon_area_km2)
+                )));
+            }
+        }
         if let Some(bbox) = info.bbox {
             file_info_text.push(Line::from(format!(
                 "BBox: [{:.2},{:.2},{:.2},{:.2}]",
@@ -247,6 +1824,30 @@ fn render_geojson_mapper_ui(frame: &mut Frame, app: &mut App, area: ratatui::lay
         } else {
             file_info_text.push(Line::from(String::from("BBox: Not applicable/Found")));
         }
+        if let Some((first, last)) = info.time_extent {
+            file_info_text.push(Line::from(format!(
+                "Time: {} to {}",
+                temporal::format_time(first),
+                temporal::format_time(last)
+            )));
+        }
+        if let Some(ref crs) = info.crs {
+            let source = if info.crs_overridden { " override" } else { "" };
+            let line = if crs::is_wgs84(crs) {
+                Line::from(format!("CRS: {}{}", crs, source))
+            } else if info.crs_warning.is_some() {
+                Line::from(format!("CRS: {}{} (not reprojected)", crs, source)).fg(Color::Yellow)
+            } else {
+                Line::from(format!("CRS: {}{} (reprojected to WGS 84)", crs, source))
+            };
+            file_info_text.push(line);
+        }
+        if let Some(ref warning) = info.crs_warning {
+            file_info_text.push(Line::from(format!("Warning: {}", warning)).fg(Color::Yellow));
+        }
+        if let Some(ref message) = info.skipped_message {
+            file_info_text.push(Line::from(format!("Warning: {}", message)).fg(Color::Yellow));
+        }
         if let Some(ref error) = info.parse_error {
             file_info_text.push(Line::from(format!("Error: {}", error)).fg(Color::Red));
         }
@@ -255,17 +1856,152 @@ fn render_geojson_mapper_ui(frame: &mut Frame, app: &mut App, area: ratatui::lay
         file_info_text
             .push(Line::from("Or no file selected/available.".to_string()).fg(Color::Gray));
     }
-    let file_info_paragraph = Paragraph::new(file_info_text)
-        .block(file_info_block)
-        .wrap(Wrap { trim: false });
-    frame.render_widget(file_info_paragraph, right_panel_chunks[0]);
+    file_info_text
+}
 
-    // Section 2: Plotting Configuration Options
-    let plotting_options_block = Block::default()
-        .title(" Plotting Options ")
-        .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::LightYellow));
+// Draws `lines` wrapped, from the scroll position of the right panel, which
+// is clamped so the last line stays visible
+fn render_scrolled(frame: &mut Frame, app: &mut App, lines: Vec<Line<'static>>, area: Rect) {
+    app.info_scroll = app.info_scroll.min(lines.len().saturating_sub(1) as u16);
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .scroll((app.info_scroll, 0));
+    frame.render_widget(paragraph, area);
+}
+
+/// Renders the Properties tab: how the highlighted layer is drawn and which
+/// of its features are used.
+fn render_properties_tab(frame: &mut Frame, app: &mut App, area: Rect) {
+    let Some(index) = app.highlighted_file_index() else {
+        frame.render_widget(
+            Paragraph::new(Line::from("No file highlighted.").fg(Color::Gray)),
+            area,
+        );
+        return;
+    };
+    let mut lines = vec![Line::from(app.layers[index].file.clone()).fg(Color::LightCyan)];
+    lines.extend(layer_property_lines(app, index, None));
+    lines.push(Line::from(format!(
+        "Simplify: {}",
+        app.layers[index].simplify_tolerance.map_or_else(
+            || format!("{} (global)", app.simplify_label()),
+            |t| t.to_string()
+        )
+    )));
+    lines.push(Line::from(format!(
+        "CRS: {}",
+        app.layers[index].crs.as_deref().unwrap_or("as declared")
+    )));
+    lines.push(Line::from(format!(
+        "Filter: {}",
+        app.layers[index]
+            .filter
+            .as_ref()
+            .map_or_else(|| String::from("none"), |f| f.to_string())
+    )));
+    let mut roles = Vec::new();
+    if app.clip_mask_index == Some(index) {
+        roles.push("clip mask");
+    }
+    if app.focus_index == Some(index) {
+        roles.push("focus");
+    }
+    if !roles.is_empty() {
+        lines.push(Line::from(format!("Used as: {}", roles.join(", "))));
+    }
+    lines.push(Line::from(""));
+    lines.push(
+        Line::from(format!(
+            "{}: Sizes | {}: Simplify | {}: CRS | {}: Filter",
+            app.keymap.keys_for(Action::LayerProperties),
+            app.keymap.keys_for(Action::SimplifyLayer),
+            app.keymap.keys_for(Action::SetCrs),
+            app.keymap.keys_for(Action::FilterLayer),
+        ))
+        .fg(Color::Gray),
+    );
+    render_scrolled(frame, app, lines, area);
+}
+
+/// Renders the Log tab: the notifications shown so far, newest first.
+fn render_log_tab(frame: &mut Frame, app: &mut App, area: Rect) {
+    let lines = if app.log.is_empty() {
+        vec![Line::from("Nothing logged yet.").fg(Color::Gray)]
+    } else {
+        app.log.iter().rev().map(log_line).collect()
+    };
+    render_scrolled(frame, app, lines, area);
+}
+
+/// Renders the JSON tab: the start of the highlighted file, indented and
+/// highlighted.
+fn render_json_tab(frame: &mut Frame, app: &mut App, area: Rect) {
+    let highlighted = app.highlighted_file_index().map(|i| &app.layers[i].file);
+    let lines = match &app.json_preview {
+        Some((file, preview)) if Some(file) == highlighted => match preview {
+            Ok(preview) => {
+                let mut lines: Vec<Line<'static>> =
+                    preview.lines.iter().map(|line| json_line(line)).collect();
+                if preview.truncated {
+                    lines.push(
+                        Line::from(format!(
+                            "... (only the first {} KB are shown)",
+                            json_preview::MAX_PREVIEW_BYTES / 1024
+                        ))
+                        .fg(Color::Gray),
+                    );
+                }
+                lines
+            }
+            Err(e) => vec![Line::from(format!("Cannot read {}: {}", file, e)).fg(Color::Red)],
+        },
+        _ => vec![Line::from("No file highlighted.").fg(Color::Gray)],
+    };
+    render_scrolled(frame, app, lines, area);
+}
+
+// A line of the JSON tab with keys, strings, numbers and literals highlighted
+fn json_line(line: &str) -> Line<'static> {
+    let spans: Vec<Span> = json_preview::tokens(line)
+        .into_iter()
+        .map(|(token, text)| {
+            let color = match token {
+                JsonToken::Key => Color::LightCyan,
+                JsonToken::String => Color::LightGreen,
+                JsonToken::Number => Color::Yellow,
+                JsonToken::Literal => Color::LightMagenta,
+                JsonToken::Punctuation | JsonToken::Space => Color::Gray,
+            };
+            Span::styled(text.to_string(), Style::default().fg(color))
+        })
+        .collect();
+    Line::from(spans)
+}
+
+// A log entry with its time, and its level unless it is a notification
+fn log_line(entry: &LogEntry) -> Line<'static> {
+    let mut spans = vec![Span::styled(
+        format!("{} ", entry.time),
+        Style::default().fg(Color::Gray),
+    )];
+    let color = match entry.level {
+        LogLevel::Info => Color::White,
+        LogLevel::Warning => Color::Yellow,
+        LogLevel::Error => Color::LightRed,
+    };
+    if entry.level != LogLevel::Info {
+        spans.push(Span::styled(
+            format!("{}: ", entry.level.name()),
+            Style::default().fg(color).bold(),
+        ));
+    }
+    spans.push(Span::styled(entry.text.clone(), Style::default().fg(color)));
+    Line::from(spans)
+}
 
+/// Renders the Style tab: the options applying to the whole plot, each
+/// line running the action that changes it when clicked.
+fn render_style_tab(frame: &mut Frame, app: &mut App, area: Rect) {
     let inner_plotting_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -273,20 +2009,82 @@ fn render_geojson_mapper_ui(frame: &mut Frame, app: &mut App, area: ratatui::lay
             Constraint::Length(1), // Points Visible
             Constraint::Length(1), // Lines Visible
             Constraint::Length(1), // Polygons Visible
+            Constraint::Length(1), // Scale Bar
+            Constraint::Length(1), // North Arrow
+            Constraint::Length(1), // Basemap
+            Constraint::Length(1), // Underlay
+            Constraint::Length(1), // Thumbnail
+            Constraint::Length(1), // Extent
+            Constraint::Length(1), // Time
+            Constraint::Length(1), // Clip Mask
+            Constraint::Length(1), // Focus
+            Constraint::Length(1), // Figure Mode
+            Constraint::Length(1), // Projection
+            Constraint::Length(1), // Aspect
+            Constraint::Length(1), // Clusters
+            Constraint::Length(1), // Inset
+            Constraint::Length(1), // Simplification
+            Constraint::Length(1), // Title
             Constraint::Length(1), // Spacer (only one spacer now)
             Constraint::Length(1), // For Output Filename label and input
             Constraint::Min(0),    // Any remaining space for padding within the block
         ])
-        .split(plotting_options_block.inner(right_panel_chunks[1]));
+        .split(area);
+    // Lines that run the action changing them when clicked, in layout order
+    let line_actions = [
+        Some(Action::CycleColor),
+        Some(Action::TogglePoints),
+        Some(Action::ToggleLines),
+        Some(Action::TogglePolygons),
+        Some(Action::ToggleScaleBar),
+        Some(Action::ToggleNorthArrow),
+        Some(Action::ToggleBasemap),
+        Some(Action::OpenFile),
+        Some(Action::ToggleThumbnail),
+        Some(Action::EditExtent),
+        Some(Action::TimeRange),
+        None, // Clip mask and focus are set on the highlighted file
+        None,
+        Some(Action::FigureMode),
+        Some(Action::Projection),
+        Some(Action::ToggleAspect),
+        Some(Action::ToggleClusters),
+        Some(Action::Inset),
+        None, // Simplification
+        Some(Action::EditTitle),
+        None, // Spacer
+        Some(Action::Rename),
+    ];
+    for (area, action) in inner_plotting_layout.iter().zip(line_actions) {
+        if let Some(action) = action {
+            app.regions.add(*area, Region::Action(action));
+        }
+    }
 
     let mut current_inner_chunk_idx = 0;
 
-    // "Next Color" line
+    // "Next Color" line, with a swatch and a button to cycle it
     let next_plot_color = &app.plot_colors[app.current_color_index_for_assignment];
-    let next_color_line = Line::from(format!(
-        "Next Color: R{} G{} B{}",
-        next_plot_color.0, next_plot_color.1, next_plot_color.2
-    ));
+    let next_color_line = Line::from(vec![
+        Span::raw("Next Color: "),
+        Span::styled(
+            "  ",
+            Style::default().bg(Color::Rgb(
+                next_plot_color.0,
+                next_plot_color.1,
+                next_plot_color.2,
+            )),
+        ),
+        Span::raw(format!(
+            " R{} G{} B{}  ",
+            next_plot_color.0, next_plot_color.1, next_plot_color.2
+        )),
+        Span::styled(
+            " Cycle ",
+            Style::default().fg(Color::Black).bg(Color::LightYellow),
+        ),
+        key_hint(app, Action::CycleColor),
+    ]);
     frame.render_widget(
         Paragraph::new(next_color_line),
         inner_plotting_layout[current_inner_chunk_idx],
@@ -294,10 +2092,129 @@ fn render_geojson_mapper_ui(frame: &mut Frame, app: &mut App, area: ratatui::lay
     current_inner_chunk_idx += 1;
 
     // Toggles for visibility
+    frame.render_widget(
+        Paragraph::new(toggle_control(
+            app,
+            "Points Visible",
+            app.plot_points,
+            Action::TogglePoints,
+        )),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    frame.render_widget(
+        Paragraph::new(toggle_control(
+            app,
+            "Lines Visible",
+            app.plot_lines,
+            Action::ToggleLines,
+        )),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    frame.render_widget(
+        Paragraph::new(toggle_control(
+            app,
+            "Polygons Visible",
+            app.plot_polygons,
+            Action::TogglePolygons,
+        )),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            "Scale Bar: {}",
+            if app.scale_bar { "Yes" } else { "No" }
+        )),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            "North Arrow: {}",
+            if app.north_arrow { "Yes" } else { "No" }
+        )),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            "Basemap: {}",
+            if app.basemap_enabled { "Yes" } else { "No" }
+        )),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    let underlay = match &app.underlay {
+        Some(raster) => raster.path.file_name().map_or_else(
+            || raster.path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        ),
+        None => String::from("None"),
+    };
+    frame.render_widget(
+        Paragraph::new(format!("Underlay: {}", underlay)),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    let thumbnail = if app.thumbnails {
+        format!("{} px", app.thumbnail_size)
+    } else {
+        String::from("No")
+    };
+    frame.render_widget(
+        Paragraph::new(format!("Thumbnail: {}", thumbnail)),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            "Extent: {}",
+            if app.extent_locked {
+                "Locked to preview"
+            } else {
+                "Auto"
+            }
+        )),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    let time = app
+        .time_range
+        .as_ref()
+        .map_or_else(|| String::from("All"), TimeRange::to_string);
+    frame.render_widget(
+        Paragraph::new(format!("Time: {}", time)),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    let clip_mask = match app.clip_mask_index {
+        Some(i) if app.clip_mask_shade => format!("{} (shade outside)", app.layers[i].file),
+        Some(i) => app.layers[i].file.clone(),
+        None => String::from("None"),
+    };
+    frame.render_widget(
+        Paragraph::new(format!("Clip Mask: {}", clip_mask)),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
     frame.render_widget(
         Paragraph::new(format!(
-            "Points Visible: {}",
-            if app.plot_points { "Yes" } else { "No" }
+            "Focus: {}",
+            app.focus_index
+                .map_or("Off", |i| app.layers[i].file.as_str())
         )),
         inner_plotting_layout[current_inner_chunk_idx],
     );
@@ -305,22 +2222,72 @@ fn render_geojson_mapper_ui(frame: &mut Frame, app: &mut App, area: ratatui::lay
 
     frame.render_widget(
         Paragraph::new(format!(
-            "Lines Visible: {}",
-            if app.plot_lines { "Yes" } else { "No" }
+            "Figure Mode: {}",
+            app.figure_mode.map_or("Off", FigureBackground::name)
+        )),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    frame.render_widget(
+        Paragraph::new(format!("Projection: {}", app.projection.name())),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            "True Aspect: {}",
+            if app.keep_aspect { "Yes" } else { "No" }
         )),
         inner_plotting_layout[current_inner_chunk_idx],
     );
     current_inner_chunk_idx += 1;
 
+    frame.render_widget(
+        Paragraph::new(match app.cluster_distance {
+            Some(distance) => format!("Cluster Points: {} px", distance),
+            None => String::from("Cluster Points: No"),
+        }),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    frame.render_widget(
+        Paragraph::new(format!("Inset Map: {}", Inset::label(app.inset))),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
+    let overrides = app
+        .layers
+        .iter()
+        .filter(|layer| layer.simplify_tolerance.is_some() && layer.selected)
+        .count();
     frame.render_widget(
         Paragraph::new(format!(
-            "Polygons Visible: {}",
-            if app.plot_polygons { "Yes" } else { "No" }
+            "Simplify: {}{}",
+            app.simplify_label(),
+            if overrides > 0 {
+                format!(" ({} layer overrides)", overrides)
+            } else {
+                String::new()
+            }
         )),
         inner_plotting_layout[current_inner_chunk_idx],
     );
     current_inner_chunk_idx += 1;
 
+    frame.render_widget(
+        Paragraph::new(if app.title_input.text.is_empty() {
+            String::from("Title: (none)")
+        } else {
+            format!("Title: {}", app.title_input.text)
+        }),
+        inner_plotting_layout[current_inner_chunk_idx],
+    );
+    current_inner_chunk_idx += 1;
+
     // Spacer
     frame.render_widget(
         Paragraph::new(""),
@@ -336,13 +2303,12 @@ fn render_geojson_mapper_ui(frame: &mut Frame, app: &mut App, area: ratatui::lay
             Constraint::Min(0),     // Input field
         ])
         .split(inner_plotting_layout[current_inner_chunk_idx]);
-    current_inner_chunk_idx += 1; // Increment after splitting
 
     let filename_label = Paragraph::new("Output Filename:");
     frame.render_widget(filename_label, output_filename_layout[0]);
 
     // Removed borders from filename input paragraph.
-    let filename_input_paragraph = Paragraph::new(app.output_filename_buffer.clone()).style(
+    let filename_input_paragraph = Paragraph::new(app.output_filename_input.text.clone()).style(
         if app.current_mode == AppMode::EditingFilename {
             Style::default().fg(Color::White).bg(Color::Blue)
         } else {
@@ -350,37 +2316,329 @@ fn render_geojson_mapper_ui(frame: &mut Frame, app: &mut App, area: ratatui::lay
         },
     );
     frame.render_widget(filename_input_paragraph, output_filename_layout[1]);
-    frame.render_widget(plotting_options_block, right_panel_chunks[1]);
+}
 
-    // Section 3: Dynamic Help / Keybinds
-    let help_block = Block::default()
-        .title(" Help / Keybinds ")
+/// Renders the loaded layers on a braille canvas: selected files in their
+/// assigned colors, the highlighted (unselected) file in gray.
+// Rough size in pixels of a terminal cell, to cluster the preview like the image
+const CELL_PIXELS: (f64, f64) = (8.0, 16.0);
+
+fn render_preview(frame: &mut Frame, app: &mut App, area: Rect) {
+    let mut title = match &app.follow {
+        Some(follow) => format!(
+            " Preview (following {}) ",
+            app.layers[follow.file_index].file
+        ),
+        None => String::from(" Preview "),
+    };
+    if app.gps.is_some() {
+        title.push_str(&match (app.gps_position, app.gps_recording) {
+            (Some(fix), recording) => format!(
+                "[GPS {:.5}, {:.5}{}] ",
+                fix.lat,
+                fix.lon,
+                if recording { ", REC" } else { "" }
+            ),
+            (None, _) => String::from("[GPS: no fix] "),
+        });
+    }
+    if app.extent_locked {
+        title.push_str("[extent locked] ");
+    } else if app.preview_view.is_some() {
+        title.push_str("[zoomed] ");
+    }
+    let preview_block = Block::default()
+        .title(title)
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::LightCyan));
+        .border_style(Style::default().fg(
+            if matches!(
+                app.current_mode,
+                AppMode::NavigatingPreview | AppMode::Measuring
+            ) {
+                Color::Yellow
+            } else {
+                Color::LightMagenta
+            },
+        ));
 
-    let help_lines: Vec<Line> = app
-        .help_keybinds
-        .iter()
-        .map(|s| Line::from(s.clone()))
-        .collect();
-    let help_paragraph = Paragraph::new(help_lines)
-        .block(help_block)
-        .wrap(Wrap { trim: false });
-    frame.render_widget(help_paragraph, right_panel_chunks[2]);
+    let extent = app.preview_view.unwrap_or_else(|| {
+        app.preview_extent()
+            .map(plot::pad_bbox)
+            .unwrap_or([-180.0, -90.0, 180.0, 90.0])
+    });
+    // Remembered so that mouse clicks can be converted back to coordinates
+    app.preview_area = area;
+    app.regions.add(area, Region::Preview);
+    app.preview_bounds = extent;
+    app.update_feature_indexes();
+    let app = &*app;
+    // Braille cells hold 2x4 dots; vertices closer than one dot are skipped
+    let min_step = ((extent[2] - extent[0]) / (area.width.max(1) as f64 * 2.0))
+        .max((extent[3] - extent[1]) / (area.height.max(1) as f64 * 4.0));
+    // Clustering distance along each axis, its pixels taken over to terminal cells
+    let cluster_reach = app.cluster_distance.map(|distance| {
+        (
+            (extent[2] - extent[0]) * distance as f64 / (area.width.max(1) as f64 * CELL_PIXELS.0),
+            (extent[3] - extent[1]) * distance as f64 / (area.height.max(1) as f64 * CELL_PIXELS.1),
+        )
+    });
 
-    // divider for resizing the main panels
-    let divider_x_pos = main_content_layout[0].x + main_content_layout[0].width;
-    for y in main_content_layout[0].y..(main_content_layout[0].y + main_content_layout[0].height) {
-        let style = if app.is_resizing {
-            Style::default().bg(Color::LightRed)
-        } else {
-            Style::default().bg(Color::DarkGray)
-        };
-        frame
-            .buffer_mut()
-            .get_mut(divider_x_pos, y)
-            .set_symbol("│")
-            .set_style(style);
+    let canvas = Canvas::default()
+        .block(preview_block)
+        .marker(Marker::Braille)
+        .x_bounds([extent[0], extent[2]])
+        .y_bounds([extent[1], extent[3]])
+        .paint(|ctx| {
+            let styler = Styler::new(
+                &app.style_rules,
+                app.layers
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, layer)| {
+                        layer
+                            .features
+                            .iter()
+                            .flatten()
+                            .enumerate()
+                            .filter(move |&(j, f)| app.layer_accepts(i, j, f))
+                            .map(|(_, f)| f)
+                    })
+                    .filter(|f| style::accepts(&app.style_rules, f)),
+            );
+            for (i, layer) in app.layers.iter().enumerate() {
+                let Some(features) = &layer.features else {
+                    continue;
+                };
+                // Layers out of focus are gray like unselected ones
+                let layer_color = match app.layers[i].color {
+                    Some(c)
+                        if app.layers[i].selected
+                            && app.focus_index.is_none_or(|focus| focus == i) =>
+                    {
+                        Some(c)
+                    }
+                    _ => None,
+                };
+                let types = app.layer_drawn_types(i);
+                // Only features whose bbox reaches into the preview are painted
+                let visible = match &app.layers[i].feature_index {
+                    Some(index) => index.in_bbox(extent),
+                    None => (0..features.len()).collect(),
+                };
+                // Points are batched per color; gradient rules can give every feature its own
+                let mut points: Vec<(Color, Vec<(f64, f64)>)> = Vec::new();
+                // Point features set aside for clustering, with their colors
+                let mut clusterable: Vec<((f64, f64), Color)> = Vec::new();
+                for feature in visible
+                    .iter()
+                    .filter(|&&j| app.layer_accepts(i, j, &features[j]))
+                    .map(|&j| &features[j])
+                    .filter(|f| styler.accepts(f))
+                {
+                    let Some(geometry) = &feature.geometry else {
+                        continue;
+                    };
+                    // Only selected layers are styled, the highlighted file stays gray
+                    let color = layer_color
+                        .map_or(Color::Gray, |c| preview_color(styler.color(feature, c)));
+                    if let (Some(_), Value::Point(p)) = (cluster_reach, &geometry.value)
+                        && types.points
+                    {
+                        clusterable.push(((p[0], p[1]), color));
+                        continue;
+                    }
+                    let batch = point_batch(&mut points, color);
+                    paint_geometry(ctx, types, &geometry.value, color, min_step, batch);
+                }
+                let mut counts = Vec::new(); // Position and size of the clusters
+                if let Some((width, height)) = cluster_reach {
+                    let positions: Vec<(f64, f64)> =
+                        clusterable.iter().map(|(position, _)| *position).collect();
+                    for group in cluster::cluster_points(&positions, width, height) {
+                        match group.members[..] {
+                            [single] => {
+                                let (position, color) = clusterable[single];
+                                point_batch(&mut points, color).push(position);
+                            }
+                            _ => counts.push(((group.x, group.y), group.members.len())),
+                        }
+                    }
+                }
+                for (color, coords) in &points {
+                    ctx.draw(&Points {
+                        coords,
+                        color: *color,
+                    });
+                }
+                let cluster_color = layer_color.map_or(Color::Gray, preview_color);
+                for ((x, y), count) in counts {
+                    ctx.print(
+                        x,
+                        y,
+                        Span::styled(
+                            format!("({})", count),
+                            Style::default().fg(cluster_color).bold(),
+                        ),
+                    );
+                }
+            }
+
+            // Markers and the GPS track are drawn on a separate layer above the files
+            ctx.layer();
+            for segment in app.gps_track.windows(2) {
+                ctx.draw(&CanvasLine {
+                    x1: segment[0].lon,
+                    y1: segment[0].lat,
+                    x2: segment[1].lon,
+                    y2: segment[1].lat,
+                    color: Color::LightRed,
+                });
+            }
+            for marker in &app.markers {
+                let text = if marker.label.is_empty() {
+                    String::from("✚")
+                } else {
+                    format!("✚ {}", marker.label)
+                };
+                ctx.print(
+                    marker.lon,
+                    marker.lat,
+                    Span::styled(text, Style::default().fg(Color::LightYellow).bold()),
+                );
+            }
+            // The measured path, closed back to its first point once it is a polygon
+            let closing = (app.measure_closed && app.measure_points.len() > 2)
+                .then(|| app.measure_points.last().zip(app.measure_points.first()))
+                .flatten();
+            for ((x1, y1), (x2, y2)) in app
+                .measure_points
+                .windows(2)
+                .map(|pair| (pair[0], pair[1]))
+                .chain(closing.map(|(a, b)| (*a, *b)))
+            {
+                ctx.draw(&CanvasLine {
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    color: Color::LightGreen,
+                });
+            }
+            for &(lon, lat) in &app.measure_points {
+                ctx.print(
+                    lon,
+                    lat,
+                    Span::styled("•", Style::default().fg(Color::LightGreen).bold()),
+                );
+            }
+            if matches!(
+                app.current_mode,
+                AppMode::NavigatingPreview | AppMode::Identify | AppMode::Measuring
+            ) && let Some((lon, lat)) = app.preview_cursor_position()
+            {
+                ctx.print(
+                    lon,
+                    lat,
+                    Span::styled("┼", Style::default().fg(Color::LightYellow).bold()),
+                );
+            }
+            if let Some((lon, lat)) = app.identified_point {
+                ctx.print(
+                    lon,
+                    lat,
+                    Span::styled("×", Style::default().fg(Color::LightCyan).bold()),
+                );
+            }
+            if let Some((lon, lat)) = app.jump_point {
+                ctx.print(
+                    lon,
+                    lat,
+                    Span::styled("✛", Style::default().fg(Color::LightMagenta).bold()),
+                );
+            }
+            if let Some(fix) = app.gps_position {
+                ctx.print(
+                    fix.lon,
+                    fix.lat,
+                    Span::styled("◉", Style::default().fg(Color::LightRed).bold()),
+                );
+            }
+        });
+    frame.render_widget(canvas, area);
+}
+
+// Black, the first plot color, would be invisible on most terminal backgrounds
+fn preview_color(color: RGBColor) -> Color {
+    match color {
+        RGBColor(0, 0, 0) => Color::White,
+        RGBColor(r, g, b) => Color::Rgb(r, g, b),
+    }
+}
+
+// Positions of the points painted in `color`, added to `points` if there are none yet
+fn point_batch(points: &mut Vec<(Color, Vec<(f64, f64)>)>, color: Color) -> &mut Vec<(f64, f64)> {
+    match points.iter().position(|(c, _)| *c == color) {
+        Some(i) => &mut points[i].1,
+        None => {
+            points.push((color, Vec::new()));
+            &mut points.last_mut().unwrap().1
+        }
+    }
+}
+
+// Draws lines directly and collects point positions into `points`
+fn paint_geometry(
+    ctx: &mut Context,
+    types: GeometryTypes,
+    value: &Value,
+    color: Color,
+    min_step: f64,
+    points: &mut Vec<(f64, f64)>,
+) {
+    let mut paint_line = |line: &[Vec<f64>]| {
+        let Some(first) = line.first() else { return };
+        let mut last = (first[0], first[1]);
+        for (i, position) in line.iter().enumerate().skip(1) {
+            let next = (position[0], position[1]);
+            let is_last = i == line.len() - 1;
+            if !is_last && (next.0 - last.0).abs().max((next.1 - last.1).abs()) < min_step {
+                continue;
+            }
+            ctx.draw(&CanvasLine {
+                x1: last.0,
+                y1: last.1,
+                x2: next.0,
+                y2: next.1,
+                color,
+            });
+            last = next;
+        }
+    };
+    match value {
+        Value::Point(c) if types.points => points.push((c[0], c[1])),
+        Value::MultiPoint(coords) if types.points => {
+            points.extend(coords.iter().map(|c| (c[0], c[1])))
+        }
+        Value::LineString(line) if types.lines => paint_line(line),
+        Value::MultiLineString(lines) if types.lines => {
+            lines.iter().for_each(|line| paint_line(line))
+        }
+        Value::Polygon(rings) if types.polygons => {
+            if let Some(exterior) = rings.first() {
+                paint_line(exterior);
+            }
+        }
+        Value::MultiPolygon(polygons) if types.polygons => {
+            for exterior in polygons.iter().filter_map(|rings| rings.first()) {
+                paint_line(exterior);
+            }
+        }
+        Value::GeometryCollection(geometries) => {
+            for geometry in geometries {
+                paint_geometry(ctx, types, &geometry.value, color, min_step, points);
+            }
+        }
+        _ => {}
     }
 }
 
@@ -390,12 +2648,60 @@ fn render_footer(frame: &mut Frame, app: &mut App, area: ratatui::layout::Rect)
         CurrentScreen::Main => "Main",
         CurrentScreen::Help => "Help",
         CurrentScreen::GeoJsonMapper => "GeoJSON Mapper",
+        CurrentScreen::Styling => "Styling",
+        CurrentScreen::Validation => "Validation",
+        CurrentScreen::Duplicates => "Duplicates",
+        CurrentScreen::Topology => "Topology",
+        CurrentScreen::PlotSummary => "Plot Summary",
+        CurrentScreen::Logs => "Logs",
+        CurrentScreen::AttributeTable => "Attribute Table",
     };
 
     let current_mode_name = match app.current_mode {
         AppMode::Navigation => "Navigation",
         AppMode::EditingFilename => "Editing Filename",
+        AppMode::EditingTitle => "Editing Title",
         AppMode::Searching => "Searching",
+        AppMode::EditingSelection => "Selecting",
+        AppMode::EditingOpenPath => "Opening File",
+        AppMode::EditingExport => "Exporting",
+        AppMode::Sessions => "Sessions",
+        AppMode::EditingSessionName => "Naming Session",
+        AppMode::EditingMarker => "Adding Marker",
+        AppMode::EditingTolerance => "Editing Tolerance",
+        AppMode::ConfirmPlot => "Confirm Plot",
+        AppMode::NavigatingPreview => "Preview",
+        AppMode::EditingExtent => "Editing Extent",
+        AppMode::EditingPlace => "Going to Place",
+        AppMode::EditingJump => "Jumping",
+        AppMode::EditingTime => "Editing Time Range",
+        AppMode::EditingCrs => "Editing CRS",
+        AppMode::EditingFilter => "Editing Filter",
+        AppMode::EditingBuffer => "Buffering",
+        AppMode::EditingDissolve => "Dissolving",
+        AppMode::EditingJoin => "Joining",
+        AppMode::ChoosingPoints => "Points",
+        AppMode::EditingMinify => "Minifying",
+        AppMode::EditingSnap => "Snapping",
+        AppMode::LayerProperties => "Layer Properties",
+        AppMode::Validation => "Validation",
+        AppMode::EditingDuplicates => "Finding Duplicates",
+        AppMode::Duplicates => "Duplicates",
+        AppMode::ChoosingTopology => "Checking Topology",
+        AppMode::Topology => "Topology",
+        AppMode::PlotSummary => "Plot Summary",
+        AppMode::Logs => "Logs",
+        AppMode::Identify => "Identify",
+        AppMode::Measuring => "Measure",
+        AppMode::SelectingFeatures => "Selecting Features",
+        AppMode::AttributeTable => "Attribute Table",
+        AppMode::EditingCell => "Editing Cell",
+        AppMode::AddingColumn => "Adding Property",
+        AppMode::SavingTable => "Saving Table",
+        AppMode::Wfs => "WFS",
+        AppMode::EditingWfsUrl => "Editing WFS URL",
+        AppMode::Styling => "Styling",
+        AppMode::EditingRule => "Adding Rule",
     };
 
     let footer_text = Line::from(vec![
@@ -439,4 +2745,46 @@ fn render_footer(frame: &mut Frame, app: &mut App, area: ratatui::layout::Rect)
         .style(Style::default().fg(Color::Gray));
 
     frame.render_widget(footer, area);
+
+    // The line below the footer text shows a plot rendering in the background
+    if area.height >= 3 {
+        let gauge_area = Rect {
+            y: area.y + 2,
+            height: 1,
+            ..area
+        };
+        render_plot_progress(frame, app, gauge_area);
+    }
+}
+
+/// Renders the progress of the plot rendering in the background as a gauge
+/// one line high, so it does not hide the screen being used meanwhile.
+fn render_plot_progress(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(job) = &app.plot_job else {
+        return;
+    };
+    let (drawn, total) = job.counts;
+    let action = if job.progress.is_cancelled() {
+        "Cancelling the plot..."
+    } else if job.to_clipboard {
+        "Plotting to the clipboard (Esc in the file list cancels)"
+    } else {
+        "Plotting (Esc in the file list cancels)"
+    };
+    // Nothing is counted while the layers are clipped and the basemap is drawn
+    let label = if total == 0 {
+        format!("{} Preparing...", action)
+    } else {
+        format!("{} {} / {} features", action, drawn, total)
+    };
+    let ratio = if total == 0 {
+        0.0
+    } else {
+        (drawn as f64 / total as f64).min(1.0)
+    };
+    let gauge = Gauge::default()
+        .gauge_style(Style::default().fg(Color::LightGreen).bg(Color::DarkGray))
+        .ratio(ratio)
+        .label(label);
+    frame.render_widget(gauge, area);
 }
@@ -0,0 +1,602 @@
+// validate.rs
+
+use geojson::Geometry;
+use serde_json::Value;
+use std::{fs, path::Path};
+
+use crate::crs;
+use crate::loader::{self, is_line_delimited};
+use crate::osm;
+
+const MAX_ISSUES: usize = 500; // Further issues are only counted
+
+/// Kind of problem found by `validate_file`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IssueKind {
+    InvalidGeometry, // Not a geometry that can be drawn at all
+    NotANumber,      // Coordinate that is missing, not a number or not finite
+    TooFewPositions, // Line with fewer than 2 or ring with fewer than 4 positions
+    UnclosedRing,    // Ring whose last position differs from its first
+    OutOfRange,      // Longitude outside ±180 or latitude outside ±90
+    WindingOrder,    // Exterior ring not counterclockwise or hole not clockwise
+    DuplicatePoint,  // Position repeated right after itself
+}
+
+impl IssueKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            IssueKind::InvalidGeometry => "Invalid geometry",
+            IssueKind::NotANumber => "Invalid coordinate",
+            IssueKind::TooFewPositions => "Too few positions",
+            IssueKind::UnclosedRing => "Unclosed ring",
+            IssueKind::OutOfRange => "Out of range",
+            IssueKind::WindingOrder => "Winding order",
+            IssueKind::DuplicatePoint => "Duplicate point",
+        }
+    }
+
+    /// Whether the issue breaks the spec rather than one of its recommendations
+    /// (RFC 7946 only says rings *should* follow the right-hand rule).
+    pub fn is_error(self) -> bool {
+        !matches!(self, IssueKind::WindingOrder | IssueKind::DuplicatePoint)
+    }
+}
+
+/// One problem, with where in the file it was found.
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub location: String, // Zero-based, e.g. "feature 3, polygon 1, ring 0, position 17"
+    pub message: String,
+}
+
+/// Result of checking a GeoJSON file against RFC 7946.
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub file: String,
+    pub features: usize,
+    pub positions: usize,
+    pub counts: Vec<(IssueKind, usize)>, // Number of issues of each kind found
+    pub issues: Vec<Issue>,              // The first `MAX_ISSUES` issues
+    pub crs: Option<String>,             // Declared non-WGS 84 system; ranges are not checked
+    pub error: Option<String>,           // Why the file could not be read at all
+}
+
+impl ValidationReport {
+    pub fn total(&self) -> usize {
+        self.counts.iter().map(|(_, count)| count).sum()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.counts
+            .iter()
+            .filter(|(kind, _)| kind.is_error())
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// One-line outcome, e.g. for the notification bar.
+    pub fn summary(&self) -> String {
+        if let Some(error) = &self.error {
+            return format!("{}: {}", self.file, error);
+        }
+        let errors = self.error_count();
+        match self.total() {
+            0 => format!("{}: valid ({} features)", self.file, self.features),
+            total => format!(
+                "{}: {} error{}, {} warning{} in {} features",
+                self.file,
+                errors,
+                if errors == 1 { "" } else { "s" },
+                total - errors,
+                if total - errors == 1 { "" } else { "s" },
+                self.features
+            ),
+        }
+    }
+
+    fn add(&mut self, kind: IssueKind, location: &[String], message: String) {
+        match self.counts.iter_mut().find(|(k, _)| *k == kind) {
+            Some((_, count)) => *count += 1,
+            None => {
+                self.counts.push((kind, 1));
+                self.counts.sort();
+            }
+        }
+        if self.issues.len() < MAX_ISSUES {
+            self.issues.push(Issue {
+                kind,
+                location: location.join(", "),
+                message,
+            });
+        }
+    }
+}
+
+/// Checks every geometry of a GeoJSON, GeoJSONL, WKT, WKB or OSM PBF file for unclosed rings,
+/// wrong winding order, out-of-range or non-numeric coordinates and duplicate
+/// points. The raw JSON is walked, so features the loader would skip are reported too.
+pub fn validate_file(path: &Path) -> ValidationReport {
+    let mut report = ValidationReport {
+        file: path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().to_string(),
+        ),
+        ..ValidationReport::default()
+    };
+    if loader::is_geometry_dump(path) {
+        let records = match loader::read_geometry_dump(path) {
+            Ok(records) => records,
+            Err(e) => {
+                report.error = Some(e.to_string());
+                return report;
+            }
+        };
+        for (location, value) in records {
+            let location = vec![location];
+            match value {
+                Ok(value) => {
+                    let geometry = serde_json::to_value(Geometry::new(value)).unwrap_or_default();
+                    validate_object(&mut report, &geometry, location, true);
+                }
+                Err(e) => report.add(IssueKind::InvalidGeometry, &location, e),
+            }
+        }
+        return report;
+    }
+    if loader::is_osm_pbf(path) {
+        match osm::read_osm_pbf(path) {
+            Ok(osm) => {
+                for feature in osm.features {
+                    let location = feature
+                        .property("@id")
+                        .and_then(|id| id.as_str())
+                        .map(|id| vec![id.to_string()])
+                        .unwrap_or_default();
+                    if let Some(geometry) = &feature.geometry {
+                        let geometry = serde_json::to_value(geometry).unwrap_or_default();
+                        validate_object(&mut report, &geometry, location, true);
+                    }
+                }
+                for error in osm.skipped {
+                    let (id, message) = error.split_once(": ").unwrap_or(("", &error));
+                    report.add(
+                        IssueKind::InvalidGeometry,
+                        &[id.to_string()],
+                        message.to_string(),
+                    );
+                }
+            }
+            Err(e) => report.error = Some(e),
+        }
+        return report;
+    }
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+
+    if is_line_delimited(path) {
+        for (line_no, line) in contents.lines().enumerate() {
+            let line = line.trim().trim_start_matches('\u{1e}');
+            if line.is_empty() {
+                continue;
+            }
+            let location = vec![format!("line {}", line_no + 1)];
+            match serde_json::from_str::<Value>(line) {
+                Ok(value) => validate_object(&mut report, &value, location, true),
+                Err(e) => report.add(IssueKind::InvalidGeometry, &location, e.to_string()),
+            }
+        }
+        return report;
+    }
+
+    let value: Value = match serde_json::from_str(&contents) {
+        Ok(value) => value,
+        Err(e) => {
+            report.error = Some(format!("Not valid JSON: {}", e));
+            return report;
+        }
+    };
+    report.crs = crs::crs_name(&value).filter(|name| !crs::is_wgs84(name));
+    let check_range = report.crs.is_none();
+    match value.get("features").and_then(|f| f.as_array()) {
+        Some(features)
+            if value.get("type").and_then(|t| t.as_str()) == Some("FeatureCollection") =>
+        {
+            for (index, feature) in features.iter().enumerate() {
+                validate_object(
+                    &mut report,
+                    feature,
+                    vec![format!("feature {}", index)],
+                    check_range,
+                );
+            }
+        }
+        _ => validate_object(&mut report, &value, Vec::new(), check_range),
+    }
+    report
+}
+
+// Validates a Feature or a bare geometry
+fn validate_object(
+    report: &mut ValidationReport,
+    value: &Value,
+    location: Vec<String>,
+    check_range: bool,
+) {
+    let mut checker = Checker {
+        report,
+        location,
+        check_range,
+    };
+    match value.get("type").and_then(|t| t.as_str()) {
+        Some("Feature") => {
+            checker.report.features += 1;
+            match value.get("geometry") {
+                None => checker.issue(
+                    IssueKind::InvalidGeometry,
+                    String::from("Feature has no geometry member"),
+                ),
+                Some(Value::Null) => {} // Unlocated features are valid
+                Some(geometry) => checker.geometry(geometry),
+            }
+        }
+        Some(_) => {
+            checker.report.features += 1;
+            checker.geometry(value);
+        }
+        None => checker.issue(
+            IssueKind::InvalidGeometry,
+            String::from("Object has no type member"),
+        ),
+    }
+}
+
+// Walks one geometry, keeping track of where in the feature it is
+struct Checker<'a> {
+    report: &'a mut ValidationReport,
+    location: Vec<String>,
+    check_range: bool,
+}
+
+impl Checker<'_> {
+    fn issue(&mut self, kind: IssueKind, message: String) {
+        self.report.add(kind, &self.location, message);
+    }
+
+    // Runs `f` with `part` appended to the location
+    fn within(&mut self, part: String, f: impl FnOnce(&mut Self)) {
+        self.location.push(part);
+        f(self);
+        self.location.pop();
+    }
+
+    fn geometry(&mut self, geometry: &Value) {
+        let kind = geometry
+            .get("type")
+            .and_then(|t| t.as_str())
+            .unwrap_or_default();
+        if kind == "GeometryCollection" {
+            match geometry.get("geometries").and_then(|g| g.as_array()) {
+                Some(geometries) => {
+                    for (i, member) in geometries.iter().enumerate() {
+                        self.within(format!("geometry {}", i), |c| c.geometry(member));
+                    }
+                }
+                None => self.issue(
+                    IssueKind::InvalidGeometry,
+                    String::from("GeometryCollection without geometries"),
+                ),
+            }
+            return;
+        }
+        let Some(coordinates) = geometry.get("coordinates") else {
+            self.issue(
+                IssueKind::InvalidGeometry,
+                format!("'{}' geometry without coordinates", kind),
+            );
+            return;
+        };
+        match kind {
+            "Point" => {
+                self.position(coordinates);
+            }
+            "MultiPoint" => match array(coordinates) {
+                Some(points) => {
+                    self.positions(&points);
+                }
+                None => self.malformed(kind),
+            },
+            "LineString" => match array(coordinates) {
+                Some(line) => self.line(&line),
+                None => self.malformed(kind),
+            },
+            "MultiLineString" => match rings(coordinates) {
+                Some(lines) => {
+                    for (i, line) in lines.iter().enumerate() {
+                        self.within(format!("linestring {}", i), |c| c.line(line));
+                    }
+                }
+                None => self.malformed(kind),
+            },
+            "Polygon" => match rings(coordinates) {
+                Some(rings) => self.polygon(&rings),
+                None => self.malformed(kind),
+            },
+            "MultiPolygon" => match array(coordinates)
+                .and_then(|polygons| polygons.into_iter().map(rings).collect::<Option<Vec<_>>>())
+            {
+                Some(polygons) => {
+                    for (i, rings) in polygons.iter().enumerate() {
+                        self.within(format!("polygon {}", i), |c| c.polygon(rings));
+                    }
+                }
+                None => self.malformed(kind),
+            },
+            _ => self.issue(
+                IssueKind::InvalidGeometry,
+                format!("Unknown geometry type '{}'", kind),
+            ),
+        }
+    }
+
+    fn malformed(&mut self, kind: &str) {
+        self.issue(
+            IssueKind::InvalidGeometry,
+            format!("Coordinates are not nested as a {} needs", kind),
+        );
+    }
+
+    fn line(&mut self, line: &[&Value]) {
+        if line.len() < 2 {
+            self.issue(
+                IssueKind::TooFewPositions,
+                format!(
+                    "Line has {} position{}, needs at least 2",
+                    line.len(),
+                    if line.len() == 1 { "" } else { "s" }
+                ),
+            );
+        }
+        self.positions(line);
+    }
+
+    fn polygon(&mut self, rings: &[Vec<&Value>]) {
+        for (i, ring) in rings.iter().enumerate() {
+            self.within(format!("ring {}", i), |c| c.ring(ring, i == 0));
+        }
+    }
+
+    fn ring(&mut self, ring: &[&Value], exterior: bool) {
+        if ring.len() < 4 {
+            self.issue(
+                IssueKind::TooFewPositions,
+                format!("Ring has {} positions, needs at least 4", ring.len()),
+            );
+        }
+        let points = self.positions(ring);
+        if points.len() != ring.len() || points.is_empty() {
+            return; // Bad positions are already reported
+        }
+        let (first, last) = (points[0], points[points.len() - 1]);
+        if first != last {
+            self.issue(
+                IssueKind::UnclosedRing,
+                format!(
+                    "Ring ends at ({}, {}) instead of its start ({}, {})",
+                    last.0, last.1, first.0, first.1
+                ),
+            );
+        }
+        let area = signed_area(&points);
+        if area != 0.0 && (area > 0.0) != exterior {
+            self.issue(
+                IssueKind::WindingOrder,
+                format!(
+                    "{} is {}, should be {}",
+                    if exterior { "Exterior ring" } else { "Hole" },
+                    if area > 0.0 {
+                        "counterclockwise"
+                    } else {
+                        "clockwise"
+                    },
+                    if exterior {
+                        "counterclockwise"
+                    } else {
+                        "clockwise"
+                    }
+                ),
+            );
+        }
+    }
+
+    // Validates each position, returning the valid ones
+    fn positions(&mut self, positions: &[&Value]) -> Vec<(f64, f64)> {
+        let mut points: Vec<(f64, f64)> = Vec::with_capacity(positions.len());
+        let mut previous: Option<(f64, f64)> = None;
+        for (i, position) in positions.iter().enumerate() {
+            self.location.push(format!("position {}", i));
+            let point = self.position(position);
+            if let (Some(point), Some(previous)) = (point, previous)
+                && point == previous
+            {
+                self.issue(
+                    IssueKind::DuplicatePoint,
+                    format!("({}, {}) repeats the previous position", point.0, point.1),
+                );
+            }
+            self.location.pop();
+            previous = point;
+            points.extend(point);
+        }
+        points
+    }
+
+    fn position(&mut self, position: &Value) -> Option<(f64, f64)> {
+        self.report.positions += 1;
+        let Some(values) = position.as_array() else {
+            self.issue(
+                IssueKind::InvalidGeometry,
+                format!("Position {} is not an array", position),
+            );
+            return None;
+        };
+        if values.len() < 2 {
+            self.issue(
+                IssueKind::TooFewPositions,
+                format!("Position {} needs longitude and latitude", position),
+            );
+            return None;
+        }
+        let numbers: Vec<Option<f64>> = values
+            .iter()
+            .map(|v| v.as_f64().filter(|n| n.is_finite()))
+            .collect();
+        if let Some(axis) = numbers.iter().position(Option::is_none) {
+            self.issue(
+                IssueKind::NotANumber,
+                format!(
+                    "Coordinate {} of {} is not a finite number",
+                    axis + 1,
+                    position
+                ),
+            );
+            return None;
+        }
+        let (lon, lat) = (numbers[0]?, numbers[1]?);
+        if self.check_range && !((-180.0..=180.0).contains(&lon) && (-90.0..=90.0).contains(&lat)) {
+            let axis = if (-180.0..=180.0).contains(&lon) {
+                "Latitude"
+            } else {
+                "Longitude"
+            };
+            self.issue(
+                IssueKind::OutOfRange,
+                format!("{} out of range in ({}, {})", axis, lon, lat),
+            );
+        }
+        Some((lon, lat))
+    }
+}
+
+fn array(value: &Value) -> Option<Vec<&Value>> {
+    Some(value.as_array()?.iter().collect())
+}
+
+// Rings of a Polygon or lines of a MultiLineString
+fn rings(value: &Value) -> Option<Vec<Vec<&Value>>> {
+    array(value)?.into_iter().map(array).collect()
+}
+
+// Twice the signed area; positive for counterclockwise rings
+fn signed_area(points: &[(f64, f64)]) -> f64 {
+    points
+        .windows(2)
+        .map(|pair| pair[0].0 * pair[1].1 - pair[1].0 * pair[0].1)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(name: &str, contents: &str) -> ValidationReport {
+        let path =
+            std::env::temp_dir().join(format!("plots_validate_{}_{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        let report = validate_file(&path);
+        let _ = fs::remove_file(&path);
+        report
+    }
+
+    fn issue(report: &ValidationReport, kind: IssueKind) -> &Issue {
+        report.issues.iter().find(|i| i.kind == kind).unwrap()
+    }
+
+    #[test]
+    fn each_kind_of_problem_is_found_where_it_is() {
+        let report = validate(
+            "problems.geojson",
+            r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature", "geometry": {"type": "Polygon", "coordinates": [
+                    [[0, 0], [0, 1], [1, 1], [1, 0], [0, 0]]]}},
+                {"type": "Feature", "geometry": {"type": "MultiPolygon", "coordinates": [[
+                    [[0, 0], [1, 0], [1, 1], [0, 0]]], [[[0, 0], [1, 0], [1, 1], [0, 1]]]]}},
+                {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [
+                    [0, 0], [0, 0], [200, 0], [0, "north"]]}},
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [5]}},
+                {"type": "Feature", "geometry": null},
+                {"type": "Feature"},
+                {"type": "Circle", "coordinates": [0, 0]}
+            ]}"#,
+        );
+        assert_eq!(report.features, 7);
+        assert_eq!(
+            issue(&report, IssueKind::WindingOrder).location,
+            "feature 0, ring 0"
+        );
+        assert_eq!(
+            issue(&report, IssueKind::UnclosedRing).location,
+            "feature 1, polygon 1, ring 0"
+        );
+        assert_eq!(
+            issue(&report, IssueKind::DuplicatePoint).location,
+            "feature 2, position 1"
+        );
+        assert_eq!(
+            issue(&report, IssueKind::OutOfRange).location,
+            "feature 2, position 2"
+        );
+        assert_eq!(
+            issue(&report, IssueKind::NotANumber).location,
+            "feature 2, position 3"
+        );
+        assert_eq!(
+            issue(&report, IssueKind::TooFewPositions).location,
+            "feature 3"
+        );
+        let invalid: Vec<&str> = report
+            .issues
+            .iter()
+            .filter(|i| i.kind == IssueKind::InvalidGeometry)
+            .map(|i| i.location.as_str())
+            .collect();
+        assert_eq!(invalid, ["feature 5", "feature 6"]);
+        assert_eq!(report.error_count(), 6);
+        assert!(
+            report
+                .summary()
+                .ends_with("6 errors, 2 warnings in 7 features")
+        );
+    }
+
+    #[test]
+    fn ranges_are_not_checked_in_other_coordinate_systems() {
+        let report = validate(
+            "utm.geojson",
+            r#"{"type": "FeatureCollection",
+                "crs": {"type": "name", "properties": {"name": "urn:ogc:def:crs:EPSG::32632"}},
+                "features": [{"type": "Feature", "geometry": {"type": "Point", "coordinates": [500000, 5000000]}}]}"#,
+        );
+        assert!(report.crs.is_some());
+        assert!(report.summary().ends_with(": valid (1 features)"));
+    }
+
+    #[test]
+    fn lines_of_delimited_files_are_checked_on_their_own() {
+        let report = validate(
+            "lines.geojsonl",
+            "{\"type\": \"Point\", \"coordinates\": [1, 2]}\n\n{not json}\n",
+        );
+        assert_eq!(report.features, 1);
+        assert_eq!(
+            issue(&report, IssueKind::InvalidGeometry).location,
+            "line 3"
+        );
+        assert!(validate("broken.geojson", "{").error.is_some());
+    }
+}
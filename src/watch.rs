@@ -0,0 +1,186 @@
+// watch.rs
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    time::{Duration, Instant},
+};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::loader;
+
+// How long a file has to stay untouched before its change is reported, so a
+// file being written is read once it is complete
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+/// Files that appeared in, disappeared from or changed in a watched directory
+/// since the previous poll, by file name.
+#[derive(Debug, Default)]
+pub struct DirectoryChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+impl DirectoryChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Notices supported files being added to, removed from or rewritten in a
+/// directory through the file system notifications of the platform.
+pub struct DirectoryWatcher {
+    dir: PathBuf,
+    files: HashSet<String>,            // Supported files listed so far
+    touched: HashMap<String, Instant>, // Files with changes not reported yet, by last change
+    events: Receiver<notify::Result<notify::Event>>,
+    _watcher: RecommendedWatcher, // Stops the notifications when dropped
+}
+
+impl DirectoryWatcher {
+    /// Starts watching `dir` from its current contents.
+    pub fn new(dir: &Path) -> Result<DirectoryWatcher, String> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // Sending fails once the watcher was dropped
+            let _ = sender.send(event);
+        })
+        .map_err(|e| format!("Could not watch {}: {}", dir.display(), e))?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Could not watch {}: {}", dir.display(), e))?;
+        Ok(DirectoryWatcher {
+            dir: dir.to_path_buf(),
+            files: scan(dir).unwrap_or_default(),
+            touched: HashMap::new(),
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Returns the files whose changes have settled since the last call, or
+    /// `None` if there are none.
+    pub fn poll(&mut self) -> io::Result<Option<DirectoryChanges>> {
+        loop {
+            match self.events.try_recv() {
+                Ok(Ok(event)) => {
+                    for path in event.paths {
+                        if let Some(name) = supported_name(&path) {
+                            self.touched.insert(name, Instant::now());
+                        }
+                    }
+                }
+                Ok(Err(e)) => return Err(io::Error::other(e.to_string())),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    return Err(io::Error::other("the directory is no longer watched"));
+                }
+            }
+        }
+
+        let settled: Vec<String> = self
+            .touched
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= SETTLE_TIME)
+            .map(|(name, _)| name.clone())
+            .collect();
+        let mut changes = DirectoryChanges::default();
+        for name in settled {
+            self.touched.remove(&name);
+            let exists = self.dir.join(&name).is_file();
+            match (self.files.contains(&name), exists) {
+                (false, true) => changes.added.push(name.clone()),
+                (true, true) => changes.modified.push(name.clone()),
+                (true, false) => changes.removed.push(name.clone()),
+                (false, false) => {} // Created and deleted again, e.g. a temporary file
+            }
+            if exists {
+                self.files.insert(name);
+            } else {
+                self.files.remove(&name);
+            }
+        }
+        if changes.is_empty() {
+            return Ok(None);
+        }
+        // Notification order is arbitrary; added files are listed alphabetically
+        changes.added.sort();
+        Ok(Some(changes))
+    }
+}
+
+// File name of `path` if it is of a supported format
+fn supported_name(path: &Path) -> Option<String> {
+    if !loader::is_supported_file(path) {
+        return None;
+    }
+    Some(path.file_name()?.to_str()?.to_string())
+}
+
+// Names of the supported files in `dir`
+fn scan(dir: &Path) -> io::Result<HashSet<String>> {
+    let mut files = HashSet::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file()
+            && let Some(name) = supported_name(&path)
+        {
+            files.insert(name);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    // Polls `watcher` until the changes it reported have all settled, or a few
+    // seconds have passed
+    fn wait_for_changes(watcher: &mut DirectoryWatcher) -> DirectoryChanges {
+        // Notifications of one burst may arrive over several polls
+        let mut all = DirectoryChanges::default();
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if let Some(changes) = watcher.poll().unwrap() {
+                all.added.extend(changes.added);
+                all.removed.extend(changes.removed);
+                all.modified.extend(changes.modified);
+            }
+            if !all.is_empty() && watcher.touched.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        all
+    }
+
+    #[test]
+    fn changes_are_reported_once_settled() {
+        let dir = std::env::temp_dir().join(format!("plots_watch_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("old.geojson"), "{}").unwrap();
+        fs::write(dir.join("kept.geojson"), "{}").unwrap();
+        let mut watcher = DirectoryWatcher::new(&dir).unwrap();
+
+        fs::write(dir.join("new.geojson"), "{}").unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        fs::remove_file(dir.join("old.geojson")).unwrap();
+        fs::write(
+            dir.join("kept.geojson"),
+            "{\"type\": \"FeatureCollection\"}",
+        )
+        .unwrap();
+        let changes = wait_for_changes(&mut watcher);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(changes.added, vec!["new.geojson"]);
+        assert_eq!(changes.removed, vec!["old.geojson"]);
+        assert_eq!(changes.modified, vec!["kept.geojson"]);
+    }
+}
@@ -0,0 +1,305 @@
+// wfs.rs
+
+use std::{
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::Instant,
+};
+
+use crate::remote;
+
+const CAPABILITIES_TIMEOUT: u32 = 30; // Seconds
+
+// Parameters this client sets, replacing any the typed service URL has
+const REQUEST_PARAMETERS: [&str; 8] = [
+    "service",
+    "version",
+    "request",
+    "typename",
+    "typenames",
+    "outputformat",
+    "srsname",
+    "bbox",
+];
+
+/// A feature type offered by a WFS service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureType {
+    pub name: String,  // e.g. `topp:states`, passed to GetFeature
+    pub title: String, // Human-readable; empty if the service gives none
+}
+
+/// What a service's capabilities document says about fetching features.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    pub feature_types: Vec<FeatureType>,
+    pub output_format: String, // The GeoJSON output format the service names, or `application/json`
+}
+
+/// The GetCapabilities request URL of `service`.
+pub fn capabilities_url(service: &str) -> String {
+    request_url(
+        service,
+        &[
+            ("service", "WFS"),
+            ("version", "1.1.0"),
+            ("request", "GetCapabilities"),
+        ],
+    )
+}
+
+/// The GetFeature request URL for `type_name` as GeoJSON in lon/lat,
+/// restricted to `bbox` (min lon, min lat, max lon, max lat) if given.
+/// WFS 1.1.0 with the short `EPSG:4326` name keeps the axes in lon/lat
+/// order on GeoServer and MapServer alike.
+pub fn get_feature_url(
+    service: &str,
+    type_name: &str,
+    output_format: &str,
+    bbox: Option<[f64; 4]>,
+) -> String {
+    let bbox = bbox.map(|[min_lon, min_lat, max_lon, max_lat]| {
+        format!("{},{},{},{},EPSG:4326", min_lon, min_lat, max_lon, max_lat)
+    });
+    let mut parameters = vec![
+        ("service", "WFS"),
+        ("version", "1.1.0"),
+        ("request", "GetFeature"),
+        ("typeName", type_name),
+        ("outputFormat", output_format),
+        ("srsName", "EPSG:4326"),
+    ];
+    if let Some(bbox) = &bbox {
+        parameters.push(("bbox", bbox));
+    }
+    request_url(service, &parameters)
+}
+
+// `service` with `parameters` in its query string, keeping parameters such
+// as `map=` that some servers need
+fn request_url(service: &str, parameters: &[(&str, &str)]) -> String {
+    let service = service.trim();
+    let (base, query) = service.split_once('?').unwrap_or((service, ""));
+    let mut pairs: Vec<String> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .filter(|pair| {
+            let key = pair.split('=').next().unwrap_or_default().to_lowercase();
+            !REQUEST_PARAMETERS.contains(&key.as_str())
+        })
+        .map(str::to_string)
+        .collect();
+    pairs.extend(
+        parameters
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, encode(value))),
+    );
+    format!("{}?{}", base, pairs.join("&"))
+}
+
+// Percent-encodes a query value; `:` and `,` are left as servers expect them
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-_.~:,".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect()
+}
+
+/// Reads the feature types and the GeoJSON output format from a
+/// GetCapabilities response, or the exception the service sent instead.
+pub fn parse_capabilities(xml: &str) -> Result<Capabilities, String> {
+    if let Some(exception) = elements(xml, "ExceptionText")
+        .into_iter()
+        .chain(elements(xml, "ServiceException"))
+        .next()
+    {
+        return Err(format!(
+            "The service reported: {}",
+            unescape(exception.trim())
+        ));
+    }
+    if !xml.contains("Capabilities") {
+        return Err(String::from(
+            "The response is not a WFS capabilities document",
+        ));
+    }
+    let feature_types: Vec<FeatureType> = elements(xml, "FeatureType")
+        .into_iter()
+        .filter_map(|block| {
+            let name = unescape(elements(block, "Name").first()?.trim());
+            let title = elements(block, "Title")
+                .first()
+                .map(|title| unescape(title.trim()))
+                .unwrap_or_default();
+            Some(FeatureType { name, title })
+        })
+        .collect();
+    if feature_types.is_empty() {
+        return Err(String::from("The service offers no feature types"));
+    }
+    // Values of the outputFormat parameter of GetFeature, among others
+    let output_format = elements(xml, "Value")
+        .into_iter()
+        .map(|value| unescape(value.trim()))
+        .find(|value| value.to_lowercase().contains("json"))
+        .unwrap_or_else(|| String::from("application/json"));
+    Ok(Capabilities {
+        feature_types,
+        output_format,
+    })
+}
+
+// The contents of the elements called `local_name` in any namespace, not
+// looking inside the ones found
+fn elements<'a>(xml: &'a str, local_name: &str) -> Vec<&'a str> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let name = &rest[..name_end];
+        if name.rsplit(':').next() != Some(local_name) {
+            continue;
+        }
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        if rest[..tag_end].ends_with('/') {
+            found.push("");
+            continue;
+        }
+        let content = &rest[tag_end + 1..];
+        let Some(end) = content.find(&format!("</{}>", name)) else {
+            break;
+        };
+        found.push(&content[..end]);
+        rest = &content[end..];
+    }
+    found
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The capabilities of a service being fetched on a background thread.
+pub struct CapabilitiesRequest {
+    pub service: String,
+    started: Instant,
+    result: Receiver<Result<Capabilities, String>>,
+}
+
+impl CapabilitiesRequest {
+    pub fn start(service: &str) -> CapabilitiesRequest {
+        let service = service.trim().to_string();
+        let url = capabilities_url(&service);
+        let (sender, result) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(fetch_capabilities(&url));
+        });
+        CapabilitiesRequest {
+            service,
+            started: Instant::now(),
+            result,
+        }
+    }
+
+    /// Describes the request with a spinner.
+    pub fn status(&self) -> String {
+        format!(
+            "{} Listing the feature types of {}",
+            remote::spinner(self.started),
+            self.service
+        )
+    }
+
+    /// Returns the capabilities once they arrived.
+    pub fn poll(&self) -> Option<Result<Capabilities, String>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(String::from("Request failed"))),
+        }
+    }
+}
+
+fn fetch_capabilities(url: &str) -> Result<Capabilities, String> {
+    let body = remote::request(url, &[], CAPABILITIES_TIMEOUT)?;
+    parse_capabilities(&String::from_utf8_lossy(&body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feature_types_and_the_json_format_are_read() {
+        let xml = r#"<?xml version="1.0"?>
+<wfs:WFS_Capabilities xmlns:wfs="http://www.opengis.net/wfs" xmlns:ows="http://www.opengis.net/ows">
+  <ows:OperationsMetadata><ows:Operation name="GetFeature"><ows:Parameter name="outputFormat">
+    <ows:Value>text/xml; subtype=gml/3.1.1</ows:Value>
+    <ows:Value>application/json; subtype=geojson</ows:Value>
+  </ows:Parameter></ows:Operation></ows:OperationsMetadata>
+  <FeatureTypeList>
+    <FeatureType><Name>topp:states</Name><Title>USA &amp; territories</Title></FeatureType>
+    <FeatureType><Name>topp:roads</Name><Title/></FeatureType>
+  </FeatureTypeList>
+</wfs:WFS_Capabilities>"#;
+        let capabilities = parse_capabilities(xml).unwrap();
+        assert_eq!(
+            capabilities.feature_types,
+            [
+                FeatureType {
+                    name: String::from("topp:states"),
+                    title: String::from("USA & territories"),
+                },
+                FeatureType {
+                    name: String::from("topp:roads"),
+                    title: String::new(),
+                },
+            ]
+        );
+        assert_eq!(
+            capabilities.output_format,
+            "application/json; subtype=geojson"
+        );
+    }
+
+    #[test]
+    fn exceptions_are_reported() {
+        let xml = r#"<ows:ExceptionReport><ows:Exception><ows:ExceptionText>Unknown &quot;map&quot;</ows:ExceptionText></ows:Exception></ows:ExceptionReport>"#;
+        assert_eq!(
+            parse_capabilities(xml),
+            Err(String::from(r#"The service reported: Unknown "map""#))
+        );
+        assert!(parse_capabilities("<html>Not found</html>").is_err());
+    }
+
+    #[test]
+    fn request_urls_keep_the_service_parameters() {
+        let url = get_feature_url(
+            " https://example.org/wfs?map=/maps/a.map&SERVICE=WMS&version=2.0.0 ",
+            "ns:rivers & lakes",
+            "application/json",
+            Some([5.0, 45.5, 16.0, 56.0]),
+        );
+        assert_eq!(
+            url,
+            "https://example.org/wfs?map=/maps/a.map&service=WFS&version=1.1.0&request=GetFeature\
+             &typeName=ns:rivers%20%26%20lakes&outputFormat=application%2Fjson&srsName=EPSG:4326\
+             &bbox=5,45.5,16,56,EPSG:4326"
+        );
+    }
+}
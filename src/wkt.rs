@@ -0,0 +1,545 @@
+// wkt.rs
+
+use geojson::{Geometry, Value};
+
+/// Parses one Well-Known Text geometry, e.g. `POINT (30 10)` or
+/// `MULTIPOLYGON Z (((...)))`. An EWKT `SRID=...;` prefix is ignored, and
+/// Z and M values are dropped except for a Z kept as third coordinate.
+pub fn parse_wkt(text: &str) -> Result<Value, String> {
+    let text = text.trim();
+    // EWKT as written by PostGIS; the coordinates are taken as lon/lat anyway
+    let text = match text.split_once(';') {
+        Some((srid, rest)) if srid.trim().to_uppercase().starts_with("SRID=") => rest,
+        _ => text,
+    };
+    let mut parser = WktParser {
+        chars: text.chars().collect(),
+        at: 0,
+    };
+    let value = parser.geometry()?;
+    parser.skip_spaces();
+    if parser.at < parser.chars.len() {
+        return Err(format!(
+            "unexpected text after the geometry at {}",
+            parser.at + 1
+        ));
+    }
+    Ok(value)
+}
+
+// Geometry types a WKT text can start with
+const GEOMETRY_TYPES: [&str; 7] = [
+    "GEOMETRYCOLLECTION",
+    "MULTILINESTRING",
+    "MULTIPOLYGON",
+    "MULTIPOINT",
+    "LINESTRING",
+    "POLYGON",
+    "POINT",
+];
+
+struct WktParser {
+    chars: Vec<char>,
+    at: usize,
+}
+
+impl WktParser {
+    fn skip_spaces(&mut self) {
+        while self.chars.get(self.at).is_some_and(|c| c.is_whitespace()) {
+            self.at += 1;
+        }
+    }
+
+    // The next word, uppercased, without consuming it
+    fn peek_word(&mut self) -> String {
+        self.skip_spaces();
+        self.chars[self.at..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .collect::<String>()
+            .to_uppercase()
+    }
+
+    fn word(&mut self) -> String {
+        let word = self.peek_word();
+        self.at += word.len();
+        word
+    }
+
+    // Consumes `c` if it comes next
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_spaces();
+        if self.chars.get(self.at) == Some(&c) {
+            self.at += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at {}", c, self.at + 1))
+        }
+    }
+
+    fn geometry(&mut self) -> Result<Value, String> {
+        let kind = self.word();
+        if kind.is_empty() {
+            return Err(format!("expected a geometry type at {}", self.at + 1));
+        }
+        // `POINT Z (...)`, `POINT M (...)`, `POINT ZM (...)`; also `POINTZ`
+        let (kind, mut dims) = GEOMETRY_TYPES
+            .iter()
+            .find_map(|name| {
+                let rest = kind.strip_prefix(name)?;
+                matches!(rest, "" | "Z" | "M" | "ZM").then(|| (name.to_string(), rest.to_string()))
+            })
+            .ok_or_else(|| format!("unknown geometry type '{}'", kind))?;
+        if dims.is_empty() && matches!(self.peek_word().as_str(), "Z" | "M" | "ZM") {
+            dims = self.word();
+        }
+        let has_m = dims.contains('M');
+        if self.peek_word() == "EMPTY" {
+            self.word();
+            return Ok(empty(&kind));
+        }
+        match kind.as_str() {
+            "POINT" => {
+                self.expect('(')?;
+                let position = self.position(has_m)?;
+                self.expect(')')?;
+                Ok(Value::Point(position))
+            }
+            "LINESTRING" => Ok(Value::LineString(self.positions(has_m)?)),
+            "POLYGON" => Ok(Value::Polygon(self.rings(has_m)?)),
+            "MULTIPOINT" => {
+                self.expect('(')?;
+                let mut points = Vec::new();
+                loop {
+                    // Both `MULTIPOINT (1 2, 3 4)` and `MULTIPOINT ((1 2), (3 4))`
+                    if self.eat('(') {
+                        points.push(self.position(has_m)?);
+                        self.expect(')')?;
+                    } else {
+                        points.push(self.position(has_m)?);
+                    }
+                    if !self.eat(',') {
+                        break;
+                    }
+                }
+                self.expect(')')?;
+                Ok(Value::MultiPoint(points))
+            }
+            "MULTILINESTRING" => self
+                .list(|parser| parser.positions(has_m))
+                .map(Value::MultiLineString),
+            "MULTIPOLYGON" => self
+                .list(|parser| parser.rings(has_m))
+                .map(Value::MultiPolygon),
+            _ => self
+                .list(|parser| parser.geometry().map(Geometry::new))
+                .map(Value::GeometryCollection),
+        }
+    }
+
+    // `(item, item, ...)`
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut WktParser) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        self.expect('(')?;
+        let mut items = vec![item(self)?];
+        while self.eat(',') {
+            items.push(item(self)?);
+        }
+        self.expect(')')?;
+        Ok(items)
+    }
+
+    fn rings(&mut self, has_m: bool) -> Result<Vec<Vec<Vec<f64>>>, String> {
+        self.list(|parser| parser.positions(has_m))
+    }
+
+    fn positions(&mut self, has_m: bool) -> Result<Vec<Vec<f64>>, String> {
+        self.list(|parser| parser.position(has_m))
+    }
+
+    // `x y`, `x y z`, `x y m` or `x y z m`; M values are dropped
+    fn position(&mut self, has_m: bool) -> Result<Vec<f64>, String> {
+        let mut numbers = Vec::new();
+        loop {
+            self.skip_spaces();
+            let start = self.at;
+            while self
+                .chars
+                .get(self.at)
+                .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+            {
+                self.at += 1;
+            }
+            if start == self.at {
+                break;
+            }
+            let number: String = self.chars[start..self.at].iter().collect();
+            numbers.push(
+                number
+                    .parse::<f64>()
+                    .map_err(|_| format!("'{}' is not a number", number))?,
+            );
+        }
+        if has_m || numbers.len() > 3 {
+            numbers.truncate(numbers.len().saturating_sub(1).max(2));
+        }
+        if numbers.len() < 2 {
+            return Err(format!("expected a position at {}", self.at + 1));
+        }
+        Ok(numbers)
+    }
+}
+
+// The empty geometry of a type, which has no coordinates to draw
+fn empty(kind: &str) -> Value {
+    match kind {
+        "POINT" | "MULTIPOINT" => Value::MultiPoint(Vec::new()),
+        "LINESTRING" => Value::LineString(Vec::new()),
+        "MULTILINESTRING" => Value::MultiLineString(Vec::new()),
+        "POLYGON" => Value::Polygon(Vec::new()),
+        "MULTIPOLYGON" => Value::MultiPolygon(Vec::new()),
+        _ => Value::GeometryCollection(Vec::new()),
+    }
+}
+
+/// Parses one Well-Known Binary geometry from the start of `bytes`, as ISO
+/// WKB or PostGIS EWKB in either byte order. Returns it with the number of
+/// bytes it took, so a dump of several geometries can be read in turn.
+pub fn parse_wkb(bytes: &[u8]) -> Result<(Value, usize), String> {
+    let mut reader = WkbReader {
+        bytes,
+        at: 0,
+        little_endian: true,
+    };
+    let value = reader.geometry()?;
+    Ok((value, reader.at))
+}
+
+/// Decodes a line of hexadecimal WKB, as printed by `ST_AsBinary` or found
+/// in CSV exports.
+pub fn decode_hex(line: &str) -> Result<Vec<u8>, String> {
+    let line = line.trim();
+    let line = line.strip_prefix("\\x").unwrap_or(line);
+    if !line.len().is_multiple_of(2) {
+        return Err(String::from("odd number of hex digits"));
+    }
+    (0..line.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&line[i..i + 2], 16)
+                .map_err(|_| format!("'{}' is not hexadecimal", &line[i..i + 2]))
+        })
+        .collect()
+}
+
+struct WkbReader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    little_endian: bool,
+}
+
+impl WkbReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let bytes = self
+            .bytes
+            .get(self.at..self.at + N)
+            .ok_or_else(|| format!("the data ends at byte {}", self.bytes.len()))?;
+        self.at += N;
+        Ok(bytes.try_into().expect("slice of N bytes"))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take::<4>()?;
+        Ok(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        let bytes = self.take::<8>()?;
+        Ok(if self.little_endian {
+            f64::from_le_bytes(bytes)
+        } else {
+            f64::from_be_bytes(bytes)
+        })
+    }
+
+    fn geometry(&mut self) -> Result<Value, String> {
+        self.little_endian = match self.take::<1>()?[0] {
+            0 => false,
+            1 => true,
+            other => return Err(format!("invalid byte order {} at byte {}", other, self.at)),
+        };
+        let code = self.u32()?;
+        // EWKB flags in the high bits, ISO dimensions as thousands
+        let mut has_z = code & 0x8000_0000 != 0;
+        let mut has_m = code & 0x4000_0000 != 0;
+        if code & 0x2000_0000 != 0 {
+            self.u32()?; // SRID
+        }
+        let iso = code & 0x0fff_ffff;
+        match iso / 1000 {
+            1 => has_z = true,
+            2 => has_m = true,
+            3 => (has_z, has_m) = (true, true),
+            _ => {}
+        }
+        let dims = (has_z, has_m);
+        match iso % 1000 {
+            1 => Ok(Value::Point(self.position(dims)?)),
+            2 => Ok(Value::LineString(self.positions(dims)?)),
+            3 => Ok(Value::Polygon(self.rings(dims)?)),
+            4 => Ok(Value::MultiPoint(self.members(|value| match value {
+                Value::Point(position) => Ok(position),
+                _ => Err(String::from("a MultiPoint holds a non-point")),
+            })?)),
+            5 => Ok(Value::MultiLineString(self.members(
+                |value| match value {
+                    Value::LineString(line) => Ok(line),
+                    _ => Err(String::from("a MultiLineString holds a non-line")),
+                },
+            )?)),
+            6 => Ok(Value::MultiPolygon(self.members(|value| match value {
+                Value::Polygon(rings) => Ok(rings),
+                _ => Err(String::from("a MultiPolygon holds a non-polygon")),
+            })?)),
+            7 => Ok(Value::GeometryCollection(
+                self.members(|value| Ok(Geometry::new(value)))?,
+            )),
+            other => Err(format!("unknown geometry type {}", other)),
+        }
+    }
+
+    // The geometries of a multi-geometry or collection, each with its own header
+    fn members<T>(
+        &mut self,
+        convert: impl Fn(Value) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        let count = self.u32()?;
+        let mut members = Vec::new();
+        for _ in 0..count {
+            members.push(convert(self.geometry()?)?);
+        }
+        Ok(members)
+    }
+
+    fn rings(&mut self, dims: (bool, bool)) -> Result<Vec<Vec<Vec<f64>>>, String> {
+        let count = self.u32()?;
+        (0..count).map(|_| self.positions(dims)).collect()
+    }
+
+    fn positions(&mut self, dims: (bool, bool)) -> Result<Vec<Vec<f64>>, String> {
+        let count = self.u32()?;
+        if count as usize > self.bytes.len() / 16 {
+            return Err(format!("{} positions cannot fit in the data", count));
+        }
+        (0..count).map(|_| self.position(dims)).collect()
+    }
+
+    // Z is kept as third coordinate, M is dropped
+    fn position(&mut self, (has_z, has_m): (bool, bool)) -> Result<Vec<f64>, String> {
+        let mut position = vec![self.f64()?, self.f64()?];
+        if has_z {
+            position.push(self.f64()?);
+        }
+        if has_m {
+            self.f64()?;
+        }
+        Ok(position)
+    }
+}
+
+/// Encodes a geometry as little-endian ISO WKB in two dimensions; Z values
+/// are dropped.
+pub fn to_wkb(value: &Value) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    write_wkb(&mut bytes, value);
+    bytes
+}
+
+fn write_wkb(bytes: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Point(position) => {
+            write_header(bytes, 1);
+            write_position(bytes, position);
+        }
+        Value::LineString(line) => {
+            write_header(bytes, 2);
+            write_positions(bytes, line);
+        }
+        Value::Polygon(rings) => write_polygon(bytes, rings),
+        Value::MultiPoint(points) => {
+            write_header(bytes, 4);
+            bytes.extend((points.len() as u32).to_le_bytes());
+            for position in points {
+                write_header(bytes, 1);
+                write_position(bytes, position);
+            }
+        }
+        Value::MultiLineString(lines) => {
+            write_header(bytes, 5);
+            bytes.extend((lines.len() as u32).to_le_bytes());
+            for line in lines {
+                write_header(bytes, 2);
+                write_positions(bytes, line);
+            }
+        }
+        Value::MultiPolygon(polygons) => {
+            write_header(bytes, 6);
+            bytes.extend((polygons.len() as u32).to_le_bytes());
+            for rings in polygons {
+                write_polygon(bytes, rings);
+            }
+        }
+        Value::GeometryCollection(geometries) => {
+            write_header(bytes, 7);
+            bytes.extend((geometries.len() as u32).to_le_bytes());
+            for geometry in geometries {
+                write_wkb(bytes, &geometry.value);
+            }
+        }
+    }
+}
+
+// Byte order (little endian) and geometry type
+fn write_header(bytes: &mut Vec<u8>, code: u32) {
+    bytes.push(1);
+    bytes.extend(code.to_le_bytes());
+}
+
+fn write_polygon(bytes: &mut Vec<u8>, rings: &[Vec<Vec<f64>>]) {
+    write_header(bytes, 3);
+    bytes.extend((rings.len() as u32).to_le_bytes());
+    for ring in rings {
+        write_positions(bytes, ring);
+    }
+}
+
+fn write_positions(bytes: &mut Vec<u8>, positions: &[Vec<f64>]) {
+    bytes.extend((positions.len() as u32).to_le_bytes());
+    for position in positions {
+        write_position(bytes, position);
+    }
+}
+
+fn write_position(bytes: &mut Vec<u8>, position: &[f64]) {
+    for i in 0..2 {
+        bytes.extend(position.get(i).copied().unwrap_or(f64::NAN).to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(text: &str) -> Value {
+        let value = parse_wkt(text).unwrap();
+        let bytes = to_wkb(&value);
+        let (decoded, length) = parse_wkb(&bytes).unwrap();
+        assert_eq!(length, bytes.len(), "{}", text);
+        decoded
+    }
+
+    #[test]
+    fn wkt_survives_a_wkb_round_trip() {
+        for text in [
+            "POINT (30 10)",
+            "LINESTRING (30 10, 10 30, 40 40)",
+            "POLYGON ((35 10, 45 45, 15 40, 10 20, 35 10), (20 30, 35 35, 30 20, 20 30))",
+            "MULTIPOINT ((10 40), (40 30))",
+            "MULTILINESTRING ((10 10, 20 20), (40 40, 30 30))",
+            "MULTIPOLYGON (((30 20, 45 40, 10 40, 30 20)), ((15 5, 40 10, 10 20, 15 5)))",
+            "GEOMETRYCOLLECTION (POINT (4 6), LINESTRING (4 6, 7 10))",
+        ] {
+            assert_eq!(round_trip(text), parse_wkt(text).unwrap(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn z_is_kept_and_m_dropped() {
+        assert_eq!(
+            parse_wkt("POINT Z (1 2 3)").unwrap(),
+            Value::Point(vec![1.0, 2.0, 3.0])
+        );
+        assert_eq!(
+            parse_wkt("POINTM (1 2 9)").unwrap(),
+            Value::Point(vec![1.0, 2.0])
+        );
+        assert_eq!(
+            parse_wkt("LINESTRING ZM (1 2 3 9, 4 5 6 9)").unwrap(),
+            Value::LineString(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]])
+        );
+        // WKB written for export is two-dimensional
+        assert_eq!(round_trip("POINT Z (1 2 3)"), Value::Point(vec![1.0, 2.0]));
+    }
+
+    #[test]
+    fn multipoints_with_or_without_parentheses() {
+        assert_eq!(
+            parse_wkt("MULTIPOINT (10 40, 40 30)").unwrap(),
+            parse_wkt("MULTIPOINT ((10 40), (40 30))").unwrap()
+        );
+    }
+
+    #[test]
+    fn ewkt_and_empty_geometries() {
+        assert_eq!(
+            parse_wkt("SRID=4326;POINT(1 2)").unwrap(),
+            Value::Point(vec![1.0, 2.0])
+        );
+        assert_eq!(
+            parse_wkt("polygon empty").unwrap(),
+            Value::Polygon(Vec::new())
+        );
+    }
+
+    #[test]
+    fn malformed_wkt_is_rejected() {
+        for text in [
+            "",
+            "CIRCLE (1 2)",
+            "POINT (1)",
+            "POINT (1 2",
+            "POINT (1 2) x",
+            "LINESTRING (1 2, a b)",
+        ] {
+            assert!(parse_wkt(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn big_endian_and_ewkb() {
+        let big_endian = decode_hex("00000000013FF00000000000004000000000000000").unwrap();
+        assert_eq!(
+            parse_wkb(&big_endian).unwrap().0,
+            Value::Point(vec![1.0, 2.0])
+        );
+        // PostGIS EWKB with an SRID and a Z flag
+        let ewkb =
+            decode_hex("\\x01010000A0E6100000000000000000F03F00000000000000400000000000000840")
+                .unwrap();
+        assert_eq!(
+            parse_wkb(&ewkb).unwrap().0,
+            Value::Point(vec![1.0, 2.0, 3.0])
+        );
+    }
+
+    #[test]
+    fn truncated_wkb_is_rejected() {
+        let bytes = to_wkb(&parse_wkt("LINESTRING (1 2, 3 4)").unwrap());
+        assert!(parse_wkb(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode_hex("0101x").is_err());
+    }
+}